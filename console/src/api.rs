@@ -1,4 +1,4 @@
-use crate::types::{Bucket, ObjectInfo, CreateBucketRequest, SystemStats};
+use crate::types::{Bucket, ObjectInfo, ObjectPage, CreateBucketRequest, SystemStats};
 use gloo_net::http::Request;
 use serde::Deserialize;
 use chrono::{DateTime, Utc};
@@ -16,6 +16,10 @@ pub struct ListBucketsResponse {
 pub struct BucketInfo {
     pub name: String,
     pub creation_date: String,
+    #[serde(default)]
+    pub objects_count: u64,
+    #[serde(default)]
+    pub size_bytes: u64,
 }
 
 #[derive(Debug, Deserialize)]
@@ -24,18 +28,43 @@ pub struct OwnerInfo {
     pub display_name: String,
 }
 
+/// Admin listing of every bucket, with its owner
+#[derive(Debug, Deserialize)]
+pub struct ListAllBucketsResponse {
+    pub buckets: Vec<AdminBucketInfo>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AdminBucketInfo {
+    pub name: String,
+    pub owner: String,
+    pub creation_date: String,
+    pub objects_count: u64,
+    pub size_bytes: u64,
+}
+
 pub async fn get_system_stats() -> Result<SystemStats, String> {
-    // For now, return mock stats since we don't have a stats endpoint yet
-    Ok(SystemStats {
-        total_buckets: 0,
-        total_objects: 0,
-        total_size_bytes: 0,
-        storage_usage_percent: 0.0,
-    })
+    let response = Request::get(&format!("{}/_admin/stats", API_BASE))
+        .send()
+        .await
+        .map_err(|e| format!("Network error: {}", e))?;
+
+    if response.ok() {
+        response
+            .json()
+            .await
+            .map_err(|e| format!("JSON parse error: {}", e))
+    } else if response.status() == 403 {
+        Err("Stats are only available to administrators".to_string())
+    } else {
+        Err(format!("HTTP error: {}", response.status()))
+    }
 }
 
 pub async fn list_buckets() -> Result<Vec<Bucket>, String> {
+    // JSON rather than S3's XML, for the per-bucket usage figures
     let response = Request::get(API_BASE)
+        .header("Accept", "application/json")
         .send()
         .await
         .map_err(|e| format!("Network error: {}", e))?;
@@ -48,9 +77,11 @@ pub async fn list_buckets() -> Result<Vec<Bucket>, String> {
             
         let buckets = s3_response.buckets.into_iter().map(|bucket_info| Bucket {
             name: bucket_info.name,
-            created_at: chrono::Utc::now(), // Use current time for now
-            objects_count: 0,
-            size_bytes: 0,
+            created_at: DateTime::parse_from_rfc3339(&bucket_info.creation_date)
+                .map(|t| t.with_timezone(&Utc))
+                .unwrap_or_else(|_| Utc::now()),
+            objects_count: bucket_info.objects_count,
+            size_bytes: bucket_info.size_bytes,
             region: "us-east-1".to_string(),
             versioning_enabled: false,
         }).collect();
@@ -61,6 +92,40 @@ pub async fn list_buckets() -> Result<Vec<Bucket>, String> {
     }
 }
 
+/// Every bucket on the server with its owner's ID (admins, or users granted
+/// `list_all_buckets`, only)
+pub async fn list_all_buckets() -> Result<Vec<(String, Bucket)>, String> {
+    let response = Request::get(&format!("{}/_admin/buckets", API_BASE))
+        .send()
+        .await
+        .map_err(|e| format!("Network error: {}", e))?;
+
+    if response.ok() {
+        let listing: ListAllBucketsResponse = response
+            .json()
+            .await
+            .map_err(|e| format!("JSON parse error: {}", e))?;
+
+        Ok(listing.buckets.into_iter().map(|info| {
+            let bucket = Bucket {
+                name: info.name,
+                created_at: DateTime::parse_from_rfc3339(&info.creation_date)
+                    .map(|t| t.with_timezone(&Utc))
+                    .unwrap_or_else(|_| Utc::now()),
+                objects_count: info.objects_count,
+                size_bytes: info.size_bytes,
+                region: "us-east-1".to_string(),
+                versioning_enabled: false,
+            };
+            (info.owner, bucket)
+        }).collect())
+    } else if response.status() == 403 {
+        Err("Listing every bucket is only available to administrators".to_string())
+    } else {
+        Err(format!("HTTP error: {}", response.status()))
+    }
+}
+
 pub async fn create_bucket(request: CreateBucketRequest) -> Result<Bucket, String> {
     let response = Request::put(&format!("{}/{}", API_BASE, request.name))
         .header("Content-Type", "application/json")
@@ -99,14 +164,103 @@ pub async fn delete_bucket(bucket_name: &str) -> Result<(), String> {
     }
 }
 
-pub async fn list_objects(_bucket_name: &str) -> Result<Vec<ObjectInfo>, String> {
-    // For now, return empty list since we need to implement S3 list objects endpoint
-    // TODO: Implement GET /{bucket}?list-type=2 endpoint
-    Ok(vec![])
+/// Objects returned per page of a bucket listing
+const OBJECTS_PAGE_SIZE: u32 = 100;
+
+/// List one page of the objects under `prefix`, continuing from
+/// `continuation_token` when given (GET /{bucket}?list-type=2)
+pub async fn list_objects(
+    bucket_name: &str,
+    prefix: &str,
+    continuation_token: Option<&str>,
+) -> Result<ObjectPage, String> {
+    let mut url = format!(
+        "{}/{}?list-type=2&max-keys={}&prefix={}",
+        API_BASE,
+        bucket_name,
+        OBJECTS_PAGE_SIZE,
+        js_sys::encode_uri_component(prefix)
+    );
+    if let Some(token) = continuation_token {
+        url.push_str(&format!("&continuation-token={}", js_sys::encode_uri_component(token)));
+    }
+
+    let response = Request::get(&url)
+        .send()
+        .await
+        .map_err(|e| format!("Network error: {}", e))?;
+
+    if response.ok() {
+        let xml = response
+            .text()
+            .await
+            .map_err(|e| format!("Response read error: {}", e))?;
+        parse_object_page(&xml)
+    } else {
+        Err(format!("HTTP error: {}", response.status()))
+    }
+}
+
+/// Parse a `ListBucketResult` (V2) document
+fn parse_object_page(xml: &str) -> Result<ObjectPage, String> {
+    let document = web_sys::DomParser::new()
+        .and_then(|parser| parser.parse_from_string(xml, web_sys::SupportedType::TextXml))
+        .map_err(|_| "XML parse error".to_string())?;
+
+    let child_text = |element: &web_sys::Element, tag: &str| {
+        element
+            .get_elements_by_tag_name(tag)
+            .item(0)
+            .and_then(|child| child.text_content())
+            .unwrap_or_default()
+    };
+
+    let contents = document.get_elements_by_tag_name("Contents");
+    let objects = (0..contents.length())
+        .filter_map(|i| contents.item(i))
+        .map(|entry| ObjectInfo {
+            key: child_text(&entry, "Key"),
+            size: child_text(&entry, "Size").parse().unwrap_or(0),
+            last_modified: DateTime::parse_from_rfc3339(&child_text(&entry, "LastModified"))
+                .map(|t| t.with_timezone(&Utc))
+                .unwrap_or_else(|_| Utc::now()),
+            etag: child_text(&entry, "ETag"),
+            // Listings do not carry content types
+            content_type: String::new(),
+            storage_class: child_text(&entry, "StorageClass"),
+        })
+        .collect();
+
+    let next_token = document
+        .get_elements_by_tag_name("NextContinuationToken")
+        .item(0)
+        .and_then(|token| token.text_content())
+        .filter(|token| !token.is_empty());
+
+    Ok(ObjectPage { objects, next_token })
+}
+
+/// URL of an object, with each key segment percent-encoded
+pub fn object_url(bucket_name: &str, key: &str) -> String {
+    let key = key
+        .split('/')
+        .map(|segment| String::from(js_sys::encode_uri_component(segment)))
+        .collect::<Vec<_>>()
+        .join("/");
+    format!("{}/{}/{}", API_BASE, bucket_name, key)
+}
+
+/// User-facing message for a failed object request
+fn object_error(status: u16, key: &str) -> String {
+    match status {
+        404 => format!("Object '{}' no longer exists", key),
+        403 => format!("Permission denied for object '{}'", key),
+        _ => format!("HTTP error: {}", status),
+    }
 }
 
 pub async fn upload_object(bucket_name: &str, key: &str, data: Vec<u8>) -> Result<ObjectInfo, String> {
-    let response = Request::put(&format!("{}/{}/{}", API_BASE, bucket_name, key))
+    let response = Request::put(&object_url(bucket_name, key))
         .header("Content-Type", "application/octet-stream")
         .body(data)
         .map_err(|e| format!("Request error: {}", e))?
@@ -128,8 +282,50 @@ pub async fn upload_object(bucket_name: &str, key: &str, data: Vec<u8>) -> Resul
     }
 }
 
+/// Fetch an object's contents
+pub async fn download_object(bucket_name: &str, key: &str) -> Result<Vec<u8>, String> {
+    let response = Request::get(&object_url(bucket_name, key))
+        .send()
+        .await
+        .map_err(|e| format!("Network error: {}", e))?;
+
+    if response.ok() {
+        response
+            .binary()
+            .await
+            .map_err(|e| format!("Response read error: {}", e))
+    } else {
+        Err(object_error(response.status(), key))
+    }
+}
+
+/// Presigned URL response
+#[derive(Debug, Deserialize)]
+struct PresignResponse {
+    url: String,
+}
+
+/// Get a time-limited URL that downloads an object without credentials
+pub async fn presign_object(bucket_name: &str, key: &str, expires_secs: u64) -> Result<String, String> {
+    let url = format!("{}?presign&expires={}", object_url(bucket_name, key), expires_secs);
+    let response = Request::post(&url)
+        .send()
+        .await
+        .map_err(|e| format!("Network error: {}", e))?;
+
+    if response.ok() {
+        response
+            .json::<PresignResponse>()
+            .await
+            .map(|presigned| presigned.url)
+            .map_err(|e| format!("JSON parse error: {}", e))
+    } else {
+        Err(object_error(response.status(), key))
+    }
+}
+
 pub async fn delete_object(bucket_name: &str, key: &str) -> Result<(), String> {
-    let response = Request::delete(&format!("{}/{}/{}", API_BASE, bucket_name, key))
+    let response = Request::delete(&object_url(bucket_name, key))
         .send()
         .await
         .map_err(|e| format!("Network error: {}", e))?;
@@ -137,6 +333,6 @@ pub async fn delete_object(bucket_name: &str, key: &str) -> Result<(), String> {
     if response.ok() {
         Ok(())
     } else {
-        Err(format!("HTTP error: {}", response.status()))
+        Err(object_error(response.status(), key))
     }
 }
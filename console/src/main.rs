@@ -1,5 +1,9 @@
 use leptos::prelude::*;
 use leptos_meta::*;
+use leptos_router::{
+    components::{Route, Router, Routes},
+    path,
+};
 
 mod components;
 mod pages;
@@ -18,16 +22,19 @@ pub fn App() -> impl IntoView {
         <Meta name="viewport" content="width=device-width, initial-scale=1"/>
         <Meta name="description" content="ObjectIO - S3-compatible storage management console"/>
         
-        <div class="app">
-            <Header/>
-            <main class="main-content">
-                <div style="padding: 20px; background: white; margin: 20px; border-radius: 8px;">
-                    <h1>"ObjectIO Console"</h1>
-                    <p>"S3-Compatible Storage Management"</p>
-                    <BucketsPage/>
-                </div>
-            </main>
-        </div>
+        <Router>
+            <div class="app">
+                <Header/>
+                <main class="main-content">
+                    <Routes fallback=|| view! { <NotFoundPage/> }>
+                        <Route path=path!("/") view=HomePage/>
+                        <Route path=path!("/buckets") view=BucketsPage/>
+                        <Route path=path!("/buckets/:name") view=BucketDetailPage/>
+                        <Route path=path!("/settings") view=SettingsPage/>
+                    </Routes>
+                </main>
+            </div>
+        </Router>
     }
 }
 
@@ -40,9 +47,9 @@ fn Header() -> impl IntoView {
                     "ObjectIO"
                 </div>
                 <nav class="nav">
-                    <span class="nav-link">"Dashboard"</span>
-                    <span class="nav-link">"Buckets"</span>
-                    <span class="nav-link">"Settings"</span>
+                    <a href="/" class="nav-link">"Dashboard"</a>
+                    <a href="/buckets" class="nav-link">"Buckets"</a>
+                    <a href="/settings" class="nav-link">"Settings"</a>
                 </nav>
             </div>
         </header>
@@ -1,24 +1,60 @@
 use leptos::prelude::*;
+use leptos::Params;
+use leptos_router::{hooks::use_params, params::Params};
+use wasm_bindgen::JsCast;
 use wasm_bindgen_futures::spawn_local;
 use crate::api;
 use crate::types::ObjectInfo;
 
+/// Route parameters of `/buckets/:name`
+#[derive(Params, PartialEq, Clone)]
+struct BucketParams {
+    name: Option<String>,
+}
+
 #[component]
 pub fn BucketDetailPage() -> impl IntoView {
-    let bucket_name = "example-bucket".to_string(); // Temporary fixed value
-    
+    let params = use_params::<BucketParams>();
+    let bucket_name = Memo::new(move |_| {
+        params
+            .read()
+            .as_ref()
+            .ok()
+            .and_then(|params| params.name.clone())
+            .unwrap_or_default()
+    });
+
     let (objects, set_objects) = signal(Vec::<ObjectInfo>::new());
+    let (next_token, set_next_token) = signal(None::<String>);
+    let (prefix, set_prefix) = signal(String::new());
+    // Prefix the current listing was loaded with
+    let (listed_prefix, set_listed_prefix) = signal(String::new());
     let (loading, set_loading) = signal(true);
+    let (loading_more, set_loading_more) = signal(false);
     let (error, set_error) = signal(None::<String>);
+    // Outcome of the last download or delete: a confirmation or an error
+    let (notice, set_notice) = signal(None::<Result<String, String>>);
 
-    // Load objects on mount
-    {
-        let bucket_name = bucket_name.clone();
-        spawn_local(async move {
+    // Load the first page when `token` is None, otherwise append the next one
+    let load_page = move |token: Option<String>| {
+        let bucket_name = bucket_name.get_untracked();
+        let prefix = prefix.get_untracked();
+        let append = token.is_some();
+        if append {
+            set_loading_more.set(true);
+        } else {
             set_loading.set(true);
-            match api::list_objects(&bucket_name).await {
-                Ok(object_list) => {
-                    set_objects.set(object_list);
+        }
+        spawn_local(async move {
+            match api::list_objects(&bucket_name, &prefix, token.as_deref()).await {
+                Ok(page) => {
+                    if append {
+                        set_objects.update(|objects| objects.extend(page.objects));
+                    } else {
+                        set_objects.set(page.objects);
+                        set_listed_prefix.set(prefix);
+                    }
+                    set_next_token.set(page.next_token);
                     set_error.set(None);
                 }
                 Err(err) => {
@@ -26,14 +62,30 @@ pub fn BucketDetailPage() -> impl IntoView {
                 }
             }
             set_loading.set(false);
+            set_loading_more.set(false);
         });
-    }
+    };
+
+    let on_deleted = Callback::new(move |key: String| {
+        set_notice.set(Some(Ok(format!("Deleted '{}'", key))));
+        load_page(None);
+    });
+    let on_error = Callback::new(move |err: String| set_notice.set(Some(Err(err))));
+    let on_shared = Callback::new(move |url: String| {
+        set_notice.set(Some(Ok(format!("Share link (valid for 1 hour): {}", url))))
+    });
+
+    // Load objects on mount and whenever the bucket changes
+    Effect::new(move |_| {
+        bucket_name.track();
+        load_page(None);
+    });
 
     view! {
         <div class="buckets-container">
             <div class="buckets-header">
                 <h2 class="section-title">
-                    "Bucket: " {bucket_name.clone()}
+                    "Bucket: " {move || bucket_name.get()}
                 </h2>
                 <div style="display: flex; gap: 1rem;">
                     <button class="btn">
@@ -44,6 +96,41 @@ pub fn BucketDetailPage() -> impl IntoView {
                     </a>
                 </div>
             </div>
+
+            <form
+                style="display: flex; gap: 0.5rem; margin-bottom: 1rem;"
+                on:submit=move |e| {
+                    e.prevent_default();
+                    load_page(None);
+                }
+            >
+                <input
+                    type="text"
+                    placeholder="Filter by prefix, e.g. photos/2024/"
+                    style="flex: 1; padding: 0.5rem; border: 1px solid #d1d5db; border-radius: 0.375rem;"
+                    prop:value=prefix
+                    on:input=move |e| set_prefix.set(event_target_value(&e))
+                />
+                <button type="submit" class="btn btn-secondary">
+                    "Filter"
+                </button>
+            </form>
+
+            {move || notice.get().map(|notice| {
+                let (class, message) = match notice {
+                    Ok(message) => ("success", message),
+                    Err(message) => ("error", message),
+                };
+                view! {
+                    <div class=class>
+                        {message}
+                        " "
+                        <button class="btn btn-small btn-secondary" on:click=move |_| set_notice.set(None)>
+                            "Dismiss"
+                        </button>
+                    </div>
+                }
+            })}
             
             {move || {
                 if loading.get() {
@@ -61,7 +148,15 @@ pub fn BucketDetailPage() -> impl IntoView {
                     }.into_any()
                 } else {
                     let object_list = objects.get();
-                    if object_list.is_empty() {
+                    if object_list.is_empty() && !listed_prefix.get().is_empty() {
+                        view! {
+                            <div style="text-align: center; padding: 2rem;">
+                                <p style="color: #6b7280;">
+                                    "No objects match this prefix."
+                                </p>
+                            </div>
+                        }.into_any()
+                    } else if object_list.is_empty() {
                         view! {
                             <div style="text-align: center; padding: 2rem;">
                                 <p style="color: #6b7280; margin-bottom: 1rem;">
@@ -74,17 +169,65 @@ pub fn BucketDetailPage() -> impl IntoView {
                         }.into_any()
                     } else {
                         view! {
-                            <ObjectList objects=object_list/>
+                            <ObjectList
+                                bucket=bucket_name.get_untracked()
+                                objects=object_list
+                                on_deleted=on_deleted
+                                on_shared=on_shared
+                                on_error=on_error
+                            />
                         }.into_any()
                     }
                 }
             }}
+
+            {move || next_token.get().map(|token| view! {
+                <div style="text-align: center; margin-top: 1rem;">
+                    <button
+                        class="btn btn-secondary"
+                        disabled=move || loading_more.get()
+                        on:click=move |_| load_page(Some(token.clone()))
+                    >
+                        {move || if loading_more.get() { "Loading..." } else { "Load more" }}
+                    </button>
+                </div>
+            })}
         </div>
     }
 }
 
+/// Validity of share links created from the object list
+const SHARE_EXPIRES_SECS: u64 = 60 * 60;
+
+/// Offer `data` to the user as a file download named `file_name`
+fn save_file(file_name: &str, data: &[u8]) -> Result<(), String> {
+    let parts = js_sys::Array::of1(&js_sys::Uint8Array::from(data));
+    let blob = web_sys::Blob::new_with_u8_array_sequence(&parts)
+        .map_err(|_| "Failed to prepare download".to_string())?;
+    let url = web_sys::Url::create_object_url_with_blob(&blob)
+        .map_err(|_| "Failed to prepare download".to_string())?;
+
+    let anchor = web_sys::window()
+        .and_then(|window| window.document())
+        .and_then(|document| document.create_element("a").ok())
+        .and_then(|element| element.dyn_into::<web_sys::HtmlAnchorElement>().ok())
+        .ok_or_else(|| "Failed to start download".to_string())?;
+    anchor.set_href(&url);
+    anchor.set_download(file_name);
+    anchor.click();
+
+    let _ = web_sys::Url::revoke_object_url(&url);
+    Ok(())
+}
+
 #[component]
-fn ObjectList(objects: Vec<ObjectInfo>) -> impl IntoView {
+fn ObjectList(
+    bucket: String,
+    objects: Vec<ObjectInfo>,
+    on_deleted: Callback<String>,
+    on_shared: Callback<String>,
+    on_error: Callback<String>,
+) -> impl IntoView {
     let format_size = |bytes: u64| {
         if bytes < 1024 {
             format!("{} B", bytes)
@@ -100,6 +243,58 @@ fn ObjectList(objects: Vec<ObjectInfo>) -> impl IntoView {
     view! {
         <div class="bucket-list">
             {objects.into_iter().map(|object| {
+                let download = {
+                    let bucket = bucket.clone();
+                    let key = object.key.clone();
+                    move |_| {
+                        let bucket = bucket.clone();
+                        let key = key.clone();
+                        spawn_local(async move {
+                            let file_name = key.rsplit('/').next().unwrap_or(&key).to_string();
+                            let result = api::download_object(&bucket, &key)
+                                .await
+                                .and_then(|data| save_file(&file_name, &data));
+                            if let Err(err) = result {
+                                on_error.run(err);
+                            }
+                        });
+                    }
+                };
+                let share = {
+                    let bucket = bucket.clone();
+                    let key = object.key.clone();
+                    move |_| {
+                        let bucket = bucket.clone();
+                        let key = key.clone();
+                        spawn_local(async move {
+                            match api::presign_object(&bucket, &key, SHARE_EXPIRES_SECS).await {
+                                Ok(url) => on_shared.run(url),
+                                Err(err) => on_error.run(err),
+                            }
+                        });
+                    }
+                };
+                let delete = {
+                    let bucket = bucket.clone();
+                    let key = object.key.clone();
+                    move |_| {
+                        let bucket = bucket.clone();
+                        let key = key.clone();
+                        if web_sys::window()
+                            .unwrap()
+                            .confirm_with_message(&format!("Delete object '{}'?", key))
+                            .unwrap_or(false)
+                        {
+                            spawn_local(async move {
+                                match api::delete_object(&bucket, &key).await {
+                                    Ok(()) => on_deleted.run(key),
+                                    Err(err) => on_error.run(err),
+                                }
+                            });
+                        }
+                    }
+                };
+
                 view! {
                     <div class="bucket-item">
                         <div class="bucket-info">
@@ -107,16 +302,19 @@ fn ObjectList(objects: Vec<ObjectInfo>) -> impl IntoView {
                             <div class="bucket-meta">
                                 {format!("{} • {} • Modified {}", 
                                     format_size(object.size),
-                                    object.content_type,
+                                    object.storage_class,
                                     object.last_modified.format("%Y-%m-%d %H:%M")
                                 )}
                             </div>
                         </div>
                         <div class="bucket-actions">
-                            <button class="btn btn-small">
+                            <button class="btn btn-small" on:click=download>
                                 "Download"
                             </button>
-                            <button class="btn btn-small btn-secondary">
+                            <button class="btn btn-small btn-secondary" on:click=share>
+                                "Share"
+                            </button>
+                            <button class="btn btn-small btn-secondary" on:click=delete>
                                 "Delete"
                             </button>
                         </div>
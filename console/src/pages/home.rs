@@ -1,7 +1,7 @@
 use leptos::prelude::*;
 use wasm_bindgen_futures::spawn_local;
 use crate::api;
-use crate::types::SystemStats;
+use crate::types::{ActivityEntry, SystemStats};
 
 #[component]
 pub fn HomePage() -> impl IntoView {
@@ -71,7 +71,18 @@ pub fn HomePage() -> impl IntoView {
                 <div class="card-header">
                     <h3 class="card-title">"Recent Activity"</h3>
                 </div>
-                <p class="card-description">"Recent uploads, downloads, and bucket operations will appear here."</p>
+                {move || {
+                    let activity = stats.get().map(|stats| stats.recent_activity).unwrap_or_default();
+                    if activity.is_empty() {
+                        view! {
+                            <p class="card-description">"No recent activity."</p>
+                        }.into_any()
+                    } else {
+                        view! {
+                            <RecentActivity activity=activity/>
+                        }.into_any()
+                    }
+                }}
             </div>
         </div>
     }
@@ -113,7 +124,37 @@ fn StatsCards(stats: SystemStats) -> impl IntoView {
                 <h3 class="card-title">"Storage Used"</h3>
             </div>
             <div class="card-value">{format_size(stats.total_size_bytes)}</div>
-            <p class="card-description">{format!("{:.1}% of capacity", stats.storage_usage_percent)}</p>
+            <p class="card-description">
+                {format!("Metadata database: {}", format_size(stats.database_size_bytes))}
+            </p>
         </div>
+
+        <div class="dashboard-card">
+            <div class="card-header">
+                <h3 class="card-title">"Users"</h3>
+            </div>
+            <div class="card-value">{stats.total_users}</div>
+            <p class="card-description">
+                {format!("As of {}", stats.checked_at.format("%Y-%m-%d %H:%M"))}
+            </p>
+        </div>
+    }
+}
+
+#[component]
+fn RecentActivity(activity: Vec<ActivityEntry>) -> impl IntoView {
+    view! {
+        <ul style="list-style: none; padding: 0; margin: 0;">
+            {activity.into_iter().map(|entry| {
+                view! {
+                    <li style="display: flex; justify-content: space-between; gap: 1rem; padding: 0.5rem 0;">
+                        <span>{entry.description}</span>
+                        <span class="card-description">
+                            {entry.timestamp.format("%Y-%m-%d %H:%M").to_string()}
+                        </span>
+                    </li>
+                }
+            }).collect::<Vec<_>>()}
+        </ul>
     }
 }
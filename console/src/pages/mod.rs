@@ -6,3 +6,6 @@ pub mod not_found;
 
 pub use home::*;
 pub use buckets::*;
+pub use bucket_detail::*;
+pub use settings::*;
+pub use not_found::*;
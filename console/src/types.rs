@@ -21,6 +21,14 @@ pub struct ObjectInfo {
     pub storage_class: String,
 }
 
+/// One page of a bucket listing
+#[derive(Debug, Clone, Default)]
+pub struct ObjectPage {
+    pub objects: Vec<ObjectInfo>,
+    /// Pass back to `list_objects` to fetch the next page; `None` on the last page
+    pub next_token: Option<String>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CreateBucketRequest {
     pub name: String,
@@ -35,12 +43,23 @@ pub struct UploadObjectRequest {
     pub data: Vec<u8>,
 }
 
+/// Server-wide stats from `GET /_admin/stats`
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SystemStats {
     pub total_buckets: u64,
     pub total_objects: u64,
     pub total_size_bytes: u64,
-    pub storage_usage_percent: f64,
+    pub total_users: u64,
+    pub database_size_bytes: u64,
+    pub checked_at: DateTime<Utc>,
+    #[serde(default)]
+    pub recent_activity: Vec<ActivityEntry>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ActivityEntry {
+    pub timestamp: DateTime<Utc>,
+    pub description: String,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -3,18 +3,20 @@
 pub mod sigv4;
 
 use axum::{
-    extract::{Request, State},
-    http::{HeaderMap, StatusCode},
+    extract::{OriginalUri, Query, Request, State},
+    http::{HeaderMap, Method, StatusCode, Uri},
     middleware::Next,
     response::Response,
 };
 use chrono::{DateTime, Utc};
-use object_io_core::{ObjectIOError, Result};
-use object_io_metadata::MetadataOperations;
+use object_io_core::{acl_allows, parse_copy_source, AccessKeyScope, ObjectIOError, Permission, Result};
+use object_io_metadata::{models::UserRecord, MetadataStore};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::sync::Arc;
 
-use crate::state::AppState;
-use sigv4::{AuthorizationHeader, SignatureRequest, SigV4Validator};
+use crate::{config::ServerConfig, handlers::form_upload, responses::xml::ErrorDocument, state::AppState};
+use sigv4::{AuthorizationHeader, PresignedQuery, SignatureRequest, SigV4Validator};
 
 /// Authentication middleware for S3 API requests
 pub async fn auth_middleware(
@@ -27,13 +29,40 @@ pub async fn auth_middleware(
     if path == "/health" || request.method() == "OPTIONS" {
         return Ok(next.run(request).await);
     }
+    // Browser form uploads authenticate with their signed policy instead
+    let target = path.trim_matches('/');
+    let bucket_level = !target.is_empty() && !target.contains('/');
+    if request.method() == Method::POST && bucket_level && form_upload::is_form_upload(request.headers()) {
+        return Ok(next.run(request).await);
+    }
 
     // Extract authentication information from headers
     let headers = request.headers().clone();
-    let auth_result = authenticate_request(&headers, &request, &state.metadata).await;
+    let method = request.method().clone();
+    let uri = request.uri().clone();
+    // Clients sign the URI they sent, before any virtual-host rewrite
+    let signed_uri = request
+        .extensions()
+        .get::<OriginalUri>()
+        .map(|original| original.0.clone())
+        .unwrap_or_else(|| uri.clone());
+    let anonymous_read = anonymous_object_read(&method, &uri, &headers);
+    if let Some((bucket, key)) = &anonymous_read {
+        if is_public_object(&state.metadata, bucket, key).await {
+            return Ok(next.run(request).await);
+        }
+    }
+    let auth_result = authenticate_request(&headers, &method, &signed_uri, &state.metadata, &state.config).await;
 
     match auth_result {
         Ok(auth_context) => {
+            if let Some(scope) = &auth_context.scope {
+                if let Err(e) = check_scope(scope, &method, &uri, &headers) {
+                    eprintln!("Access key {} denied: {}", auth_context.access_key, e);
+                    return Ok(access_denied("Access Denied"));
+                }
+            }
+
             // Add auth context to request extensions for use in handlers
             let (mut parts, body) = request.into_parts();
             parts.extensions.insert(auth_context);
@@ -42,63 +71,176 @@ pub async fn auth_middleware(
         }
         Err(ObjectIOError::AuthError { message }) => {
             eprintln!("Authentication failed: {}", message);
-            
-            // Return appropriate S3 error response
-            let error_response = format!(
-                r#"<?xml version="1.0" encoding="UTF-8"?>
-<Error>
-    <Code>AccessDenied</Code>
-    <Message>{}</Message>
-    <RequestId>00000000-0000-0000-0000-000000000000</RequestId>
-</Error>"#,
-                message
-            );
-
-            let response = Response::builder()
-                .status(StatusCode::FORBIDDEN)
-                .header("content-type", "application/xml")
-                .body(error_response.into())
-                .unwrap();
-            
-            Ok(response)
+            match anonymous_read {
+                Some((bucket, key)) if state.config.denied_object_reads == DeniedReads::NotFound => {
+                    let error = ObjectIOError::ObjectNotFound { bucket, key: key.clone() };
+                    Ok(ErrorDocument::from_error(&error).with_key(key).into_response(StatusCode::NOT_FOUND))
+                }
+                _ => Ok(access_denied(&message)),
+            }
         }
         Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR),
     }
 }
 
+/// How anonymous reads of objects that are not public are refused
+///
+/// Either way, a missing key is refused exactly like an existing private
+/// one, so the response never reveals whether an object exists. `forbidden`
+/// is what S3 answers callers who may not list the bucket; `not_found`
+/// hides that the bucket holds anything at all, at the cost of telling
+/// clients that lack credentials (or forgot to sign) the object is missing
+/// rather than protected, which makes such mistakes harder to diagnose.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DeniedReads {
+    /// `403 AccessDenied`
+    #[default]
+    Forbidden,
+    /// `404 NoSuchKey`
+    NotFound,
+}
+
+/// The bucket and key a request without credentials reads, if it is a
+/// plain GET or HEAD of an object
+///
+/// Only the object itself qualifies, optionally overriding response
+/// headers, not subresources like part listings or the ACL.
+fn anonymous_object_read(method: &Method, uri: &Uri, headers: &HeaderMap) -> Option<(String, String)> {
+    let query = uri.query().unwrap_or("");
+    if !matches!(*method, Method::GET | Method::HEAD)
+        || headers.contains_key("authorization")
+        || PresignedQuery::is_presigned(query)
+        || !query.split('&').all(|param| param.is_empty() || param.starts_with("response-"))
+    {
+        return None;
+    }
+
+    let path = urlencoding::decode(uri.path()).ok()?;
+    match path.trim_start_matches('/').split_once('/') {
+        Some((bucket, key)) if !bucket.is_empty() && !key.is_empty() => Some((bucket.to_string(), key.to_string())),
+        _ => None,
+    }
+}
+
+/// Whether an object's ACL grants read access to everyone
+///
+/// Anonymous reads of such objects go through without an `AuthContext`.
+async fn is_public_object(metadata: &Arc<dyn MetadataStore>, bucket: &str, key: &str) -> bool {
+    matches!(
+        metadata.get_object(bucket, key).await,
+        Ok(Some(object)) if acl_allows(&object.acl, None, Permission::Read)
+    )
+}
+
+/// S3 `AccessDenied` error response
+fn access_denied(message: &str) -> Response {
+    ErrorDocument::new("AccessDenied", message).into_response(StatusCode::FORBIDDEN)
+}
+
+/// Check that a request made with a scoped access key stays within its scope
+///
+/// The target is taken from the path (`/bucket[/key]`); bucket-level reads
+/// are checked against their `prefix` parameter, copies against their
+/// source and renames against their destination as well. Service-level and admin requests are out of scope.
+fn check_scope(scope: &AccessKeyScope, method: &Method, uri: &Uri, headers: &HeaderMap) -> Result<()> {
+    let denied = || ObjectIOError::AuthorizationFailed {
+        reason: format!("Request is outside the access key scope '{}/{}'", scope.bucket, scope.prefix),
+    };
+
+    let path = urlencoding::decode(uri.path()).map_err(|_| denied())?;
+    let (bucket, key) = match path.trim_start_matches('/').split_once('/') {
+        Some((bucket, key)) if !key.is_empty() => (bucket.to_string(), Some(key.to_string())),
+        Some((bucket, _)) => (bucket.to_string(), None),
+        None => (path.trim_start_matches('/').to_string(), None),
+    };
+    if bucket.is_empty() {
+        return Err(denied());
+    }
+
+    let write = !matches!(*method, Method::GET | Method::HEAD);
+    let key = match key {
+        Some(key) => Some(key),
+        None if !write => Query::<HashMap<String, String>>::try_from_uri(uri)
+            .map_err(|_| denied())?
+            .0
+            .remove("prefix")
+            .filter(|prefix| !prefix.is_empty()),
+        None => None,
+    };
+    if !scope.allows(&bucket, key.as_deref(), write) {
+        return Err(denied());
+    }
+
+    if let Some(source) = headers.get("x-amz-copy-source").and_then(|h| h.to_str().ok()) {
+        let (source_bucket, source_key) = parse_copy_source(source).ok_or_else(denied)?;
+        if !scope.allows(&source_bucket, Some(&source_key), false) {
+            return Err(denied());
+        }
+    }
+
+    // A rename writes its destination too
+    let rename = Query::<HashMap<String, String>>::try_from_uri(uri).map_err(|_| denied())?.0.remove("rename");
+    if let Some(dst_key) = rename {
+        if !scope.allows(&bucket, Some(&dst_key), true) {
+            return Err(denied());
+        }
+    }
+
+    Ok(())
+}
+
 /// Authentication context for requests
 #[derive(Debug, Clone)]
 pub struct AuthContext {
     pub access_key: String,
     pub user_id: String,
     pub is_admin: bool,
+    /// Named permissions granted to the user, such as `list_all_buckets`
+    pub permissions: Vec<String>,
+    /// Bucket and prefix the access key is restricted to, if any
+    pub scope: Option<AccessKeyScope>,
 }
 
 /// Authenticate S3 API request
 async fn authenticate_request(
     headers: &HeaderMap,
-    request: &Request,
-    metadata: &Arc<MetadataOperations>,
+    method: &Method,
+    uri: &Uri,
+    metadata: &Arc<dyn MetadataStore>,
+    config: &ServerConfig,
 ) -> Result<AuthContext> {
-    // Check for Authorization header
-    let auth_header = headers
-        .get("authorization")
-        .and_then(|h| h.to_str().ok())
-        .ok_or_else(|| ObjectIOError::AuthError {
-            message: "Missing Authorization header".to_string(),
-        })?;
+    let query = uri.query().unwrap_or("");
+    let validator = SigV4Validator::new(config.region.clone(), config.service.clone());
+
+    // Presigned URLs carry their signature in the query string instead
+    let auth_header = match headers.get("authorization").and_then(|h| h.to_str().ok()) {
+        Some(auth_header) => auth_header,
+        None if PresignedQuery::is_presigned(query) => {
+            let presigned = PresignedQuery::parse(query)?;
+            if presigned.is_expired(Utc::now()) {
+                return Err(ObjectIOError::AuthError {
+                    message: "Request has expired".to_string(),
+                });
+            }
+            let user = lookup_user(metadata, &presigned.auth).await?;
+            if !validator.validate_presigned(method, uri.path(), headers, &presigned, &user.secret_key)? {
+                return Err(ObjectIOError::AuthError {
+                    message: "Signature verification failed".to_string(),
+                });
+            }
+            return Ok(authenticated(metadata, user));
+        }
+        None => {
+            return Err(ObjectIOError::AuthError {
+                message: "Missing Authorization header".to_string(),
+            })
+        }
+    };
 
     // Parse authorization header
     let parsed_auth = AuthorizationHeader::parse(auth_header)?;
-    let access_key = parsed_auth.access_key()?;
-
-    // Look up user by access key
-    let user = metadata
-        .get_user_by_access_key(&access_key)
-        .await?
-        .ok_or_else(|| ObjectIOError::AuthError {
-            message: "Invalid access key".to_string(),
-        })?;
+    let user = lookup_user(metadata, &parsed_auth).await?;
 
     // Extract timestamp from x-amz-date header
     let timestamp = extract_timestamp(headers)?;
@@ -111,20 +253,15 @@ async fn authenticate_request(
 
     // Create signature request
     let sig_request = SignatureRequest {
-        method: request.method(),
-        uri: request.uri().path(),
-        query_string: request.uri().query().unwrap_or(""),
+        method,
+        uri: uri.path(),
+        query_string: query,
         headers,
         payload_hash,
         timestamp,
     };
 
     // Validate signature
-    let validator = SigV4Validator::new(
-        "us-east-1".to_string(), // TODO: Get from config
-        "s3".to_string(),
-    );
-
     let is_valid = validator.validate_signature(&sig_request, &parsed_auth, &user.secret_key)?;
 
     if !is_valid {
@@ -133,11 +270,37 @@ async fn authenticate_request(
         });
     }
 
-    Ok(AuthContext {
+    Ok(authenticated(metadata, user))
+}
+
+/// Look up the user owning the access key a request was signed with
+async fn lookup_user(metadata: &Arc<dyn MetadataStore>, auth: &AuthorizationHeader) -> Result<UserRecord> {
+    metadata
+        .get_user_by_access_key(&auth.access_key()?)
+        .await?
+        .ok_or_else(|| ObjectIOError::AuthError {
+            message: "Invalid access key".to_string(),
+        })
+}
+
+/// Build the context of a verified request, recording the key's use
+fn authenticated(metadata: &Arc<dyn MetadataStore>, user: UserRecord) -> AuthContext {
+    // Record last use in the background; a failed write must not fail the request
+    let metadata = Arc::clone(metadata);
+    let used_key = user.access_key.clone();
+    tokio::spawn(async move {
+        if let Err(e) = metadata.record_access_key_use(&used_key).await {
+            tracing::warn!("Failed to record use of access key {}: {}", used_key, e);
+        }
+    });
+
+    AuthContext {
         access_key: user.access_key,
-        user_id: user.id.unwrap_or_default().to_string(),
+        user_id: user.id.as_ref().and_then(|id| id.as_str()).unwrap_or_default().to_string(),
         is_admin: user.is_admin,
-    })
+        permissions: user.permissions,
+        scope: user.scope,
+    }
 }
 
 /// Extract timestamp from request headers
@@ -153,8 +316,8 @@ fn extract_timestamp(headers: &HeaderMap) -> Result<DateTime<Utc>> {
 
     // Parse timestamp (x-amz-date format: 20230101T120000Z)
     if timestamp_str.ends_with('Z') && timestamp_str.contains('T') {
-        DateTime::parse_from_str(timestamp_str, "%Y%m%dT%H%M%SZ")
-            .map(|dt| dt.with_timezone(&Utc))
+        chrono::NaiveDateTime::parse_from_str(timestamp_str, "%Y%m%dT%H%M%SZ")
+            .map(|dt| dt.and_utc())
             .map_err(|_| ObjectIOError::AuthError {
                 message: "Invalid timestamp format".to_string(),
             })
@@ -169,7 +332,7 @@ fn extract_timestamp(headers: &HeaderMap) -> Result<DateTime<Utc>> {
 }
 
 /// Create initial admin user if none exists
-pub async fn ensure_admin_user(metadata: &Arc<MetadataOperations>) -> Result<()> {
+pub async fn ensure_admin_user(metadata: &Arc<dyn MetadataStore>) -> Result<()> {
     // Check if any admin users exist
     let admin_exists = metadata.admin_user_exists().await?;
     
@@ -189,3 +352,75 @@ pub async fn ensure_admin_user(metadata: &Arc<MetadataOperations>) -> Result<()>
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check(scope: &AccessKeyScope, method: Method, uri: &str, copy_source: Option<&str>) -> bool {
+        let mut headers = HeaderMap::new();
+        if let Some(source) = copy_source {
+            headers.insert("x-amz-copy-source", source.parse().unwrap());
+        }
+        check_scope(scope, &method, &uri.parse().unwrap(), &headers).is_ok()
+    }
+
+    #[test]
+    fn test_check_scope() {
+        let scope = AccessKeyScope {
+            bucket: "analytics".to_string(),
+            prefix: "reports/".to_string(),
+            read_only: true,
+        };
+
+        assert!(check(&scope, Method::GET, "/analytics/reports/2024.csv", None));
+        assert!(check(&scope, Method::HEAD, "/analytics/reports/q%201.csv", None));
+        assert!(check(&scope, Method::GET, "/analytics?prefix=reports/2024", None));
+        assert!(check(&scope, Method::GET, "/analytics/?list-type=2&prefix=reports%2F", None));
+
+        // Outside the prefix or bucket
+        assert!(!check(&scope, Method::GET, "/analytics/private/keys.txt", None));
+        assert!(!check(&scope, Method::GET, "/analytics", None));
+        assert!(!check(&scope, Method::GET, "/analytics?prefix=rep", None));
+        assert!(!check(&scope, Method::GET, "/other/reports/2024.csv", None));
+        assert!(!check(&scope, Method::GET, "/", None));
+        assert!(!check(&scope, Method::GET, "/_admin/users", None));
+
+        // Writes are denied on a read-only key
+        assert!(!check(&scope, Method::PUT, "/analytics/reports/2024.csv", None));
+        assert!(!check(&scope, Method::DELETE, "/analytics/reports/2024.csv", None));
+
+        let scope = AccessKeyScope {
+            read_only: false,
+            ..scope
+        };
+        assert!(check(&scope, Method::PUT, "/analytics/reports/2024.csv", None));
+        assert!(check(&scope, Method::POST, "/analytics/reports/big.csv?uploads", None));
+        assert!(!check(&scope, Method::PUT, "/analytics/private/2024.csv", None));
+        assert!(!check(&scope, Method::DELETE, "/analytics", None));
+
+        // Copies must read from within the scope too
+        assert!(check(&scope, Method::PUT, "/analytics/reports/copy.csv", Some("/analytics/reports/2024.csv")));
+        assert!(!check(&scope, Method::PUT, "/analytics/reports/copy.csv", Some("/analytics/private/keys.txt")));
+        assert!(!check(&scope, Method::PUT, "/analytics/reports/copy.csv", Some("not-a-source")));
+
+        // Renames must stay within the scope
+        assert!(check(&scope, Method::POST, "/analytics/reports/a.csv?rename=reports/b.csv", None));
+        assert!(!check(&scope, Method::POST, "/analytics/reports/a.csv?rename=private/a.csv", None));
+    }
+
+    #[test]
+    fn test_scope_covers_whole_bucket() {
+        let scope = AccessKeyScope {
+            bucket: "shared".to_string(),
+            prefix: String::new(),
+            read_only: false,
+        };
+
+        assert!(check(&scope, Method::GET, "/shared", None));
+        assert!(check(&scope, Method::PUT, "/shared/any/key", None));
+        // Bucket-level writes are never in scope
+        assert!(!check(&scope, Method::PUT, "/shared", None));
+        assert!(!check(&scope, Method::DELETE, "/shared", None));
+    }
+}
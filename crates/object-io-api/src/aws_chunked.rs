@@ -0,0 +1,331 @@
+//! Decoding of `aws-chunked` request bodies
+//!
+//! Streaming uploads from the AWS SDKs frame the payload in chunks, ending
+//! with trailers that may carry the object's checksum:
+//!
+//! ```text
+//! <size in hex>[;chunk-signature=<signature>]\r\n
+//! <data>\r\n
+//! ...
+//! 0[;chunk-signature=<signature>]\r\n
+//! <trailer name>:<value>\r\n
+//! \r\n
+//! ```
+//!
+//! Chunk and trailer signatures are not checked.
+
+use axum::{
+    body::{Body, BodyDataStream, Bytes},
+    http::HeaderMap,
+};
+use bytes::BytesMut;
+use futures::StreamExt;
+use object_io_core::{checksum::CHECKSUM_HEADER_PREFIX, ChecksumAlgorithm, ChecksumHasher, ObjectIOError};
+use std::sync::{Arc, Mutex};
+use tokio::io::AsyncRead;
+
+/// Longest chunk header or trailer line accepted
+const MAX_LINE_LENGTH: usize = 4096;
+
+/// How a request's `aws-chunked` body is to be decoded
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ChunkedUpload {
+    /// Checksum declared in `x-amz-trailer`, to be checked against the data
+    pub trailer: Option<ChecksumAlgorithm>,
+    /// Size of the payload once decoded (`x-amz-decoded-content-length`)
+    pub decoded_length: Option<u64>,
+}
+
+impl ChunkedUpload {
+    /// The decoding a request's body needs, or `None` if it is not
+    /// `aws-chunked`
+    ///
+    /// Fails if the request declares a checksum trailer of an unsupported
+    /// algorithm.
+    pub fn from_headers(headers: &HeaderMap) -> object_io_core::Result<Option<Self>> {
+        let header = |name: &str| headers.get(name).and_then(|value| value.to_str().ok());
+        let chunked = header("content-encoding")
+            .is_some_and(|encoding| encoding.split(',').any(|coding| coding.trim() == "aws-chunked"))
+            || header("x-amz-content-sha256").is_some_and(|hash| hash.starts_with("STREAMING-"));
+        if !chunked {
+            return Ok(None);
+        }
+
+        let mut trailer = None;
+        for name in header("x-amz-trailer").unwrap_or_default().split(',') {
+            let name = name.trim();
+            if name.to_ascii_lowercase().starts_with(CHECKSUM_HEADER_PREFIX) {
+                trailer = Some(ChecksumAlgorithm::from_header(name).ok_or_else(|| ObjectIOError::InvalidRequest {
+                    message: format!("Unsupported checksum trailer: {}", name),
+                })?);
+            }
+        }
+
+        let decoded_length = match header("x-amz-decoded-content-length") {
+            Some(length) => Some(length.parse().map_err(|_| ObjectIOError::InvalidArgument {
+                message: format!("Invalid x-amz-decoded-content-length: {}", length),
+            })?),
+            None => None,
+        };
+        Ok(Some(Self { trailer, decoded_length }))
+    }
+
+    /// Decode `body` into the payload it carries
+    ///
+    /// The reader fails if the body is malformed, shorter or longer than
+    /// declared, or its checksum trailer is missing or does not match the
+    /// data; the returned [`DecodeError`] then holds the reason. The error is
+    /// raised in place of the end of the data, so a store reading it never
+    /// completes.
+    pub fn decode(self, body: Body) -> (Box<dyn AsyncRead + Send + Unpin>, DecodeError) {
+        let error = DecodeError::default();
+        let decoder = Decoder {
+            body: body.into_data_stream(),
+            buffer: BytesMut::new(),
+            state: State::Header,
+            hasher: self.trailer.map(ChecksumAlgorithm::hasher),
+            decoded: 0,
+            upload: self,
+            error: error.clone(),
+        };
+        let stream = futures::stream::unfold(Some(decoder), |decoder| async move {
+            let mut decoder = decoder?;
+            match decoder.next_chunk().await {
+                Ok(Some(data)) => Some((Ok(data), Some(decoder))),
+                Ok(None) => None,
+                Err(e) => Some((Err(e), None)),
+            }
+        });
+        (Box::new(tokio_util::io::StreamReader::new(Box::pin(stream))), error)
+    }
+}
+
+/// Why decoding an `aws-chunked` body failed, once its reader has failed
+#[derive(Debug, Clone, Default)]
+pub struct DecodeError(Arc<Mutex<Option<ObjectIOError>>>);
+
+impl DecodeError {
+    /// The reason decoding failed, if it did
+    pub fn take(&self) -> Option<ObjectIOError> {
+        self.0.lock().unwrap().take()
+    }
+
+    /// Record `error` and convert it for the reader to return
+    fn raise(&self, error: ObjectIOError) -> std::io::Error {
+        let io_error = std::io::Error::new(std::io::ErrorKind::InvalidData, error.to_string());
+        *self.0.lock().unwrap() = Some(error);
+        io_error
+    }
+}
+
+/// Where the decoder is in the body
+enum State {
+    /// Expecting a chunk header line
+    Header,
+    /// Inside a chunk's data, with this many bytes left
+    Data(u64),
+    /// Expecting the line break closing a chunk's data
+    DataEnd,
+    /// Past the final chunk, expecting trailers
+    Trailers,
+    Done,
+}
+
+struct Decoder {
+    body: BodyDataStream,
+    buffer: BytesMut,
+    state: State,
+    hasher: Option<ChecksumHasher>,
+    decoded: u64,
+    upload: ChunkedUpload,
+    error: DecodeError,
+}
+
+fn malformed(message: &str) -> ObjectIOError {
+    ObjectIOError::InvalidRequest {
+        message: format!("Malformed aws-chunked body: {}", message),
+    }
+}
+
+impl Decoder {
+    /// Read more of the body into the buffer, returning false at its end
+    async fn fill(&mut self) -> std::io::Result<bool> {
+        match self.body.next().await {
+            Some(Ok(data)) => {
+                self.buffer.extend_from_slice(&data);
+                Ok(true)
+            }
+            Some(Err(e)) => Err(std::io::Error::other(e)),
+            None => Ok(false),
+        }
+    }
+
+    /// The next `\r\n`-terminated line, without its terminator
+    async fn line(&mut self) -> std::io::Result<String> {
+        loop {
+            if let Some(end) = self.buffer.windows(2).position(|pair| pair == b"\r\n") {
+                let line = self.buffer.split_to(end + 2);
+                return String::from_utf8(line[..end].to_vec())
+                    .map_err(|_| self.error.raise(malformed("line is not UTF-8")));
+            }
+            if self.buffer.len() > MAX_LINE_LENGTH {
+                return Err(self.error.raise(malformed("line too long")));
+            }
+            if !self.fill().await? {
+                return Err(self.error.raise(malformed("body ended early")));
+            }
+        }
+    }
+
+    /// The next piece of decoded data, or `None` once the body has been
+    /// read and verified
+    async fn next_chunk(&mut self) -> std::io::Result<Option<Bytes>> {
+        loop {
+            match self.state {
+                State::Header => {
+                    let line = self.line().await?;
+                    let size = line.split(';').next().unwrap_or_default().trim();
+                    let size = u64::from_str_radix(size, 16)
+                        .map_err(|_| self.error.raise(malformed(&format!("invalid chunk size {:?}", size))))?;
+                    self.state = if size == 0 { State::Trailers } else { State::Data(size) };
+                }
+                State::Data(remaining) => {
+                    if self.buffer.is_empty() && !self.fill().await? {
+                        return Err(self.error.raise(malformed("body ended early")));
+                    }
+                    let n = remaining.min(self.buffer.len() as u64);
+                    let data = self.buffer.split_to(n as usize).freeze();
+                    self.state = if n == remaining { State::DataEnd } else { State::Data(remaining - n) };
+                    if let Some(hasher) = &mut self.hasher {
+                        hasher.update(&data);
+                    }
+                    self.decoded += n;
+                    return Ok(Some(data));
+                }
+                State::DataEnd => {
+                    if !self.line().await?.is_empty() {
+                        return Err(self.error.raise(malformed("chunk longer than its size")));
+                    }
+                    self.state = State::Header;
+                }
+                State::Trailers => {
+                    let mut checksum = None;
+                    loop {
+                        let line = self.line().await?;
+                        if line.is_empty() {
+                            break;
+                        }
+                        let (name, value) = line
+                            .split_once(':')
+                            .ok_or_else(|| self.error.raise(malformed("invalid trailer")))?;
+                        if let Some(algorithm) = self.upload.trailer {
+                            if name.trim().eq_ignore_ascii_case(algorithm.header_name()) {
+                                checksum = Some(value.trim().to_string());
+                            }
+                        }
+                    }
+                    self.state = State::Done;
+                    self.verify(checksum).map_err(|e| self.error.raise(e))?;
+                }
+                State::Done => return Ok(None),
+            }
+        }
+    }
+
+    /// Check the decoded data against the declared length and checksum
+    fn verify(&mut self, checksum: Option<String>) -> object_io_core::Result<()> {
+        if let Some(expected) = self.upload.decoded_length.filter(|&expected| expected != self.decoded) {
+            return Err(ObjectIOError::InvalidRequest {
+                message: format!("Decoded {} bytes, but x-amz-decoded-content-length is {}", self.decoded, expected),
+            });
+        }
+
+        let (Some(algorithm), Some(hasher)) = (self.upload.trailer, self.hasher.take()) else {
+            return Ok(());
+        };
+        let checksum = checksum.ok_or_else(|| ObjectIOError::InvalidRequest {
+            message: format!("Missing trailer {}", algorithm.header_name()),
+        })?;
+        let actual = hasher.finalize();
+        if actual != checksum {
+            return Err(ObjectIOError::BadDigest {
+                message: format!(
+                    "The {} you specified did not match the calculated checksum ({})",
+                    algorithm.header_name(),
+                    actual
+                ),
+            });
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::AsyncReadExt;
+
+    fn headers(pairs: &[(&'static str, &'static str)]) -> HeaderMap {
+        pairs.iter().map(|(name, value)| (name.parse().unwrap(), value.parse().unwrap())).collect()
+    }
+
+    async fn decode(upload: ChunkedUpload, body: &'static str) -> Result<Vec<u8>, Option<ObjectIOError>> {
+        // Split the body across several frames to exercise buffering
+        let frames: Vec<Result<&'static [u8], std::io::Error>> =
+            body.as_bytes().chunks(3).map(Ok).collect();
+        let body = Body::from_stream(futures::stream::iter(frames));
+        let (mut reader, error) = upload.decode(body);
+        let mut data = Vec::new();
+        match reader.read_to_end(&mut data).await {
+            Ok(_) => Ok(data),
+            Err(_) => Err(error.take()),
+        }
+    }
+
+    #[test]
+    fn test_from_headers() {
+        assert_eq!(ChunkedUpload::from_headers(&headers(&[("content-encoding", "gzip")])).unwrap(), None);
+
+        let upload = ChunkedUpload::from_headers(&headers(&[
+            ("content-encoding", "aws-chunked"),
+            ("x-amz-trailer", "x-amz-checksum-crc32c"),
+            ("x-amz-decoded-content-length", "11"),
+        ]))
+        .unwrap()
+        .unwrap();
+        assert_eq!(upload.trailer, Some(ChecksumAlgorithm::Crc32c));
+        assert_eq!(upload.decoded_length, Some(11));
+
+        let upload = headers(&[("x-amz-content-sha256", "STREAMING-AWS4-HMAC-SHA256-PAYLOAD")]);
+        assert_eq!(ChunkedUpload::from_headers(&upload).unwrap(), Some(ChunkedUpload::default()));
+
+        let unsupported = headers(&[("content-encoding", "aws-chunked"), ("x-amz-trailer", "x-amz-checksum-md5")]);
+        assert!(matches!(
+            ChunkedUpload::from_headers(&unsupported),
+            Err(ObjectIOError::InvalidRequest { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_decode() {
+        let signed = "5;chunk-signature=abc\r\nhello\r\n6;chunk-signature=def\r\n world\r\n0;chunk-signature=0\r\n\r\n";
+        assert_eq!(decode(ChunkedUpload::default(), signed).await.unwrap(), b"hello world");
+
+        let upload = ChunkedUpload {
+            trailer: None,
+            decoded_length: Some(12),
+        };
+        assert!(matches!(decode(upload, signed).await, Err(Some(ObjectIOError::InvalidRequest { .. }))));
+
+        let upload = ChunkedUpload {
+            trailer: Some(ChecksumAlgorithm::Sha256),
+            decoded_length: None,
+        };
+        assert!(matches!(decode(upload, signed).await, Err(Some(ObjectIOError::InvalidRequest { .. }))));
+
+        for malformed in ["5\r\nhello", "5\r\nhello world\r\n0\r\n\r\n", "x\r\n\r\n", "5\r\nhello\r\n0\r\n"] {
+            let result = decode(ChunkedUpload::default(), malformed).await;
+            assert!(matches!(result, Err(Some(ObjectIOError::InvalidRequest { .. }))), "{:?}", malformed);
+        }
+    }
+}
@@ -0,0 +1,595 @@
+//! Server configuration loading
+
+use ::config::{Config, Environment, File, Map};
+use object_io_core::{CannedAcl, ObjectIOError, Result};
+use object_io_storage::{KeyCase, KeyLayout, StorageBackendKind, StorageConfig};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+use crate::auth::DeniedReads;
+use crate::middleware::{EncodedSlashes, RateLimit};
+
+/// Server configuration
+///
+/// Values are taken from the config file when given, then from environment
+/// variables named `OBJECTIO_` and the setting in upper case (e.g.
+/// `OBJECTIO_STORAGE_PATH`), then from the unprefixed variables earlier
+/// releases read (e.g. `DEFAULT_REGION`), then from the defaults below.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ServerConfig {
+    /// Address to bind
+    pub host: String,
+    /// Port to listen on
+    pub port: u16,
+    /// Metadata database path
+    pub database_path: String,
+    /// Storage backend to use
+    pub storage_backend: StorageBackendKind,
+    /// Storage root path (filesystem backend)
+    pub storage_path: String,
+    /// Directory for in-progress writes (filesystem backend); `.objectio-tmp`
+    /// under the storage root when unset
+    pub storage_temp_path: Option<String>,
+    /// How object keys map to file paths (filesystem backend): `native` uses
+    /// them unchanged, `preserve` escapes letters with case so keys differing
+    /// only in case stay separate on case-insensitive filesystems. Must not
+    /// change once the storage root holds objects.
+    pub storage_key_case: KeyCase,
+    /// Where object files go under their bucket (filesystem backend):
+    /// `nested` uses the key as the path, `hashed` stores each object under a
+    /// hash of its key so keys of any length fit the filesystem's path
+    /// limits. Must not change once the storage root holds objects.
+    pub storage_key_layout: KeyLayout,
+    /// Store the bytes of identical objects once, shared between their keys
+    /// (filesystem backend). Objects stored before it was enabled keep their
+    /// own copies.
+    pub storage_dedup: bool,
+    /// Region used for SigV4 credential scopes
+    pub region: String,
+    /// Service name used for SigV4 credential scopes
+    pub service: String,
+    /// Maximum request body (and therefore object) size in bytes
+    pub max_body_size: usize,
+    /// Largest PUT body buffered in memory before it is stored, in bytes;
+    /// larger bodies are spooled to a temporary file in `storage_temp_path`
+    /// (or the system's temporary directory) first. 0 streams every body
+    /// straight to the storage backend.
+    pub upload_memory_threshold: usize,
+    /// Largest header section accepted, in bytes: the names and values of
+    /// every request header together. Larger ones are refused with `400`
+    /// (0 disables the limit)
+    pub max_header_size: usize,
+    /// Total time allowed to handle a request, in seconds
+    pub request_timeout: u64,
+    /// Time allowed for a client to send its request headers, in seconds
+    /// (0 disables the limit)
+    pub header_read_timeout: u64,
+    /// Longest a request body may go without sending data, or a response
+    /// without being read, in seconds (0 disables the limit)
+    pub body_idle_timeout: u64,
+    /// Time to wait for in-flight requests on shutdown, in seconds
+    pub shutdown_timeout: u64,
+    /// Require SigV4 authentication on S3 requests
+    pub auth_enabled: bool,
+    /// Age in seconds after which incomplete multipart uploads are aborted
+    pub multipart_upload_max_age: u64,
+    /// Seconds between abandoned multipart upload sweeps (0 disables the sweeper)
+    pub multipart_sweep_interval: u64,
+    /// Largest `max-keys` honoured by object listings; larger requests are clamped
+    pub max_list_keys: u32,
+    /// Treat recreating a bucket its owner already has as a no-op in every
+    /// region, not only us-east-1 as S3 does
+    pub idempotent_bucket_create: bool,
+    /// Canned ACL given to objects and buckets created without `x-amz-acl`
+    pub default_acl: CannedAcl,
+    /// Base domain for virtual-host-style requests (`bucket.<domain>/key`);
+    /// only path-style addressing is served when unset
+    pub virtual_host_domain: Option<String>,
+    /// Whether `%2F` in object keys is decoded to `/` (`canonicalize`) or
+    /// refused (`reject`)
+    pub encoded_slashes: EncodedSlashes,
+    /// Longest an integrity scrub may run, in seconds, before it stops with a
+    /// marker to resume from
+    pub scrub_max_duration: u64,
+    /// Requests taking longer than this, in milliseconds, are logged as a
+    /// warning (0 disables the log)
+    pub slow_request_threshold_ms: u64,
+    /// Requests per second allowed per access key, and per client IP for
+    /// anonymous requests unless they have their own limit (0 disables rate
+    /// limiting)
+    pub rate_limit_per_second: u32,
+    /// Requests allowed at once after a quiet period (0 means one second's worth)
+    pub rate_limit_burst: u32,
+    /// Per-user replacements for the global rate limit (config file only)
+    #[serde(default)]
+    pub rate_limit_overrides: Vec<UserRateLimit>,
+    /// Requests per second allowed per client IP for anonymous requests
+    /// (0 disables); the global rate limit applies when unset
+    pub anonymous_rate_limit_per_second: Option<u32>,
+    /// Anonymous burst allowance (0 means one second's worth)
+    pub anonymous_rate_limit_burst: u32,
+    /// Bytes each anonymous client IP may send and receive per UTC day
+    /// (0 disables the quota)
+    pub anonymous_daily_byte_quota: u64,
+    /// Requests handled at once; further requests are refused with `503
+    /// SlowDown` until one finishes (0 disables the limit)
+    pub max_concurrent_requests: usize,
+    /// How long, in seconds, a PUT made with an idempotency key is
+    /// remembered for retries to replay (0 disables replay)
+    pub idempotency_window: u64,
+    /// Whether anonymous reads of private objects are refused as
+    /// `forbidden` (403) or `not_found` (404)
+    pub denied_object_reads: DeniedReads,
+    /// Start in read-only mode, refusing writes until an admin lifts it
+    pub read_only: bool,
+    /// `Retry-After`, in seconds, sent with writes refused in read-only mode
+    pub read_only_retry_after: u64,
+    /// Compress listings, error documents and text objects for clients that
+    /// send `Accept-Encoding: gzip` or `br`
+    pub compression_enabled: bool,
+    /// Smallest response body, in bytes, worth compressing
+    pub compression_min_size: u16,
+    /// Secondary storage the change feed is replicated into (config file
+    /// only); nothing is replicated when unset
+    #[serde(default)]
+    pub replication: Option<ReplicationConfig>,
+}
+
+/// Rate limit applied to all access keys of one user
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UserRateLimit {
+    pub user_id: String,
+    /// Requests per second (0 exempts the user)
+    pub per_second: u32,
+    #[serde(default)]
+    pub burst: u32,
+}
+
+/// Replication target and progress tracking
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReplicationConfig {
+    /// Storage backend objects are copied into, unless `endpoint` is set
+    #[serde(default)]
+    pub backend: StorageBackendKind,
+    /// Root of the target (filesystem backend)
+    #[serde(default)]
+    pub path: String,
+    /// S3 endpoint objects are copied to instead of a storage backend, such
+    /// as another ObjectIO server (`https://backup.example.com:5500`); its
+    /// buckets must already exist
+    pub endpoint: Option<String>,
+    /// Access key requests to `endpoint` are signed with
+    #[serde(default)]
+    pub access_key: String,
+    /// Secret key of `access_key`
+    #[serde(default)]
+    pub secret_key: String,
+    /// Region requests to `endpoint` are signed for
+    #[serde(default = "default_replication_region")]
+    pub region: String,
+    /// File recording how far through the change feed replication has got;
+    /// without one, progress is kept in memory and a restart replays the
+    /// feed from the start
+    pub cursor_path: Option<String>,
+    /// Seconds between polls of the change feed once replication has caught up
+    #[serde(default = "default_replication_interval")]
+    pub interval: u64,
+    /// Longest wait between retries after a failure, in seconds; waits
+    /// double from `interval` up to it
+    #[serde(default = "default_replication_max_backoff")]
+    pub max_backoff: u64,
+}
+
+fn default_replication_region() -> String {
+    "us-east-1".to_string()
+}
+
+fn default_replication_interval() -> u64 {
+    10
+}
+
+fn default_replication_max_backoff() -> u64 {
+    5 * 60
+}
+
+impl ReplicationConfig {
+    /// Storage configuration of the replication target
+    pub fn storage_config(&self) -> Result<StorageConfig> {
+        StorageConfig::new(self.backend, Some(&self.path))
+    }
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            host: "0.0.0.0".to_string(),
+            port: 5500,
+            database_path: "./data/objectio.db".to_string(),
+            storage_backend: StorageBackendKind::Filesystem,
+            storage_path: "./data/storage".to_string(),
+            storage_temp_path: None,
+            storage_key_case: KeyCase::Native,
+            storage_key_layout: KeyLayout::Nested,
+            storage_dedup: false,
+            region: "us-east-1".to_string(),
+            service: "s3".to_string(),
+            max_body_size: 5 * 1024 * 1024 * 1024, // 5GB
+            upload_memory_threshold: 8 * 1024 * 1024, // 8MB
+            max_header_size: 16 * 1024, // 16KB
+            request_timeout: 30,
+            header_read_timeout: 30,
+            body_idle_timeout: 30,
+            shutdown_timeout: 30,
+            auth_enabled: false,
+            multipart_upload_max_age: 7 * 24 * 60 * 60, // 7 days
+            multipart_sweep_interval: 60 * 60,
+            max_list_keys: 1000,
+            idempotent_bucket_create: false,
+            default_acl: CannedAcl::Private,
+            virtual_host_domain: None,
+            encoded_slashes: EncodedSlashes::Canonicalize,
+            scrub_max_duration: 5 * 60,
+            slow_request_threshold_ms: 5000,
+            rate_limit_per_second: 0,
+            rate_limit_burst: 0,
+            rate_limit_overrides: Vec::new(),
+            anonymous_rate_limit_per_second: None,
+            anonymous_rate_limit_burst: 0,
+            anonymous_daily_byte_quota: 0,
+            max_concurrent_requests: 0,
+            idempotency_window: 10 * 60,
+            denied_object_reads: DeniedReads::Forbidden,
+            read_only: false,
+            read_only_retry_after: 60,
+            compression_enabled: false,
+            compression_min_size: 1024,
+            replication: None,
+        }
+    }
+}
+
+/// Prefix of the environment variables settings are read from
+const ENV_PREFIX: &str = "OBJECTIO";
+
+/// Unprefixed environment variables read by earlier releases, and the
+/// setting each one sets
+///
+/// A prefixed variable takes precedence over these. `REGION` takes
+/// precedence over `DEFAULT_REGION`, as it always has.
+const LEGACY_ENV_VARS: &[(&str, &str)] = &[
+    ("HOST", "host"),
+    ("PORT", "port"),
+    ("DATABASE_PATH", "database_path"),
+    ("STORAGE_PATH", "storage_path"),
+    ("REGION", "region"),
+    ("DEFAULT_REGION", "region"),
+    ("SIGNING_SERVICE", "service"),
+    ("MAX_BODY_SIZE", "max_body_size"),
+    ("REQUEST_TIMEOUT", "request_timeout"),
+    ("SHUTDOWN_TIMEOUT", "shutdown_timeout"),
+];
+
+impl ServerConfig {
+    /// Load configuration from an optional TOML/YAML file, falling back to
+    /// environment variables and then defaults
+    pub fn load(path: Option<&Path>) -> Result<Self> {
+        Self::load_with_env(path, std::env::vars().collect())
+    }
+
+    /// Load configuration as [`ServerConfig::load`] does, with `env` in
+    /// place of the process environment
+    fn load_with_env(path: Option<&Path>, env: Map<String, String>) -> Result<Self> {
+        let defaults = Config::try_from(&Self::default()).map_err(config_error)?;
+
+        let mut legacy = Map::new();
+        for (var, setting) in LEGACY_ENV_VARS {
+            if let Some(value) = env.get(*var) {
+                legacy.entry(setting.to_string()).or_insert_with(|| value.clone());
+            }
+        }
+
+        let mut builder = Config::builder()
+            .add_source(defaults)
+            .add_source(Environment::default().source(Some(legacy)))
+            .add_source(Environment::with_prefix(ENV_PREFIX).source(Some(env)));
+
+        if let Some(path) = path {
+            builder = builder.add_source(File::from(path));
+        }
+
+        builder
+            .build()
+            .and_then(|config| config.try_deserialize())
+            .map_err(config_error)
+    }
+
+    /// Storage backend configuration, validated for the chosen backend
+    pub fn storage_config(&self) -> Result<StorageConfig> {
+        StorageConfig::new(self.storage_backend, Some(&self.storage_path))
+            .map(|config| {
+                config
+                    .with_temp_path(self.storage_temp_path.clone())
+                    .with_key_case(self.storage_key_case)
+                    .with_key_layout(self.storage_key_layout)
+                    .with_dedup(self.storage_dedup)
+            })
+    }
+
+    /// Global rate limit, applied to anonymous clients and users without an override
+    pub fn rate_limit(&self) -> RateLimit {
+        RateLimit {
+            per_second: self.rate_limit_per_second,
+            burst: self.rate_limit_burst,
+        }
+    }
+
+    /// Rate limit for each anonymous client IP
+    pub fn anonymous_rate_limit(&self) -> RateLimit {
+        match self.anonymous_rate_limit_per_second {
+            Some(per_second) => RateLimit {
+                per_second,
+                burst: self.anonymous_rate_limit_burst,
+            },
+            None => self.rate_limit(),
+        }
+    }
+
+    /// Rate limit for the access keys of `user_id`
+    pub fn rate_limit_for(&self, user_id: &str) -> RateLimit {
+        self.rate_limit_overrides
+            .iter()
+            .find(|o| o.user_id == user_id)
+            .map_or_else(
+                || self.rate_limit(),
+                |o| RateLimit {
+                    per_second: o.per_second,
+                    burst: o.burst,
+                },
+            )
+    }
+
+    /// Load configuration from environment variables and defaults only
+    pub fn from_env() -> Result<Self> {
+        Self::load(None)
+    }
+}
+
+fn config_error(e: ::config::ConfigError) -> ObjectIOError {
+    ObjectIOError::ConfigurationError {
+        message: e.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_load_config_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("objectio.toml");
+        std::fs::write(
+            &path,
+            r#"
+host = "127.0.0.1"
+port = 9000
+database_path = "/var/lib/objectio/meta.db"
+storage_backend = "memory"
+storage_path = "/var/lib/objectio/data"
+storage_temp_path = "/var/tmp/objectio"
+storage_key_case = "preserve"
+storage_key_layout = "hashed"
+storage_dedup = true
+region = "eu-central-1"
+service = "s3"
+max_body_size = 1048576
+upload_memory_threshold = 65536
+max_header_size = 4096
+request_timeout = 60
+header_read_timeout = 10
+body_idle_timeout = 20
+shutdown_timeout = 5
+auth_enabled = true
+multipart_upload_max_age = 86400
+multipart_sweep_interval = 0
+max_list_keys = 250
+idempotent_bucket_create = true
+default_acl = "public-read"
+virtual_host_domain = "s3.example.com"
+encoded_slashes = "reject"
+scrub_max_duration = 30
+slow_request_threshold_ms = 250
+rate_limit_per_second = 100
+rate_limit_burst = 200
+anonymous_rate_limit_per_second = 5
+anonymous_daily_byte_quota = 1073741824
+max_concurrent_requests = 512
+idempotency_window = 60
+denied_object_reads = "not_found"
+read_only = true
+read_only_retry_after = 300
+compression_enabled = true
+compression_min_size = 256
+
+[[rate_limit_overrides]]
+user_id = "BatchJob"
+per_second = 1000
+
+[[rate_limit_overrides]]
+user_id = "ops"
+per_second = 0
+
+[replication]
+backend = "filesystem"
+path = "/mnt/replica"
+cursor_path = "/var/lib/objectio/replication.cursor"
+"#,
+        )
+        .unwrap();
+
+        let config = ServerConfig::load(Some(&path)).unwrap();
+        assert_eq!(config.host, "127.0.0.1");
+        assert_eq!(config.port, 9000);
+        assert_eq!(config.database_path, "/var/lib/objectio/meta.db");
+        assert_eq!(config.storage_backend, StorageBackendKind::Memory);
+        assert_eq!(config.storage_path, "/var/lib/objectio/data");
+        assert_eq!(config.storage_temp_path.as_deref(), Some("/var/tmp/objectio"));
+        assert_eq!(config.storage_key_case, KeyCase::Preserve);
+        assert_eq!(config.storage_key_layout, KeyLayout::Hashed);
+        assert!(config.storage_dedup);
+        assert_eq!(config.region, "eu-central-1");
+        assert_eq!(config.max_body_size, 1048576);
+        assert_eq!(config.upload_memory_threshold, 65536);
+        assert_eq!(config.max_header_size, 4096);
+        assert_eq!(config.request_timeout, 60);
+        assert_eq!(config.header_read_timeout, 10);
+        assert_eq!(config.body_idle_timeout, 20);
+        assert_eq!(config.shutdown_timeout, 5);
+        assert!(config.auth_enabled);
+        assert_eq!(config.multipart_upload_max_age, 86400);
+        assert_eq!(config.multipart_sweep_interval, 0);
+        let replication = config.replication.as_ref().unwrap();
+        assert_eq!(replication.backend, StorageBackendKind::Filesystem);
+        assert_eq!(replication.path, "/mnt/replica");
+        assert_eq!(replication.cursor_path.as_deref(), Some("/var/lib/objectio/replication.cursor"));
+        assert_eq!(replication.interval, 10);
+        assert_eq!(config.max_list_keys, 250);
+        assert!(config.idempotent_bucket_create);
+        assert_eq!(config.default_acl, CannedAcl::PublicRead);
+        assert_eq!(config.virtual_host_domain.as_deref(), Some("s3.example.com"));
+        assert_eq!(config.encoded_slashes, EncodedSlashes::Reject);
+        assert_eq!(config.scrub_max_duration, 30);
+        assert_eq!(config.slow_request_threshold_ms, 250);
+        assert_eq!(config.rate_limit(), RateLimit { per_second: 100, burst: 200 });
+        assert_eq!(config.rate_limit_for("BatchJob"), RateLimit { per_second: 1000, burst: 0 });
+        assert!(config.rate_limit_for("ops").is_unlimited());
+        assert_eq!(config.rate_limit_for("other"), config.rate_limit());
+        assert_eq!(config.anonymous_rate_limit(), RateLimit { per_second: 5, burst: 0 });
+        assert_eq!(config.anonymous_daily_byte_quota, 1073741824);
+        assert_eq!(config.idempotency_window, 60);
+        assert_eq!(config.denied_object_reads, DeniedReads::NotFound);
+        assert_eq!(config.max_concurrent_requests, 512);
+        assert!(config.read_only);
+        assert_eq!(config.read_only_retry_after, 300);
+        assert!(config.compression_enabled);
+        assert_eq!(config.compression_min_size, 256);
+    }
+
+    #[test]
+    fn test_partial_config_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("objectio.toml");
+        std::fs::write(&path, "port = 9000").unwrap();
+
+        let config = ServerConfig::load(Some(&path)).unwrap();
+        assert_eq!(config.port, 9000);
+        assert!(config.rate_limit_overrides.is_empty());
+        assert!(config.replication.is_none());
+        assert_eq!(config.anonymous_rate_limit(), config.rate_limit());
+    }
+
+    #[test]
+    fn test_remote_replication_target() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("objectio.toml");
+        std::fs::write(
+            &path,
+            r#"
+[replication]
+endpoint = "https://backup.example.com"
+access_key = "REPLICA"
+secret_key = "replica-secret"
+"#,
+        )
+        .unwrap();
+
+        let config = ServerConfig::load(Some(&path)).unwrap();
+        let replication = config.replication.as_ref().unwrap();
+        assert_eq!(replication.endpoint.as_deref(), Some("https://backup.example.com"));
+        assert_eq!(replication.access_key, "REPLICA");
+        assert_eq!(replication.secret_key, "replica-secret");
+        assert_eq!(replication.region, "us-east-1");
+    }
+
+    fn env(vars: &[(&str, &str)]) -> Map<String, String> {
+        vars.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+    }
+
+    #[test]
+    fn test_prefixed_environment_variables() {
+        let config = ServerConfig::load_with_env(
+            None,
+            env(&[
+                ("OBJECTIO_STORAGE_PATH", "/srv/objectio"),
+                ("OBJECTIO_PORT", "9000"),
+                ("OBJECTIO_AUTH_ENABLED", "true"),
+                // Unprefixed variables other than the legacy ones are not settings
+                ("MAX_LIST_KEYS", "5"),
+            ]),
+        )
+        .unwrap();
+        assert_eq!(config.storage_path, "/srv/objectio");
+        assert_eq!(config.port, 9000);
+        assert!(config.auth_enabled);
+        assert_eq!(config.max_list_keys, ServerConfig::default().max_list_keys);
+
+        // The config file takes precedence over the environment
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("objectio.toml");
+        std::fs::write(&path, "port = 9001").unwrap();
+        let config = ServerConfig::load_with_env(Some(&path), env(&[("OBJECTIO_PORT", "9000")])).unwrap();
+        assert_eq!(config.port, 9001);
+    }
+
+    #[test]
+    fn test_legacy_environment_variables() {
+        let config = ServerConfig::load_with_env(
+            None,
+            env(&[
+                ("DEFAULT_REGION", "eu-west-1"),
+                ("SIGNING_SERVICE", "objectio"),
+                ("STORAGE_PATH", "/srv/legacy"),
+                ("PORT", "9000"),
+            ]),
+        )
+        .unwrap();
+        assert_eq!(config.region, "eu-west-1");
+        assert_eq!(config.service, "objectio");
+        assert_eq!(config.storage_path, "/srv/legacy");
+        assert_eq!(config.port, 9000);
+
+        // REGION takes precedence over DEFAULT_REGION
+        let config = ServerConfig::load_with_env(
+            None,
+            env(&[("DEFAULT_REGION", "eu-west-1"), ("REGION", "ap-south-1")]),
+        )
+        .unwrap();
+        assert_eq!(config.region, "ap-south-1");
+
+        // Prefixed variables take precedence over legacy ones
+        let config = ServerConfig::load_with_env(
+            None,
+            env(&[
+                ("REGION", "ap-south-1"),
+                ("OBJECTIO_REGION", "us-west-2"),
+                ("SIGNING_SERVICE", "objectio"),
+                ("OBJECTIO_SERVICE", "s3"),
+            ]),
+        )
+        .unwrap();
+        assert_eq!(config.region, "us-west-2");
+        assert_eq!(config.service, "s3");
+    }
+
+    #[test]
+    fn test_invalid_config_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("objectio.toml");
+        std::fs::write(&path, "port = \"not-a-port\"").unwrap();
+
+        assert!(matches!(
+            ServerConfig::load(Some(&path)),
+            Err(ObjectIOError::ConfigurationError { .. })
+        ));
+    }
+}
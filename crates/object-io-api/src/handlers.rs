@@ -1,6 +1,16 @@
 //! API request handlers
 
+pub mod acl;
+pub mod admin;
+pub mod archive;
 pub mod bucket;
+pub mod form_upload;
+pub mod key_patterns;
+pub mod multipart;
 pub mod object;
+pub mod object_lock;
+pub mod presign;
+pub mod rename_prefix;
+pub mod snapshot;
 
 // Placeholder for handler implementations
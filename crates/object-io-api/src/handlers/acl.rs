@@ -0,0 +1,216 @@
+//! Object ACLs (GET and PUT /{bucket}/{key+}?acl)
+//!
+//! An object's owner is its bucket's owner, who always has full control;
+//! the stored ACL holds only the grants beyond that. Reading an ACL takes
+//! `READ_ACP` and replacing it `WRITE_ACP`, unless the caller is the owner
+//! or an admin.
+
+use axum::{
+    body::Body,
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    Extension,
+};
+use object_io_core::{acl_allows, CannedAcl, Grant, ObjectIOError, Permission};
+
+use crate::{
+    auth::AuthContext,
+    handlers::object::{canned_acl, object_error},
+    responses::{
+        to_xml_response,
+        xml::{AccessControlPolicy, Owner},
+    },
+    state::AppState,
+};
+
+/// Largest `AccessControlPolicy` document accepted, in bytes
+const MAX_ACL_SIZE: usize = 64 * 1024;
+
+/// Owner and stored grants of an object, once the caller is found to hold
+/// `permission` on it
+async fn authorized_acl(
+    state: &AppState,
+    bucket: &str,
+    key: &str,
+    auth: &Option<Extension<AuthContext>>,
+    permission: Permission,
+) -> std::result::Result<(String, Vec<Grant>), StatusCode> {
+    let internal_error = |e: ObjectIOError| {
+        eprintln!("Failed to load ACL of '{}/{}': {}", bucket, key, e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    };
+    let owner = state
+        .metadata
+        .get_bucket(bucket)
+        .await
+        .map_err(internal_error)?
+        .ok_or(StatusCode::NOT_FOUND)?
+        .access_control
+        .owner
+        .name;
+    let acl = state
+        .metadata
+        .get_object(bucket, key)
+        .await
+        .map_err(internal_error)?
+        .ok_or(StatusCode::NOT_FOUND)?
+        .acl;
+
+    if let Some(Extension(auth)) = auth {
+        if !auth.is_admin && auth.user_id != owner && !acl_allows(&acl, Some(&auth.user_id), permission) {
+            return Err(StatusCode::FORBIDDEN);
+        }
+    }
+    Ok((owner, acl))
+}
+
+fn acl_owner(id: String) -> Owner {
+    Owner {
+        display_name: id.clone(),
+        id,
+    }
+}
+
+/// Get an object's ACL (GET /{bucket}/{key+}?acl)
+pub async fn get_object_acl(
+    state: &AppState,
+    bucket: &str,
+    key: &str,
+    auth: &Option<Extension<AuthContext>>,
+) -> std::result::Result<Response, StatusCode> {
+    let (owner_id, acl) = authorized_acl(state, bucket, key, auth, Permission::ReadAcp).await?;
+    to_xml_response(&AccessControlPolicy::new(acl_owner(owner_id), &acl))
+}
+
+/// Replace an object's ACL (PUT /{bucket}/{key+}?acl)
+///
+/// Takes an `AccessControlPolicy` document, or a canned ACL in `x-amz-acl`
+/// with an empty body.
+pub async fn put_object_acl(
+    state: &AppState,
+    bucket: &str,
+    key: &str,
+    auth: &Option<Extension<AuthContext>>,
+    headers: &HeaderMap,
+    body: Body,
+) -> std::result::Result<Response, StatusCode> {
+    let (owner_id, _) = authorized_acl(state, bucket, key, auth, Permission::WriteAcp).await?;
+    let body = axum::body::to_bytes(body, MAX_ACL_SIZE)
+        .await
+        .map_err(|_| StatusCode::BAD_REQUEST)?;
+
+    let grants = if body.is_empty() && headers.contains_key(CannedAcl::HEADER) {
+        canned_acl(headers, CannedAcl::Private).map(CannedAcl::grants)
+    } else {
+        let malformed = |message: String| ObjectIOError::MalformedAcl { message };
+        std::str::from_utf8(&body)
+            .map_err(|e| malformed(e.to_string()))
+            .and_then(|xml| quick_xml::de::from_str::<AccessControlPolicy>(xml).map_err(|e| malformed(e.to_string())))
+            .and_then(|policy| {
+                if policy.owner.id != owner_id {
+                    return Err(malformed("The owner of an object cannot be changed".to_string()));
+                }
+                policy.grants()
+            })
+    };
+    let grants = match grants {
+        Ok(grants) => grants,
+        Err(e) => return Ok(object_error(&e, key)),
+    };
+
+    match state.metadata.set_object_acl(bucket, key, &grants).await {
+        Ok(()) => Ok(StatusCode::OK.into_response()),
+        Err(ObjectIOError::ObjectNotFound { .. }) => Err(StatusCode::NOT_FOUND),
+        Err(e) => {
+            eprintln!("Failed to set ACL of '{}/{}': {}", bucket, key, e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{body::to_bytes, http::HeaderValue};
+    use object_io_core::Grantee;
+    use std::collections::HashMap;
+    use uuid::Uuid;
+
+    async fn body_of(response: Response) -> (StatusCode, String) {
+        let status = response.status();
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, String::from_utf8_lossy(&body).into_owned())
+    }
+
+    #[tokio::test]
+    async fn test_object_acl_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let state = AppState::for_test(dir.path()).await;
+        state.metadata.create_bucket("photos", "owner-id").await.unwrap();
+        state
+            .metadata
+            .put_object_metadata("photos", "a.jpg", 3, "image/jpeg", "etag", HashMap::new())
+            .await
+            .unwrap();
+
+        let caller = |user_id: &str| {
+            Some(Extension(AuthContext {
+                access_key: format!("{}-key", user_id),
+                user_id: user_id.to_string(),
+                is_admin: false,
+                permissions: Vec::new(),
+                scope: None,
+            }))
+        };
+        let owner = caller("owner-id");
+        let get = |auth: Option<Extension<AuthContext>>| {
+            let state = state.clone();
+            async move { get_object_acl(&state, "photos", "a.jpg", &auth).await }
+        };
+        let put = |auth: Option<Extension<AuthContext>>, headers: HeaderMap, body: String| {
+            let state = state.clone();
+            async move { put_object_acl(&state, "photos", "a.jpg", &auth, &headers, Body::from(body)).await }
+        };
+
+        // A new object lists only its owner's full control
+        let (status, xml) = body_of(get(owner.clone()).await.unwrap()).await;
+        assert_eq!(status, StatusCode::OK);
+        let policy: AccessControlPolicy = quick_xml::de::from_str(&xml).unwrap();
+        assert_eq!(policy.owner.id, "owner-id");
+        assert!(policy.grants().unwrap().is_empty());
+
+        // Grants written by the owner are read back unchanged
+        let editor = Uuid::new_v4();
+        let acl = vec![
+            Grant::new(Grantee::AllUsers, Permission::Read),
+            Grant::new(Grantee::User(editor), Permission::WriteAcp),
+        ];
+        let policy = AccessControlPolicy::new(acl_owner("owner-id".to_string()), &acl);
+        let document = crate::responses::xml::to_xml_string(&policy).unwrap();
+        let response = put(owner.clone(), HeaderMap::new(), document.clone()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let (_, xml) = body_of(get(owner.clone()).await.unwrap()).await;
+        assert_eq!(xml, document);
+        assert_eq!(state.metadata.get_object("photos", "a.jpg").await.unwrap().unwrap().acl, acl);
+
+        // Changing the ACL takes WRITE_ACP, and reading it READ_ACP
+        let stranger = caller(&Uuid::new_v4().to_string());
+        assert_eq!(put(stranger.clone(), HeaderMap::new(), document.clone()).await.unwrap_err(), StatusCode::FORBIDDEN);
+        assert_eq!(get(stranger).await.unwrap_err(), StatusCode::FORBIDDEN);
+        let editor = caller(&editor.to_string());
+        assert_eq!(get(editor.clone()).await.unwrap_err(), StatusCode::FORBIDDEN);
+        let mut headers = HeaderMap::new();
+        headers.insert(CannedAcl::HEADER, HeaderValue::from_static("private"));
+        let response = put(editor, headers, String::new()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(state.metadata.get_object("photos", "a.jpg").await.unwrap().unwrap().acl.is_empty());
+
+        // Malformed documents and owner changes are refused
+        let (status, xml) = body_of(put(owner.clone(), HeaderMap::new(), "<Nope".to_string()).await.unwrap()).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(xml.contains("<Code>MalformedACLError</Code>"), "{}", xml);
+        let stolen = document.replace("owner-id", "thief-id");
+        let (status, _) = body_of(put(owner, HeaderMap::new(), stolen).await.unwrap()).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+}
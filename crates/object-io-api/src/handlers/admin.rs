@@ -0,0 +1,712 @@
+//! Administrative handlers for users and access keys
+
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::Json,
+    Extension,
+};
+use object_io_core::{AccessKey, AccessKeyScope, AccessKeyStatus, ChangeEvent, ObjectIOError};
+use object_io_storage::scrub::ScrubReport;
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+use crate::{auth::AuthContext, handlers::multipart::check_bucket, state::AppState};
+
+/// Access key information for listing (secret omitted)
+#[derive(Debug, Serialize)]
+pub struct AccessKeyInfo {
+    pub access_key_id: String,
+    pub status: AccessKeyStatus,
+    pub created_at: String,
+    pub last_used: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scope: Option<AccessKeyScope>,
+}
+
+impl From<AccessKey> for AccessKeyInfo {
+    fn from(key: AccessKey) -> Self {
+        Self {
+            access_key_id: key.access_key_id,
+            status: key.status,
+            created_at: key.created_at.to_rfc3339(),
+            last_used: key.last_used.map(|t| t.to_rfc3339()),
+            scope: key.scope,
+        }
+    }
+}
+
+/// List access keys response
+#[derive(Debug, Serialize)]
+pub struct ListAccessKeysResponse {
+    pub user_id: String,
+    pub access_keys: Vec<AccessKeyInfo>,
+}
+
+/// User information for the admin listing
+#[derive(Debug, Serialize)]
+pub struct UserSummary {
+    pub user_id: String,
+    pub is_admin: bool,
+    pub created_at: String,
+    pub last_access: Option<String>,
+    pub access_keys: Vec<AccessKeyInfo>,
+}
+
+/// List users response
+#[derive(Debug, Serialize)]
+pub struct ListUsersResponse {
+    pub users: Vec<UserSummary>,
+}
+
+/// Create access key response (the only time the secret is returned)
+#[derive(Debug, Serialize)]
+pub struct CreateAccessKeyResponse {
+    pub access_key_id: String,
+    pub secret_access_key: String,
+    pub status: AccessKeyStatus,
+    pub created_at: String,
+}
+
+/// Aborted multipart upload information
+#[derive(Debug, Serialize)]
+pub struct AbortedUploadInfo {
+    pub bucket: String,
+    pub key: String,
+    pub upload_id: String,
+    pub initiated: String,
+}
+
+/// Sweep abandoned multipart uploads response
+#[derive(Debug, Serialize)]
+pub struct SweepUploadsResponse {
+    pub aborted: Vec<AbortedUploadInfo>,
+}
+
+/// Bucket information for the admin listing
+#[derive(Debug, Serialize)]
+pub struct BucketSummary {
+    pub name: String,
+    pub owner: String,
+    pub creation_date: String,
+    pub objects_count: u64,
+    pub size_bytes: u64,
+}
+
+/// List all buckets response
+#[derive(Debug, Serialize)]
+pub struct ListAllBucketsResponse {
+    pub buckets: Vec<BucketSummary>,
+}
+
+/// Entries listed under recent activity in the stats response
+const RECENT_ACTIVITY_LIMIT: usize = 10;
+
+/// A recent event shown on the dashboard
+#[derive(Debug, Serialize)]
+pub struct ActivityEntry {
+    pub timestamp: String,
+    pub description: String,
+}
+
+/// Server-wide stats response
+#[derive(Debug, Serialize)]
+pub struct StatsResponse {
+    pub total_buckets: u64,
+    pub total_objects: u64,
+    pub total_size_bytes: u64,
+    pub total_users: u64,
+    /// Space taken by the metadata database, in bytes
+    pub database_size_bytes: u64,
+    pub checked_at: String,
+    /// Bucket creations and user sign-ins, newest first
+    pub recent_activity: Vec<ActivityEntry>,
+}
+
+/// Scrub request query parameters
+#[derive(Debug, Deserialize)]
+pub struct ScrubQuery {
+    pub bucket: String,
+    /// Resume after this key (the `next_marker` of an earlier incomplete scrub)
+    pub marker: Option<String>,
+    /// Time limit in seconds, clamped to the configured maximum
+    #[serde(rename = "max-seconds")]
+    pub max_seconds: Option<u64>,
+}
+
+/// Most change feed events returned in one page
+const CHANGES_PAGE_LIMIT: usize = 1000;
+
+/// Change feed query parameters
+#[derive(Debug, Deserialize)]
+pub struct ChangesQuery {
+    /// Resume after this token (the `next_token` of an earlier page); the
+    /// feed starts from its first event without one
+    pub since: Option<String>,
+    #[serde(rename = "max-events")]
+    pub max_events: Option<usize>,
+}
+
+/// One page of the change feed
+#[derive(Debug, Serialize)]
+pub struct ChangesResponse {
+    /// Events in the order they were made
+    pub events: Vec<ChangeEvent>,
+    /// Pass as `since` to continue after these events; the same token when
+    /// there were none, to poll for new ones
+    pub next_token: String,
+    /// More events follow this page
+    pub is_truncated: bool,
+}
+
+/// Update access key request
+#[derive(Debug, Deserialize)]
+pub struct UpdateAccessKeyRequest {
+    pub status: AccessKeyStatus,
+}
+
+/// Only admins may manage other users' keys; users may manage their own
+///
+/// Access keys are never managed anonymously: with authentication off there
+/// is no caller to check, so every key operation is refused.
+fn authorize(auth: &Option<Extension<AuthContext>>, user_id: &str) -> std::result::Result<(), StatusCode> {
+    match auth {
+        Some(Extension(ctx)) if ctx.is_admin || ctx.user_id == user_id => Ok(()),
+        _ => Err(StatusCode::FORBIDDEN),
+    }
+}
+
+/// Only authenticated admins may change access key scopes
+fn authorize_scope_change(auth: &Option<Extension<AuthContext>>) -> std::result::Result<(), StatusCode> {
+    match auth {
+        Some(Extension(ctx)) if ctx.is_admin => Ok(()),
+        _ => Err(StatusCode::FORBIDDEN),
+    }
+}
+
+/// Only admins may list all users
+pub(crate) fn authorize_admin(auth: &Option<Extension<AuthContext>>) -> std::result::Result<(), StatusCode> {
+    match auth {
+        Some(Extension(ctx)) if !ctx.is_admin => Err(StatusCode::FORBIDDEN),
+        _ => Ok(()),
+    }
+}
+
+/// Admins, and users granted `permission`, may see past their own resources
+fn authorize_permission(auth: &Option<Extension<AuthContext>>, permission: &str) -> std::result::Result<(), StatusCode> {
+    match auth {
+        Some(Extension(ctx)) if !ctx.is_admin && !ctx.permissions.iter().any(|p| p == permission) => {
+            Err(StatusCode::FORBIDDEN)
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Map access key operation errors to status codes
+fn error_status(e: &ObjectIOError) -> StatusCode {
+    match e {
+        ObjectIOError::UserNotFound { .. } | ObjectIOError::AccessKeyNotFound { .. } => StatusCode::NOT_FOUND,
+        ObjectIOError::InvalidRequest { .. } | ObjectIOError::InvalidBucketName { .. } => StatusCode::BAD_REQUEST,
+        _ => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
+
+/// List users handler (GET /_admin/users)
+pub async fn list_users(
+    State(state): State<AppState>,
+    auth: Option<Extension<AuthContext>>,
+) -> std::result::Result<Json<ListUsersResponse>, StatusCode> {
+    authorize_admin(&auth)?;
+
+    let users = state.metadata.list_users().await.map_err(|e| {
+        eprintln!("Failed to list users: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    let mut summaries = Vec::with_capacity(users.len());
+    for user in users {
+        let user_id = user.id.as_ref().and_then(|id| id.as_str()).unwrap_or_default().to_string();
+        let access_keys = state.metadata.list_access_keys(&user_id).await.map_err(|e| {
+            eprintln!("Failed to list access keys for user '{}': {}", user_id, e);
+            error_status(&e)
+        })?;
+
+        summaries.push(UserSummary {
+            user_id,
+            is_admin: user.is_admin,
+            created_at: user.created_at.to_rfc3339(),
+            last_access: user.last_access.map(|t| t.to_rfc3339()),
+            access_keys: access_keys.into_iter().map(AccessKeyInfo::from).collect(),
+        });
+    }
+
+    Ok(Json(ListUsersResponse { users: summaries }))
+}
+
+/// List access keys handler (GET /_admin/users/{user_id}/access-keys)
+pub async fn list_access_keys(
+    Path(user_id): Path<String>,
+    State(state): State<AppState>,
+    auth: Option<Extension<AuthContext>>,
+) -> std::result::Result<Json<ListAccessKeysResponse>, StatusCode> {
+    authorize(&auth, &user_id)?;
+
+    match state.metadata.list_access_keys(&user_id).await {
+        Ok(keys) => Ok(Json(ListAccessKeysResponse {
+            user_id,
+            access_keys: keys.into_iter().map(AccessKeyInfo::from).collect(),
+        })),
+        Err(e) => {
+            eprintln!("Failed to list access keys for user '{}': {}", user_id, e);
+            Err(error_status(&e))
+        }
+    }
+}
+
+/// Create access key handler (POST /_admin/users/{user_id}/access-keys)
+pub async fn create_access_key(
+    Path(user_id): Path<String>,
+    State(state): State<AppState>,
+    auth: Option<Extension<AuthContext>>,
+) -> std::result::Result<(StatusCode, Json<CreateAccessKeyResponse>), StatusCode> {
+    authorize(&auth, &user_id)?;
+
+    match state.metadata.create_access_key(&user_id).await {
+        Ok(key) => Ok((
+            StatusCode::CREATED,
+            Json(CreateAccessKeyResponse {
+                access_key_id: key.access_key_id,
+                secret_access_key: key.secret_access_key,
+                status: key.status,
+                created_at: key.created_at.to_rfc3339(),
+            }),
+        )),
+        Err(e) => {
+            eprintln!("Failed to create access key for user '{}': {}", user_id, e);
+            Err(error_status(&e))
+        }
+    }
+}
+
+/// Update access key status handler (PUT /_admin/users/{user_id}/access-keys/{access_key})
+pub async fn update_access_key(
+    Path((user_id, access_key)): Path<(String, String)>,
+    State(state): State<AppState>,
+    auth: Option<Extension<AuthContext>>,
+    Json(request): Json<UpdateAccessKeyRequest>,
+) -> std::result::Result<Json<AccessKeyInfo>, StatusCode> {
+    authorize(&auth, &user_id)?;
+
+    match state.metadata.set_access_key_status(&user_id, &access_key, request.status).await {
+        Ok(key) => Ok(Json(AccessKeyInfo::from(key))),
+        Err(e) => {
+            eprintln!("Failed to update access key '{}': {}", access_key, e);
+            Err(error_status(&e))
+        }
+    }
+}
+
+/// Set access key scope handler (PUT /_admin/users/{user_id}/access-keys/{access_key}/scope)
+pub async fn set_access_key_scope(
+    Path((user_id, access_key)): Path<(String, String)>,
+    State(state): State<AppState>,
+    auth: Option<Extension<AuthContext>>,
+    Json(scope): Json<AccessKeyScope>,
+) -> std::result::Result<Json<AccessKeyInfo>, StatusCode> {
+    update_access_key_scope(&state, &auth, &user_id, &access_key, Some(scope)).await
+}
+
+/// Remove access key scope handler (DELETE /_admin/users/{user_id}/access-keys/{access_key}/scope)
+pub async fn delete_access_key_scope(
+    Path((user_id, access_key)): Path<(String, String)>,
+    State(state): State<AppState>,
+    auth: Option<Extension<AuthContext>>,
+) -> std::result::Result<Json<AccessKeyInfo>, StatusCode> {
+    update_access_key_scope(&state, &auth, &user_id, &access_key, None).await
+}
+
+/// Scope changes are admin-only so a scoped key cannot widen its own access
+async fn update_access_key_scope(
+    state: &AppState,
+    auth: &Option<Extension<AuthContext>>,
+    user_id: &str,
+    access_key: &str,
+    scope: Option<AccessKeyScope>,
+) -> std::result::Result<Json<AccessKeyInfo>, StatusCode> {
+    authorize_scope_change(auth)?;
+
+    match state.metadata.set_access_key_scope(user_id, access_key, scope).await {
+        Ok(key) => Ok(Json(AccessKeyInfo::from(key))),
+        Err(e) => {
+            eprintln!("Failed to update scope of access key '{}': {}", access_key, e);
+            Err(error_status(&e))
+        }
+    }
+}
+
+/// Delete access key handler (DELETE /_admin/users/{user_id}/access-keys/{access_key})
+pub async fn delete_access_key(
+    Path((user_id, access_key)): Path<(String, String)>,
+    State(state): State<AppState>,
+    auth: Option<Extension<AuthContext>>,
+) -> std::result::Result<StatusCode, StatusCode> {
+    authorize(&auth, &user_id)?;
+
+    match state.metadata.delete_access_key(&user_id, &access_key).await {
+        Ok(()) => Ok(StatusCode::NO_CONTENT),
+        Err(e) => {
+            eprintln!("Failed to delete access key '{}': {}", access_key, e);
+            Err(error_status(&e))
+        }
+    }
+}
+
+/// Read-only mode state, as reported and as set
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ReadOnlyMode {
+    pub enabled: bool,
+}
+
+/// Report whether the server is read-only (GET /_admin/read-only)
+pub async fn get_read_only(
+    State(state): State<AppState>,
+    auth: Option<Extension<AuthContext>>,
+) -> std::result::Result<Json<ReadOnlyMode>, StatusCode> {
+    authorize_admin(&auth)?;
+    Ok(Json(ReadOnlyMode {
+        enabled: state.read_only.load(Ordering::Relaxed),
+    }))
+}
+
+/// Enter or leave read-only mode (PUT /_admin/read-only)
+///
+/// The mode lasts until changed again or the server restarts, when the
+/// configured `read_only` applies.
+pub async fn set_read_only(
+    State(state): State<AppState>,
+    auth: Option<Extension<AuthContext>>,
+    Json(request): Json<ReadOnlyMode>,
+) -> std::result::Result<Json<ReadOnlyMode>, StatusCode> {
+    authorize_admin(&auth)?;
+    state.read_only.store(request.enabled, Ordering::Relaxed);
+    tracing::warn!("Read-only mode {}", if request.enabled { "enabled" } else { "disabled" });
+    Ok(Json(request))
+}
+
+/// Sweep abandoned multipart uploads handler (POST /_admin/multipart/sweep)
+pub async fn sweep_multipart_uploads(
+    State(state): State<AppState>,
+    auth: Option<Extension<AuthContext>>,
+) -> std::result::Result<Json<SweepUploadsResponse>, StatusCode> {
+    authorize_admin(&auth)?;
+
+    match state.sweep_abandoned_uploads(chrono::Utc::now()).await {
+        Ok(aborted) => Ok(Json(SweepUploadsResponse {
+            aborted: aborted
+                .into_iter()
+                .map(|upload| AbortedUploadInfo {
+                    bucket: upload.bucket,
+                    key: upload.key,
+                    upload_id: upload.upload_id,
+                    initiated: upload.initiated.to_rfc3339(),
+                })
+                .collect(),
+        })),
+        Err(e) => {
+            eprintln!("Failed to sweep multipart uploads: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+/// Object changes in order (GET /_admin/changes?since=<token>)
+pub async fn list_changes(
+    State(state): State<AppState>,
+    auth: Option<Extension<AuthContext>>,
+    Query(params): Query<ChangesQuery>,
+) -> std::result::Result<Json<ChangesResponse>, StatusCode> {
+    authorize_admin(&auth)?;
+
+    // The token is the sequence number of the last event seen
+    let after = match params.since.as_deref() {
+        Some(token) => token.parse::<u64>().map_err(|_| StatusCode::BAD_REQUEST)?,
+        None => 0,
+    };
+    let limit = params.max_events.unwrap_or(CHANGES_PAGE_LIMIT).clamp(1, CHANGES_PAGE_LIMIT);
+
+    match state.metadata.list_changes(after, limit + 1).await {
+        Ok(mut events) => {
+            let is_truncated = events.len() > limit;
+            events.truncate(limit);
+            let next_token = events.last().map_or(after, |event| event.sequence).to_string();
+            Ok(Json(ChangesResponse {
+                events,
+                next_token,
+                is_truncated,
+            }))
+        }
+        Err(e) => {
+            eprintln!("Failed to list changes: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+/// Verify stored object data against recorded ETags (POST /_admin/scrub?bucket=X)
+pub async fn scrub_bucket(
+    State(state): State<AppState>,
+    auth: Option<Extension<AuthContext>>,
+    Query(params): Query<ScrubQuery>,
+) -> std::result::Result<Json<ScrubReport>, StatusCode> {
+    authorize_admin(&auth)?;
+    check_bucket(&state, &params.bucket).await?;
+
+    let limit = params
+        .max_seconds
+        .map_or(state.config.scrub_max_duration, |s| s.min(state.config.scrub_max_duration));
+    let deadline = tokio::time::Instant::now() + Duration::from_secs(limit);
+
+    match state.scrub_bucket(&params.bucket, params.marker, deadline).await {
+        Ok(report) => {
+            if !report.is_clean() {
+                eprintln!(
+                    "Scrub of bucket '{}' found {} corrupted and {} missing objects",
+                    report.bucket,
+                    report.corrupted.len(),
+                    report.missing.len()
+                );
+            }
+            Ok(Json(report))
+        }
+        Err(e) => {
+            eprintln!("Failed to scrub bucket '{}': {}", params.bucket, e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+/// List every bucket, whatever its owner (GET /_admin/buckets)
+pub async fn list_all_buckets(
+    State(state): State<AppState>,
+    auth: Option<Extension<AuthContext>>,
+) -> std::result::Result<Json<ListAllBucketsResponse>, StatusCode> {
+    authorize_permission(&auth, "list_all_buckets")?;
+
+    match state.metadata.list_all_buckets().await {
+        Ok(buckets) => Ok(Json(ListAllBucketsResponse {
+            buckets: buckets
+                .into_iter()
+                .map(|bucket| BucketSummary {
+                    name: bucket.name,
+                    owner: bucket.access_control.owner.name,
+                    creation_date: bucket.created_at.to_rfc3339(),
+                    objects_count: bucket.object_count,
+                    size_bytes: bucket.total_size,
+                })
+                .collect(),
+        })),
+        Err(e) => {
+            eprintln!("Failed to list buckets: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+/// Server-wide stats handler (GET /_admin/stats)
+pub async fn stats(
+    State(state): State<AppState>,
+    auth: Option<Extension<AuthContext>>,
+) -> std::result::Result<Json<StatsResponse>, StatusCode> {
+    authorize_admin(&auth)?;
+
+    let internal_error = |e: ObjectIOError| {
+        eprintln!("Failed to collect stats: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    };
+    let stats = state.metadata.stats().await.map_err(internal_error)?;
+    let buckets = state.metadata.list_all_buckets().await.map_err(internal_error)?;
+    let users = state.metadata.list_users().await.map_err(internal_error)?;
+
+    let mut activity: Vec<_> = buckets
+        .iter()
+        .map(|bucket| (bucket.created_at, format!("Bucket '{}' created", bucket.name)))
+        .chain(users.iter().filter_map(|user| {
+            let user_id = user.id.as_ref().and_then(|id| id.as_str()).unwrap_or_default();
+            user.last_access
+                .map(|at| (at, format!("User '{}' authenticated", user_id)))
+        }))
+        .collect();
+    activity.sort_by_key(|(at, _)| std::cmp::Reverse(*at));
+    activity.truncate(RECENT_ACTIVITY_LIMIT);
+
+    Ok(Json(StatsResponse {
+        total_buckets: stats.bucket_count,
+        total_objects: stats.object_count,
+        total_size_bytes: buckets.iter().map(|bucket| bucket.total_size).sum(),
+        total_users: stats.user_count,
+        database_size_bytes: stats.size_on_disk,
+        checked_at: stats.checked_at.to_rfc3339(),
+        recent_activity: activity
+            .into_iter()
+            .map(|(at, description)| ActivityEntry {
+                timestamp: at.to_rfc3339(),
+                description,
+            })
+            .collect(),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn caller(is_admin: bool) -> Option<Extension<AuthContext>> {
+        Some(Extension(AuthContext {
+            access_key: "key".to_string(),
+            user_id: "user".to_string(),
+            is_admin,
+            permissions: Vec::new(),
+            scope: None,
+        }))
+    }
+
+    #[tokio::test]
+    async fn test_access_keys_require_authentication() {
+        let dir = tempfile::tempdir().unwrap();
+        let state = AppState::for_test(dir.path()).await;
+        state.metadata.create_user("AKUSER", "secret", "User").await.unwrap();
+        let user_id = state.metadata.get_user_by_access_key("AKUSER").await.unwrap().unwrap().id.unwrap();
+        let user_id = user_id.as_str().unwrap().to_string();
+        let path = || Path(user_id.clone());
+        let key_path = || Path((user_id.clone(), "AKUSER".to_string()));
+        let disable = || {
+            Json(UpdateAccessKeyRequest {
+                status: AccessKeyStatus::Inactive,
+            })
+        };
+        let scope = || {
+            Json(AccessKeyScope {
+                bucket: "photos".to_string(),
+                prefix: String::new(),
+                read_only: true,
+            })
+        };
+
+        // Without authentication nothing about keys can be read or changed
+        let forbidden = Err(StatusCode::FORBIDDEN);
+        assert_eq!(list_access_keys(path(), State(state.clone()), None).await.map(|_| ()), forbidden);
+        assert_eq!(create_access_key(path(), State(state.clone()), None).await.map(|_| ()), forbidden);
+        assert_eq!(update_access_key(key_path(), State(state.clone()), None, disable()).await.map(|_| ()), forbidden);
+        assert_eq!(set_access_key_scope(key_path(), State(state.clone()), None, scope()).await.map(|_| ()), forbidden);
+        assert_eq!(delete_access_key_scope(key_path(), State(state.clone()), None).await.map(|_| ()), forbidden);
+        assert_eq!(delete_access_key(key_path(), State(state.clone()), None).await.map(|_| ()), forbidden);
+        assert_eq!(state.metadata.list_access_keys(&user_id).await.unwrap().len(), 1);
+
+        // Users manage their own keys, but only admins change scopes
+        let mut own = caller(false);
+        if let Some(Extension(ctx)) = &mut own {
+            ctx.user_id = user_id.clone();
+        }
+        let (status, _) = create_access_key(path(), State(state.clone()), own.clone()).await.unwrap();
+        assert_eq!(status, StatusCode::CREATED);
+        let result = set_access_key_scope(key_path(), State(state.clone()), own, scope()).await;
+        assert_eq!(result.map(|_| ()), forbidden);
+        assert_eq!(list_access_keys(path(), State(state.clone()), caller(false)).await.map(|_| ()), forbidden);
+        let Json(listed) = list_access_keys(path(), State(state.clone()), caller(true)).await.unwrap();
+        assert_eq!(listed.access_keys.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_list_all_buckets() {
+        let dir = tempfile::tempdir().unwrap();
+        let state = AppState::for_test(dir.path()).await;
+        state.metadata.create_bucket("photos", "alice").await.unwrap();
+        state.metadata.create_bucket("docs", "bob").await.unwrap();
+
+        let result = list_all_buckets(State(state.clone()), caller(false)).await;
+        assert_eq!(result.unwrap_err(), StatusCode::FORBIDDEN);
+
+        let Json(response) = list_all_buckets(State(state.clone()), caller(true)).await.unwrap();
+        let mut owners: Vec<_> = response.buckets.iter().map(|b| (b.name.as_str(), b.owner.as_str())).collect();
+        owners.sort();
+        assert_eq!(owners, [("docs", "bob"), ("photos", "alice")]);
+
+        // The permission grants the listing without admin rights
+        let mut granted = caller(false);
+        if let Some(Extension(ctx)) = &mut granted {
+            ctx.permissions.push("list_all_buckets".to_string());
+        }
+        let Json(response) = list_all_buckets(State(state), granted).await.unwrap();
+        assert_eq!(response.buckets.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_stats() {
+        let dir = tempfile::tempdir().unwrap();
+        let state = AppState::for_test(dir.path()).await;
+        state.metadata.create_bucket("photos", "owner").await.unwrap();
+        state.metadata.create_bucket("docs", "owner").await.unwrap();
+        for (key, size) in [("a.jpg", 100), ("b.jpg", 20)] {
+            state
+                .metadata
+                .put_object_metadata("photos", key, size, "image/jpeg", "etag", HashMap::new())
+                .await
+                .unwrap();
+        }
+
+        let result = stats(State(state.clone()), caller(false)).await;
+        assert_eq!(result.unwrap_err(), StatusCode::FORBIDDEN);
+
+        let Json(response) = stats(State(state), caller(true)).await.unwrap();
+        assert_eq!(response.total_buckets, 2);
+        assert_eq!(response.total_objects, 2);
+        assert_eq!(response.total_size_bytes, 120);
+        assert_eq!(response.recent_activity.len(), 2);
+        assert!(response.recent_activity[0].timestamp >= response.recent_activity[1].timestamp);
+    }
+
+    #[tokio::test]
+    async fn test_list_changes() {
+        let dir = tempfile::tempdir().unwrap();
+        let state = AppState::for_test(dir.path()).await;
+        state.metadata.create_bucket("photos", "owner").await.unwrap();
+        for key in ["a.jpg", "b.jpg", "c.jpg"] {
+            state
+                .metadata
+                .put_object_metadata("photos", key, 1, "image/jpeg", "etag", HashMap::new())
+                .await
+                .unwrap();
+        }
+        state.metadata.delete_object("photos", "a.jpg").await.unwrap();
+
+        let query = |since: Option<&str>, max_events: Option<usize>| {
+            Query(ChangesQuery {
+                since: since.map(str::to_string),
+                max_events,
+            })
+        };
+        let result = list_changes(State(state.clone()), caller(false), query(None, None)).await;
+        assert_eq!(result.unwrap_err(), StatusCode::FORBIDDEN);
+        let result = list_changes(State(state.clone()), caller(true), query(Some("latest"), None)).await;
+        assert_eq!(result.unwrap_err(), StatusCode::BAD_REQUEST);
+
+        // Page through the feed, resuming from each token
+        let Json(page) = list_changes(State(state.clone()), caller(true), query(None, Some(3))).await.unwrap();
+        assert!(page.is_truncated);
+        let keys: Vec<_> = page.events.iter().map(|event| event.key.as_str()).collect();
+        assert_eq!(keys, ["a.jpg", "b.jpg", "c.jpg"]);
+        let since = Some(page.next_token.as_str());
+        let Json(page) = list_changes(State(state.clone()), caller(true), query(since, Some(3))).await.unwrap();
+        assert!(!page.is_truncated);
+        assert_eq!(page.events.len(), 1);
+        assert_eq!(page.events[0].key, "a.jpg");
+        assert_eq!(page.events[0].operation, object_io_core::ChangeOperation::Delete);
+
+        // Caught up: no events, and the same token to poll with
+        let since = page.next_token.clone();
+        let Json(page) = list_changes(State(state), caller(true), query(Some(&since), None)).await.unwrap();
+        assert!(page.events.is_empty());
+        assert_eq!(page.next_token, since);
+    }
+}
@@ -0,0 +1,547 @@
+//! Bucket export and import as streamed tar or zip archives
+
+use async_zip::{base::write::ZipFileWriter, Compression, ZipDateTime, ZipEntryBuilder};
+use axum::{
+    body::Body,
+    http::{header, HeaderMap, StatusCode},
+    response::{Json, Response},
+};
+use futures::{stream, StreamExt};
+use object_io_core::{ListObjectsRequest, Object, ObjectIOError, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt, DuplexStream};
+use tokio_util::{compat::TokioAsyncReadCompatExt, io::ReaderStream};
+
+use crate::{
+    handlers::{key_patterns, multipart, object},
+    state::AppState,
+};
+
+/// Name of the archive entry describing every exported object; written last
+pub const MANIFEST_NAME: &str = ".objectio-manifest.json";
+
+/// Bytes buffered between the archive writer and the response body
+const PIPE_CAPACITY: usize = 64 * 1024;
+
+/// Archive formats supported by bucket export
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveFormat {
+    Tar,
+    Zip,
+}
+
+impl ArchiveFormat {
+    /// Parse an `export` or `import` query value
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "tar" => Some(Self::Tar),
+            "zip" => Some(Self::Zip),
+            _ => None,
+        }
+    }
+
+    fn content_type(self) -> &'static str {
+        match self {
+            Self::Tar => "application/x-tar",
+            Self::Zip => "application/zip",
+        }
+    }
+
+    fn extension(self) -> &'static str {
+        match self {
+            Self::Tar => "tar",
+            Self::Zip => "zip",
+        }
+    }
+}
+
+/// Manifest record for one exported object
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ManifestEntry {
+    pub key: String,
+    pub size: u64,
+    pub etag: String,
+    pub content_type: String,
+    pub last_modified: String,
+    #[serde(default)]
+    pub metadata: HashMap<String, String>,
+}
+
+impl From<&Object> for ManifestEntry {
+    fn from(object: &Object) -> Self {
+        // Standard headers travel with the custom metadata, as on upload
+        let mut metadata = object.metadata.clone();
+        metadata.extend(object.headers().map(|(name, value)| (name.to_string(), value.to_string())));
+
+        Self {
+            key: object.key.clone(),
+            size: object.size,
+            etag: object.etag.clone(),
+            content_type: object.content_type.clone(),
+            last_modified: object.last_modified.to_rfc3339(),
+            metadata,
+        }
+    }
+}
+
+/// Stream every object in `bucket` as an archive, keyed by object key
+///
+/// The archive is written by a background task into a bounded pipe, so only
+/// `PIPE_CAPACITY` bytes are held in memory at a time. Object metadata is
+/// collected into a JSON manifest appended as the final entry. If the export
+/// fails part way, the body ends with an error so the client sees a broken
+/// transfer rather than a short archive.
+pub async fn export_bucket(
+    state: &AppState,
+    bucket: String,
+    format: ArchiveFormat,
+) -> std::result::Result<Response, StatusCode> {
+    multipart::check_bucket(state, &bucket).await?;
+
+    let (writer, reader) = tokio::io::duplex(PIPE_CAPACITY);
+    let (done_tx, done_rx) = tokio::sync::oneshot::channel();
+
+    let task_state = state.clone();
+    let task_bucket = bucket.clone();
+    tokio::spawn(async move {
+        let result = match format {
+            ArchiveFormat::Tar => write_tar(&task_state, &task_bucket, writer).await,
+            ArchiveFormat::Zip => write_zip(&task_state, &task_bucket, writer).await,
+        };
+        if let Err(e) = &result {
+            eprintln!("Failed to export bucket '{}': {}", task_bucket, e);
+        }
+        let _ = done_tx.send(result);
+    });
+
+    let trailer = stream::once(done_rx).filter_map(|result| async move {
+        match result {
+            Ok(Ok(())) => None,
+            Ok(Err(e)) => Some(Err(std::io::Error::other(e.to_string()))),
+            Err(_) => Some(Err(std::io::Error::other("export task ended unexpectedly"))),
+        }
+    });
+    let body = Body::from_stream(ReaderStream::new(reader).chain(trailer));
+
+    Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, format.content_type())
+        .header(
+            header::CONTENT_DISPOSITION,
+            format!("attachment; filename=\"{}.{}\"", bucket, format.extension()),
+        )
+        .body(body)
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+/// Visit every object in a bucket in key order, paging through the listing
+struct ObjectWalker<'a> {
+    state: &'a AppState,
+    request: ListObjectsRequest,
+    page: std::vec::IntoIter<String>,
+    done: bool,
+}
+
+impl<'a> ObjectWalker<'a> {
+    fn new(state: &'a AppState, bucket: &str) -> Self {
+        Self {
+            state,
+            request: ListObjectsRequest::new(bucket),
+            page: Vec::new().into_iter(),
+            done: false,
+        }
+    }
+
+    /// Next object and a reader over its data; objects deleted while the
+    /// export is running are skipped
+    async fn next(&mut self) -> Result<Option<(Object, Box<dyn AsyncRead + Send + Unpin>)>> {
+        loop {
+            let Some(key) = self.page.next() else {
+                if self.done {
+                    return Ok(None);
+                }
+                let page = self.state.metadata.list_objects(&self.request).await?;
+                self.done = !page.is_truncated;
+                self.request.marker = page.next_marker;
+                self.page = page.objects.into_iter().map(|o| o.key).collect::<Vec<_>>().into_iter();
+                continue;
+            };
+
+            let bucket = &self.request.bucket;
+            let Some(object) = self.state.metadata.get_object(bucket, &key).await? else {
+                continue;
+            };
+            match self.state.storage.get_object_range(bucket, &key, 0, object.size).await {
+                Ok(reader) => return Ok(Some((object, reader))),
+                Err(ObjectIOError::ObjectNotFound { .. }) => continue,
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+async fn write_tar(state: &AppState, bucket: &str, writer: DuplexStream) -> Result<()> {
+    let mut builder = tokio_tar::Builder::new(writer);
+    let mut manifest = Vec::new();
+
+    let mut objects = ObjectWalker::new(state, bucket);
+    while let Some((object, reader)) = objects.next().await? {
+        let mut header = tar_header(object.size, object.last_modified.timestamp());
+        builder.append_data(&mut header, &object.key, reader).await?;
+        manifest.push(ManifestEntry::from(&object));
+    }
+
+    let manifest = serde_json::to_vec_pretty(&manifest)?;
+    let mut header = tar_header(manifest.len() as u64, chrono::Utc::now().timestamp());
+    builder.append_data(&mut header, MANIFEST_NAME, manifest.as_slice()).await?;
+
+    builder.into_inner().await?.shutdown().await?;
+    Ok(())
+}
+
+fn tar_header(size: u64, mtime: i64) -> tokio_tar::Header {
+    let mut header = tokio_tar::Header::new_gnu();
+    header.set_entry_type(tokio_tar::EntryType::Regular);
+    header.set_size(size);
+    header.set_mode(0o644);
+    header.set_mtime(mtime.max(0) as u64);
+    header
+}
+
+async fn write_zip<W: AsyncWrite + Unpin>(state: &AppState, bucket: &str, writer: W) -> Result<()> {
+    let mut zip = ZipFileWriter::with_tokio(writer);
+    let mut manifest = Vec::new();
+
+    let mut objects = ObjectWalker::new(state, bucket);
+    while let Some((object, reader)) = objects.next().await? {
+        let entry = ZipEntryBuilder::new(object.key.clone().into(), Compression::Stored)
+            .last_modification_date(ZipDateTime::from(object.last_modified))
+            .unix_permissions(0o644);
+        let mut entry_writer = zip.write_entry_stream(entry).await.map_err(zip_error)?;
+        futures::io::copy(&mut reader.compat(), &mut entry_writer).await?;
+        entry_writer.close().await.map_err(zip_error)?;
+        manifest.push(ManifestEntry::from(&object));
+    }
+
+    let manifest = serde_json::to_vec_pretty(&manifest)?;
+    let entry = ZipEntryBuilder::new(MANIFEST_NAME.into(), Compression::Stored)
+        .last_modification_date(ZipDateTime::from(chrono::Utc::now()))
+        .unix_permissions(0o644);
+    zip.write_entry_whole(entry, &manifest).await.map_err(zip_error)?;
+
+    zip.close().await.map_err(zip_error)?.into_inner().shutdown().await?;
+    Ok(())
+}
+
+/// Imported archive entry
+#[derive(Debug, Serialize)]
+pub struct ImportedEntry {
+    pub key: String,
+    pub size: u64,
+    pub etag: String,
+}
+
+/// Archive entry that could not be imported
+#[derive(Debug, Serialize)]
+pub struct FailedEntry {
+    pub path: String,
+    pub error: String,
+}
+
+/// Bulk import summary
+#[derive(Debug, Default, Serialize)]
+pub struct ImportSummary {
+    pub imported: Vec<ImportedEntry>,
+    pub failed: Vec<FailedEntry>,
+}
+
+impl ImportSummary {
+    fn fail(&mut self, path: impl Into<String>, error: impl ToString) {
+        self.failed.push(FailedEntry {
+            path: path.into(),
+            error: error.to_string(),
+        });
+    }
+}
+
+/// Create one object per regular file in an uploaded tar archive
+///
+/// Each entry is streamed to storage through the same path as a single
+/// object PUT, keyed by its path within the archive. Entries with unsafe or
+/// invalid keys, or keys the bucket's key patterns refuse, are reported as
+/// failures and skipped. A manifest written by
+/// `export_bucket` restores the exported content types and metadata.
+pub async fn import_bucket(
+    state: &AppState,
+    bucket: &str,
+    format: ArchiveFormat,
+    body: Body,
+) -> std::result::Result<Json<ImportSummary>, StatusCode> {
+    multipart::check_bucket(state, bucket).await?;
+    let patterns = key_patterns::bucket_key_patterns(state, bucket).await.map_err(|e| {
+        eprintln!("Failed to get key patterns of '{}': {}", bucket, e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    // Zip central directories sit at the end of the archive, so zip files
+    // cannot be imported as they stream in
+    if format != ArchiveFormat::Tar {
+        return Err(StatusCode::NOT_IMPLEMENTED);
+    }
+
+    let mut summary = ImportSummary::default();
+    let mut manifest: Option<Vec<ManifestEntry>> = None;
+
+    let mut archive = tokio_tar::Archive::new(object::body_reader(body));
+    let mut entries = archive.entries().map_err(|_| StatusCode::BAD_REQUEST)?;
+    while let Some(entry) = entries.next().await {
+        let mut entry = match entry {
+            Ok(entry) => entry,
+            Err(e) => {
+                // The archive cannot be read past a corrupt header
+                summary.fail("", format!("Malformed archive: {}", e));
+                break;
+            }
+        };
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+
+        let path = String::from_utf8_lossy(&entry.path_bytes()).into_owned();
+        if path == MANIFEST_NAME {
+            let mut data = Vec::new();
+            match tokio::io::AsyncReadExt::read_to_end(&mut entry, &mut data).await {
+                Ok(_) => match serde_json::from_slice(&data) {
+                    Ok(entries) => manifest = Some(entries),
+                    Err(e) => summary.fail(path, format!("Invalid manifest: {}", e)),
+                },
+                Err(e) => summary.fail(path, e),
+            }
+            continue;
+        }
+
+        let key = match archive_key(&path).and_then(|key| patterns.check(&key).map(|_| key)) {
+            Ok(key) => key,
+            Err(e) => {
+                summary.fail(path, e);
+                continue;
+            }
+        };
+
+        let metadata = match object::object_metadata(&key, &HeaderMap::new(), state.config.default_acl) {
+            Ok(metadata) => metadata,
+            Err(e) => {
+                summary.fail(path, e);
+                continue;
+            }
+        };
+        match object::store_object(state, bucket, &key, Box::new(entry), metadata).await {
+            Ok(stored) => summary.imported.push(ImportedEntry {
+                key,
+                size: stored.size,
+                etag: stored.etag,
+            }),
+            Err(e) => {
+                eprintln!("Failed to import '{}' into bucket '{}': {}", key, bucket, e);
+                summary.fail(path, e);
+            }
+        }
+    }
+
+    if let Some(manifest) = manifest {
+        apply_manifest(state, bucket, &manifest, &mut summary).await;
+    }
+
+    Ok(Json(summary))
+}
+
+/// Restore content types and metadata recorded by an export
+async fn apply_manifest(state: &AppState, bucket: &str, manifest: &[ManifestEntry], summary: &mut ImportSummary) {
+    for entry in manifest {
+        if !summary.imported.iter().any(|imported| imported.key == entry.key) {
+            continue;
+        }
+        let result = state
+            .metadata
+            .update_object_metadata(bucket, &entry.key, &entry.content_type, entry.metadata.clone())
+            .await;
+        if let Err(e) = result {
+            summary.fail(&entry.key, format!("Failed to restore metadata: {}", e));
+        }
+    }
+}
+
+/// Object key for an archive entry path
+///
+/// Leading `./` segments are dropped; absolute paths and `..` segments are
+/// rejected so an archive cannot name keys outside the bucket.
+fn archive_key(path: &str) -> Result<String> {
+    let mut key = path;
+    while let Some(rest) = key.strip_prefix("./") {
+        key = rest;
+    }
+    if key.starts_with('/') || key.split('/').any(|segment| segment == "..") {
+        return Err(ObjectIOError::InvalidObjectKey { key: path.to_string() });
+    }
+    object_io_core::validate_object_key(key)?;
+    Ok(key.to_string())
+}
+
+fn zip_error(e: async_zip::error::ZipError) -> ObjectIOError {
+    ObjectIOError::StorageError {
+        message: format!("Failed to write zip archive: {}", e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::to_bytes;
+    use std::io::Cursor;
+    use tokio::io::AsyncReadExt;
+
+    async fn put(state: &AppState, bucket: &str, key: &str, data: &[u8]) {
+        state
+            .storage
+            .put_object(bucket, key, Box::new(Cursor::new(data.to_vec())), HashMap::new())
+            .await
+            .unwrap();
+        let metadata = HashMap::from([("x-amz-meta-owner".to_string(), "ops".to_string())]);
+        state
+            .metadata
+            .put_object_metadata(bucket, key, data.len() as u64, "text/plain", "etag", metadata)
+            .await
+            .unwrap();
+    }
+
+    async fn export(state: &AppState, bucket: &str, format: ArchiveFormat) -> Vec<u8> {
+        let response = export_bucket(state, bucket.to_string(), format).await.unwrap();
+        to_bytes(response.into_body(), usize::MAX).await.unwrap().to_vec()
+    }
+
+    #[tokio::test]
+    async fn test_export_tar() {
+        let dir = tempfile::tempdir().unwrap();
+        let state = AppState::for_test(dir.path()).await;
+
+        assert_eq!(
+            export_bucket(&state, "missing".to_string(), ArchiveFormat::Tar).await.err(),
+            Some(StatusCode::NOT_FOUND)
+        );
+
+        state.metadata.create_bucket("backup", "owner").await.unwrap();
+        put(&state, "backup", "a.txt", b"alpha").await;
+        put(&state, "backup", "b.txt", &vec![7u8; 70_000]).await;
+
+        let archive = export(&state, "backup", ArchiveFormat::Tar).await;
+        let mut archive = tokio_tar::Archive::new(archive.as_slice());
+        let mut entries = archive.entries().unwrap();
+        let mut contents = Vec::new();
+        while let Some(entry) = entries.next().await {
+            let mut entry = entry.unwrap();
+            let path = entry.path().unwrap().to_string_lossy().into_owned();
+            let mut data = Vec::new();
+            entry.read_to_end(&mut data).await.unwrap();
+            contents.push((path, data));
+        }
+
+        assert_eq!(contents.len(), 3);
+        assert_eq!(contents[0], ("a.txt".to_string(), b"alpha".to_vec()));
+        assert_eq!(contents[1].1.len(), 70_000);
+        assert_eq!(contents[2].0, MANIFEST_NAME);
+        let manifest: Vec<ManifestEntry> = serde_json::from_slice(&contents[2].1).unwrap();
+        assert_eq!(manifest.len(), 2);
+        assert_eq!(manifest[0].content_type, "text/plain");
+        assert_eq!(manifest[1].metadata["x-amz-meta-owner"], "ops");
+    }
+
+    #[tokio::test]
+    async fn test_export_zip() {
+        let dir = tempfile::tempdir().unwrap();
+        let state = AppState::for_test(dir.path()).await;
+
+        state.metadata.create_bucket("backup", "owner").await.unwrap();
+        put(&state, "backup", "a.txt", b"alpha").await;
+
+        let archive = export(&state, "backup", ArchiveFormat::Zip).await;
+        let zip = async_zip::base::read::mem::ZipFileReader::new(archive).await.unwrap();
+        let names: Vec<_> = zip
+            .file()
+            .entries()
+            .iter()
+            .map(|entry| entry.filename().as_str().unwrap().to_string())
+            .collect();
+        assert_eq!(names, ["a.txt", MANIFEST_NAME]);
+
+        let mut data = Vec::new();
+        let mut reader = zip.reader_with_entry(0).await.unwrap();
+        futures::AsyncReadExt::read_to_end(&mut reader, &mut data).await.unwrap();
+        assert_eq!(data, b"alpha");
+    }
+
+    fn raw_tar_entry(path: &str, data: &[u8]) -> Vec<u8> {
+        // Bypasses the builder's path checks so unsafe names can be tested
+        let mut header = tar_header(data.len() as u64, 0);
+        header.as_gnu_mut().unwrap().name[..path.len()].copy_from_slice(path.as_bytes());
+        header.set_cksum();
+        let mut entry = header.as_bytes().to_vec();
+        entry.extend_from_slice(data);
+        entry.resize(entry.len().div_ceil(512) * 512, 0);
+        entry
+    }
+
+    #[tokio::test]
+    async fn test_import_tar() {
+        let dir = tempfile::tempdir().unwrap();
+        let state = AppState::for_test(dir.path()).await;
+        state.metadata.create_bucket("dest", "owner").await.unwrap();
+
+        let mut archive = Vec::new();
+        archive.extend(raw_tar_entry("./docs/readme.txt", b"# hello"));
+        archive.extend(raw_tar_entry("../escape.txt", b"nope"));
+        archive.extend(raw_tar_entry("/etc/passwd", b"nope"));
+        archive.extend(raw_tar_entry("data.bin", &[1, 2, 3]));
+        archive.extend([0u8; 1024]);
+
+        let Json(summary) = import_bucket(&state, "dest", ArchiveFormat::Tar, Body::from(archive)).await.unwrap();
+        let keys: Vec<_> = summary.imported.iter().map(|entry| entry.key.as_str()).collect();
+        assert_eq!(keys, ["docs/readme.txt", "data.bin"]);
+        assert_eq!(summary.imported[1].size, 3);
+        let failed: Vec<_> = summary.failed.iter().map(|entry| entry.path.as_str()).collect();
+        assert_eq!(failed, ["../escape.txt", "/etc/passwd"]);
+
+        let object = state.metadata.get_object("dest", "docs/readme.txt").await.unwrap().unwrap();
+        assert_eq!(object.size, 7);
+        assert_eq!(object.content_type, "text/plain");
+
+        assert_eq!(
+            import_bucket(&state, "dest", ArchiveFormat::Zip, Body::empty()).await.err(),
+            Some(StatusCode::NOT_IMPLEMENTED)
+        );
+        assert_eq!(
+            import_bucket(&state, "missing", ArchiveFormat::Tar, Body::empty()).await.err(),
+            Some(StatusCode::NOT_FOUND)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_export_import_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let state = AppState::for_test(dir.path()).await;
+        state.metadata.create_bucket("source", "owner").await.unwrap();
+        state.metadata.create_bucket("copy", "owner").await.unwrap();
+        put(&state, "source", "a.txt", b"alpha").await;
+
+        let archive = export(&state, "source", ArchiveFormat::Tar).await;
+        let Json(summary) = import_bucket(&state, "copy", ArchiveFormat::Tar, Body::from(archive)).await.unwrap();
+        assert_eq!(summary.imported.len(), 1);
+        assert!(summary.failed.is_empty());
+
+        // The manifest restores metadata rather than being stored as an object
+        let object = state.metadata.get_object("copy", "a.txt").await.unwrap().unwrap();
+        assert_eq!(object.content_type, "text/plain");
+        assert_eq!(object.metadata["x-amz-meta-owner"], "ops");
+        assert!(state.metadata.get_object("copy", MANIFEST_NAME).await.unwrap().is_none());
+    }
+}
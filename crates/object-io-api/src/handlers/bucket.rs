@@ -1,27 +1,50 @@
 //! Bucket operation handlers
 
 use axum::{
-    extract::{Path, Query, State},
-    http::StatusCode,
-    response::Json,
+    extract::{FromRequest, Path, Query, Request, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Json, Response},
     Extension,
 };
+use object_io_core::policy::{validate_policy, PolicyIssue};
+use object_io_core::{ContinuationToken, ListObjectsRequest, ObjectIOError};
 use serde::{Deserialize, Serialize};
-use std::{collections::HashMap, sync::Arc};
-use crate::state::AppState;
+use std::collections::HashMap;
+use crate::{
+    auth::AuthContext,
+    handlers::{admin, archive, form_upload, key_patterns, multipart, object, object_lock, rename_prefix, snapshot},
+    responses::{
+        to_xml_response,
+        xml::{
+            BucketEntry, CommonPrefix, ErrorDocument, ListAllMyBucketsResult, ListBucketResult, ListBucketResultV2,
+            ObjectEntry, Owner, URL_ENCODING,
+        },
+    },
+    state::AppState,
+};
 
 /// List buckets request parameters
 #[derive(Debug, Deserialize)]
 pub struct ListBucketsQuery {
     #[serde(rename = "max-buckets")]
     pub max_buckets: Option<u32>,
+    /// Token from a truncated listing to continue after
+    #[serde(rename = "continuation-token")]
+    pub continuation_token: Option<String>,
 }
 
+/// Most buckets returned in one listing, and the default page size
+pub const MAX_BUCKETS: u32 = 10_000;
+
 /// List buckets response
 #[derive(Debug, Serialize)]
 pub struct ListBucketsResponse {
     pub buckets: Vec<BucketInfo>,
     pub owner: OwnerInfo,
+    pub is_truncated: bool,
+    /// Pass back as `continuation-token` to fetch the next page
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub continuation_token: Option<String>,
 }
 
 /// Bucket information for listing
@@ -29,6 +52,8 @@ pub struct ListBucketsResponse {
 pub struct BucketInfo {
     pub name: String,
     pub creation_date: String,
+    pub objects_count: u64,
+    pub size_bytes: u64,
 }
 
 /// Owner information
@@ -38,27 +63,176 @@ pub struct OwnerInfo {
     pub display_name: String,
 }
 
+/// List objects response, for clients that accept JSON
+///
+/// Carries the same fields as `ListBucketResult`; those of the other
+/// listing version are left out.
+#[derive(Debug, Serialize)]
+pub struct ListObjectsResponse {
+    pub name: String,
+    pub prefix: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub marker: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_marker: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub continuation_token: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_continuation_token: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub start_after: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub key_count: Option<usize>,
+    pub max_keys: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub delimiter: Option<String>,
+    pub is_truncated: bool,
+    pub contents: Vec<ObjectListing>,
+    pub common_prefixes: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub encoding_type: Option<&'static str>,
+}
+
+/// Object information for listing
+#[derive(Debug, Serialize)]
+pub struct ObjectListing {
+    pub key: String,
+    pub last_modified: String,
+    pub etag: String,
+    pub size: u64,
+    pub storage_class: &'static str,
+}
+
+impl From<ObjectEntry> for ObjectListing {
+    fn from(entry: ObjectEntry) -> Self {
+        Self {
+            key: entry.key,
+            last_modified: entry.last_modified,
+            etag: entry.etag,
+            size: entry.size,
+            storage_class: entry.storage_class,
+        }
+    }
+}
+
+fn prefixes(common_prefixes: Vec<CommonPrefix>) -> Vec<String> {
+    common_prefixes.into_iter().map(|common| common.prefix).collect()
+}
+
+impl From<ListBucketResult> for ListObjectsResponse {
+    fn from(listing: ListBucketResult) -> Self {
+        Self {
+            name: listing.name,
+            prefix: listing.prefix,
+            marker: Some(listing.marker),
+            next_marker: listing.next_marker,
+            continuation_token: None,
+            next_continuation_token: None,
+            start_after: None,
+            key_count: None,
+            max_keys: listing.max_keys,
+            delimiter: listing.delimiter,
+            is_truncated: listing.is_truncated,
+            contents: listing.contents.into_iter().map(ObjectListing::from).collect(),
+            common_prefixes: prefixes(listing.common_prefixes),
+            encoding_type: listing.encoding_type,
+        }
+    }
+}
+
+impl From<ListBucketResultV2> for ListObjectsResponse {
+    fn from(listing: ListBucketResultV2) -> Self {
+        Self {
+            name: listing.name,
+            prefix: listing.prefix,
+            marker: None,
+            next_marker: None,
+            continuation_token: listing.continuation_token,
+            next_continuation_token: listing.next_continuation_token,
+            start_after: listing.start_after,
+            key_count: Some(listing.key_count),
+            max_keys: listing.max_keys,
+            delimiter: listing.delimiter,
+            is_truncated: listing.is_truncated,
+            contents: listing.contents.into_iter().map(ObjectListing::from).collect(),
+            common_prefixes: prefixes(listing.common_prefixes),
+            encoding_type: listing.encoding_type,
+        }
+    }
+}
+
 /// Create bucket request
 #[derive(Debug, Deserialize)]
 pub struct CreateBucketRequest {
     pub location_constraint: Option<String>,
 }
 
+/// Owner recorded for buckets created without authentication
+const DEFAULT_OWNER: &str = "default-owner";
+
+/// The bucket owner a request acts as: the authenticated user, or the
+/// default owner when authentication is disabled
+fn request_owner(auth: &Option<Extension<AuthContext>>) -> &str {
+    auth.as_ref().map_or(DEFAULT_OWNER, |Extension(auth)| auth.user_id.as_str())
+}
+
+/// Whether a client asked for JSON rather than S3's XML (the console does,
+/// to get the usage figures S3 listings lack)
+fn wants_json(headers: &HeaderMap) -> bool {
+    headers
+        .get(header::ACCEPT)
+        .and_then(|accept| accept.to_str().ok())
+        .is_some_and(|accept| accept.contains("application/json"))
+}
+
 /// List buckets handler (GET /)
+///
+/// Returns `ListAllMyBucketsResult`, or `ListBucketsResponse` as JSON when
+/// the client accepts `application/json`.
 pub async fn list_buckets(
-    Query(_params): Query<ListBucketsQuery>,
+    Query(params): Query<ListBucketsQuery>,
     State(state): State<AppState>,
-) -> std::result::Result<Json<ListBucketsResponse>, StatusCode> {
-    // TODO: Get actual owner from authentication context
-    let owner = "default-owner";
-    
-    match state.metadata.list_buckets(owner).await {
-        Ok(buckets) => {
-            let bucket_infos: Vec<BucketInfo> = buckets
+    auth: Option<Extension<AuthContext>>,
+    headers: HeaderMap,
+) -> std::result::Result<Response, StatusCode> {
+    let owner = request_owner(&auth);
+
+    let max_buckets = match params.max_buckets {
+        Some(0) => return Err(StatusCode::BAD_REQUEST),
+        Some(max) => max.min(MAX_BUCKETS),
+        None => MAX_BUCKETS,
+    };
+    let start_after = match params.continuation_token.as_deref().filter(|token| !token.is_empty()) {
+        Some(token) => match ContinuationToken::decode(token) {
+            Ok(token) => Some(token.last_key),
+            Err(e) => return Ok(ErrorDocument::from_error(&e).into_response(StatusCode::BAD_REQUEST)),
+        },
+        None => None,
+    };
+    let continuation_token = |last_bucket: String| ContinuationToken::new("", last_bucket).encode();
+
+    match state.metadata.list_buckets_page(owner, start_after.as_deref(), max_buckets as usize).await {
+        Ok(page) if !wants_json(&headers) => {
+            let buckets = page
+                .buckets
+                .iter()
+                .map(|bucket| BucketEntry::new(&bucket.name, &bucket.created_at))
+                .collect();
+            let owner = Owner {
+                id: owner.to_string(),
+                display_name: "Default Owner".to_string(),
+            };
+            to_xml_response(&ListAllMyBucketsResult::new(owner, buckets, page.next_marker.map(continuation_token)))
+        }
+        Ok(page) => {
+            let bucket_infos: Vec<BucketInfo> = page
+                .buckets
                 .into_iter()
                 .map(|bucket| BucketInfo {
                     name: bucket.name,
                     creation_date: bucket.created_at.to_rfc3339(),
+                    objects_count: bucket.object_count,
+                    size_bytes: bucket.total_size,
                 })
                 .collect();
 
@@ -68,9 +242,11 @@ pub async fn list_buckets(
                     id: owner.to_string(),
                     display_name: "Default Owner".to_string(),
                 },
+                is_truncated: page.next_marker.is_some(),
+                continuation_token: page.next_marker.map(continuation_token),
             };
 
-            Ok(Json(response))
+            Ok(Json(response).into_response())
         }
         Err(e) => {
             eprintln!("Failed to list buckets: {}", e);
@@ -79,62 +255,537 @@ pub async fn list_buckets(
     }
 }
 
+/// Bucket PUT parameters
+#[derive(Debug, Deserialize)]
+pub struct PutBucketQuery {
+    pub import: Option<String>,
+    #[serde(rename = "object-lock")]
+    pub object_lock: Option<String>,
+    #[serde(rename = "key-patterns")]
+    pub key_patterns: Option<String>,
+}
+
+/// Bucket PUT handler (PUT /{bucket})
+pub async fn put_bucket(
+    Path(bucket_name): Path<String>,
+    State(state): State<AppState>,
+    auth: Option<Extension<AuthContext>>,
+    Query(params): Query<PutBucketQuery>,
+    request: Request,
+) -> std::result::Result<Response, StatusCode> {
+    // Bulk import an archive into the bucket (PUT /{bucket}?import=tar)
+    if let Some(import) = params.import {
+        let format = archive::ArchiveFormat::parse(&import).ok_or(StatusCode::BAD_REQUEST)?;
+        return archive::import_bucket(&state, &bucket_name, format, request.into_body())
+            .await
+            .map(IntoResponse::into_response);
+    }
+
+    // Enable object lock (PUT /{bucket}?object-lock), owner or admin only
+    if params.object_lock.is_some() {
+        check_bucket_owner(&state, &bucket_name, &auth).await?;
+        return object_lock::put_bucket_object_lock(&state, &bucket_name, request.into_body()).await;
+    }
+
+    // Restrict the keys written to the bucket (PUT /{bucket}?key-patterns), owner or admin only
+    if params.key_patterns.is_some() {
+        check_bucket_owner(&state, &bucket_name, &auth).await?;
+        return key_patterns::put_bucket_key_patterns(&state, &bucket_name, request.into_body()).await;
+    }
+
+    let headers = request.headers().clone();
+    let body = Json::<CreateBucketRequest>::from_request(request, &state)
+        .await
+        .map_err(|rejection| rejection.status())?;
+    create_bucket(Path(bucket_name), State(state), auth, headers, body).await
+}
+
+/// S3 error response for a bucket-level conflict
+pub(crate) fn bucket_error(error: &ObjectIOError, bucket: &str) -> Response {
+    let status = StatusCode::from_u16(error.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+    ErrorDocument::from_error(error).with_bucket(bucket).into_response(status)
+}
+
 /// Create bucket handler (PUT /{bucket})
+///
+/// Recreating a bucket the caller already owns is a no-op in us-east-1 (or
+/// any region with `idempotent_bucket_create`) and `BucketAlreadyOwnedByYou`
+/// elsewhere; a bucket owned by someone else is `BucketAlreadyExists`.
+/// A new bucket gets the `x-amz-acl` canned ACL, or the configured default,
+/// and object lock if `x-amz-bucket-object-lock-enabled` is `true`.
 pub async fn create_bucket(
     Path(bucket_name): Path<String>,
     State(state): State<AppState>,
+    auth: Option<Extension<AuthContext>>,
+    headers: HeaderMap,
     Json(_request): Json<CreateBucketRequest>,
-) -> std::result::Result<StatusCode, StatusCode> {
+) -> std::result::Result<Response, StatusCode> {
     // Validate bucket name
     if let Err(_) = object_io_core::validate_bucket_name(&bucket_name) {
         return Err(StatusCode::BAD_REQUEST);
     }
+    let acl = match object::canned_acl(&headers, state.config.default_acl) {
+        Ok(acl) => acl,
+        Err(e) => return Ok(bucket_error(&e, &bucket_name)),
+    };
+
+    let owner = request_owner(&auth);
 
-    // TODO: Get actual owner from authentication context
-    let owner = "default-owner";
-    
     match state.metadata.create_bucket(&bucket_name, owner).await {
-        Ok(_) => Ok(StatusCode::OK),
+        Ok(_) => {
+            state.metadata.set_bucket_acl(&bucket_name, &acl.grants()).await.map_err(|e| {
+                eprintln!("Failed to set ACL of bucket '{}': {}", bucket_name, e);
+                StatusCode::INTERNAL_SERVER_ERROR
+            })?;
+            let lock_enabled = headers.get(object_io_core::object_lock::BUCKET_ENABLED_HEADER);
+            if lock_enabled.is_some_and(|value| value.as_bytes().eq_ignore_ascii_case(b"true")) {
+                state.metadata.enable_object_lock(&bucket_name, None).await.map_err(|e| {
+                    eprintln!("Failed to enable object lock on bucket '{}': {}", bucket_name, e);
+                    StatusCode::INTERNAL_SERVER_ERROR
+                })?;
+            }
+            Ok(StatusCode::OK.into_response())
+        }
+        Err(error @ ObjectIOError::BucketAlreadyExists { .. }) => {
+            let existing = state.metadata.get_bucket(&bucket_name).await.map_err(|e| {
+                eprintln!("Failed to look up bucket '{}': {}", bucket_name, e);
+                StatusCode::INTERNAL_SERVER_ERROR
+            })?;
+            match existing {
+                Some(bucket) if bucket.access_control.owner.name == owner => {
+                    if state.config.idempotent_bucket_create || state.config.region == "us-east-1" {
+                        Ok(StatusCode::OK.into_response())
+                    } else {
+                        let error = ObjectIOError::BucketAlreadyOwnedByYou {
+                            bucket: bucket_name.clone(),
+                        };
+                        Ok(bucket_error(&error, &bucket_name))
+                    }
+                }
+                _ => Ok(bucket_error(&error, &bucket_name)),
+            }
+        }
         Err(e) => {
             eprintln!("Failed to create bucket '{}': {}", bucket_name, e);
-            
-            // Check if it's a conflict (bucket already exists)
-            if e.to_string().contains("already exists") {
-                Err(StatusCode::CONFLICT)
-            } else {
-                Err(StatusCode::INTERNAL_SERVER_ERROR)
-            }
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
         }
     }
 }
 
+/// Bucket DELETE parameters
+#[derive(Debug, Deserialize)]
+pub struct DeleteBucketQuery {
+    #[serde(rename = "key-patterns")]
+    pub key_patterns: Option<String>,
+}
+
 /// Delete bucket handler (DELETE /{bucket})
 pub async fn delete_bucket(
     Path(bucket_name): Path<String>,
     State(state): State<AppState>,
-) -> std::result::Result<StatusCode, StatusCode> {
+    auth: Option<Extension<AuthContext>>,
+    Query(params): Query<DeleteBucketQuery>,
+) -> std::result::Result<Response, StatusCode> {
+    // Remove key patterns (DELETE /{bucket}?key-patterns), owner or admin only
+    if params.key_patterns.is_some() {
+        check_bucket_owner(&state, &bucket_name, &auth).await?;
+        return key_patterns::delete_bucket_key_patterns(&state, &bucket_name).await;
+    }
+
     match state.metadata.delete_bucket(&bucket_name).await {
-        Ok(_) => Ok(StatusCode::NO_CONTENT),
+        Ok(true) => Ok(StatusCode::NO_CONTENT.into_response()),
+        Ok(false) => Err(StatusCode::NOT_FOUND),
+        Err(ObjectIOError::BucketNotEmpty { .. }) => Err(StatusCode::CONFLICT),
         Err(e) => {
             eprintln!("Failed to delete bucket '{}': {}", bucket_name, e);
-            
-            // Check if it's a not found error
-            if e.to_string().contains("not found") {
-                Err(StatusCode::NOT_FOUND)
-            } else {
-                Err(StatusCode::INTERNAL_SERVER_ERROR)
-            }
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
         }
     }
 }
 
+/// Bucket GET parameters; the subresource present selects the operation
+#[derive(Debug, Deserialize)]
+pub struct GetBucketQuery {
+    pub location: Option<String>,
+    pub export: Option<String>,
+    pub snapshots: Option<String>,
+    pub snapshot: Option<String>,
+    pub uploads: Option<String>,
+    #[serde(rename = "object-lock")]
+    pub object_lock: Option<String>,
+    #[serde(rename = "key-patterns")]
+    pub key_patterns: Option<String>,
+    /// Count and size the objects under `prefix` instead of listing them
+    pub aggregate: Option<String>,
+    pub prefix: Option<String>,
+    pub delimiter: Option<String>,
+    pub marker: Option<String>,
+    #[serde(rename = "max-keys")]
+    pub max_keys: Option<u32>,
+    /// `2` selects ListObjectsV2
+    #[serde(rename = "list-type")]
+    pub list_type: Option<String>,
+    #[serde(rename = "continuation-token")]
+    pub continuation_token: Option<String>,
+    #[serde(rename = "start-after")]
+    pub start_after: Option<String>,
+    /// `url` to percent-encode keys in the listing
+    #[serde(rename = "encoding-type")]
+    pub encoding_type: Option<String>,
+    #[serde(rename = "key-marker")]
+    pub key_marker: Option<String>,
+    #[serde(rename = "upload-id-marker")]
+    pub upload_id_marker: Option<String>,
+    #[serde(rename = "max-uploads")]
+    pub max_uploads: Option<u32>,
+}
+
+/// Bucket GET handler (GET /{bucket})
+///
+/// Object listings are `ListBucketResult` documents, or
+/// `ListObjectsResponse` as JSON when the client accepts `application/json`.
+pub async fn get_bucket(
+    Path(bucket_name): Path<String>,
+    State(state): State<AppState>,
+    auth: Option<Extension<AuthContext>>,
+    Query(params): Query<GetBucketQuery>,
+    headers: HeaderMap,
+) -> std::result::Result<Response, StatusCode> {
+    // List multipart uploads (GET /{bucket}?uploads)
+    if params.uploads.is_some() {
+        let list_params = multipart::ListMultipartUploadsQuery {
+            prefix: params.prefix,
+            key_marker: params.key_marker,
+            upload_id_marker: params.upload_id_marker,
+            max_uploads: params.max_uploads,
+        };
+        return multipart::list_multipart_uploads(&state, bucket_name, list_params).await;
+    }
+
+    if params.object_lock.is_some() {
+        return object_lock::get_bucket_object_lock(&state, &bucket_name).await;
+    }
+
+    if params.key_patterns.is_some() {
+        return key_patterns::get_bucket_key_patterns(&state, &bucket_name).await;
+    }
+
+    if params.aggregate.is_some() {
+        return prefix_usage(&state, bucket_name, params.prefix.unwrap_or_default())
+            .await
+            .map(IntoResponse::into_response);
+    }
+
+    if params.location.is_some() {
+        return get_bucket_location(Path(bucket_name), State(state))
+            .await
+            .map(IntoResponse::into_response);
+    }
+
+    // Export the whole bucket as an archive (GET /{bucket}?export=tar|zip), admin only
+    if let Some(export) = params.export {
+        admin::authorize_admin(&auth)?;
+        let format = archive::ArchiveFormat::parse(&export).ok_or(StatusCode::BAD_REQUEST)?;
+        return archive::export_bucket(&state, bucket_name, format).await;
+    }
+
+    // List snapshots (GET /{bucket}?snapshots) or the objects in one
+    // (GET /{bucket}?snapshot=ID), owner or admin only
+    if params.snapshots.is_some() || params.snapshot.is_some() {
+        check_bucket_owner(&state, &bucket_name, &auth).await?;
+        return match params.snapshot {
+            Some(id) => snapshot::get_snapshot(&state, &bucket_name, id).await.map(IntoResponse::into_response),
+            None => snapshot::list_snapshots(&state, &bucket_name).await.map(IntoResponse::into_response),
+        };
+    }
+
+    let url_encoding = match params.encoding_type.as_deref() {
+        None => false,
+        Some(URL_ENCODING) => true,
+        Some(_) => return Err(StatusCode::BAD_REQUEST),
+    };
+
+    if params.list_type.as_deref() == Some("2") {
+        // The continuation token holds the last key of the previous page,
+        // which becomes the marker; it takes precedence over `start-after`
+        let marker = match params.continuation_token.as_deref().filter(|token| !token.is_empty()) {
+            Some(token) => match ContinuationToken::decode_for(token, params.prefix.as_deref().unwrap_or_default()) {
+                Ok(token) => Some(token.last_key),
+                Err(e) => return Ok(bucket_error(&e, &bucket_name)),
+            },
+            None => params.start_after.clone(),
+        };
+        let request = ListObjectsRequest {
+            bucket: bucket_name,
+            prefix: params.prefix,
+            delimiter: params.delimiter,
+            marker,
+            max_keys: params.max_keys,
+        };
+        let listing = list_objects_v2(&state, request, params.continuation_token, params.start_after).await?;
+        return listing_response(listing, url_encoding, wants_json(&headers), ListBucketResultV2::url_encoded);
+    }
+
+    let request = ListObjectsRequest {
+        bucket: bucket_name,
+        prefix: params.prefix,
+        delimiter: params.delimiter,
+        marker: params.marker,
+        max_keys: params.max_keys,
+    };
+    let listing = list_objects(&state, request).await?;
+    listing_response(listing, url_encoding, wants_json(&headers), ListBucketResult::url_encoded)
+}
+
+/// Render a listing as XML, or as JSON for clients that asked for it
+fn listing_response<T>(
+    listing: T,
+    url_encoding: bool,
+    json: bool,
+    url_encoded: fn(T) -> T,
+) -> std::result::Result<Response, StatusCode>
+where
+    T: Serialize + Into<ListObjectsResponse>,
+{
+    let listing = if url_encoding { url_encoded(listing) } else { listing };
+    if json {
+        Ok(Json(listing.into()).into_response())
+    } else {
+        to_xml_response(&listing)
+    }
+}
+
+/// Fetch one page of a bucket listing
+///
+/// `max-keys` defaults to 1000 and is clamped to the configured
+/// `max_list_keys`.
+async fn fetch_listing(
+    state: &AppState,
+    request: ListObjectsRequest,
+) -> std::result::Result<object_io_core::ListObjectsResponse, StatusCode> {
+    multipart::check_bucket(state, &request.bucket).await?;
+
+    let request = request.capped(state.config.max_list_keys);
+    state.metadata.list_objects(&request).await.map_err(|e| {
+        eprintln!("Failed to list objects in '{}': {}", request.bucket, e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })
+}
+
+/// List objects (GET /{bucket})
+async fn list_objects(
+    state: &AppState,
+    request: ListObjectsRequest,
+) -> std::result::Result<ListBucketResult, StatusCode> {
+    Ok(ListBucketResult::from_listing(fetch_listing(state, request).await?))
+}
+
+/// List objects, V2 (GET /{bucket}?list-type=2)
+async fn list_objects_v2(
+    state: &AppState,
+    request: ListObjectsRequest,
+    continuation_token: Option<String>,
+    start_after: Option<String>,
+) -> std::result::Result<ListBucketResultV2, StatusCode> {
+    let listing = fetch_listing(state, request).await?;
+    Ok(ListBucketResultV2::from_listing(listing, continuation_token, start_after))
+}
+
+/// Bucket POST parameters; the subresource present selects the operation
+#[derive(Debug, Deserialize)]
+pub struct PostBucketQuery {
+    pub policy: Option<String>,
+    pub validate: Option<String>,
+    pub empty: Option<String>,
+    pub snapshot: Option<String>,
+    /// Snapshot to restore from
+    pub restore: Option<String>,
+    /// Restore only this object
+    pub key: Option<String>,
+    #[serde(rename = "rename-prefix")]
+    pub rename_prefix: Option<String>,
+    /// Source and destination prefixes of a prefix rename
+    pub from: Option<String>,
+    pub to: Option<String>,
+}
+
+/// Policy validation response
+#[derive(Debug, Serialize)]
+pub struct ValidatePolicyResponse {
+    pub valid: bool,
+    pub issues: Vec<PolicyIssue>,
+}
+
+/// Bucket emptying response
+#[derive(Debug, Serialize)]
+pub struct EmptyBucketResponse {
+    pub deleted: u64,
+}
+
+/// Prefix aggregation response
+#[derive(Debug, Serialize)]
+pub struct PrefixUsageResponse {
+    pub bucket: String,
+    pub prefix: String,
+    pub object_count: u64,
+    pub total_size: u64,
+}
+
+/// Count and size the objects under a prefix (GET /{bucket}?aggregate&prefix=X)
+///
+/// The metadata store computes the totals, so no listing is paged through.
+async fn prefix_usage(
+    state: &AppState,
+    bucket_name: String,
+    prefix: String,
+) -> std::result::Result<Json<PrefixUsageResponse>, StatusCode> {
+    match state.metadata.get_bucket(&bucket_name).await {
+        Ok(Some(_)) => {}
+        Ok(None) => return Err(StatusCode::NOT_FOUND),
+        Err(e) => {
+            eprintln!("Failed to check bucket '{}': {}", bucket_name, e);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    }
+
+    match state.metadata.prefix_usage(&bucket_name, &prefix).await {
+        Ok(usage) => Ok(Json(PrefixUsageResponse {
+            bucket: bucket_name,
+            prefix,
+            object_count: usage.object_count,
+            total_size: usage.total_size,
+        })),
+        Err(e) => {
+            eprintln!("Failed to aggregate '{}/{}': {}", bucket_name, prefix, e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+/// Bucket POST handler (POST /{bucket})
+pub async fn post_bucket(
+    Path(bucket_name): Path<String>,
+    State(state): State<AppState>,
+    Query(params): Query<PostBucketQuery>,
+    auth: Option<Extension<AuthContext>>,
+    request: Request,
+) -> std::result::Result<Response, StatusCode> {
+    // Browser form upload (POST /{bucket} with multipart/form-data)
+    if form_upload::is_form_upload(request.headers()) {
+        return form_upload::post_object(&state, &bucket_name, request).await;
+    }
+
+    // Validate a policy without applying it (POST /{bucket}?policy&validate)
+    if params.policy.is_some() && params.validate.is_some() {
+        let body = String::from_request(request, &state).await.map_err(|_| StatusCode::BAD_REQUEST)?;
+        return validate_bucket_policy(&state, &bucket_name, &body)
+            .await
+            .map(IntoResponse::into_response);
+    }
+
+    // Delete every object but keep the bucket (POST /{bucket}?empty)
+    if params.empty.is_some() {
+        return empty_bucket(&state, &bucket_name, &auth)
+            .await
+            .map(IntoResponse::into_response);
+    }
+
+    // Snapshot the bucket (POST /{bucket}?snapshot), owner or admin only
+    if params.snapshot.is_some() {
+        check_bucket_owner(&state, &bucket_name, &auth).await?;
+        return snapshot::create_snapshot(&state, &bucket_name)
+            .await
+            .map(IntoResponse::into_response);
+    }
+
+    // Restore from a snapshot (POST /{bucket}?restore=ID[&key=K]), owner or admin only
+    if let Some(id) = params.restore {
+        check_bucket_owner(&state, &bucket_name, &auth).await?;
+        return snapshot::restore(&state, &bucket_name, id, params.key.as_deref())
+            .await
+            .map(IntoResponse::into_response);
+    }
+
+    // Move every object under a prefix (POST /{bucket}?rename-prefix&from=X&to=Y), owner or admin only
+    if params.rename_prefix.is_some() {
+        check_bucket_owner(&state, &bucket_name, &auth).await?;
+        return rename_prefix::rename(&state, &bucket_name, params.from, params.to).await;
+    }
+
+    Err(StatusCode::BAD_REQUEST)
+}
+
+/// Ensure the bucket exists and the caller is its owner or an admin
+pub(crate) async fn check_bucket_owner(
+    state: &AppState,
+    bucket_name: &str,
+    auth: &Option<Extension<AuthContext>>,
+) -> std::result::Result<(), StatusCode> {
+    let bucket = match state.metadata.get_bucket(bucket_name).await {
+        Ok(Some(bucket)) => bucket,
+        Ok(None) => return Err(StatusCode::NOT_FOUND),
+        Err(e) => {
+            eprintln!("Failed to check bucket '{}': {}", bucket_name, e);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+    if let Some(Extension(auth)) = auth {
+        if !auth.is_admin && auth.user_id != bucket.access_control.owner.name {
+            return Err(StatusCode::FORBIDDEN);
+        }
+    }
+    Ok(())
+}
+
+/// Delete every object in a bucket and reset its stats; only the bucket's
+/// owner or an admin may do so
+async fn empty_bucket(
+    state: &AppState,
+    bucket_name: &str,
+    auth: &Option<Extension<AuthContext>>,
+) -> std::result::Result<Json<EmptyBucketResponse>, StatusCode> {
+    check_bucket_owner(state, bucket_name, auth).await?;
+
+    match state.empty_bucket(bucket_name).await {
+        Ok(deleted) => Ok(Json(EmptyBucketResponse { deleted })),
+        Err(ObjectIOError::ObjectLocked { .. }) => Err(StatusCode::FORBIDDEN),
+        Err(e) => {
+            eprintln!("Failed to empty bucket '{}': {}", bucket_name, e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+/// Parse and lint a bucket policy document, reporting errors and warnings
+/// without persisting anything
+async fn validate_bucket_policy(
+    state: &AppState,
+    bucket_name: &str,
+    document: &str,
+) -> std::result::Result<Json<ValidatePolicyResponse>, StatusCode> {
+    match state.metadata.bucket_exists(bucket_name).await {
+        Ok(true) => {}
+        Ok(false) => return Err(StatusCode::NOT_FOUND),
+        Err(e) => {
+            eprintln!("Failed to check bucket '{}': {}", bucket_name, e);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    }
+
+    let validation = validate_policy(document, bucket_name);
+    Ok(Json(ValidatePolicyResponse {
+        valid: validation.is_valid(),
+        issues: validation.issues,
+    }))
+}
+
 /// Get bucket location handler (GET /{bucket}?location)
 pub async fn get_bucket_location(
     Path(_bucket_name): Path<String>,
-    Extension(_storage): Extension<Arc<dyn object_io_storage::Storage>>,
+    State(state): State<AppState>,
 ) -> std::result::Result<Json<HashMap<String, String>>, StatusCode> {
     let mut response = HashMap::new();
-    response.insert("LocationConstraint".to_string(), "us-east-1".to_string());
+    response.insert("LocationConstraint".to_string(), state.config.region.clone());
     Ok(Json(response))
 }
 
@@ -152,3 +803,201 @@ pub async fn head_bucket(
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::to_bytes;
+    use std::sync::Arc;
+
+    fn caller(user_id: &str) -> Option<Extension<AuthContext>> {
+        Some(Extension(AuthContext {
+            access_key: format!("{}-key", user_id),
+            user_id: user_id.to_string(),
+            is_admin: false,
+            permissions: Vec::new(),
+            scope: None,
+        }))
+    }
+
+    async fn create(state: &AppState, user_id: &str) -> (StatusCode, String) {
+        let request = CreateBucketRequest { location_constraint: None };
+        let response = create_bucket(
+            Path("photos".to_string()),
+            State(state.clone()),
+            caller(user_id),
+            HeaderMap::new(),
+            Json(request),
+        )
+        .await
+        .unwrap();
+        let status = response.status();
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, String::from_utf8_lossy(&body).into_owned())
+    }
+
+    #[tokio::test]
+    async fn test_empty_bucket() {
+        let dir = tempfile::tempdir().unwrap();
+        let state = AppState::for_test(dir.path()).await;
+        assert_eq!(create(&state, "alice").await.0, StatusCode::OK);
+        for key in ["a.jpg", "b.jpg", "albums/c.jpg"] {
+            let reader = Box::new(std::io::Cursor::new(key.as_bytes().to_vec()));
+            object::store_object(&state, "photos", key, reader, HashMap::new()).await.unwrap();
+        }
+
+        // Only the owner may empty the bucket
+        assert_eq!(empty_bucket(&state, "photos", &caller("mallory")).await.unwrap_err(), StatusCode::FORBIDDEN);
+        assert_eq!(empty_bucket(&state, "missing", &caller("alice")).await.unwrap_err(), StatusCode::NOT_FOUND);
+
+        let Json(response) = empty_bucket(&state, "photos", &caller("alice")).await.unwrap();
+        assert_eq!(response.deleted, 3);
+        let listing = state.metadata.list_objects(&ListObjectsRequest::new("photos")).await.unwrap();
+        assert!(listing.objects.is_empty());
+        let bucket = state.metadata.get_bucket("photos").await.unwrap().unwrap();
+        assert_eq!((bucket.object_count, bucket.total_size), (0, 0));
+        assert_eq!(state.storage.bucket_usage("photos").await.unwrap(), (0, 0));
+        assert!(!state.storage.object_exists("photos", "albums/c.jpg").await.unwrap());
+
+        // The bucket remains, and emptying it again finds nothing to delete
+        let Json(response) = empty_bucket(&state, "photos", &caller("alice")).await.unwrap();
+        assert_eq!(response.deleted, 0);
+    }
+
+    #[tokio::test]
+    async fn test_empty_bucket_keeps_locked_objects() {
+        let dir = tempfile::tempdir().unwrap();
+        let state = AppState::for_test(dir.path()).await;
+        assert_eq!(create(&state, "alice").await.0, StatusCode::OK);
+        state.metadata.enable_object_lock("photos", None).await.unwrap();
+        for key in ["a.jpg", "b.jpg"] {
+            let reader = Box::new(std::io::Cursor::new(key.as_bytes().to_vec()));
+            object::store_object(&state, "photos", key, reader, HashMap::new()).await.unwrap();
+        }
+        let mut metadata = HashMap::new();
+        object_io_core::ObjectRetention {
+            mode: object_io_core::ObjectLockMode::Compliance,
+            retain_until: chrono::Utc::now() + chrono::Duration::days(1),
+        }
+        .insert_into(&mut metadata);
+        state.metadata.update_object_metadata("photos", "b.jpg", "", metadata).await.unwrap();
+
+        // Nothing is deleted while any object is under retention
+        assert_eq!(empty_bucket(&state, "photos", &caller("alice")).await.unwrap_err(), StatusCode::FORBIDDEN);
+        let bucket = state.metadata.get_bucket("photos").await.unwrap().unwrap();
+        assert_eq!(bucket.object_count, 2);
+        assert!(state.storage.object_exists("photos", "a.jpg").await.unwrap());
+        assert!(state.storage.object_exists("photos", "b.jpg").await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_prefix_usage() {
+        let dir = tempfile::tempdir().unwrap();
+        let state = AppState::for_test(dir.path()).await;
+        assert_eq!(create(&state, "alice").await.0, StatusCode::OK);
+        for (key, size) in [("2024/01/a.log", 10), ("2024/01/b.log", 20), ("2024/02/c.log", 5), ("2025/d.log", 7)] {
+            let reader = Box::new(std::io::Cursor::new(vec![b'x'; size]));
+            object::store_object(&state, "photos", key, reader, HashMap::new()).await.unwrap();
+        }
+
+        let usage = |prefix: &str| prefix_usage(&state, "photos".to_string(), prefix.to_string());
+        let Json(response) = usage("2024/").await.unwrap();
+        assert_eq!((response.object_count, response.total_size), (3, 35));
+        assert_eq!(response.prefix, "2024/");
+        let Json(response) = usage("2024/01/").await.unwrap();
+        assert_eq!((response.object_count, response.total_size), (2, 30));
+        let Json(response) = usage("").await.unwrap();
+        assert_eq!((response.object_count, response.total_size), (4, 42));
+        let Json(response) = usage("2026/").await.unwrap();
+        assert_eq!((response.object_count, response.total_size), (0, 0));
+
+        // Overwrites and deletes are reflected
+        let reader = Box::new(std::io::Cursor::new(vec![b'x'; 1]));
+        object::store_object(&state, "photos", "2024/01/a.log", reader, HashMap::new()).await.unwrap();
+        state.metadata.delete_object("photos", "2024/02/c.log").await.unwrap();
+        let Json(response) = usage("2024/").await.unwrap();
+        assert_eq!((response.object_count, response.total_size), (2, 21));
+
+        let missing = prefix_usage(&state, "missing".to_string(), String::new()).await;
+        assert_eq!(missing.unwrap_err(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_recreate_bucket_same_owner() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut state = AppState::for_test(dir.path()).await;
+
+        assert_eq!(create(&state, "alice").await.0, StatusCode::OK);
+        // us-east-1 treats recreating your own bucket as a no-op
+        assert_eq!(create(&state, "alice").await, (StatusCode::OK, String::new()));
+
+        let mut config = (*state.config).clone();
+        config.region = "eu-central-1".to_string();
+        state.config = Arc::new(config);
+        let (status, body) = create(&state, "alice").await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert!(body.contains("<Code>BucketAlreadyOwnedByYou</Code>"));
+        assert!(body.contains("<BucketName>photos</BucketName>"));
+
+        // Idempotent mode makes it a no-op in any region
+        let mut config = (*state.config).clone();
+        config.idempotent_bucket_create = true;
+        state.config = Arc::new(config);
+        assert_eq!(create(&state, "alice").await, (StatusCode::OK, String::new()));
+    }
+
+    #[tokio::test]
+    async fn test_list_buckets_xml_and_json() {
+        let dir = tempfile::tempdir().unwrap();
+        let state = AppState::for_test(dir.path()).await;
+        assert_eq!(create(&state, "alice").await.0, StatusCode::OK);
+
+        let list = |accept: Option<&'static str>| {
+            let state = state.clone();
+            async move {
+                let mut headers = HeaderMap::new();
+                if let Some(accept) = accept {
+                    headers.insert(header::ACCEPT, accept.parse().unwrap());
+                }
+                let query = ListBucketsQuery {
+                    max_buckets: None,
+                    continuation_token: None,
+                };
+                let response = list_buckets(Query(query), State(state), caller("alice"), headers).await.unwrap();
+                let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+                String::from_utf8_lossy(&body).into_owned()
+            }
+        };
+
+        let xml = list(None).await;
+        assert!(xml.contains("<ListAllMyBucketsResult"), "{}", xml);
+        assert!(xml.contains("<Owner><ID>alice</ID>"), "{}", xml);
+        assert!(xml.contains("<Buckets><Bucket><Name>photos</Name><CreationDate>"), "{}", xml);
+
+        let json: serde_json::Value = serde_json::from_str(&list(Some("application/json")).await).unwrap();
+        assert_eq!(json["buckets"][0]["name"], "photos");
+    }
+
+    #[tokio::test]
+    async fn test_recreate_bucket_different_owner() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut state = AppState::for_test(dir.path()).await;
+
+        assert_eq!(create(&state, "alice").await.0, StatusCode::OK);
+        let (status, body) = create(&state, "bob").await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert!(body.contains("<Code>BucketAlreadyExists</Code>"));
+
+        // Idempotent mode only covers the owner's own bucket
+        let mut config = (*state.config).clone();
+        config.idempotent_bucket_create = true;
+        state.config = Arc::new(config);
+        let (status, body) = create(&state, "bob").await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert!(body.contains("<Code>BucketAlreadyExists</Code>"));
+
+        // The bucket still belongs to its creator
+        let bucket = state.metadata.get_bucket("photos").await.unwrap().unwrap();
+        assert_eq!(bucket.access_control.owner.name, "alice");
+    }
+}
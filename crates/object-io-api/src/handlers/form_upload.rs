@@ -0,0 +1,580 @@
+//! Browser form uploads (POST /{bucket} with multipart/form-data)
+//!
+//! The form carries the object key, a base64 policy document signed with
+//! SigV4, optional metadata fields and, last, the `file` field holding the
+//! data, so browsers can upload directly without a proxy.
+
+use axum::{
+    body::Body,
+    extract::{FromRequest, Multipart, Request},
+    http::{header, HeaderMap, HeaderName, HeaderValue, StatusCode},
+    response::Response,
+};
+use base64::Engine;
+use bytes::Bytes;
+use chrono::{DateTime, NaiveDateTime, Utc};
+use futures::{channel::mpsc, SinkExt};
+use object_io_core::CannedAcl;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::{
+    auth::sigv4::{AuthorizationHeader, SigV4Validator},
+    handlers::{
+        object::{object_error, object_metadata, store_object},
+        key_patterns, object_lock,
+    },
+    responses::{to_xml_response, S3_XMLNS},
+    state::AppState,
+};
+
+/// Response to a form upload with `success_action_status` 201
+#[derive(Debug, Serialize)]
+#[serde(rename = "PostResponse")]
+pub struct PostResponse {
+    #[serde(rename = "@xmlns")]
+    pub xmlns: &'static str,
+    #[serde(rename = "Location")]
+    pub location: String,
+    #[serde(rename = "Bucket")]
+    pub bucket: String,
+    #[serde(rename = "Key")]
+    pub key: String,
+    #[serde(rename = "ETag")]
+    pub etag: String,
+}
+
+/// Whether a request carries a form upload, which authenticates with its
+/// signed policy instead of an `Authorization` header
+pub(crate) fn is_form_upload(headers: &HeaderMap) -> bool {
+    headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.trim_start().to_ascii_lowercase().starts_with("multipart/form-data"))
+}
+
+/// Form fields that need no policy condition of their own
+fn is_exempt_field(name: &str) -> bool {
+    matches!(
+        name,
+        "bucket" | "policy" | "x-amz-algorithm" | "x-amz-credential" | "x-amz-date" | "x-amz-signature"
+    ) || name.starts_with("x-ignore-")
+}
+
+/// One condition of a POST policy; field names are lower-cased
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Condition {
+    /// The field must equal the value exactly
+    Equals { field: String, value: String },
+    /// The field must start with the prefix (any value when it is empty)
+    StartsWith { field: String, prefix: String },
+    /// The file must be between `min` and `max` bytes long, inclusive
+    ContentLengthRange { min: u64, max: u64 },
+}
+
+impl Condition {
+    /// Parse `{"field": "value"}`, `["eq" | "starts-with", "$field", "value"]`
+    /// or `["content-length-range", min, max]`
+    fn parse(value: &serde_json::Value) -> Option<Self> {
+        if let Some(object) = value.as_object() {
+            let (field, value) = object.iter().next().filter(|_| object.len() == 1)?;
+            return Some(Condition::Equals {
+                field: field.to_ascii_lowercase(),
+                value: value.as_str()?.to_string(),
+            });
+        }
+
+        let [operator, first, second] = value.as_array()?.as_slice() else {
+            return None;
+        };
+        let field = || first.as_str()?.strip_prefix('$').map(str::to_ascii_lowercase);
+        match operator.as_str()?.to_ascii_lowercase().as_str() {
+            "eq" => Some(Condition::Equals {
+                field: field()?,
+                value: second.as_str()?.to_string(),
+            }),
+            "starts-with" => Some(Condition::StartsWith {
+                field: field()?,
+                prefix: second.as_str()?.to_string(),
+            }),
+            "content-length-range" => Some(Condition::ContentLengthRange {
+                min: first.as_u64()?,
+                max: second.as_u64()?,
+            }),
+            _ => None,
+        }
+    }
+
+    /// The form field the condition applies to
+    fn field(&self) -> Option<&str> {
+        match self {
+            Condition::Equals { field, .. } | Condition::StartsWith { field, .. } => Some(field),
+            Condition::ContentLengthRange { .. } => None,
+        }
+    }
+}
+
+/// A POST policy document as encoded in the form
+#[derive(Debug, Deserialize)]
+struct PolicyDocument {
+    expiration: String,
+    #[serde(default)]
+    conditions: Vec<serde_json::Value>,
+}
+
+/// A decoded POST policy
+#[derive(Debug)]
+struct PostPolicy {
+    expiration: DateTime<Utc>,
+    conditions: Vec<Condition>,
+}
+
+impl PostPolicy {
+    /// Decode the base64 JSON policy from the `policy` form field
+    fn decode(encoded: &str) -> Option<Self> {
+        let json = base64::engine::general_purpose::STANDARD.decode(encoded.trim()).ok()?;
+        let document: PolicyDocument = serde_json::from_slice(&json).ok()?;
+        Some(Self {
+            expiration: object_io_core::parse_s3_timestamp(&document.expiration).ok()?,
+            conditions: document.conditions.iter().map(Condition::parse).collect::<Option<_>>()?,
+        })
+    }
+
+    /// Check the form fields against the policy, describing the first
+    /// failure. The file size is checked separately while it streams.
+    fn check_fields(&self, fields: &HashMap<String, String>) -> std::result::Result<(), String> {
+        if self.expiration <= Utc::now() {
+            return Err("policy expired".to_string());
+        }
+
+        for condition in &self.conditions {
+            let satisfied = match condition {
+                Condition::Equals { field, value } => fields.get(field) == Some(value),
+                Condition::StartsWith { field, prefix } => {
+                    fields.get(field).is_some_and(|value| value.starts_with(prefix.as_str()))
+                }
+                Condition::ContentLengthRange { .. } => true,
+            };
+            if !satisfied {
+                return Err(format!("condition failed: {:?}", condition));
+            }
+        }
+
+        // Every other field must be covered by a condition
+        match fields
+            .keys()
+            .find(|name| !is_exempt_field(name) && !self.conditions.iter().any(|c| c.field() == Some(name.as_str())))
+        {
+            Some(name) => Err(format!("extra input field '{}'", name)),
+            None => Ok(()),
+        }
+    }
+
+    /// Allowed file size as an inclusive `(min, max)` range
+    fn length_range(&self) -> (u64, u64) {
+        self.conditions.iter().fold((0, u64::MAX), |(low, high), condition| match condition {
+            Condition::ContentLengthRange { min, max } => (low.max(*min), high.min(*max)),
+            _ => (low, high),
+        })
+    }
+}
+
+/// Check the policy signature and that the signing key may write `key`
+async fn authorize(state: &AppState, bucket: &str, key: &str, fields: &HashMap<String, String>) -> std::result::Result<(), String> {
+    let field = |name: &str| fields.get(name).map(String::as_str).ok_or(format!("missing '{}' field", name));
+    let policy = field("policy")?;
+    if field("x-amz-algorithm")? != "AWS4-HMAC-SHA256" {
+        return Err("unsupported signing algorithm".to_string());
+    }
+    let timestamp = NaiveDateTime::parse_from_str(field("x-amz-date")?, "%Y%m%dT%H%M%SZ")
+        .map_err(|_| "invalid x-amz-date".to_string())?
+        .and_utc();
+
+    let auth = AuthorizationHeader {
+        algorithm: "AWS4-HMAC-SHA256".to_string(),
+        credential: field("x-amz-credential")?.to_string(),
+        signed_headers: Vec::new(),
+        signature: field("x-amz-signature")?.to_string(),
+    };
+    let access_key = auth.access_key().map_err(|e| e.to_string())?;
+    let user = match state.metadata.get_user_by_access_key(&access_key).await {
+        Ok(Some(user)) => user,
+        Ok(None) => return Err("invalid access key".to_string()),
+        Err(e) => return Err(e.to_string()),
+    };
+
+    let validator = SigV4Validator::new(state.config.region.clone(), state.config.service.clone());
+    match validator.validate_policy_signature(policy, timestamp, &auth, &user.secret_key) {
+        Ok(true) => {}
+        Ok(false) => return Err("signature verification failed".to_string()),
+        Err(e) => return Err(e.to_string()),
+    }
+
+    if user.scope.is_some_and(|scope| !scope.allows(bucket, Some(key), true)) {
+        return Err(format!("access key {} may not write '{}/{}'", access_key, bucket, key));
+    }
+    Ok(())
+}
+
+/// Form upload handler (POST /{bucket} with multipart/form-data)
+///
+/// Fields before `file` are checked first: with auth enabled the policy must
+/// be present and correctly signed, and any policy must be unexpired and
+/// satisfied by the fields. The file is then streamed into the object,
+/// enforcing `content-length-range`; fields after it are ignored.
+pub async fn post_object(state: &AppState, bucket: &str, request: Request) -> std::result::Result<Response, StatusCode> {
+    match state.metadata.get_bucket(bucket).await {
+        Ok(Some(_)) => {}
+        Ok(None) => return Err(StatusCode::NOT_FOUND),
+        Err(e) => {
+            eprintln!("Failed to check bucket '{}': {}", bucket, e);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    }
+
+    let mut form = Multipart::from_request(request, &()).await.map_err(|_| StatusCode::BAD_REQUEST)?;
+    let mut fields = HashMap::new();
+    let mut file = loop {
+        let field = match form.next_field().await {
+            Ok(Some(field)) => field,
+            Ok(None) | Err(_) => return Err(StatusCode::BAD_REQUEST),
+        };
+        let name = field.name().unwrap_or_default().to_ascii_lowercase();
+        if name == "file" {
+            break field;
+        }
+        let value = field.text().await.map_err(|_| StatusCode::BAD_REQUEST)?;
+        fields.insert(name, value);
+    };
+
+    let key = match fields.get("key") {
+        Some(key) => key.replace("${filename}", file.file_name().unwrap_or_default()),
+        None => return Err(StatusCode::BAD_REQUEST),
+    };
+    // The file name is the client's, so the key is checked once it is in:
+    // a name like `../../x` must not lead out of the bucket
+    if let Err(e) = object_io_core::validate_object_key(&key) {
+        return Ok(object_error(&e, &key));
+    }
+    // Conditions apply to the key as stored and to the bucket from the path
+    fields.insert("key".to_string(), key.clone());
+    fields.insert("bucket".to_string(), bucket.to_string());
+
+    if state.config.auth_enabled {
+        if let Err(reason) = authorize(state, bucket, &key, &fields).await {
+            eprintln!("Form upload to '{}/{}' denied: {}", bucket, key, reason);
+            return Err(StatusCode::FORBIDDEN);
+        }
+    }
+    let policy = match fields.get("policy") {
+        Some(encoded) => Some(PostPolicy::decode(encoded).ok_or(StatusCode::BAD_REQUEST)?),
+        None => None,
+    };
+    if let Some(Err(reason)) = policy.as_ref().map(|policy| policy.check_fields(&fields)) {
+        eprintln!("Form upload to '{}/{}' denied by policy: {}", bucket, key, reason);
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    // Checked on the fields, as names that are not valid headers would be
+    // dropped below
+    let custom = fields
+        .iter()
+        .filter_map(|(name, value)| Some((name.strip_prefix("x-amz-meta-")?, value.as_str())));
+    if let Err(e) = object_io_core::validate_user_metadata(custom) {
+        return Ok(object_error(&e, &key));
+    }
+
+    // Form fields named like the headers a PUT would take become metadata
+    let mut headers = HeaderMap::new();
+    for (name, value) in &fields {
+        let stored = name == "content-type"
+            || name == CannedAcl::HEADER
+            || name.starts_with("x-amz-meta-")
+            || object_io_core::OBJECT_HEADERS.contains(&name.as_str());
+        if let (true, Ok(name), Ok(value)) = (stored, HeaderName::from_bytes(name.as_bytes()), HeaderValue::from_str(value)) {
+            headers.insert(name, value);
+        }
+    }
+    // The form's `acl` field takes the place of the `x-amz-acl` header
+    if let Some(acl) = fields.get("acl").and_then(|acl| HeaderValue::from_str(acl).ok()) {
+        headers.insert(CannedAcl::HEADER, acl);
+    }
+    if let Err(e) = key_patterns::check_key(state, bucket, &key).await {
+        return Ok(object_error(&e, &key));
+    }
+    let mut metadata = match object_metadata(&key, &headers, state.config.default_acl) {
+        Ok(metadata) => metadata,
+        Err(e) => return Ok(object_error(&e, &key)),
+    };
+    // Only the bucket's default retention applies, as there are no
+    // retention fields
+    match object_lock::new_object_retention(state, bucket, &headers).await {
+        Ok(Some(retention)) => retention.insert_into(&mut metadata),
+        Ok(None) => {}
+        Err(e) => return Ok(object_error(&e, &key)),
+    }
+
+    // Pipe the file into storage, failing the write if it breaks the
+    // policy's size range so nothing is kept
+    let (min, max) = policy.as_ref().map_or((0, u64::MAX), PostPolicy::length_range);
+    let (mut sender, receiver) = mpsc::channel::<std::io::Result<Bytes>>(1);
+    let pump = async move {
+        let mut size = 0u64;
+        let outcome = loop {
+            match file.chunk().await {
+                Ok(Some(chunk)) => {
+                    size += chunk.len() as u64;
+                    if size > max {
+                        break Err(format!("file exceeds the policy's {} byte limit", max));
+                    }
+                    if sender.send(Ok(chunk)).await.is_err() {
+                        // Storage gave up and reports its own error
+                        return Ok(());
+                    }
+                }
+                Ok(None) if size < min => break Err(format!("file is under the policy's {} byte minimum", min)),
+                Ok(None) => return Ok(()),
+                Err(e) => break Err(format!("malformed form data: {}", e)),
+            }
+        };
+        let _ = sender.send(Err(std::io::Error::other("form upload rejected"))).await;
+        outcome
+    };
+    let reader = Box::new(tokio_util::io::StreamReader::new(receiver));
+    let (pumped, stored) = tokio::join!(pump, store_object(state, bucket, &key, reader, metadata));
+
+    if let Err(reason) = pumped {
+        eprintln!("Form upload to '{}/{}' rejected: {}", bucket, key, reason);
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let object = match stored {
+        Ok(object) => object,
+        Err(e) => {
+            eprintln!("Failed to store object '{}/{}': {}", bucket, key, e);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+    let etag = format!("\"{}\"", object.etag);
+
+    if let Some(redirect) = fields.get("success_action_redirect").filter(|r| !r.is_empty()) {
+        let separator = if redirect.contains('?') { '&' } else { '?' };
+        let location = format!(
+            "{}{}bucket={}&key={}&etag={}",
+            redirect,
+            separator,
+            urlencoding::encode(bucket),
+            urlencoding::encode(&key),
+            urlencoding::encode(&etag)
+        );
+        return Ok(Response::builder()
+            .status(StatusCode::SEE_OTHER)
+            .header(header::LOCATION, location)
+            .header(header::ETAG, etag)
+            .body(Body::empty())
+            .unwrap());
+    }
+
+    let status = match fields.get("success_action_status").map(String::as_str) {
+        Some("200") => StatusCode::OK,
+        Some("201") => StatusCode::CREATED,
+        _ => StatusCode::NO_CONTENT,
+    };
+    let mut response = if status == StatusCode::CREATED {
+        to_xml_response(&PostResponse {
+            xmlns: S3_XMLNS,
+            location: format!("/{}/{}", bucket, urlencoding::encode(&key)),
+            bucket: bucket.to_string(),
+            key: key.clone(),
+            etag: etag.clone(),
+        })?
+    } else {
+        Response::new(Body::empty())
+    };
+    *response.status_mut() = status;
+    response
+        .headers_mut()
+        .insert(header::ETAG, HeaderValue::from_str(&etag).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?);
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{body::to_bytes, http::Method, Router};
+    use hmac::{Hmac, Mac};
+    use serde_json::json;
+    use sha2::Sha256;
+    use std::sync::Arc;
+    use tower::ServiceExt;
+
+    const ACCESS_KEY: &str = "AKIAFORMUPLOAD000001";
+    const SECRET_KEY: &str = "form-upload-secret";
+
+    fn hmac(key: &[u8], data: &str) -> Vec<u8> {
+        let mut mac = Hmac::<Sha256>::new_from_slice(key).unwrap();
+        mac.update(data.as_bytes());
+        mac.finalize().into_bytes().to_vec()
+    }
+
+    /// Encode a policy and the signing fields a browser form would carry
+    fn signed_fields(conditions: serde_json::Value, expires_in: chrono::Duration) -> Vec<(String, String)> {
+        let now = Utc::now();
+        let document = json!({
+            "expiration": (now + expires_in).format("%Y-%m-%dT%H:%M:%S%.3fZ").to_string(),
+            "conditions": conditions,
+        });
+        let policy = base64::engine::general_purpose::STANDARD.encode(document.to_string());
+
+        let date = now.format("%Y%m%d").to_string();
+        let mut key = hmac(format!("AWS4{}", SECRET_KEY).as_bytes(), &date);
+        for part in ["us-east-1", "s3", "aws4_request"] {
+            key = hmac(&key, part);
+        }
+        let signature = hex::encode(hmac(&key, &policy));
+
+        vec![
+            ("policy".to_string(), policy),
+            ("x-amz-algorithm".to_string(), "AWS4-HMAC-SHA256".to_string()),
+            (
+                "x-amz-credential".to_string(),
+                format!("{}/{}/us-east-1/s3/aws4_request", ACCESS_KEY, date),
+            ),
+            ("x-amz-date".to_string(), now.format("%Y%m%dT%H%M%SZ").to_string()),
+            ("x-amz-signature".to_string(), signature),
+        ]
+    }
+
+    async fn upload(app: &Router, fields: &[(String, String)], file: &[u8]) -> (StatusCode, HeaderMap, String) {
+        upload_named(app, fields, "photo.jpg", file).await
+    }
+
+    async fn upload_named(
+        app: &Router,
+        fields: &[(String, String)],
+        filename: &str,
+        file: &[u8],
+    ) -> (StatusCode, HeaderMap, String) {
+        let boundary = "----objectio-form-boundary";
+        let mut body = Vec::new();
+        for (name, value) in fields {
+            body.extend(format!("--{}\r\nContent-Disposition: form-data; name=\"{}\"\r\n\r\n{}\r\n", boundary, name, value).bytes());
+        }
+        body.extend(
+            format!(
+                "--{}\r\nContent-Disposition: form-data; name=\"file\"; filename=\"{}\"\r\nContent-Type: image/jpeg\r\n\r\n",
+                boundary, filename
+            )
+            .bytes(),
+        );
+        body.extend(file);
+        body.extend(format!("\r\n--{}--\r\n", boundary).bytes());
+
+        let request = Request::builder()
+            .method(Method::POST)
+            .uri("/uploads")
+            .header("content-type", format!("multipart/form-data; boundary={}", boundary))
+            .body(Body::from(body))
+            .unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        let status = response.status();
+        let headers = response.headers().clone();
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, headers, String::from_utf8_lossy(&body).into_owned())
+    }
+
+    fn with(mut fields: Vec<(String, String)>, extra: &[(&str, &str)]) -> Vec<(String, String)> {
+        fields.extend(extra.iter().map(|(name, value)| (name.to_string(), value.to_string())));
+        fields
+    }
+
+    #[tokio::test]
+    async fn test_form_upload() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut state = AppState::for_test(dir.path()).await;
+        let mut config = (*state.config).clone();
+        config.auth_enabled = true;
+        state.config = Arc::new(config);
+        state.metadata.create_user(ACCESS_KEY, SECRET_KEY, "Form Uploader").await.unwrap();
+        state.metadata.create_bucket("uploads", "owner").await.unwrap();
+        let app = crate::routes::create_router(state.clone());
+
+        let conditions = json!([
+            {"bucket": "uploads"},
+            ["starts-with", "$key", "user/"],
+            ["eq", "$Content-Type", "image/jpeg"],
+            ["content-length-range", 1, 10],
+        ]);
+        let hour = chrono::Duration::hours(1);
+        let valid = || with(signed_fields(conditions.clone(), hour), &[("key", "user/a.jpg"), ("Content-Type", "image/jpeg")]);
+
+        let (status, headers, _) = upload(&app, &valid(), b"jpeg").await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        let object = state.metadata.get_object("uploads", "user/a.jpg").await.unwrap().unwrap();
+        assert_eq!((object.size, object.content_type.as_str()), (4, "image/jpeg"));
+        assert_eq!(headers["etag"], format!("\"{}\"", object.etag));
+
+        // The file's name can be substituted into the key
+        let conditions_201 = json!([
+            ["starts-with", "$key", "user/"],
+            ["content-length-range", 1, 10],
+            {"success_action_status": "201"},
+        ]);
+        let fields = with(
+            signed_fields(conditions_201, hour),
+            &[("key", "user/${filename}"), ("success_action_status", "201")],
+        );
+        let (status, _, body) = upload(&app, &fields, b"jpeg").await;
+        assert_eq!(status, StatusCode::CREATED);
+        assert!(body.contains("<Key>user/photo.jpg</Key>"));
+
+        // A file name cannot lead the key out of the bucket, though the key
+        // still starts with "user/"
+        let (status, _, body) = upload_named(&app, &fields, "../../../escaped.jpg", b"jpeg").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(body.contains("<Code>InvalidKey</Code>"));
+        assert!(state.metadata.get_object("uploads", "user/../../../escaped.jpg").await.unwrap().is_none());
+        assert!(!dir.path().join("escaped.jpg").exists());
+
+        let conditions_redirect = json!([
+            ["starts-with", "$key", ""],
+            ["starts-with", "$success_action_redirect", "https://app.example/"],
+        ]);
+        let fields = with(
+            signed_fields(conditions_redirect, hour),
+            &[("key", "done.txt"), ("success_action_redirect", "https://app.example/done")],
+        );
+        let (status, headers, _) = upload(&app, &fields, b"x").await;
+        assert_eq!(status, StatusCode::SEE_OTHER);
+        let location = headers["location"].to_str().unwrap();
+        assert!(location.starts_with("https://app.example/done?bucket=uploads&key=done.txt&etag="));
+
+        // Policy violations store nothing
+        let outside = with(signed_fields(conditions.clone(), hour), &[("key", "admin/a.jpg"), ("Content-Type", "image/jpeg")]);
+        assert_eq!(upload(&app, &outside, b"jpeg").await.0, StatusCode::FORBIDDEN);
+        assert!(state.metadata.get_object("uploads", "admin/a.jpg").await.unwrap().is_none());
+
+        let extra = with(valid(), &[("x-amz-meta-owner", "mallory")]);
+        assert_eq!(upload(&app, &extra, b"jpeg").await.0, StatusCode::FORBIDDEN);
+
+        let too_large = with(signed_fields(conditions.clone(), hour), &[("key", "user/big.jpg"), ("Content-Type", "image/jpeg")]);
+        assert_eq!(upload(&app, &too_large, b"far too large").await.0, StatusCode::BAD_REQUEST);
+        assert!(!state.storage.object_exists("uploads", "user/big.jpg").await.unwrap());
+        assert!(state.metadata.get_object("uploads", "user/big.jpg").await.unwrap().is_none());
+
+        let empty = with(signed_fields(conditions.clone(), hour), &[("key", "user/empty.jpg"), ("Content-Type", "image/jpeg")]);
+        assert_eq!(upload(&app, &empty, b"").await.0, StatusCode::BAD_REQUEST);
+        assert!(!state.storage.object_exists("uploads", "user/empty.jpg").await.unwrap());
+
+        let expired = with(signed_fields(conditions.clone(), -hour), &[("key", "user/old.jpg"), ("Content-Type", "image/jpeg")]);
+        assert_eq!(upload(&app, &expired, b"jpeg").await.0, StatusCode::FORBIDDEN);
+
+        // Tampering with the policy breaks the signature
+        let mut forged = valid();
+        forged[0].1 = signed_fields(json!([["starts-with", "$key", ""]]), hour)[0].1.clone();
+        assert_eq!(upload(&app, &forged, b"jpeg").await.0, StatusCode::FORBIDDEN);
+
+        // Without a policy the request is unauthenticated
+        let unsigned = with(Vec::new(), &[("key", "user/anon.jpg")]);
+        assert_eq!(upload(&app, &unsigned, b"jpeg").await.0, StatusCode::FORBIDDEN);
+    }
+}
@@ -0,0 +1,210 @@
+//! Bucket key patterns (GET, PUT and DELETE /{bucket}?key-patterns)
+//!
+//! An ObjectIO extension: the owner of a bucket may restrict the keys
+//! written to it with `allow` and `deny` glob patterns, given as JSON such as
+//! `{"allow": ["uploads/*"], "deny": ["*.exe"]}`. Every path that writes a
+//! new key checks it: PUT and copy, multipart and form uploads, renames and
+//! archive imports. A key that breaks the patterns is refused with
+//! `403 AccessDenied`.
+
+use axum::{
+    body::Body,
+    http::StatusCode,
+    response::{IntoResponse, Json, Response},
+};
+use object_io_core::{KeyPatterns, ObjectIOError};
+
+use crate::{handlers::bucket::bucket_error, responses::xml::ErrorDocument, state::AppState};
+
+/// Largest key patterns document accepted, in bytes
+const MAX_CONFIGURATION_SIZE: usize = 256 * 1024;
+
+/// The patterns of `bucket`, which allow every key when it has none, or
+/// `BucketNotFound`
+pub(crate) async fn bucket_key_patterns(state: &AppState, bucket: &str) -> object_io_core::Result<KeyPatterns> {
+    match state.metadata.get_bucket(bucket).await? {
+        Some(bucket_info) => Ok(bucket_info.key_patterns.unwrap_or_default()),
+        None => Err(ObjectIOError::BucketNotFound {
+            bucket: bucket.to_string(),
+        }),
+    }
+}
+
+/// Fail with `AccessDenied` unless `key` may be written to `bucket`
+pub(crate) async fn check_key(state: &AppState, bucket: &str, key: &str) -> object_io_core::Result<()> {
+    bucket_key_patterns(state, bucket).await?.check(key)
+}
+
+/// Get a bucket's key patterns (GET /{bucket}?key-patterns)
+pub async fn get_bucket_key_patterns(state: &AppState, bucket: &str) -> std::result::Result<Response, StatusCode> {
+    let bucket_info = match state.metadata.get_bucket(bucket).await {
+        Ok(Some(bucket_info)) => bucket_info,
+        Ok(None) => return Err(StatusCode::NOT_FOUND),
+        Err(e) => {
+            eprintln!("Failed to check bucket '{}': {}", bucket, e);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+    match bucket_info.key_patterns {
+        Some(key_patterns) => Ok(Json(key_patterns).into_response()),
+        None => {
+            let message = "The bucket has no key patterns";
+            Ok(ErrorDocument::new("NoSuchKeyPatterns", message)
+                .with_bucket(bucket)
+                .into_response(StatusCode::NOT_FOUND))
+        }
+    }
+}
+
+/// Replace a bucket's key patterns (PUT /{bucket}?key-patterns)
+///
+/// Objects already in the bucket are kept whatever their keys; the patterns
+/// only apply to later writes. Empty `allow` and `deny` lists remove them.
+pub async fn put_bucket_key_patterns(
+    state: &AppState,
+    bucket: &str,
+    body: Body,
+) -> std::result::Result<Response, StatusCode> {
+    let body = axum::body::to_bytes(body, MAX_CONFIGURATION_SIZE)
+        .await
+        .map_err(|_| StatusCode::BAD_REQUEST)?;
+    let key_patterns = match serde_json::from_slice::<KeyPatterns>(&body) {
+        Ok(key_patterns) => key_patterns,
+        Err(e) => {
+            let error = ObjectIOError::InvalidArgument {
+                message: format!("Invalid key patterns: {}", e),
+            };
+            return Ok(bucket_error(&error, bucket));
+        }
+    };
+    if let Err(e) = key_patterns.validate() {
+        return Ok(bucket_error(&e, bucket));
+    }
+    let key_patterns = Some(key_patterns).filter(|patterns| !patterns.allow.is_empty() || !patterns.deny.is_empty());
+    set_bucket_key_patterns(state, bucket, key_patterns).await
+}
+
+/// Remove a bucket's key patterns (DELETE /{bucket}?key-patterns)
+pub async fn delete_bucket_key_patterns(state: &AppState, bucket: &str) -> std::result::Result<Response, StatusCode> {
+    set_bucket_key_patterns(state, bucket, None)
+        .await
+        .map(|_| StatusCode::NO_CONTENT.into_response())
+}
+
+async fn set_bucket_key_patterns(
+    state: &AppState,
+    bucket: &str,
+    key_patterns: Option<KeyPatterns>,
+) -> std::result::Result<Response, StatusCode> {
+    match state.metadata.set_bucket_key_patterns(bucket, key_patterns).await {
+        Ok(()) => Ok(StatusCode::OK.into_response()),
+        Err(ObjectIOError::BucketNotFound { .. }) => Err(StatusCode::NOT_FOUND),
+        Err(e) => {
+            eprintln!("Failed to set key patterns of '{}': {}", bucket, e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::handlers::object::{self, PutObjectQuery};
+    use axum::{
+        extract::{Path, Query, State},
+        http::HeaderMap,
+    };
+
+    async fn put(state: &AppState, key: &str) -> Response {
+        object::put_object(
+            Path(("inbox".to_string(), key.to_string())),
+            State(state.clone()),
+            None,
+            Query(PutObjectQuery {
+                content_type: None,
+                metadata: None,
+                part_number: None,
+                upload_id: None,
+                acl: None,
+                legal_hold: None,
+            }),
+            HeaderMap::new(),
+            Body::from("data"),
+        )
+        .await
+        .unwrap()
+    }
+
+    async fn body_text(response: Response) -> String {
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        String::from_utf8_lossy(&body).into_owned()
+    }
+
+    #[tokio::test]
+    async fn test_bucket_key_patterns_configuration() {
+        let dir = tempfile::tempdir().unwrap();
+        let state = AppState::for_test(dir.path()).await;
+        state.metadata.create_bucket("inbox", "owner").await.unwrap();
+
+        let response = get_bucket_key_patterns(&state, "inbox").await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert!(body_text(response).await.contains("NoSuchKeyPatterns"));
+
+        for invalid in [r#"{"allow": "uploads/*"}"#, r#"{"deny": [""]}"#, "{"] {
+            let response = put_bucket_key_patterns(&state, "inbox", Body::from(invalid)).await.unwrap();
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST, "{}", invalid);
+        }
+
+        let patterns = r#"{"allow": ["uploads/*"], "deny": ["*.exe"]}"#;
+        let response = put_bucket_key_patterns(&state, "inbox", Body::from(patterns)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let response = get_bucket_key_patterns(&state, "inbox").await.unwrap();
+        let stored: KeyPatterns = serde_json::from_str(&body_text(response).await).unwrap();
+        assert_eq!(stored.allow, ["uploads/*"]);
+        assert_eq!(stored.deny, ["*.exe"]);
+
+        // Empty lists remove the patterns, as DELETE does
+        let response = put_bucket_key_patterns(&state, "inbox", Body::from("{}")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(state.metadata.get_bucket("inbox").await.unwrap().unwrap().key_patterns.is_none());
+        put_bucket_key_patterns(&state, "inbox", Body::from(patterns)).await.unwrap();
+        let response = delete_bucket_key_patterns(&state, "inbox").await.unwrap();
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        assert!(state.metadata.get_bucket("inbox").await.unwrap().unwrap().key_patterns.is_none());
+
+        assert_eq!(
+            put_bucket_key_patterns(&state, "missing", Body::from(patterns)).await.unwrap_err(),
+            StatusCode::NOT_FOUND
+        );
+    }
+
+    #[tokio::test]
+    async fn test_writes_follow_key_patterns() {
+        let dir = tempfile::tempdir().unwrap();
+        let state = AppState::for_test(dir.path()).await;
+        state.metadata.create_bucket("inbox", "owner").await.unwrap();
+        put(&state, "notes.txt").await;
+        let patterns = KeyPatterns {
+            allow: vec!["uploads/*".to_string()],
+            deny: vec!["*.exe".to_string()],
+        };
+        state.metadata.set_bucket_key_patterns("inbox", Some(patterns)).await.unwrap();
+
+        assert_eq!(put(&state, "uploads/report.pdf").await.status(), StatusCode::OK);
+        assert!(state.storage.object_exists("inbox", "uploads/report.pdf").await.unwrap());
+
+        for denied in ["uploads/setup.exe", "other/report.pdf"] {
+            let response = put(&state, denied).await;
+            assert_eq!(response.status(), StatusCode::FORBIDDEN, "{}", denied);
+            assert!(body_text(response).await.contains("<Code>AccessDenied</Code>"));
+            assert!(!state.storage.object_exists("inbox", denied).await.unwrap());
+            assert!(state.metadata.get_object("inbox", denied).await.unwrap().is_none());
+        }
+
+        // Existing objects are kept, but cannot be renamed to a refused key
+        let response = object::rename_object(&state, "inbox", "notes.txt", "notes.exe", false).await.unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        let response = object::rename_object(&state, "inbox", "notes.txt", "uploads/notes.txt", false).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+}
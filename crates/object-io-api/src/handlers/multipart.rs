@@ -0,0 +1,717 @@
+//! Multipart upload handlers
+
+use axum::{
+    body::Body,
+    extract::{Path, Query, State},
+    http::{HeaderMap, StatusCode},
+    response::Response,
+    Extension,
+};
+use object_io_core::{CompletedPart, ListUploadsPage, ListUploadsRequest, ObjectIOError, UploadPart};
+use serde::{Deserialize, Serialize};
+use crate::{
+    auth::AuthContext,
+    handlers::{
+        object::{self, body_reader, object_error, object_metadata, record_object},
+        key_patterns, object_lock, presign,
+    },
+    responses::{to_xml_response, S3_XMLNS},
+    state::AppState,
+};
+
+/// Default and maximum page size for upload and part listings
+const MAX_LIST_ENTRIES: u32 = 1000;
+
+/// Byte range of the copy source an upload part copy takes
+pub const COPY_SOURCE_RANGE_HEADER: &str = "x-amz-copy-source-range";
+
+/// Object POST parameters: multipart upload, presign, rename or touch
+#[derive(Debug, Deserialize)]
+pub struct MultipartQuery {
+    pub uploads: Option<String>,
+    #[serde(rename = "uploadId")]
+    pub upload_id: Option<String>,
+    pub presign: Option<String>,
+    /// Presigned URL validity in seconds
+    pub expires: Option<u64>,
+    /// Destination key of a rename
+    pub rename: Option<String>,
+    /// Let a rename replace an existing object at the destination
+    #[serde(default)]
+    pub overwrite: bool,
+    /// Refresh the object's last-modified time
+    pub touch: Option<String>,
+}
+
+/// Initiate multipart upload response
+#[derive(Debug, Serialize)]
+#[serde(rename = "InitiateMultipartUploadResult")]
+pub struct InitiateMultipartUploadResult {
+    #[serde(rename = "@xmlns")]
+    pub xmlns: &'static str,
+    #[serde(rename = "Bucket")]
+    pub bucket: String,
+    #[serde(rename = "Key")]
+    pub key: String,
+    #[serde(rename = "UploadId")]
+    pub upload_id: String,
+}
+
+/// Upload part copy response
+#[derive(Debug, Serialize)]
+#[serde(rename = "CopyPartResult")]
+pub struct CopyPartResult {
+    #[serde(rename = "@xmlns")]
+    pub xmlns: &'static str,
+    #[serde(rename = "ETag")]
+    pub etag: String,
+    #[serde(rename = "LastModified")]
+    pub last_modified: String,
+}
+
+/// Complete multipart upload request body
+#[derive(Debug, Deserialize)]
+pub struct CompleteMultipartUploadRequest {
+    #[serde(rename = "Part", default)]
+    pub parts: Vec<CompletedPartXml>,
+}
+
+/// A part listed in a complete multipart upload request
+#[derive(Debug, Deserialize)]
+pub struct CompletedPartXml {
+    #[serde(rename = "PartNumber")]
+    pub part_number: u32,
+    #[serde(rename = "ETag")]
+    pub etag: String,
+}
+
+/// Complete multipart upload response
+#[derive(Debug, Serialize)]
+#[serde(rename = "CompleteMultipartUploadResult")]
+pub struct CompleteMultipartUploadResult {
+    #[serde(rename = "@xmlns")]
+    pub xmlns: &'static str,
+    #[serde(rename = "Bucket")]
+    pub bucket: String,
+    #[serde(rename = "Key")]
+    pub key: String,
+    #[serde(rename = "ETag")]
+    pub etag: String,
+}
+
+/// List multipart uploads parameters (GET /{bucket}?uploads)
+#[derive(Debug, Default, Deserialize)]
+pub struct ListMultipartUploadsQuery {
+    pub prefix: Option<String>,
+    #[serde(rename = "key-marker")]
+    pub key_marker: Option<String>,
+    #[serde(rename = "upload-id-marker")]
+    pub upload_id_marker: Option<String>,
+    #[serde(rename = "max-uploads")]
+    pub max_uploads: Option<u32>,
+}
+
+/// List parts parameters (GET /{bucket}/{key+}?uploadId=X)
+#[derive(Debug, Default, Deserialize)]
+pub struct ListPartsQuery {
+    #[serde(rename = "part-number-marker")]
+    pub part_number_marker: Option<u32>,
+    #[serde(rename = "max-parts")]
+    pub max_parts: Option<u32>,
+}
+
+/// List multipart uploads response
+#[derive(Debug, Serialize)]
+#[serde(rename = "ListMultipartUploadsResult")]
+pub struct ListMultipartUploadsResult {
+    #[serde(rename = "@xmlns")]
+    pub xmlns: &'static str,
+    #[serde(rename = "Bucket")]
+    pub bucket: String,
+    #[serde(rename = "KeyMarker")]
+    pub key_marker: String,
+    #[serde(rename = "UploadIdMarker")]
+    pub upload_id_marker: String,
+    #[serde(rename = "NextKeyMarker", skip_serializing_if = "Option::is_none")]
+    pub next_key_marker: Option<String>,
+    #[serde(rename = "NextUploadIdMarker", skip_serializing_if = "Option::is_none")]
+    pub next_upload_id_marker: Option<String>,
+    #[serde(rename = "Prefix")]
+    pub prefix: String,
+    #[serde(rename = "MaxUploads")]
+    pub max_uploads: u32,
+    #[serde(rename = "IsTruncated")]
+    pub is_truncated: bool,
+    #[serde(rename = "Upload")]
+    pub uploads: Vec<UploadEntry>,
+}
+
+/// An in-progress upload in a listing
+#[derive(Debug, Serialize)]
+pub struct UploadEntry {
+    #[serde(rename = "Key")]
+    pub key: String,
+    #[serde(rename = "UploadId")]
+    pub upload_id: String,
+    #[serde(rename = "Initiated")]
+    pub initiated: String,
+}
+
+/// List parts response
+#[derive(Debug, Serialize)]
+#[serde(rename = "ListPartsResult")]
+pub struct ListPartsResult {
+    #[serde(rename = "@xmlns")]
+    pub xmlns: &'static str,
+    #[serde(rename = "Bucket")]
+    pub bucket: String,
+    #[serde(rename = "Key")]
+    pub key: String,
+    #[serde(rename = "UploadId")]
+    pub upload_id: String,
+    #[serde(rename = "PartNumberMarker")]
+    pub part_number_marker: u32,
+    #[serde(rename = "NextPartNumberMarker", skip_serializing_if = "Option::is_none")]
+    pub next_part_number_marker: Option<u32>,
+    #[serde(rename = "MaxParts")]
+    pub max_parts: u32,
+    #[serde(rename = "IsTruncated")]
+    pub is_truncated: bool,
+    #[serde(rename = "Part")]
+    pub parts: Vec<PartEntry>,
+}
+
+/// An uploaded part in a listing
+#[derive(Debug, Serialize)]
+pub struct PartEntry {
+    #[serde(rename = "PartNumber")]
+    pub part_number: u32,
+    #[serde(rename = "LastModified")]
+    pub last_modified: String,
+    #[serde(rename = "ETag")]
+    pub etag: String,
+    #[serde(rename = "Size")]
+    pub size: u64,
+}
+
+/// Select one page of parts after the given part number, returning whether more remain
+fn paginate_parts(parts: Vec<UploadPart>, part_number_marker: u32, max_parts: usize) -> (Vec<UploadPart>, bool) {
+    let mut page: Vec<UploadPart> = parts
+        .into_iter()
+        .filter(|part| part.part_number > part_number_marker)
+        .take(max_parts + 1)
+        .collect();
+
+    let is_truncated = page.len() > max_parts;
+    page.truncate(max_parts);
+    (page, is_truncated)
+}
+
+/// Map multipart operation errors to status codes
+fn error_status(e: &ObjectIOError) -> StatusCode {
+    StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+/// Ensure the target bucket exists
+pub(crate) async fn check_bucket(state: &AppState, bucket: &str) -> std::result::Result<(), StatusCode> {
+    match state.metadata.get_bucket(bucket).await {
+        Ok(Some(_)) => Ok(()),
+        Ok(None) => Err(StatusCode::NOT_FOUND),
+        Err(e) => {
+            eprintln!("Failed to check bucket '{}': {}", bucket, e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+/// Object POST handler: initiate (?uploads) or complete (?uploadId=X) a
+/// multipart upload, presign a GET of the object (?presign), rename it
+/// (?rename=KEY) or refresh its last-modified time (?touch)
+pub async fn post_object(
+    Path((bucket, key)): Path<(String, String)>,
+    State(state): State<AppState>,
+    auth: Option<Extension<AuthContext>>,
+    Query(params): Query<MultipartQuery>,
+    headers: HeaderMap,
+    body: String,
+) -> std::result::Result<Response, StatusCode> {
+    check_bucket(&state, &bucket).await?;
+
+    if params.presign.is_some() {
+        return presign::presign_object(&state, &bucket, &key, &auth, &headers, params.expires).await;
+    }
+
+    if let Some(dst_key) = params.rename {
+        return object::rename_object(&state, &bucket, &key, &dst_key, params.overwrite).await;
+    }
+
+    if params.touch.is_some() {
+        return object::touch_object(&state, &bucket, &key).await;
+    }
+
+    if let Some(upload_id) = params.upload_id {
+        let bypass_governance = object_lock::bypass_governance(&headers, &auth);
+        return complete_multipart_upload(&state, bucket, key, &upload_id, &body, bypass_governance).await;
+    }
+    if params.uploads.is_none() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    if let Err(e) = key_patterns::check_key(&state, &bucket, &key).await {
+        return Ok(object_error(&e, &key));
+    }
+
+    let mut metadata = match object_metadata(&key, &headers, state.config.default_acl) {
+        Ok(metadata) => metadata,
+        Err(e) => return Ok(object_error(&e, &key)),
+    };
+    match object_lock::new_object_retention(&state, &bucket, &headers).await {
+        Ok(Some(retention)) => retention.insert_into(&mut metadata),
+        Ok(None) => {}
+        Err(e) => return Ok(object_error(&e, &key)),
+    }
+    match state.storage.create_multipart_upload(&bucket, &key, metadata).await {
+        Ok(upload_id) => to_xml_response(&InitiateMultipartUploadResult {
+            xmlns: S3_XMLNS,
+            bucket,
+            key,
+            upload_id,
+        }),
+        Err(e) => {
+            eprintln!("Failed to initiate multipart upload for '{}/{}': {}", bucket, key, e);
+            Err(error_status(&e))
+        }
+    }
+}
+
+/// Complete multipart upload (POST /{bucket}/{key+}?uploadId=X)
+///
+/// Fails without assembling the object if object lock protects the one it
+/// would replace.
+async fn complete_multipart_upload(
+    state: &AppState,
+    bucket: String,
+    key: String,
+    upload_id: &str,
+    body: &str,
+    bypass_governance: bool,
+) -> std::result::Result<Response, StatusCode> {
+    let request: CompleteMultipartUploadRequest = quick_xml::de::from_str(body).map_err(|e| {
+        eprintln!("Invalid complete multipart upload request: {}", e);
+        StatusCode::BAD_REQUEST
+    })?;
+
+    let parts: Vec<CompletedPart> = request
+        .parts
+        .into_iter()
+        .map(|part| CompletedPart {
+            part_number: part.part_number,
+            etag: object_io_core::normalize_etag(&part.etag).to_string(),
+        })
+        .collect();
+
+    // Size of the assembled object, for the metadata record
+    let uploaded = state.storage.list_parts(&bucket, &key, upload_id).await.map_err(|e| {
+        eprintln!("Failed to list parts of '{}': {}", upload_id, e);
+        error_status(&e)
+    })?;
+    let size = uploaded
+        .iter()
+        .filter(|uploaded| parts.iter().any(|part| part.part_number == uploaded.part_number))
+        .map(|uploaded| uploaded.size)
+        .sum();
+
+    let _lock = state.locks.lock(&bucket, &key).await;
+    match state.check_object_lock(&bucket, &key, bypass_governance).await {
+        Ok(()) => {}
+        Err(e @ ObjectIOError::ObjectLocked { .. }) => return Ok(object_error(&e, &key)),
+        Err(e) => {
+            eprintln!("Failed to check object lock of '{}/{}': {}", bucket, key, e);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    }
+    match state.storage.complete_multipart_upload(&bucket, &key, upload_id, &parts).await {
+        Ok(etag) => {
+            let metadata = state.storage.get_object_metadata(&bucket, &key).await.unwrap_or_default();
+            if let Err(e) = record_object(state, &bucket, &key, size, &etag, &metadata).await {
+                eprintln!("Failed to record object '{}/{}': {}", bucket, key, e);
+                return Err(StatusCode::INTERNAL_SERVER_ERROR);
+            }
+
+            to_xml_response(&CompleteMultipartUploadResult {
+                xmlns: S3_XMLNS,
+                bucket,
+                key,
+                etag: format!("\"{}\"", etag),
+            })
+        }
+        Err(e) => {
+            eprintln!("Failed to complete multipart upload '{}': {}", upload_id, e);
+            Err(error_status(&e))
+        }
+    }
+}
+
+/// Upload part (PUT /{bucket}/{key+}?partNumber=N&uploadId=X)
+pub(crate) async fn upload_part(
+    state: &AppState,
+    bucket: &str,
+    key: &str,
+    upload_id: &str,
+    part_number: u32,
+    body: Body,
+) -> std::result::Result<Response, StatusCode> {
+    check_bucket(state, bucket).await?;
+
+    match state.storage.upload_part(bucket, key, upload_id, part_number, body_reader(body)).await {
+        Ok(etag) => {
+            let response = Response::builder()
+                .status(StatusCode::OK)
+                .header("ETag", format!("\"{}\"", etag))
+                .body(Body::empty())
+                .unwrap();
+            Ok(response)
+        }
+        Err(e) => {
+            eprintln!("Failed to upload part {} of '{}': {}", part_number, upload_id, e);
+            Err(error_status(&e))
+        }
+    }
+}
+
+/// Resolve an `x-amz-copy-source-range` of `bytes=first-last` against a
+/// source object of `size` bytes, as an offset and length
+fn copy_source_range(range: &str, size: u64) -> object_io_core::Result<(u64, u64)> {
+    match object_io_core::parse_content_range(range) {
+        Some((first, Some(last))) if first <= last && last < size => Ok((first, last - first + 1)),
+        Some((_, Some(_))) => Err(ObjectIOError::InvalidArgument {
+            message: format!("Range specified is not valid for source object of size: {}", size),
+        }),
+        _ => Err(ObjectIOError::InvalidArgument {
+            message: "The x-amz-copy-source-range value must be of the form bytes=first-last where first and last \
+                      are the zero-based offsets of the first and last bytes to copy"
+                .to_string(),
+        }),
+    }
+}
+
+/// Upload part copy (PUT /{bucket}/{key+}?partNumber=N&uploadId=X with
+/// x-amz-copy-source)
+///
+/// The part is the whole source object, or the bytes named by
+/// `x-amz-copy-source-range`, read straight from storage.
+pub(crate) async fn upload_part_copy(
+    state: &AppState,
+    bucket: &str,
+    key: &str,
+    upload_id: &str,
+    part_number: u32,
+    headers: &HeaderMap,
+) -> std::result::Result<Response, StatusCode> {
+    check_bucket(state, bucket).await?;
+
+    let (source_bucket, source_key) = headers
+        .get("x-amz-copy-source")
+        .and_then(|source| source.to_str().ok())
+        .and_then(object_io_core::parse_copy_source)
+        .ok_or(StatusCode::BAD_REQUEST)?;
+    check_bucket(state, &source_bucket).await?;
+    let source = match state.metadata.get_object(&source_bucket, &source_key).await {
+        Ok(Some(source)) => source,
+        Ok(None) => {
+            let error = ObjectIOError::ObjectNotFound {
+                bucket: source_bucket,
+                key: source_key.clone(),
+            };
+            return Ok(object_error(&error, &source_key));
+        }
+        Err(e) => {
+            eprintln!("Failed to look up copy source '{}/{}': {}", source_bucket, source_key, e);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+    let (offset, length) = match headers.get(COPY_SOURCE_RANGE_HEADER) {
+        None => (0, source.size),
+        Some(range) => match copy_source_range(range.to_str().unwrap_or_default(), source.size) {
+            Ok(range) => range,
+            Err(e) => return Ok(object_error(&e, key)),
+        },
+    };
+
+    let reader = state
+        .storage
+        .get_object_range(&source_bucket, &source_key, offset, length)
+        .await
+        .map_err(|e| {
+            eprintln!("Failed to read copy source '{}/{}': {}", source_bucket, source_key, e);
+            error_status(&e)
+        })?;
+    match state.storage.upload_part(bucket, key, upload_id, part_number, reader).await {
+        Ok(etag) => to_xml_response(&CopyPartResult {
+            xmlns: S3_XMLNS,
+            etag: format!("\"{}\"", etag),
+            last_modified: object_io_core::format_s3_timestamp(&object_io_core::timestamp_now()),
+        }),
+        Err(e) => {
+            eprintln!("Failed to copy part {} of '{}': {}", part_number, upload_id, e);
+            Err(error_status(&e))
+        }
+    }
+}
+
+/// Abort multipart upload (DELETE /{bucket}/{key+}?uploadId=X)
+pub(crate) async fn abort_multipart_upload(
+    state: &AppState,
+    bucket: &str,
+    key: &str,
+    upload_id: &str,
+) -> std::result::Result<StatusCode, StatusCode> {
+    check_bucket(state, bucket).await?;
+
+    match state.storage.abort_multipart_upload(bucket, key, upload_id).await {
+        Ok(()) => Ok(StatusCode::NO_CONTENT),
+        Err(e) => {
+            eprintln!("Failed to abort multipart upload '{}': {}", upload_id, e);
+            Err(error_status(&e))
+        }
+    }
+}
+
+/// List multipart uploads (GET /{bucket}?uploads)
+pub(crate) async fn list_multipart_uploads(
+    state: &AppState,
+    bucket: String,
+    params: ListMultipartUploadsQuery,
+) -> std::result::Result<Response, StatusCode> {
+    check_bucket(state, &bucket).await?;
+
+    let max_uploads = params.max_uploads.unwrap_or(MAX_LIST_ENTRIES).min(MAX_LIST_ENTRIES);
+    let request = ListUploadsRequest {
+        bucket: bucket.clone(),
+        prefix: params.prefix.clone(),
+        key_marker: params.key_marker.clone(),
+        upload_id_marker: params.upload_id_marker.clone(),
+        max_uploads,
+    };
+    let ListUploadsPage { uploads: page, is_truncated } =
+        state.storage.list_multipart_uploads_page(&request).await.map_err(|e| {
+            eprintln!("Failed to list multipart uploads in '{}': {}", bucket, e);
+            error_status(&e)
+        })?;
+
+    let last = page.last().filter(|_| is_truncated);
+    to_xml_response(&ListMultipartUploadsResult {
+        xmlns: S3_XMLNS,
+        next_key_marker: last.map(|upload| upload.key.clone()),
+        next_upload_id_marker: last.map(|upload| upload.upload_id.clone()),
+        bucket,
+        key_marker: params.key_marker.unwrap_or_default(),
+        upload_id_marker: params.upload_id_marker.unwrap_or_default(),
+        prefix: params.prefix.unwrap_or_default(),
+        max_uploads,
+        is_truncated,
+        uploads: page
+            .into_iter()
+            .map(|upload| UploadEntry {
+                key: upload.key,
+                upload_id: upload.upload_id,
+                initiated: object_io_core::format_s3_timestamp(&upload.initiated),
+            })
+            .collect(),
+    })
+}
+
+/// List parts (GET /{bucket}/{key+}?uploadId=X)
+pub(crate) async fn list_parts(
+    state: &AppState,
+    bucket: String,
+    key: String,
+    upload_id: String,
+    params: ListPartsQuery,
+) -> std::result::Result<Response, StatusCode> {
+    check_bucket(state, &bucket).await?;
+
+    let parts = state.storage.list_parts(&bucket, &key, &upload_id).await.map_err(|e| {
+        eprintln!("Failed to list parts of '{}': {}", upload_id, e);
+        error_status(&e)
+    })?;
+
+    let part_number_marker = params.part_number_marker.unwrap_or(0);
+    let max_parts = params.max_parts.unwrap_or(MAX_LIST_ENTRIES).min(MAX_LIST_ENTRIES);
+    let (page, is_truncated) = paginate_parts(parts, part_number_marker, max_parts as usize);
+
+    to_xml_response(&ListPartsResult {
+        xmlns: S3_XMLNS,
+        bucket,
+        key,
+        upload_id,
+        part_number_marker,
+        next_part_number_marker: page.last().filter(|_| is_truncated).map(|part| part.part_number),
+        max_parts,
+        is_truncated,
+        parts: page
+            .into_iter()
+            .map(|part| PartEntry {
+                part_number: part.part_number,
+                last_modified: object_io_core::format_s3_timestamp(&part.last_modified),
+                etag: format!("\"{}\"", part.etag),
+                size: part.size,
+            })
+            .collect(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::to_bytes;
+    use chrono::Utc;
+    use object_io_storage::multipart::MIN_PART_SIZE;
+    use std::collections::HashMap;
+
+    fn part(part_number: u32) -> UploadPart {
+        UploadPart {
+            part_number,
+            etag: format!("etag{}", part_number),
+            size: 1,
+            last_modified: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn test_paginate_parts() {
+        let parts = vec![part(1), part(2), part(5)];
+
+        let (page, truncated) = paginate_parts(parts.clone(), 0, 2);
+        assert_eq!(page.iter().map(|p| p.part_number).collect::<Vec<_>>(), [1, 2]);
+        assert!(truncated);
+
+        let (page, truncated) = paginate_parts(parts, 2, 2);
+        assert_eq!(page.iter().map(|p| p.part_number).collect::<Vec<_>>(), [5]);
+        assert!(!truncated);
+    }
+
+    #[tokio::test]
+    async fn test_list_multipart_uploads_pages() {
+        let dir = tempfile::tempdir().unwrap();
+        let state = AppState::for_test(dir.path()).await;
+        state.metadata.create_bucket("media", "owner").await.unwrap();
+        let mut expected = Vec::new();
+        for key in ["video/b.mp4", "video/a.mp4", "audio/c.mp3", "video/a.mp4", "video/d.mp4"] {
+            let upload_id = state.storage.create_multipart_upload("media", key, HashMap::new()).await.unwrap();
+            expected.push((key.to_string(), upload_id));
+        }
+        expected.sort();
+        let expected: Vec<String> = expected
+            .into_iter()
+            .filter(|(key, _)| key.starts_with("video/"))
+            .map(|(_, id)| id)
+            .collect();
+
+        let mut listed = Vec::new();
+        let mut params = ListMultipartUploadsQuery {
+            prefix: Some("video/".to_string()),
+            max_uploads: Some(2),
+            ..ListMultipartUploadsQuery::default()
+        };
+        loop {
+            let response = list_multipart_uploads(&state, "media".to_string(), params).await.unwrap();
+            let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+            let body = String::from_utf8_lossy(&body).into_owned();
+            let tag = |name: &str| {
+                let (open, close) = (format!("<{}>", name), format!("</{}>", name));
+                body.split(&open)
+                    .skip(1)
+                    .map(|rest| rest.split(&close).next().unwrap().to_string())
+                    .collect::<Vec<_>>()
+            };
+            let page = tag("UploadId");
+            assert!(page.len() <= 2, "{}", body);
+            listed.extend(page.iter().cloned());
+            if tag("IsTruncated") != ["true"] {
+                assert!(tag("NextKeyMarker").is_empty(), "{}", body);
+                break;
+            }
+
+            // Resume after the last upload listed
+            assert_eq!(tag("NextUploadIdMarker"), page[page.len() - 1..]);
+            params = ListMultipartUploadsQuery {
+                prefix: Some("video/".to_string()),
+                key_marker: tag("NextKeyMarker").pop(),
+                upload_id_marker: tag("NextUploadIdMarker").pop(),
+                max_uploads: Some(2),
+            };
+        }
+        assert_eq!(listed, expected);
+
+        let missing = list_multipart_uploads(&state, "missing".to_string(), ListMultipartUploadsQuery::default()).await;
+        assert_eq!(missing.unwrap_err(), StatusCode::NOT_FOUND);
+    }
+
+    #[test]
+    fn test_copy_source_range() {
+        assert_eq!(copy_source_range("bytes=0-9", 10).unwrap(), (0, 10));
+        assert_eq!(copy_source_range("bytes=4-4", 10).unwrap(), (4, 1));
+        for range in ["bytes=0-10", "bytes=5-4", "bytes=5-", "bytes=-5", "0-4", ""] {
+            assert!(matches!(copy_source_range(range, 10), Err(ObjectIOError::InvalidArgument { .. })), "{}", range);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_upload_part_copy_assembles_object() {
+        let dir = tempfile::tempdir().unwrap();
+        let state = AppState::for_test(dir.path()).await;
+        state.metadata.create_bucket("media", "owner").await.unwrap();
+        let source: Vec<u8> = (0..MIN_PART_SIZE + 1000).map(|i| (i % 251) as u8).collect();
+        let reader = Box::new(std::io::Cursor::new(source.clone()));
+        object::store_object(&state, "media", "source.bin", reader, HashMap::new()).await.unwrap();
+
+        let upload_id = state.storage.create_multipart_upload("media", "copy.bin", HashMap::new()).await.unwrap();
+        let copy_part = |part_number: u32, range: Option<&str>| {
+            let mut headers = HeaderMap::new();
+            headers.insert("x-amz-copy-source", "/media/source.bin".parse().unwrap());
+            if let Some(range) = range {
+                headers.insert(COPY_SOURCE_RANGE_HEADER, range.parse().unwrap());
+            }
+            let (state, upload_id) = (state.clone(), upload_id.clone());
+            async move { upload_part_copy(&state, "media", "copy.bin", &upload_id, part_number, &headers).await }
+        };
+
+        // The source split in two at the minimum part size
+        let mut parts = String::new();
+        for (part_number, range) in [
+            (1, format!("bytes=0-{}", MIN_PART_SIZE - 1)),
+            (2, format!("bytes={}-{}", MIN_PART_SIZE, source.len() - 1)),
+        ] {
+            let response = copy_part(part_number, Some(&range)).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+            let body = String::from_utf8_lossy(&body).into_owned();
+            assert!(body.contains("<CopyPartResult"), "{}", body);
+            let etag = body.split("<ETag>").nth(1).unwrap().split("</ETag>").next().unwrap().to_string();
+            parts.push_str(&format!("<Part><PartNumber>{}</PartNumber><ETag>{}</ETag></Part>", part_number, etag));
+        }
+        let listed = state.storage.list_parts("media", "copy.bin", &upload_id).await.unwrap();
+        assert_eq!(listed.iter().map(|part| part.size).collect::<Vec<_>>(), [MIN_PART_SIZE, 1000]);
+
+        let body = format!("<CompleteMultipartUpload>{}</CompleteMultipartUpload>", parts);
+        let (bucket, key) = ("media".to_string(), "copy.bin".to_string());
+        let response = complete_multipart_upload(&state, bucket, key, &upload_id, &body, false).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let mut copy = Vec::new();
+        let mut reader = state.storage.get_object("media", "copy.bin").await.unwrap();
+        tokio::io::AsyncReadExt::read_to_end(&mut reader, &mut copy).await.unwrap();
+        assert!(copy == source, "assembled object differs from its source");
+        let record = state.metadata.get_object("media", "copy.bin").await.unwrap().unwrap();
+        assert_eq!(record.size, source.len() as u64);
+
+        // Ranges outside the source and missing sources are refused
+        let response = copy_part(3, Some("bytes=0-99999999")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let response = copy_part(3, Some("bytes=0-")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let mut headers = HeaderMap::new();
+        headers.insert("x-amz-copy-source", "/media/missing.bin".parse().unwrap());
+        let response = upload_part_copy(&state, "media", "copy.bin", &upload_id, 3, &headers).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+}
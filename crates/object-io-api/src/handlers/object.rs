@@ -1,16 +1,36 @@
 //! Object operation handlers
 
 use axum::{
-    body::Body,
+    body::{Body, Bytes},
     extract::{Path, Query, State},
     http::{HeaderMap, StatusCode},
     response::Response,
+    Extension,
 };
+use async_compression::tokio::bufread::GzipDecoder;
+use chrono::{DateTime, SubsecRound, Utc};
 use futures::StreamExt;
-use serde::Deserialize;
+use object_io_core::{
+    object_lock as lock, CannedAcl, ConditionalPut, Object, ObjectIOError, ObjectRetention, VersioningStatus,
+};
+use object_io_storage::scrub;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use tokio::io::AsyncReadExt;
-use crate::state::AppState;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use tokio::io::{AsyncRead, AsyncReadExt, ReadBuf};
+use tokio_util::io::ReaderStream;
+use crate::{
+    auth::AuthContext,
+    aws_chunked::{ChunkedUpload, DecodeError},
+    idempotency::{Claim, RecordedPut, IDEMPOTENCY_HEADER},
+    handlers::{acl, bucket, key_patterns, multipart, object_lock},
+    responses::{to_xml_response, xml::ErrorDocument, S3_XMLNS},
+    spool::{self, SpooledBody},
+    state::AppState,
+};
 
 /// Put object parameters
 #[derive(Debug, Deserialize)]
@@ -19,25 +39,288 @@ pub struct PutObjectQuery {
     pub content_type: Option<String>,
     #[serde(rename = "x-amz-meta-")]
     pub metadata: Option<HashMap<String, String>>,
+    #[serde(rename = "partNumber")]
+    pub part_number: Option<u32>,
+    #[serde(rename = "uploadId")]
+    pub upload_id: Option<String>,
+    pub acl: Option<String>,
+    #[serde(rename = "legal-hold")]
+    pub legal_hold: Option<String>,
 }
 
-/// Get object parameters
+/// Delete object parameters
 #[derive(Debug, Deserialize)]
+pub struct DeleteObjectQuery {
+    #[serde(rename = "uploadId")]
+    pub upload_id: Option<String>,
+}
+
+/// Get object parameters
+#[derive(Debug, Default, Deserialize)]
 pub struct GetObjectQuery {
     #[serde(rename = "response-content-type")]
     pub response_content_type: Option<String>,
     #[serde(rename = "response-content-disposition")]
     pub response_content_disposition: Option<String>,
+    #[serde(rename = "uploadId")]
+    pub upload_id: Option<String>,
+    #[serde(rename = "part-number-marker")]
+    pub part_number_marker: Option<u32>,
+    #[serde(rename = "max-parts")]
+    pub max_parts: Option<u32>,
+    pub acl: Option<String>,
+    #[serde(rename = "legal-hold")]
+    pub legal_hold: Option<String>,
+    /// Check the data against the stored ETag while streaming it
+    pub verify: Option<bool>,
+    /// Decode a gzip-encoded object while streaming it
+    pub decompress: Option<bool>,
+}
+
+/// Extract object metadata from request headers
+///
+/// Fails if the `x-amz-meta-*` headers break `validate_user_metadata`.
+pub(crate) fn object_metadata(
+    key: &str,
+    headers: &HeaderMap,
+    default_acl: CannedAcl,
+) -> object_io_core::Result<HashMap<String, String>> {
+    let mut metadata = HashMap::new();
+    let acl = canned_acl(headers, default_acl)?;
+    metadata.insert(CannedAcl::HEADER.to_string(), acl.as_str().to_string());
+
+    // Add content type, guessing from the key when the client didn't send one
+    let content_type = headers
+        .get("content-type")
+        .and_then(|ct| ct.to_str().ok())
+        .unwrap_or_else(|| object_io_core::guess_content_type(key));
+    metadata.insert("content-type".to_string(), content_type.to_string());
+
+    // Add standard headers stored with the object
+    for name in object_io_core::OBJECT_HEADERS {
+        if let Some(value) = headers.get(name).and_then(|value| value.to_str().ok()) {
+            metadata.insert(name.to_string(), value.to_string());
+        }
+    }
+    // `aws-chunked` describes how the upload was framed, not the object
+    if let Some(encoding) = metadata.remove("content-encoding") {
+        let codings: Vec<&str> = encoding.split(',').map(str::trim).filter(|coding| *coding != "aws-chunked").collect();
+        if !codings.is_empty() {
+            metadata.insert("content-encoding".to_string(), codings.join(", "));
+        }
+    }
+
+    // Add custom metadata (x-amz-meta-* headers)
+    let custom: Vec<(&str, &str)> = headers
+        .iter()
+        .filter_map(|(name, value)| Some((name.as_str().strip_prefix("x-amz-meta-")?, value.to_str().ok()?)))
+        .collect();
+    object_io_core::validate_user_metadata(custom.iter().copied())?;
+    for (name, value) in custom {
+        metadata.insert(name.to_string(), value.to_string());
+    }
+
+    Ok(metadata)
+}
+
+/// Canned ACL requested with `x-amz-acl`, or `default` when none was sent
+pub(crate) fn canned_acl(headers: &HeaderMap, default: CannedAcl) -> object_io_core::Result<CannedAcl> {
+    match headers.get(CannedAcl::HEADER) {
+        None => Ok(default),
+        Some(value) => value
+            .to_str()
+            .ok()
+            .and_then(CannedAcl::parse)
+            .ok_or_else(|| object_io_core::ObjectIOError::InvalidArgument {
+                message: format!("Unsupported canned ACL: {}", String::from_utf8_lossy(value.as_bytes())),
+            }),
+    }
+}
+
+/// S3 XML error response for a request on an object
+pub(crate) fn object_error(error: &object_io_core::ObjectIOError, key: &str) -> Response {
+    let status = StatusCode::from_u16(error.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+    ErrorDocument::from_error(error).with_key(key).into_response(status)
+}
+
+/// Convert a request body to an async reader
+pub(crate) fn body_reader(body: Body) -> Box<dyn AsyncRead + Send + Unpin> {
+    Box::new(tokio_util::io::StreamReader::new(
+        body.into_data_stream().map(|result| result.map_err(std::io::Error::other))
+    ))
+}
+
+/// Copy object response
+#[derive(Debug, Serialize)]
+#[serde(rename = "CopyObjectResult")]
+pub struct CopyObjectResult {
+    #[serde(rename = "@xmlns")]
+    pub xmlns: &'static str,
+    #[serde(rename = "ETag")]
+    pub etag: String,
+    #[serde(rename = "LastModified")]
+    pub last_modified: String,
+}
+
+/// Counts the bytes read through it, so a streamed upload's size is known afterwards
+struct CountingReader {
+    inner: Box<dyn AsyncRead + Send + Unpin>,
+    count: Arc<AtomicU64>,
+}
+
+impl AsyncRead for CountingReader {
+    fn poll_read(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<std::io::Result<()>> {
+        let before = buf.filled().len();
+        let result = Pin::new(&mut self.inner).poll_read(cx, buf);
+        if let Poll::Ready(Ok(())) = result {
+            self.count.fetch_add((buf.filled().len() - before) as u64, Ordering::Relaxed);
+        }
+        result
+    }
+}
+
+/// Record a stored object in the metadata database
+///
+/// `metadata` is in storage form: custom metadata plus a `content-type` entry.
+pub(crate) async fn record_object(
+    state: &AppState,
+    bucket: &str,
+    key: &str,
+    size: u64,
+    etag: &str,
+    metadata: &HashMap<String, String>,
+) -> object_io_core::Result<object_io_core::Object> {
+    let mut custom = metadata.clone();
+    let content_type = custom
+        .remove("content-type")
+        .unwrap_or_else(|| "application/octet-stream".to_string());
+
+    state.metadata
+        .put_object_metadata(bucket, key, size, &content_type, etag, custom)
+        .await
+}
+
+/// Stream an object's data to storage and record it in the metadata database
+///
+/// Holds the object's lock throughout, so conditional operations never see
+/// the data and the record out of step. Concurrent writers of a key are thus
+/// applied one at a time in the order they took the lock, and the last one
+/// wins with the recorded ETag always that of the bytes in place; the
+/// backend installs each object in one step, so readers see either the old
+/// object or the new one. `metadata` is in storage form, as for
+/// `record_object`.
+pub(crate) async fn store_object(
+    state: &AppState,
+    bucket: &str,
+    key: &str,
+    reader: Box<dyn AsyncRead + Send + Unpin>,
+    metadata: HashMap<String, String>,
+) -> object_io_core::Result<object_io_core::Object> {
+    store_object_if(state, bucket, key, reader, metadata, &ConditionalPut::default(), false).await
+}
+
+/// Store an object as `store_object` does, if the object it replaces meets
+/// `condition` and object lock allows replacing it
+pub(crate) async fn store_object_if(
+    state: &AppState,
+    bucket: &str,
+    key: &str,
+    reader: Box<dyn AsyncRead + Send + Unpin>,
+    metadata: HashMap<String, String>,
+    condition: &ConditionalPut,
+    bypass_governance: bool,
+) -> object_io_core::Result<object_io_core::Object> {
+    let _lock = state.locks.lock(bucket, key).await;
+    state.check_object_lock(bucket, key, bypass_governance).await?;
+    let size = Arc::new(AtomicU64::new(0));
+    let reader = Box::new(CountingReader {
+        inner: reader,
+        count: size.clone(),
+    });
+    let etag = state.storage.put_object_if(bucket, key, reader, metadata.clone(), condition).await?;
+    record_object(state, bucket, key, size.load(Ordering::Relaxed), &etag, &metadata).await
+}
+
+/// Storage-form metadata and validators an object is served with
+struct StoredObject {
+    metadata: HashMap<String, String>,
+    /// The remaining fields are unknown for objects the metadata database
+    /// has no record of
+    last_modified: Option<DateTime<Utc>>,
+    etag: Option<String>,
+    size: Option<u64>,
+    retention: Option<ObjectRetention>,
+    legal_hold: bool,
+}
+
+/// The metadata of an object recorded in the metadata database, in the
+/// form it was stored in: custom metadata, standard headers and content type
+pub(crate) fn storage_metadata(object: &Object) -> HashMap<String, String> {
+    let mut metadata = object.metadata.clone();
+    metadata.extend(object.headers().map(|(name, value)| (name.to_string(), value.to_string())));
+    metadata.insert("content-type".to_string(), object.content_type.clone());
+    metadata
+}
+
+impl StoredObject {
+    /// An object as recorded in the metadata database
+    fn from_record(object: Object) -> Self {
+        StoredObject {
+            metadata: storage_metadata(&object),
+            last_modified: Some(object.last_modified),
+            etag: Some(object.etag),
+            size: Some(object.size),
+            retention: object.retention,
+            legal_hold: object.legal_hold,
+        }
+    }
+}
+
+/// Object metadata for response headers, preferring the metadata database
+/// and falling back to the storage backend for objects it has no record of
+async fn stored_object(state: &AppState, bucket: &str, key: &str) -> StoredObject {
+    match state.metadata.get_object(bucket, key).await {
+        Ok(Some(object)) => StoredObject::from_record(object),
+        _ => StoredObject {
+            metadata: state.storage.get_object_metadata(bucket, key).await.unwrap_or_default(),
+            last_modified: None,
+            etag: None,
+            size: None,
+            retention: None,
+            legal_hold: false,
+        },
+    }
 }
 
 /// Put object handler (PUT /{bucket}/{key+})
 pub async fn put_object(
     Path((bucket, key)): Path<(String, String)>,
     State(state): State<AppState>,
-    Query(_params): Query<PutObjectQuery>,
+    auth: Option<Extension<AuthContext>>,
+    Query(params): Query<PutObjectQuery>,
     headers: HeaderMap,
     body: Body,
 ) -> std::result::Result<Response, StatusCode> {
+    // Upload part (PUT /{bucket}/{key+}?partNumber=N&uploadId=X), or copy one
+    // from another object with x-amz-copy-source
+    if let (Some(part_number), Some(upload_id)) = (params.part_number, params.upload_id) {
+        if headers.contains_key("x-amz-copy-source") {
+            return multipart::upload_part_copy(&state, &bucket, &key, &upload_id, part_number, &headers).await;
+        }
+        return multipart::upload_part(&state, &bucket, &key, &upload_id, part_number, body).await;
+    }
+
+    // Replace the object's ACL (PUT /{bucket}/{key+}?acl)
+    if params.acl.is_some() {
+        return acl::put_object_acl(&state, &bucket, &key, &auth, &headers, body).await;
+    }
+
+    // Place or remove a legal hold (PUT /{bucket}/{key+}?legal-hold), owner or admin only
+    if params.legal_hold.is_some() {
+        bucket::check_bucket_owner(&state, &bucket, &auth).await?;
+        return object_lock::put_object_legal_hold(&state, &bucket, &key, body).await;
+    }
+
     // Check if bucket exists
     match state.metadata.get_bucket(&bucket).await {
         Ok(Some(_)) => {},
@@ -48,55 +331,625 @@ pub async fn put_object(
         }
     }
 
-    // Extract metadata from headers
-    let mut metadata = HashMap::new();
-    
-    // Add content type
-    if let Some(content_type) = headers.get("content-type") {
-        if let Ok(ct_str) = content_type.to_str() {
-            metadata.insert("content-type".to_string(), ct_str.to_string());
-        }
+    if let Err(e) = key_patterns::check_key(&state, &bucket, &key).await {
+        return Ok(object_error(&e, &key));
     }
+    let retention = match object_lock::new_object_retention(&state, &bucket, &headers).await {
+        Ok(retention) => retention,
+        Err(e) => return Ok(object_error(&e, &key)),
+    };
+    let bypass_governance = object_lock::bypass_governance(&headers, &auth);
 
-    // Add custom metadata (x-amz-meta-* headers)
-    for (name, value) in headers.iter() {
-        if let Some(name_str) = name.as_str().strip_prefix("x-amz-meta-") {
-            if let Ok(value_str) = value.to_str() {
-                metadata.insert(name_str.to_string(), value_str.to_string());
-            }
-        }
+    // Copy object (PUT /{bucket}/{key+} with x-amz-copy-source)
+    if let Some(copy_source) = headers.get("x-amz-copy-source") {
+        let copy_source = copy_source.to_str().map_err(|_| StatusCode::BAD_REQUEST)?;
+        return copy_object(&state, &bucket, &key, copy_source, &headers, retention, bypass_governance).await;
     }
 
-    // Convert body to async reader
-    let body_stream = tokio_util::io::StreamReader::new(
-        body.into_data_stream().map(|result| {
-            result.map_err(|err| std::io::Error::new(std::io::ErrorKind::Other, err))
-        })
-    );
+    let mut metadata = match object_metadata(&key, &headers, state.config.default_acl) {
+        Ok(metadata) => metadata,
+        Err(e) => return Ok(object_error(&e, &key)),
+    };
+    if let Some(retention) = &retention {
+        retention.insert_into(&mut metadata);
+    }
+    let condition = match put_condition(&headers) {
+        Ok(condition) => condition,
+        Err(e) => return Ok(object_error(&e, &key)),
+    };
+
+    // Decode aws-chunked uploads, checking any trailing checksum
+    let (reader, decode_error) = match ChunkedUpload::from_headers(&headers) {
+        Ok(Some(upload)) => upload.decode(body),
+        Ok(None) => (body_reader(body), DecodeError::default()),
+        Err(e) => return Ok(object_error(&e, &key)),
+    };
+
+    // A retry of a PUT made with an idempotency key is answered from the
+    // first attempt's outcome rather than written again, and refused while
+    // the first attempt is still being written
+    let idempotency = headers
+        .get(IDEMPOTENCY_HEADER)
+        .and_then(|token| token.to_str().ok())
+        .filter(|_| state.config.idempotency_window > 0)
+        .map(|token| (auth.as_ref().map_or("", |Extension(auth)| auth.user_id.as_str()), token));
+    let reservation = match idempotency.map(|(caller, token)| state.idempotency.claim(caller, token, Utc::now())) {
+        None => None,
+        Some(Claim::Reserved(reservation)) => Some(reservation),
+        Some(Claim::Recorded(recorded)) => {
+            return replay_put(&bucket, &key, reader, &recorded, &decode_error).await;
+        }
+        Some(Claim::InFlight) => {
+            let message = "A request with the same idempotency key is still in progress.";
+            return Ok(ErrorDocument::new("IdempotencyKeyInUse", message)
+                .with_key(&key)
+                .into_response(StatusCode::CONFLICT));
+        }
+        Some(Claim::Full) => {
+            return Ok(ErrorDocument::new("SlowDown", "Please reduce your request rate.")
+                .into_response(StatusCode::SERVICE_UNAVAILABLE));
+        }
+    };
+
+    // Read the body ahead of storing it, in memory or in a temporary file
+    let reader = if state.config.upload_memory_threshold > 0 {
+        let temp_dir = spool::spool_dir(state.config.storage_temp_path.as_deref());
+        match SpooledBody::read(reader, state.config.upload_memory_threshold, &temp_dir).await {
+            Ok(body) => body.into_reader(),
+            Err(e) => match decode_error.take() {
+                Some(e) => return Ok(object_error(&e, &key)),
+                None => {
+                    eprintln!("Failed to read body for '{}/{}': {}", bucket, key, e);
+                    return Err(StatusCode::INTERNAL_SERVER_ERROR);
+                }
+            },
+        }
+    } else {
+        reader
+    };
 
     // Store object
-    match state.storage.put_object(&bucket, &key, Box::new(body_stream), metadata).await {
-        Ok(etag) => {
+    match store_object_if(&state, &bucket, &key, reader, metadata, &condition, bypass_governance).await {
+        Ok(object) => {
+            if let Some(reservation) = reservation {
+                let window = chrono::Duration::seconds(state.config.idempotency_window as i64);
+                reservation.record(RecordedPut::new(&bucket, &key, &object.etag, window, Utc::now()));
+            }
             let response = Response::builder()
                 .status(StatusCode::OK)
-                .header("ETag", format!("\"{}\"", etag))
+                .header("ETag", format!("\"{}\"", object.etag))
                 .body(Body::empty())
                 .unwrap();
             Ok(response)
         }
+        Err(e) => match decode_error.take() {
+            Some(e) => Ok(object_error(&e, &key)),
+            None => match e {
+                ObjectIOError::PreconditionFailed { .. }
+                | ObjectIOError::ObjectNotFound { .. }
+                | ObjectIOError::ObjectLocked { .. } => Ok(object_error(&e, &key)),
+                e => {
+                    eprintln!("Failed to store object '{}/{}': {}", bucket, key, e);
+                    Err(StatusCode::INTERNAL_SERVER_ERROR)
+                }
+            },
+        },
+    }
+}
+
+/// Preconditions a PUT places on the object it replaces: `If-Match`, and
+/// `If-None-Match: *` (the only form S3 accepts on writes)
+fn put_condition(headers: &HeaderMap) -> object_io_core::Result<ConditionalPut> {
+    let header = |name: &str| {
+        headers
+            .get(name)
+            .map(|value| value.to_str().map(str::to_string))
+            .transpose()
+            .map_err(|_| ObjectIOError::InvalidArgument {
+                message: format!("{} is not a valid header value", name),
+            })
+    };
+    let if_none_match_star = match header("if-none-match")? {
+        Some(value) if value.trim() == "*" => true,
+        Some(_) => {
+            return Err(ObjectIOError::NotImplemented {
+                message: "If-None-Match on PUT only supports *".to_string(),
+            })
+        }
+        None => false,
+    };
+    Ok(ConditionalPut {
+        if_match: header("if-match")?,
+        if_none_match_star,
+    })
+}
+
+/// Answer a retried PUT from the recorded outcome of the first attempt
+///
+/// The retry's data is hashed but not stored. If it is the same PUT, the
+/// first attempt's ETag is returned; anything else reusing the key is a
+/// `409 Conflict`.
+async fn replay_put(
+    bucket: &str,
+    key: &str,
+    mut reader: Box<dyn AsyncRead + Send + Unpin>,
+    recorded: &RecordedPut,
+    decode_error: &DecodeError,
+) -> std::result::Result<Response, StatusCode> {
+    let mut hasher = object_io_core::EtagHasher::new();
+    let mut buf = vec![0u8; 64 * 1024];
+    loop {
+        match reader.read(&mut buf).await {
+            Ok(0) => break,
+            Ok(n) => hasher.update(&buf[..n]),
+            Err(e) => {
+                if let Some(error) = decode_error.take() {
+                    return Ok(object_error(&error, key));
+                }
+                eprintln!("Failed to read retried upload of '{}/{}': {}", bucket, key, e);
+                return Err(StatusCode::BAD_REQUEST);
+            }
+        }
+    }
+
+    if !recorded.matches(bucket, key, &hasher.finalize()) {
+        let message = "The idempotency key was already used for a different request.";
+        return Ok(ErrorDocument::new("IdempotencyKeyMismatch", message)
+            .with_key(key)
+            .into_response(StatusCode::CONFLICT));
+    }
+    Ok(Response::builder()
+        .status(StatusCode::OK)
+        .header("ETag", format!("\"{}\"", recorded.etag))
+        .body(Body::empty())
+        .unwrap())
+}
+
+/// Copy object (PUT /{bucket}/{key+} with x-amz-copy-source)
+///
+/// Copying an object onto itself with `x-amz-metadata-directive: REPLACE`
+/// updates its metadata in place without rewriting the data. Either way the
+/// destination gets `retention`, and object lock must allow replacing it.
+async fn copy_object(
+    state: &AppState,
+    bucket: &str,
+    key: &str,
+    copy_source: &str,
+    headers: &HeaderMap,
+    retention: Option<ObjectRetention>,
+    bypass_governance: bool,
+) -> std::result::Result<Response, StatusCode> {
+    let (source_bucket, source_key) = object_io_core::parse_copy_source(copy_source)
+        .ok_or(StatusCode::BAD_REQUEST)?;
+    let replace_metadata = match headers.get("x-amz-metadata-directive").map(|v| v.to_str()) {
+        None => false,
+        Some(Ok(directive)) if directive.eq_ignore_ascii_case("COPY") => false,
+        Some(Ok(directive)) if directive.eq_ignore_ascii_case("REPLACE") => true,
+        Some(_) => return Err(StatusCode::BAD_REQUEST),
+    };
+
+    if state.metadata.get_bucket(&source_bucket).await.ok().flatten().is_none() {
+        return Err(StatusCode::NOT_FOUND);
+    }
+
+    let object = if source_bucket == bucket && source_key == key {
+        // Copying onto itself only makes sense when replacing the metadata
+        if !replace_metadata {
+            return Err(StatusCode::BAD_REQUEST);
+        }
+
+        let mut metadata = match object_metadata(key, headers, state.config.default_acl) {
+            Ok(metadata) => metadata,
+            Err(e) => return Ok(object_error(&e, key)),
+        };
+        if let Some(retention) = &retention {
+            retention.insert_into(&mut metadata);
+        }
+        let content_type = metadata.remove("content-type").unwrap_or_default();
+        let _lock = state.locks.lock(bucket, key).await;
+        if let Err(e) = state.check_object_lock(bucket, key, bypass_governance).await {
+            return Ok(object_error(&e, key));
+        }
+        match state.metadata.update_object_metadata(bucket, key, &content_type, metadata).await {
+            Ok(object) => object,
+            Err(object_io_core::ObjectIOError::ObjectNotFound { .. }) => return Err(StatusCode::NOT_FOUND),
+            Err(e) => {
+                eprintln!("Failed to update metadata of '{}/{}': {}", bucket, key, e);
+                return Err(StatusCode::INTERNAL_SERVER_ERROR);
+            }
+        }
+    } else {
+        let mut metadata = if replace_metadata {
+            match object_metadata(key, headers, state.config.default_acl) {
+                Ok(metadata) => metadata,
+                Err(e) => return Ok(object_error(&e, key)),
+            }
+        } else {
+            // Like S3, the copy gets the requested or default ACL rather
+            // than the source's
+            let mut metadata = stored_object(state, &source_bucket, &source_key).await.metadata;
+            match canned_acl(headers, state.config.default_acl) {
+                Ok(acl) => metadata.insert(CannedAcl::HEADER.to_string(), acl.as_str().to_string()),
+                Err(e) => return Ok(object_error(&e, key)),
+            };
+            metadata
+        };
+        if let Some(retention) = &retention {
+            retention.insert_into(&mut metadata);
+        }
+
+        let reader = match state.storage.get_object(&source_bucket, &source_key).await {
+            Ok(reader) => reader,
+            Err(object_io_core::ObjectIOError::ObjectNotFound { .. }) => return Err(StatusCode::NOT_FOUND),
+            Err(e) => {
+                eprintln!("Failed to read copy source '{}/{}': {}", source_bucket, source_key, e);
+                return Err(StatusCode::INTERNAL_SERVER_ERROR);
+            }
+        };
+
+        let condition = ConditionalPut::default();
+        match store_object_if(state, bucket, key, reader, metadata, &condition, bypass_governance).await {
+            Ok(object) => object,
+            Err(e @ ObjectIOError::ObjectLocked { .. }) => return Ok(object_error(&e, key)),
+            Err(e) => {
+                eprintln!("Failed to copy object to '{}/{}': {}", bucket, key, e);
+                return Err(StatusCode::INTERNAL_SERVER_ERROR);
+            }
+        }
+    };
+
+    to_xml_response(&CopyObjectResult {
+        xmlns: S3_XMLNS,
+        etag: format!("\"{}\"", object.etag),
+        last_modified: object_io_core::format_s3_timestamp(&object.last_modified),
+    })
+}
+
+/// Rename an object within its bucket (POST /{bucket}/{key+}?rename=<dstKey>)
+///
+/// An ObjectIO extension, as S3 has no rename. The object keeps its ETag,
+/// metadata and ACL. An existing object at the destination is only replaced
+/// when `overwrite` is set.
+pub(crate) async fn rename_object(
+    state: &AppState,
+    bucket: &str,
+    key: &str,
+    dst_key: &str,
+    overwrite: bool,
+) -> std::result::Result<Response, StatusCode> {
+    if let Err(e) = object_io_core::validate_object_key(dst_key) {
+        return Ok(object_error(&e, dst_key));
+    }
+    if dst_key == key {
+        let error = object_io_core::ObjectIOError::InvalidRequest {
+            message: "An object cannot be renamed to its own key".to_string(),
+        };
+        return Ok(object_error(&error, key));
+    }
+    match key_patterns::check_key(state, bucket, dst_key).await {
+        Ok(()) => {}
+        Err(e @ ObjectIOError::AuthorizationFailed { .. }) => return Ok(object_error(&e, dst_key)),
+        Err(e) => {
+            eprintln!("Failed to check key patterns of '{}': {}", bucket, e);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    }
+
+    // Both keys are locked, always in the same order so that concurrent
+    // renames between them cannot deadlock
+    let (first, second) = if key < dst_key { (key, dst_key) } else { (dst_key, key) };
+    let _first = state.locks.lock(bucket, first).await;
+    let _second = state.locks.lock(bucket, second).await;
+
+    let internal_error = |e: object_io_core::ObjectIOError| {
+        eprintln!("Failed to rename '{}/{}' to '{}': {}", bucket, key, dst_key, e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    };
+    if state.metadata.get_object(bucket, key).await.map_err(internal_error)?.is_none() {
+        let error = object_io_core::ObjectIOError::ObjectNotFound {
+            bucket: bucket.to_string(),
+            key: key.to_string(),
+        };
+        return Ok(object_error(&error, key));
+    }
+    // Renaming deletes the source and may replace the destination
+    for locked_key in [key, dst_key] {
+        match state.check_object_lock(bucket, locked_key, false).await {
+            Ok(()) => {}
+            Err(e @ ObjectIOError::ObjectLocked { .. }) => return Ok(object_error(&e, locked_key)),
+            Err(e) => return Err(internal_error(e)),
+        }
+    }
+    if !overwrite {
+        let exists = state.metadata.get_object(bucket, dst_key).await.map_err(internal_error)?.is_some()
+            || state.storage.object_exists(bucket, dst_key).await.map_err(internal_error)?;
+        if exists {
+            let error = object_io_core::ObjectIOError::ObjectAlreadyExists {
+                bucket: bucket.to_string(),
+                key: dst_key.to_string(),
+            };
+            return Ok(object_error(&error, dst_key));
+        }
+    }
+
+    state.storage.move_object(bucket, key, dst_key).await.map_err(internal_error)?;
+    let object = state.metadata.move_object(bucket, key, dst_key).await.map_err(internal_error)?;
+
+    to_xml_response(&CopyObjectResult {
+        xmlns: S3_XMLNS,
+        etag: format!("\"{}\"", object.etag),
+        last_modified: object_io_core::format_s3_timestamp(&object.last_modified),
+    })
+}
+
+/// Refresh an object's last-modified time (POST /{bucket}/{key+}?touch)
+///
+/// Only the record changes: the data, ETag and metadata stay as they are.
+pub(crate) async fn touch_object(state: &AppState, bucket: &str, key: &str) -> std::result::Result<Response, StatusCode> {
+    let _lock = state.locks.lock(bucket, key).await;
+    match state.metadata.touch_object(bucket, key).await {
+        Ok(object) => to_xml_response(&CopyObjectResult {
+            xmlns: S3_XMLNS,
+            etag: format!("\"{}\"", object.etag),
+            last_modified: object_io_core::format_s3_timestamp(&object.last_modified),
+        }),
+        Err(e @ ObjectIOError::ObjectNotFound { .. }) => Ok(object_error(&e, key)),
+        Err(e) => {
+            eprintln!("Failed to touch '{}/{}': {}", bucket, key, e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+/// Whether a stored metadata entry is user metadata, returned as an
+/// `x-amz-meta-*` header, rather than a standard header
+pub(crate) fn is_user_metadata(name: &str) -> bool {
+    !name.starts_with("content-")
+        && !object_io_core::OBJECT_HEADERS.contains(&name)
+        && ![CannedAcl::HEADER, lock::MODE_HEADER, lock::RETAIN_UNTIL_HEADER].contains(&name)
+}
+
+/// Add the content type, stored standard headers, user metadata, `ETag`,
+/// `Last-Modified` and any retention or legal hold, shared by GET and HEAD so both return
+/// the same metadata
+fn with_object_headers(
+    mut builder: axum::http::response::Builder,
+    object: &StoredObject,
+) -> axum::http::response::Builder {
+    let metadata = &object.metadata;
+    if let Some(last_modified) = &object.last_modified {
+        builder = builder.header("last-modified", object_io_core::format_http_date(last_modified));
+    }
+    if let Some(etag) = &object.etag {
+        builder = builder.header("etag", format!("\"{}\"", etag));
+    }
+
+    let content_type = metadata
+        .get("content-type")
+        .map(String::as_str)
+        .unwrap_or("application/octet-stream");
+    builder = builder.header("content-type", content_type);
+
+    for name in object_io_core::OBJECT_HEADERS {
+        if let Some(value) = metadata.get(name) {
+            builder = builder.header(name, value);
+        }
+    }
+    for (name, value) in metadata.iter().filter(|(name, _)| is_user_metadata(name)) {
+        builder = builder.header(format!("x-amz-meta-{}", name), value);
+    }
+    if let Some(retention) = &object.retention {
+        builder = builder
+            .header(lock::MODE_HEADER, retention.mode.as_str())
+            .header(lock::RETAIN_UNTIL_HEADER, object_io_core::format_s3_timestamp(&retention.retain_until));
+    }
+    if object.legal_hold {
+        builder = builder.header(lock::LEGAL_HOLD_HEADER, "ON");
+    }
+    builder
+}
+
+/// Part of an object a GET returns
+#[derive(Debug, PartialEq, Eq)]
+enum ReadRange {
+    Full,
+    /// Inclusive byte offsets into an object of `size` bytes
+    Partial { first: u64, last: u64, size: u64 },
+    /// The range starts past the end of an object of `size` bytes
+    Unsatisfiable { size: u64 },
+}
+
+/// Whether an `If-Range` validator (an ETag or HTTP date) still matches the
+/// object. Weak ETags never match, and dates must equal `Last-Modified`.
+fn if_range_matches(if_range: &str, object: &StoredObject) -> bool {
+    let if_range = if_range.trim();
+    if if_range.starts_with("W/") {
+        false
+    } else if if_range.starts_with('"') {
+        object
+            .etag
+            .as_deref()
+            .is_some_and(|etag| object_io_core::etags_equal(if_range, etag))
+    } else {
+        object
+            .last_modified
+            .is_some_and(|last_modified| object_io_core::format_http_date(&last_modified) == if_range)
+    }
+}
+
+/// Whether an `If-Modified-Since` date shows the client's copy is current
+///
+/// `Last-Modified` has whole seconds, so the stored time is truncated before
+/// comparing. Invalid dates, and objects with no recorded time, never match.
+fn not_modified_since(headers: &HeaderMap, object: &StoredObject) -> bool {
+    let Some(since) = headers
+        .get("if-modified-since")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| object_io_core::parse_http_date(v).ok())
+    else {
+        return false;
+    };
+    object
+        .last_modified
+        .is_some_and(|last_modified| last_modified.trunc_subsecs(0) <= since)
+}
+
+/// Resolve the `Range` and `If-Range` headers of a GET
+///
+/// Malformed or multi-part ranges, objects of unknown size and a stale
+/// `If-Range` all fall back to the full object.
+fn read_range(headers: &HeaderMap, object: &StoredObject) -> ReadRange {
+    let (Some(range), Some(size)) = (headers.get("range").and_then(|v| v.to_str().ok()), object.size) else {
+        return ReadRange::Full;
+    };
+    if let Some(if_range) = headers.get("if-range") {
+        if !if_range.to_str().is_ok_and(|if_range| if_range_matches(if_range, object)) {
+            return ReadRange::Full;
+        }
+    }
+
+    match object_io_core::parse_content_range(range) {
+        Some((first, Some(last))) if last < first => ReadRange::Full,
+        Some((first, _)) if first >= size => ReadRange::Unsatisfiable { size },
+        Some((first, last)) => ReadRange::Partial {
+            first,
+            last: last.unwrap_or(u64::MAX).min(size - 1),
+            size,
+        },
+        None => ReadRange::Full,
+    }
+}
+
+/// Whether an object was stored with `Content-Encoding: gzip`
+fn is_gzip_encoded(object: &StoredObject) -> bool {
+    object
+        .metadata
+        .get("content-encoding")
+        .is_some_and(|encoding| ["gzip", "x-gzip"].iter().any(|gzip| encoding.trim().eq_ignore_ascii_case(gzip)))
+}
+
+/// Body for data whose length isn't known until it has all been read, such
+/// as an object decoded as it streams
+///
+/// Without a `Content-Length` it is sent with `Transfer-Encoding: chunked`
+/// over HTTP/1.1. A read error ends the body early: the connection is
+/// dropped without the closing chunk, so clients see a failed transfer
+/// rather than a short object.
+fn streamed_body(reader: impl AsyncRead + Send + 'static) -> Body {
+    Body::from_stream(ReaderStream::new(reader))
+}
+
+/// Stream an object while recomputing its ETag
+///
+/// Each chunk is held back until the next one arrives, so the last is only
+/// sent once the whole object has been hashed. If the data does not match
+/// `etag`, the body ends with an error in its place: the connection is
+/// dropped without completing the response, and the client never holds the
+/// full object.
+fn verified_body(reader: Box<dyn AsyncRead + Send + Unpin>, bucket: String, key: String, etag: String) -> Body {
+    struct Verifier {
+        chunks: ReaderStream<Box<dyn AsyncRead + Send + Unpin>>,
+        hasher: object_io_core::EtagHasher,
+        held: Option<Bytes>,
+        bucket: String,
+        key: String,
+        etag: String,
+    }
+
+    let verifier = Verifier {
+        chunks: ReaderStream::new(reader),
+        hasher: object_io_core::EtagHasher::new(),
+        held: None,
+        bucket,
+        key,
+        etag,
+    };
+    let stream = futures::stream::unfold(Some(verifier), |verifier| async move {
+        let mut verifier = verifier?;
+        loop {
+            match verifier.chunks.next().await {
+                Some(Ok(chunk)) => {
+                    verifier.hasher.update(&chunk);
+                    if let Some(previous) = verifier.held.replace(chunk) {
+                        return Some((Ok(previous), Some(verifier)));
+                    }
+                }
+                Some(Err(e)) => return Some((Err(e), None)),
+                None => {
+                    let actual = verifier.hasher.finalize();
+                    if object_io_core::etags_equal(&actual, &verifier.etag) {
+                        return verifier.held.map(|last| (Ok(last), None));
+                    }
+                    eprintln!(
+                        "Object '{}/{}' is corrupted: expected ETag {}, data hashes to {}",
+                        verifier.bucket, verifier.key, verifier.etag, actual
+                    );
+                    let error = std::io::Error::new(std::io::ErrorKind::InvalidData, "object data is corrupted");
+                    return Some((Err(error), None));
+                }
+            }
+        }
+    });
+    Body::from_stream(stream)
+}
+
+/// Answer a GET or HEAD of a missing key
+///
+/// A key whose last delete left a delete marker is answered like S3 does,
+/// with `x-amz-delete-marker` and the marker's `x-amz-version-id`, so clients
+/// can tell it from a key that never existed.
+async fn object_not_found(state: &AppState, bucket: &str, key: &str) -> std::result::Result<Response, StatusCode> {
+    match state.metadata.get_delete_marker(bucket, key).await {
+        Ok(Some(version_id)) => Ok(Response::builder()
+            .status(StatusCode::NOT_FOUND)
+            .header("x-amz-delete-marker", "true")
+            .header("x-amz-version-id", version_id)
+            .body(Body::empty())
+            .unwrap()),
+        Ok(None) => Err(StatusCode::NOT_FOUND),
         Err(e) => {
-            eprintln!("Failed to store object '{}/{}': {}", bucket, key, e);
+            eprintln!("Failed to look up delete marker for '{}/{}': {}", bucket, key, e);
             Err(StatusCode::INTERNAL_SERVER_ERROR)
         }
     }
 }
 
 /// Get object handler (GET /{bucket}/{key+})
+///
+/// Supports a single `Range`, optionally guarded by `If-Range`, and answers
+/// `304 Not Modified` to an `If-Modified-Since` the object is not newer than.
+///
+/// With `verify=true` the full object is streamed through [`verified_body`],
+/// ignoring any `Range`. Objects whose ETag is not a hash of their data
+/// (multipart objects, and data with no metadata record) are refused with
+/// `400 Bad Request`.
 pub async fn get_object(
     Path((bucket, key)): Path<(String, String)>,
     State(state): State<AppState>,
-    Query(_params): Query<GetObjectQuery>,
+    auth: Option<Extension<AuthContext>>,
+    Query(params): Query<GetObjectQuery>,
+    headers: HeaderMap,
 ) -> std::result::Result<Response, StatusCode> {
+    // Get the object's ACL (GET /{bucket}/{key+}?acl)
+    if params.acl.is_some() {
+        return acl::get_object_acl(&state, &bucket, &key, &auth).await;
+    }
+
+    // Get the object's legal hold (GET /{bucket}/{key+}?legal-hold), owner or admin only
+    if params.legal_hold.is_some() {
+        bucket::check_bucket_owner(&state, &bucket, &auth).await?;
+        return object_lock::get_object_legal_hold(&state, &bucket, &key).await;
+    }
+
+    // List parts (GET /{bucket}/{key+}?uploadId=X)
+    if let Some(upload_id) = params.upload_id {
+        let list_params = multipart::ListPartsQuery {
+            part_number_marker: params.part_number_marker,
+            max_parts: params.max_parts,
+        };
+        return multipart::list_parts(&state, bucket, key, upload_id, list_params).await;
+    }
+
     // Check if bucket exists
     match state.metadata.get_bucket(&bucket).await {
         Ok(Some(_)) => {},
@@ -107,23 +960,74 @@ pub async fn get_object(
         }
     }
 
+    // Get object metadata for headers and to resolve the requested range
+    let mut object = stored_object(&state, &bucket, &key).await;
+    if not_modified_since(&headers, &object) {
+        let mut response_builder = Response::builder().status(StatusCode::NOT_MODIFIED);
+        if let Some(last_modified) = &object.last_modified {
+            response_builder = response_builder.header("last-modified", object_io_core::format_http_date(last_modified));
+        }
+        if let Some(etag) = &object.etag {
+            response_builder = response_builder.header("etag", format!("\"{}\"", etag));
+        }
+        return Ok(response_builder.body(Body::empty()).unwrap());
+    }
+    let verify_etag = match (params.verify, &object.etag) {
+        (Some(true), Some(etag)) if !scrub::is_composite_etag(etag) => Some(etag.clone()),
+        (Some(true), _) => return Err(StatusCode::BAD_REQUEST),
+        _ => None,
+    };
+    // Objects stored gzip-encoded may be decoded on the way out
+    // (?decompress=true); the rest are sent as stored
+    let decompress = params.decompress == Some(true) && is_gzip_encoded(&object);
+    if decompress && verify_etag.is_some() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let range = if verify_etag.is_some() || decompress {
+        ReadRange::Full
+    } else {
+        read_range(&headers, &object)
+    };
+
+    let data = match range {
+        ReadRange::Full => state.storage.get_object(&bucket, &key).await,
+        ReadRange::Partial { first, last, .. } => {
+            state.storage.get_object_range(&bucket, &key, first, last - first + 1).await
+        }
+        ReadRange::Unsatisfiable { size } => {
+            return Ok(Response::builder()
+                .status(StatusCode::RANGE_NOT_SATISFIABLE)
+                .header("content-range", format!("bytes */{}", size))
+                .body(Body::empty())
+                .unwrap());
+        }
+    };
+
     // Get object from storage
-    match state.storage.get_object(&bucket, &key).await {
+    match data {
         Ok(mut reader) => {
-            // Get object metadata for headers
-            let metadata = match state.storage.get_object_metadata(&bucket, &key).await {
-                Ok(meta) => meta,
-                Err(_) => HashMap::new(),
+            // Create response with appropriate headers
+            let mut response_builder = Response::builder().header("accept-ranges", "bytes");
+            response_builder = match range {
+                ReadRange::Partial { first, last, size } => response_builder
+                    .status(StatusCode::PARTIAL_CONTENT)
+                    .header("content-range", format!("bytes {}-{}/{}", first, last, size)),
+                _ => response_builder.status(StatusCode::OK),
             };
 
-            // Create response with appropriate headers
-            let mut response_builder = Response::builder().status(StatusCode::OK);
+            // Set content type, the stored headers, user metadata and Last-Modified
+            if decompress {
+                object.metadata.remove("content-encoding");
+            }
+            response_builder = with_object_headers(response_builder, &object);
 
-            // Set content type
-            if let Some(content_type) = metadata.get("content-type") {
-                response_builder = response_builder.header("content-type", content_type);
-            } else {
-                response_builder = response_builder.header("content-type", "application/octet-stream");
+            if let Some(etag) = verify_etag {
+                return Ok(response_builder.body(verified_body(reader, bucket, key, etag)).unwrap());
+            }
+            if decompress {
+                let mut decoder = GzipDecoder::new(tokio::io::BufReader::new(reader));
+                decoder.multiple_members(true);
+                return Ok(response_builder.body(streamed_body(decoder)).unwrap());
             }
 
             // Read the data to create body
@@ -139,7 +1043,7 @@ pub async fn get_object(
             Ok(response)
         }
         Err(object_io_core::ObjectIOError::ObjectNotFound { .. }) => {
-            Err(StatusCode::NOT_FOUND)
+            object_not_found(&state, &bucket, &key).await
         }
         Err(e) => {
             eprintln!("Failed to get object '{}/{}': {}", bucket, key, e);
@@ -163,69 +1067,120 @@ pub async fn head_object(
         }
     }
 
-    // Check if object exists and get metadata
-    match state.storage.object_exists(&bucket, &key).await {
-        Ok(true) => {
-            // Get object metadata for headers
-            let metadata = match state.storage.get_object_metadata(&bucket, &key).await {
-                Ok(meta) => meta,
-                Err(_) => HashMap::new(),
-            };
-
-            let mut response_builder = Response::builder().status(StatusCode::OK);
-
-            // Set content type
-            if let Some(content_type) = metadata.get("content-type") {
-                response_builder = response_builder.header("content-type", content_type);
-            } else {
-                response_builder = response_builder.header("content-type", "application/octet-stream");
-            }
-
-            // Add custom metadata as x-amz-meta-* headers
-            for (key, value) in metadata.iter() {
-                if !key.starts_with("content-") {
-                    response_builder = response_builder.header(
-                        format!("x-amz-meta-{}", key),
-                        value
-                    );
-                }
-            }
-
-            let response = response_builder
-                .body(Body::empty())
-                .unwrap();
-            Ok(response)
-        }
-        Ok(false) => {
-            Err(StatusCode::NOT_FOUND)
-        }
+    // One storage lookup answers whether the data exists and describes it;
+    // a metadata record, when there is one, takes precedence for the headers
+    let head = match state.storage.head_object(&bucket, &key).await {
+        Ok(head) => head,
+        Err(ObjectIOError::ObjectNotFound { .. }) => return object_not_found(&state, &bucket, &key).await,
         Err(e) => {
             eprintln!("Failed to check object '{}/{}': {}", bucket, key, e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
         }
-    }
+    };
+    let object = match state.metadata.get_object(&bucket, &key).await {
+        Ok(Some(object)) => StoredObject::from_record(object),
+        _ => StoredObject {
+            metadata: head.metadata,
+            last_modified: Some(head.last_modified),
+            etag: head.etag,
+            size: Some(head.size),
+            retention: None,
+            legal_hold: false,
+        },
+    };
+
+    // Set content type, the stored headers, user metadata and Last-Modified
+    let response = with_object_headers(Response::builder().status(StatusCode::OK), &object)
+        .body(Body::empty())
+        .unwrap();
+    Ok(response)
 }
 
 /// Delete object handler (DELETE /{bucket}/{key+})
+///
+/// With `If-Match`, the object is only deleted if its current ETag matches;
+/// the check and the delete happen under the object's lock, as does the
+/// object lock check that answers `403` for objects under retention.
 pub async fn delete_object(
     Path((bucket, key)): Path<(String, String)>,
     State(state): State<AppState>,
+    auth: Option<Extension<AuthContext>>,
+    Query(params): Query<DeleteObjectQuery>,
+    headers: HeaderMap,
 ) -> std::result::Result<StatusCode, StatusCode> {
+    // Abort multipart upload (DELETE /{bucket}/{key+}?uploadId=X)
+    if let Some(upload_id) = params.upload_id {
+        return multipart::abort_multipart_upload(&state, &bucket, &key, &upload_id).await;
+    }
+
     // Check if bucket exists
-    match state.metadata.get_bucket(&bucket).await {
-        Ok(Some(_)) => {},
+    let versioning = match state.metadata.get_bucket(&bucket).await {
+        Ok(Some(bucket)) => bucket.versioning,
         Ok(None) => return Err(StatusCode::NOT_FOUND),
         Err(e) => {
             eprintln!("Failed to check bucket '{}': {}", bucket, e);
             return Err(StatusCode::INTERNAL_SERVER_ERROR);
         }
+    };
+
+    let _lock = state.locks.lock(&bucket, &key).await;
+
+    match state.check_object_lock(&bucket, &key, object_lock::bypass_governance(&headers, &auth)).await {
+        Ok(()) => {}
+        Err(ObjectIOError::ObjectLocked { .. }) => return Err(StatusCode::FORBIDDEN),
+        Err(e) => {
+            eprintln!("Failed to check object lock of '{}/{}': {}", bucket, key, e);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    }
+
+    if let Some(if_match) = headers.get("if-match") {
+        let if_match = if_match.to_str().map_err(|_| StatusCode::BAD_REQUEST)?;
+        // Objects stored without a record are compared by the ETag the
+        // backend keeps, if any, so only `*` matches one it doesn't
+        let current_etag = match state.metadata.get_object(&bucket, &key).await {
+            Ok(Some(object)) => Some(object.etag),
+            Ok(None) => match state.storage.head_object(&bucket, &key).await {
+                Ok(head) => Some(head.etag.unwrap_or_default()),
+                Err(ObjectIOError::ObjectNotFound { .. }) | Err(ObjectIOError::BucketNotFound { .. }) => None,
+                Err(e) => {
+                    eprintln!("Failed to look up object '{}/{}': {}", bucket, key, e);
+                    return Err(StatusCode::INTERNAL_SERVER_ERROR);
+                }
+            },
+            Err(e) => {
+                eprintln!("Failed to look up object '{}/{}': {}", bucket, key, e);
+                return Err(StatusCode::INTERNAL_SERVER_ERROR);
+            }
+        };
+        // A missing object has no current ETag, so the condition is false
+        // (RFC 9110, section 13.1.1)
+        if !current_etag.is_some_and(|etag| object_io_core::etag_matches(if_match, &etag)) {
+            return Err(StatusCode::PRECONDITION_FAILED);
+        }
+    }
+
+    if let Err(e) = state.metadata.delete_object(&bucket, &key).await {
+        eprintln!("Failed to delete object record '{}/{}': {}", bucket, key, e);
+        return Err(StatusCode::INTERNAL_SERVER_ERROR);
+    }
+
+    // Versioned buckets remember the delete, so HEAD and GET can tell a
+    // deleted key from one that never existed
+    if versioning != VersioningStatus::Unversioned {
+        if let Err(e) = state.metadata.put_delete_marker(&bucket, &key).await {
+            eprintln!("Failed to store delete marker for '{}/{}': {}", bucket, key, e);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
     }
 
     // Delete object from storage
     match state.storage.delete_object(&bucket, &key).await {
         Ok(()) => Ok(StatusCode::NO_CONTENT),
-        Err(object_io_core::ObjectIOError::ObjectNotFound { .. }) => {
-            // S3 returns 204 even if object doesn't exist
+        Err(object_io_core::ObjectIOError::ObjectNotFound { .. })
+        | Err(object_io_core::ObjectIOError::BucketNotFound { .. }) => {
+            // S3 returns 204 even if object doesn't exist; the bucket exists
+            // in metadata but the backend may not have stored anything in it yet
             Ok(StatusCode::NO_CONTENT)
         }
         Err(e) => {
@@ -234,3 +1189,1082 @@ pub async fn delete_object(
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn delete(state: &AppState, bucket: &str, key: &str) -> std::result::Result<StatusCode, StatusCode> {
+        delete_if_match(state, bucket, key, None).await
+    }
+
+    async fn delete_if_match(
+        state: &AppState,
+        bucket: &str,
+        key: &str,
+        if_match: Option<&str>,
+    ) -> std::result::Result<StatusCode, StatusCode> {
+        let mut headers = HeaderMap::new();
+        if let Some(if_match) = if_match {
+            headers.insert("if-match", if_match.parse().unwrap());
+        }
+        delete_object(
+            Path((bucket.to_string(), key.to_string())),
+            State(state.clone()),
+            None,
+            Query(DeleteObjectQuery { upload_id: None }),
+            headers,
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn test_delete_object_status() {
+        let dir = tempfile::tempdir().unwrap();
+        let state = AppState::for_test(dir.path()).await;
+
+        // Missing bucket
+        assert_eq!(delete(&state, "missing", "a.txt").await, Err(StatusCode::NOT_FOUND));
+
+        // Existing bucket with nothing stored in it yet, then a missing key
+        state.metadata.create_bucket("photos", "owner").await.unwrap();
+        assert_eq!(delete(&state, "photos", "a.txt").await, Ok(StatusCode::NO_CONTENT));
+
+        state
+            .storage
+            .put_object("photos", "b.txt", Box::new(std::io::Cursor::new(b"b".to_vec())), HashMap::new())
+            .await
+            .unwrap();
+        assert_eq!(delete(&state, "photos", "a.txt").await, Ok(StatusCode::NO_CONTENT));
+
+        // Existing object
+        assert_eq!(delete(&state, "photos", "b.txt").await, Ok(StatusCode::NO_CONTENT));
+        assert!(!state.storage.object_exists("photos", "b.txt").await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_delete_object_if_match() {
+        let dir = tempfile::tempdir().unwrap();
+        let state = AppState::for_test(dir.path()).await;
+        state.metadata.create_bucket("docs", "owner").await.unwrap();
+
+        let put = |key: &'static str| {
+            let state = state.clone();
+            async move {
+                let reader = Box::new(std::io::Cursor::new(key.as_bytes().to_vec()));
+                store_object(&state, "docs", key, reader, HashMap::new()).await.unwrap()
+            }
+        };
+
+        // Mismatched ETag leaves the object in place
+        let object = put("a.txt").await;
+        assert_eq!(
+            delete_if_match(&state, "docs", "a.txt", Some("\"0123\"")).await,
+            Err(StatusCode::PRECONDITION_FAILED)
+        );
+        assert!(state.storage.object_exists("docs", "a.txt").await.unwrap());
+        assert!(state.metadata.get_object("docs", "a.txt").await.unwrap().is_some());
+
+        // Matching ETag, quoted as clients send it
+        let quoted = format!("\"{}\"", object.etag);
+        assert_eq!(delete_if_match(&state, "docs", "a.txt", Some(&quoted)).await, Ok(StatusCode::NO_CONTENT));
+        assert!(!state.storage.object_exists("docs", "a.txt").await.unwrap());
+        assert!(state.metadata.get_object("docs", "a.txt").await.unwrap().is_none());
+
+        // A missing object matches no ETag, though an unconditional delete
+        // of it still succeeds
+        assert_eq!(
+            delete_if_match(&state, "docs", "a.txt", Some(&quoted)).await,
+            Err(StatusCode::PRECONDITION_FAILED)
+        );
+        assert_eq!(delete_if_match(&state, "docs", "a.txt", Some("*")).await, Err(StatusCode::PRECONDITION_FAILED));
+        assert_eq!(delete_if_match(&state, "docs", "a.txt", None).await, Ok(StatusCode::NO_CONTENT));
+
+        // Data stored without a record is compared by its stored ETag
+        let reader = Box::new(std::io::Cursor::new(b"unrecorded".to_vec()));
+        let etag = state.storage.put_object("docs", "c.txt", reader, HashMap::new()).await.unwrap();
+        assert_eq!(
+            delete_if_match(&state, "docs", "c.txt", Some("\"0123\"")).await,
+            Err(StatusCode::PRECONDITION_FAILED)
+        );
+        assert!(state.storage.object_exists("docs", "c.txt").await.unwrap());
+        let quoted = format!("\"{}\"", etag);
+        assert_eq!(delete_if_match(&state, "docs", "c.txt", Some(&quoted)).await, Ok(StatusCode::NO_CONTENT));
+        assert!(!state.storage.object_exists("docs", "c.txt").await.unwrap());
+
+        // Without the header the delete is unconditional
+        put("b.txt").await;
+        assert_eq!(delete_if_match(&state, "docs", "b.txt", None).await, Ok(StatusCode::NO_CONTENT));
+        assert!(!state.storage.object_exists("docs", "b.txt").await.unwrap());
+        assert!(state.locks.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_rename_object() {
+        let dir = tempfile::tempdir().unwrap();
+        let state = AppState::for_test(dir.path()).await;
+        state.metadata.create_bucket("docs", "owner").await.unwrap();
+
+        let put = |key: &'static str| {
+            let state = state.clone();
+            async move {
+                let reader = Box::new(std::io::Cursor::new(key.as_bytes().to_vec()));
+                let metadata = HashMap::from([("x-amz-meta-origin".to_string(), key.to_string())]);
+                store_object(&state, "docs", key, reader, metadata).await.unwrap()
+            }
+        };
+        let rename = |key: &'static str, dst_key: &'static str, overwrite: bool| {
+            let state = state.clone();
+            async move { rename_object(&state, "docs", key, dst_key, overwrite).await.unwrap().status() }
+        };
+
+        // An existing destination is only replaced when asked to
+        let object = put("a.txt").await;
+        put("b.txt").await;
+        assert_eq!(rename("a.txt", "b.txt", false).await, StatusCode::CONFLICT);
+        assert!(state.storage.object_exists("docs", "a.txt").await.unwrap());
+        assert_eq!(state.metadata.get_object("docs", "b.txt").await.unwrap().unwrap().metadata["x-amz-meta-origin"], "b.txt");
+
+        assert_eq!(rename("a.txt", "b.txt", true).await, StatusCode::OK);
+        assert!(!state.storage.object_exists("docs", "a.txt").await.unwrap());
+        assert!(state.metadata.get_object("docs", "a.txt").await.unwrap().is_none());
+        let moved = state.metadata.get_object("docs", "b.txt").await.unwrap().unwrap();
+        assert_eq!(moved.etag, object.etag);
+        assert_eq!(moved.metadata["x-amz-meta-origin"], "a.txt");
+        let mut data = String::new();
+        let mut reader = state.storage.get_object("docs", "b.txt").await.unwrap();
+        tokio::io::AsyncReadExt::read_to_string(&mut reader, &mut data).await.unwrap();
+        assert_eq!(data, "a.txt");
+
+        // A new destination needs no flag
+        assert_eq!(rename("b.txt", "c/d.txt", false).await, StatusCode::OK);
+        assert!(state.storage.object_exists("docs", "c/d.txt").await.unwrap());
+        let bucket = state.metadata.get_bucket("docs").await.unwrap().unwrap();
+        assert_eq!((bucket.object_count, bucket.total_size), (1, 5));
+        assert_eq!(state.storage.bucket_usage("docs").await.unwrap(), (1, 5));
+
+        // Missing sources and renames onto the same key are refused
+        assert_eq!(rename("a.txt", "e.txt", false).await, StatusCode::NOT_FOUND);
+        assert_eq!(rename("c/d.txt", "c/d.txt", true).await, StatusCode::BAD_REQUEST);
+        assert!(state.locks.is_empty());
+
+        // Destinations that would lead out of the bucket's directory
+        for dst_key in ["../../x", "c/../../x", "/x", "c//x"] {
+            assert_eq!(rename("c/d.txt", dst_key, true).await, StatusCode::BAD_REQUEST, "{}", dst_key);
+        }
+        assert!(!dir.path().join("x").exists());
+        assert!(state.storage.object_exists("docs", "c/d.txt").await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_touch_object() {
+        let dir = tempfile::tempdir().unwrap();
+        let state = AppState::for_test(dir.path()).await;
+        state.metadata.create_bucket("docs", "owner").await.unwrap();
+        let reader = Box::new(std::io::Cursor::new(b"unchanged".to_vec()));
+        let metadata = HashMap::from([("x-amz-meta-origin".to_string(), "sync".to_string())]);
+        let before = store_object(&state, "docs", "a.txt", reader, metadata).await.unwrap();
+
+        // Timestamps are kept to the millisecond
+        tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+        let response = touch_object(&state, "docs", "a.txt").await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let after = state.metadata.get_object("docs", "a.txt").await.unwrap().unwrap();
+        assert!(after.last_modified > before.last_modified);
+        assert_eq!(after.etag, before.etag);
+        assert_eq!(after.size, before.size);
+        assert_eq!(after.metadata["x-amz-meta-origin"], "sync");
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body = String::from_utf8_lossy(&body);
+        assert!(body.contains(&object_io_core::format_s3_timestamp(&after.last_modified)));
+        assert!(body.contains(&before.etag));
+        let mut data = String::new();
+        let mut reader = state.storage.get_object("docs", "a.txt").await.unwrap();
+        tokio::io::AsyncReadExt::read_to_string(&mut reader, &mut data).await.unwrap();
+        assert_eq!(data, "unchanged");
+
+        let response = touch_object(&state, "docs", "missing.txt").await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert!(state.locks.is_empty());
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_concurrent_overwrites() {
+        let dir = tempfile::tempdir().unwrap();
+        let state = AppState::for_test(dir.path()).await;
+        state.metadata.create_bucket("docs", "owner").await.unwrap();
+
+        // Large enough to be written in several chunks, so torn reads would show
+        let content = |writer: usize, round: usize| format!("writer {} round {}\n", writer, round).repeat(10_000);
+        let etags: std::collections::HashSet<_> = (0..8)
+            .flat_map(|writer| (0..5).map(move |round| object_io_core::generate_etag(content(writer, round).as_bytes())))
+            .collect();
+        let etags = Arc::new(etags);
+
+        let done = Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let readers: Vec<_> = (0..4)
+            .map(|_| {
+                let (state, etags, done) = (state.clone(), etags.clone(), done.clone());
+                tokio::spawn(async move {
+                    while !done.load(Ordering::Relaxed) {
+                        tokio::task::yield_now().await;
+                        let Ok(mut reader) = state.storage.get_object("docs", "shared.txt").await else {
+                            continue;
+                        };
+                        let mut data = Vec::new();
+                        reader.read_to_end(&mut data).await.unwrap();
+                        assert!(etags.contains(&object_io_core::generate_etag(&data)), "torn read");
+                    }
+                })
+            })
+            .collect();
+        let writers: Vec<_> = (0..8)
+            .map(|writer| {
+                let state = state.clone();
+                tokio::spawn(async move {
+                    let mut stored = Vec::new();
+                    for round in 0..5 {
+                        let reader = Box::new(std::io::Cursor::new(content(writer, round).into_bytes()));
+                        stored.push(store_object(&state, "docs", "shared.txt", reader, HashMap::new()).await.unwrap());
+                    }
+                    stored
+                })
+            })
+            .collect();
+
+        let mut stored = Vec::new();
+        for writer in writers {
+            stored.extend(writer.await.unwrap());
+        }
+        done.store(true, Ordering::Relaxed);
+        for reader in readers {
+            reader.await.unwrap();
+        }
+
+        // Each write returned the ETag of its own bytes, and the record left
+        // behind is the last write's, matching the bytes in place
+        for object in &stored {
+            assert!(etags.contains(&object.etag));
+        }
+        let last = stored.iter().map(|object| object.last_modified).max().unwrap();
+        let record = state.metadata.get_object("docs", "shared.txt").await.unwrap().unwrap();
+        assert_eq!(record.last_modified, last);
+        let mut data = Vec::new();
+        let mut reader = state.storage.get_object("docs", "shared.txt").await.unwrap();
+        reader.read_to_end(&mut data).await.unwrap();
+        assert_eq!(object_io_core::generate_etag(&data), record.etag);
+        assert_eq!(record.size, data.len() as u64);
+        assert!(state.storage.get_object_metadata("docs", "shared.txt").await.is_ok());
+        assert!(state.locks.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_storage_usage_matches_bucket_stats() {
+        let dir = tempfile::tempdir().unwrap();
+        let state = AppState::for_test(dir.path()).await;
+        state.metadata.create_bucket("docs", "owner").await.unwrap();
+
+        let put = |key: &'static str, data: &'static [u8]| {
+            let state = state.clone();
+            async move {
+                let reader = Box::new(std::io::Cursor::new(data.to_vec()));
+                store_object(&state, "docs", key, reader, HashMap::new()).await.unwrap();
+            }
+        };
+        let assert_consistent = |expected: (u64, u64)| {
+            let state = state.clone();
+            async move {
+                let bucket = state.metadata.get_bucket("docs").await.unwrap().unwrap();
+                assert_eq!((bucket.object_count, bucket.total_size), expected);
+                assert_eq!(state.storage.bucket_usage("docs").await.unwrap(), expected);
+            }
+        };
+
+        assert_consistent((0, 0)).await;
+        put("a.txt", b"aaaa").await;
+        put("b.txt", b"bb").await;
+        assert_consistent((2, 6)).await;
+        put("a.txt", b"a").await;
+        assert_consistent((2, 3)).await;
+        assert_eq!(delete(&state, "docs", "b.txt").await, Ok(StatusCode::NO_CONTENT));
+        assert_consistent((1, 1)).await;
+    }
+
+    #[tokio::test]
+    async fn test_standard_headers_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let state = AppState::for_test(dir.path()).await;
+        state.metadata.create_bucket("site", "owner").await.unwrap();
+
+        let sent = [
+            ("cache-control", "public, max-age=31536000, immutable"),
+            ("content-disposition", "attachment; filename=\"app.js\""),
+            ("content-encoding", "gzip"),
+            ("content-language", "en-GB"),
+            ("expires", "Thu, 01 Dec 2044 16:00:00 GMT"),
+        ];
+        let path = || Path(("site".to_string(), "app.js".to_string()));
+
+        for (name, value) in sent {
+            let mut headers = HeaderMap::new();
+            headers.insert(name, value.parse().unwrap());
+            let query = PutObjectQuery {
+                content_type: None,
+                metadata: None,
+                part_number: None,
+                upload_id: None,
+                acl: None,
+                legal_hold: None,
+            };
+            let response = put_object(path(), State(state.clone()), None, Query(query), headers, Body::from("x"))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+
+            let query = GetObjectQuery {
+                response_content_type: None,
+                response_content_disposition: None,
+                upload_id: None,
+                part_number_marker: None,
+                max_parts: None,
+                acl: None,
+                legal_hold: None,
+                verify: None,
+                decompress: None,
+            };
+            let get = get_object(path(), State(state.clone()), None, Query(query), HeaderMap::new()).await.unwrap();
+            let head = head_object(path(), State(state.clone())).await.unwrap();
+            for response in [get, head] {
+                assert_eq!(response.headers()[name], value, "{}", name);
+                assert_eq!(response.headers()["content-type"], "text/javascript");
+                // Only the header sent with this upload is returned
+                for (other, _) in sent.iter().filter(|(other, _)| *other != name) {
+                    assert!(!response.headers().contains_key(*other), "{}", other);
+                }
+                assert!(!response.headers().contains_key(format!("x-amz-meta-{}", name)));
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_user_metadata_size_limit() {
+        let dir = tempfile::tempdir().unwrap();
+        let state = AppState::for_test(dir.path()).await;
+        state.metadata.create_bucket("docs", "owner").await.unwrap();
+
+        let put = |value_len: usize| {
+            let state = state.clone();
+            async move {
+                let mut headers = HeaderMap::new();
+                headers.insert("x-amz-meta-owner", "ops".parse().unwrap());
+                // "owner" + "ops" + "notes" count towards the limit too
+                let notes = "n".repeat(value_len);
+                headers.insert("x-amz-meta-notes", notes.parse().unwrap());
+                let query = PutObjectQuery {
+                    content_type: None,
+                    metadata: None,
+                    part_number: None,
+                    upload_id: None,
+                    acl: None,
+                    legal_hold: None,
+                };
+                let path = Path(("docs".to_string(), "a.txt".to_string()));
+                put_object(path, State(state), None, Query(query), headers, Body::from("x")).await.unwrap()
+            }
+        };
+
+        let at_limit = object_io_core::MAX_USER_METADATA_SIZE - "owneropsnotes".len();
+        assert_eq!(put(at_limit).await.status(), StatusCode::OK);
+
+        let response = put(at_limit + 1).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert!(String::from_utf8_lossy(&body).contains("<Code>MetadataTooLarge</Code>"));
+        // The rejected upload left the stored object alone
+        let object = state.metadata.get_object("docs", "a.txt").await.unwrap().unwrap();
+        assert_eq!(object.metadata["notes"].len(), at_limit);
+    }
+
+    #[tokio::test]
+    async fn test_get_and_head_return_same_metadata() {
+        let dir = tempfile::tempdir().unwrap();
+        let state = AppState::for_test(dir.path()).await;
+        state.metadata.create_bucket("docs", "owner").await.unwrap();
+        let path = || Path(("docs".to_string(), "report.pdf".to_string()));
+
+        let mut headers = HeaderMap::new();
+        headers.insert("x-amz-meta-owner", "ops".parse().unwrap());
+        headers.insert("x-amz-meta-reviewed", "2024-05-01".parse().unwrap());
+        headers.insert("cache-control", "no-cache".parse().unwrap());
+        let query = PutObjectQuery {
+            content_type: None,
+            metadata: None,
+            part_number: None,
+            upload_id: None,
+            acl: None,
+            legal_hold: None,
+        };
+        put_object(path(), State(state.clone()), None, Query(query), headers, Body::from("pdf"))
+            .await
+            .unwrap();
+
+        let query = GetObjectQuery {
+            response_content_type: None,
+            response_content_disposition: None,
+            upload_id: None,
+            part_number_marker: None,
+            max_parts: None,
+            acl: None,
+            legal_hold: None,
+            verify: None,
+            decompress: None,
+        };
+        let get = get_object(path(), State(state.clone()), None, Query(query), HeaderMap::new()).await.unwrap();
+        let head = head_object(path(), State(state.clone())).await.unwrap();
+
+        let metadata_headers = |response: &Response| {
+            let mut headers: Vec<(String, String)> = response
+                .headers()
+                .iter()
+                .filter(|(name, _)| name.as_str().starts_with("x-amz-meta-"))
+                .map(|(name, value)| (name.to_string(), value.to_str().unwrap().to_string()))
+                .collect();
+            headers.sort();
+            headers
+        };
+        let expected = vec![
+            ("x-amz-meta-owner".to_string(), "ops".to_string()),
+            ("x-amz-meta-reviewed".to_string(), "2024-05-01".to_string()),
+        ];
+        assert_eq!(metadata_headers(&get), expected);
+        assert_eq!(metadata_headers(&head), expected);
+        for response in [get, head] {
+            assert_eq!(response.headers()["cache-control"], "no-cache");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_get_object_verify() {
+        let dir = tempfile::tempdir().unwrap();
+        let state = AppState::for_test(dir.path()).await;
+        state.metadata.create_bucket("docs", "owner").await.unwrap();
+        let data: Vec<u8> = (0..20_000u32).map(|i| i as u8).collect();
+        let reader = Box::new(std::io::Cursor::new(data.clone()));
+        store_object(&state, "docs", "data.bin", reader, HashMap::new()).await.unwrap();
+
+        let get = |verify: Option<bool>, range: Option<&'static str>| {
+            let state = state.clone();
+            async move {
+                let query = GetObjectQuery {
+                    response_content_type: None,
+                    response_content_disposition: None,
+                    upload_id: None,
+                    part_number_marker: None,
+                    max_parts: None,
+                    acl: None,
+                    legal_hold: None,
+                    verify,
+                    decompress: None,
+                };
+                let mut headers = HeaderMap::new();
+                if let Some(range) = range {
+                    headers.insert("range", range.parse().unwrap());
+                }
+                let path = Path(("docs".to_string(), "data.bin".to_string()));
+                get_object(path, State(state), None, Query(query), headers).await
+            }
+        };
+
+        // Intact data streams in full; a verified read ignores the range
+        let response = get(Some(true), Some("bytes=0-9")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap(), data);
+
+        // Corrupt the last byte on disk
+        let mut corrupted = data.clone();
+        *corrupted.last_mut().unwrap() ^= 0xff;
+        std::fs::write(dir.path().join("storage/docs/data.bin"), &corrupted).unwrap();
+
+        let response = get(Some(true), None).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let mut body = response.into_body().into_data_stream();
+        let mut received = 0;
+        let error = loop {
+            match body.next().await {
+                Some(Ok(chunk)) => received += chunk.len(),
+                Some(Err(e)) => break e,
+                None => panic!("corrupted data was delivered in full"),
+            }
+        };
+        assert!(error.to_string().contains("corrupted"), "{}", error);
+        assert!(received < data.len());
+
+        // Without verification the data is served as stored
+        let response = get(None, None).await.unwrap();
+        assert_eq!(axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap(), corrupted);
+
+        // Multipart ETags are not a hash of the data
+        let etag = format!("{}-2", object_io_core::generate_etag(&data));
+        let size = data.len() as u64;
+        state
+            .metadata
+            .put_object_metadata("docs", "data.bin", size, "application/octet-stream", &etag, HashMap::new())
+            .await
+            .unwrap();
+        assert_eq!(get(Some(true), None).await.unwrap_err(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_get_object_decompress() {
+        use axum::body::HttpBody;
+        use std::io::Write;
+
+        let dir = tempfile::tempdir().unwrap();
+        let state = AppState::for_test(dir.path()).await;
+        state.metadata.create_bucket("logs", "owner").await.unwrap();
+        let text = "GET /index.html 200 1024\n".repeat(20_000);
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(text.as_bytes()).unwrap();
+        let gzipped = encoder.finish().unwrap();
+        for (key, encoding) in [("access.log.gz", "gzip"), ("access.log", "identity")] {
+            let data = if encoding == "gzip" { gzipped.clone() } else { text.as_bytes().to_vec() };
+            let metadata = HashMap::from([
+                ("content-type".to_string(), "text/plain".to_string()),
+                ("content-encoding".to_string(), encoding.to_string()),
+            ]);
+            store_object(&state, "logs", key, Box::new(std::io::Cursor::new(data)), metadata).await.unwrap();
+        }
+
+        let get = |key: &'static str, verify: Option<bool>, decompress: Option<bool>| {
+            let state = state.clone();
+            async move {
+                let query = GetObjectQuery {
+                    response_content_type: None,
+                    response_content_disposition: None,
+                    upload_id: None,
+                    part_number_marker: None,
+                    max_parts: None,
+                    acl: None,
+                    legal_hold: None,
+                    verify,
+                    decompress,
+                };
+                let mut headers = HeaderMap::new();
+                headers.insert("range", "bytes=0-9".parse().unwrap());
+                let path = Path(("logs".to_string(), key.to_string()));
+                get_object(path, State(state), None, Query(query), headers).await
+            }
+        };
+
+        // Decoded in full, whatever the range, with no length known up front
+        let response = get("access.log.gz", None, Some(true)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(!response.headers().contains_key("content-encoding"));
+        assert_eq!(response.body().size_hint().exact(), None);
+        assert_eq!(axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap(), text.as_bytes());
+
+        // Otherwise objects are sent as stored, with their length
+        let response = get("access.log.gz", None, None).await.unwrap();
+        assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(response.headers()["content-encoding"], "gzip");
+        assert_eq!(response.body().size_hint().exact(), Some(10));
+        let response = get("access.log", None, Some(true)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap(), &text.as_bytes()[..10]);
+
+        // Verification hashes the stored bytes, so cannot be combined
+        assert_eq!(get("access.log.gz", Some(true), Some(true)).await.unwrap_err(), StatusCode::BAD_REQUEST);
+
+        // Data that fails to decode ends the body with an error
+        let mut truncated = gzipped.clone();
+        truncated.truncate(gzipped.len() / 2);
+        std::fs::write(dir.path().join("storage/logs/access.log.gz"), &truncated).unwrap();
+        let response = get("access.log.gz", None, Some(true)).await.unwrap();
+        assert!(axum::body::to_bytes(response.into_body(), usize::MAX).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_put_object_trailing_checksum() {
+        let dir = tempfile::tempdir().unwrap();
+        let state = AppState::for_test(dir.path()).await;
+        state.metadata.create_bucket("docs", "owner").await.unwrap();
+
+        let put = |checksum: String| {
+            let state = state.clone();
+            async move {
+                let query = PutObjectQuery {
+                    content_type: None,
+                    metadata: None,
+                    part_number: None,
+                    upload_id: None,
+                    acl: None,
+                    legal_hold: None,
+                };
+                let mut headers = HeaderMap::new();
+                headers.insert("content-encoding", "aws-chunked".parse().unwrap());
+                headers.insert("x-amz-content-sha256", "STREAMING-UNSIGNED-PAYLOAD-TRAILER".parse().unwrap());
+                headers.insert("x-amz-decoded-content-length", "11".parse().unwrap());
+                headers.insert("x-amz-trailer", "x-amz-checksum-crc32c".parse().unwrap());
+                let body = format!("5\r\nhello\r\n6\r\n world\r\n0\r\nx-amz-checksum-crc32c:{}\r\n\r\n", checksum);
+                let path = Path(("docs".to_string(), "greeting.txt".to_string()));
+                put_object(path, State(state), None, Query(query), headers, Body::from(body)).await.unwrap()
+            }
+        };
+
+        let mut hasher = object_io_core::ChecksumAlgorithm::Crc32c.hasher();
+        hasher.update(b"hello world");
+        let response = put(hasher.finalize()).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let object = state.metadata.get_object("docs", "greeting.txt").await.unwrap().unwrap();
+        assert_eq!(object.size, 11);
+        assert_eq!(object.etag, object_io_core::generate_etag(b"hello world"));
+        assert!(object.header("content-encoding").is_none());
+
+        // A mismatched checksum is rejected and leaves the stored object alone
+        let response = put("AAAAAA==".to_string()).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert!(String::from_utf8_lossy(&body).contains("<Code>BadDigest</Code>"));
+        let mut data = Vec::new();
+        let mut reader = state.storage.get_object("docs", "greeting.txt").await.unwrap();
+        reader.read_to_end(&mut data).await.unwrap();
+        assert_eq!(data, b"hello world");
+    }
+
+    #[tokio::test]
+    async fn test_put_object_idempotency_key() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut state = AppState::for_test(dir.path()).await;
+        state.metadata.create_bucket("docs", "owner").await.unwrap();
+
+        let put = |state: AppState, key: &'static str, body: &'static str| async move {
+            let query = PutObjectQuery {
+                content_type: None,
+                metadata: None,
+                part_number: None,
+                upload_id: None,
+                acl: None,
+                legal_hold: None,
+            };
+            let mut headers = HeaderMap::new();
+            headers.insert(IDEMPOTENCY_HEADER, "retry-1".parse().unwrap());
+            let path = Path(("docs".to_string(), key.to_string()));
+            put_object(path, State(state), None, Query(query), headers, Body::from(body)).await.unwrap()
+        };
+
+        let first = put(state.clone(), "a.txt", "alpha").await;
+        assert_eq!(first.status(), StatusCode::OK);
+        let recorded = state.metadata.get_object("docs", "a.txt").await.unwrap().unwrap();
+
+        // A replay returns the original result without writing again
+        let replay = put(state.clone(), "a.txt", "alpha").await;
+        assert_eq!(replay.status(), StatusCode::OK);
+        assert_eq!(replay.headers()["etag"], first.headers()["etag"]);
+        let object = state.metadata.get_object("docs", "a.txt").await.unwrap().unwrap();
+        assert_eq!(object.last_modified, recorded.last_modified);
+
+        // Other content or another key under the same token conflicts
+        for (key, body) in [("a.txt", "bravo"), ("b.txt", "alpha")] {
+            let response = put(state.clone(), key, body).await;
+            assert_eq!(response.status(), StatusCode::CONFLICT);
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+            assert!(String::from_utf8_lossy(&body).contains("<Code>IdempotencyKeyMismatch</Code>"));
+        }
+        assert!(state.metadata.get_object("docs", "b.txt").await.unwrap().is_none());
+
+        // With replay disabled the token is ignored
+        let mut config = (*state.config).clone();
+        config.idempotency_window = 0;
+        state.config = Arc::new(config);
+        assert_eq!(put(state.clone(), "a.txt", "bravo").await.status(), StatusCode::OK);
+        let object = state.metadata.get_object("docs", "a.txt").await.unwrap().unwrap();
+        assert_eq!(object.etag, object_io_core::generate_etag(b"bravo"));
+    }
+
+    #[tokio::test]
+    async fn test_idempotency_key_in_flight() {
+        let dir = tempfile::tempdir().unwrap();
+        let state = AppState::for_test(dir.path()).await;
+        state.metadata.create_bucket("docs", "owner").await.unwrap();
+
+        let put = |state: AppState, body: Body| async move {
+            let query = PutObjectQuery {
+                content_type: None,
+                metadata: None,
+                part_number: None,
+                upload_id: None,
+                acl: None,
+                legal_hold: None,
+            };
+            let mut headers = HeaderMap::new();
+            headers.insert(IDEMPOTENCY_HEADER, "retry-1".parse().unwrap());
+            let path = Path(("docs".to_string(), "a.txt".to_string()));
+            put_object(path, State(state), None, Query(query), headers, body).await.unwrap()
+        };
+
+        // The first attempt's body only arrives once released, so it is
+        // still being written when the retry comes in
+        let (started_tx, started_rx) = tokio::sync::oneshot::channel();
+        let (release_tx, release_rx) = tokio::sync::oneshot::channel::<()>();
+        let body = futures::stream::once(async move {
+            let _ = started_tx.send(());
+            let _ = release_rx.await;
+            Ok::<_, std::io::Error>(bytes::Bytes::from_static(b"alpha"))
+        });
+        let first = tokio::spawn(put(state.clone(), Body::from_stream(body)));
+        started_rx.await.unwrap();
+
+        let retry = put(state.clone(), Body::from("alpha")).await;
+        assert_eq!(retry.status(), StatusCode::CONFLICT);
+        let body = axum::body::to_bytes(retry.into_body(), usize::MAX).await.unwrap();
+        assert!(String::from_utf8_lossy(&body).contains("<Code>IdempotencyKeyInUse</Code>"));
+
+        release_tx.send(()).unwrap();
+        let first = first.await.unwrap();
+        assert_eq!(first.status(), StatusCode::OK);
+        let written = state.metadata.get_object("docs", "a.txt").await.unwrap().unwrap();
+        let retry = put(state.clone(), Body::from("alpha")).await;
+        assert_eq!(retry.status(), StatusCode::OK);
+        assert_eq!(retry.headers()["etag"], first.headers()["etag"]);
+        let object = state.metadata.get_object("docs", "a.txt").await.unwrap().unwrap();
+        assert_eq!(object.last_modified, written.last_modified);
+    }
+
+    #[tokio::test]
+    async fn test_put_object_conditional() {
+        let dir = tempfile::tempdir().unwrap();
+        let state = AppState::for_test(dir.path()).await;
+        state.metadata.create_bucket("docs", "owner").await.unwrap();
+
+        let put = |condition: Option<(&'static str, String)>, body: &'static str| {
+            let state = state.clone();
+            async move {
+                let query = PutObjectQuery {
+                    content_type: None,
+                    metadata: None,
+                    part_number: None,
+                    upload_id: None,
+                    acl: None,
+                    legal_hold: None,
+                };
+                let mut headers = HeaderMap::new();
+                if let Some((name, value)) = condition {
+                    headers.insert(name, value.parse().unwrap());
+                }
+                let path = Path(("docs".to_string(), "a.txt".to_string()));
+                put_object(path, State(state), None, Query(query), headers, Body::from(body)).await.unwrap()
+            }
+        };
+        let stored_etag = || async {
+            let object = state.metadata.get_object("docs", "a.txt").await.unwrap();
+            object.map(|object| object.etag)
+        };
+
+        // If-Match needs an object to match
+        let response = put(Some(("if-match", "*".to_string())), "one").await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert_eq!(stored_etag().await, None);
+
+        let response = put(Some(("if-none-match", "*".to_string())), "one").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let etag = object_io_core::generate_etag(b"one");
+        let response = put(Some(("if-none-match", "*".to_string())), "two").await;
+        assert_eq!(response.status(), StatusCode::PRECONDITION_FAILED);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert!(String::from_utf8_lossy(&body).contains("<Code>PreconditionFailed</Code>"));
+
+        let response = put(Some(("if-match", "\"0000\"".to_string())), "two").await;
+        assert_eq!(response.status(), StatusCode::PRECONDITION_FAILED);
+        assert_eq!(stored_etag().await.as_deref(), Some(etag.as_str()));
+        let response = put(Some(("if-match", format!("\"{}\"", etag))), "two").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(stored_etag().await, Some(object_io_core::generate_etag(b"two")));
+
+        // Only * is supported for If-None-Match on writes
+        let response = put(Some(("if-none-match", format!("\"{}\"", etag))), "three").await;
+        assert_eq!(response.status(), StatusCode::NOT_IMPLEMENTED);
+    }
+
+    #[tokio::test]
+    async fn test_put_object_spools_large_bodies() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut state = AppState::for_test(dir.path()).await;
+        let spool_dir = dir.path().join("spool");
+        let mut config = (*state.config).clone();
+        config.upload_memory_threshold = 16;
+        config.storage_temp_path = Some(spool_dir.to_string_lossy().into_owned());
+        state.config = Arc::new(config);
+        state.metadata.create_bucket("docs", "owner").await.unwrap();
+
+        // Either side of the threshold, and with buffering disabled
+        for (threshold, key, body) in [
+            (16, "small.txt", "fits in memory".to_string()),
+            (16, "large.txt", "spills to a temporary file ".repeat(100)),
+            (0, "streamed.txt", "goes straight to storage".to_string()),
+        ] {
+            let mut config = (*state.config).clone();
+            config.upload_memory_threshold = threshold;
+            state.config = Arc::new(config);
+            let query = PutObjectQuery {
+                content_type: None,
+                metadata: None,
+                part_number: None,
+                upload_id: None,
+                acl: None,
+                legal_hold: None,
+            };
+            let path = Path(("docs".to_string(), key.to_string()));
+            let body_in = Body::from(body.clone());
+            let response = put_object(path, State(state.clone()), None, Query(query), HeaderMap::new(), body_in)
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+
+            let object = state.metadata.get_object("docs", key).await.unwrap().unwrap();
+            assert_eq!(object.size, body.len() as u64);
+            assert_eq!(object.etag, object_io_core::generate_etag(body.as_bytes()));
+            let mut stored = Vec::new();
+            state.storage.get_object("docs", key).await.unwrap().read_to_end(&mut stored).await.unwrap();
+            assert_eq!(stored, body.as_bytes());
+        }
+        // Spooled bodies leave nothing behind
+        assert_eq!(std::fs::read_dir(&spool_dir).map_or(0, |entries| entries.count()), 0);
+    }
+
+    #[tokio::test]
+    async fn test_last_modified_header() {
+        let dir = tempfile::tempdir().unwrap();
+        let state = AppState::for_test(dir.path()).await;
+        state.metadata.create_bucket("site", "owner").await.unwrap();
+
+        let path = || Path(("site".to_string(), "index.html".to_string()));
+        let query = PutObjectQuery {
+            content_type: None,
+            metadata: None,
+            part_number: None,
+            upload_id: None,
+            acl: None,
+            legal_hold: None,
+        };
+        put_object(path(), State(state.clone()), None, Query(query), HeaderMap::new(), Body::from("<html>"))
+            .await
+            .unwrap();
+        let stored = state.metadata.get_object("site", "index.html").await.unwrap().unwrap();
+
+        let query = GetObjectQuery {
+            response_content_type: None,
+            response_content_disposition: None,
+            upload_id: None,
+            part_number_marker: None,
+            max_parts: None,
+            acl: None,
+            legal_hold: None,
+            verify: None,
+            decompress: None,
+        };
+        let get = get_object(path(), State(state.clone()), None, Query(query), HeaderMap::new()).await.unwrap();
+        let head = head_object(path(), State(state.clone())).await.unwrap();
+        for response in [get, head] {
+            // HTTP dates have whole seconds
+            let header = response.headers()["last-modified"].to_str().unwrap();
+            assert_eq!(header, stored.last_modified.format("%a, %d %b %Y %H:%M:%S GMT").to_string());
+            let parsed = DateTime::parse_from_rfc2822(header).unwrap();
+            assert_eq!(parsed.timestamp(), stored.last_modified.timestamp());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_head_object_sources() {
+        let dir = tempfile::tempdir().unwrap();
+        let state = AppState::for_test(dir.path()).await;
+        state.metadata.create_bucket("site", "owner").await.unwrap();
+        let path = |key: &str| Path(("site".to_string(), key.to_string()));
+
+        // Data the metadata database has no record of is described by storage alone
+        let mut metadata = HashMap::new();
+        metadata.insert("content-type".to_string(), "text/css".to_string());
+        state
+            .storage
+            .put_object("site", "orphan.css", Box::new(std::io::Cursor::new(b"body{}".to_vec())), metadata)
+            .await
+            .unwrap();
+        let head = head_object(path("orphan.css"), State(state.clone())).await.unwrap();
+        assert_eq!(head.headers()["content-type"], "text/css");
+        assert!(head.headers().contains_key("last-modified"));
+
+        // A record whose data is gone is not found
+        let reader = Box::new(std::io::Cursor::new(b"<html>".to_vec()));
+        store_object(&state, "site", "index.html", reader, HashMap::new()).await.unwrap();
+        assert_eq!(head_object(path("index.html"), State(state.clone())).await.unwrap().status(), StatusCode::OK);
+        state.storage.delete_object("site", "index.html").await.unwrap();
+        assert_eq!(head_object(path("index.html"), State(state.clone())).await.unwrap_err(), StatusCode::NOT_FOUND);
+
+        assert_eq!(head_object(path("missing"), State(state.clone())).await.unwrap_err(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_head_delete_marker() {
+        let dir = tempfile::tempdir().unwrap();
+        let state = AppState::for_test(dir.path()).await;
+        state.metadata.create_bucket("site", "owner").await.unwrap();
+        let path = |key: &str| Path(("site".to_string(), key.to_string()));
+        let put = |key: &'static str| {
+            let state = state.clone();
+            async move {
+                let reader = Box::new(std::io::Cursor::new(key.as_bytes().to_vec()));
+                store_object(&state, "site", key, reader, HashMap::new()).await.unwrap()
+            }
+        };
+
+        // Unversioned buckets forget deleted keys
+        put("old.html").await;
+        assert_eq!(delete(&state, "site", "old.html").await, Ok(StatusCode::NO_CONTENT));
+        assert_eq!(head_object(path("old.html"), State(state.clone())).await.unwrap_err(), StatusCode::NOT_FOUND);
+
+        state.metadata.set_versioning("site", VersioningStatus::Enabled).await.unwrap();
+        put("index.html").await;
+        assert_eq!(delete(&state, "site", "index.html").await, Ok(StatusCode::NO_CONTENT));
+        let head = head_object(path("index.html"), State(state.clone())).await.unwrap();
+        assert_eq!(head.status(), StatusCode::NOT_FOUND);
+        assert_eq!(head.headers()["x-amz-delete-marker"], "true");
+        let version_id = head.headers()["x-amz-version-id"].to_str().unwrap().to_string();
+        assert!(!version_id.is_empty() && version_id != "null");
+
+        // A key that never existed has no marker
+        assert_eq!(head_object(path("missing"), State(state.clone())).await.unwrap_err(), StatusCode::NOT_FOUND);
+
+        // Storing the key again clears the marker
+        put("index.html").await;
+        assert_eq!(head_object(path("index.html"), State(state.clone())).await.unwrap().status(), StatusCode::OK);
+
+        // While suspended, the marker has the null version
+        state.metadata.set_versioning("site", VersioningStatus::Suspended).await.unwrap();
+        assert_eq!(delete(&state, "site", "index.html").await, Ok(StatusCode::NO_CONTENT));
+        let head = head_object(path("index.html"), State(state.clone())).await.unwrap();
+        assert_eq!(head.headers()["x-amz-version-id"], "null");
+    }
+
+    #[tokio::test]
+    async fn test_range_and_if_range() {
+        let dir = tempfile::tempdir().unwrap();
+        let state = AppState::for_test(dir.path()).await;
+        state.metadata.create_bucket("media", "owner").await.unwrap();
+        let reader = Box::new(std::io::Cursor::new(b"0123456789".to_vec()));
+        let object = store_object(&state, "media", "clip", reader, HashMap::new()).await.unwrap();
+        let etag = format!("\"{}\"", object.etag);
+        let last_modified = object_io_core::format_http_date(&object.last_modified);
+
+        let get = |range: Option<&str>, if_range: Option<&str>| {
+            let mut headers = HeaderMap::new();
+            if let Some(range) = range {
+                headers.insert("range", range.parse().unwrap());
+            }
+            if let Some(if_range) = if_range {
+                headers.insert("if-range", if_range.parse().unwrap());
+            }
+            let state = state.clone();
+            async move {
+                let query = GetObjectQuery {
+                    response_content_type: None,
+                    response_content_disposition: None,
+                    upload_id: None,
+                    part_number_marker: None,
+                    max_parts: None,
+                    acl: None,
+                    legal_hold: None,
+                    verify: None,
+                    decompress: None,
+                };
+                let path = Path(("media".to_string(), "clip".to_string()));
+                let response = get_object(path, State(state), None, Query(query), headers).await.unwrap();
+                let status = response.status();
+                let content_range = response
+                    .headers()
+                    .get("content-range")
+                    .map(|v| v.to_str().unwrap().to_string());
+                let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+                (status, content_range, body.to_vec())
+            }
+        };
+        let full = (StatusCode::OK, None, b"0123456789".to_vec());
+        let partial = (StatusCode::PARTIAL_CONTENT, Some("bytes 2-5/10".to_string()), b"2345".to_vec());
+
+        assert_eq!(get(None, None).await, full);
+        assert_eq!(get(Some("bytes=2-5"), None).await, partial);
+        assert_eq!(
+            get(Some("bytes=7-"), None).await,
+            (StatusCode::PARTIAL_CONTENT, Some("bytes 7-9/10".to_string()), b"789".to_vec())
+        );
+        assert_eq!(
+            get(Some("bytes=10-"), None).await,
+            (StatusCode::RANGE_NOT_SATISFIABLE, Some("bytes */10".to_string()), Vec::new())
+        );
+        assert_eq!(get(Some("bytes=5-2"), None).await, full);
+
+        // A matching validator serves the range, anything else the whole object
+        assert_eq!(get(Some("bytes=2-5"), Some(&etag)).await, partial);
+        assert_eq!(get(Some("bytes=2-5"), Some(&last_modified)).await, partial);
+        assert_eq!(get(Some("bytes=2-5"), Some("\"0123\"")).await, full);
+        assert_eq!(get(Some("bytes=2-5"), Some(&format!("W/{}", etag))).await, full);
+        assert_eq!(get(Some("bytes=2-5"), Some("Thu, 01 Jan 1970 00:00:00 GMT")).await, full);
+        // If-Range without Range has no effect
+        assert_eq!(get(None, Some("\"0123\"")).await, full);
+    }
+
+    #[tokio::test]
+    async fn test_if_modified_since() {
+        let dir = tempfile::tempdir().unwrap();
+        let state = AppState::for_test(dir.path()).await;
+        state.metadata.create_bucket("media", "owner").await.unwrap();
+        let reader = Box::new(std::io::Cursor::new(b"0123456789".to_vec()));
+        let object = store_object(&state, "media", "clip", reader, HashMap::new()).await.unwrap();
+
+        let get = |if_modified_since: String| {
+            let mut headers = HeaderMap::new();
+            headers.insert("if-modified-since", if_modified_since.parse().unwrap());
+            let state = state.clone();
+            async move {
+                let query = GetObjectQuery {
+                    response_content_type: None,
+                    response_content_disposition: None,
+                    upload_id: None,
+                    part_number_marker: None,
+                    max_parts: None,
+                    acl: None,
+                    legal_hold: None,
+                    verify: None,
+                    decompress: None,
+                };
+                let path = Path(("media".to_string(), "clip".to_string()));
+                let response = get_object(path, State(state), None, Query(query), headers).await.unwrap();
+                let status = response.status();
+                let etag = response.headers().get("etag").map(|v| v.to_str().unwrap().to_string());
+                let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+                (status, etag, body.len())
+            }
+        };
+        let etag = Some(format!("\"{}\"", object.etag));
+
+        // The stored time has milliseconds the header's whole seconds lack, so
+        // echoing Last-Modified back must still count as unchanged
+        let last_modified = object.last_modified.trunc_subsecs(0);
+        let formats = [
+            "%a, %d %b %Y %H:%M:%S GMT",
+            "%A, %d-%b-%y %H:%M:%S GMT",
+            "%a %b %e %H:%M:%S %Y",
+        ];
+        for format in formats {
+            let date = last_modified.format(format).to_string();
+            assert_eq!(get(date.clone()).await, (StatusCode::NOT_MODIFIED, etag.clone(), 0), "{}", date);
+            let later = (last_modified + chrono::Duration::hours(1)).format(format).to_string();
+            assert_eq!(get(later).await.0, StatusCode::NOT_MODIFIED);
+            let earlier = (last_modified - chrono::Duration::seconds(1)).format(format).to_string();
+            assert_eq!(get(earlier).await, (StatusCode::OK, etag.clone(), 10), "{}", date);
+        }
+
+        // Dates that cannot be parsed are ignored
+        assert_eq!(get("yesterday".to_string()).await.0, StatusCode::OK);
+        assert_eq!(get(object.last_modified.to_rfc3339()).await.0, StatusCode::OK);
+    }
+}
@@ -0,0 +1,475 @@
+//! Object lock (GET and PUT /{bucket}?object-lock, retention headers, and
+//! GET and PUT /{bucket}/{key+}?legal-hold)
+//!
+//! Buckets opt in to object lock, at creation with
+//! `x-amz-bucket-object-lock-enabled: true` or later with an
+//! `ObjectLockConfiguration`. Objects uploaded to them may then carry a
+//! retention period, and have a legal hold placed on them, both of which
+//! `AppState::check_object_lock` enforces on every path that deletes or
+//! overwrites an object.
+
+use axum::{
+    body::Body,
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    Extension,
+};
+use chrono::Utc;
+use object_io_core::{
+    object_lock::{BYPASS_GOVERNANCE_HEADER, MODE_HEADER, RETAIN_UNTIL_HEADER},
+    Bucket, ObjectIOError, ObjectRetention,
+};
+
+use crate::{
+    auth::AuthContext,
+    handlers::{bucket::bucket_error, object::object_error},
+    responses::{
+        to_xml_response,
+        xml::{ErrorDocument, LegalHold, ObjectLockConfiguration},
+    },
+    state::AppState,
+};
+
+/// Largest `ObjectLockConfiguration` or `LegalHold` document accepted, in bytes
+const MAX_CONFIGURATION_SIZE: usize = 64 * 1024;
+
+/// Permission letting non-admin users bypass `GOVERNANCE` retention
+pub const BYPASS_GOVERNANCE_PERMISSION: &str = "bypass_governance_retention";
+
+/// Whether the request asks to bypass `GOVERNANCE` retention and the caller
+/// may: an admin, a user granted `bypass_governance_retention`, or anyone
+/// when authentication is disabled
+pub(crate) fn bypass_governance(headers: &HeaderMap, auth: &Option<Extension<AuthContext>>) -> bool {
+    let requested = headers
+        .get(BYPASS_GOVERNANCE_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.eq_ignore_ascii_case("true"));
+    requested
+        && auth.as_ref().is_none_or(|Extension(auth)| {
+            auth.is_admin || auth.permissions.iter().any(|p| p == BYPASS_GOVERNANCE_PERMISSION)
+        })
+}
+
+/// Retention for a new object written to `bucket`: the one requested with
+/// `x-amz-object-lock-mode` and `x-amz-object-lock-retain-until-date`,
+/// which only buckets with object lock enabled accept, or else the
+/// bucket's default retention
+pub(crate) async fn new_object_retention(
+    state: &AppState,
+    bucket: &str,
+    headers: &HeaderMap,
+) -> object_io_core::Result<Option<ObjectRetention>> {
+    let header = |name: &str| headers.get(name).map(|value| value.to_str().unwrap_or_default());
+    let now = Utc::now();
+    let requested = ObjectRetention::from_headers(header(MODE_HEADER), header(RETAIN_UNTIL_HEADER), now)?;
+    let bucket_info = lock_bucket(state, bucket).await?;
+    match requested {
+        Some(_) if !bucket_info.object_lock_enabled => Err(missing_configuration()),
+        Some(retention) => Ok(Some(retention)),
+        None => Ok(bucket_info.default_retention.map(|default| default.retention_at(now))),
+    }
+}
+
+/// `bucket`, or `BucketNotFound`
+async fn lock_bucket(state: &AppState, bucket: &str) -> object_io_core::Result<Bucket> {
+    state.metadata.get_bucket(bucket).await?.ok_or_else(|| ObjectIOError::BucketNotFound {
+        bucket: bucket.to_string(),
+    })
+}
+
+fn missing_configuration() -> ObjectIOError {
+    ObjectIOError::InvalidRequest {
+        message: "Bucket is missing Object Lock Configuration".to_string(),
+    }
+}
+
+/// Fail with `InvalidRequest` unless `bucket` has object lock enabled
+async fn check_lock_enabled(state: &AppState, bucket: &str) -> object_io_core::Result<()> {
+    match lock_bucket(state, bucket).await? {
+        bucket if bucket.object_lock_enabled => Ok(()),
+        _ => Err(missing_configuration()),
+    }
+}
+
+/// Get a bucket's object lock configuration (GET /{bucket}?object-lock)
+pub async fn get_bucket_object_lock(state: &AppState, bucket: &str) -> std::result::Result<Response, StatusCode> {
+    let bucket_info = match state.metadata.get_bucket(bucket).await {
+        Ok(Some(bucket_info)) => bucket_info,
+        Ok(None) => return Err(StatusCode::NOT_FOUND),
+        Err(e) => {
+            eprintln!("Failed to check bucket '{}': {}", bucket, e);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+    if !bucket_info.object_lock_enabled {
+        let message = "Object Lock configuration does not exist for this bucket";
+        return Ok(ErrorDocument::new("ObjectLockConfigurationNotFoundError", message)
+            .with_bucket(bucket)
+            .into_response(StatusCode::NOT_FOUND));
+    }
+    to_xml_response(&ObjectLockConfiguration::enabled(bucket_info.default_retention))
+}
+
+/// Enable object lock on a bucket (PUT /{bucket}?object-lock)
+///
+/// Takes an `ObjectLockConfiguration` with `ObjectLockEnabled` set to
+/// `Enabled`, and optionally a `Rule` whose `DefaultRetention` has a `Mode`
+/// and either `Days` or `Years`. The rule replaces any earlier one, and
+/// leaving it out removes it. Object lock cannot be disabled again.
+pub async fn put_bucket_object_lock(
+    state: &AppState,
+    bucket: &str,
+    body: Body,
+) -> std::result::Result<Response, StatusCode> {
+    let body = axum::body::to_bytes(body, MAX_CONFIGURATION_SIZE)
+        .await
+        .map_err(|_| StatusCode::BAD_REQUEST)?;
+    let configuration = std::str::from_utf8(&body)
+        .map_err(|e| e.to_string())
+        .and_then(|xml| quick_xml::de::from_str::<ObjectLockConfiguration>(xml).map_err(|e| e.to_string()));
+    let configuration = match configuration {
+        Ok(configuration) => configuration,
+        Err(message) => {
+            let message = format!("The XML you provided was not well-formed: {}", message);
+            return Ok(ErrorDocument::new("MalformedXML", message).into_response(StatusCode::BAD_REQUEST));
+        }
+    };
+    if configuration.object_lock_enabled.as_deref() != Some("Enabled") {
+        let error = ObjectIOError::InvalidRequest {
+            message: "ObjectLockEnabled must be Enabled".to_string(),
+        };
+        return Ok(bucket_error(&error, bucket));
+    }
+    let default_retention = match configuration.default_retention() {
+        Ok(default_retention) => default_retention,
+        Err(e) => return Ok(bucket_error(&e, bucket)),
+    };
+
+    match state.metadata.enable_object_lock(bucket, default_retention).await {
+        Ok(()) => Ok(StatusCode::OK.into_response()),
+        Err(ObjectIOError::BucketNotFound { .. }) => Err(StatusCode::NOT_FOUND),
+        Err(e) => {
+            eprintln!("Failed to enable object lock on '{}': {}", bucket, e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+/// Get an object's legal hold (GET /{bucket}/{key+}?legal-hold)
+pub async fn get_object_legal_hold(
+    state: &AppState,
+    bucket: &str,
+    key: &str,
+) -> std::result::Result<Response, StatusCode> {
+    match state.metadata.get_object(bucket, key).await {
+        Ok(Some(object)) => to_xml_response(&LegalHold::new(object.legal_hold)),
+        Ok(None) => Err(StatusCode::NOT_FOUND),
+        Err(e) => {
+            eprintln!("Failed to get legal hold of '{}/{}': {}", bucket, key, e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+/// Place or remove an object's legal hold (PUT /{bucket}/{key+}?legal-hold)
+///
+/// Takes a `LegalHold` with `Status` `ON` or `OFF`. While the hold is on the
+/// object cannot be deleted or overwritten, whatever its retention.
+pub async fn put_object_legal_hold(
+    state: &AppState,
+    bucket: &str,
+    key: &str,
+    body: Body,
+) -> std::result::Result<Response, StatusCode> {
+    let body = axum::body::to_bytes(body, MAX_CONFIGURATION_SIZE)
+        .await
+        .map_err(|_| StatusCode::BAD_REQUEST)?;
+    let legal_hold = std::str::from_utf8(&body)
+        .map_err(|e| e.to_string())
+        .and_then(|xml| quick_xml::de::from_str::<LegalHold>(xml).map_err(|e| e.to_string()));
+    let on = match legal_hold.as_ref().map(|legal_hold| legal_hold.status.as_str()) {
+        Ok("ON") => true,
+        Ok("OFF") => false,
+        Ok(status) => {
+            let error = ObjectIOError::InvalidArgument {
+                message: format!("Unknown legal hold status: {}", status),
+            };
+            return Ok(object_error(&error, key));
+        }
+        Err(message) => {
+            let message = format!("The XML you provided was not well-formed: {}", message);
+            return Ok(ErrorDocument::new("MalformedXML", message).into_response(StatusCode::BAD_REQUEST));
+        }
+    };
+    if let Err(e) = check_lock_enabled(state, bucket).await {
+        return Ok(object_error(&e, key));
+    }
+
+    let _lock = state.locks.lock(bucket, key).await;
+    match state.metadata.set_object_legal_hold(bucket, key, on).await {
+        Ok(()) => Ok(StatusCode::OK.into_response()),
+        Err(e @ ObjectIOError::ObjectNotFound { .. }) => Ok(object_error(&e, key)),
+        Err(e) => {
+            eprintln!("Failed to set legal hold of '{}/{}': {}", bucket, key, e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::handlers::object::{self, DeleteObjectQuery, PutObjectQuery};
+    use axum::extract::{Path, Query, State};
+    use chrono::{Duration, SubsecRound};
+    use std::collections::HashMap;
+    use std::io::Cursor;
+
+    fn caller(is_admin: bool) -> Option<Extension<AuthContext>> {
+        Some(Extension(AuthContext {
+            access_key: "key".to_string(),
+            user_id: "user".to_string(),
+            is_admin,
+            permissions: Vec::new(),
+            scope: None,
+        }))
+    }
+
+    fn retention_headers(mode: &str, retain_until: chrono::DateTime<Utc>) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(MODE_HEADER, mode.parse().unwrap());
+        let retain_until = object_io_core::format_s3_timestamp(&retain_until);
+        headers.insert(RETAIN_UNTIL_HEADER, retain_until.parse().unwrap());
+        headers
+    }
+
+    async fn put(state: &AppState, key: &str, headers: HeaderMap) -> Response {
+        object::put_object(
+            Path(("vault".to_string(), key.to_string())),
+            State(state.clone()),
+            caller(false),
+            Query(PutObjectQuery {
+                content_type: None,
+                metadata: None,
+                part_number: None,
+                upload_id: None,
+                acl: None,
+                legal_hold: None,
+            }),
+            headers,
+            Body::from("data"),
+        )
+        .await
+        .unwrap()
+    }
+
+    async fn delete(state: &AppState, key: &str, auth: Option<Extension<AuthContext>>, bypass: bool) -> StatusCode {
+        let mut headers = HeaderMap::new();
+        if bypass {
+            headers.insert(BYPASS_GOVERNANCE_HEADER, "true".parse().unwrap());
+        }
+        let result = object::delete_object(
+            Path(("vault".to_string(), key.to_string())),
+            State(state.clone()),
+            auth,
+            Query(DeleteObjectQuery { upload_id: None }),
+            headers,
+        )
+        .await;
+        result.unwrap_or_else(|status| status)
+    }
+
+    async fn body_text(response: Response) -> String {
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        String::from_utf8_lossy(&body).into_owned()
+    }
+
+    #[tokio::test]
+    async fn test_bucket_object_lock_configuration() {
+        let dir = tempfile::tempdir().unwrap();
+        let state = AppState::for_test(dir.path()).await;
+        state.metadata.create_bucket("vault", "owner").await.unwrap();
+
+        let response = get_bucket_object_lock(&state, "vault").await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert!(body_text(response).await.contains("ObjectLockConfigurationNotFoundError"));
+
+        // Retention needs object lock on the bucket
+        let future = Utc::now() + Duration::days(1);
+        let response = put(&state, "early.txt", retention_headers("COMPLIANCE", future)).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert!(body_text(response).await.contains("Object Lock Configuration"));
+
+        let disabled = "<ObjectLockConfiguration><ObjectLockEnabled>Disabled</ObjectLockEnabled></ObjectLockConfiguration>";
+        let response = put_bucket_object_lock(&state, "vault", Body::from(disabled)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let response = put_bucket_object_lock(&state, "vault", Body::from("<ObjectLock")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let enabled = r#"<ObjectLockConfiguration xmlns="http://s3.amazonaws.com/doc/2006-03-01/">
+            <ObjectLockEnabled>Enabled</ObjectLockEnabled>
+        </ObjectLockConfiguration>"#;
+        let response = put_bucket_object_lock(&state, "vault", Body::from(enabled)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let response = get_bucket_object_lock(&state, "vault").await.unwrap();
+        assert!(body_text(response).await.contains("<ObjectLockEnabled>Enabled</ObjectLockEnabled>"));
+
+        assert_eq!(
+            put_bucket_object_lock(&state, "missing", Body::from(enabled)).await.unwrap_err(),
+            StatusCode::NOT_FOUND
+        );
+    }
+
+    #[tokio::test]
+    async fn test_retention_blocks_delete_and_overwrite() {
+        let dir = tempfile::tempdir().unwrap();
+        let state = AppState::for_test(dir.path()).await;
+        state.metadata.create_bucket("vault", "owner").await.unwrap();
+        state.metadata.enable_object_lock("vault", None).await.unwrap();
+        let future = Utc::now() + Duration::days(1);
+
+        let response = put(&state, "compliance.txt", retention_headers("COMPLIANCE", future)).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let object = state.metadata.get_object("vault", "compliance.txt").await.unwrap().unwrap();
+        assert_eq!(object.retention.unwrap().mode, object_io_core::ObjectLockMode::Compliance);
+
+        // Nobody may delete or overwrite a COMPLIANCE object, admins included
+        assert_eq!(delete(&state, "compliance.txt", caller(true), true).await, StatusCode::FORBIDDEN);
+        let response = put(&state, "compliance.txt", HeaderMap::new()).await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        assert!(body_text(response).await.contains("<Code>AccessDenied</Code>"));
+        assert!(state.storage.object_exists("vault", "compliance.txt").await.unwrap());
+
+        // GOVERNANCE retention yields to privileged callers who ask
+        put(&state, "governance.txt", retention_headers("GOVERNANCE", future)).await;
+        assert_eq!(delete(&state, "governance.txt", caller(false), true).await, StatusCode::FORBIDDEN);
+        assert_eq!(delete(&state, "governance.txt", caller(true), false).await, StatusCode::FORBIDDEN);
+        assert_eq!(delete(&state, "governance.txt", caller(true), true).await, StatusCode::NO_CONTENT);
+        assert!(!state.storage.object_exists("vault", "governance.txt").await.unwrap());
+
+        // Objects without retention, or whose retention has run out, are unprotected
+        put(&state, "plain.txt", HeaderMap::new()).await;
+        assert_eq!(delete(&state, "plain.txt", caller(false), false).await, StatusCode::NO_CONTENT);
+        put(&state, "expired.txt", retention_headers("COMPLIANCE", future)).await;
+        state
+            .metadata
+            .update_object_metadata("vault", "expired.txt", "text/plain", {
+                let mut metadata = HashMap::new();
+                ObjectRetention {
+                    mode: object_io_core::ObjectLockMode::Compliance,
+                    retain_until: Utc::now() - Duration::seconds(1),
+                }
+                .insert_into(&mut metadata);
+                metadata
+            })
+            .await
+            .unwrap();
+        assert_eq!(delete(&state, "expired.txt", caller(false), false).await, StatusCode::NO_CONTENT);
+
+        // Other writes to a locked object are refused too
+        let reader = Box::new(Cursor::new(b"data".to_vec()));
+        assert!(matches!(
+            object::store_object(&state, "vault", "compliance.txt", reader, HashMap::new()).await,
+            Err(ObjectIOError::ObjectLocked { .. })
+        ));
+        let response = object::rename_object(&state, "vault", "compliance.txt", "moved.txt", false).await.unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    }
+
+    #[tokio::test]
+    async fn test_default_retention() {
+        let dir = tempfile::tempdir().unwrap();
+        let state = AppState::for_test(dir.path()).await;
+        state.metadata.create_bucket("vault", "owner").await.unwrap();
+
+        let rule = |retention: &str| {
+            format!(
+                "<ObjectLockConfiguration><ObjectLockEnabled>Enabled</ObjectLockEnabled>\
+                 <Rule><DefaultRetention>{}</DefaultRetention></Rule></ObjectLockConfiguration>",
+                retention
+            )
+        };
+        for invalid in [
+            "<Mode>GOVERNANCE</Mode><Days>1</Days><Years>1</Years>",
+            "<Mode>GOVERNANCE</Mode>",
+            "<Mode>GOVERNANCE</Mode><Days>0</Days>",
+            "<Mode>FOREVER</Mode><Days>1</Days>",
+        ] {
+            let response = put_bucket_object_lock(&state, "vault", Body::from(rule(invalid))).await.unwrap();
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST, "{}", invalid);
+        }
+        assert!(!state.metadata.get_bucket("vault").await.unwrap().unwrap().object_lock_enabled);
+
+        let days = rule("<Mode>GOVERNANCE</Mode><Days>30</Days>");
+        let response = put_bucket_object_lock(&state, "vault", Body::from(days)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let response = get_bucket_object_lock(&state, "vault").await.unwrap();
+        let body = body_text(response).await;
+        assert!(body.contains("<Rule><DefaultRetention><Mode>GOVERNANCE</Mode><Days>30</Days></DefaultRetention></Rule>"));
+
+        // New objects without their own retention get the default, and HEAD shows it
+        let before = Utc::now();
+        put(&state, "default.txt", HeaderMap::new()).await;
+        let path = || Path(("vault".to_string(), "default.txt".to_string()));
+        let response = object::head_object(path(), State(state.clone())).await.unwrap();
+        assert_eq!(response.headers()[MODE_HEADER], "GOVERNANCE");
+        let retain_until = response.headers()[RETAIN_UNTIL_HEADER].to_str().unwrap();
+        let retain_until = object_io_core::parse_s3_timestamp(retain_until).unwrap();
+        assert!(retain_until >= (before + Duration::days(30)).trunc_subsecs(3));
+        assert!(retain_until <= Utc::now() + Duration::days(30));
+        assert_eq!(delete(&state, "default.txt", caller(false), false).await, StatusCode::FORBIDDEN);
+
+        // Retention sent with the object takes precedence
+        let future = Utc::now() + Duration::days(1);
+        put(&state, "own.txt", retention_headers("COMPLIANCE", future)).await;
+        let object = state.metadata.get_object("vault", "own.txt").await.unwrap().unwrap();
+        assert_eq!(object.retention.unwrap().mode, object_io_core::ObjectLockMode::Compliance);
+
+        // Leaving the rule out removes the default
+        let enabled = "<ObjectLockConfiguration><ObjectLockEnabled>Enabled</ObjectLockEnabled></ObjectLockConfiguration>";
+        put_bucket_object_lock(&state, "vault", Body::from(enabled)).await.unwrap();
+        let response = get_bucket_object_lock(&state, "vault").await.unwrap();
+        assert!(!body_text(response).await.contains("<Rule>"));
+        put(&state, "plain.txt", HeaderMap::new()).await;
+        assert!(state.metadata.get_object("vault", "plain.txt").await.unwrap().unwrap().retention.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_legal_hold_on_and_off() {
+        let dir = tempfile::tempdir().unwrap();
+        let state = AppState::for_test(dir.path()).await;
+        state.metadata.create_bucket("vault", "owner").await.unwrap();
+        put(&state, "held.txt", HeaderMap::new()).await;
+
+        // Legal holds need object lock on the bucket
+        let on = "<LegalHold><Status>ON</Status></LegalHold>";
+        let response = put_object_legal_hold(&state, "vault", "held.txt", Body::from(on)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        state.metadata.enable_object_lock("vault", None).await.unwrap();
+
+        let response = get_object_legal_hold(&state, "vault", "held.txt").await.unwrap();
+        assert!(body_text(response).await.contains("<Status>OFF</Status>"));
+        let response = put_object_legal_hold(&state, "vault", "held.txt", Body::from(on)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let response = get_object_legal_hold(&state, "vault", "held.txt").await.unwrap();
+        assert!(body_text(response).await.contains("<Status>ON</Status>"));
+
+        // A hold cannot be bypassed, even without retention
+        assert_eq!(delete(&state, "held.txt", caller(true), true).await, StatusCode::FORBIDDEN);
+        assert_eq!(put(&state, "held.txt", HeaderMap::new()).await.status(), StatusCode::FORBIDDEN);
+        assert!(state.storage.object_exists("vault", "held.txt").await.unwrap());
+
+        let off = "<LegalHold><Status>OFF</Status></LegalHold>";
+        let response = put_object_legal_hold(&state, "vault", "held.txt", Body::from(off)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(!state.metadata.get_object("vault", "held.txt").await.unwrap().unwrap().legal_hold);
+        assert_eq!(delete(&state, "held.txt", caller(false), false).await, StatusCode::NO_CONTENT);
+
+        let bad = "<LegalHold><Status>MAYBE</Status></LegalHold>";
+        let response = put_object_legal_hold(&state, "vault", "other.txt", Body::from(bad)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let response = put_object_legal_hold(&state, "vault", "held.txt", Body::from(on)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert!(body_text(response).await.contains("NoSuchKey"));
+    }
+}
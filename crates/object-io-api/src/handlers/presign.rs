@@ -0,0 +1,191 @@
+//! Presigned URL generation (POST /{bucket}/{key+}?presign)
+//!
+//! Signs a time-limited GET for an object with the caller's access key, so
+//! the link can be shared with clients that hold no credentials. The URL is
+//! verified by the same SigV4 code in the auth middleware.
+
+use axum::{
+    http::{header, HeaderMap, Method, StatusCode},
+    response::{IntoResponse, Json, Response},
+    Extension,
+};
+use chrono::Utc;
+use serde::Serialize;
+
+use crate::{
+    auth::{
+        sigv4::{SigV4Validator, MAX_PRESIGN_EXPIRES},
+        AuthContext,
+    },
+    state::AppState,
+};
+
+/// Validity of a presigned URL when the request names none, in seconds
+pub const DEFAULT_EXPIRES: u64 = 60 * 60;
+
+/// Presigned URL response
+#[derive(Debug, Serialize)]
+pub struct PresignResponse {
+    pub url: String,
+    /// When the URL stops working; absent when authentication is disabled
+    /// and the plain object URL is returned
+    pub expires_at: Option<String>,
+}
+
+/// Path of an object as a client sends it, with each key segment percent-encoded
+fn object_path(bucket: &str, key: &str) -> String {
+    let key = key
+        .split('/')
+        .map(|segment| urlencoding::encode(segment).into_owned())
+        .collect::<Vec<_>>()
+        .join("/");
+    format!("/{}/{}", bucket, key)
+}
+
+/// Generate a presigned GET URL for an object
+///
+/// Only admins and the bucket's owner may presign. `expires` is in seconds,
+/// defaulting to an hour and capped at the SigV4 maximum of seven days.
+pub async fn presign_object(
+    state: &AppState,
+    bucket: &str,
+    key: &str,
+    auth: &Option<Extension<AuthContext>>,
+    headers: &HeaderMap,
+    expires: Option<u64>,
+) -> std::result::Result<Response, StatusCode> {
+    let expires = expires.unwrap_or(DEFAULT_EXPIRES);
+    if expires == 0 || expires > MAX_PRESIGN_EXPIRES {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let host = headers
+        .get(header::HOST)
+        .and_then(|host| host.to_str().ok())
+        .ok_or(StatusCode::BAD_REQUEST)?;
+    let scheme = headers
+        .get("x-forwarded-proto")
+        .and_then(|proto| proto.to_str().ok())
+        .unwrap_or("http");
+
+    let internal_error = |e: object_io_core::ObjectIOError| {
+        eprintln!("Failed to presign '{}/{}': {}", bucket, key, e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    };
+    let owner = state
+        .metadata
+        .get_bucket(bucket)
+        .await
+        .map_err(internal_error)?
+        .ok_or(StatusCode::NOT_FOUND)?
+        .access_control
+        .owner
+        .name;
+    if state.metadata.get_object(bucket, key).await.map_err(internal_error)?.is_none() {
+        return Err(StatusCode::NOT_FOUND);
+    }
+
+    let path = object_path(bucket, key);
+    let Some(Extension(auth)) = auth else {
+        // Without authentication any client can GET the object directly
+        let url = format!("{}://{}{}", scheme, host, path);
+        return Ok(Json(PresignResponse { url, expires_at: None }).into_response());
+    };
+    if !auth.is_admin && auth.user_id != owner {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    let user = state
+        .metadata
+        .get_user_by_access_key(&auth.access_key)
+        .await
+        .map_err(internal_error)?
+        .ok_or(StatusCode::FORBIDDEN)?;
+    let now = Utc::now();
+    let validator = SigV4Validator::new(state.config.region.clone(), state.config.service.clone());
+    let query = validator
+        .presign(&Method::GET, &path, host, (&user.access_key, &user.secret_key), now, expires)
+        .map_err(internal_error)?;
+
+    Ok(Json(PresignResponse {
+        url: format!("{}://{}{}?{}", scheme, host, path, query),
+        expires_at: Some((now + chrono::Duration::seconds(expires as i64)).to_rfc3339()),
+    })
+    .into_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::sigv4::PresignedQuery;
+    use axum::{body::to_bytes, http::HeaderValue};
+    use std::collections::HashMap;
+
+    async fn url_of(response: Response) -> String {
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        serde_json::from_slice::<serde_json::Value>(&body).unwrap()["url"]
+            .as_str()
+            .unwrap()
+            .to_string()
+    }
+
+    #[tokio::test]
+    async fn test_presign_object() {
+        let dir = tempfile::tempdir().unwrap();
+        let state = AppState::for_test(dir.path()).await;
+        state.metadata.create_user("AKOWNER", "owner-secret", "Owner").await.unwrap();
+        state.metadata.create_user("AKOTHER", "other-secret", "Other").await.unwrap();
+        let owner = state.metadata.get_user_by_access_key("AKOWNER").await.unwrap().unwrap();
+        let owner_id = owner.id.as_ref().and_then(|id| id.as_str()).unwrap().to_string();
+        state.metadata.create_bucket("photos", &owner_id).await.unwrap();
+        state
+            .metadata
+            .put_object_metadata("photos", "2024/a b.jpg", 3, "image/jpeg", "etag", HashMap::new())
+            .await
+            .unwrap();
+
+        let mut headers = HeaderMap::new();
+        headers.insert(header::HOST, HeaderValue::from_static("s3.local:5500"));
+        let caller = |access_key: &str, user_id: &str| {
+            Some(Extension(AuthContext {
+                access_key: access_key.to_string(),
+                user_id: user_id.to_string(),
+                is_admin: false,
+                permissions: Vec::new(),
+                scope: None,
+            }))
+        };
+        let presign = |key: &'static str, auth: Option<Extension<AuthContext>>, expires: Option<u64>| {
+            let (state, headers) = (state.clone(), headers.clone());
+            async move { presign_object(&state, "photos", key, &auth, &headers, expires).await }
+        };
+
+        // Authentication disabled: the plain object URL
+        let url = url_of(presign("2024/a b.jpg", None, None).await.unwrap()).await;
+        assert_eq!(url, "http://s3.local:5500/photos/2024/a%20b.jpg");
+
+        // Only the owner may presign, and only existing objects
+        let other = caller("AKOTHER", "someone-else");
+        assert_eq!(presign("2024/a b.jpg", other, None).await.unwrap_err(), StatusCode::FORBIDDEN);
+        let owner = caller("AKOWNER", &owner_id);
+        assert_eq!(presign("missing.jpg", owner.clone(), None).await.unwrap_err(), StatusCode::NOT_FOUND);
+        assert_eq!(
+            presign("2024/a b.jpg", owner.clone(), Some(MAX_PRESIGN_EXPIRES + 1)).await.unwrap_err(),
+            StatusCode::BAD_REQUEST
+        );
+
+        // The signed URL verifies against the owner's secret
+        let url = url_of(presign("2024/a b.jpg", owner, Some(60)).await.unwrap()).await;
+        let (path, query) = url.strip_prefix("http://s3.local:5500").unwrap().split_once('?').unwrap();
+        assert_eq!(path, "/photos/2024/a%20b.jpg");
+        let query = PresignedQuery::parse(query).unwrap();
+        assert_eq!(query.expires, 60);
+        assert!(!query.is_expired(Utc::now()));
+        let validator = SigV4Validator::new(state.config.region.clone(), state.config.service.clone());
+        assert!(validator
+            .validate_presigned(&Method::GET, path, &headers, &query, "owner-secret")
+            .unwrap());
+        assert!(!validator
+            .validate_presigned(&Method::GET, path, &headers, &query, "other-secret")
+            .unwrap());
+    }
+}
@@ -0,0 +1,411 @@
+//! Prefix renames (POST /{bucket}?rename-prefix&from=X&to=Y)
+//!
+//! An ObjectIO extension that moves a "folder": every object under one
+//! prefix goes to the same key under another, keeping its ETag, metadata and
+//! ACL. The storage backend moves the whole prefix in one step when it can
+//! (a directory rename on the filesystem), and each object is moved in turn
+//! otherwise.
+
+use axum::{
+    http::StatusCode,
+    response::{IntoResponse, Json, Response},
+};
+use object_io_core::{KeyPatterns, ListObjectsRequest, ObjectIOError};
+use serde::Serialize;
+
+use crate::{
+    handlers::{bucket::bucket_error, key_patterns},
+    state::AppState,
+};
+
+/// Outcome of a prefix rename, complete or not
+#[derive(Debug, Serialize)]
+pub struct RenamePrefixResponse {
+    pub from: String,
+    pub to: String,
+    /// Objects whose records now sit under `to`
+    pub moved: u64,
+    /// Objects whose records are still under `from`
+    pub remaining: u64,
+    /// Key the rename stopped at, if it failed part way
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub failed_key: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Where and why a prefix rename stopped
+#[derive(Debug)]
+pub struct RenameFailure {
+    /// Objects moved before the failure
+    pub moved: u64,
+    /// Objects the rename set out to move
+    pub total: u64,
+    pub key: Option<String>,
+    pub error: ObjectIOError,
+}
+
+impl From<ObjectIOError> for RenameFailure {
+    fn from(error: ObjectIOError) -> Self {
+        Self {
+            moved: 0,
+            total: 0,
+            key: None,
+            error,
+        }
+    }
+}
+
+/// Objects listed, checked and moved at a time
+const PAGE_SIZE: u32 = 1000;
+
+/// Fail unless `from` and `to` are distinct folder prefixes, neither inside
+/// the other, whose segments could all begin a valid key
+fn validate_prefixes(from: &str, to: &str) -> object_io_core::Result<()> {
+    let invalid = |message: &str| {
+        Err(ObjectIOError::InvalidArgument {
+            message: message.to_string(),
+        })
+    };
+    if from.is_empty() || to.is_empty() || !from.ends_with('/') || !to.ends_with('/') {
+        return invalid("Both prefixes must be non-empty and end in '/'");
+    }
+    let valid_segments = |prefix: &str| object_io_core::validate_object_key(&prefix[..prefix.len() - 1]).is_ok();
+    if !valid_segments(from) || !valid_segments(to) {
+        return invalid("Prefixes cannot start with '/' or have empty, '.' or '..' segments");
+    }
+    if from.starts_with(to) || to.starts_with(from) {
+        return invalid("A prefix cannot be renamed into itself or one of its parents");
+    }
+    Ok(())
+}
+
+/// Up to `max_keys` keys under `prefix` in the metadata store, in order,
+/// starting after `marker`
+async fn keys_page(
+    state: &AppState,
+    bucket: &str,
+    prefix: &str,
+    marker: Option<&str>,
+    max_keys: u32,
+) -> object_io_core::Result<Vec<String>> {
+    let mut request = ListObjectsRequest::new(bucket);
+    request.prefix = Some(prefix.to_string());
+    request.marker = marker.map(str::to_string);
+    request.max_keys = Some(max_keys);
+    let page = state.metadata.list_objects(&request).await?;
+    Ok(page.objects.into_iter().map(|object| object.key).collect())
+}
+
+/// Fail unless the object at `key` may move to `dst_key`: a valid key that
+/// fits the bucket's key patterns, with the object under no object lock
+async fn check_move(
+    state: &AppState,
+    bucket: &str,
+    patterns: &KeyPatterns,
+    key: &str,
+    dst_key: &str,
+) -> object_io_core::Result<()> {
+    object_io_core::validate_object_key(dst_key)?;
+    patterns.check(dst_key)?;
+    state.check_object_lock(bucket, key, false).await
+}
+
+/// Move every object under `from` in `bucket` to the same key under `to`,
+/// returning how many were moved
+///
+/// Nothing is moved unless every object may be: the destination must be
+/// empty, every new key must fit the bucket's key patterns, and no object
+/// may be under object lock. Keys are listed, checked and moved a page at a
+/// time, each object checked again with its keys locked as it moves. A
+/// prefix that fits in one page has all its keys locked at once, and the
+/// backend moves its data in one step when it can. A failure part way
+/// leaves the objects moved so far under `to` and says how many there were
+/// and where it stopped. When the backend moved all the data in one step,
+/// only the records of the remaining objects are left behind.
+pub async fn rename_prefix(state: &AppState, bucket: &str, from: &str, to: &str) -> Result<u64, RenameFailure> {
+    rename_prefix_in_pages(state, bucket, from, to, PAGE_SIZE).await
+}
+
+async fn rename_prefix_in_pages(
+    state: &AppState,
+    bucket: &str,
+    from: &str,
+    to: &str,
+    page_size: u32,
+) -> Result<u64, RenameFailure> {
+    validate_prefixes(from, to)?;
+    let patterns = key_patterns::bucket_key_patterns(state, bucket).await?;
+    let destination = |key: &str| format!("{}{}", to, &key[from.len()..]);
+
+    let mut probe = ListObjectsRequest::new(bucket);
+    probe.prefix = Some(to.to_string());
+    probe.max_keys = Some(1);
+    let existing = state.metadata.list_objects(&probe).await?.objects.into_iter().next();
+    let existing = match existing {
+        Some(object) => Some(object),
+        None => state.storage.list_objects(&probe).await?.objects.into_iter().next(),
+    };
+    if let Some(object) = existing {
+        return Err(ObjectIOError::ObjectAlreadyExists {
+            bucket: bucket.to_string(),
+            key: object.key,
+        }
+        .into());
+    }
+
+    // Every object is checked before any moves; objects written under
+    // `from` after this pass are left where they are
+    let mut total = 0;
+    let mut last: Option<String> = None;
+    loop {
+        let keys = keys_page(state, bucket, from, last.as_deref(), page_size).await?;
+        for key in &keys {
+            check_move(state, bucket, &patterns, key, &destination(key)).await?;
+        }
+        total += keys.len() as u64;
+        let full = keys.len() == page_size as usize;
+        if let Some(key) = keys.into_iter().last() {
+            last = Some(key);
+        }
+        if !full {
+            break;
+        }
+    }
+    let Some(last) = last else {
+        return Ok(0);
+    };
+
+    let mut moved = 0;
+    let mut data_moved = false;
+    let mut marker: Option<String> = None;
+    loop {
+        let failure = |moved: u64, key: Option<&String>, error: ObjectIOError| RenameFailure {
+            moved,
+            total,
+            key: key.cloned(),
+            error,
+        };
+        let keys: Vec<String> = keys_page(state, bucket, from, marker.as_deref(), page_size)
+            .await
+            .map_err(|error| failure(moved, None, error))?
+            .into_iter()
+            .filter(|key| *key <= last)
+            .collect();
+        if keys.is_empty() {
+            return Ok(moved);
+        }
+
+        let mut locked: Vec<String> = keys.iter().flat_map(|key| [key.clone(), destination(key)]).collect();
+        locked.sort();
+        let mut _guards = Vec::with_capacity(locked.len());
+        for key in &locked {
+            _guards.push(state.locks.lock(bucket, key).await);
+        }
+
+        if moved == 0 && total <= page_size as u64 {
+            for key in &keys {
+                check_move(state, bucket, &patterns, key, &destination(key)).await?;
+            }
+            data_moved = state.storage.move_prefix(bucket, from, to).await?;
+        }
+        for key in &keys {
+            let dst_key = destination(key);
+            let result = async {
+                if !data_moved {
+                    check_move(state, bucket, &patterns, key, &dst_key).await?;
+                    state.storage.move_object(bucket, key, &dst_key).await?;
+                }
+                state.metadata.move_object(bucket, key, &dst_key).await
+            };
+            if let Err(error) = result.await {
+                return Err(failure(moved, Some(key), error));
+            }
+            moved += 1;
+        }
+        marker = keys.into_iter().last();
+    }
+}
+
+/// Rename a prefix (POST /{bucket}?rename-prefix&from=X&to=Y)
+///
+/// Failures before anything was moved get the usual error document; a
+/// failure part way answers `500` with a [`RenamePrefixResponse`] saying
+/// how far the rename got.
+pub async fn rename(
+    state: &AppState,
+    bucket: &str,
+    from: Option<String>,
+    to: Option<String>,
+) -> std::result::Result<Response, StatusCode> {
+    let (Some(from), Some(to)) = (from, to) else {
+        return Err(StatusCode::BAD_REQUEST);
+    };
+    match rename_prefix(state, bucket, &from, &to).await {
+        Ok(moved) => Ok(Json(RenamePrefixResponse {
+            from,
+            to,
+            moved,
+            remaining: 0,
+            failed_key: None,
+            error: None,
+        })
+        .into_response()),
+        Err(failure) if failure.moved == 0 && failure.error.status_code() < 500 => {
+            Ok(bucket_error(&failure.error, bucket))
+        }
+        Err(failure) => {
+            eprintln!(
+                "Renaming '{}/{}' to '{}' stopped after {} of {} objects: {}",
+                bucket, from, to, failure.moved, failure.total, failure.error
+            );
+            let report = RenamePrefixResponse {
+                from,
+                to,
+                moved: failure.moved,
+                remaining: failure.total.saturating_sub(failure.moved),
+                failed_key: failure.key,
+                error: Some(failure.error.to_string()),
+            };
+            Ok((StatusCode::INTERNAL_SERVER_ERROR, Json(report)).into_response())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::handlers::object;
+    use std::collections::HashMap;
+    use std::io::Cursor;
+
+    async fn populate(state: &AppState) {
+        state.metadata.create_bucket("photos", "owner").await.unwrap();
+        for key in ["2023/a.jpg", "2023/trip/b.jpg", "2023/trip/c.jpg", "2024/d.jpg"] {
+            let reader = Box::new(Cursor::new(key.as_bytes().to_vec()));
+            let metadata = HashMap::from([("x-amz-meta-album".to_string(), "summer".to_string())]);
+            object::store_object(state, "photos", key, reader, metadata).await.unwrap();
+        }
+    }
+
+    async fn keys(state: &AppState) -> Vec<String> {
+        keys_page(state, "photos", "", None, 1000).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_rename_populated_prefix() {
+        // The filesystem renames the directory; the memory backend moves each object
+        for memory in [false, true] {
+            let dir = tempfile::tempdir().unwrap();
+            let mut state = AppState::for_test(dir.path()).await;
+            if memory {
+                state.storage = object_io_storage::StorageBackend::memory();
+            }
+            populate(&state).await;
+            let before = state.metadata.get_object("photos", "2023/trip/b.jpg").await.unwrap().unwrap();
+
+            assert_eq!(rename_prefix(&state, "photos", "2023/", "archive/2023/").await.unwrap(), 3);
+            assert_eq!(
+                keys(&state).await,
+                ["2024/d.jpg", "archive/2023/a.jpg", "archive/2023/trip/b.jpg", "archive/2023/trip/c.jpg"]
+            );
+            let after = state.metadata.get_object("photos", "archive/2023/trip/b.jpg").await.unwrap().unwrap();
+            assert_eq!(after.etag, before.etag);
+            assert_eq!(after.metadata, before.metadata);
+            assert!(!state.storage.object_exists("photos", "2023/a.jpg").await.unwrap());
+            let metadata = state.storage.get_object_metadata("photos", "archive/2023/trip/b.jpg").await.unwrap();
+            assert_eq!(metadata["x-amz-meta-album"], "summer");
+
+            // An empty prefix moves nothing
+            assert_eq!(rename_prefix(&state, "photos", "2025/", "2026/").await.unwrap(), 0);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_rename_prefix_checks_before_moving() {
+        let dir = tempfile::tempdir().unwrap();
+        let state = AppState::for_test(dir.path()).await;
+        populate(&state).await;
+        let all = keys(&state).await;
+
+        let invalid = [
+            ("2023", "old/"),
+            ("2023/", "2023/trip/"),
+            ("2023/trip/", "2023/"),
+            ("", "old/"),
+            // Prefixes that would lead out of the bucket's directory
+            ("2023/", "../../"),
+            ("2023/", "old/../../"),
+            ("../", "old/"),
+            ("2023/", "/old/"),
+            ("2023/", "old//"),
+            ("./", "old/"),
+        ];
+        for (from, to) in invalid {
+            let failure = rename_prefix(&state, "photos", from, to).await.unwrap_err();
+            assert!(matches!(failure.error, ObjectIOError::InvalidArgument { .. }), "{} -> {}", from, to);
+        }
+        let failure = rename_prefix(&state, "photos", "2023/", "2024/").await.unwrap_err();
+        assert!(matches!(failure.error, ObjectIOError::ObjectAlreadyExists { .. }));
+        let failure = rename_prefix(&state, "missing", "2023/", "old/").await.unwrap_err();
+        assert!(matches!(failure.error, ObjectIOError::BucketNotFound { .. }));
+
+        // One held object keeps the whole prefix in place
+        state.metadata.enable_object_lock("photos", None).await.unwrap();
+        state.metadata.set_object_legal_hold("photos", "2023/trip/c.jpg", true).await.unwrap();
+        let response = rename(&state, "photos", Some("2023/".to_string()), Some("old/".to_string())).await.unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        assert_eq!(keys(&state).await, all);
+        assert!(state.storage.object_exists("photos", "2023/a.jpg").await.unwrap());
+
+        let response = rename(&state, "photos", Some("2023/".to_string()), None).await.unwrap_err();
+        assert_eq!(response, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_rename_prefix_in_pages() {
+        for memory in [false, true] {
+            let dir = tempfile::tempdir().unwrap();
+            let mut state = AppState::for_test(dir.path()).await;
+            if memory {
+                state.storage = object_io_storage::StorageBackend::memory();
+            }
+            populate(&state).await;
+
+            // Three objects in pages of two move one at a time
+            assert_eq!(rename_prefix_in_pages(&state, "photos", "2023/", "old/", 2).await.unwrap(), 3);
+            assert_eq!(keys(&state).await, ["2024/d.jpg", "old/a.jpg", "old/trip/b.jpg", "old/trip/c.jpg"]);
+            for key in ["old/a.jpg", "old/trip/b.jpg", "old/trip/c.jpg"] {
+                assert!(state.storage.object_exists("photos", key).await.unwrap(), "{}", key);
+            }
+            assert!(!state.storage.object_exists("photos", "2023/a.jpg").await.unwrap());
+            assert!(state.locks.is_empty());
+
+            // A failing check in a later page moves nothing
+            state.metadata.enable_object_lock("photos", None).await.unwrap();
+            state.metadata.set_object_legal_hold("photos", "old/trip/c.jpg", true).await.unwrap();
+            let failure = rename_prefix_in_pages(&state, "photos", "old/", "new/", 2).await.unwrap_err();
+            assert_eq!(failure.moved, 0);
+            assert_eq!(keys(&state).await, ["2024/d.jpg", "old/a.jpg", "old/trip/b.jpg", "old/trip/c.jpg"]);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_partial_rename_is_reported() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut state = AppState::for_test(dir.path()).await;
+        state.storage = object_io_storage::StorageBackend::memory();
+        populate(&state).await;
+        // A record whose data is gone cannot be moved
+        state.storage.delete_object("photos", "2023/trip/b.jpg").await.unwrap();
+
+        let response = rename(&state, "photos", Some("2023/".to_string()), Some("old/".to_string())).await.unwrap();
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let report: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(report["moved"], 1);
+        assert_eq!(report["remaining"], 2);
+        assert_eq!(report["failed_key"], "2023/trip/b.jpg");
+        assert_eq!(keys(&state).await, ["2023/trip/b.jpg", "2023/trip/c.jpg", "2024/d.jpg", "old/a.jpg"]);
+    }
+}
@@ -0,0 +1,262 @@
+//! Point-in-time bucket snapshots
+//!
+//! A snapshot captures every object in a bucket with its database record.
+//! The filesystem backend hard-links the data, so a snapshot takes almost no
+//! space until the live objects it shares are overwritten or deleted.
+
+use axum::{http::StatusCode, response::Json};
+use chrono::Utc;
+use object_io_core::{ListObjectsRequest, Object, ObjectIOError, Result};
+use serde::Serialize;
+
+use crate::{handlers::object, state::AppState};
+
+/// Format of snapshot IDs: the UTC time the snapshot was taken, so IDs sort
+/// in the order snapshots were taken
+const SNAPSHOT_ID_FORMAT: &str = "%Y%m%dT%H%M%S%.6fZ";
+
+/// A snapshot just taken
+#[derive(Debug, Serialize)]
+pub struct SnapshotSummary {
+    pub snapshot: String,
+    pub objects: u64,
+    pub bytes: u64,
+}
+
+/// Snapshots of a bucket, oldest first
+#[derive(Debug, Serialize)]
+pub struct SnapshotList {
+    pub snapshots: Vec<String>,
+}
+
+/// One object in a snapshot
+#[derive(Debug, Serialize)]
+pub struct SnapshotObject {
+    pub key: String,
+    pub size: u64,
+    pub etag: String,
+    pub last_modified: String,
+}
+
+/// The objects in a snapshot, ordered by key
+#[derive(Debug, Serialize)]
+pub struct SnapshotContents {
+    pub snapshot: String,
+    pub objects: Vec<SnapshotObject>,
+}
+
+/// Result of restoring from a snapshot
+#[derive(Debug, Serialize)]
+pub struct RestoreSummary {
+    pub snapshot: String,
+    pub restored: u64,
+}
+
+fn error_status(bucket: &str, e: &ObjectIOError) -> StatusCode {
+    let status = StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+    if status.is_server_error() {
+        eprintln!("Snapshot operation on bucket '{}' failed: {}", bucket, e);
+    }
+    status
+}
+
+/// Snapshot every object in `bucket`
+///
+/// Each object is added under its lock, so its data and record match; the
+/// bucket as a whole is not frozen, so objects written while the snapshot
+/// is taken may or may not be in it.
+pub async fn snapshot_bucket(state: &AppState, bucket: &str) -> Result<SnapshotSummary> {
+    let snapshot = Utc::now().format(SNAPSHOT_ID_FORMAT).to_string();
+    let mut manifest: Vec<Object> = Vec::new();
+    let mut request = ListObjectsRequest::new(bucket);
+    loop {
+        let page = state.metadata.list_objects(&request).await?;
+        for summary in &page.objects {
+            request.marker = Some(summary.key.clone());
+            let _lock = state.locks.lock(bucket, &summary.key).await;
+            let Some(record) = state.metadata.get_object(bucket, &summary.key).await? else {
+                continue;
+            };
+            match state.storage.snapshot_object(bucket, &snapshot, &record.key).await {
+                Ok(()) => manifest.push(record),
+                // A record whose data is gone has nothing to restore
+                Err(ObjectIOError::ObjectNotFound { .. }) => {}
+                Err(e) => return Err(e),
+            }
+        }
+        if !page.is_truncated {
+            break;
+        }
+    }
+
+    let bytes = manifest.iter().map(|object| object.size).sum();
+    state
+        .storage
+        .save_snapshot_manifest(bucket, &snapshot, &serde_json::to_vec(&manifest)?)
+        .await?;
+    Ok(SnapshotSummary {
+        snapshot,
+        objects: manifest.len() as u64,
+        bytes,
+    })
+}
+
+/// The database records saved with `snapshot`
+async fn load_manifest(state: &AppState, bucket: &str, snapshot: &str) -> Result<Vec<Object>> {
+    let manifest = state.storage.snapshot_manifest(bucket, snapshot).await?;
+    serde_json::from_slice(&manifest).map_err(|e| ObjectIOError::StorageError {
+        message: format!("Invalid manifest in snapshot {}: {}", snapshot, e),
+    })
+}
+
+/// Put the objects of `snapshot` back, or only `key` when given, replacing
+/// their live data and records, and return how many were restored
+///
+/// Objects written since the snapshot was taken are left alone; empty the
+/// bucket first to return it exactly to the snapshot. Restoring over an
+/// object under retention fails with `ObjectLocked`.
+pub async fn restore_snapshot(state: &AppState, bucket: &str, snapshot: &str, key: Option<&str>) -> Result<u64> {
+    let manifest = load_manifest(state, bucket, snapshot).await?;
+    let mut restored = 0;
+    for record in manifest.iter().filter(|record| key.is_none_or(|key| record.key == key)) {
+        let _lock = state.locks.lock(bucket, &record.key).await;
+        state.check_object_lock(bucket, &record.key, false).await?;
+        state.storage.restore_object(bucket, snapshot, &record.key).await?;
+
+        let mut metadata = record.metadata.clone();
+        metadata.extend(record.headers().map(|(name, value)| (name.to_string(), value.to_string())));
+        metadata.insert("content-type".to_string(), record.content_type.clone());
+        object::record_object(state, bucket, &record.key, record.size, &record.etag, &metadata).await?;
+        if !record.acl.is_empty() {
+            state.metadata.set_object_acl(bucket, &record.key, &record.acl).await?;
+        }
+        restored += 1;
+    }
+
+    match key {
+        Some(key) if restored == 0 => Err(ObjectIOError::ObjectNotFound {
+            bucket: bucket.to_string(),
+            key: key.to_string(),
+        }),
+        _ => Ok(restored),
+    }
+}
+
+/// Take a snapshot (POST /{bucket}?snapshot)
+pub async fn create_snapshot(state: &AppState, bucket: &str) -> std::result::Result<Json<SnapshotSummary>, StatusCode> {
+    snapshot_bucket(state, bucket)
+        .await
+        .map(Json)
+        .map_err(|e| error_status(bucket, &e))
+}
+
+/// List a bucket's snapshots (GET /{bucket}?snapshots)
+pub async fn list_snapshots(state: &AppState, bucket: &str) -> std::result::Result<Json<SnapshotList>, StatusCode> {
+    match state.storage.list_snapshots(bucket).await {
+        Ok(snapshots) => Ok(Json(SnapshotList { snapshots })),
+        Err(e) => Err(error_status(bucket, &e)),
+    }
+}
+
+/// List the objects in a snapshot (GET /{bucket}?snapshot=ID)
+pub async fn get_snapshot(
+    state: &AppState,
+    bucket: &str,
+    snapshot: String,
+) -> std::result::Result<Json<SnapshotContents>, StatusCode> {
+    let manifest = load_manifest(state, bucket, &snapshot)
+        .await
+        .map_err(|e| error_status(bucket, &e))?;
+    let objects = manifest
+        .into_iter()
+        .map(|record| SnapshotObject {
+            key: record.key,
+            size: record.size,
+            etag: record.etag,
+            last_modified: record.last_modified.to_rfc3339(),
+        })
+        .collect();
+    Ok(Json(SnapshotContents { snapshot, objects }))
+}
+
+/// Restore from a snapshot (POST /{bucket}?restore=ID[&key=K])
+pub async fn restore(
+    state: &AppState,
+    bucket: &str,
+    snapshot: String,
+    key: Option<&str>,
+) -> std::result::Result<Json<RestoreSummary>, StatusCode> {
+    match restore_snapshot(state, bucket, &snapshot, key).await {
+        Ok(restored) => Ok(Json(RestoreSummary { snapshot, restored })),
+        Err(e) => Err(error_status(bucket, &e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::io::Cursor;
+    use tokio::io::AsyncReadExt;
+
+    async fn store(state: &AppState, key: &str, data: &[u8], content_type: &str) -> Object {
+        let metadata = HashMap::from([("content-type".to_string(), content_type.to_string())]);
+        object::store_object(state, "photos", key, Box::new(Cursor::new(data.to_vec())), metadata)
+            .await
+            .unwrap()
+    }
+
+    async fn read(state: &AppState, key: &str) -> Vec<u8> {
+        let mut data = Vec::new();
+        let mut reader = state.storage.get_object("photos", key).await.unwrap();
+        reader.read_to_end(&mut data).await.unwrap();
+        data
+    }
+
+    #[tokio::test]
+    async fn test_snapshot_and_restore() {
+        let dir = tempfile::tempdir().unwrap();
+        let state = AppState::for_test(dir.path()).await;
+        state.metadata.create_bucket("photos", "owner").await.unwrap();
+        let cat = store(&state, "cat.jpg", b"meow", "image/jpeg").await;
+        store(&state, "albums/dog.jpg", b"woof", "image/jpeg").await;
+
+        let summary = snapshot_bucket(&state, "photos").await.unwrap();
+        assert_eq!((summary.objects, summary.bytes), (2, 8));
+        let Json(list) = list_snapshots(&state, "photos").await.unwrap();
+        assert_eq!(list.snapshots, [summary.snapshot.as_str()]);
+        let Json(contents) = get_snapshot(&state, "photos", summary.snapshot.clone()).await.unwrap();
+        let keys: Vec<&str> = contents.objects.iter().map(|object| object.key.as_str()).collect();
+        assert_eq!(keys, ["albums/dog.jpg", "cat.jpg"]);
+
+        // Changes to the live bucket leave the snapshot as it was
+        store(&state, "cat.jpg", b"hiss", "text/plain").await;
+        state.storage.delete_object("photos", "albums/dog.jpg").await.unwrap();
+        state.metadata.delete_object("photos", "albums/dog.jpg").await.unwrap();
+        store(&state, "new.jpg", b"new", "image/jpeg").await;
+
+        assert_eq!(restore_snapshot(&state, "photos", &summary.snapshot, Some("cat.jpg")).await.unwrap(), 1);
+        assert_eq!(read(&state, "cat.jpg").await, b"meow");
+        let record = state.metadata.get_object("photos", "cat.jpg").await.unwrap().unwrap();
+        assert_eq!((record.etag, record.content_type), (cat.etag, "image/jpeg".to_string()));
+        assert!(state.metadata.get_object("photos", "albums/dog.jpg").await.unwrap().is_none());
+
+        assert_eq!(restore_snapshot(&state, "photos", &summary.snapshot, None).await.unwrap(), 2);
+        assert_eq!(read(&state, "albums/dog.jpg").await, b"woof");
+        assert!(state.metadata.get_object("photos", "albums/dog.jpg").await.unwrap().is_some());
+        // Objects written since the snapshot are kept
+        assert_eq!(read(&state, "new.jpg").await, b"new");
+
+        assert!(matches!(
+            restore_snapshot(&state, "photos", &summary.snapshot, Some("new.jpg")).await,
+            Err(ObjectIOError::ObjectNotFound { .. })
+        ));
+        for (snapshot, status) in [("20000101T000000Z", StatusCode::NOT_FOUND), ("../photos", StatusCode::BAD_REQUEST)] {
+            assert_eq!(get_snapshot(&state, "photos", snapshot.to_string()).await.unwrap_err(), status);
+        }
+
+        // Only backends that can share data support snapshots
+        let memory = object_io_storage::StorageBackend::memory();
+        assert!(matches!(memory.list_snapshots("photos").await, Err(ObjectIOError::NotImplemented { .. })));
+    }
+}
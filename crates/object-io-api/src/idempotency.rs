@@ -0,0 +1,227 @@
+//! Replay of retried PUTs carrying an idempotency key
+//!
+//! A client may tag a PUT with `x-objectio-idempotency-key`. The outcome is
+//! remembered for the configured window, so a retry with the same key and
+//! the same content is answered with the original result instead of being
+//! written again. The key is reserved before the first attempt is written,
+//! so a retry racing it is refused rather than writing a second time.
+
+use chrono::{DateTime, Duration, Utc};
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// Header carrying a client's idempotency key
+pub const IDEMPOTENCY_HEADER: &str = "x-objectio-idempotency-key";
+
+/// The outcome of a PUT made with an idempotency key
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecordedPut {
+    pub bucket: String,
+    pub key: String,
+    pub etag: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+impl RecordedPut {
+    /// A PUT of `etag` to `bucket`/`key`, remembered for `window` from `now`
+    pub fn new(bucket: &str, key: &str, etag: &str, window: Duration, now: DateTime<Utc>) -> Self {
+        Self {
+            bucket: bucket.to_string(),
+            key: key.to_string(),
+            etag: etag.to_string(),
+            expires_at: now + window,
+        }
+    }
+
+    /// Whether a retry writing `etag` to `bucket`/`key` repeats this PUT
+    pub fn matches(&self, bucket: &str, key: &str, etag: &str) -> bool {
+        self.bucket == bucket && self.key == key && self.etag == etag
+    }
+}
+
+/// Most keys remembered at once; past it the soonest to expire is forgotten
+const MAX_ENTRIES: usize = 100_000;
+
+/// A remembered idempotency key
+enum Entry {
+    /// The first PUT with the key is still being written
+    InFlight,
+    Recorded(RecordedPut),
+}
+
+/// What a PUT carrying an idempotency key should do
+pub enum Claim<'a> {
+    /// Go ahead and write; the key is held until the reservation is recorded
+    /// or dropped
+    Reserved(Reservation<'a>),
+    /// Replay this earlier PUT
+    Recorded(RecordedPut),
+    /// Another PUT with the key is still being written
+    InFlight,
+    /// Every remembered key is in flight, so this one cannot be held
+    Full,
+}
+
+/// A key held for a PUT being written; dropping it without recording the
+/// outcome releases the key, so a failed PUT can be retried
+pub struct Reservation<'a> {
+    cache: &'a IdempotencyCache,
+    id: (String, String),
+    recorded: bool,
+}
+
+impl Reservation<'_> {
+    /// Remember the outcome of the PUT
+    pub fn record(mut self, put: RecordedPut) {
+        let mut entries = self.cache.entries.lock().unwrap();
+        entries.insert(std::mem::take(&mut self.id), Entry::Recorded(put));
+        self.recorded = true;
+    }
+}
+
+impl Drop for Reservation<'_> {
+    fn drop(&mut self) {
+        if !self.recorded {
+            self.cache.entries.lock().unwrap().remove(&self.id);
+        }
+    }
+}
+
+/// Recent PUTs by caller and idempotency key, and those being written
+pub struct IdempotencyCache {
+    entries: Mutex<HashMap<(String, String), Entry>>,
+    max_entries: usize,
+}
+
+impl Default for IdempotencyCache {
+    fn default() -> Self {
+        Self::with_limit(MAX_ENTRIES)
+    }
+}
+
+impl IdempotencyCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A cache remembering at most `max_entries` keys
+    pub fn with_limit(max_entries: usize) -> Self {
+        Self {
+            entries: Mutex::new(HashMap::new()),
+            max_entries,
+        }
+    }
+
+    /// Look up the key `caller` sent as `token`, reserving it if it is not
+    /// remembered, and drop entries that have expired by `now`
+    ///
+    /// Reserving and checking happen under one lock, so of several PUTs
+    /// racing with the same key exactly one is written.
+    pub fn claim(&self, caller: &str, token: &str, now: DateTime<Utc>) -> Claim<'_> {
+        let mut entries = self.entries.lock().unwrap();
+        entries.retain(|_, entry| match entry {
+            Entry::InFlight => true,
+            Entry::Recorded(recorded) => recorded.expires_at > now,
+        });
+        let id = (caller.to_string(), token.to_string());
+        match entries.get(&id) {
+            Some(Entry::InFlight) => return Claim::InFlight,
+            Some(Entry::Recorded(recorded)) => return Claim::Recorded(recorded.clone()),
+            None => {}
+        }
+        if entries.len() >= self.max_entries {
+            let soonest = entries
+                .iter()
+                .filter_map(|(id, entry)| match entry {
+                    Entry::Recorded(recorded) => Some((id, recorded.expires_at)),
+                    Entry::InFlight => None,
+                })
+                .min_by_key(|(_, expires_at)| *expires_at)
+                .map(|(id, _)| id.clone());
+            match soonest {
+                Some(soonest) => entries.remove(&soonest),
+                None => return Claim::Full,
+            };
+        }
+        entries.insert(id.clone(), Entry::InFlight);
+        Claim::Reserved(Reservation {
+            cache: self,
+            id,
+            recorded: false,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reserve<'a>(cache: &'a IdempotencyCache, caller: &str, token: &str, now: DateTime<Utc>) -> Reservation<'a> {
+        match cache.claim(caller, token, now) {
+            Claim::Reserved(reservation) => reservation,
+            _ => panic!("{} was not reserved", token),
+        }
+    }
+
+    /// The PUT remembered for `token`, without reserving it
+    fn recorded(cache: &IdempotencyCache, caller: &str, token: &str, now: DateTime<Utc>) -> Option<RecordedPut> {
+        match cache.entries.lock().unwrap().get(&(caller.to_string(), token.to_string())) {
+            Some(Entry::Recorded(put)) if put.expires_at > now => Some(put.clone()),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_idempotency_cache() {
+        let cache = IdempotencyCache::new();
+        let now = Utc::now();
+        let window = Duration::seconds(60);
+        reserve(&cache, "alice", "t1", now).record(RecordedPut::new("docs", "a.txt", "etag-a", window, now));
+
+        let put = recorded(&cache, "alice", "t1", now + Duration::seconds(30)).unwrap();
+        assert!(put.matches("docs", "a.txt", "etag-a"));
+        assert!(!put.matches("docs", "a.txt", "etag-b"));
+        assert!(!put.matches("docs", "b.txt", "etag-a"));
+
+        // Keys are per caller, and forgotten once the window has passed
+        assert!(recorded(&cache, "bob", "t1", now).is_none());
+        assert!(recorded(&cache, "alice", "t1", now + window).is_none());
+        let later = now + window;
+        reserve(&cache, "alice", "t2", later).record(RecordedPut::new("docs", "b.txt", "etag-b", window, later));
+        assert_eq!(cache.entries.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_keys_in_flight_are_held() {
+        let cache = IdempotencyCache::new();
+        let now = Utc::now();
+        let first = reserve(&cache, "alice", "t1", now);
+        assert!(matches!(cache.claim("alice", "t1", now), Claim::InFlight));
+
+        // A PUT that fails releases its key for the retry
+        drop(first);
+        let retry = reserve(&cache, "alice", "t1", now);
+        retry.record(RecordedPut::new("docs", "a.txt", "etag-a", Duration::seconds(60), now));
+        assert!(recorded(&cache, "alice", "t1", now).is_some());
+    }
+
+    #[test]
+    fn test_cache_size_is_capped() {
+        let cache = IdempotencyCache::with_limit(2);
+        let now = Utc::now();
+        let window = |seconds| Duration::seconds(seconds);
+        reserve(&cache, "alice", "t1", now).record(RecordedPut::new("docs", "a", "etag", window(60), now));
+        reserve(&cache, "alice", "t2", now).record(RecordedPut::new("docs", "b", "etag", window(30), now));
+
+        // The key expiring soonest makes way
+        let third = reserve(&cache, "alice", "t3", now);
+        assert!(recorded(&cache, "alice", "t2", now).is_none());
+        assert!(recorded(&cache, "alice", "t1", now).is_some());
+
+        // Keys in flight are never dropped, and new ones wait for room
+        let fourth = reserve(&cache, "alice", "t4", now);
+        assert!(matches!(cache.claim("alice", "t5", now), Claim::Full));
+        drop((third, fourth));
+        assert_eq!(cache.entries.lock().unwrap().len(), 0);
+    }
+}
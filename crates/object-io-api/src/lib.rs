@@ -3,11 +3,21 @@
 //! This crate implements the S3-compatible REST API endpoints for ObjectIO.
 
 pub mod auth;
+pub mod aws_chunked;
+pub mod config;
 pub mod handlers;
+pub mod idempotency;
 pub mod middleware;
+pub mod replication;
 pub mod responses;
 pub mod routes;
+pub mod self_check;
+pub mod server;
+pub mod spool;
 pub mod state;
+pub mod tasks;
 
-pub use routes::create_app;
-pub use state::{AppState, ServerConfig};
+pub use routes::{create_app, create_router};
+pub use config::ServerConfig;
+pub use server::serve;
+pub use state::AppState;
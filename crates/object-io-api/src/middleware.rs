@@ -1,15 +1,29 @@
 //! HTTP middleware for the API
 
 use axum::{
-    extract::Request,
-    http::{HeaderName, HeaderValue, Method},
+    body::{Body, Bytes, HttpBody},
+    BoxError,
+    extract::{ConnectInfo, Request, State},
+    http::{header, HeaderMap, HeaderName, HeaderValue, Method, StatusCode, Uri},
     middleware::Next,
-    response::Response,
+    response::{IntoResponse, Response},
 };
+use tower_http::compression::predicate::{And, Predicate, SizeAbove};
+use tower_http::compression::CompressionLayer;
 use tower_http::cors::{Any, CorsLayer};
-use tower_http::timeout::TimeoutLayer;
 use tower_http::limit::RequestBodyLimitLayer;
-use std::time::Duration;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use futures::StreamExt;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use chrono::{DateTime, NaiveDate, NaiveTime, Utc};
+
+use crate::{auth::AuthContext, responses::xml::ErrorDocument, state::AppState};
 
 /// Create CORS middleware for S3 API compatibility
 pub fn cors_layer() -> CorsLayer {
@@ -48,6 +62,7 @@ pub fn cors_layer() -> CorsLayer {
             HeaderName::from_static("if-none-match"),
             HeaderName::from_static("if-modified-since"),
             HeaderName::from_static("if-unmodified-since"),
+            HeaderName::from_static("if-range"),
         ])
         .expose_headers([
             HeaderName::from_static("etag"),
@@ -62,14 +77,667 @@ pub fn cors_layer() -> CorsLayer {
         ])
 }
 
-/// Create timeout middleware (30 second timeout)
-pub fn timeout_layer() -> TimeoutLayer {
-    TimeoutLayer::new(Duration::from_secs(30))
+/// Create request body size limit middleware
+pub fn body_limit_layer(max_body_size: usize) -> RequestBodyLimitLayer {
+    RequestBodyLimitLayer::new(max_body_size)
+}
+
+/// Whether a `Content-Type` names text that compresses well: `text/*`,
+/// XML, JSON, JavaScript, YAML and the like
+fn is_compressible(content_type: &str) -> bool {
+    let essence = content_type.split(';').next().unwrap_or_default().trim().to_ascii_lowercase();
+    essence.starts_with("text/")
+        || essence.ends_with("+xml")
+        || essence.ends_with("+json")
+        || matches!(
+            essence.as_str(),
+            "application/xml"
+                | "application/json"
+                | "application/x-ndjson"
+                | "application/javascript"
+                | "application/x-javascript"
+                | "application/yaml"
+                | "application/x-yaml"
+                | "application/toml"
+                | "application/sql"
+                | "application/x-sh"
+        )
+}
+
+/// Compresses compressible responses other than partial content
+///
+/// The compression layer itself already leaves alone responses with a
+/// `Content-Encoding` (objects stored compressed) or a `Content-Range`.
+#[derive(Clone, Copy, Debug)]
+pub struct CompressibleResponse;
+
+impl Predicate for CompressibleResponse {
+    fn should_compress<B: HttpBody>(&self, response: &Response<B>) -> bool {
+        response.status() != StatusCode::PARTIAL_CONTENT
+            && response
+                .headers()
+                .get(header::CONTENT_TYPE)
+                .and_then(|value| value.to_str().ok())
+                .is_some_and(is_compressible)
+    }
+}
+
+/// Create response compression middleware: gzip or brotli, as the client's
+/// `Accept-Encoding` prefers, for compressible responses of at least
+/// `min_size` bytes
+pub fn compression_layer(min_size: u16) -> CompressionLayer<And<SizeAbove, CompressibleResponse>> {
+    CompressionLayer::new()
+        .no_deflate()
+        .no_zstd()
+        .compress_when(SizeAbove::new(min_size).and(CompressibleResponse))
+}
+
+/// Drop `Accept-Encoding` from requests with a `Range`, so byte offsets keep
+/// referring to the stored object even when the range is ignored and the
+/// whole object is sent
+pub async fn uncompressed_ranges_middleware(mut request: Request, next: Next) -> Response {
+    if request.headers().contains_key(header::RANGE) {
+        request.headers_mut().remove(header::ACCEPT_ENCODING);
+    }
+    next.run(request).await
+}
+
+/// Bytes a header map takes on the wire, counting `: ` and the line break
+/// after each header
+fn header_section_size(headers: &HeaderMap) -> usize {
+    headers
+        .iter()
+        .map(|(name, value)| name.as_str().len() + value.len() + 4)
+        .sum()
+}
+
+/// Refuse requests whose headers together exceed `max_header_size` with
+/// `400 RequestHeaderSectionTooLarge`, before authentication parses them
+pub async fn header_size_middleware(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    let max = state.config.max_header_size;
+    if max > 0 && header_section_size(request.headers()) > max {
+        return ErrorDocument::new(
+            "RequestHeaderSectionTooLarge",
+            format!("Your request header section exceeds the maximum allowed size of {} bytes.", max),
+        )
+        .into_response(StatusCode::BAD_REQUEST);
+    }
+    next.run(request).await
+}
+
+/// S3's response to a client that stopped sending its request
+fn request_timeout() -> Response {
+    let mut response = ErrorDocument::new(
+        "RequestTimeout",
+        "Your socket connection to the server was not read from or written to within the timeout period.",
+    )
+    .into_response(StatusCode::REQUEST_TIMEOUT);
+    response.headers_mut().insert(header::CONNECTION, HeaderValue::from_static("close"));
+    response
+}
+
+/// Wrap a request body so it fails if no data arrives for `idle`, setting `timed_out`
+fn idle_timeout_body(body: Body, idle: Duration, timed_out: Arc<AtomicBool>) -> Body {
+    let stream = futures::stream::unfold(Some(body.into_data_stream()), move |stream| {
+        let timed_out = timed_out.clone();
+        async move {
+            let mut stream = stream?;
+            match tokio::time::timeout(idle, stream.next()).await {
+                Ok(Some(chunk)) => Some((chunk, Some(stream))),
+                Ok(None) => None,
+                Err(_) => {
+                    timed_out.store(true, Ordering::SeqCst);
+                    let error = std::io::Error::new(std::io::ErrorKind::TimedOut, "request body timed out");
+                    Some((Err(axum::Error::new(error)), None))
+                }
+            }
+        }
+    });
+    Body::from_stream(stream)
+}
+
+/// Answer `408 RequestTimeout` when a request takes longer than the
+/// configured total time, or its body stalls for longer than the idle timeout
+///
+/// A stalled body fails the handler's read, so storage backends discard the
+/// partial object; dropping the handler on the total timeout does the same.
+/// A zero idle timeout disables the body check.
+pub async fn request_timeout_middleware(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let timed_out = Arc::new(AtomicBool::new(false));
+    let request = match state.config.body_idle_timeout {
+        0 => request,
+        idle => {
+            let idle = Duration::from_secs(idle);
+            request.map(|body| idle_timeout_body(body, idle, timed_out.clone()))
+        }
+    };
+
+    let total = Duration::from_secs(state.config.request_timeout);
+    match tokio::time::timeout(total, next.run(request)).await {
+        Ok(response) if !timed_out.load(Ordering::SeqCst) => response,
+        _ => request_timeout(),
+    }
+}
+
+/// Check the preconditions of a request sent with `Expect: 100-continue`
+///
+/// Only `100-continue` is a supported expectation (RFC 9110 §10.1.1), and a
+/// declared body over the size limit can be refused before it is sent.
+fn check_expectation(headers: &HeaderMap, max_body_size: usize) -> std::result::Result<(), StatusCode> {
+    let Some(expect) = headers.get(header::EXPECT) else {
+        return Ok(());
+    };
+
+    if !expect.as_bytes().eq_ignore_ascii_case(b"100-continue") {
+        return Err(StatusCode::EXPECTATION_FAILED);
+    }
+
+    let content_length = headers
+        .get(header::CONTENT_LENGTH)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<u64>().ok());
+    match content_length {
+        Some(length) if length > max_body_size as u64 => Err(StatusCode::PAYLOAD_TOO_LARGE),
+        _ => Ok(()),
+    }
+}
+
+/// Handle `Expect: 100-continue` without wasting bandwidth on doomed uploads
+///
+/// hyper sends the interim `100 Continue` the first time the request body is
+/// polled, not when the headers arrive. So a handler that rejects the request
+/// (missing bucket, failed auth) before reading the body answers with its
+/// final status, and the client never sends the body. This middleware only
+/// rejects what can be decided from the headers alone; handlers must keep
+/// their checks ahead of the first body read.
+pub async fn expect_continue_middleware(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if let Err(status) = check_expectation(request.headers(), state.config.max_body_size) {
+        return status.into_response();
+    }
+    next.run(request).await
+}
+
+/// Bucket addressed by a virtual-host-style request (`bucket.<domain>`)
+///
+/// Uses the `Host` header, falling back to the URI authority (HTTP/2). A
+/// request to `domain` itself or to any other host is path-style.
+fn virtual_host_bucket(headers: &HeaderMap, uri: &Uri, domain: &str) -> Option<String> {
+    let host = match headers.get(header::HOST) {
+        Some(host) => host.to_str().ok()?,
+        None => uri.host()?,
+    };
+    let host = match host.rsplit_once(':') {
+        Some((name, port)) if port.bytes().all(|b| b.is_ascii_digit()) => name,
+        _ => host,
+    };
+
+    let host = host.trim_end_matches('.').to_ascii_lowercase();
+    let domain = domain.trim_end_matches('.').to_ascii_lowercase();
+    let bucket = host.strip_suffix(&domain)?.strip_suffix('.')?;
+    (!bucket.is_empty()).then(|| bucket.to_string())
+}
+
+/// Prefix a request URI's path with `bucket`, keeping the query
+fn path_style_uri(uri: &Uri, bucket: &str) -> Option<Uri> {
+    let path = match uri.path() {
+        "" | "/" => format!("/{}", bucket),
+        path => format!("/{}{}", bucket, path),
+    };
+    with_path(uri, &path)
+}
+
+/// Replace a request URI's path, keeping the query
+fn with_path(uri: &Uri, path: &str) -> Option<Uri> {
+    let path_and_query = match uri.query() {
+        Some(query) => format!("{}?{}", path, query),
+        None => path.to_string(),
+    };
+
+    let mut parts = uri.clone().into_parts();
+    parts.path_and_query = Some(path_and_query.parse().ok()?);
+    Uri::from_parts(parts).ok()
+}
+
+/// Rewrite virtual-host-style requests to path-style so the router and
+/// handlers only deal with `/bucket/key`
+///
+/// Must wrap the router rather than be one of its layers, as those run after
+/// routing. The client's URI stays available as `OriginalUri` for SigV4.
+pub async fn virtual_host_middleware(
+    State(state): State<AppState>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    if let Some(domain) = state.config.virtual_host_domain.as_deref() {
+        if let Some(bucket) = virtual_host_bucket(request.headers(), request.uri(), domain) {
+            match path_style_uri(request.uri(), &bucket) {
+                Some(uri) => *request.uri_mut() = uri,
+                None => return StatusCode::BAD_REQUEST.into_response(),
+            }
+        }
+    }
+    next.run(request).await
+}
+
+/// How object keys containing a percent-encoded slash (`%2F`) are handled
+///
+/// Keys are canonically addressed with literal slashes, which is how S3
+/// clients send them.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EncodedSlashes {
+    /// Decode `%2F` in keys to `/`, so `a%2Fb` and `a/b` name the same object
+    #[default]
+    Canonicalize,
+    /// Refuse requests with `%2F` in the key as ambiguous
+    Reject,
+}
+
+/// Split a path-style request path into its bucket segment (with the
+/// leading slash) and the encoded key, for paths addressing an object
+fn split_object_path(path: &str) -> Option<(&str, &str)> {
+    let slash = path.get(1..)?.find('/')? + 1;
+    let (bucket, key) = (&path[..slash], &path[slash + 1..]);
+    object_io_core::validate_bucket_name(&bucket[1..]).ok()?;
+    Some((bucket, key))
+}
+
+/// Whether an encoded path contains `%2F` in either case
+fn has_encoded_slash(path: &str) -> bool {
+    path.as_bytes()
+        .windows(3)
+        .any(|w| w[0] == b'%' && w[1] == b'2' && w[2].eq_ignore_ascii_case(&b'f'))
+}
+
+/// S3 `InvalidURI` error response for keys refused by `EncodedSlashes::Reject`
+fn encoded_slash_rejected() -> Response {
+    ErrorDocument::new(
+        "InvalidURI",
+        "Object keys may not contain an encoded slash (%2F); send '/' unencoded.",
+    )
+    .into_response(StatusCode::BAD_REQUEST)
+}
+
+/// Give object keys a single representation before routing, per the
+/// `encoded_slashes` policy
+///
+/// Must wrap the router, after the virtual-host rewrite, so routing, scope
+/// checks, handlers and listings all see the canonical key. SigV4 still
+/// verifies against the URI the client signed (`OriginalUri`).
+pub async fn encoded_slash_middleware(
+    State(state): State<AppState>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let canonical = match split_object_path(request.uri().path()) {
+        Some((bucket, key)) if has_encoded_slash(key) => match state.config.encoded_slashes {
+            EncodedSlashes::Reject => return encoded_slash_rejected(),
+            EncodedSlashes::Canonicalize => {
+                let key = key.replace("%2F", "/").replace("%2f", "/");
+                with_path(request.uri(), &format!("{}/{}", bucket, key))
+            }
+        },
+        _ => None,
+    };
+    if let Some(uri) = canonical {
+        *request.uri_mut() = uri;
+    }
+    next.run(request).await
+}
+
+/// Sustained request rate and burst allowance for one client
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct RateLimit {
+    /// Requests per second (0 means unlimited)
+    pub per_second: u32,
+    /// Requests allowed at once after a quiet period; 0 means one second's worth
+    #[serde(default)]
+    pub burst: u32,
+}
+
+impl RateLimit {
+    pub fn is_unlimited(&self) -> bool {
+        self.per_second == 0
+    }
+
+    fn capacity(&self) -> f64 {
+        if self.burst == 0 {
+            self.per_second as f64
+        } else {
+            self.burst as f64
+        }
+    }
+}
+
+/// Source of the current time for the rate limiter, replaceable in tests
+pub trait Clock: Send + Sync {
+    fn now(&self) -> Instant;
+}
+
+/// The real monotonic clock
+#[derive(Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+/// Number of tracked clients above which idle ones are dropped
+const RATE_LIMIT_PRUNE_THRESHOLD: usize = 10_000;
+
+#[derive(Debug)]
+struct TokenBucket {
+    tokens: f64,
+    updated: Instant,
+    limit: RateLimit,
+}
+
+impl TokenBucket {
+    /// Tokens available at `now`, capped at the bucket's capacity
+    fn available(&self, now: Instant) -> f64 {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        (self.tokens + elapsed * self.limit.per_second as f64).min(self.limit.capacity())
+    }
+}
+
+#[derive(Default)]
+struct RateLimiterState {
+    buckets: HashMap<String, TokenBucket>,
+    prune_at: usize,
+}
+
+/// Token-bucket rate limiter keyed by client (access key or address)
+///
+/// A client's bucket holds up to the limit's burst in tokens and refills at
+/// its per-second rate; each request takes one token. Clients whose buckets
+/// have refilled completely are forgotten once many are tracked, since a
+/// full bucket is the same as a new one.
+pub struct RateLimiter {
+    clock: Arc<dyn Clock>,
+    state: Mutex<RateLimiterState>,
+}
+
+impl RateLimiter {
+    pub fn new() -> Self {
+        Self::with_clock(Arc::new(SystemClock))
+    }
+
+    /// Rate limiter reading the time from `clock`
+    pub fn with_clock(clock: Arc<dyn Clock>) -> Self {
+        Self {
+            clock,
+            state: Mutex::new(RateLimiterState {
+                buckets: HashMap::new(),
+                prune_at: RATE_LIMIT_PRUNE_THRESHOLD,
+            }),
+        }
+    }
+
+    /// Take a token for `client`, or return how long until one is available
+    pub fn check(&self, client: &str, limit: RateLimit) -> std::result::Result<(), Duration> {
+        if limit.is_unlimited() {
+            return Ok(());
+        }
+
+        let now = self.clock.now();
+        let mut state = self.state.lock().unwrap();
+        if state.buckets.len() >= state.prune_at {
+            state.buckets.retain(|_, bucket| bucket.available(now) < bucket.limit.capacity());
+            state.prune_at = (state.buckets.len() * 2).max(RATE_LIMIT_PRUNE_THRESHOLD);
+        }
+
+        let bucket = state.buckets.entry(client.to_string()).or_insert_with(|| TokenBucket {
+            tokens: limit.capacity(),
+            updated: now,
+            limit,
+        });
+        bucket.limit = limit;
+        bucket.tokens = bucket.available(now);
+        bucket.updated = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64((1.0 - bucket.tokens) / limit.per_second as f64))
+        }
+    }
+
+    /// Number of clients currently tracked
+    pub fn len(&self) -> usize {
+        self.state.lock().unwrap().buckets.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl Default for RateLimiter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Synthetic identity of an anonymous client, keyed by its IP address
+///
+/// Added to the extensions of requests without credentials, so anonymous
+/// traffic can be rate limited, metered and audited like an access key.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AnonymousIdentity(pub String);
+
+impl AnonymousIdentity {
+    fn from_request(request: &Request) -> Self {
+        let ip = request
+            .extensions()
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(addr)| addr.ip().to_string())
+            .unwrap_or_default();
+        Self(format!("anonymous:{}", ip))
+    }
+
+    pub fn get(&self) -> &str {
+        &self.0
+    }
+}
+
+#[derive(Default)]
+struct ByteQuotaState {
+    /// UTC day the counters are for
+    day: Option<NaiveDate>,
+    used: HashMap<String, u64>,
+}
+
+/// Bytes transferred per client each UTC day, checked against a daily quota
+///
+/// Counters start again at midnight UTC, when the previous day's are dropped.
+#[derive(Default)]
+pub struct ByteQuota {
+    state: Mutex<ByteQuotaState>,
+}
+
+impl ByteQuota {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Check that `client` is under `quota` bytes today, or return how long
+    /// until its counter resets (a zero quota is unlimited)
+    pub fn check(&self, client: &str, quota: u64, now: DateTime<Utc>) -> std::result::Result<(), Duration> {
+        if quota == 0 {
+            return Ok(());
+        }
+
+        let mut state = self.state.lock().unwrap();
+        let used = Self::today(&mut state, now).get(client).copied().unwrap_or(0);
+        if used < quota {
+            return Ok(());
+        }
+
+        let midnight = (now.date_naive() + chrono::Days::new(1)).and_time(NaiveTime::MIN).and_utc();
+        Err((midnight - now).to_std().unwrap_or_default())
+    }
+
+    /// Add `bytes` to what `client` has transferred today
+    pub fn charge(&self, client: &str, bytes: u64, now: DateTime<Utc>) {
+        let mut state = self.state.lock().unwrap();
+        let used = Self::today(&mut state, now).entry(client.to_string()).or_default();
+        *used = used.saturating_add(bytes);
+    }
+
+    /// Bytes `client` has transferred today
+    pub fn used(&self, client: &str, now: DateTime<Utc>) -> u64 {
+        let mut state = self.state.lock().unwrap();
+        Self::today(&mut state, now).get(client).copied().unwrap_or(0)
+    }
+
+    /// Counters for the day of `now`, dropping any from an earlier day
+    fn today(state: &mut ByteQuotaState, now: DateTime<Utc>) -> &mut HashMap<String, u64> {
+        let day = now.date_naive();
+        if state.day != Some(day) {
+            state.day = Some(day);
+            state.used.clear();
+        }
+        &mut state.used
+    }
+}
+
+/// A request or response body charging the bytes read from it to a
+/// client's daily byte quota as they pass
+struct MeteredBody {
+    inner: Body,
+    quota: Arc<ByteQuota>,
+    client: String,
+}
+
+impl MeteredBody {
+    fn wrap(inner: Body, quota: Arc<ByteQuota>, client: String) -> Body {
+        Body::new(Self { inner, quota, client })
+    }
+}
+
+impl HttpBody for MeteredBody {
+    type Data = Bytes;
+    type Error = axum::Error;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<std::result::Result<hyper::body::Frame<Bytes>, axum::Error>>> {
+        let frame = std::task::ready!(Pin::new(&mut self.inner).poll_frame(cx));
+        if let Some(data) = frame.as_ref().and_then(|frame| frame.as_ref().ok()?.data_ref()) {
+            self.quota.charge(&self.client, data.len() as u64, Utc::now());
+        }
+        Poll::Ready(frame)
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> hyper::body::SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+/// Admin endpoint switching read-only mode, which stays writable so the
+/// mode can be lifted
+pub const READ_ONLY_PATH: &str = "/_admin/read-only";
+
+/// Refuse writes with `503 ServiceUnavailable` while the server is in
+/// read-only mode
+///
+/// Every method other than GET, HEAD and OPTIONS counts as a write, so all
+/// write paths are covered without each handler checking.
+pub async fn read_only_middleware(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    let write = !matches!(*request.method(), Method::GET | Method::HEAD | Method::OPTIONS);
+    if !write || !state.read_only.load(Ordering::Relaxed) || request.uri().path() == READ_ONLY_PATH {
+        return next.run(request).await;
+    }
+
+    let mut response = ErrorDocument::new(
+        "ServiceUnavailable",
+        "The server is in read-only maintenance mode. Please retry later.",
+    )
+    .into_response(StatusCode::SERVICE_UNAVAILABLE);
+    let retry_after = state.config.read_only_retry_after.max(1);
+    response.headers_mut().insert(header::RETRY_AFTER, HeaderValue::from(retry_after));
+    response
+}
+
+/// S3's throttling response, asking the client to retry after `retry_after`
+fn slow_down(retry_after: Duration) -> Response {
+    let mut response = ErrorDocument::new("SlowDown", "Please reduce your request rate.")
+        .into_response(StatusCode::SERVICE_UNAVAILABLE);
+
+    // Whole seconds, rounded up so clients don't retry too early
+    let seconds = retry_after.as_secs() + u64::from(retry_after.subsec_nanos() > 0);
+    response.headers_mut().insert(header::RETRY_AFTER, HeaderValue::from(seconds.max(1)));
+    response
+}
+
+/// Refuse a request shed by the concurrency limit with `503 SlowDown`
+///
+/// Used with `HandleErrorLayer` around a load-shedding concurrency limit;
+/// the limit is the only source of errors in that stack.
+pub async fn concurrency_limit_exceeded(_error: BoxError) -> Response {
+    slow_down(Duration::from_secs(1))
 }
 
-/// Create request body size limit middleware (5GB max for S3 compatibility)
-pub fn body_limit_layer() -> RequestBodyLimitLayer {
-    RequestBodyLimitLayer::new(5 * 1024 * 1024 * 1024) // 5GB limit
+/// Throttle clients exceeding their rate limit with `503 SlowDown`
+///
+/// Authenticated requests are limited per access key, using the owning
+/// user's override if one is configured. Anonymous requests are attributed
+/// to an [`AnonymousIdentity`] for their client IP, limited by the anonymous
+/// rate limit and, when configured, refused once the bytes they sent and
+/// received today reach the anonymous daily quota. Bytes are counted as the
+/// bodies are read and sent, so streamed bodies of unknown length count too.
+/// Must run inside the auth middleware to see the access key.
+pub async fn rate_limit_middleware(
+    State(state): State<AppState>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    if request.uri().path() == "/health" {
+        return next.run(request).await;
+    }
+
+    let (client, limit) = match request.extensions().get::<AuthContext>() {
+        Some(ctx) => (format!("key:{}", ctx.access_key), state.config.rate_limit_for(&ctx.user_id)),
+        None => {
+            let identity = AnonymousIdentity::from_request(&request);
+            let client = identity.get().to_string();
+            request.extensions_mut().insert(identity);
+            (client, state.config.anonymous_rate_limit())
+        }
+    };
+
+    if let Err(retry_after) = state.rate_limiter.check(&client, limit) {
+        return slow_down(retry_after);
+    }
+
+    let quota = state.config.anonymous_daily_byte_quota;
+    if quota == 0 || request.extensions().get::<AnonymousIdentity>().is_none() {
+        return next.run(request).await;
+    }
+    if let Err(retry_after) = state.anonymous_quota.check(&client, quota, Utc::now()) {
+        eprintln!("Daily byte quota exhausted for {}", client);
+        return slow_down(retry_after);
+    }
+
+    let request = request.map(|body| MeteredBody::wrap(body, state.anonymous_quota.clone(), client.clone()));
+    let response = next.run(request).await;
+    response.map(|body| MeteredBody::wrap(body, state.anonymous_quota.clone(), client))
 }
 
 /// Add request ID header for tracking
@@ -90,6 +758,136 @@ pub async fn request_id_middleware(mut request: Request, next: Next) -> Response
     response
 }
 
+/// Count of requests currently being handled, used to drain on shutdown
+#[derive(Clone, Debug, Default)]
+pub struct InFlightRequests(Arc<AtomicUsize>);
+
+impl InFlightRequests {
+    /// Number of requests currently in flight
+    pub fn count(&self) -> usize {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
+/// Decrements the in-flight count when a request finishes or is dropped
+struct InFlightGuard(InFlightRequests);
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        (self.0).0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Track the number of in-flight requests
+pub async fn in_flight_middleware(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    state.in_flight.0.fetch_add(1, Ordering::SeqCst);
+    let _guard = InFlightGuard(state.in_flight.clone());
+    next.run(request).await
+}
+
+/// S3 operation name for a path-style request, for logs
+fn operation_name(method: &Method, uri: &Uri, headers: &HeaderMap) -> &'static str {
+    let path = uri.path().trim_start_matches('/');
+    let has = |param: &str| {
+        uri.query()
+            .unwrap_or("")
+            .split('&')
+            .any(|pair| pair.split('=').next() == Some(param))
+    };
+    if path.starts_with("_admin") {
+        return "Admin";
+    }
+    if path == "health" {
+        return "HealthCheck";
+    }
+    let object = path.split_once('/').is_some_and(|(_, key)| !key.is_empty());
+
+    match (method.clone(), object) {
+        (Method::GET, _) if path.is_empty() => "ListBuckets",
+        (Method::GET, false) if has("uploads") => "ListMultipartUploads",
+        (Method::GET, false) if has("location") => "GetBucketLocation",
+        (Method::GET, false) if uri.query().unwrap_or("").contains("list-type=2") => "ListObjectsV2",
+        (Method::GET, false) => "ListObjects",
+        (Method::PUT, false) => "CreateBucket",
+        (Method::DELETE, false) => "DeleteBucket",
+        (Method::HEAD, false) => "HeadBucket",
+        (Method::POST, false) if has("empty") => "EmptyBucket",
+        (Method::POST, false) => "PostObject",
+        (Method::GET, true) if has("uploadId") => "ListParts",
+        (Method::GET, true) if has("acl") => "GetObjectAcl",
+        (Method::GET, true) => "GetObject",
+        (Method::PUT, true) if has("uploadId") && headers.contains_key("x-amz-copy-source") => "UploadPartCopy",
+        (Method::PUT, true) if has("uploadId") => "UploadPart",
+        (Method::PUT, true) if has("acl") => "PutObjectAcl",
+        (Method::PUT, true) if headers.contains_key("x-amz-copy-source") => "CopyObject",
+        (Method::PUT, true) => "PutObject",
+        (Method::DELETE, true) if has("uploadId") => "AbortMultipartUpload",
+        (Method::DELETE, true) => "DeleteObject",
+        (Method::HEAD, true) => "HeadObject",
+        (Method::POST, true) if has("uploads") => "CreateMultipartUpload",
+        (Method::POST, true) if has("uploadId") => "CompleteMultipartUpload",
+        (Method::POST, true) if has("rename") => "RenameObject",
+        (Method::POST, true) if has("touch") => "TouchObject",
+        (Method::POST, true) => "PostObject",
+        _ => "Unknown",
+    }
+}
+
+/// Size of a request or response body, from its `Content-Length` header or
+/// else the body itself when its length is known up front (0 otherwise)
+fn body_size(headers: &HeaderMap, body: &Body) -> u64 {
+    headers
+        .get(header::CONTENT_LENGTH)
+        .and_then(|length| length.to_str().ok()?.parse().ok())
+        .or_else(|| body.size_hint().exact())
+        .unwrap_or(0)
+}
+
+/// Log a warning for requests slower than `slow_request_threshold_ms`
+///
+/// The duration runs until the response is ready to send, so it covers the
+/// handler's work (a listing walking a large tree, an upload being stored)
+/// but not the streaming of a download.
+pub async fn slow_request_middleware(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let threshold = state.config.slow_request_threshold_ms;
+    if threshold == 0 {
+        return next.run(request).await;
+    }
+
+    let operation = operation_name(request.method(), request.uri(), request.headers());
+    let path = urlencoding::decode(request.uri().path())
+        .map(|path| path.into_owned())
+        .unwrap_or_else(|_| request.uri().path().to_string());
+    let bytes_in = body_size(request.headers(), request.body());
+
+    let started = Instant::now();
+    let response = next.run(request).await;
+    let elapsed = started.elapsed();
+
+    if elapsed > Duration::from_millis(threshold) {
+        let (bucket, key) = path.trim_start_matches('/').split_once('/').unwrap_or((path.trim_start_matches('/'), ""));
+        tracing::warn!(
+            operation,
+            bucket,
+            key,
+            bytes_in,
+            bytes_out = body_size(response.headers(), response.body()),
+            status = response.status().as_u16(),
+            duration_ms = elapsed.as_millis() as u64,
+            "Slow request"
+        );
+    }
+    response
+}
+
 /// Add basic security headers
 pub async fn security_headers_middleware(request: Request, next: Next) -> Response {
     let mut response = next.run(request).await;
@@ -114,3 +912,353 @@ impl RequestId {
         &self.0
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers(pairs: &[(&'static str, &'static str)]) -> HeaderMap {
+        pairs
+            .iter()
+            .map(|(name, value)| (HeaderName::from_static(name), HeaderValue::from_static(value)))
+            .collect()
+    }
+
+    #[test]
+    fn test_check_expectation() {
+        assert_eq!(check_expectation(&headers(&[("content-length", "2048")]), 1024), Ok(()));
+        assert_eq!(
+            check_expectation(&headers(&[("expect", "100-Continue"), ("content-length", "1024")]), 1024),
+            Ok(())
+        );
+        assert_eq!(
+            check_expectation(&headers(&[("expect", "100-continue"), ("content-length", "1025")]), 1024),
+            Err(StatusCode::PAYLOAD_TOO_LARGE)
+        );
+        assert_eq!(
+            check_expectation(&headers(&[("expect", "200-ok")]), 1024),
+            Err(StatusCode::EXPECTATION_FAILED)
+        );
+    }
+
+    #[test]
+    fn test_header_section_size() {
+        assert_eq!(header_section_size(&HeaderMap::new()), 0);
+        // "host: a\r\n" and "range: bytes=0-1\r\n"
+        assert_eq!(header_section_size(&headers(&[("host", "a"), ("range", "bytes=0-1")])), 9 + 18);
+    }
+
+    #[test]
+    fn test_virtual_host_bucket() {
+        let uri = Uri::from_static("/key");
+        let bucket = |host: &'static str| virtual_host_bucket(&headers(&[("host", host)]), &uri, "s3.example.com");
+
+        assert_eq!(bucket("photos.s3.example.com").as_deref(), Some("photos"));
+        assert_eq!(bucket("my.photos.s3.example.com:5500").as_deref(), Some("my.photos"));
+        assert_eq!(bucket("Photos.S3.Example.com.").as_deref(), Some("photos"));
+        // Path-style hosts
+        assert_eq!(bucket("s3.example.com"), None);
+        assert_eq!(bucket("s3.example.com:5500"), None);
+        assert_eq!(bucket("photoss3.example.com"), None);
+        assert_eq!(bucket("localhost:5500"), None);
+
+        let uri = Uri::from_static("https://photos.s3.example.com/key");
+        assert_eq!(virtual_host_bucket(&HeaderMap::new(), &uri, "s3.example.com").as_deref(), Some("photos"));
+    }
+
+    #[test]
+    fn test_path_style_uri() {
+        let rewrite = |uri: &'static str| path_style_uri(&Uri::from_static(uri), "photos").unwrap().to_string();
+
+        assert_eq!(rewrite("/"), "/photos");
+        assert_eq!(rewrite("/?list-type=2&prefix=a%2F"), "/photos?list-type=2&prefix=a%2F");
+        assert_eq!(rewrite("/2024/a.jpg"), "/photos/2024/a.jpg");
+        assert_eq!(rewrite("/a.jpg?uploadId=1&partNumber=2"), "/photos/a.jpg?uploadId=1&partNumber=2");
+    }
+
+    /// Clock that only moves when told to
+    struct ManualClock(Mutex<Instant>);
+
+    impl ManualClock {
+        fn advance(&self, by: Duration) {
+            *self.0.lock().unwrap() += by;
+        }
+    }
+
+    impl Clock for ManualClock {
+        fn now(&self) -> Instant {
+            *self.0.lock().unwrap()
+        }
+    }
+
+    #[test]
+    fn test_rate_limiter() {
+        let clock = Arc::new(ManualClock(Mutex::new(Instant::now())));
+        let limiter = RateLimiter::with_clock(clock.clone());
+        let limit = RateLimit { per_second: 2, burst: 3 };
+
+        // The burst is available at once, then requests wait for a refill
+        for _ in 0..3 {
+            assert_eq!(limiter.check("a", limit), Ok(()));
+        }
+        assert_eq!(limiter.check("a", limit), Err(Duration::from_millis(500)));
+        // Other clients have their own buckets
+        assert_eq!(limiter.check("b", limit), Ok(()));
+
+        clock.advance(Duration::from_millis(500));
+        assert_eq!(limiter.check("a", limit), Ok(()));
+        assert!(limiter.check("a", limit).is_err());
+
+        // Refills stop at the burst size
+        clock.advance(Duration::from_secs(60));
+        for _ in 0..3 {
+            assert_eq!(limiter.check("a", limit), Ok(()));
+        }
+        assert!(limiter.check("a", limit).is_err());
+
+        // A zero burst allows one second's worth
+        let limit = RateLimit { per_second: 2, burst: 0 };
+        assert_eq!(limiter.check("c", limit), Ok(()));
+        assert_eq!(limiter.check("c", limit), Ok(()));
+        assert!(limiter.check("c", limit).is_err());
+
+        let unlimited = RateLimit { per_second: 0, burst: 0 };
+        for _ in 0..100 {
+            assert_eq!(limiter.check("d", unlimited), Ok(()));
+        }
+        assert_eq!(limiter.len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_rate_limit_middleware() {
+        use axum::body::Body;
+        use tower::ServiceExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut state = AppState::for_test(dir.path()).await;
+        let mut config = (*state.config).clone();
+        config.rate_limit_per_second = 1;
+        state.config = Arc::new(config);
+        let app = crate::routes::create_router(state);
+
+        let request = |uri: &str| Request::builder().uri(uri).body(Body::empty()).unwrap();
+        assert_eq!(app.clone().oneshot(request("/")).await.unwrap().status(), StatusCode::OK);
+
+        let response = app.clone().oneshot(request("/")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(response.headers()[header::RETRY_AFTER], "1");
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert!(String::from_utf8_lossy(&body).contains("<Code>SlowDown</Code>"));
+
+        // Health checks are never throttled
+        assert_eq!(app.clone().oneshot(request("/health")).await.unwrap().status(), StatusCode::OK);
+    }
+
+    #[test]
+    fn test_byte_quota() {
+        let quota = ByteQuota::new();
+        let now = "2024-05-01T23:00:00Z".parse::<DateTime<Utc>>().unwrap();
+
+        assert_eq!(quota.check("a", 100, now), Ok(()));
+        quota.charge("a", 60, now);
+        assert_eq!(quota.check("a", 100, now), Ok(()));
+        // The request that crosses the quota completes; the next is refused
+        quota.charge("a", 60, now);
+        assert_eq!(quota.check("a", 100, now), Err(Duration::from_secs(3600)));
+        assert_eq!(quota.check("b", 100, now), Ok(()));
+        assert_eq!(quota.check("a", 0, now), Ok(()));
+
+        // Counters reset at midnight UTC
+        let tomorrow = now + chrono::Duration::hours(1);
+        assert_eq!(quota.check("a", 100, tomorrow), Ok(()));
+        assert_eq!(quota.used("a", tomorrow), 0);
+    }
+
+    #[tokio::test]
+    async fn test_anonymous_limits() {
+        use axum::body::Body;
+        use tower::ServiceExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut state = AppState::for_test(dir.path()).await;
+        let mut config = (*state.config).clone();
+        config.anonymous_rate_limit_per_second = Some(1);
+        config.anonymous_rate_limit_burst = 2;
+        config.anonymous_daily_byte_quota = 1;
+        state.config = Arc::new(config);
+        let app = crate::routes::create_router(state.clone());
+
+        let request = |uri: &str| Request::builder().uri(uri).body(Body::empty()).unwrap();
+        let response = app.clone().oneshot(request("/")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        // Bytes are charged as the response is sent
+        assert_eq!(state.anonymous_quota.used("anonymous:", Utc::now()), 0);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let used = state.anonymous_quota.used("anonymous:", Utc::now());
+        assert_eq!(used, body.len() as u64);
+        assert!(used > 0);
+
+        // Over the daily quota until midnight UTC, within the rate limit
+        let response = app.clone().oneshot(request("/")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert!(response.headers()[header::RETRY_AFTER].to_str().unwrap().parse::<u64>().unwrap() <= 86400);
+        assert_eq!(state.anonymous_quota.used("anonymous:", Utc::now()), used);
+
+        // Then over the anonymous rate limit
+        let response = app.clone().oneshot(request("/")).await.unwrap();
+        assert_eq!(response.headers()[header::RETRY_AFTER], "1");
+    }
+
+    #[tokio::test]
+    async fn test_quota_counts_streamed_bodies() {
+        use axum::{routing::put, Router};
+        use tower::ServiceExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut state = AppState::for_test(dir.path()).await;
+        let mut config = (*state.config).clone();
+        config.anonymous_daily_byte_quota = 1024;
+        state.config = Arc::new(config);
+
+        // Echo the request body back, streamed, so neither side has a length
+        let app = Router::new()
+            .route("/echo", put(|body: Body| async move { Body::from_stream(body.into_data_stream()) }))
+            .layer(axum::middleware::from_fn_with_state(state.clone(), rate_limit_middleware));
+        let chunks = ["first chunk", "second chunk"].map(|chunk| Ok::<_, std::io::Error>(Bytes::from(chunk)));
+        let request = Request::builder()
+            .method(Method::PUT)
+            .uri("/echo")
+            .body(Body::from_stream(futures::stream::iter(chunks)))
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert!(!response.headers().contains_key(header::CONTENT_LENGTH));
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(body, "first chunksecond chunk");
+        assert_eq!(state.anonymous_quota.used("anonymous:", Utc::now()), 2 * body.len() as u64);
+    }
+
+    #[test]
+    fn test_operation_name() {
+        let name = |method: Method, uri: &'static str| operation_name(&method, &Uri::from_static(uri), &HeaderMap::new());
+
+        assert_eq!(name(Method::GET, "/"), "ListBuckets");
+        assert_eq!(name(Method::GET, "/photos?list-type=2&prefix=a"), "ListObjectsV2");
+        assert_eq!(name(Method::GET, "/photos/"), "ListObjects");
+        assert_eq!(name(Method::PUT, "/photos/a.jpg?partNumber=1&uploadId=x"), "UploadPart");
+        assert_eq!(name(Method::GET, "/photos/a.jpg?acl"), "GetObjectAcl");
+        assert_eq!(name(Method::POST, "/photos/a.jpg?uploads"), "CreateMultipartUpload");
+        assert_eq!(name(Method::POST, "/photos/a.jpg?rename=b.jpg"), "RenameObject");
+        assert_eq!(name(Method::POST, "/photos/a.jpg?touch"), "TouchObject");
+        assert_eq!(name(Method::POST, "/photos?empty"), "EmptyBucket");
+        assert_eq!(name(Method::GET, "/_admin/stats"), "Admin");
+        let copy = headers(&[("x-amz-copy-source", "/photos/b.jpg")]);
+        assert_eq!(operation_name(&Method::PUT, &Uri::from_static("/photos/a.jpg"), &copy), "CopyObject");
+        let part = Uri::from_static("/photos/a.jpg?partNumber=1&uploadId=x");
+        assert_eq!(operation_name(&Method::PUT, &part, &copy), "UploadPartCopy");
+    }
+
+    /// Log output captured by a test subscriber
+    #[derive(Clone, Default)]
+    struct CapturedLogs(Arc<Mutex<Vec<u8>>>);
+
+    impl CapturedLogs {
+        fn take(&self) -> String {
+            String::from_utf8(std::mem::take(&mut *self.0.lock().unwrap())).unwrap()
+        }
+    }
+
+    impl std::io::Write for CapturedLogs {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_slow_requests_are_logged() {
+        use axum::{routing::get, Router};
+        use tower::ServiceExt;
+
+        let logs = CapturedLogs::default();
+        let writer = logs.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .with_writer(move || writer.clone())
+            .with_max_level(tracing::Level::WARN)
+            .with_ansi(false)
+            .finish();
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut state = AppState::for_test(dir.path()).await;
+        let mut config = (*state.config).clone();
+        config.slow_request_threshold_ms = 50;
+        state.config = Arc::new(config);
+        let app = |state: AppState| {
+            Router::new()
+                .route(
+                    "/:bucket/*key",
+                    get(|| async {
+                        tokio::time::sleep(Duration::from_millis(100)).await;
+                        "slow"
+                    }),
+                )
+                .route("/:bucket", get(|| async { "fast" }))
+                .layer(axum::middleware::from_fn_with_state(state.clone(), slow_request_middleware))
+                .with_state(state)
+        };
+        let request = |uri: &str| Request::builder().uri(uri).body(Body::empty()).unwrap();
+
+        app(state.clone()).oneshot(request("/photos")).await.unwrap();
+        assert_eq!(logs.take(), "");
+
+        app(state.clone()).oneshot(request("/photos/2024/a%20b.jpg")).await.unwrap();
+        let logged = logs.take();
+        assert!(logged.contains("WARN"), "{}", logged);
+        assert!(logged.contains("Slow request"), "{}", logged);
+        for field in ["operation=\"GetObject\"", "bucket=\"photos\"", "key=\"2024/a b.jpg\"", "bytes_out=4", "status=200"] {
+            assert!(logged.contains(field), "{} missing from {}", field, logged);
+        }
+
+        // A zero threshold turns the log off
+        let mut config = (*state.config).clone();
+        config.slow_request_threshold_ms = 0;
+        state.config = Arc::new(config);
+        app(state).oneshot(request("/photos/a.jpg")).await.unwrap();
+        assert_eq!(logs.take(), "");
+    }
+
+    #[tokio::test]
+    async fn test_stalled_body_times_out() {
+        use tower::ServiceExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut state = AppState::for_test(dir.path()).await;
+        let mut config = (*state.config).clone();
+        config.body_idle_timeout = 1;
+        state.config = Arc::new(config);
+        state.metadata.create_bucket("uploads", "owner").await.unwrap();
+        let app = crate::routes::create_router(state.clone());
+
+        // Sends part of the body and then nothing more
+        let chunk: std::result::Result<_, std::io::Error> = Ok(bytes::Bytes::from_static(b"partial"));
+        let stalled = futures::stream::iter([chunk]).chain(futures::stream::pending());
+        let request = Request::builder()
+            .method(Method::PUT)
+            .uri("/uploads/stalled.txt")
+            .header(header::CONTENT_LENGTH, "1024")
+            .body(Body::from_stream(stalled))
+            .unwrap();
+
+        let started = Instant::now();
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::REQUEST_TIMEOUT);
+        assert!(started.elapsed() < Duration::from_secs(5));
+
+        assert!(state.metadata.get_object("uploads", "stalled.txt").await.unwrap().is_none());
+        assert!(!state.storage.object_exists("uploads", "stalled.txt").await.unwrap());
+    }
+}
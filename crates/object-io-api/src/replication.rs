@@ -0,0 +1,396 @@
+//! Replication of object changes into secondary storage
+//!
+//! The replicator tails the metadata change feed and applies each put and
+//! delete to the target, a storage backend or an S3 endpoint, in order. How
+//! far it has got is a sequence number in the feed, saved to the cursor file
+//! after every page so a restart resumes where it stopped. Applying an event
+//! twice is harmless, so a crash between applying events and saving the
+//! cursor only repeats work.
+//!
+//! Each copy carries the ETag of the object it was made from in its metadata.
+//! A put whose data the target already has, such as a touch or a metadata
+//! replacement, only replaces the copy's metadata.
+
+pub mod remote;
+
+use object_io_core::{ChangeEvent, ChangeOperation, Object, ObjectIOError, Result};
+use object_io_metadata::MetadataStore;
+use object_io_storage::{Storage, StorageBackend};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+use tokio::io::AsyncRead;
+
+use crate::{config::ReplicationConfig, handlers::object::storage_metadata};
+use remote::RemoteTarget;
+
+/// Change feed events read per page
+const PAGE_SIZE: usize = 100;
+
+/// Metadata entry recording the ETag of the object a copy was made from
+pub const SOURCE_ETAG_ENTRY: &str = "objectio-source-etag";
+
+/// Where replicated objects are copied to
+pub enum ReplicationTarget {
+    /// A storage backend of this server's own
+    Storage(Arc<dyn Storage>),
+    /// An S3 endpoint, such as another ObjectIO server
+    Remote(RemoteTarget),
+}
+
+impl ReplicationTarget {
+    /// The target `config` describes
+    pub async fn open(config: &ReplicationConfig) -> Result<Self> {
+        match &config.endpoint {
+            Some(endpoint) => {
+                let credentials = (config.access_key.as_str(), config.secret_key.as_str());
+                Ok(Self::Remote(RemoteTarget::new(endpoint, &config.region, credentials)?))
+            }
+            None => Ok(Self::Storage(StorageBackend::new(config.storage_config()?).await?)),
+        }
+    }
+
+    /// ETag of the object the target's copy of `key` was made from, `None`
+    /// if it has no copy or doesn't know
+    async fn source_etag(&self, bucket: &str, key: &str) -> Result<Option<String>> {
+        match self {
+            Self::Storage(storage) => match storage.head_object(bucket, key).await {
+                Ok(mut head) => Ok(head.metadata.remove(SOURCE_ETAG_ENTRY)),
+                Err(ObjectIOError::ObjectNotFound { .. }) | Err(ObjectIOError::BucketNotFound { .. }) => Ok(None),
+                Err(e) => Err(e),
+            },
+            Self::Remote(remote) => Ok(remote.head_object(bucket, key).await?.and_then(|headers| {
+                let name = format!("x-amz-meta-{}", SOURCE_ETAG_ENTRY);
+                headers.get(name).and_then(|value| value.to_str().ok()).map(str::to_string)
+            })),
+        }
+    }
+
+    async fn put_object(
+        &self,
+        bucket: &str,
+        key: &str,
+        data: Box<dyn AsyncRead + Send + Unpin>,
+        size: u64,
+        metadata: HashMap<String, String>,
+    ) -> Result<()> {
+        match self {
+            Self::Storage(storage) => storage.put_object(bucket, key, data, metadata).await.map(|_| ()),
+            Self::Remote(remote) => remote.put_object(bucket, key, data, size, &metadata).await,
+        }
+    }
+
+    async fn replace_object_metadata(&self, bucket: &str, key: &str, metadata: HashMap<String, String>) -> Result<()> {
+        match self {
+            Self::Storage(storage) => storage.replace_object_metadata(bucket, key, metadata).await,
+            Self::Remote(remote) => remote.replace_object_metadata(bucket, key, &metadata).await,
+        }
+    }
+
+    /// Delete the target's copy of `key`, if it has one
+    async fn delete_object(&self, bucket: &str, key: &str) -> Result<()> {
+        match self {
+            Self::Storage(storage) => match storage.delete_object(bucket, key).await {
+                Ok(()) | Err(ObjectIOError::ObjectNotFound { .. }) | Err(ObjectIOError::BucketNotFound { .. }) => {
+                    Ok(())
+                }
+                Err(e) => Err(e),
+            },
+            Self::Remote(remote) => remote.delete_object(bucket, key).await,
+        }
+    }
+}
+
+/// Copies object changes from the server's storage into a target
+pub struct Replicator {
+    metadata: Arc<dyn MetadataStore>,
+    source: Arc<dyn Storage>,
+    target: ReplicationTarget,
+    cursor_path: Option<PathBuf>,
+    /// Sequence number of the last change applied
+    cursor: u64,
+}
+
+impl Replicator {
+    /// Replicator resuming from the cursor saved at `cursor_path`, or from
+    /// the start of the feed when there is none
+    pub async fn new(
+        metadata: Arc<dyn MetadataStore>,
+        source: Arc<dyn Storage>,
+        target: ReplicationTarget,
+        cursor_path: Option<PathBuf>,
+    ) -> Result<Self> {
+        let cursor = match &cursor_path {
+            Some(path) => load_cursor(path).await?,
+            None => 0,
+        };
+        Ok(Self {
+            metadata,
+            source,
+            target,
+            cursor_path,
+            cursor,
+        })
+    }
+
+    /// Sequence number of the last change applied to the target
+    pub fn cursor(&self) -> u64 {
+        self.cursor
+    }
+
+    /// Apply every change recorded since the cursor, returning how many were
+    /// applied
+    ///
+    /// Stops at the first change that fails, with the cursor saved just
+    /// before it, so the next run retries it.
+    pub async fn replicate(&mut self) -> Result<u64> {
+        let mut applied = 0;
+        loop {
+            let events = self.metadata.list_changes(self.cursor, PAGE_SIZE).await?;
+            let mut failure = None;
+            for event in &events {
+                if let Err(e) = self.apply(event).await {
+                    failure = Some(e);
+                    break;
+                }
+                self.cursor = event.sequence;
+                applied += 1;
+            }
+            if !events.is_empty() {
+                self.save_cursor().await?;
+            }
+            if let Some(e) = failure {
+                return Err(e);
+            }
+            if events.len() < PAGE_SIZE {
+                return Ok(applied);
+            }
+        }
+    }
+
+    /// Make the target match one change
+    async fn apply(&self, event: &ChangeEvent) -> Result<()> {
+        match event.operation {
+            ChangeOperation::Put => {
+                // A put since replaced or deleted is skipped; its own event
+                // follows in the feed
+                match self.metadata.get_object(&event.bucket, &event.key).await? {
+                    Some(object) if Some(&object.etag) == event.etag.as_ref() => self.copy(&object).await,
+                    _ => Ok(()),
+                }
+            }
+            ChangeOperation::Delete => self.target.delete_object(&event.bucket, &event.key).await,
+        }
+    }
+
+    /// Bring the target's copy of `object` up to date, sending the data
+    /// only if the copy was made from other data
+    async fn copy(&self, object: &Object) -> Result<()> {
+        let (bucket, key) = (object.bucket.as_str(), object.key.as_str());
+        let mut metadata = storage_metadata(object);
+        metadata.insert(SOURCE_ETAG_ENTRY.to_string(), object.etag.clone());
+        if self.target.source_etag(bucket, key).await?.as_ref() == Some(&object.etag) {
+            match self.target.replace_object_metadata(bucket, key, metadata.clone()).await {
+                // Deleted from the target since it was looked at
+                Err(ObjectIOError::ObjectNotFound { .. }) => {}
+                result => return result,
+            }
+        }
+        let copied = async {
+            let data = self.source.get_object(bucket, key).await?;
+            self.target.put_object(bucket, key, data, object.size, metadata).await
+        };
+        match copied.await {
+            Ok(()) | Err(ObjectIOError::ObjectNotFound { .. }) => Ok(()),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Record the cursor in the cursor file, replacing it in one step
+    async fn save_cursor(&self) -> Result<()> {
+        let Some(path) = &self.cursor_path else {
+            return Ok(());
+        };
+        let staged = path.with_extension("tmp");
+        tokio::fs::write(&staged, self.cursor.to_string()).await?;
+        tokio::fs::rename(&staged, path).await?;
+        Ok(())
+    }
+}
+
+/// The cursor saved at `path`; 0 when no cursor has been saved yet
+async fn load_cursor(path: &std::path::Path) -> Result<u64> {
+    match tokio::fs::read_to_string(path).await {
+        Ok(contents) => contents.trim().parse().map_err(|_| ObjectIOError::ConfigurationError {
+            message: format!("Replication cursor {} is not a sequence number", path.display()),
+        }),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(0),
+        Err(e) => Err(e.into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::AppState;
+    use std::io::Cursor;
+    use tokio::io::AsyncReadExt;
+
+    async fn put(state: &AppState, key: &str, body: &str) {
+        put_with(state, key, body, HashMap::new()).await
+    }
+
+    async fn put_with(state: &AppState, key: &str, body: &str, metadata: HashMap<String, String>) {
+        let reader = Box::new(Cursor::new(body.as_bytes().to_vec()));
+        crate::handlers::object::store_object(state, "photos", key, reader, metadata)
+            .await
+            .unwrap();
+    }
+
+    async fn read(storage: &Arc<dyn Storage>, key: &str) -> Option<String> {
+        let mut reader = storage.get_object("photos", key).await.ok()?;
+        let mut body = String::new();
+        reader.read_to_string(&mut body).await.unwrap();
+        Some(body)
+    }
+
+    fn colour(colour: &str) -> HashMap<String, String> {
+        HashMap::from([("colour".to_string(), colour.to_string())])
+    }
+
+    #[tokio::test]
+    async fn test_replicate_into_memory_target() {
+        let dir = tempfile::tempdir().unwrap();
+        let state = AppState::for_test(dir.path()).await;
+        state.metadata.create_bucket("photos", "owner").await.unwrap();
+        let target = object_io_storage::StorageBackend::memory();
+        let cursor_path = dir.path().join("replication.cursor");
+        let replicator = |target: Arc<dyn Storage>| {
+            let target = ReplicationTarget::Storage(target);
+            Replicator::new(state.metadata.clone(), state.storage.clone(), target, Some(cursor_path.clone()))
+        };
+
+        put(&state, "a.jpg", "first").await;
+        put(&state, "b.jpg", "bee").await;
+        put(&state, "a.jpg", "second").await;
+        state.metadata.delete_object("photos", "b.jpg").await.unwrap();
+        state.storage.delete_object("photos", "b.jpg").await.unwrap();
+
+        let mut first = replicator(target.clone()).await.unwrap();
+        assert_eq!(first.replicate().await.unwrap(), 4);
+        assert_eq!(read(&target, "a.jpg").await.as_deref(), Some("second"));
+        assert_eq!(read(&target, "b.jpg").await, None);
+        assert_eq!(std::fs::read_to_string(&cursor_path).unwrap(), "4");
+
+        // A new replicator resumes from the saved cursor
+        put(&state, "c.jpg", "sea").await;
+        let mut resumed = replicator(target.clone()).await.unwrap();
+        assert_eq!(resumed.cursor(), 4);
+        assert_eq!(resumed.replicate().await.unwrap(), 1);
+        assert_eq!(read(&target, "c.jpg").await.as_deref(), Some("sea"));
+        assert_eq!(resumed.replicate().await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_metadata_changes_keep_replicated_data() {
+        let dir = tempfile::tempdir().unwrap();
+        let state = AppState::for_test(dir.path()).await;
+        state.metadata.create_bucket("photos", "owner").await.unwrap();
+        let target = object_io_storage::StorageBackend::memory();
+        let mut replicator =
+            Replicator::new(state.metadata.clone(), state.storage.clone(), ReplicationTarget::Storage(target.clone()), None)
+                .await
+                .unwrap();
+
+        put_with(&state, "a.jpg", "first", colour("red")).await;
+        assert_eq!(replicator.replicate().await.unwrap(), 1);
+        let copied = target.get_object_metadata("photos", "a.jpg").await.unwrap();
+        assert_eq!(copied["colour"], "red");
+        let etag = state.metadata.get_object("photos", "a.jpg").await.unwrap().unwrap().etag;
+        assert_eq!(copied[SOURCE_ETAG_ENTRY], etag);
+
+        // Mark the copy's data so a second transfer would show
+        target.put_object("photos", "a.jpg", Box::new(Cursor::new(b"marked".to_vec())), copied).await.unwrap();
+        state.metadata.touch_object("photos", "a.jpg").await.unwrap();
+        state.metadata.update_object_metadata("photos", "a.jpg", "image/jpeg", colour("blue")).await.unwrap();
+        assert_eq!(replicator.replicate().await.unwrap(), 2);
+        assert_eq!(read(&target, "a.jpg").await.as_deref(), Some("marked"));
+        let copied = target.get_object_metadata("photos", "a.jpg").await.unwrap();
+        assert_eq!(copied["colour"], "blue");
+        assert_eq!(copied["content-type"], "image/jpeg");
+
+        // New data is sent
+        put(&state, "a.jpg", "second").await;
+        assert_eq!(replicator.replicate().await.unwrap(), 1);
+        assert_eq!(read(&target, "a.jpg").await.as_deref(), Some("second"));
+    }
+
+    #[tokio::test]
+    async fn test_replicate_to_remote_endpoint() {
+        let dir = tempfile::tempdir().unwrap();
+        let state = AppState::for_test(&dir.path().join("primary")).await;
+        state.metadata.create_bucket("photos", "owner").await.unwrap();
+
+        // Another server, requiring signed requests
+        let mut replica = AppState::for_test(&dir.path().join("replica")).await;
+        let mut config = (*replica.config).clone();
+        config.auth_enabled = true;
+        replica.config = Arc::new(config);
+        replica.metadata.create_user("REPLICA", "replica-secret", "Replica").await.unwrap();
+        let user = replica.metadata.get_user_by_access_key("REPLICA").await.unwrap().unwrap();
+        let owner = user.id.as_ref().and_then(|id| id.as_str()).unwrap().to_string();
+        replica.metadata.create_bucket("photos", &owner).await.unwrap();
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let endpoint = format!("http://{}", listener.local_addr().unwrap());
+        let (stop_tx, stop_rx) = tokio::sync::oneshot::channel::<()>();
+        let server = {
+            let (app, config) = (crate::routes::create_router(replica.clone()), replica.config.clone());
+            tokio::spawn(async move {
+                crate::server::serve(listener, app, &config, async move {
+                    let _ = stop_rx.await;
+                })
+                .await
+            })
+        };
+
+        let remote = |secret: &str| {
+            ReplicationTarget::Remote(RemoteTarget::new(&endpoint, "us-east-1", ("REPLICA", secret)).unwrap())
+        };
+        put_with(&state, "albums/my cat.jpg", "meow", colour("red")).await;
+        put(&state, "b.jpg", "bee").await;
+        state.metadata.delete_object("photos", "b.jpg").await.unwrap();
+        state.storage.delete_object("photos", "b.jpg").await.unwrap();
+
+        // Requests are signed; the wrong secret gets nothing through
+        let mut refused = Replicator::new(state.metadata.clone(), state.storage.clone(), remote("wrong"), None)
+            .await
+            .unwrap();
+        assert!(refused.replicate().await.is_err());
+        assert_eq!(refused.cursor(), 0);
+
+        let mut replicator = Replicator::new(state.metadata.clone(), state.storage.clone(), remote("replica-secret"), None)
+            .await
+            .unwrap();
+        assert_eq!(replicator.replicate().await.unwrap(), 3);
+        assert_eq!(read(&replica.storage, "albums/my cat.jpg").await.as_deref(), Some("meow"));
+        let copied = replica.metadata.get_object("photos", "albums/my cat.jpg").await.unwrap().unwrap();
+        assert_eq!(copied.metadata["colour"], "red");
+        assert!(replica.metadata.get_object("photos", "b.jpg").await.unwrap().is_none());
+
+        // A metadata change is copied without the data
+        replica
+            .storage
+            .put_object("photos", "albums/my cat.jpg", Box::new(Cursor::new(b"marked".to_vec())), HashMap::new())
+            .await
+            .unwrap();
+        state.metadata.update_object_metadata("photos", "albums/my cat.jpg", "image/jpeg", colour("blue")).await.unwrap();
+        assert_eq!(replicator.replicate().await.unwrap(), 1);
+        assert_eq!(read(&replica.storage, "albums/my cat.jpg").await.as_deref(), Some("marked"));
+        let copied = replica.metadata.get_object("photos", "albums/my cat.jpg").await.unwrap().unwrap();
+        assert_eq!(copied.metadata["colour"], "blue");
+        assert_eq!(copied.content_type, "image/jpeg");
+
+        stop_tx.send(()).unwrap();
+        server.await.unwrap().unwrap();
+    }
+}
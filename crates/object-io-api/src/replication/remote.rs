@@ -0,0 +1,206 @@
+//! Replication into an S3 endpoint
+//!
+//! Requests are signed with SigV4 for the configured region. Object data is
+//! streamed with an unsigned payload, and metadata-only changes are sent as
+//! a copy of the object onto itself with `x-amz-metadata-directive: REPLACE`,
+//! so the data is not sent again.
+
+use axum::http::{HeaderMap, HeaderName, HeaderValue, Method, StatusCode};
+use chrono::Utc;
+use object_io_core::{CannedAcl, ObjectIOError, Result};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use tokio::io::AsyncRead;
+
+use crate::{
+    auth::sigv4::{percent_encode, SignatureRequest, SigV4Validator},
+    handlers::object::is_user_metadata,
+};
+
+/// Payload hash sent for streamed object data
+const UNSIGNED_PAYLOAD: &str = "UNSIGNED-PAYLOAD";
+
+/// An S3 endpoint objects are replicated to
+pub struct RemoteTarget {
+    client: reqwest::Client,
+    endpoint: reqwest::Url,
+    signer: SigV4Validator,
+    access_key: String,
+    secret_key: String,
+}
+
+impl RemoteTarget {
+    /// Target at `endpoint`, an `http` or `https` URL with no path, signing
+    /// requests for `region` with an `(access_key, secret_key)` pair
+    pub fn new(endpoint: &str, region: &str, (access_key, secret_key): (&str, &str)) -> Result<Self> {
+        let invalid = |message: String| ObjectIOError::ConfigurationError { message };
+        let endpoint = reqwest::Url::parse(endpoint)
+            .map_err(|e| invalid(format!("Invalid replication endpoint '{}': {}", endpoint, e)))?;
+        if !matches!(endpoint.scheme(), "http" | "https") || endpoint.host_str().is_none() {
+            return Err(invalid(format!("Replication endpoint '{}' is not an HTTP URL", endpoint)));
+        }
+        if access_key.is_empty() || secret_key.is_empty() {
+            return Err(invalid("Replication to an endpoint needs an access_key and secret_key".to_string()));
+        }
+        let client = reqwest::Client::builder()
+            .build()
+            .map_err(|e| invalid(format!("Failed to create replication client: {}", e)))?;
+        Ok(Self {
+            client,
+            endpoint,
+            signer: SigV4Validator::new(region.to_string(), "s3".to_string()),
+            access_key: access_key.to_string(),
+            secret_key: secret_key.to_string(),
+        })
+    }
+
+    /// The endpoint's copy of `key`, `None` if it has none
+    pub async fn head_object(&self, bucket: &str, key: &str) -> Result<Option<HeaderMap>> {
+        let response = self.send(Method::HEAD, bucket, key, HeaderMap::new(), None).await?;
+        match response.status() {
+            StatusCode::NOT_FOUND => Ok(None),
+            status if status.is_success() => Ok(Some(response.headers().clone())),
+            status => Err(request_failed(&Method::HEAD, bucket, key, status)),
+        }
+    }
+
+    /// Upload `size` bytes of `data` as `key` with storage-form `metadata`
+    pub async fn put_object(
+        &self,
+        bucket: &str,
+        key: &str,
+        data: Box<dyn AsyncRead + Send + Unpin>,
+        size: u64,
+        metadata: &HashMap<String, String>,
+    ) -> Result<()> {
+        let mut headers = metadata_headers(metadata)?;
+        headers.insert(reqwest::header::CONTENT_LENGTH, HeaderValue::from(size));
+        let body = reqwest::Body::wrap_stream(tokio_util::io::ReaderStream::new(data));
+        let response = self.send(Method::PUT, bucket, key, headers, Some(body)).await?;
+        match response.status() {
+            status if status.is_success() => Ok(()),
+            status => Err(request_failed(&Method::PUT, bucket, key, status)),
+        }
+    }
+
+    /// Replace the metadata of the endpoint's copy of `key` by copying it
+    /// onto itself
+    pub async fn replace_object_metadata(
+        &self,
+        bucket: &str,
+        key: &str,
+        metadata: &HashMap<String, String>,
+    ) -> Result<()> {
+        let mut headers = metadata_headers(metadata)?;
+        headers.insert("x-amz-copy-source", header_value(&object_path(bucket, key))?);
+        headers.insert("x-amz-metadata-directive", HeaderValue::from_static("REPLACE"));
+        let response = self.send(Method::PUT, bucket, key, headers, None).await?;
+        match response.status() {
+            StatusCode::NOT_FOUND => Err(ObjectIOError::ObjectNotFound {
+                bucket: bucket.to_string(),
+                key: key.to_string(),
+            }),
+            status if status.is_success() => Ok(()),
+            status => Err(request_failed(&Method::PUT, bucket, key, status)),
+        }
+    }
+
+    /// Delete the endpoint's copy of `key`; a copy already gone is not an
+    /// error
+    pub async fn delete_object(&self, bucket: &str, key: &str) -> Result<()> {
+        let response = self.send(Method::DELETE, bucket, key, HeaderMap::new(), None).await?;
+        match response.status() {
+            StatusCode::NOT_FOUND => Ok(()),
+            status if status.is_success() => Ok(()),
+            status => Err(request_failed(&Method::DELETE, bucket, key, status)),
+        }
+    }
+
+    /// Sign and send a request for `key`, with `body` streamed unsigned
+    async fn send(
+        &self,
+        method: Method,
+        bucket: &str,
+        key: &str,
+        mut headers: HeaderMap,
+        body: Option<reqwest::Body>,
+    ) -> Result<reqwest::Response> {
+        let path = object_path(bucket, key);
+        let mut url = self.endpoint.clone();
+        url.set_path(&path);
+
+        let host = match (url.host_str(), url.port()) {
+            (Some(host), Some(port)) => format!("{}:{}", host, port),
+            (Some(host), None) => host.to_string(),
+            (None, _) => String::new(),
+        };
+        let timestamp = Utc::now();
+        let payload_hash = match body {
+            Some(_) => UNSIGNED_PAYLOAD.to_string(),
+            None => hex::encode(Sha256::digest(b"")),
+        };
+        headers.insert(reqwest::header::HOST, header_value(&host)?);
+        headers.insert("x-amz-date", header_value(&timestamp.format("%Y%m%dT%H%M%SZ").to_string())?);
+        headers.insert("x-amz-content-sha256", header_value(&payload_hash)?);
+        let request = SignatureRequest {
+            method: &method,
+            uri: &path,
+            query_string: "",
+            headers: &headers,
+            payload_hash: &payload_hash,
+            timestamp,
+        };
+        let authorization = self.signer.sign(&request, (&self.access_key, &self.secret_key))?;
+        headers.insert(reqwest::header::AUTHORIZATION, header_value(&authorization)?);
+
+        let mut request = self.client.request(method, url).headers(headers);
+        if let Some(body) = body {
+            request = request.body(body);
+        }
+        request.send().await.map_err(|e| ObjectIOError::StorageError {
+            message: format!("Replication request for '{}/{}' failed: {}", bucket, key, e),
+        })
+    }
+}
+
+/// Path of `key`, each segment percent-encoded as SigV4 canonicalizes it
+fn object_path(bucket: &str, key: &str) -> String {
+    let key = key.split('/').map(percent_encode).collect::<Vec<_>>().join("/");
+    format!("/{}/{}", percent_encode(bucket), key)
+}
+
+/// Request headers carrying storage-form `metadata`: the content type,
+/// standard headers, canned ACL and custom metadata
+///
+/// Retention is left out, as the endpoint's bucket may not have object lock.
+fn metadata_headers(metadata: &HashMap<String, String>) -> Result<HeaderMap> {
+    let mut headers = HeaderMap::new();
+    for (name, value) in metadata {
+        let name = if name.starts_with("content-") || object_io_core::OBJECT_HEADERS.contains(&name.as_str()) {
+            name.clone()
+        } else if name == CannedAcl::HEADER {
+            CannedAcl::HEADER.to_string()
+        } else if is_user_metadata(name) {
+            format!("x-amz-meta-{}", name)
+        } else {
+            continue;
+        };
+        let name = HeaderName::try_from(name).map_err(|_| ObjectIOError::InvalidArgument {
+            message: "Metadata name is not a valid header name".to_string(),
+        })?;
+        headers.insert(name, header_value(value)?);
+    }
+    Ok(headers)
+}
+
+fn header_value(value: &str) -> Result<HeaderValue> {
+    HeaderValue::from_str(value).map_err(|_| ObjectIOError::InvalidArgument {
+        message: "Metadata value is not a valid header value".to_string(),
+    })
+}
+
+fn request_failed(method: &Method, bucket: &str, key: &str, status: StatusCode) -> ObjectIOError {
+    ObjectIOError::StorageError {
+        message: format!("Replication {} of '{}/{}' returned {}", method, bucket, key, status),
+    }
+}
@@ -2,24 +2,13 @@
 
 use axum::{
     http::StatusCode,
-    response::IntoResponse,
+    response::{IntoResponse, Response},
     Json,
 };
 use serde::Serialize;
 use serde_json::json;
 
-/// S3-compatible error response
-#[derive(Debug, Serialize)]
-pub struct S3ErrorResponse {
-    #[serde(rename = "Code")]
-    pub code: String,
-    #[serde(rename = "Message")]
-    pub message: String,
-    #[serde(rename = "RequestId")]
-    pub request_id: String,
-    #[serde(rename = "Resource")]
-    pub resource: Option<String>,
-}
+pub mod xml;
 
 /// Standard API error response
 #[derive(Debug, Serialize)]
@@ -30,28 +19,19 @@ pub struct ApiErrorResponse {
     pub timestamp: String,
 }
 
-/// Convert ObjectIO error to HTTP response  
+/// Convert ObjectIO error to HTTP response
 pub fn error_response(error: &object_io_core::ObjectIOError, request_id: String) -> impl IntoResponse {
     let status = StatusCode::from_u16(error.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
-    
-    let error_response = S3ErrorResponse {
-        code: error.s3_error_code().to_string(),
-        message: error.to_string(),
-        request_id: request_id.clone(),
-        resource: None,
-    };
 
-    let mut response = (status, Json(error_response)).into_response();
-    
+    let mut document = xml::ErrorDocument::from_error(error);
+    document.request_id = request_id.clone();
+    let mut response = document.into_response(status);
+
     // Add standard AWS headers
     response.headers_mut().insert(
         "x-amz-request-id",
         request_id.parse().unwrap_or_else(|_| "unknown".parse().unwrap()),
     );
-    response.headers_mut().insert(
-        "content-type",
-        "application/xml".parse().unwrap(),
-    );
 
     response
 }
@@ -70,6 +50,20 @@ pub fn xml_response(xml: String) -> impl IntoResponse {
     )
 }
 
+/// XML namespace for S3 response documents
+pub const S3_XMLNS: &str = "http://s3.amazonaws.com/doc/2006-03-01/";
+
+/// Serialize an S3 response document to an XML response
+pub fn to_xml_response<T: Serialize>(value: &T) -> std::result::Result<Response, StatusCode> {
+    match xml::to_xml_string(value) {
+        Ok(xml) => Ok(xml_response(xml).into_response()),
+        Err(e) => {
+            eprintln!("Failed to serialize XML response: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
 /// Create a health check response
 pub fn health_response() -> impl IntoResponse {
     json_response(json!({
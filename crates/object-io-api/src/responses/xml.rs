@@ -0,0 +1,618 @@
+//! Typed S3 XML documents
+//!
+//! Documents are serialized with `quick-xml`, which escapes `&`, `<`, `>`
+//! and quotes in element text, so keys and bucket names never need to be
+//! escaped by hand.
+
+use axum::{
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+};
+use chrono::{DateTime, Utc};
+use object_io_core::{
+    ContinuationToken, DefaultRetention, Grant, Grantee, ListObjectsResponse, ObjectIOError, ObjectLockMode, ObjectSummary,
+    Permission, RetentionPeriod,
+};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use super::S3_XMLNS;
+
+/// Request ID reported in error documents until requests carry their own
+const NULL_REQUEST_ID: &str = "00000000-0000-0000-0000-000000000000";
+
+/// Serialize a document with the XML declaration S3 responses start with
+pub fn to_xml_string<T: Serialize>(value: &T) -> Result<String, quick_xml::DeError> {
+    let xml = quick_xml::se::to_string(value)?;
+    Ok(format!("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n{}", xml))
+}
+
+/// List objects response
+#[derive(Debug, Serialize)]
+#[serde(rename = "ListBucketResult")]
+pub struct ListBucketResult {
+    #[serde(rename = "@xmlns")]
+    pub xmlns: &'static str,
+    #[serde(rename = "Name")]
+    pub name: String,
+    #[serde(rename = "Prefix")]
+    pub prefix: String,
+    #[serde(rename = "Marker")]
+    pub marker: String,
+    #[serde(rename = "NextMarker", skip_serializing_if = "Option::is_none")]
+    pub next_marker: Option<String>,
+    #[serde(rename = "MaxKeys")]
+    pub max_keys: u32,
+    #[serde(rename = "Delimiter", skip_serializing_if = "Option::is_none")]
+    pub delimiter: Option<String>,
+    #[serde(rename = "IsTruncated")]
+    pub is_truncated: bool,
+    #[serde(rename = "Contents")]
+    pub contents: Vec<ObjectEntry>,
+    #[serde(rename = "CommonPrefixes")]
+    pub common_prefixes: Vec<CommonPrefix>,
+    /// `url` when keys and prefixes are percent-encoded
+    #[serde(rename = "EncodingType", skip_serializing_if = "Option::is_none")]
+    pub encoding_type: Option<&'static str>,
+}
+
+impl ListBucketResult {
+    pub fn from_listing(listing: ListObjectsResponse) -> Self {
+        Self {
+            xmlns: S3_XMLNS,
+            name: listing.bucket,
+            prefix: listing.prefix.unwrap_or_default(),
+            marker: listing.marker.unwrap_or_default(),
+            next_marker: listing.next_marker,
+            max_keys: listing.max_keys,
+            delimiter: listing.delimiter,
+            is_truncated: listing.is_truncated,
+            contents: listing.objects.into_iter().map(ObjectEntry::from).collect(),
+            common_prefixes: listing.common_prefixes.into_iter().map(|prefix| CommonPrefix { prefix }).collect(),
+            encoding_type: None,
+        }
+    }
+
+    /// Percent-encode keys, prefixes, the delimiter and markers, as
+    /// requested by `encoding-type=url`
+    pub fn url_encoded(mut self) -> Self {
+        self.prefix = url_encode(&self.prefix);
+        self.marker = url_encode(&self.marker);
+        self.next_marker = self.next_marker.as_deref().map(url_encode);
+        self.delimiter = self.delimiter.as_deref().map(url_encode);
+        encode_entries(&mut self.contents, &mut self.common_prefixes);
+        self.encoding_type = Some(URL_ENCODING);
+        self
+    }
+}
+
+/// List objects (V2) response
+#[derive(Debug, Serialize)]
+#[serde(rename = "ListBucketResult")]
+pub struct ListBucketResultV2 {
+    #[serde(rename = "@xmlns")]
+    pub xmlns: &'static str,
+    #[serde(rename = "Name")]
+    pub name: String,
+    #[serde(rename = "Prefix")]
+    pub prefix: String,
+    #[serde(rename = "ContinuationToken", skip_serializing_if = "Option::is_none")]
+    pub continuation_token: Option<String>,
+    #[serde(rename = "NextContinuationToken", skip_serializing_if = "Option::is_none")]
+    pub next_continuation_token: Option<String>,
+    #[serde(rename = "StartAfter", skip_serializing_if = "Option::is_none")]
+    pub start_after: Option<String>,
+    #[serde(rename = "KeyCount")]
+    pub key_count: usize,
+    #[serde(rename = "MaxKeys")]
+    pub max_keys: u32,
+    #[serde(rename = "Delimiter", skip_serializing_if = "Option::is_none")]
+    pub delimiter: Option<String>,
+    #[serde(rename = "IsTruncated")]
+    pub is_truncated: bool,
+    #[serde(rename = "Contents")]
+    pub contents: Vec<ObjectEntry>,
+    #[serde(rename = "CommonPrefixes")]
+    pub common_prefixes: Vec<CommonPrefix>,
+    /// `url` when keys and prefixes are percent-encoded
+    #[serde(rename = "EncodingType", skip_serializing_if = "Option::is_none")]
+    pub encoding_type: Option<&'static str>,
+}
+
+impl ListBucketResultV2 {
+    /// `continuation_token` and `start_after` are echoed back as the
+    /// request sent them
+    pub fn from_listing(
+        listing: ListObjectsResponse,
+        continuation_token: Option<String>,
+        start_after: Option<String>,
+    ) -> Self {
+        let listing = ListBucketResult::from_listing(listing);
+        let next_continuation_token = listing
+            .next_marker
+            .filter(|_| listing.is_truncated)
+            .map(|last_key| ContinuationToken::new(listing.prefix.clone(), last_key).encode());
+        Self {
+            xmlns: S3_XMLNS,
+            name: listing.name,
+            prefix: listing.prefix,
+            continuation_token,
+            next_continuation_token,
+            start_after,
+            key_count: listing.contents.len() + listing.common_prefixes.len(),
+            max_keys: listing.max_keys,
+            delimiter: listing.delimiter,
+            is_truncated: listing.is_truncated,
+            contents: listing.contents,
+            common_prefixes: listing.common_prefixes,
+            encoding_type: None,
+        }
+    }
+
+    /// Percent-encode keys, prefixes, the delimiter and `StartAfter`, as
+    /// requested by `encoding-type=url`; continuation tokens are opaque and
+    /// left as they are
+    pub fn url_encoded(mut self) -> Self {
+        self.prefix = url_encode(&self.prefix);
+        self.start_after = self.start_after.as_deref().map(url_encode);
+        self.delimiter = self.delimiter.as_deref().map(url_encode);
+        encode_entries(&mut self.contents, &mut self.common_prefixes);
+        self.encoding_type = Some(URL_ENCODING);
+        self
+    }
+}
+
+/// Value of `encoding-type` (and `EncodingType`) selecting percent-encoding
+pub const URL_ENCODING: &str = "url";
+
+/// Percent-encode a key or prefix, leaving `/` readable
+fn url_encode(value: &str) -> String {
+    value
+        .split('/')
+        .map(|segment| urlencoding::encode(segment).into_owned())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+fn encode_entries(contents: &mut [ObjectEntry], common_prefixes: &mut [CommonPrefix]) {
+    for entry in contents {
+        entry.key = url_encode(&entry.key);
+    }
+    for common in common_prefixes {
+        common.prefix = url_encode(&common.prefix);
+    }
+}
+
+/// An object in a listing
+#[derive(Debug, Serialize)]
+pub struct ObjectEntry {
+    #[serde(rename = "Key")]
+    pub key: String,
+    #[serde(rename = "LastModified")]
+    pub last_modified: String,
+    #[serde(rename = "ETag")]
+    pub etag: String,
+    #[serde(rename = "Size")]
+    pub size: u64,
+    #[serde(rename = "StorageClass")]
+    pub storage_class: &'static str,
+}
+
+impl From<ObjectSummary> for ObjectEntry {
+    fn from(object: ObjectSummary) -> Self {
+        Self {
+            key: object.key,
+            last_modified: object_io_core::format_s3_timestamp(&object.last_modified),
+            etag: format!("\"{}\"", object.etag),
+            size: object.size,
+            storage_class: "STANDARD",
+        }
+    }
+}
+
+/// A rolled-up key prefix in a delimited listing
+#[derive(Debug, Serialize)]
+pub struct CommonPrefix {
+    #[serde(rename = "Prefix")]
+    pub prefix: String,
+}
+
+/// List buckets response
+#[derive(Debug, Serialize)]
+#[serde(rename = "ListAllMyBucketsResult")]
+pub struct ListAllMyBucketsResult {
+    #[serde(rename = "@xmlns")]
+    pub xmlns: &'static str,
+    #[serde(rename = "Owner")]
+    pub owner: Owner,
+    #[serde(rename = "Buckets")]
+    pub buckets: Buckets,
+    /// Pass back as `continuation-token` to fetch the next page
+    #[serde(rename = "ContinuationToken", skip_serializing_if = "Option::is_none")]
+    pub continuation_token: Option<String>,
+}
+
+impl ListAllMyBucketsResult {
+    pub fn new(owner: Owner, buckets: Vec<BucketEntry>, continuation_token: Option<String>) -> Self {
+        Self {
+            xmlns: S3_XMLNS,
+            owner,
+            buckets: Buckets { bucket: buckets },
+            continuation_token,
+        }
+    }
+}
+
+/// Owner of the listed buckets, or of a resource in an ACL
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Owner {
+    #[serde(rename = "ID")]
+    pub id: String,
+    #[serde(rename = "DisplayName")]
+    pub display_name: String,
+}
+
+/// Wrapper giving each bucket its own `<Bucket>` element
+#[derive(Debug, Serialize)]
+pub struct Buckets {
+    #[serde(rename = "Bucket")]
+    pub bucket: Vec<BucketEntry>,
+}
+
+/// A bucket in a listing
+#[derive(Debug, Serialize)]
+pub struct BucketEntry {
+    #[serde(rename = "Name")]
+    pub name: String,
+    #[serde(rename = "CreationDate")]
+    pub creation_date: String,
+}
+
+impl BucketEntry {
+    pub fn new(name: impl Into<String>, created_at: &DateTime<Utc>) -> Self {
+        Self {
+            name: name.into(),
+            creation_date: object_io_core::format_s3_timestamp(created_at),
+        }
+    }
+}
+
+/// Namespace of the `xsi:type` attribute on ACL grantees
+const XSI_NAMESPACE: &str = "http://www.w3.org/2001/XMLSchema-instance";
+
+/// Object ACL, as returned by `GET ?acl` and accepted by `PUT ?acl`
+///
+/// The owner's full control is listed as the first grant; it is implied
+/// rather than stored, so `grants` leaves it out.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename = "AccessControlPolicy")]
+pub struct AccessControlPolicy {
+    #[serde(rename = "@xmlns", skip_deserializing)]
+    pub xmlns: &'static str,
+    #[serde(rename = "Owner")]
+    pub owner: Owner,
+    #[serde(rename = "AccessControlList")]
+    pub access_control_list: AccessControlList,
+}
+
+/// Wrapper giving each grant its own `<Grant>` element
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct AccessControlList {
+    #[serde(rename = "Grant", default)]
+    pub grants: Vec<GrantEntry>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GrantEntry {
+    #[serde(rename = "Grantee")]
+    pub grantee: GranteeEntry,
+    #[serde(rename = "Permission")]
+    pub permission: String,
+}
+
+/// Grantee, identified by canonical user ID or group URI according to its
+/// `xsi:type`
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GranteeEntry {
+    #[serde(rename = "@xmlns:xsi", skip_deserializing)]
+    pub xmlns_xsi: &'static str,
+    /// Read back by local name, as the deserializer drops the prefix
+    #[serde(rename = "@xsi:type", alias = "@type")]
+    pub kind: String,
+    #[serde(rename = "ID", skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    #[serde(rename = "DisplayName", skip_serializing_if = "Option::is_none")]
+    pub display_name: Option<String>,
+    #[serde(rename = "URI", skip_serializing_if = "Option::is_none")]
+    pub uri: Option<String>,
+    #[serde(rename = "EmailAddress", skip_serializing_if = "Option::is_none")]
+    pub email_address: Option<String>,
+}
+
+impl GranteeEntry {
+    fn canonical_user(id: String) -> Self {
+        Self {
+            xmlns_xsi: XSI_NAMESPACE,
+            kind: "CanonicalUser".to_string(),
+            id: Some(id),
+            display_name: None,
+            uri: None,
+            email_address: None,
+        }
+    }
+
+    fn group(uri: &str) -> Self {
+        Self {
+            xmlns_xsi: XSI_NAMESPACE,
+            kind: "Group".to_string(),
+            id: None,
+            display_name: None,
+            uri: Some(uri.to_string()),
+            email_address: None,
+        }
+    }
+}
+
+impl AccessControlPolicy {
+    pub fn new(owner: Owner, acl: &[Grant]) -> Self {
+        let owner_grant = GrantEntry {
+            grantee: GranteeEntry::canonical_user(owner.id.clone()),
+            permission: Permission::FullControl.as_str().to_string(),
+        };
+        let grants = acl.iter().map(|grant| GrantEntry {
+            grantee: match &grant.grantee {
+                Grantee::User(id) => GranteeEntry::canonical_user(id.to_string()),
+                Grantee::Group(uri) => GranteeEntry::group(uri),
+                Grantee::AllUsers => GranteeEntry::group(Grantee::ALL_USERS_URI),
+                Grantee::AuthenticatedUsers => GranteeEntry::group(Grantee::AUTHENTICATED_USERS_URI),
+            },
+            permission: grant.permission.as_str().to_string(),
+        });
+        Self {
+            xmlns: S3_XMLNS,
+            owner,
+            access_control_list: AccessControlList {
+                grants: std::iter::once(owner_grant).chain(grants).collect(),
+            },
+        }
+    }
+
+    /// The grants to store, leaving out the owner's full control
+    ///
+    /// Users are identified by canonical ID only; grants by email address
+    /// are refused.
+    pub fn grants(&self) -> Result<Vec<Grant>, ObjectIOError> {
+        let malformed = |message: String| ObjectIOError::MalformedAcl { message };
+        let mut grants = Vec::new();
+        for entry in &self.access_control_list.grants {
+            let permission = Permission::parse(&entry.permission)
+                .ok_or_else(|| malformed(format!("Unknown permission '{}'", entry.permission)))?;
+            let grantee = match (entry.grantee.kind.as_str(), &entry.grantee.id, &entry.grantee.uri) {
+                ("CanonicalUser", Some(id), _) if *id == self.owner.id => {
+                    if permission == Permission::FullControl {
+                        continue;
+                    }
+                    return Err(malformed("The owner always has full control".to_string()));
+                }
+                ("CanonicalUser", Some(id), _) => Grantee::User(
+                    Uuid::parse_str(id).map_err(|_| malformed(format!("Unknown canonical user ID '{}'", id)))?,
+                ),
+                ("Group", _, Some(uri)) => Grantee::group(uri),
+                (kind, _, _) => return Err(malformed(format!("Unsupported grantee type '{}'", kind))),
+            };
+            let grant = Grant::new(grantee, permission);
+            if !grants.contains(&grant) {
+                grants.push(grant);
+            }
+        }
+        Ok(grants)
+    }
+}
+
+/// Bucket object lock configuration, as returned by `GET ?object-lock` and
+/// accepted by `PUT ?object-lock`
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename = "ObjectLockConfiguration")]
+pub struct ObjectLockConfiguration {
+    #[serde(rename = "@xmlns", skip_deserializing)]
+    pub xmlns: &'static str,
+    #[serde(rename = "ObjectLockEnabled", skip_serializing_if = "Option::is_none")]
+    pub object_lock_enabled: Option<String>,
+    #[serde(rename = "Rule", default, skip_serializing_if = "Option::is_none")]
+    pub rule: Option<ObjectLockRule>,
+}
+
+/// Wrapper around a bucket's default retention
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ObjectLockRule {
+    #[serde(rename = "DefaultRetention")]
+    pub default_retention: DefaultRetentionEntry,
+}
+
+/// Default retention, of either `Days` or `Years`
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DefaultRetentionEntry {
+    #[serde(rename = "Mode")]
+    pub mode: String,
+    #[serde(rename = "Days", default, skip_serializing_if = "Option::is_none")]
+    pub days: Option<u32>,
+    #[serde(rename = "Years", default, skip_serializing_if = "Option::is_none")]
+    pub years: Option<u32>,
+}
+
+impl ObjectLockConfiguration {
+    /// The configuration of a bucket with object lock enabled
+    pub fn enabled(default_retention: Option<DefaultRetention>) -> Self {
+        let rule = default_retention.map(|default_retention| {
+            let (days, years) = match default_retention.period {
+                RetentionPeriod::Days(days) => (Some(days), None),
+                RetentionPeriod::Years(years) => (None, Some(years)),
+            };
+            ObjectLockRule {
+                default_retention: DefaultRetentionEntry {
+                    mode: default_retention.mode.as_str().to_string(),
+                    days,
+                    years,
+                },
+            }
+        });
+        Self {
+            xmlns: S3_XMLNS,
+            object_lock_enabled: Some("Enabled".to_string()),
+            rule,
+        }
+    }
+
+    /// The default retention the rule describes, if there is one
+    pub fn default_retention(&self) -> Result<Option<DefaultRetention>, ObjectIOError> {
+        let Some(rule) = &self.rule else {
+            return Ok(None);
+        };
+        let entry = &rule.default_retention;
+        let mode = ObjectLockMode::parse(&entry.mode).ok_or_else(|| ObjectIOError::InvalidArgument {
+            message: format!("Unknown object lock mode: {}", entry.mode),
+        })?;
+        DefaultRetention::new(mode, entry.days, entry.years).map(Some)
+    }
+}
+
+/// Object legal hold, as returned by `GET ?legal-hold` and accepted by
+/// `PUT ?legal-hold`
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename = "LegalHold")]
+pub struct LegalHold {
+    #[serde(rename = "@xmlns", skip_deserializing)]
+    pub xmlns: &'static str,
+    /// `ON` or `OFF`
+    #[serde(rename = "Status")]
+    pub status: String,
+}
+
+impl LegalHold {
+    pub fn new(on: bool) -> Self {
+        Self {
+            xmlns: S3_XMLNS,
+            status: if on { "ON" } else { "OFF" }.to_string(),
+        }
+    }
+}
+
+/// S3 error document
+#[derive(Debug, Serialize)]
+#[serde(rename = "Error")]
+pub struct ErrorDocument {
+    #[serde(rename = "Code")]
+    pub code: String,
+    #[serde(rename = "Message")]
+    pub message: String,
+    #[serde(rename = "BucketName", skip_serializing_if = "Option::is_none")]
+    pub bucket_name: Option<String>,
+    #[serde(rename = "Key", skip_serializing_if = "Option::is_none")]
+    pub key: Option<String>,
+    #[serde(rename = "RequestId")]
+    pub request_id: String,
+}
+
+impl ErrorDocument {
+    pub fn new(code: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            code: code.into(),
+            message: message.into(),
+            bucket_name: None,
+            key: None,
+            request_id: NULL_REQUEST_ID.to_string(),
+        }
+    }
+
+    /// Document for `error`, with its S3 error code
+    pub fn from_error(error: &ObjectIOError) -> Self {
+        Self::new(error.s3_error_code(), error.to_string())
+    }
+
+    pub fn with_bucket(mut self, bucket: impl Into<String>) -> Self {
+        self.bucket_name = Some(bucket.into());
+        self
+    }
+
+    pub fn with_key(mut self, key: impl Into<String>) -> Self {
+        self.key = Some(key.into());
+        self
+    }
+
+    /// Response carrying this document with `status`
+    pub fn into_response(self, status: StatusCode) -> Response {
+        match to_xml_string(&self) {
+            Ok(body) => (status, [(header::CONTENT_TYPE, "application/xml")], body).into_response(),
+            Err(e) => {
+                eprintln!("Failed to serialize error document: {}", e);
+                status.into_response()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_keys_are_escaped() {
+        let listing = ListObjectsResponse {
+            bucket: "photos".to_string(),
+            prefix: Some("a&b/".to_string()),
+            delimiter: None,
+            marker: None,
+            next_marker: None,
+            max_keys: 1000,
+            is_truncated: false,
+            objects: vec![ObjectSummary {
+                key: "a&b/<script>\"x\".txt".to_string(),
+                size: 3,
+                etag: "abc".to_string(),
+                last_modified: Utc::now(),
+                storage_class: object_io_core::StorageClass::Standard,
+            }],
+            common_prefixes: Vec::new(),
+        };
+        let xml = to_xml_string(&ListBucketResult::from_listing(listing)).unwrap();
+        assert!(xml.contains("<Prefix>a&amp;b/</Prefix>"), "{}", xml);
+        assert!(xml.contains("<Key>a&amp;b/&lt;script&gt;&quot;x&quot;.txt</Key>"), "{}", xml);
+        assert!(!xml.contains("<script>"));
+
+        let error = ErrorDocument::new("NoSuchKey", "The specified key does not exist.").with_key("a&b<c>");
+        let xml = to_xml_string(&error).unwrap();
+        assert!(xml.contains("<Key>a&amp;b&lt;c&gt;</Key>"), "{}", xml);
+    }
+
+    #[test]
+    fn test_access_control_policy_round_trip() {
+        let reader = Uuid::new_v4();
+        let acl = vec![
+            Grant::new(Grantee::AllUsers, Permission::Read),
+            Grant::new(Grantee::User(reader), Permission::WriteAcp),
+            Grant::new(Grantee::Group("http://acs.amazonaws.com/groups/s3/LogDelivery".to_string()), Permission::Write),
+        ];
+        let owner = || Owner {
+            id: "owner-id".to_string(),
+            display_name: "owner".to_string(),
+        };
+        let xml = to_xml_string(&AccessControlPolicy::new(owner(), &acl)).unwrap();
+        assert!(
+            xml.contains(
+                "<Grant><Grantee xmlns:xsi=\"http://www.w3.org/2001/XMLSchema-instance\" xsi:type=\"CanonicalUser\">\
+                 <ID>owner-id</ID></Grantee><Permission>FULL_CONTROL</Permission></Grant>"
+            ),
+            "{}",
+            xml
+        );
+        assert!(xml.contains("<URI>http://acs.amazonaws.com/groups/global/AllUsers</URI>"), "{}", xml);
+
+        let policy: AccessControlPolicy = quick_xml::de::from_str(&xml).unwrap();
+        assert_eq!(policy.owner.id, "owner-id");
+        assert_eq!(policy.grants().unwrap(), acl);
+
+        let policy = AccessControlPolicy::new(owner(), &[]);
+        assert_eq!(policy.access_control_list.grants.len(), 1);
+        assert!(policy.grants().unwrap().is_empty());
+    }
+}
@@ -1,29 +1,44 @@
 //! API Routes for ObjectIO
 
 use axum::{
+    error_handling::HandleErrorLayer,
     middleware,
-    routing::{delete, get, head, put},
+    routing::{delete, get, head, post, put},
     Router,
 };
 use object_io_core::Result;
+use object_io_storage::StorageBackend;
+use tower::{limit::GlobalConcurrencyLimitLayer, ServiceBuilder};
 use tower_http::trace::TraceLayer;
 use tracing::info;
 
 use crate::{
-    handlers::{bucket, object},
+    handlers::{admin, bucket, multipart, object},
     middleware::{
-        cors_layer, timeout_layer, body_limit_layer,
-        request_id_middleware, security_headers_middleware
+        cors_layer, body_limit_layer, compression_layer, concurrency_limit_exceeded, expect_continue_middleware,
+        header_size_middleware, in_flight_middleware, rate_limit_middleware, read_only_middleware,
+        request_id_middleware, request_timeout_middleware, security_headers_middleware, slow_request_middleware,
+        uncompressed_ranges_middleware, virtual_host_middleware, encoded_slash_middleware, READ_ONLY_PATH,
     },
+    config::ServerConfig,
     state::AppState,
 };
 
 pub mod health;
 
 /// Create the main application router
-pub async fn create_app() -> Result<Router> {
+///
+/// Fails with every problem the startup self-check finds before anything is
+/// initialized.
+pub async fn create_app(config: ServerConfig) -> Result<Router> {
+    info!("Running startup self-check...");
+    crate::self_check::self_check(&config).await.into_result()?;
+
+    info!("Initializing storage backend ({:?})...", config.storage_backend);
+    let storage = StorageBackend::new(config.storage_config()?).await?;
+
     info!("Initializing application state...");
-    let state = AppState::new().await?;
+    let state = AppState::new(config, storage).await?;
     
     // Ensure admin user exists
     // TODO: Re-enable after fixing authentication system
@@ -31,40 +46,789 @@ pub async fn create_app() -> Result<Router> {
     
     info!("Application state initialized successfully");
     
+    Ok(create_router(state))
+}
+
+/// Create the application router for already-initialized state
+pub fn create_router(state: AppState) -> Router {
     info!("Setting up routes and middleware...");
     let app = Router::new()
         // Health check endpoint
         .route("/health", get(health::health_check))
         
+        // Admin routes (the leading underscore keeps them clear of valid bucket names)
+        .route("/_admin/stats", get(admin::stats))
+        .route("/_admin/users", get(admin::list_users))
+        .route("/_admin/buckets", get(admin::list_all_buckets))
+        .route("/_admin/users/:user_id/access-keys", get(admin::list_access_keys))
+        .route("/_admin/users/:user_id/access-keys", post(admin::create_access_key))
+        .route("/_admin/users/:user_id/access-keys/:access_key", put(admin::update_access_key))
+        .route("/_admin/users/:user_id/access-keys/:access_key", delete(admin::delete_access_key))
+        .route("/_admin/users/:user_id/access-keys/:access_key/scope", put(admin::set_access_key_scope))
+        .route("/_admin/users/:user_id/access-keys/:access_key/scope", delete(admin::delete_access_key_scope))
+        .route("/_admin/multipart/sweep", post(admin::sweep_multipart_uploads))
+        .route("/_admin/scrub", post(admin::scrub_bucket))
+        .route("/_admin/changes", get(admin::list_changes))
+        .route(READ_ONLY_PATH, get(admin::get_read_only))
+        .route(READ_ONLY_PATH, put(admin::set_read_only))
+        
         // S3 API routes
         // Root endpoint - List buckets
         .route("/", get(bucket::list_buckets))
         
         // Bucket operations
-        .route("/:bucket", put(bucket::create_bucket))
+        .route("/:bucket", put(bucket::put_bucket))
         .route("/:bucket", delete(bucket::delete_bucket))
         .route("/:bucket", head(bucket::head_bucket))
-        .route("/:bucket", get(bucket::get_bucket_location))
+        .route("/:bucket", get(bucket::get_bucket))
+        .route("/:bucket", post(bucket::post_bucket))
         
         // Object operations
-        .route("/:bucket/:key", put(object::put_object))
-        .route("/:bucket/:key", get(object::get_object))
-        .route("/:bucket/:key", delete(object::delete_object))
-        .route("/:bucket/:key", head(object::head_object))
+        .route("/:bucket/*key", put(object::put_object))
+        .route("/:bucket/*key", get(object::get_object))
+        .route("/:bucket/*key", delete(object::delete_object))
+        .route("/:bucket/*key", head(object::head_object))
+        .route("/:bucket/*key", post(multipart::post_object))
         
         // Add application state
-        .with_state(state.clone())
-        
-        // Add middleware layers (applied in reverse order)
-        // TODO: Re-enable authentication middleware after fixing trait bounds
-        // .layer(middleware::from_fn_with_state(state.clone(), crate::auth::auth_middleware))
+        .with_state(state.clone());
+
+    // Add middleware layers (applied in reverse order)
+    // Auth and the handlers' bucket checks run before the body is read, so
+    // `Expect: 100-continue` uploads that would fail get the final status
+    // instead of `100 Continue` (see `expect_continue_middleware`)
+    // Inside auth so authenticated clients are limited by access key
+    let app = app.layer(middleware::from_fn_with_state(state.clone(), rate_limit_middleware));
+    let app = if state.config.auth_enabled {
+        app.layer(middleware::from_fn_with_state(state.clone(), crate::auth::auth_middleware))
+    } else {
+        app
+    };
+    // Writes refused in read-only mode are refused before authenticating
+    let app = app.layer(middleware::from_fn_with_state(state.clone(), read_only_middleware));
+    // Oversized header sections are refused before auth parses them
+    let app = app.layer(middleware::from_fn_with_state(state.clone(), header_size_middleware));
+
+    let app = app
+        .layer(middleware::from_fn_with_state(state.clone(), expect_continue_middleware))
         .layer(middleware::from_fn(security_headers_middleware))
         .layer(middleware::from_fn(request_id_middleware))
-        .layer(cors_layer())
-        .layer(timeout_layer())
-        .layer(body_limit_layer())
-        .layer(TraceLayer::new_for_http());
+        .layer(middleware::from_fn_with_state(state.clone(), in_flight_middleware))
+        .layer(middleware::from_fn_with_state(state.clone(), request_timeout_middleware))
+        // Outside the timeout so requests cut short by it are logged too
+        .layer(middleware::from_fn_with_state(state.clone(), slow_request_middleware))
+        .layer(body_limit_layer(state.config.max_body_size));
+    // Requests over the limit are shed at once rather than queued, so a
+    // flood is refused cheaply instead of piling up open connections
+    let app = match state.config.max_concurrent_requests {
+        0 => app,
+        max => app.layer(
+            ServiceBuilder::new()
+                .layer(HandleErrorLayer::new(concurrency_limit_exceeded))
+                .load_shed()
+                .layer(GlobalConcurrencyLimitLayer::new(max)),
+        ),
+    };
+    // Outside the limits, so their error documents are compressed too
+    let app = if state.config.compression_enabled {
+        app.layer(compression_layer(state.config.compression_min_size))
+            .layer(middleware::from_fn(uncompressed_ranges_middleware))
+    } else {
+        app
+    };
+    let app = app.layer(TraceLayer::new_for_http());
+
+    // Virtual-host-style requests and encoded slashes in keys are rewritten
+    // before routing, so these wrap the whole router instead of being among
+    // its layers (the virtual-host rewrite runs first)
+    let app = Router::new()
+        .fallback_service(app)
+        .layer(middleware::from_fn_with_state(state.clone(), encoded_slash_middleware));
+    let app = if state.config.virtual_host_domain.is_some() {
+        app.layer(middleware::from_fn_with_state(state.clone(), virtual_host_middleware))
+    } else {
+        app
+    };
+
+    // CORS is outermost so every response, including auth failures, timeouts
+    // and oversized-body rejections, is readable by browser clients
+    let app = app.layer(cors_layer());
 
     info!("Application router configured successfully");
-    Ok(app)
+    app
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{
+        body::{to_bytes, Body, Bytes},
+        http::{header, Method, Request, StatusCode},
+    };
+    use object_io_core::{CannedAcl, ContinuationToken, Grant, Grantee, Permission};
+    use crate::auth::DeniedReads;
+    use std::sync::Arc;
+    use tower::ServiceExt;
+
+    async fn send(app: &Router, method: Method, host: &str, uri: &str, body: &'static str) -> (StatusCode, String) {
+        let request = Request::builder()
+            .method(method)
+            .uri(uri)
+            .header("host", host)
+            .body(Body::from(body))
+            .unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        let status = response.status();
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, String::from_utf8_lossy(&body).into_owned())
+    }
+
+    #[tokio::test]
+    async fn test_path_and_virtual_host_addressing() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut state = AppState::for_test(dir.path()).await;
+        let mut config = (*state.config).clone();
+        config.virtual_host_domain = Some("s3.local".to_string());
+        state.config = Arc::new(config);
+        state.metadata.create_bucket("photos", "owner").await.unwrap();
+        let app = create_router(state);
+
+        // Written virtual-host-style, read path-style
+        let (status, _) = send(&app, Method::PUT, "photos.s3.local", "/a.txt", "alpha").await;
+        assert_eq!(status, StatusCode::OK);
+        let (status, body) = send(&app, Method::GET, "s3.local", "/photos/a.txt", "").await;
+        assert_eq!((status, body.as_str()), (StatusCode::OK, "alpha"));
+
+        // Written path-style, read virtual-host-style
+        let (status, _) = send(&app, Method::PUT, "s3.local:5500", "/photos/b.txt", "beta").await;
+        assert_eq!(status, StatusCode::OK);
+        let (status, body) = send(&app, Method::GET, "photos.s3.local:5500", "/b.txt", "").await;
+        assert_eq!((status, body.as_str()), (StatusCode::OK, "beta"));
+
+        // The bucket root lists the bucket, with the query intact
+        let (status, body) = send(&app, Method::GET, "photos.s3.local", "/?prefix=a", "").await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.contains("<Key>a.txt</Key>"));
+        assert!(!body.contains("<Key>b.txt</Key>"));
+
+        // Unknown virtual-host buckets and path-style routes behave as before
+        let (status, _) = send(&app, Method::GET, "missing.s3.local", "/b.txt", "").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        let (status, _) = send(&app, Method::GET, "s3.local", "/health", "").await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_virtual_host_addressing_disabled() {
+        let dir = tempfile::tempdir().unwrap();
+        let state = AppState::for_test(dir.path()).await;
+        state.metadata.create_bucket("photos", "owner").await.unwrap();
+        let app = create_router(state);
+
+        // Without a configured domain the Host header is ignored
+        let (status, _) = send(&app, Method::PUT, "photos.s3.local", "/photos/a.txt", "alpha").await;
+        assert_eq!(status, StatusCode::OK);
+        let (status, body) = send(&app, Method::GET, "photos.s3.local", "/photos/a.txt", "").await;
+        assert_eq!((status, body.as_str()), (StatusCode::OK, "alpha"));
+    }
+
+    #[tokio::test]
+    async fn test_delete_bucket_status_codes() {
+        let dir = tempfile::tempdir().unwrap();
+        let state = AppState::for_test(dir.path()).await;
+        state.metadata.create_bucket("photos", "owner").await.unwrap();
+        let app = create_router(state);
+
+        let (status, _) = send(&app, Method::PUT, "localhost", "/photos/a.txt", "alpha").await;
+        assert_eq!(status, StatusCode::OK);
+        let (status, _) = send(&app, Method::DELETE, "localhost", "/photos", "").await;
+        assert_eq!(status, StatusCode::CONFLICT);
+
+        let (status, _) = send(&app, Method::DELETE, "localhost", "/photos/a.txt", "").await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        let (status, _) = send(&app, Method::DELETE, "localhost", "/photos", "").await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        let (status, _) = send(&app, Method::DELETE, "localhost", "/photos", "").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_error_responses_allow_cross_origin_reads() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut state = AppState::for_test(dir.path()).await;
+        let mut config = (*state.config).clone();
+        config.auth_enabled = true;
+        config.max_body_size = 4;
+        state.config = Arc::new(config);
+        let app = create_router(state);
+
+        let request = |method: Method, uri: &str, body: &'static str| {
+            Request::builder()
+                .method(method)
+                .uri(uri)
+                .header("host", "localhost")
+                .header("origin", "http://localhost:8080")
+                .header("content-length", body.len())
+                .body(Body::from(body))
+                .unwrap()
+        };
+
+        // Rejected by the auth middleware
+        let response = app.clone().oneshot(request(Method::GET, "/photos", "")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        assert_eq!(response.headers()["access-control-allow-origin"], "*");
+
+        // Rejected by the body limit, outside the router
+        let response = app.oneshot(request(Method::PUT, "/photos/a.txt", "too large")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+        assert_eq!(response.headers()["access-control-allow-origin"], "*");
+    }
+
+    /// Parse an XML document, failing on malformed XML, and return the
+    /// unescaped text of each `element`
+    fn xml_texts(xml: &str, element: &str) -> Vec<String> {
+        use quick_xml::events::Event;
+
+        let mut reader = quick_xml::Reader::from_str(xml);
+        let (mut texts, mut inside) = (Vec::new(), false);
+        loop {
+            match reader.read_event().unwrap() {
+                Event::Start(start) => inside = start.name().as_ref() == element.as_bytes(),
+                Event::Text(text) if inside => texts.push(text.unescape().unwrap().into_owned()),
+                Event::End(_) => inside = false,
+                Event::Eof => return texts,
+                _ => {}
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_listings_escape_special_characters() {
+        let dir = tempfile::tempdir().unwrap();
+        let state = AppState::for_test(dir.path()).await;
+        state.metadata.create_bucket("photos", "owner").await.unwrap();
+        let app = create_router(state);
+
+        let (status, _) = send(&app, Method::PUT, "localhost", "/photos/a%26b%3Cc%3E.txt", "x").await;
+        assert_eq!(status, StatusCode::OK);
+        let (status, _) = send(&app, Method::PUT, "localhost", "/photos/%22q%27/d.txt", "x").await;
+        assert_eq!(status, StatusCode::OK);
+
+        for uri in ["/photos", "/photos?list-type=2"] {
+            let (status, body) = send(&app, Method::GET, "localhost", uri, "").await;
+            assert_eq!(status, StatusCode::OK);
+            assert_eq!(xml_texts(&body, "Key"), ["\"q'/d.txt", "a&b<c>.txt"], "{}", body);
+        }
+
+        let (status, body) = send(&app, Method::GET, "localhost", "/photos?delimiter=/&prefix=%22", "").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(xml_texts(&body, "Prefix"), ["\"", "\"q'/"], "{}", body);
+    }
+
+    #[tokio::test]
+    async fn test_listings_with_url_encoding() {
+        let dir = tempfile::tempdir().unwrap();
+        let state = AppState::for_test(dir.path()).await;
+        state.metadata.create_bucket("photos", "owner").await.unwrap();
+        let app = create_router(state);
+
+        for key in ["my%20trip/caf%C3%A9%20%C3%BC.jpg", "my%20trip/sub/x+y.jpg", "z.jpg"] {
+            let (status, _) = send(&app, Method::PUT, "localhost", &format!("/photos/{}", key), "x").await;
+            assert_eq!(status, StatusCode::OK);
+        }
+
+        let (status, body) = send(&app, Method::GET, "localhost", "/photos?encoding-type=url&max-keys=1", "").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(xml_texts(&body, "Key"), ["my%20trip/caf%C3%A9%20%C3%BC.jpg"], "{}", body);
+        assert_eq!(xml_texts(&body, "NextMarker"), ["my%20trip/caf%C3%A9%20%C3%BC.jpg"]);
+        assert_eq!(xml_texts(&body, "EncodingType"), ["url"]);
+
+        let (status, body) = send(
+            &app,
+            Method::GET,
+            "localhost",
+            "/photos?list-type=2&encoding-type=url&prefix=my%20trip/&delimiter=/",
+            "",
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(xml_texts(&body, "Key"), ["my%20trip/caf%C3%A9%20%C3%BC.jpg"], "{}", body);
+        assert_eq!(xml_texts(&body, "Prefix"), ["my%20trip/", "my%20trip/sub/"]);
+        assert_eq!(xml_texts(&body, "EncodingType"), ["url"]);
+
+        // Without the parameter keys are returned as stored
+        let (_, body) = send(&app, Method::GET, "localhost", "/photos?prefix=my%20trip/sub/", "").await;
+        assert_eq!(xml_texts(&body, "Key"), ["my trip/sub/x+y.jpg"]);
+        assert!(xml_texts(&body, "EncodingType").is_empty());
+        let (_, body) = send(&app, Method::GET, "localhost", "/photos?prefix=my%20trip/sub/&encoding-type=url", "").await;
+        assert_eq!(xml_texts(&body, "Key"), ["my%20trip/sub/x%2By.jpg"]);
+
+        let (status, _) = send(&app, Method::GET, "localhost", "/photos?encoding-type=base64", "").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_list_objects_v2_pages_with_continuation_token() {
+        let dir = tempfile::tempdir().unwrap();
+        let state = AppState::for_test(dir.path()).await;
+        state.metadata.create_bucket("photos", "owner").await.unwrap();
+        let app = create_router(state);
+        for key in ["a.txt", "docs/b.txt", "docs/c.txt", "docs/d.txt"] {
+            let (status, _) = send(&app, Method::PUT, "localhost", &format!("/photos/{}", key), "x").await;
+            assert_eq!(status, StatusCode::OK);
+        }
+
+        let (status, body) = send(&app, Method::GET, "localhost", "/photos?list-type=2&prefix=docs/&max-keys=2", "").await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.contains("<KeyCount>2</KeyCount>"));
+        assert!(body.contains("<IsTruncated>true</IsTruncated>"));
+        assert!(!body.contains("<Key>a.txt</Key>"));
+        // The token is opaque, and bound to the listing's prefix
+        let token = xml_texts(&body, "NextContinuationToken").remove(0);
+        assert_eq!(ContinuationToken::decode_for(&token, "docs/").unwrap().last_key, "docs/c.txt");
+
+        let uri = format!("/photos?list-type=2&prefix=docs/&max-keys=2&continuation-token={}", token);
+        let (status, body) = send(&app, Method::GET, "localhost", &uri, "").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(xml_texts(&body, "ContinuationToken"), [token.as_str()]);
+        assert!(body.contains("<Key>docs/d.txt</Key>"));
+        assert!(body.contains("<IsTruncated>false</IsTruncated>"));
+        assert!(!body.contains("<NextContinuationToken>"));
+
+        // Tokens that were altered, or are for another prefix, are refused
+        let mut altered = token.clone();
+        altered.replace_range(..1, if token.starts_with('A') { "B" } else { "A" });
+        for uri in [
+            format!("/photos?list-type=2&prefix=docs/&continuation-token={}", altered),
+            "/photos?list-type=2&prefix=docs/&continuation-token=docs/c.txt".to_string(),
+            format!("/photos?list-type=2&prefix=doc&continuation-token={}", token),
+        ] {
+            let (status, body) = send(&app, Method::GET, "localhost", &uri, "").await;
+            assert_eq!(status, StatusCode::BAD_REQUEST, "{}", uri);
+            assert!(body.contains("<Code>InvalidArgument</Code>"), "{}", body);
+        }
+
+        let (_, body) = send(&app, Method::GET, "localhost", "/photos?list-type=2&start-after=docs/b.txt", "").await;
+        assert!(body.contains("<StartAfter>docs/b.txt</StartAfter>"));
+        assert!(!body.contains("<Key>docs/b.txt</Key>"));
+        assert!(body.contains("<Key>docs/c.txt</Key>"));
+    }
+
+    #[tokio::test]
+    async fn test_listings_as_json() {
+        let dir = tempfile::tempdir().unwrap();
+        let state = AppState::for_test(dir.path()).await;
+        state.metadata.create_bucket("photos", "owner").await.unwrap();
+        let app = create_router(state);
+        for key in ["a.txt", "docs/b.txt", "docs/c.txt"] {
+            let (status, _) = send(&app, Method::PUT, "localhost", &format!("/photos/{}", key), "x").await;
+            assert_eq!(status, StatusCode::OK);
+        }
+
+        let list_json = |uri: &'static str| {
+            let app = app.clone();
+            async move {
+                let request = Request::builder()
+                    .uri(uri)
+                    .header("host", "localhost")
+                    .header("accept", "application/json")
+                    .body(Body::empty())
+                    .unwrap();
+                let response = app.oneshot(request).await.unwrap();
+                assert_eq!(response.status(), StatusCode::OK);
+                assert_eq!(response.headers()["content-type"], "application/json");
+                let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+                serde_json::from_slice::<serde_json::Value>(&body).unwrap()
+            }
+        };
+
+        // Both representations list the same entries from the same state
+        for uri in ["/photos?delimiter=/&max-keys=1", "/photos?list-type=2&delimiter=/&max-keys=1"] {
+            let (status, xml) = send(&app, Method::GET, "localhost", uri, "").await;
+            assert_eq!(status, StatusCode::OK);
+            let json = list_json(uri).await;
+
+            assert_eq!(json["name"], "photos");
+            assert_eq!(json["is_truncated"], true);
+            assert_eq!(json["max_keys"], 1);
+            let keys: Vec<_> = json["contents"].as_array().unwrap().iter().map(|o| o["key"].clone()).collect();
+            assert_eq!(keys, xml_texts(&xml, "Key"));
+            let etags: Vec<_> = json["contents"].as_array().unwrap().iter().map(|o| o["etag"].clone()).collect();
+            assert_eq!(etags, xml_texts(&xml, "ETag"));
+            assert_eq!(json["contents"][0]["size"], 1);
+            assert_eq!(json["contents"][0]["last_modified"], xml_texts(&xml, "LastModified")[0]);
+        }
+
+        let json = list_json("/photos?delimiter=/&max-keys=1").await;
+        assert_eq!(json["next_marker"], "a.txt");
+        assert!(json.get("key_count").is_none());
+        let json = list_json("/photos?list-type=2&delimiter=/&start-after=a.txt").await;
+        assert_eq!(json["start_after"], "a.txt");
+        assert_eq!(json["key_count"], 1);
+        assert_eq!(json["common_prefixes"], serde_json::json!(["docs/"]));
+        assert!(json.get("marker").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_encoded_slashes_in_keys() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut state = AppState::for_test(dir.path()).await;
+        state.metadata.create_bucket("docs", "owner").await.unwrap();
+        let app = create_router(state.clone());
+
+        // Encoded and literal slashes name the same key
+        let (status, _) = send(&app, Method::PUT, "localhost", "/docs/reports%2F2024.csv", "data").await;
+        assert_eq!(status, StatusCode::OK);
+        for uri in ["/docs/reports/2024.csv", "/docs/reports%2f2024.csv"] {
+            let (status, body) = send(&app, Method::GET, "localhost", uri, "").await;
+            assert_eq!((status, body.as_str()), (StatusCode::OK, "data"), "{}", uri);
+        }
+        let (_, body) = send(&app, Method::GET, "localhost", "/docs?prefix=reports%2F", "").await;
+        assert!(body.contains("<Key>reports/2024.csv</Key>"));
+        // An encoded percent sign is not a slash
+        let (status, _) = send(&app, Method::PUT, "localhost", "/docs/a%252Fb", "literal").await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(state.metadata.get_object("docs", "a%2Fb").await.unwrap().is_some());
+
+        let mut config = (*state.config).clone();
+        config.encoded_slashes = crate::middleware::EncodedSlashes::Reject;
+        state.config = Arc::new(config);
+        let app = create_router(state);
+
+        let (status, body) = send(&app, Method::GET, "localhost", "/docs/reports%2F2024.csv", "").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(body.contains("<Code>InvalidURI</Code>"));
+        let (status, _) = send(&app, Method::PUT, "localhost", "/docs/x%2Fy", "data").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let (status, body) = send(&app, Method::GET, "localhost", "/docs/reports/2024.csv", "").await;
+        assert_eq!((status, body.as_str()), (StatusCode::OK, "data"));
+        let (status, _) = send(&app, Method::GET, "localhost", "/docs?prefix=reports%2F", "").await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_canned_acls_and_anonymous_reads() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut state = AppState::for_test(dir.path()).await;
+        state.metadata.create_bucket("photos", "owner").await.unwrap();
+
+        let put = |app: Router, uri: &'static str, acl: Option<&'static str>| async move {
+            let mut request = Request::builder()
+                .method(Method::PUT)
+                .uri(uri)
+                .header("host", "localhost")
+                .header("content-type", "application/json");
+            if let Some(acl) = acl {
+                request = request.header("x-amz-acl", acl);
+            }
+            let response = app.oneshot(request.body(Body::from("{}")).unwrap()).await.unwrap();
+            response.status()
+        };
+
+        // Written with auth disabled, read anonymously with it enabled
+        let writer = create_router(state.clone());
+        assert_eq!(put(writer.clone(), "/photos/private.jpg", None).await, StatusCode::OK);
+        assert_eq!(put(writer.clone(), "/photos/public.jpg", Some("public-read")).await, StatusCode::OK);
+        assert_eq!(put(writer.clone(), "/photos/bad.jpg", Some("world-readable")).await, StatusCode::BAD_REQUEST);
+        assert_eq!(put(writer.clone(), "/shared", Some("public-read")).await, StatusCode::OK);
+        assert_eq!(put(writer, "/hidden", None).await, StatusCode::OK);
+
+        let public_read = [Grant::new(Grantee::AllUsers, Permission::Read)];
+        let object = state.metadata.get_object("photos", "public.jpg").await.unwrap().unwrap();
+        assert_eq!(object.acl, public_read);
+        assert!(state.metadata.get_object("photos", "private.jpg").await.unwrap().unwrap().acl.is_empty());
+        let bucket = state.metadata.get_bucket("shared").await.unwrap().unwrap();
+        assert_eq!(bucket.access_control.acl, public_read);
+        assert!(state.metadata.get_bucket("hidden").await.unwrap().unwrap().access_control.acl.is_empty());
+
+        let mut config = (*state.config).clone();
+        config.auth_enabled = true;
+        state.config = Arc::new(config);
+        let reader = create_router(state.clone());
+
+        let (status, body) = send(&reader, Method::GET, "localhost", "/photos/private.jpg", "").await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert!(body.contains("<Code>AccessDenied</Code>"));
+        let (status, body) = send(&reader, Method::GET, "localhost", "/photos/public.jpg", "").await;
+        assert_eq!((status, body.as_str()), (StatusCode::OK, "{}"));
+        let (status, _) = send(&reader, Method::HEAD, "localhost", "/photos/public.jpg", "").await;
+        assert_eq!(status, StatusCode::OK);
+        // Only the object itself is public, not its subresources or writes
+        for uri in ["/photos/public.jpg?uploadId=x", "/photos/public.jpg?acl"] {
+            let (status, _) = send(&reader, Method::GET, "localhost", uri, "").await;
+            assert_eq!(status, StatusCode::FORBIDDEN, "{}", uri);
+        }
+        let uri = "/photos/public.jpg?response-content-type=text/plain";
+        let (status, _) = send(&reader, Method::GET, "localhost", uri, "").await;
+        assert_eq!(status, StatusCode::OK);
+        let (status, _) = send(&reader, Method::PUT, "localhost", "/photos/public.jpg", "x").await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        let (status, _) = send(&reader, Method::GET, "localhost", "/photos", "").await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+
+        // The configured default applies when no x-amz-acl is sent
+        let mut config = (*state.config).clone();
+        config.auth_enabled = false;
+        config.default_acl = CannedAcl::PublicRead;
+        state.config = Arc::new(config);
+        assert_eq!(put(create_router(state.clone()), "/photos/default.jpg", None).await, StatusCode::OK);
+        let (status, _) = send(&reader, Method::GET, "localhost", "/photos/default.jpg", "").await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_denied_object_reads() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut state = AppState::for_test(dir.path()).await;
+        state.metadata.create_bucket("photos", "owner").await.unwrap();
+        let writer = create_router(state.clone());
+        let (status, _) = send(&writer, Method::PUT, "localhost", "/photos/private.jpg", "x").await;
+        assert_eq!(status, StatusCode::OK);
+
+        for (mode, status, code) in [
+            (DeniedReads::Forbidden, StatusCode::FORBIDDEN, "AccessDenied"),
+            (DeniedReads::NotFound, StatusCode::NOT_FOUND, "NoSuchKey"),
+        ] {
+            let mut config = (*state.config).clone();
+            config.auth_enabled = true;
+            config.denied_object_reads = mode;
+            state.config = Arc::new(config);
+            let reader = create_router(state.clone());
+
+            // Existing and missing objects are refused alike
+            for uri in ["/photos/private.jpg", "/photos/missing.jpg", "/absent/private.jpg"] {
+                let (actual, body) = send(&reader, Method::GET, "localhost", uri, "").await;
+                assert_eq!(actual, status, "{:?} {}", mode, uri);
+                assert!(body.contains(&format!("<Code>{}</Code>", code)), "{}", body);
+                let (actual, _) = send(&reader, Method::HEAD, "localhost", uri, "").await;
+                assert_eq!(actual, status, "{:?} {}", mode, uri);
+            }
+
+            // Only anonymous object reads are affected
+            let (actual, _) = send(&reader, Method::PUT, "localhost", "/photos/private.jpg", "y").await;
+            assert_eq!(actual, StatusCode::FORBIDDEN);
+            let (actual, _) = send(&reader, Method::GET, "localhost", "/photos/private.jpg?acl", "").await;
+            assert_eq!(actual, StatusCode::FORBIDDEN);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_read_only_mode() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut state = AppState::for_test(dir.path()).await;
+        let mut config = (*state.config).clone();
+        config.read_only_retry_after = 120;
+        state.config = Arc::new(config);
+        state.metadata.create_bucket("photos", "owner").await.unwrap();
+        let app = create_router(state.clone());
+        let (status, _) = send(&app, Method::PUT, "localhost", "/photos/cat.jpg", "meow").await;
+        assert_eq!(status, StatusCode::OK);
+
+        let set_read_only = |enabled: bool| {
+            Request::builder()
+                .method(Method::PUT)
+                .uri("/_admin/read-only")
+                .header("content-type", "application/json")
+                .body(Body::from(format!("{{\"enabled\":{}}}", enabled)))
+                .unwrap()
+        };
+        let response = app.clone().oneshot(set_read_only(true)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let (status, body) = send(&app, Method::GET, "localhost", "/_admin/read-only", "").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, r#"{"enabled":true}"#);
+
+        // Reads and listings carry on
+        let (status, body) = send(&app, Method::GET, "localhost", "/photos/cat.jpg", "").await;
+        assert_eq!((status, body.as_str()), (StatusCode::OK, "meow"));
+        let (status, _) = send(&app, Method::HEAD, "localhost", "/photos/cat.jpg", "").await;
+        assert_eq!(status, StatusCode::OK);
+        let (status, body) = send(&app, Method::GET, "localhost", "/photos", "").await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.contains("<Key>cat.jpg</Key>"), "{}", body);
+
+        // Writes are refused until the mode is lifted
+        for (method, uri) in [
+            (Method::PUT, "/photos/dog.jpg"),
+            (Method::DELETE, "/photos/cat.jpg"),
+            (Method::POST, "/photos/big.bin?uploads"),
+            (Method::PUT, "/videos"),
+        ] {
+            let request = Request::builder().method(method.clone()).uri(uri).body(Body::from("woof")).unwrap();
+            let response = app.clone().oneshot(request).await.unwrap();
+            assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE, "{} {}", method, uri);
+            assert_eq!(response.headers()[header::RETRY_AFTER], "120");
+            let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+            assert!(String::from_utf8_lossy(&body).contains("<Code>ServiceUnavailable</Code>"));
+        }
+        let (status, _) = send(&app, Method::GET, "localhost", "/photos/cat.jpg", "").await;
+        assert_eq!(status, StatusCode::OK);
+
+        let response = app.clone().oneshot(set_read_only(false)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let (status, _) = send(&app, Method::PUT, "localhost", "/photos/dog.jpg", "woof").await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_concurrency_limit() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut state = AppState::for_test(dir.path()).await;
+        let mut config = (*state.config).clone();
+        config.max_concurrent_requests = 2;
+        state.config = Arc::new(config);
+        state.metadata.create_bucket("photos", "owner").await.unwrap();
+        let app = create_router(state.clone());
+
+        // Uploads whose bodies are still arriving hold their slots
+        let mut senders = Vec::new();
+        let mut uploads = Vec::new();
+        for key in ["a.jpg", "b.jpg"] {
+            let (sender, body) = futures::channel::mpsc::channel::<std::result::Result<Bytes, std::io::Error>>(1);
+            let request = Request::builder()
+                .method(Method::PUT)
+                .uri(format!("/photos/{}", key))
+                .body(Body::from_stream(body))
+                .unwrap();
+            senders.push(sender);
+            uploads.push(tokio::spawn(app.clone().oneshot(request)));
+        }
+        for _ in 0..200 {
+            if state.in_flight.count() == 2 {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        assert_eq!(state.in_flight.count(), 2);
+
+        let request = Request::builder().uri("/photos/a.jpg").body(Body::empty()).unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(response.headers()[header::RETRY_AFTER], "1");
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert!(String::from_utf8_lossy(&body).contains("<Code>SlowDown</Code>"));
+
+        // Slots are freed as requests finish
+        drop(senders);
+        for upload in uploads {
+            assert_eq!(upload.await.unwrap().unwrap().status(), StatusCode::OK);
+        }
+        let (status, _) = send(&app, Method::GET, "localhost", "/photos/a.jpg", "").await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_oversized_headers_are_refused() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut state = AppState::for_test(dir.path()).await;
+        let mut config = (*state.config).clone();
+        config.auth_enabled = true;
+        config.max_header_size = 1024;
+        state.config = Arc::new(config);
+        let app = create_router(state);
+
+        let request = |headers: &[(&str, String)]| {
+            let mut request = Request::builder().uri("/photos/cat.jpg").header("host", "localhost");
+            for (name, value) in headers {
+                request = request.header(*name, value);
+            }
+            app.clone().oneshot(request.body(Body::empty()).unwrap())
+        };
+
+        // An enormous Authorization header, or many small headers, are over the limit
+        let authorization = format!(
+            "AWS4-HMAC-SHA256 Credential=AKID/20230101/us-east-1/s3/aws4_request, SignedHeaders={}, Signature=00",
+            "x-amz-date;".repeat(100)
+        );
+        let many: Vec<_> = (0..50).map(|i| ("x-amz-meta-padding", format!("value-{}", i))).collect();
+        for headers in [vec![("authorization", authorization)], many] {
+            let response = request(&headers).await.unwrap();
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+            let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+            assert!(String::from_utf8_lossy(&body).contains("<Code>RequestHeaderSectionTooLarge</Code>"));
+        }
+
+        // Within the limit the request reaches authentication
+        let response = request(&[]).await.unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    }
+
+    #[tokio::test]
+    async fn test_response_compression() {
+        use std::io::Read;
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut state = AppState::for_test(dir.path()).await;
+        let mut config = (*state.config).clone();
+        config.compression_enabled = true;
+        config.compression_min_size = 64;
+        state.config = Arc::new(config);
+        state.metadata.create_bucket("photos", "owner").await.unwrap();
+        let app = create_router(state);
+
+        let text = "the quick brown fox jumps over the lazy dog\n".repeat(100);
+        for (key, content_type, encoding) in [
+            ("notes.txt", "text/plain; charset=utf-8", None),
+            ("photo.bin", "application/octet-stream", None),
+            ("notes.txt.gz", "text/plain", Some("gzip")),
+        ] {
+            let mut request = Request::builder()
+                .method(Method::PUT)
+                .uri(format!("/photos/{}", key))
+                .header(header::CONTENT_TYPE, content_type);
+            if let Some(encoding) = encoding {
+                request = request.header(header::CONTENT_ENCODING, encoding);
+            }
+            let response = app.clone().oneshot(request.body(Body::from(text.clone())).unwrap()).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+        }
+
+        let get = |uri: &str, headers: &[(header::HeaderName, &str)]| {
+            let mut request = Request::builder().uri(uri);
+            for (name, value) in headers {
+                request = request.header(name, *value);
+            }
+            app.clone().oneshot(request.body(Body::empty()).unwrap())
+        };
+        let gzip = [(header::ACCEPT_ENCODING, "gzip")];
+
+        // A listing is gzip-encoded when asked for, and plain otherwise
+        let response = get("/photos", &gzip).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[header::CONTENT_ENCODING], "gzip");
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let mut listing = String::new();
+        flate2::read::GzDecoder::new(&body[..]).read_to_string(&mut listing).unwrap();
+        assert!(listing.contains("<Key>notes.txt</Key>"), "{}", listing);
+        let response = get("/photos", &[]).await.unwrap();
+        assert!(!response.headers().contains_key(header::CONTENT_ENCODING));
+
+        // Text objects are compressed, with brotli if preferred
+        let response = get("/photos/notes.txt", &[(header::ACCEPT_ENCODING, "br")]).await.unwrap();
+        assert_eq!(response.headers()[header::CONTENT_ENCODING], "br");
+        let response = get("/photos/notes.txt", &gzip).await.unwrap();
+        assert_eq!(response.headers()[header::CONTENT_ENCODING], "gzip");
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert!(body.len() < text.len());
+
+        // Binary and already compressed objects are sent as stored
+        let response = get("/photos/photo.bin", &gzip).await.unwrap();
+        assert!(!response.headers().contains_key(header::CONTENT_ENCODING));
+        let response = get("/photos/notes.txt.gz", &gzip).await.unwrap();
+        assert_eq!(response.headers()[header::CONTENT_ENCODING], "gzip");
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(body, text.as_bytes());
+
+        // Ranges refer to the stored bytes
+        let response = get("/photos/notes.txt", &[gzip[0].clone(), (header::RANGE, "bytes=4-8")]).await.unwrap();
+        assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+        assert!(!response.headers().contains_key(header::CONTENT_ENCODING));
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(&body[..], b"quick");
+        let response = get("/photos/notes.txt?verify=true", &[gzip[0].clone(), (header::RANGE, "bytes=4-8")])
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(!response.headers().contains_key(header::CONTENT_ENCODING));
+    }
 }
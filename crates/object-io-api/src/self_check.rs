@@ -0,0 +1,245 @@
+//! Startup self-check
+//!
+//! Validates the configuration and the subsystems it points at before the
+//! server binds its port, reporting every problem at once rather than
+//! failing on the first request that touches a broken subsystem.
+
+use object_io_core::ObjectIOError;
+use object_io_storage::StorageConfig;
+use serde::Serialize;
+use std::collections::HashSet;
+use std::fmt;
+use std::path::Path;
+
+use crate::config::ServerConfig;
+
+/// Part of the server a problem was found in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Subsystem {
+    Config,
+    Storage,
+    Database,
+}
+
+impl fmt::Display for Subsystem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Subsystem::Config => "config",
+            Subsystem::Storage => "storage",
+            Subsystem::Database => "database",
+        })
+    }
+}
+
+/// A problem that would stop the server working
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Problem {
+    pub subsystem: Subsystem,
+    pub message: String,
+}
+
+/// Every problem the self-check found
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct SelfCheckReport {
+    pub problems: Vec<Problem>,
+}
+
+impl SelfCheckReport {
+    /// Whether no problems were found
+    pub fn is_ok(&self) -> bool {
+        self.problems.is_empty()
+    }
+
+    fn add(&mut self, subsystem: Subsystem, message: impl Into<String>) {
+        self.problems.push(Problem {
+            subsystem,
+            message: message.into(),
+        });
+    }
+
+    /// Problems found in `subsystem`
+    pub fn problems_in(&self, subsystem: Subsystem) -> impl Iterator<Item = &Problem> {
+        self.problems.iter().filter(move |problem| problem.subsystem == subsystem)
+    }
+
+    /// Fail with a configuration error listing every problem, if any were found
+    pub fn into_result(self) -> object_io_core::Result<()> {
+        if self.is_ok() {
+            return Ok(());
+        }
+        Err(ObjectIOError::ConfigurationError {
+            message: self.to_string(),
+        })
+    }
+}
+
+impl fmt::Display for SelfCheckReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} startup problem(s):", self.problems.len())?;
+        for problem in &self.problems {
+            write!(f, "\n  [{}] {}", problem.subsystem, problem.message)?;
+        }
+        Ok(())
+    }
+}
+
+/// Check that `dir` exists or can be created, and that files can be written in it
+async fn check_writable_dir(dir: &Path) -> Result<(), String> {
+    tokio::fs::create_dir_all(dir)
+        .await
+        .map_err(|e| format!("cannot create directory {}: {}", dir.display(), e))?;
+    let probe = dir.join(format!(".objectio-self-check-{}", uuid::Uuid::new_v4().simple()));
+    tokio::fs::write(&probe, b"")
+        .await
+        .map_err(|e| format!("cannot write to {}: {}", dir.display(), e))?;
+    let _ = tokio::fs::remove_file(&probe).await;
+    Ok(())
+}
+
+/// Values the configuration cannot work with
+fn check_config(config: &ServerConfig, report: &mut SelfCheckReport) {
+    if config.host.trim().is_empty() {
+        report.add(Subsystem::Config, "host is empty");
+    }
+    if config.max_body_size == 0 {
+        report.add(Subsystem::Config, "max_body_size is 0, so no object could be uploaded");
+    }
+    if config.max_list_keys == 0 {
+        report.add(Subsystem::Config, "max_list_keys is 0, so listings could return no keys");
+    }
+    if config.request_timeout == 0 {
+        report.add(Subsystem::Config, "request_timeout is 0, so every request would time out");
+    }
+    if config.virtual_host_domain.as_deref().is_some_and(|domain| domain.trim().is_empty()) {
+        report.add(Subsystem::Config, "virtual_host_domain is empty; leave it unset for path-style only");
+    }
+    let mut seen = HashSet::new();
+    for rate_limit in &config.rate_limit_overrides {
+        if !seen.insert(rate_limit.user_id.as_str()) {
+            report.add(
+                Subsystem::Config,
+                format!("rate_limit_overrides lists user '{}' more than once", rate_limit.user_id),
+            );
+        }
+    }
+}
+
+async fn check_storage(config: &ServerConfig, report: &mut SelfCheckReport) {
+    let storage = match config.storage_config().and_then(|storage| storage.validate().map(|()| storage)) {
+        Ok(storage) => storage,
+        Err(e) => return report.add(Subsystem::Storage, e.to_string()),
+    };
+    if let StorageConfig::Filesystem { root_path, temp_path, .. } = storage {
+        if let Err(message) = check_writable_dir(Path::new(&root_path)).await {
+            report.add(Subsystem::Storage, format!("storage_path: {}", message));
+        }
+        if let Some(temp_path) = temp_path {
+            if let Err(message) = check_writable_dir(Path::new(&temp_path)).await {
+                report.add(Subsystem::Storage, format!("storage_temp_path: {}", message));
+            }
+        }
+    }
+}
+
+async fn check_replication(config: &ServerConfig, report: &mut SelfCheckReport) {
+    let Some(replication) = &config.replication else {
+        return;
+    };
+    let storage = match replication.storage_config() {
+        Ok(storage) => storage,
+        Err(e) => return report.add(Subsystem::Storage, format!("replication: {}", e)),
+    };
+    if let StorageConfig::Filesystem { root_path, .. } = storage {
+        if let Err(message) = check_writable_dir(Path::new(&root_path)).await {
+            report.add(Subsystem::Storage, format!("replication path: {}", message));
+        }
+    }
+    if let Some(parent) = replication.cursor_path.as_deref().and_then(|path| Path::new(path).parent()) {
+        if let Err(message) = check_writable_dir(parent).await {
+            report.add(Subsystem::Storage, format!("replication cursor_path: {}", message));
+        }
+    }
+}
+
+async fn check_database(config: &ServerConfig, report: &mut SelfCheckReport) {
+    let path = Path::new(&config.database_path);
+    if config.database_path.trim().is_empty() {
+        return report.add(Subsystem::Database, "database_path is empty");
+    }
+    // The database is a directory, created on first start
+    if path.is_file() {
+        return report.add(
+            Subsystem::Database,
+            format!("database_path {} is a file, not a database directory", path.display()),
+        );
+    }
+    let dir = if path.exists() { path } else { path.parent().unwrap_or(Path::new(".")) };
+    if let Err(message) = check_writable_dir(dir).await {
+        report.add(Subsystem::Database, format!("database_path: {}", message));
+    }
+}
+
+/// Check the configuration and every subsystem it configures
+///
+/// Storage and database directories are created if missing, as the server
+/// would on startup.
+pub async fn self_check(config: &ServerConfig) -> SelfCheckReport {
+    let mut report = SelfCheckReport::default();
+    check_config(config, &mut report);
+    check_storage(config, &mut report).await;
+    check_replication(config, &mut report).await;
+    check_database(config, &mut report).await;
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::UserRateLimit;
+
+    #[tokio::test]
+    async fn test_self_check() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = ServerConfig {
+            database_path: dir.path().join("db").to_string_lossy().into_owned(),
+            storage_path: dir.path().join("storage").to_string_lossy().into_owned(),
+            ..ServerConfig::default()
+        };
+        let report = self_check(&config).await;
+        assert!(report.is_ok(), "{}", report);
+        assert!(report.into_result().is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_self_check_reports_every_problem() {
+        let dir = tempfile::tempdir().unwrap();
+        // A file where directories are expected
+        let blocker = dir.path().join("blocker");
+        std::fs::write(&blocker, b"").unwrap();
+        let override_for = |user_id: &str| UserRateLimit {
+            user_id: user_id.to_string(),
+            per_second: 10,
+            burst: 0,
+        };
+        let config = ServerConfig {
+            database_path: blocker.to_string_lossy().into_owned(),
+            storage_path: blocker.join("storage").to_string_lossy().into_owned(),
+            storage_temp_path: Some(blocker.join("tmp").to_string_lossy().into_owned()),
+            max_list_keys: 0,
+            rate_limit_overrides: vec![override_for("batch"), override_for("batch")],
+            ..ServerConfig::default()
+        };
+
+        let report = self_check(&config).await;
+        assert_eq!(report.problems_in(Subsystem::Config).count(), 2, "{}", report);
+        assert_eq!(report.problems_in(Subsystem::Storage).count(), 2, "{}", report);
+        assert_eq!(report.problems_in(Subsystem::Database).count(), 1, "{}", report);
+
+        let message = report.into_result().unwrap_err().to_string();
+        assert!(message.contains("5 startup problem(s)"), "{}", message);
+        for expected in ["max_list_keys", "'batch'", "storage_path:", "storage_temp_path:", "is a file"] {
+            assert!(message.contains(expected), "{} missing from {}", expected, message);
+        }
+    }
+}
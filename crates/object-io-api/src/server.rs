@@ -0,0 +1,302 @@
+//! HTTP/1.1 connection handling with timeouts against slow clients
+//!
+//! `axum::serve` has no way to limit how long a client may take to send its
+//! headers or read its response, so connections are served with hyper
+//! directly.
+
+use axum::{extract::ConnectInfo, Router};
+use hyper::body::Incoming;
+use hyper_util::rt::{TokioIo, TokioTimer};
+use hyper_util::server::graceful::GracefulShutdown;
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::time::Sleep;
+use tower::Service;
+use tracing::{debug, warn};
+
+use crate::config::ServerConfig;
+
+/// Serve `app` on `listener` until `signal` completes, then wait for open
+/// connections to finish their current requests
+///
+/// Each request carries the client address as `ConnectInfo<SocketAddr>`.
+/// Connections are closed when the client takes longer than the configured
+/// header read timeout to send request headers, or leaves a response unread
+/// for longer than the body idle timeout. Header sections over twice the
+/// configured limit are refused by hyper with `431` while they are read.
+pub async fn serve<F>(listener: TcpListener, app: Router, config: &ServerConfig, signal: F) -> io::Result<()>
+where
+    F: Future<Output = ()>,
+{
+    let header_read_timeout = timeout(config.header_read_timeout);
+    let write_idle_timeout = timeout(config.body_idle_timeout);
+    let graceful = GracefulShutdown::new();
+    tokio::pin!(signal);
+
+    loop {
+        let (stream, addr) = tokio::select! {
+            accepted = listener.accept() => match accepted {
+                Ok(accepted) => accepted,
+                Err(e) => {
+                    // Usually out of file descriptors; back off rather than spin
+                    warn!("Failed to accept connection: {}", e);
+                    tokio::time::sleep(Duration::from_secs(1)).await;
+                    continue;
+                }
+            },
+            () = &mut signal => break,
+        };
+
+        let app = app.clone();
+        let service = hyper::service::service_fn(move |mut request: hyper::Request<Incoming>| {
+            request.extensions_mut().insert(ConnectInfo(addr));
+            app.clone().call(request)
+        });
+
+        let io = TokioIo::new(WriteTimeoutStream::new(stream, write_idle_timeout));
+        let mut builder = hyper::server::conn::http1::Builder::new();
+        builder.timer(TokioTimer::new()).header_read_timeout(header_read_timeout);
+        if let Some(size) = read_buffer_size(config.max_header_size) {
+            builder.max_buf_size(size);
+        }
+        let connection = builder.serve_connection(io, service);
+        let connection = graceful.watch(connection);
+        tokio::spawn(async move {
+            if let Err(e) = connection.await {
+                debug!("Connection from {} closed: {}", addr, e);
+            }
+        });
+    }
+
+    drop(listener);
+    graceful.shutdown().await;
+    Ok(())
+}
+
+/// A timeout in seconds from the config, where 0 means none
+fn timeout(seconds: u64) -> Option<Duration> {
+    (seconds > 0).then(|| Duration::from_secs(seconds))
+}
+
+/// Largest read buffer hyper may grow to, which holds the whole header
+/// section while it is parsed
+///
+/// Twice the header limit, so sections just over it still reach the
+/// middleware that refuses them with an S3 error, but never below 64KB so
+/// upload bodies are still read in large chunks. Hyper's default (about
+/// 400KB) applies when there is no limit.
+fn read_buffer_size(max_header_size: usize) -> Option<usize> {
+    (max_header_size > 0).then(|| max_header_size.saturating_mul(2).max(64 * 1024))
+}
+
+/// TCP stream whose writes fail once they have been blocked for too long,
+/// so a client that stops reading its response cannot hold the connection
+struct WriteTimeoutStream {
+    inner: TcpStream,
+    idle: Option<Duration>,
+    blocked: Option<Pin<Box<Sleep>>>,
+}
+
+impl WriteTimeoutStream {
+    fn new(inner: TcpStream, idle: Option<Duration>) -> Self {
+        Self {
+            inner,
+            idle,
+            blocked: None,
+        }
+    }
+
+    /// Track how long writes have been blocked, given the result of the
+    /// latest attempt
+    fn check<T>(&mut self, cx: &mut Context<'_>, poll: Poll<io::Result<T>>) -> Poll<io::Result<T>> {
+        if poll.is_ready() {
+            self.blocked = None;
+            return poll;
+        }
+        let Some(idle) = self.idle else {
+            return poll;
+        };
+
+        let blocked = self.blocked.get_or_insert_with(|| Box::pin(tokio::time::sleep(idle)));
+        match blocked.as_mut().poll(cx) {
+            Poll::Ready(()) => Poll::Ready(Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                "client stopped reading the response",
+            ))),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+impl AsyncRead for WriteTimeoutStream {
+    fn poll_read(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for WriteTimeoutStream {
+    fn poll_write(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        let poll = Pin::new(&mut self.inner).poll_write(cx, buf);
+        self.check(cx, poll)
+    }
+
+    fn poll_write_vectored(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[io::IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        let poll = Pin::new(&mut self.inner).poll_write_vectored(cx, bufs);
+        self.check(cx, poll)
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        self.inner.is_write_vectored()
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let poll = Pin::new(&mut self.inner).poll_flush(cx);
+        self.check(cx, poll)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::routing::get;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    #[tokio::test]
+    async fn test_header_read_timeout() {
+        let config = ServerConfig {
+            header_read_timeout: 1,
+            ..ServerConfig::default()
+        };
+        let app = Router::new().route(
+            "/",
+            get(|ConnectInfo(addr): ConnectInfo<std::net::SocketAddr>| async move { addr.ip().to_string() }),
+        );
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (stop_tx, stop_rx) = tokio::sync::oneshot::channel::<()>();
+        let server = tokio::spawn(async move {
+            serve(listener, app, &config, async move {
+                let _ = stop_rx.await;
+            })
+            .await
+        });
+
+        // A complete request is answered, with the client address available
+        let mut client = TcpStream::connect(addr).await.unwrap();
+        client.write_all(b"GET / HTTP/1.1\r\nHost: test\r\nConnection: close\r\n\r\n").await.unwrap();
+        let mut response = String::new();
+        client.read_to_string(&mut response).await.unwrap();
+        assert!(response.starts_with("HTTP/1.1 200"));
+        assert!(response.ends_with("127.0.0.1"));
+
+        // A client that never finishes its headers is disconnected
+        let mut client = TcpStream::connect(addr).await.unwrap();
+        client.write_all(b"GET / HTTP/1.1\r\nHost: te").await.unwrap();
+        let mut response = Vec::new();
+        let read = tokio::time::timeout(Duration::from_secs(5), client.read_to_end(&mut response)).await;
+        assert!(read.is_ok(), "connection was not closed");
+
+        stop_tx.send(()).unwrap();
+        server.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_oversized_header_section() {
+        let config = ServerConfig {
+            max_header_size: 1024,
+            ..ServerConfig::default()
+        };
+        let app = Router::new().route("/", get(|| async { "ok" }));
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (stop_tx, stop_rx) = tokio::sync::oneshot::channel::<()>();
+        let server = tokio::spawn(async move {
+            serve(listener, app, &config, async move {
+                let _ = stop_rx.await;
+            })
+            .await
+        });
+
+        // Refused while being read, long before the whole section arrives
+        let mut client = TcpStream::connect(addr).await.unwrap();
+        let authorization = format!("Authorization: AWS4-HMAC-SHA256 {}\r\n", "x".repeat(1024 * 1024));
+        let request = format!("GET / HTTP/1.1\r\nHost: test\r\n{}\r\n", authorization);
+        let _ = client.write_all(request.as_bytes()).await;
+        let mut response = Vec::new();
+        let _ = tokio::time::timeout(Duration::from_secs(5), client.read_to_end(&mut response)).await;
+        assert!(String::from_utf8_lossy(&response).starts_with("HTTP/1.1 431"));
+
+        stop_tx.send(()).unwrap();
+        server.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_chunked_object_response() {
+        use std::io::Write;
+
+        let dir = tempfile::tempdir().unwrap();
+        let state = crate::state::AppState::for_test(dir.path()).await;
+        state.metadata.create_bucket("logs", "owner").await.unwrap();
+        let text = "line of log output\n".repeat(50_000);
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(text.as_bytes()).unwrap();
+        let metadata = std::collections::HashMap::from([("content-encoding".to_string(), "gzip".to_string())]);
+        let reader = Box::new(std::io::Cursor::new(encoder.finish().unwrap()));
+        crate::handlers::object::store_object(&state, "logs", "app.log", reader, metadata).await.unwrap();
+
+        let app = crate::routes::create_router(state);
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (stop_tx, stop_rx) = tokio::sync::oneshot::channel::<()>();
+        let server = tokio::spawn(async move {
+            serve(listener, app, &ServerConfig::default(), async move {
+                let _ = stop_rx.await;
+            })
+            .await
+        });
+
+        let mut client = TcpStream::connect(addr).await.unwrap();
+        let request = "GET /logs/app.log?decompress=true HTTP/1.1\r\nHost: test\r\nConnection: close\r\n\r\n";
+        client.write_all(request.as_bytes()).await.unwrap();
+        let mut response = Vec::new();
+        client.read_to_end(&mut response).await.unwrap();
+
+        // The decoded length isn't known up front, so the body is chunked
+        let split = response.windows(4).position(|window| window == b"\r\n\r\n").unwrap();
+        let head = String::from_utf8_lossy(&response[..split]).to_ascii_lowercase();
+        assert!(head.starts_with("http/1.1 200"), "{}", head);
+        assert!(head.contains("transfer-encoding: chunked"), "{}", head);
+        assert!(!head.contains("content-length"), "{}", head);
+        assert!(!head.contains("content-encoding"), "{}", head);
+
+        let mut rest = &response[split + 4..];
+        let mut body = Vec::new();
+        loop {
+            let line_end = rest.windows(2).position(|window| window == b"\r\n").unwrap();
+            let size = usize::from_str_radix(std::str::from_utf8(&rest[..line_end]).unwrap(), 16).unwrap();
+            rest = &rest[line_end + 2..];
+            if size == 0 {
+                break;
+            }
+            body.extend_from_slice(&rest[..size]);
+            rest = &rest[size + 2..];
+        }
+        assert_eq!(body, text.as_bytes());
+
+        stop_tx.send(()).unwrap();
+        server.await.unwrap().unwrap();
+    }
+}
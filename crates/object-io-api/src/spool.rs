@@ -0,0 +1,113 @@
+//! Buffering of upload bodies before they are stored
+//!
+//! A body is read in full before the object is written, so a slow client
+//! never holds the object's lock and its size is known up front. Bodies up
+//! to a threshold stay in memory; larger ones spill to an anonymous
+//! temporary file, which the operating system removes once it is closed.
+
+use std::io::{Cursor, SeekFrom};
+use std::path::{Path, PathBuf};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
+
+/// An upload body read ahead of storing it
+pub enum SpooledBody {
+    /// The whole body, held in memory
+    Memory(Vec<u8>),
+    /// A body larger than the threshold, in a temporary file
+    File { file: tokio::fs::File, size: u64 },
+}
+
+impl SpooledBody {
+    /// Read all of `reader`, keeping up to `threshold` bytes in memory and
+    /// spilling to a temporary file in `temp_dir` beyond that
+    pub async fn read(
+        mut reader: Box<dyn AsyncRead + Send + Unpin>,
+        threshold: usize,
+        temp_dir: &Path,
+    ) -> std::io::Result<Self> {
+        // One byte past the threshold tells whether the body fits
+        let mut buffer = Vec::new();
+        (&mut reader).take(threshold as u64 + 1).read_to_end(&mut buffer).await?;
+        if buffer.len() <= threshold {
+            return Ok(SpooledBody::Memory(buffer));
+        }
+
+        let dir = temp_dir.to_path_buf();
+        let file = tokio::task::spawn_blocking(move || {
+            std::fs::create_dir_all(&dir)?;
+            tempfile::tempfile_in(&dir)
+        })
+        .await
+        .map_err(std::io::Error::other)??;
+        let mut file = tokio::fs::File::from_std(file);
+        file.write_all(&buffer).await?;
+        let size = buffer.len() as u64 + tokio::io::copy(&mut reader, &mut file).await?;
+        file.flush().await?;
+        file.seek(SeekFrom::Start(0)).await?;
+        Ok(SpooledBody::File { file, size })
+    }
+
+    /// Size of the body in bytes
+    pub fn size(&self) -> u64 {
+        match self {
+            SpooledBody::Memory(buffer) => buffer.len() as u64,
+            SpooledBody::File { size, .. } => *size,
+        }
+    }
+
+    /// Whether the body was small enough to stay in memory
+    pub fn in_memory(&self) -> bool {
+        matches!(self, SpooledBody::Memory(_))
+    }
+
+    /// Reader over the body from its start
+    pub fn into_reader(self) -> Box<dyn AsyncRead + Send + Unpin> {
+        match self {
+            SpooledBody::Memory(buffer) => Box::new(Cursor::new(buffer)),
+            SpooledBody::File { file, .. } => Box::new(file),
+        }
+    }
+}
+
+/// Directory upload bodies spill to: `temp_path` when configured, otherwise
+/// the system's temporary directory
+pub fn spool_dir(temp_path: Option<&str>) -> PathBuf {
+    temp_path.map(PathBuf::from).unwrap_or_else(std::env::temp_dir)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn read_back(body: SpooledBody) -> Vec<u8> {
+        let mut data = Vec::new();
+        body.into_reader().read_to_end(&mut data).await.unwrap();
+        data
+    }
+
+    #[tokio::test]
+    async fn test_small_body_stays_in_memory() {
+        let dir = tempfile::tempdir().unwrap();
+        let data = b"small body".to_vec();
+        let body = SpooledBody::read(Box::new(Cursor::new(data.clone())), data.len(), dir.path())
+            .await
+            .unwrap();
+        assert!(body.in_memory());
+        assert_eq!(body.size(), data.len() as u64);
+        assert_eq!(read_back(body).await, data);
+    }
+
+    #[tokio::test]
+    async fn test_large_body_spools_to_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let data: Vec<u8> = (0..100_000u32).map(|i| i as u8).collect();
+        let body = SpooledBody::read(Box::new(Cursor::new(data.clone())), 4096, dir.path())
+            .await
+            .unwrap();
+        assert!(!body.in_memory());
+        assert_eq!(body.size(), data.len() as u64);
+        assert_eq!(read_back(body).await, data);
+        // The temporary file has no name to leave behind
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 0);
+    }
+}
@@ -1,69 +1,51 @@
 //! Application state and configuration
 
-use object_io_metadata::{Database, MetadataOperations};
-use object_io_storage::{filesystem::FilesystemStorage, Storage};
+use chrono::{DateTime, Utc};
+use object_io_core::{ListObjectsRequest, MultipartUpload, ObjectIOError};
+use object_io_metadata::{Database, MetadataOperations, MetadataStore};
+use object_io_storage::{multipart, scrub::{self, ScrubReport}, KeyLocks, Storage};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
+use tracing::info;
+
+use crate::{
+    config::ServerConfig,
+    idempotency::IdempotencyCache,
+    middleware::{ByteQuota, InFlightRequests, RateLimiter},
+};
 
 /// Application state shared across handlers
 #[derive(Clone)]
 pub struct AppState {
-    /// Database operations
-    pub metadata: Arc<MetadataOperations>,
+    /// Metadata store
+    pub metadata: Arc<dyn MetadataStore>,
     /// Storage backend
     pub storage: Arc<dyn Storage>,
     /// Server configuration
     pub config: Arc<ServerConfig>,
-}
-
-/// Server configuration
-#[derive(Debug, Clone)]
-pub struct ServerConfig {
-    /// Database path
-    pub database_path: String,
-    /// Storage root path
-    pub storage_path: String,
-    /// Default region
-    pub default_region: String,
-    /// Maximum request body size
-    pub max_body_size: usize,
-    /// Request timeout in seconds
-    pub request_timeout: u64,
-}
-
-impl Default for ServerConfig {
-    fn default() -> Self {
-        Self {
-            database_path: std::env::var("DATABASE_PATH")
-                .unwrap_or_else(|_| "./data/objectio.db".to_string()),
-            storage_path: std::env::var("STORAGE_PATH")
-                .unwrap_or_else(|_| "./data/storage".to_string()),
-            default_region: std::env::var("DEFAULT_REGION")
-                .unwrap_or_else(|_| "us-east-1".to_string()),
-            max_body_size: std::env::var("MAX_BODY_SIZE")
-                .unwrap_or_else(|_| "5368709120".to_string()) // 5GB
-                .parse()
-                .unwrap_or(5 * 1024 * 1024 * 1024),
-            request_timeout: std::env::var("REQUEST_TIMEOUT")
-                .unwrap_or_else(|_| "30".to_string())
-                .parse()
-                .unwrap_or(30),
-        }
-    }
+    /// Per-object locks held while an object is written or conditionally deleted
+    pub locks: Arc<KeyLocks>,
+    /// Requests currently being handled
+    pub in_flight: InFlightRequests,
+    /// Per-client request rate limiter
+    pub rate_limiter: Arc<RateLimiter>,
+    /// Bytes transferred today by each anonymous client
+    pub anonymous_quota: Arc<ByteQuota>,
+    /// Recent PUTs made with an idempotency key
+    pub idempotency: Arc<IdempotencyCache>,
+    /// Whether writes are refused (maintenance mode), starting from the
+    /// configured `read_only`
+    pub read_only: Arc<AtomicBool>,
 }
 
 impl AppState {
-    /// Create new application state
-    pub async fn new() -> object_io_core::Result<Self> {
-        let config = Arc::new(ServerConfig::default());
-        
-        // Ensure storage directory exists
-        tokio::fs::create_dir_all(&config.storage_path).await
-            .map_err(|e| object_io_core::ObjectIOError::IO(e))?;
-        
+    /// Create new application state around an already-constructed storage
+    /// backend, opening the embedded metadata database at `database_path`
+    pub async fn new(config: ServerConfig, storage: Arc<dyn Storage>) -> object_io_core::Result<Self> {
         // Ensure database directory exists
         if let Some(parent) = std::path::Path::new(&config.database_path).parent() {
             tokio::fs::create_dir_all(parent).await
-                .map_err(|e| object_io_core::ObjectIOError::IO(e))?;
+                .map_err(object_io_core::ObjectIOError::IO)?;
         }
         
         // Initialize database
@@ -72,13 +54,249 @@ impl AppState {
         
         let metadata = Arc::new(MetadataOperations::new(database));
         
-        // Initialize filesystem storage backend
-        let storage = Arc::new(FilesystemStorage::new(&config.storage_path).await?) as Arc<dyn Storage>;
-        
-        Ok(Self {
+        Ok(Self::with_metadata(config, storage, metadata))
+    }
+
+    /// Create new application state around already-constructed storage and
+    /// metadata backends
+    pub fn with_metadata(config: ServerConfig, storage: Arc<dyn Storage>, metadata: Arc<dyn MetadataStore>) -> Self {
+        Self {
+            read_only: Arc::new(AtomicBool::new(config.read_only)),
             metadata,
             storage,
-            config,
-        })
+            config: Arc::new(config),
+            locks: Arc::new(KeyLocks::new()),
+            in_flight: InFlightRequests::default(),
+            rate_limiter: Arc::new(RateLimiter::new()),
+            anonymous_quota: Arc::new(ByteQuota::new()),
+            idempotency: Arc::new(IdempotencyCache::new()),
+        }
+    }
+
+    /// Abort multipart uploads older than the configured maximum age as of `now`
+    ///
+    /// Nothing is aborted in read-only mode, which covers background work as
+    /// well as requests.
+    pub async fn sweep_abandoned_uploads(&self, now: DateTime<Utc>) -> object_io_core::Result<Vec<MultipartUpload>> {
+        if self.read_only.load(Ordering::Relaxed) {
+            return Ok(Vec::new());
+        }
+        let buckets = self.metadata.list_bucket_names().await?;
+        let max_age = chrono::Duration::seconds(self.config.multipart_upload_max_age as i64);
+        multipart::abort_incomplete_uploads(self.storage.as_ref(), &buckets, max_age, now).await
+    }
+
+    /// Re-hash the objects of `bucket` in key order, starting after `marker`,
+    /// and compare them against their recorded ETags
+    ///
+    /// Stops at `deadline`, leaving the report incomplete with a marker to
+    /// resume from; an object still being read at the deadline is checked
+    /// again on the next run. Dropping the future cancels the scrub.
+    pub async fn scrub_bucket(
+        &self,
+        bucket: &str,
+        marker: Option<String>,
+        deadline: tokio::time::Instant,
+    ) -> object_io_core::Result<ScrubReport> {
+        let mut report = ScrubReport::new(bucket);
+        let mut request = ListObjectsRequest {
+            marker,
+            ..ListObjectsRequest::new(bucket)
+        };
+
+        loop {
+            let page = self.metadata.list_objects(&request).await?;
+            for object in &page.objects {
+                let verify = scrub::verify_object(self.storage.as_ref(), bucket, &object.key, &object.etag);
+                let verification = if tokio::time::Instant::now() < deadline {
+                    tokio::time::timeout_at(deadline, verify).await.ok()
+                } else {
+                    None
+                };
+                let Some(verification) = verification else {
+                    report.next_marker = request.marker;
+                    return Ok(report);
+                };
+                report.record(&object.key, &object.etag, verification?);
+                request.marker = Some(object.key.clone());
+            }
+            if !page.is_truncated {
+                report.complete = true;
+                return Ok(report);
+            }
+        }
+    }
+
+    /// Fail with `ObjectLocked` if object lock keeps `key` in `bucket` from
+    /// being deleted or overwritten now, by a caller who may bypass
+    /// `GOVERNANCE` retention or not
+    ///
+    /// Callers hold the object's lock, so the answer stays true until they
+    /// have made their change.
+    pub async fn check_object_lock(
+        &self,
+        bucket: &str,
+        key: &str,
+        bypass_governance: bool,
+    ) -> object_io_core::Result<()> {
+        match self.metadata.get_object(bucket, key).await? {
+            Some(object) if object.is_locked(Utc::now(), bypass_governance) => Err(ObjectIOError::ObjectLocked {
+                bucket: bucket.to_string(),
+                key: key.to_string(),
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Delete every object in `bucket`, keeping the bucket itself, and
+    /// return how many object records were removed
+    ///
+    /// Objects are deleted a listing page at a time, so the bucket's keys are
+    /// never all held at once. Each object's data and record go together
+    /// under its lock, so objects written meanwhile are either deleted whole
+    /// or kept whole. A bucket holding objects under retention is left
+    /// untouched; an object locked after that check stops the emptying there.
+    pub async fn empty_bucket(&self, bucket: &str) -> object_io_core::Result<u64> {
+        if self.metadata.get_bucket(bucket).await?.is_some_and(|bucket| bucket.object_lock_enabled) {
+            self.find_locked_object(bucket).await?;
+        }
+
+        let mut deleted = 0;
+        let mut request = ListObjectsRequest::new(bucket);
+        loop {
+            let page = self.metadata.list_objects(&request).await?;
+            for object in &page.objects {
+                let _lock = self.locks.lock(bucket, &object.key).await;
+                self.check_object_lock(bucket, &object.key, false).await?;
+                match self.storage.delete_object(bucket, &object.key).await {
+                    Ok(())
+                    | Err(ObjectIOError::ObjectNotFound { .. })
+                    | Err(ObjectIOError::BucketNotFound { .. }) => {}
+                    Err(e) => return Err(e),
+                }
+                if self.metadata.delete_object(bucket, &object.key).await? {
+                    deleted += 1;
+                }
+                request.marker = Some(object.key.clone());
+            }
+            if !page.is_truncated {
+                break;
+            }
+        }
+        self.metadata.clear_delete_markers(bucket).await?;
+        Ok(deleted)
+    }
+
+    /// Fail with `ObjectLocked` for the first object in `bucket` under
+    /// retention
+    async fn find_locked_object(&self, bucket: &str) -> object_io_core::Result<()> {
+        let mut request = ListObjectsRequest::new(bucket);
+        loop {
+            let page = self.metadata.list_objects(&request).await?;
+            for object in &page.objects {
+                self.check_object_lock(bucket, &object.key, false).await?;
+                request.marker = Some(object.key.clone());
+            }
+            if !page.is_truncated {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Persist buffered metadata and storage writes before exit
+    pub async fn shutdown(&self) -> object_io_core::Result<()> {
+        info!("Flushing metadata database...");
+        self.metadata.flush().await?;
+        self.metadata.compact().await?;
+
+        info!("Syncing storage backend...");
+        self.storage.sync().await?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+impl AppState {
+    /// State backed by a filesystem store and database under `dir`
+    pub(crate) async fn for_test(dir: &std::path::Path) -> Self {
+        let config = ServerConfig {
+            database_path: dir.join("db").to_string_lossy().into_owned(),
+            storage_path: dir.join("storage").to_string_lossy().into_owned(),
+            ..ServerConfig::default()
+        };
+        let storage = object_io_storage::StorageBackend::filesystem(config.storage_path.clone())
+            .await
+            .unwrap();
+        Self::new(config, storage).await.unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::io::Cursor;
+    use tokio::time::{Duration, Instant};
+
+    #[tokio::test]
+    async fn test_scrub_bucket() {
+        let dir = tempfile::tempdir().unwrap();
+        let state = AppState::for_test(dir.path()).await;
+        state.metadata.create_bucket("data", "owner").await.unwrap();
+        for key in ["a.txt", "b.txt", "c.txt", "d.txt"] {
+            let reader = Box::new(Cursor::new(format!("contents of {}", key).into_bytes()));
+            crate::handlers::object::store_object(&state, "data", key, reader, HashMap::new())
+                .await
+                .unwrap();
+        }
+        // Multipart ETags cannot be recomputed from the data
+        state
+            .metadata
+            .put_object_metadata("data", "d.txt", 16, "text/plain", "abc-2", HashMap::new())
+            .await
+            .unwrap();
+
+        let objects = dir.path().join("storage").join("data");
+        std::fs::write(objects.join("b.txt"), "bit rot").unwrap();
+        std::fs::remove_file(objects.join("c.txt")).unwrap();
+
+        let later = Instant::now() + Duration::from_secs(60);
+        let report = state.scrub_bucket("data", None, later).await.unwrap();
+        assert!(report.complete);
+        assert_eq!(report.scanned, 4);
+        assert_eq!(report.corrupted.len(), 1);
+        assert_eq!(report.corrupted[0].key, "b.txt");
+        assert_eq!(report.corrupted[0].actual, object_io_core::generate_etag(b"bit rot"));
+        assert_eq!(report.missing, vec!["c.txt"]);
+        assert_eq!(report.unverifiable, vec!["d.txt"]);
+        assert!(!report.is_clean());
+
+        // A scrub out of time stops with a marker to resume from
+        let report = state.scrub_bucket("data", Some("a.txt".to_string()), Instant::now()).await.unwrap();
+        assert!(!report.complete);
+        assert_eq!(report.scanned, 0);
+        assert_eq!(report.next_marker.as_deref(), Some("a.txt"));
+
+        let report = state.scrub_bucket("data", report.next_marker, later).await.unwrap();
+        assert!(report.complete);
+        assert_eq!(report.scanned, 3);
+    }
+
+    #[tokio::test]
+    async fn test_sweep_waits_out_read_only_mode() {
+        let dir = tempfile::tempdir().unwrap();
+        let state = AppState::for_test(dir.path()).await;
+        state.metadata.create_bucket("data", "owner").await.unwrap();
+        state.storage.create_multipart_upload("data", "big.bin", HashMap::new()).await.unwrap();
+        let later = Utc::now() + chrono::Duration::seconds(state.config.multipart_upload_max_age as i64 + 1);
+
+        state.read_only.store(true, Ordering::Relaxed);
+        assert!(state.sweep_abandoned_uploads(later).await.unwrap().is_empty());
+        assert_eq!(state.storage.list_multipart_uploads("data").await.unwrap().len(), 1);
+
+        state.read_only.store(false, Ordering::Relaxed);
+        assert_eq!(state.sweep_abandoned_uploads(later).await.unwrap().len(), 1);
+        assert!(state.storage.list_multipart_uploads("data").await.unwrap().is_empty());
     }
 }
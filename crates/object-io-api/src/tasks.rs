@@ -0,0 +1,76 @@
+//! Background maintenance tasks
+
+use std::path::PathBuf;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+use crate::{
+    replication::{ReplicationTarget, Replicator},
+    state::AppState,
+};
+
+/// Spawn the periodic abandoned multipart upload sweeper, unless disabled by config
+pub fn spawn_multipart_sweeper(state: AppState) -> Option<JoinHandle<()>> {
+    if state.config.multipart_sweep_interval == 0 {
+        return None;
+    }
+
+    let period = Duration::from_secs(state.config.multipart_sweep_interval);
+    Some(tokio::spawn(async move {
+        let mut interval = tokio::time::interval(period);
+        loop {
+            interval.tick().await;
+            match state.sweep_abandoned_uploads(chrono::Utc::now()).await {
+                Ok(aborted) if !aborted.is_empty() => {
+                    info!("Aborted {} abandoned multipart uploads", aborted.len());
+                }
+                Ok(_) => {}
+                Err(e) => warn!("Abandoned multipart upload sweep failed: {}", e),
+            }
+        }
+    }))
+}
+
+/// Start replicating the change feed into the configured target, unless no
+/// target is configured
+///
+/// Fails if the target cannot be opened or the saved cursor read. Once
+/// running, failures are logged and retried with growing waits, never
+/// affecting requests.
+pub async fn spawn_replicator(state: AppState) -> object_io_core::Result<Option<JoinHandle<()>>> {
+    let Some(config) = state.config.replication.clone() else {
+        return Ok(None);
+    };
+
+    let target = ReplicationTarget::open(&config).await?;
+    let cursor_path = config.cursor_path.as_ref().map(PathBuf::from);
+    let mut replicator = Replicator::new(state.metadata.clone(), state.storage.clone(), target, cursor_path).await?;
+    match &config.endpoint {
+        Some(endpoint) => info!("Replicating changes after #{} to {}", replicator.cursor(), endpoint),
+        None => info!("Replicating changes after #{} to {:?} storage", replicator.cursor(), config.backend),
+    }
+
+    let interval = Duration::from_secs(config.interval.max(1));
+    let max_backoff = Duration::from_secs(config.max_backoff).max(interval);
+    Ok(Some(tokio::spawn(async move {
+        let mut backoff = interval;
+        loop {
+            match replicator.replicate().await {
+                Ok(applied) => {
+                    if applied > 0 {
+                        info!("Replicated {} changes", applied);
+                    }
+                    backoff = interval;
+                    tokio::time::sleep(interval).await;
+                }
+                Err(e) => {
+                    let failed = replicator.cursor() + 1;
+                    warn!("Replication stopped at change #{}, retrying in {:?}: {}", failed, backoff, e);
+                    tokio::time::sleep(backoff).await;
+                    backoff = (backoff * 2).min(max_backoff);
+                }
+            }
+        }
+    })))
+}
@@ -0,0 +1,140 @@
+//! Additional object checksums (`x-amz-checksum-*`)
+//!
+//! Values are base64 encoded as S3 sends them: the big-endian bytes of a
+//! CRC, or the raw digest of a hash.
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use sha2::{Digest, Sha256};
+
+/// Prefix of the headers (and trailers) carrying a checksum
+pub const CHECKSUM_HEADER_PREFIX: &str = "x-amz-checksum-";
+
+/// A checksum algorithm a client may send an object's checksum in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChecksumAlgorithm {
+    Crc32,
+    Crc32c,
+    Sha256,
+}
+
+impl ChecksumAlgorithm {
+    /// The algorithm named by an `x-amz-checksum-*` header, or `None` for
+    /// other headers and unsupported algorithms
+    pub fn from_header(name: &str) -> Option<Self> {
+        let name = name.trim().to_ascii_lowercase();
+        match name.strip_prefix(CHECKSUM_HEADER_PREFIX)? {
+            "crc32" => Some(Self::Crc32),
+            "crc32c" => Some(Self::Crc32c),
+            "sha256" => Some(Self::Sha256),
+            _ => None,
+        }
+    }
+
+    /// The header carrying a checksum of this algorithm
+    pub fn header_name(self) -> &'static str {
+        match self {
+            Self::Crc32 => "x-amz-checksum-crc32",
+            Self::Crc32c => "x-amz-checksum-crc32c",
+            Self::Sha256 => "x-amz-checksum-sha256",
+        }
+    }
+
+    /// Start computing a checksum of this algorithm
+    pub fn hasher(self) -> ChecksumHasher {
+        match self {
+            Self::Crc32 => ChecksumHasher::Crc32(crc32fast::Hasher::new()),
+            Self::Crc32c => ChecksumHasher::Crc32c(!0),
+            Self::Sha256 => ChecksumHasher::Sha256(Sha256::new()),
+        }
+    }
+}
+
+/// Incrementally computes a checksum over streamed data
+#[derive(Debug, Clone)]
+pub enum ChecksumHasher {
+    Crc32(crc32fast::Hasher),
+    /// The running CRC, before the final inversion
+    Crc32c(u32),
+    Sha256(Sha256),
+}
+
+impl ChecksumHasher {
+    /// Add the next chunk of data
+    pub fn update(&mut self, data: &[u8]) {
+        match self {
+            Self::Crc32(hasher) => hasher.update(data),
+            Self::Crc32c(crc) => *crc = crc32c_update(*crc, data),
+            Self::Sha256(hasher) => hasher.update(data),
+        }
+    }
+
+    /// Finish and base64 encode the checksum
+    pub fn finalize(self) -> String {
+        match self {
+            Self::Crc32(hasher) => STANDARD.encode(hasher.finalize().to_be_bytes()),
+            Self::Crc32c(crc) => STANDARD.encode((!crc).to_be_bytes()),
+            Self::Sha256(hasher) => STANDARD.encode(hasher.finalize()),
+        }
+    }
+}
+
+/// Lookup table for CRC-32C (Castagnoli, reflected polynomial 0x82F63B78)
+const CRC32C_TABLE: [u32; 256] = {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0x82F6_3B78 } else { crc >> 1 };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+};
+
+fn crc32c_update(mut crc: u32, data: &[u8]) -> u32 {
+    for &byte in data {
+        crc = CRC32C_TABLE[((crc ^ byte as u32) & 0xff) as usize] ^ (crc >> 8);
+    }
+    crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn checksum(algorithm: ChecksumAlgorithm, chunks: &[&[u8]]) -> String {
+        let mut hasher = algorithm.hasher();
+        for chunk in chunks {
+            hasher.update(chunk);
+        }
+        hasher.finalize()
+    }
+
+    #[test]
+    fn test_checksums() {
+        // Standard check values for "123456789"
+        let crc32 = STANDARD.encode(0xCBF4_3926u32.to_be_bytes());
+        let crc32c = STANDARD.encode(0xE306_9283u32.to_be_bytes());
+        assert_eq!(checksum(ChecksumAlgorithm::Crc32, &[b"1234", b"56789"]), crc32);
+        assert_eq!(checksum(ChecksumAlgorithm::Crc32c, &[b"1234", b"56789"]), crc32c);
+        assert_eq!(
+            checksum(ChecksumAlgorithm::Sha256, &[b""]),
+            "47DEQpj8HBSa+/TImW+5JCeuQeRkm5NMpJWZG3hSuFU="
+        );
+    }
+
+    #[test]
+    fn test_algorithm_from_header() {
+        assert_eq!(ChecksumAlgorithm::from_header("x-amz-checksum-crc32c"), Some(ChecksumAlgorithm::Crc32c));
+        assert_eq!(ChecksumAlgorithm::from_header(" X-Amz-Checksum-SHA256"), Some(ChecksumAlgorithm::Sha256));
+        assert_eq!(ChecksumAlgorithm::from_header("x-amz-checksum-crc64nvme"), None);
+        assert_eq!(ChecksumAlgorithm::from_header("content-md5"), None);
+        for algorithm in [ChecksumAlgorithm::Crc32, ChecksumAlgorithm::Crc32c, ChecksumAlgorithm::Sha256] {
+            assert_eq!(ChecksumAlgorithm::from_header(algorithm.header_name()), Some(algorithm));
+        }
+    }
+}
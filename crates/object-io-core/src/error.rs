@@ -17,12 +17,57 @@ pub enum ObjectIOError {
     #[error("Bucket already exists: {bucket}")]
     BucketAlreadyExists { bucket: String },
 
+    #[error("Bucket already owned by you: {bucket}")]
+    BucketAlreadyOwnedByYou { bucket: String },
+
+    #[error("Bucket not empty: {bucket}")]
+    BucketNotEmpty { bucket: String },
+
     #[error("Invalid bucket name: {bucket}")]
     InvalidBucketName { bucket: String },
 
     #[error("Invalid object key: {key}")]
     InvalidObjectKey { key: String },
 
+    #[error("User not found: {user_id}")]
+    UserNotFound { user_id: String },
+
+    #[error("User already exists: {user_id}")]
+    UserAlreadyExists { user_id: String },
+
+    #[error("Access key already exists: {access_key}")]
+    AccessKeyAlreadyExists { access_key: String },
+
+    #[error("Access key not found: {access_key}")]
+    AccessKeyNotFound { access_key: String },
+
+    #[error("Multipart upload not found: {upload_id}")]
+    UploadNotFound { upload_id: String },
+
+    #[error("Invalid part: {message}")]
+    InvalidPart { message: String },
+
+    #[error("Invalid part order: {message}")]
+    InvalidPartOrder { message: String },
+
+    #[error("Entity too small: {message}")]
+    EntityTooSmall { message: String },
+
+    #[error("Object already exists: {bucket}/{key}")]
+    ObjectAlreadyExists { bucket: String, key: String },
+
+    #[error("Precondition failed for {bucket}/{key}")]
+    PreconditionFailed { bucket: String, key: String },
+
+    #[error("Object {bucket}/{key} is protected by object lock")]
+    ObjectLocked { bucket: String, key: String },
+
+    #[error("Metadata too large: {size} bytes exceeds the {limit} byte limit")]
+    MetadataTooLarge { size: usize, limit: usize },
+
+    #[error("Invalid argument: {message}")]
+    InvalidArgument { message: String },
+
     #[error("Authentication failed: {reason}")]
     AuthenticationFailed { reason: String },
 
@@ -41,9 +86,24 @@ pub enum ObjectIOError {
     #[error("Configuration error: {message}")]
     ConfigurationError { message: String },
 
+    #[error("Malformed policy: {message}")]
+    MalformedPolicy { message: String },
+
+    #[error("Malformed ACL: {message}")]
+    MalformedAcl { message: String },
+
     #[error("Invalid request: {message}")]
     InvalidRequest { message: String },
 
+    #[error("Bad digest: {message}")]
+    BadDigest { message: String },
+
+    #[error("Snapshot not found: {snapshot} of bucket {bucket}")]
+    SnapshotNotFound { bucket: String, snapshot: String },
+
+    #[error("Not implemented: {message}")]
+    NotImplemented { message: String },
+
     #[error("Internal server error: {message}")]
     InternalError { message: String },
 
@@ -54,7 +114,19 @@ pub enum ObjectIOError {
     Serialization(#[from] serde_json::Error),
 
     #[error(transparent)]
-    Other(#[from] anyhow::Error),
+    Other(anyhow::Error),
+}
+
+/// Recovers an `ObjectIOError` carried inside an `anyhow::Error` (as the
+/// database crate returns them), so callers can match on its kind; anything
+/// else becomes `Other`
+impl From<anyhow::Error> for ObjectIOError {
+    fn from(error: anyhow::Error) -> Self {
+        match error.downcast::<ObjectIOError>() {
+            Ok(error) => error,
+            Err(error) => ObjectIOError::Other(error),
+        }
+    }
 }
 
 impl ObjectIOError {
@@ -64,12 +136,32 @@ impl ObjectIOError {
             ObjectIOError::BucketNotFound { .. } => 404,
             ObjectIOError::ObjectNotFound { .. } => 404,
             ObjectIOError::BucketAlreadyExists { .. } => 409,
+            ObjectIOError::BucketAlreadyOwnedByYou { .. } => 409,
+            ObjectIOError::BucketNotEmpty { .. } => 409,
             ObjectIOError::InvalidBucketName { .. } => 400,
             ObjectIOError::InvalidObjectKey { .. } => 400,
+            ObjectIOError::UserNotFound { .. } => 404,
+            ObjectIOError::UserAlreadyExists { .. } => 409,
+            ObjectIOError::AccessKeyAlreadyExists { .. } => 409,
+            ObjectIOError::AccessKeyNotFound { .. } => 404,
+            ObjectIOError::UploadNotFound { .. } => 404,
+            ObjectIOError::InvalidPart { .. } => 400,
+            ObjectIOError::InvalidPartOrder { .. } => 400,
+            ObjectIOError::EntityTooSmall { .. } => 400,
+            ObjectIOError::ObjectAlreadyExists { .. } => 409,
+            ObjectIOError::PreconditionFailed { .. } => 412,
+            ObjectIOError::ObjectLocked { .. } => 403,
+            ObjectIOError::MetadataTooLarge { .. } => 400,
+            ObjectIOError::InvalidArgument { .. } => 400,
             ObjectIOError::AuthenticationFailed { .. } => 401,
             ObjectIOError::AuthorizationFailed { .. } => 403,
             ObjectIOError::AuthError { .. } => 403,
+            ObjectIOError::MalformedPolicy { .. } => 400,
+            ObjectIOError::MalformedAcl { .. } => 400,
             ObjectIOError::InvalidRequest { .. } => 400,
+            ObjectIOError::BadDigest { .. } => 400,
+            ObjectIOError::SnapshotNotFound { .. } => 404,
+            ObjectIOError::NotImplemented { .. } => 501,
             ObjectIOError::StorageError { .. } => 500,
             ObjectIOError::DatabaseError { .. } => 500,
             ObjectIOError::ConfigurationError { .. } => 500,
@@ -86,11 +178,31 @@ impl ObjectIOError {
             ObjectIOError::BucketNotFound { .. } => "NoSuchBucket",
             ObjectIOError::ObjectNotFound { .. } => "NoSuchKey",
             ObjectIOError::BucketAlreadyExists { .. } => "BucketAlreadyExists",
+            ObjectIOError::BucketAlreadyOwnedByYou { .. } => "BucketAlreadyOwnedByYou",
+            ObjectIOError::BucketNotEmpty { .. } => "BucketNotEmpty",
             ObjectIOError::InvalidBucketName { .. } => "InvalidBucketName",
             ObjectIOError::InvalidObjectKey { .. } => "InvalidKey",
+            ObjectIOError::UserNotFound { .. } => "NoSuchEntity",
+            ObjectIOError::UserAlreadyExists { .. } => "EntityAlreadyExists",
+            ObjectIOError::AccessKeyAlreadyExists { .. } => "EntityAlreadyExists",
+            ObjectIOError::AccessKeyNotFound { .. } => "NoSuchEntity",
+            ObjectIOError::UploadNotFound { .. } => "NoSuchUpload",
+            ObjectIOError::InvalidPart { .. } => "InvalidPart",
+            ObjectIOError::InvalidPartOrder { .. } => "InvalidPartOrder",
+            ObjectIOError::EntityTooSmall { .. } => "EntityTooSmall",
+            ObjectIOError::ObjectAlreadyExists { .. } => "ObjectAlreadyExists",
+            ObjectIOError::PreconditionFailed { .. } => "PreconditionFailed",
+            ObjectIOError::ObjectLocked { .. } => "AccessDenied",
+            ObjectIOError::MetadataTooLarge { .. } => "MetadataTooLarge",
+            ObjectIOError::InvalidArgument { .. } => "InvalidArgument",
             ObjectIOError::AuthenticationFailed { .. } => "InvalidAccessKeyId",
             ObjectIOError::AuthorizationFailed { .. } => "AccessDenied",
+            ObjectIOError::MalformedPolicy { .. } => "MalformedPolicy",
+            ObjectIOError::MalformedAcl { .. } => "MalformedACLError",
             ObjectIOError::InvalidRequest { .. } => "InvalidRequest",
+            ObjectIOError::BadDigest { .. } => "BadDigest",
+            ObjectIOError::SnapshotNotFound { .. } => "NoSuchSnapshot",
+            ObjectIOError::NotImplemented { .. } => "NotImplemented",
             _ => "InternalError",
         }
     }
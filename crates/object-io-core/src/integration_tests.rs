@@ -32,6 +32,11 @@ mod integration_tests {
             region: "us-east-1".to_string(),
             versioning: VersioningStatus::Enabled,
             access_control,
+            object_count: 0,
+            total_size: 0,
+            object_lock_enabled: false,
+            default_retention: None,
+            key_patterns: None,
         };
 
         // Validate the bucket name
@@ -75,8 +80,15 @@ mod integration_tests {
             last_modified: Utc::now(),
             content_type: "application/pdf".to_string(),
             content_encoding: Some("gzip".to_string()),
+            cache_control: None,
+            content_disposition: None,
+            content_language: None,
+            expires: None,
             metadata,
             storage_class: StorageClass::Standard,
+            acl: vec![],
+            retention: None,
+            legal_hold: false,
         };
 
         // Validate bucket and object key
@@ -276,6 +288,11 @@ mod integration_tests {
             region: "us-west-2".to_string(),
             versioning: VersioningStatus::Enabled,
             access_control,
+            object_count: 0,
+            total_size: 0,
+            object_lock_enabled: false,
+            default_retention: None,
+            key_patterns: None,
         };
 
         let bucket_json = serde_json::to_string(&original_bucket).unwrap();
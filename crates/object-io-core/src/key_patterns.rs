@@ -0,0 +1,96 @@
+//! Per-bucket key patterns
+//!
+//! An ObjectIO extension for content governance that is simpler than a
+//! bucket policy: a bucket may list glob patterns that the keys written to
+//! it must match (`allow`) or must not match (`deny`). `*` matches any run
+//! of characters, `/` included, and `?` any single character, as in
+//! [`crate::policy::wildcard_match`]. Matching is case-sensitive, like keys.
+
+use crate::error::{ObjectIOError, Result};
+use crate::policy::wildcard_match;
+use serde::{Deserialize, Serialize};
+
+/// Most patterns a bucket may have, allow and deny together
+pub const MAX_KEY_PATTERNS: usize = 100;
+
+/// Glob patterns restricting the keys that may be written to a bucket
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KeyPatterns {
+    /// Keys must match one of these, unless there are none
+    #[serde(default)]
+    pub allow: Vec<String>,
+    /// Keys matching any of these are refused, whatever `allow` says
+    #[serde(default)]
+    pub deny: Vec<String>,
+}
+
+impl KeyPatterns {
+    /// Fail with `InvalidArgument` if a pattern is empty or longer than a
+    /// key may be, or there are more than [`MAX_KEY_PATTERNS`]
+    pub fn validate(&self) -> Result<()> {
+        let invalid = |message: String| Err(ObjectIOError::InvalidArgument { message });
+        if self.allow.len() + self.deny.len() > MAX_KEY_PATTERNS {
+            return invalid(format!("A bucket may have at most {} key patterns", MAX_KEY_PATTERNS));
+        }
+        for pattern in self.allow.iter().chain(&self.deny) {
+            if pattern.is_empty() || pattern.len() > 1024 {
+                return invalid(format!("Key patterns must be 1 to 1024 bytes long: '{}'", pattern));
+            }
+        }
+        Ok(())
+    }
+
+    /// Fail with `AuthorizationFailed` (`AccessDenied`) unless `key` may be
+    /// written: it matches no `deny` pattern, and some `allow` pattern if
+    /// there are any
+    pub fn check(&self, key: &str) -> Result<()> {
+        let denied = |reason: String| Err(ObjectIOError::AuthorizationFailed { reason });
+        if let Some(pattern) = self.deny.iter().find(|pattern| wildcard_match(pattern, key)) {
+            return denied(format!("Key '{}' matches the bucket's deny pattern '{}'", key, pattern));
+        }
+        if !self.allow.is_empty() && !self.allow.iter().any(|pattern| wildcard_match(pattern, key)) {
+            return denied(format!("Key '{}' matches none of the bucket's allow patterns", key));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_key_patterns() {
+        let patterns = KeyPatterns {
+            allow: vec!["uploads/*".to_string(), "shared/??/*".to_string()],
+            deny: vec!["*.exe".to_string()],
+        };
+        for allowed in ["uploads/a.txt", "uploads/deep/b.png", "shared/en/readme"] {
+            assert!(patterns.check(allowed).is_ok(), "{}", allowed);
+        }
+        for denied in ["a.txt", "uploads/setup.exe", "shared/eng/readme", "Uploads/a.txt"] {
+            let result = patterns.check(denied);
+            assert!(matches!(result, Err(ObjectIOError::AuthorizationFailed { .. })), "{}", denied);
+        }
+
+        // No allow patterns means every key not denied
+        let deny_only = KeyPatterns {
+            deny: vec!["tmp/*".to_string()],
+            ..Default::default()
+        };
+        assert!(deny_only.check("a.txt").is_ok());
+        assert!(deny_only.check("tmp/a.txt").is_err());
+
+        assert!(patterns.validate().is_ok());
+        let empty = KeyPatterns {
+            allow: vec![String::new()],
+            ..Default::default()
+        };
+        assert!(matches!(empty.validate(), Err(ObjectIOError::InvalidArgument { .. })));
+        let many = KeyPatterns {
+            deny: vec!["*.tmp".to_string(); MAX_KEY_PATTERNS + 1],
+            ..Default::default()
+        };
+        assert!(matches!(many.validate(), Err(ObjectIOError::InvalidArgument { .. })));
+    }
+}
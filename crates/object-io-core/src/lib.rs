@@ -3,7 +3,12 @@
 //! This crate contains the core types, error definitions, and shared utilities
 //! used across the ObjectIO S3-compatible storage system.
 
+pub mod checksum;
 pub mod error;
+pub mod key_patterns;
+pub mod listing;
+pub mod object_lock;
+pub mod policy;
 pub mod types;
 pub mod utils;
 
@@ -12,6 +17,10 @@ pub mod utils;
 mod integration_tests;
 
 // Re-export commonly used types
+pub use checksum::{ChecksumAlgorithm, ChecksumHasher};
 pub use error::{ObjectIOError, Result};
+pub use key_patterns::KeyPatterns;
+pub use listing::ContinuationToken;
+pub use object_lock::{DefaultRetention, ObjectLockMode, ObjectRetention, RetentionPeriod};
 pub use types::*;
 pub use utils::*;
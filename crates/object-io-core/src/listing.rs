@@ -0,0 +1,332 @@
+//! Listing pagination: object listing pages shared by storage backends and
+//! the metadata store, and the continuation tokens listings hand to clients
+
+use crate::error::{ObjectIOError, Result};
+use crate::types::{ListObjectsRequest, ListObjectsResponse, ObjectSummary};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+/// Bytes of SHA-256 appended to a token's payload to detect tampering
+const TOKEN_CHECKSUM_LEN: usize = 8;
+
+impl ListObjectsRequest {
+    /// Page size used when the client does not specify `max-keys`
+    pub const DEFAULT_MAX_KEYS: u32 = 1000;
+
+    /// Create a request listing a whole bucket with the default page size
+    pub fn new(bucket: impl Into<String>) -> Self {
+        Self {
+            bucket: bucket.into(),
+            ..Self::default()
+        }
+    }
+
+    /// Resolve `max_keys` to the default when absent and clamp it to `cap`
+    pub fn capped(mut self, cap: u32) -> Self {
+        self.max_keys = Some(self.page_size().min(cap));
+        self
+    }
+
+    /// Number of keys and common prefixes a page may hold
+    pub fn page_size(&self) -> u32 {
+        self.max_keys.unwrap_or(Self::DEFAULT_MAX_KEYS)
+    }
+}
+
+impl ListObjectsResponse {
+    /// Build one page of a listing from objects sorted by key
+    ///
+    /// Applies the request's prefix, marker and delimiter; keys sharing a
+    /// prefix up to the delimiter are rolled up into one common prefix,
+    /// which counts towards the page size like a key does. When the page
+    /// fills before the objects run out, the response is truncated and
+    /// `next_marker` holds the last key or common prefix returned.
+    pub fn paginate<I>(request: &ListObjectsRequest, objects: I) -> Self
+    where
+        I: IntoIterator<Item = ObjectSummary>,
+    {
+        let max_keys = request.page_size();
+        let prefix = request.prefix.as_deref().unwrap_or_default();
+        let delimiter = request.delimiter.as_deref().filter(|d| !d.is_empty());
+        let marker = request.marker.as_deref().filter(|m| !m.is_empty());
+
+        let mut response = Self {
+            bucket: request.bucket.clone(),
+            prefix: request.prefix.clone(),
+            delimiter: request.delimiter.clone(),
+            marker: request.marker.clone(),
+            next_marker: None,
+            max_keys,
+            is_truncated: false,
+            objects: Vec::new(),
+            common_prefixes: Vec::new(),
+        };
+
+        let mut returned = 0u32;
+        let mut last: Option<String> = None;
+        for object in objects {
+            if !object.key.starts_with(prefix) {
+                continue;
+            }
+            if let Some(marker) = marker {
+                // A common prefix used as the marker skips all keys under it
+                let under_marker = delimiter.is_some_and(|d| marker.ends_with(d)) && object.key.starts_with(marker);
+                if object.key.as_str() <= marker || under_marker {
+                    continue;
+                }
+            }
+
+            let common_prefix = delimiter.and_then(|d| {
+                object.key[prefix.len()..]
+                    .find(d)
+                    .map(|i| object.key[..prefix.len() + i + d.len()].to_string())
+            });
+            if common_prefix.is_some() && common_prefix == last {
+                continue;
+            }
+
+            if returned == max_keys {
+                response.is_truncated = true;
+                response.next_marker = last;
+                break;
+            }
+            returned += 1;
+
+            match common_prefix {
+                Some(common_prefix) => {
+                    response.common_prefixes.push(common_prefix.clone());
+                    last = Some(common_prefix);
+                }
+                None => {
+                    last = Some(object.key.clone());
+                    response.objects.push(object);
+                }
+            }
+        }
+
+        response
+    }
+}
+
+/// Where a listing resumes, handed to clients as an opaque token
+///
+/// Encoded as URL-safe base64 of the JSON fields followed by a truncated
+/// SHA-256 of them, so a hand-edited or corrupted token is rejected rather
+/// than silently resuming somewhere else. The checksum is not keyed: it
+/// guards against mistakes, not against a client forging a position in a
+/// listing it may read anyway.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ContinuationToken {
+    /// Prefix of the listing the token continues
+    #[serde(rename = "p")]
+    pub prefix: String,
+    /// Last key, common prefix or bucket name of the previous page
+    #[serde(rename = "k")]
+    pub last_key: String,
+    /// Version or upload ID within `last_key` to resume after, for listings
+    /// with several entries per key
+    #[serde(rename = "v", default, skip_serializing_if = "Option::is_none")]
+    pub version_marker: Option<String>,
+}
+
+impl ContinuationToken {
+    /// Token resuming a listing of `prefix` after `last_key`
+    pub fn new(prefix: impl Into<String>, last_key: impl Into<String>) -> Self {
+        Self {
+            prefix: prefix.into(),
+            last_key: last_key.into(),
+            version_marker: None,
+        }
+    }
+
+    /// Resume after `version_marker` within `last_key`
+    pub fn with_version_marker(mut self, version_marker: impl Into<String>) -> Self {
+        self.version_marker = Some(version_marker.into());
+        self
+    }
+
+    /// Encode as an opaque token
+    pub fn encode(&self) -> String {
+        let mut bytes = serde_json::to_vec(self).expect("token fields serialize");
+        let checksum = Sha256::digest(&bytes);
+        bytes.extend_from_slice(&checksum[..TOKEN_CHECKSUM_LEN]);
+        URL_SAFE_NO_PAD.encode(bytes)
+    }
+
+    /// Decode a token made by [`encode`](Self::encode), failing with
+    /// `InvalidArgument` if it was altered or never was one
+    pub fn decode(token: &str) -> Result<Self> {
+        let invalid = || ObjectIOError::InvalidArgument {
+            message: "The continuation token provided is incorrect".to_string(),
+        };
+        let bytes = URL_SAFE_NO_PAD.decode(token.trim()).map_err(|_| invalid())?;
+        if bytes.len() < TOKEN_CHECKSUM_LEN {
+            return Err(invalid());
+        }
+        let (payload, checksum) = bytes.split_at(bytes.len() - TOKEN_CHECKSUM_LEN);
+        if Sha256::digest(payload)[..TOKEN_CHECKSUM_LEN] != *checksum {
+            return Err(invalid());
+        }
+        serde_json::from_slice(payload).map_err(|_| invalid())
+    }
+
+    /// Decode a token that must continue a listing of `prefix`
+    pub fn decode_for(token: &str, prefix: &str) -> Result<Self> {
+        let decoded = Self::decode(token)?;
+        if decoded.prefix != prefix {
+            return Err(ObjectIOError::InvalidArgument {
+                message: "The continuation token is for a different prefix".to_string(),
+            });
+        }
+        Ok(decoded)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::StorageClass;
+    use chrono::Utc;
+
+    fn summaries(keys: &[&str]) -> Vec<ObjectSummary> {
+        keys.iter()
+            .map(|key| ObjectSummary {
+                key: key.to_string(),
+                size: 1,
+                etag: String::new(),
+                last_modified: Utc::now(),
+                storage_class: StorageClass::Standard,
+            })
+            .collect()
+    }
+
+    fn keys(response: &ListObjectsResponse) -> Vec<&str> {
+        response.objects.iter().map(|object| object.key.as_str()).collect()
+    }
+
+    #[test]
+    fn test_page_size_default_and_cap() {
+        let request = ListObjectsRequest::new("bucket");
+        assert_eq!(request.page_size(), 1000);
+        assert_eq!(request.clone().capped(500).max_keys, Some(500));
+        assert_eq!(request.capped(5000).max_keys, Some(1000));
+
+        let request = ListObjectsRequest {
+            max_keys: Some(100_000_000),
+            ..ListObjectsRequest::new("bucket")
+        };
+        assert_eq!(request.capped(1000).max_keys, Some(1000));
+    }
+
+    #[test]
+    fn test_paginate_with_marker() {
+        let objects = summaries(&["a", "b", "c", "d", "e"]);
+        let mut request = ListObjectsRequest {
+            max_keys: Some(2),
+            ..ListObjectsRequest::new("bucket")
+        };
+
+        let mut pages = Vec::new();
+        loop {
+            let page = ListObjectsResponse::paginate(&request, objects.clone());
+            pages.push(keys(&page).join(""));
+            if !page.is_truncated {
+                assert!(page.next_marker.is_none());
+                break;
+            }
+            request.marker = page.next_marker;
+        }
+        assert_eq!(pages, ["ab", "cd", "e"]);
+
+        // A page that is exactly full is not truncated
+        request.marker = Some("c".to_string());
+        let page = ListObjectsResponse::paginate(&request, objects.clone());
+        assert_eq!(keys(&page), ["d", "e"]);
+        assert!(!page.is_truncated);
+    }
+
+    #[test]
+    fn test_paginate_prefix_and_delimiter() {
+        let objects = summaries(&[
+            "photos/2023/a.jpg",
+            "photos/2023/b.jpg",
+            "photos/2024/c.jpg",
+            "photos/index.html",
+            "readme.txt",
+        ]);
+
+        let request = ListObjectsRequest {
+            prefix: Some("photos/".to_string()),
+            delimiter: Some("/".to_string()),
+            ..ListObjectsRequest::new("bucket")
+        };
+        let page = ListObjectsResponse::paginate(&request, objects.clone());
+        assert_eq!(page.common_prefixes, ["photos/2023/", "photos/2024/"]);
+        assert_eq!(keys(&page), ["photos/index.html"]);
+        assert!(!page.is_truncated);
+
+        // Common prefixes count towards the page size and can be the marker
+        let request = ListObjectsRequest {
+            max_keys: Some(1),
+            ..request
+        };
+        let page = ListObjectsResponse::paginate(&request, objects.clone());
+        assert_eq!(page.common_prefixes, ["photos/2023/"]);
+        assert!(page.is_truncated);
+        assert_eq!(page.next_marker.as_deref(), Some("photos/2023/"));
+
+        let request = ListObjectsRequest {
+            marker: page.next_marker,
+            ..request
+        };
+        let page = ListObjectsResponse::paginate(&request, objects);
+        assert_eq!(page.common_prefixes, ["photos/2024/"]);
+        assert!(page.objects.is_empty());
+        assert!(page.is_truncated);
+    }
+
+    #[test]
+    fn test_continuation_token_round_trip() {
+        let token = ContinuationToken::new("photos/", "photos/2024/c.jpg");
+        let encoded = token.encode();
+        assert!(encoded.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_'));
+        assert_eq!(ContinuationToken::decode(&encoded).unwrap(), token);
+        assert_eq!(ContinuationToken::decode_for(&encoded, "photos/").unwrap(), token);
+
+        let token = ContinuationToken::new("", "report.pdf").with_version_marker("3f2a");
+        let decoded = ContinuationToken::decode(&token.encode()).unwrap();
+        assert_eq!(decoded.version_marker.as_deref(), Some("3f2a"));
+    }
+
+    #[test]
+    fn test_continuation_token_rejects_tampering() {
+        let encoded = ContinuationToken::new("photos/", "photos/a.jpg").encode();
+        let invalid = |token: &str| {
+            matches!(ContinuationToken::decode(token), Err(ObjectIOError::InvalidArgument { .. }))
+        };
+
+        // Any changed byte of the payload or the checksum
+        let mut bytes = URL_SAFE_NO_PAD.decode(&encoded).unwrap();
+        for i in 0..bytes.len() {
+            bytes[i] ^= 1;
+            assert!(invalid(&URL_SAFE_NO_PAD.encode(&bytes)), "byte {} changed", i);
+            bytes[i] ^= 1;
+        }
+
+        // An edited payload without a checksum
+        let forged = URL_SAFE_NO_PAD.encode(br#"{"p":"photos/","k":"photos/z.jpg"}"#);
+        assert!(invalid(&forged));
+
+        // Plain keys, as older servers handed out, and garbage
+        for token in ["photos/a.jpg", "", "!!!", "AAAA"] {
+            assert!(invalid(token), "{}", token);
+        }
+
+        // A token for another prefix
+        assert!(matches!(
+            ContinuationToken::decode_for(&encoded, "videos/"),
+            Err(ObjectIOError::InvalidArgument { .. })
+        ));
+    }
+}
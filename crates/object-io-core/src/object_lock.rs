@@ -0,0 +1,247 @@
+//! Object lock: retention periods during which an object cannot be deleted
+//! or overwritten
+//!
+//! Object lock is enabled per bucket, after which each object may carry a
+//! retention mode and a date it is retained until. `COMPLIANCE` retention
+//! protects an object from everyone; `GOVERNANCE` retention can be bypassed
+//! by privileged users who ask to with `x-amz-bypass-governance-retention`.
+//! A legal hold protects an object from everyone until it is removed,
+//! independently of any retention period. A bucket may also set a default
+//! retention, given to new objects that don't ask for their own.
+
+use crate::error::{ObjectIOError, Result};
+use chrono::{DateTime, Duration, Months, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Header setting an object's retention mode on upload, and reporting it
+pub const MODE_HEADER: &str = "x-amz-object-lock-mode";
+
+/// Header setting the date an object is retained until, and reporting it
+pub const RETAIN_UNTIL_HEADER: &str = "x-amz-object-lock-retain-until-date";
+
+/// Header reporting whether an object is under a legal hold, `ON` or `OFF`
+pub const LEGAL_HOLD_HEADER: &str = "x-amz-object-lock-legal-hold";
+
+/// Header asking to delete or overwrite an object despite `GOVERNANCE`
+/// retention
+pub const BYPASS_GOVERNANCE_HEADER: &str = "x-amz-bypass-governance-retention";
+
+/// Header enabling object lock on a bucket as it is created
+pub const BUCKET_ENABLED_HEADER: &str = "x-amz-bucket-object-lock-enabled";
+
+/// How strictly a retention period protects an object
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ObjectLockMode {
+    /// Privileged users may bypass the retention period
+    Governance,
+    /// Nobody may delete or overwrite the object until the period ends
+    Compliance,
+}
+
+impl ObjectLockMode {
+    /// Parse a mode as S3 spells it, `GOVERNANCE` or `COMPLIANCE`
+    pub fn parse(mode: &str) -> Option<Self> {
+        match mode {
+            "GOVERNANCE" => Some(ObjectLockMode::Governance),
+            "COMPLIANCE" => Some(ObjectLockMode::Compliance),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ObjectLockMode::Governance => "GOVERNANCE",
+            ObjectLockMode::Compliance => "COMPLIANCE",
+        }
+    }
+}
+
+/// An object's retention period
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ObjectRetention {
+    pub mode: ObjectLockMode,
+    pub retain_until: DateTime<Utc>,
+}
+
+impl ObjectRetention {
+    /// Retention requested with `x-amz-object-lock-mode` and
+    /// `x-amz-object-lock-retain-until-date`, which must be sent together
+    /// and name a date after `now`
+    pub fn from_headers(mode: Option<&str>, retain_until: Option<&str>, now: DateTime<Utc>) -> Result<Option<Self>> {
+        let (mode, retain_until) = match (mode, retain_until) {
+            (None, None) => return Ok(None),
+            (Some(mode), Some(retain_until)) => (mode, retain_until),
+            _ => {
+                return Err(ObjectIOError::InvalidArgument {
+                    message: format!("{} and {} must be sent together", MODE_HEADER, RETAIN_UNTIL_HEADER),
+                })
+            }
+        };
+        let mode = ObjectLockMode::parse(mode).ok_or_else(|| ObjectIOError::InvalidArgument {
+            message: format!("Unknown object lock mode: {}", mode),
+        })?;
+        let retain_until = crate::utils::parse_s3_timestamp(retain_until)?;
+        if retain_until <= now {
+            return Err(ObjectIOError::InvalidArgument {
+                message: "The retain until date must be in the future".to_string(),
+            });
+        }
+        Ok(Some(Self { mode, retain_until }))
+    }
+
+    /// Whether the period still runs at `now`
+    pub fn is_active(&self, now: DateTime<Utc>) -> bool {
+        self.retain_until > now
+    }
+
+    /// Whether the period keeps the object from being deleted or
+    /// overwritten at `now`, by a caller who may bypass `GOVERNANCE`
+    /// retention or not
+    pub fn protects(&self, now: DateTime<Utc>, bypass_governance: bool) -> bool {
+        self.is_active(now) && !(bypass_governance && self.mode == ObjectLockMode::Governance)
+    }
+
+    /// Add the retention to storage-form object metadata, as the headers
+    /// that request it
+    pub fn insert_into(&self, metadata: &mut HashMap<String, String>) {
+        metadata.insert(MODE_HEADER.to_string(), self.mode.as_str().to_string());
+        metadata.insert(RETAIN_UNTIL_HEADER.to_string(), crate::utils::format_s3_timestamp(&self.retain_until));
+    }
+
+    /// Remove the entries `insert_into` adds from `metadata`, returning the
+    /// retention they describe
+    pub fn take_from(metadata: &mut HashMap<String, String>) -> Option<Self> {
+        let mode = metadata.remove(MODE_HEADER);
+        let retain_until = metadata.remove(RETAIN_UNTIL_HEADER);
+        Some(Self {
+            mode: ObjectLockMode::parse(&mode?)?,
+            retain_until: crate::utils::parse_s3_timestamp(&retain_until?).ok()?,
+        })
+    }
+}
+
+/// Length of a default retention period
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RetentionPeriod {
+    Days(u32),
+    Years(u32),
+}
+
+/// Retention a bucket gives new objects that don't ask for their own
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DefaultRetention {
+    pub mode: ObjectLockMode,
+    pub period: RetentionPeriod,
+}
+
+impl DefaultRetention {
+    /// A default retention of either `days` or `years`, which must not be
+    /// given together and must be positive
+    pub fn new(mode: ObjectLockMode, days: Option<u32>, years: Option<u32>) -> Result<Self> {
+        let period = match (days, years) {
+            (Some(days), None) if days > 0 => RetentionPeriod::Days(days),
+            (None, Some(years)) if years > 0 => RetentionPeriod::Years(years),
+            (Some(_), Some(_)) => {
+                return Err(ObjectIOError::InvalidArgument {
+                    message: "A default retention takes either Days or Years, not both".to_string(),
+                })
+            }
+            _ => {
+                return Err(ObjectIOError::InvalidArgument {
+                    message: "A default retention needs a positive number of Days or Years".to_string(),
+                })
+            }
+        };
+        Ok(Self { mode, period })
+    }
+
+    /// Retention of an object written at `now`
+    pub fn retention_at(&self, now: DateTime<Utc>) -> ObjectRetention {
+        let retain_until = match self.period {
+            RetentionPeriod::Days(days) => now.checked_add_signed(Duration::days(days.into())),
+            RetentionPeriod::Years(years) => now.checked_add_months(Months::new(years.saturating_mul(12))),
+        };
+        ObjectRetention {
+            mode: self.mode,
+            retain_until: retain_until.unwrap_or(DateTime::<Utc>::MAX_UTC),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration, SubsecRound};
+
+    #[test]
+    fn test_retention_from_headers() {
+        let now = "2024-06-01T00:00:00Z".parse::<DateTime<Utc>>().unwrap();
+        let retention = ObjectRetention::from_headers(Some("COMPLIANCE"), Some("2030-01-01T00:00:00.000Z"), now)
+            .unwrap()
+            .unwrap();
+        assert_eq!(retention.mode, ObjectLockMode::Compliance);
+        assert_eq!(retention.retain_until, "2030-01-01T00:00:00Z".parse::<DateTime<Utc>>().unwrap());
+        assert!(ObjectRetention::from_headers(None, None, now).unwrap().is_none());
+
+        for (mode, retain_until) in [
+            (Some("COMPLIANCE"), None),
+            (None, Some("2030-01-01T00:00:00Z")),
+            (Some("governance"), Some("2030-01-01T00:00:00Z")),
+            (Some("GOVERNANCE"), Some("next year")),
+            (Some("GOVERNANCE"), Some("2024-05-31T00:00:00Z")),
+        ] {
+            assert!(matches!(
+                ObjectRetention::from_headers(mode, retain_until, now),
+                Err(ObjectIOError::InvalidArgument { .. })
+            ));
+        }
+    }
+
+    #[test]
+    fn test_retention_protects_until_date() {
+        let now = Utc::now();
+        let retention = |mode| ObjectRetention {
+            mode,
+            retain_until: now + Duration::days(1),
+        };
+        let compliance = retention(ObjectLockMode::Compliance);
+        assert!(compliance.protects(now, false));
+        assert!(compliance.protects(now, true));
+        assert!(!compliance.protects(now + Duration::days(2), false));
+
+        let governance = retention(ObjectLockMode::Governance);
+        assert!(governance.protects(now, false));
+        assert!(!governance.protects(now, true));
+
+        // Round trip through storage-form metadata
+        let mut metadata = HashMap::from([("color".to_string(), "blue".to_string())]);
+        governance.insert_into(&mut metadata);
+        assert_eq!(metadata[MODE_HEADER], "GOVERNANCE");
+        let taken = ObjectRetention::take_from(&mut metadata).unwrap();
+        assert_eq!(taken.mode, ObjectLockMode::Governance);
+        assert_eq!(taken.retain_until, governance.retain_until.trunc_subsecs(3));
+        assert_eq!(metadata.len(), 1);
+        assert!(ObjectRetention::take_from(&mut metadata).is_none());
+    }
+
+    #[test]
+    fn test_default_retention() {
+        let now = "2024-02-29T12:00:00Z".parse::<DateTime<Utc>>().unwrap();
+        let days = DefaultRetention::new(ObjectLockMode::Governance, Some(30), None).unwrap();
+        let retention = days.retention_at(now);
+        assert_eq!(retention.mode, ObjectLockMode::Governance);
+        assert_eq!(retention.retain_until, "2024-03-30T12:00:00Z".parse::<DateTime<Utc>>().unwrap());
+
+        let years = DefaultRetention::new(ObjectLockMode::Compliance, None, Some(1)).unwrap();
+        assert_eq!(years.period, RetentionPeriod::Years(1));
+        assert_eq!(years.retention_at(now).retain_until, "2025-02-28T12:00:00Z".parse::<DateTime<Utc>>().unwrap());
+
+        for (days, years) in [(Some(1), Some(1)), (None, None), (Some(0), None), (None, Some(0))] {
+            assert!(matches!(
+                DefaultRetention::new(ObjectLockMode::Governance, days, years),
+                Err(ObjectIOError::InvalidArgument { .. })
+            ));
+        }
+    }
+}
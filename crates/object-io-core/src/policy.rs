@@ -0,0 +1,585 @@
+//! Bucket policy parsing and validation
+
+use crate::error::{ObjectIOError, Result};
+use crate::types::{BucketPolicy, PolicyEffect, PolicyStatement, Principal};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Policy language versions accepted in the `Version` element
+pub const POLICY_VERSIONS: &[&str] = &["2012-10-17", "2008-10-17"];
+
+/// Actions a bucket policy may name, besides wildcards
+pub const KNOWN_ACTIONS: &[&str] = &[
+    "s3:AbortMultipartUpload",
+    "s3:CreateBucket",
+    "s3:DeleteBucket",
+    "s3:DeleteBucketPolicy",
+    "s3:DeleteObject",
+    "s3:DeleteObjectVersion",
+    "s3:GetBucketAcl",
+    "s3:GetBucketLocation",
+    "s3:GetBucketPolicy",
+    "s3:GetBucketVersioning",
+    "s3:GetObject",
+    "s3:GetObjectAcl",
+    "s3:GetObjectVersion",
+    "s3:ListBucket",
+    "s3:ListBucketMultipartUploads",
+    "s3:ListBucketVersions",
+    "s3:ListMultipartUploadParts",
+    "s3:PutBucketAcl",
+    "s3:PutBucketPolicy",
+    "s3:PutBucketVersioning",
+    "s3:PutObject",
+    "s3:PutObjectAcl",
+];
+
+const S3_ARN_PREFIX: &str = "arn:aws:s3:::";
+const IAM_ARN_PREFIX: &str = "arn:aws:iam::";
+
+/// A single string or a list of strings, as policy elements allow either
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum OneOrMany {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl From<OneOrMany> for Vec<String> {
+    fn from(value: OneOrMany) -> Self {
+        match value {
+            OneOrMany::One(value) => vec![value],
+            OneOrMany::Many(values) => values,
+        }
+    }
+}
+
+/// Policy document as written in JSON
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase", deny_unknown_fields)]
+struct PolicyDocument {
+    version: Option<String>,
+    #[serde(rename = "Id")]
+    _id: Option<String>,
+    statement: StatementList,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StatementList {
+    One(Box<StatementDocument>),
+    Many(Vec<StatementDocument>),
+}
+
+/// Policy statement as written in JSON
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase", deny_unknown_fields)]
+struct StatementDocument {
+    sid: Option<String>,
+    effect: String,
+    principal: Option<PrincipalDocument>,
+    action: Option<OneOrMany>,
+    resource: Option<OneOrMany>,
+    condition: Option<HashMap<String, HashMap<String, serde_json::Value>>>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum PrincipalDocument {
+    Wildcard(String),
+    Typed(HashMap<String, OneOrMany>),
+}
+
+impl BucketPolicy {
+    /// Parse a policy document in the S3 JSON policy language
+    pub fn from_json(document: &str) -> Result<Self> {
+        let malformed = |message: String| ObjectIOError::MalformedPolicy { message };
+
+        let document: PolicyDocument =
+            serde_json::from_str(document).map_err(|e| malformed(e.to_string()))?;
+        let statements = match document.statement {
+            StatementList::One(statement) => vec![*statement],
+            StatementList::Many(statements) => statements,
+        };
+
+        let statements = statements
+            .into_iter()
+            .enumerate()
+            .map(|(i, statement)| {
+                parse_statement(statement).map_err(|message| malformed(format!("Statement {}: {}", i + 1, message)))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Self {
+            version: document.version.unwrap_or_default(),
+            statements,
+        })
+    }
+}
+
+fn parse_statement(statement: StatementDocument) -> std::result::Result<PolicyStatement, String> {
+    let effect = match statement.effect.as_str() {
+        "Allow" => PolicyEffect::Allow,
+        "Deny" => PolicyEffect::Deny,
+        other => return Err(format!("invalid Effect '{}', expected Allow or Deny", other)),
+    };
+
+    let principal = match statement.principal {
+        None => return Err("missing Principal".to_string()),
+        Some(PrincipalDocument::Wildcard(value)) if value == "*" => Principal::All,
+        Some(PrincipalDocument::Wildcard(value)) => {
+            return Err(format!("invalid Principal '{}', expected \"*\" or {{\"AWS\": ...}}", value))
+        }
+        Some(PrincipalDocument::Typed(mut principals)) => {
+            let aws: Vec<String> = match principals.remove("AWS") {
+                Some(aws) => aws.into(),
+                None => return Err("Principal must name AWS principals".to_string()),
+            };
+            if let Some(other) = principals.keys().next() {
+                return Err(format!("unsupported principal type '{}'", other));
+            }
+            if aws.iter().any(|principal| principal == "*") {
+                Principal::All
+            } else {
+                Principal::AWS(aws)
+            }
+        }
+    };
+
+    let action: Vec<String> = statement.action.ok_or("missing Action")?.into();
+    let resource: Vec<String> = statement.resource.ok_or("missing Resource")?.into();
+    if action.is_empty() {
+        return Err("Action must not be empty".to_string());
+    }
+    if resource.is_empty() {
+        return Err("Resource must not be empty".to_string());
+    }
+
+    Ok(PolicyStatement {
+        sid: statement.sid,
+        effect,
+        principal,
+        action,
+        resource,
+        condition: statement.condition,
+    })
+}
+
+/// How serious a policy validation finding is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PolicySeverity {
+    /// The policy would be rejected
+    Error,
+    /// The policy is accepted but probably does not do what was intended
+    Warning,
+}
+
+/// A single policy validation finding
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PolicyIssue {
+    pub severity: PolicySeverity,
+    /// 1-based index of the statement the finding applies to, if any
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub statement: Option<usize>,
+    pub message: String,
+}
+
+/// Result of validating a policy document without applying it
+#[derive(Debug, Clone)]
+pub struct PolicyValidation {
+    /// The parsed policy, if the document could be parsed
+    pub policy: Option<BucketPolicy>,
+    pub issues: Vec<PolicyIssue>,
+}
+
+impl PolicyValidation {
+    /// Whether the policy could be applied (warnings do not count)
+    pub fn is_valid(&self) -> bool {
+        self.policy.is_some() && !self.issues.iter().any(|issue| issue.severity == PolicySeverity::Error)
+    }
+}
+
+/// Parse and lint a policy document for a bucket
+///
+/// Checks the version, actions, resource ARNs and principals of every
+/// statement, and warns about statements a `Deny` makes ineffective,
+/// including one that denies everything to everyone.
+pub fn validate_policy(document: &str, bucket: &str) -> PolicyValidation {
+    let policy = match BucketPolicy::from_json(document) {
+        Ok(policy) => policy,
+        Err(e) => {
+            return PolicyValidation {
+                policy: None,
+                issues: vec![PolicyIssue {
+                    severity: PolicySeverity::Error,
+                    statement: None,
+                    message: match e {
+                        ObjectIOError::MalformedPolicy { message } => message,
+                        e => e.to_string(),
+                    },
+                }],
+            }
+        }
+    };
+
+    let mut issues = Vec::new();
+    let mut push = |severity, statement, message: String| {
+        issues.push(PolicyIssue {
+            severity,
+            statement,
+            message,
+        })
+    };
+
+    if policy.version.is_empty() {
+        push(
+            PolicySeverity::Warning,
+            None,
+            "Version is missing and defaults to 2008-10-17; use 2012-10-17".to_string(),
+        );
+    } else if !POLICY_VERSIONS.contains(&policy.version.as_str()) {
+        push(PolicySeverity::Error, None, format!("Unknown policy Version '{}'", policy.version));
+    }
+
+    let mut sids = HashMap::new();
+    for (i, statement) in policy.statements.iter().enumerate() {
+        let index = Some(i + 1);
+
+        if let Some(sid) = &statement.sid {
+            if let Some(first) = sids.insert(sid.as_str(), i + 1) {
+                push(
+                    PolicySeverity::Error,
+                    index,
+                    format!("Sid '{}' is already used by statement {}", sid, first),
+                );
+            }
+        }
+
+        if let Principal::AWS(principals) = &statement.principal {
+            for principal in principals {
+                if !is_valid_aws_principal(principal) {
+                    push(
+                        PolicySeverity::Error,
+                        index,
+                        format!("Invalid principal '{}', expected an account ID or IAM ARN", principal),
+                    );
+                }
+            }
+        }
+
+        for action in &statement.action {
+            if let Some(message) = check_action(action) {
+                push(PolicySeverity::Error, index, message);
+            }
+        }
+
+        for resource in &statement.resource {
+            if let Some(message) = check_resource(resource, bucket) {
+                push(PolicySeverity::Error, index, message);
+            }
+        }
+    }
+
+    for (d, deny) in policy.statements.iter().enumerate() {
+        if !matches!(deny.effect, PolicyEffect::Deny) || deny.condition.is_some() {
+            continue;
+        }
+
+        let everyone = matches!(deny.principal, Principal::All);
+        let all_actions = deny.action.iter().any(|action| action == "*" || action == "s3:*");
+        let whole_bucket = [format!("{}{}", S3_ARN_PREFIX, bucket), format!("{}{}/*", S3_ARN_PREFIX, bucket)]
+            .iter()
+            .all(|arn| deny.resource.iter().any(|resource| wildcard_match(resource, arn)));
+        if everyone && all_actions && whole_bucket {
+            push(
+                PolicySeverity::Warning,
+                Some(d + 1),
+                "Statement denies every action on the bucket to everyone, including administrators".to_string(),
+            );
+        }
+
+        for (a, allow) in policy.statements.iter().enumerate() {
+            if matches!(allow.effect, PolicyEffect::Allow) && covers(deny, allow) {
+                push(
+                    PolicySeverity::Warning,
+                    Some(a + 1),
+                    format!("Statement is entirely overridden by Deny statement {}", d + 1),
+                );
+            }
+        }
+    }
+
+    PolicyValidation {
+        policy: Some(policy),
+        issues,
+    }
+}
+
+fn is_valid_aws_principal(principal: &str) -> bool {
+    let is_account_id = |value: &str| value.len() == 12 && value.bytes().all(|b| b.is_ascii_digit());
+    match principal.strip_prefix(IAM_ARN_PREFIX) {
+        Some(rest) => match rest.split_once(':') {
+            Some((account, resource)) => is_account_id(account) && !resource.is_empty(),
+            None => false,
+        },
+        None => is_account_id(principal),
+    }
+}
+
+fn check_action(action: &str) -> Option<String> {
+    if action == "*" {
+        return None;
+    }
+    if !action.get(..3).is_some_and(|service| service.eq_ignore_ascii_case("s3:")) {
+        return Some(format!("Action '{}' is not an s3: action", action));
+    }
+
+    // Action names are case-insensitive
+    let pattern = action.to_ascii_lowercase();
+    if KNOWN_ACTIONS
+        .iter()
+        .any(|known| wildcard_match(&pattern, &known.to_ascii_lowercase()))
+    {
+        None
+    } else if pattern.contains(['*', '?']) {
+        Some(format!("Action '{}' matches no known action", action))
+    } else {
+        Some(format!("Unknown action '{}'", action))
+    }
+}
+
+fn check_resource(resource: &str, bucket: &str) -> Option<String> {
+    if resource == "*" {
+        return None;
+    }
+    let Some(path) = resource.strip_prefix(S3_ARN_PREFIX) else {
+        return Some(format!("Resource '{}' is not an S3 ARN (arn:aws:s3:::bucket[/key])", resource));
+    };
+
+    let resource_bucket = path.split('/').next().unwrap_or_default();
+    if resource_bucket.is_empty() {
+        Some(format!("Resource '{}' does not name a bucket", resource))
+    } else if !wildcard_match(resource_bucket, bucket) {
+        Some(format!("Resource '{}' is outside bucket '{}'", resource, bucket))
+    } else {
+        None
+    }
+}
+
+/// Whether an unconditional Deny statement blocks everything an Allow grants
+fn covers(deny: &PolicyStatement, allow: &PolicyStatement) -> bool {
+    let principals = match (&deny.principal, &allow.principal) {
+        (Principal::All, _) => true,
+        (Principal::AWS(_), Principal::All) => false,
+        (Principal::AWS(denied), Principal::AWS(allowed)) => allowed.iter().all(|p| denied.contains(p)),
+    };
+    let matches_any = |patterns: &[String], value: &str| {
+        patterns
+            .iter()
+            .any(|pattern| wildcard_match(&pattern.to_ascii_lowercase(), &value.to_ascii_lowercase()))
+    };
+
+    principals
+        && allow.action.iter().all(|action| matches_any(&deny.action, action))
+        && allow.resource.iter().all(|resource| matches_any(&deny.resource, resource))
+}
+
+/// Match a value against a policy pattern where `*` matches any run of
+/// characters and `?` any single character
+pub fn wildcard_match(pattern: &str, value: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let value: Vec<char> = value.chars().collect();
+    let (mut p, mut v) = (0, 0);
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    while v < value.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, v));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == value[v] => {
+                p += 1;
+                v += 1;
+            }
+            _ => match backtrack {
+                Some((star, matched)) => {
+                    p = star + 1;
+                    v = matched + 1;
+                    backtrack = Some((star, matched + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn messages(validation: &PolicyValidation, severity: PolicySeverity) -> Vec<(Option<usize>, String)> {
+        validation
+            .issues
+            .iter()
+            .filter(|issue| issue.severity == severity)
+            .map(|issue| (issue.statement, issue.message.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn test_wildcard_match() {
+        assert!(wildcard_match("*", ""));
+        assert!(wildcard_match("s3:Get*", "s3:GetObject"));
+        assert!(wildcard_match("s3:*Object", "s3:PutObject"));
+        assert!(wildcard_match("arn:aws:s3:::photos/*", "arn:aws:s3:::photos/2024/a.jpg"));
+        assert!(wildcard_match("log?", "logs"));
+        assert!(!wildcard_match("s3:Get*", "s3:PutObject"));
+        assert!(!wildcard_match("log?", "log"));
+        assert!(!wildcard_match("photos", "photos-2"));
+    }
+
+    #[test]
+    fn test_valid_policy() {
+        let validation = validate_policy(
+            r#"{
+                "Version": "2012-10-17",
+                "Statement": [{
+                    "Sid": "PublicRead",
+                    "Effect": "Allow",
+                    "Principal": "*",
+                    "Action": "s3:GetObject",
+                    "Resource": "arn:aws:s3:::photos/*"
+                }, {
+                    "Effect": "Allow",
+                    "Principal": {"AWS": ["123456789012", "arn:aws:iam::123456789012:user/alice"]},
+                    "Action": ["s3:Put*", "s3:ListBucket"],
+                    "Resource": ["arn:aws:s3:::photos", "arn:aws:s3:::photos/*"]
+                }]
+            }"#,
+            "photos",
+        );
+
+        assert!(validation.is_valid(), "{:?}", validation.issues);
+        assert!(validation.issues.is_empty());
+        let policy = validation.policy.unwrap();
+        assert_eq!(policy.statements.len(), 2);
+        assert!(matches!(policy.statements[0].principal, Principal::All));
+        assert!(matches!(&policy.statements[1].principal, Principal::AWS(p) if p.len() == 2));
+    }
+
+    #[test]
+    fn test_malformed_policy() {
+        for document in [
+            "not json",
+            r#"{"Version": "2012-10-17"}"#,
+            r#"{"Statement": {"Effect": "Permit", "Principal": "*", "Action": "s3:*", "Resource": "*"}}"#,
+            r#"{"Statement": {"Effect": "Allow", "Action": "s3:*", "Resource": "*"}}"#,
+            r#"{"Statement": {"Effect": "Allow", "Principal": {"Service": "x"}, "Action": "s3:*", "Resource": "*"}}"#,
+            r#"{"Statement": {"Effect": "Allow", "Principal": "*", "Actoin": "s3:*", "Resource": "*"}}"#,
+        ] {
+            let validation = validate_policy(document, "photos");
+            assert!(!validation.is_valid(), "{}", document);
+            assert!(validation.policy.is_none());
+            assert_eq!(messages(&validation, PolicySeverity::Error).len(), 1);
+        }
+
+        assert!(matches!(
+            BucketPolicy::from_json("{}"),
+            Err(ObjectIOError::MalformedPolicy { .. })
+        ));
+    }
+
+    #[test]
+    fn test_semantic_errors() {
+        let validation = validate_policy(
+            r#"{
+                "Version": "2012-10-17",
+                "Statement": [{
+                    "Sid": "One",
+                    "Effect": "Allow",
+                    "Principal": {"AWS": "alice"},
+                    "Action": ["s3:GetObjekt", "ec2:RunInstances", "s3:Fly*"],
+                    "Resource": ["arn:aws:s3:::other/*", "photos/*"]
+                }, {
+                    "Sid": "One",
+                    "Effect": "Allow",
+                    "Principal": "*",
+                    "Action": "s3:GetObject",
+                    "Resource": "arn:aws:s3:::photos/*"
+                }]
+            }"#,
+            "photos",
+        );
+
+        assert!(!validation.is_valid());
+        let errors = messages(&validation, PolicySeverity::Error);
+        assert_eq!(errors.len(), 7, "{:?}", errors);
+        assert!(errors.iter().all(|(statement, _)| statement.is_some()));
+        assert!(errors.iter().any(|(_, m)| m.contains("Invalid principal 'alice'")));
+        assert!(errors.iter().any(|(_, m)| m.contains("Unknown action 's3:GetObjekt'")));
+        assert!(errors.iter().any(|(_, m)| m.contains("not an s3: action")));
+        assert!(errors.iter().any(|(_, m)| m.contains("matches no known action")));
+        assert!(errors.iter().any(|(_, m)| m.contains("outside bucket")));
+        assert!(errors.iter().any(|(_, m)| m.contains("not an S3 ARN")));
+        assert!(errors.contains(&(Some(2), "Sid 'One' is already used by statement 1".to_string())));
+    }
+
+    #[test]
+    fn test_contradictions_and_lockout() {
+        let validation = validate_policy(
+            r#"{
+                "Statement": [{
+                    "Effect": "Allow",
+                    "Principal": {"AWS": "123456789012"},
+                    "Action": "s3:GetObject",
+                    "Resource": "arn:aws:s3:::photos/*"
+                }, {
+                    "Effect": "Deny",
+                    "Principal": "*",
+                    "Action": "s3:*",
+                    "Resource": ["arn:aws:s3:::photos", "arn:aws:s3:::photos/*"]
+                }]
+            }"#,
+            "photos",
+        );
+
+        // Warnings only: the policy can be applied, but probably should not be
+        assert!(validation.is_valid());
+        let warnings = messages(&validation, PolicySeverity::Warning);
+        assert_eq!(warnings.len(), 3, "{:?}", warnings);
+        assert!(warnings.iter().any(|(s, m)| s.is_none() && m.contains("Version is missing")));
+        assert!(warnings.iter().any(|(s, m)| *s == Some(2) && m.contains("including administrators")));
+        assert!(warnings.contains(&(Some(1), "Statement is entirely overridden by Deny statement 2".to_string())));
+
+        // A conditional or narrower Deny does not override the Allow
+        let validation = validate_policy(
+            r#"{
+                "Version": "2012-10-17",
+                "Statement": [{
+                    "Effect": "Allow",
+                    "Principal": "*",
+                    "Action": "s3:GetObject",
+                    "Resource": "arn:aws:s3:::photos/*"
+                }, {
+                    "Effect": "Deny",
+                    "Principal": "*",
+                    "Action": "s3:GetObject",
+                    "Resource": "arn:aws:s3:::photos/private/*"
+                }, {
+                    "Effect": "Deny",
+                    "Principal": "*",
+                    "Action": "s3:*",
+                    "Resource": "*",
+                    "Condition": {"Bool": {"aws:SecureTransport": "false"}}
+                }]
+            }"#,
+            "photos",
+        );
+        assert!(validation.is_valid());
+        assert!(validation.issues.is_empty(), "{:?}", validation.issues);
+    }
+}
@@ -13,8 +13,33 @@ pub struct Bucket {
     pub region: String,
     pub versioning: VersioningStatus,
     pub access_control: AccessControl,
+    /// Number of objects, as maintained by the metadata store
+    #[serde(default)]
+    pub object_count: u64,
+    /// Total size of all objects in bytes, as maintained by the metadata store
+    #[serde(default)]
+    pub total_size: u64,
+    /// Whether objects in the bucket may be given retention periods
+    #[serde(default)]
+    pub object_lock_enabled: bool,
+    /// Retention given to new objects that don't ask for their own
+    #[serde(default)]
+    pub default_retention: Option<crate::DefaultRetention>,
+    /// Patterns restricting the keys that may be written, if any
+    #[serde(default)]
+    pub key_patterns: Option<crate::KeyPatterns>,
 }
 
+/// Standard headers, besides `Content-Type`, recorded on PUT and returned
+/// on GET and HEAD
+pub const OBJECT_HEADERS: [&str; 5] = [
+    "cache-control",
+    "content-disposition",
+    "content-encoding",
+    "content-language",
+    "expires",
+];
+
 /// Represents an S3 object
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Object {
@@ -25,8 +50,54 @@ pub struct Object {
     pub last_modified: DateTime<Utc>,
     pub content_type: String,
     pub content_encoding: Option<String>,
+    #[serde(default)]
+    pub cache_control: Option<String>,
+    #[serde(default)]
+    pub content_disposition: Option<String>,
+    #[serde(default)]
+    pub content_language: Option<String>,
+    #[serde(default)]
+    pub expires: Option<String>,
     pub metadata: HashMap<String, String>,
     pub storage_class: StorageClass,
+    /// Grants beyond the owner's full control
+    #[serde(default)]
+    pub acl: Vec<Grant>,
+    /// Retention period set with object lock
+    #[serde(default)]
+    pub retention: Option<crate::ObjectRetention>,
+    /// Whether a legal hold keeps the object, whatever its retention
+    #[serde(default)]
+    pub legal_hold: bool,
+}
+
+impl Object {
+    /// Whether object lock keeps the object from being deleted or
+    /// overwritten at `now`, by a caller who may bypass `GOVERNANCE`
+    /// retention or not; a legal hold cannot be bypassed
+    pub fn is_locked(&self, now: DateTime<Utc>, bypass_governance: bool) -> bool {
+        self.legal_hold
+            || self.retention.as_ref().is_some_and(|retention| retention.protects(now, bypass_governance))
+    }
+
+    /// Value of one of the `OBJECT_HEADERS`, by lowercase name
+    pub fn header(&self, name: &str) -> Option<&str> {
+        match name {
+            "cache-control" => self.cache_control.as_deref(),
+            "content-disposition" => self.content_disposition.as_deref(),
+            "content-encoding" => self.content_encoding.as_deref(),
+            "content-language" => self.content_language.as_deref(),
+            "expires" => self.expires.as_deref(),
+            _ => None,
+        }
+    }
+
+    /// The `OBJECT_HEADERS` this object has, as name/value pairs
+    pub fn headers(&self) -> impl Iterator<Item = (&'static str, &str)> {
+        OBJECT_HEADERS
+            .into_iter()
+            .filter_map(|name| self.header(name).map(|value| (name, value)))
+    }
 }
 
 /// Object metadata summary (for listings)
@@ -39,6 +110,18 @@ pub struct ObjectSummary {
     pub storage_class: StorageClass,
 }
 
+/// What a storage backend knows of an object, found in one lookup
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ObjectHead {
+    pub size: u64,
+    /// `None` for backends that leave ETags to the metadata database, and
+    /// objects whose ETag the backend would have to hash the data for
+    pub etag: Option<String>,
+    pub last_modified: DateTime<Utc>,
+    /// Stored metadata, as from `get_object_metadata`
+    pub metadata: HashMap<String, String>,
+}
+
 /// Object information for head requests and metadata operations
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ObjectInfo {
@@ -106,24 +189,79 @@ pub struct AccessKey {
     pub created_at: DateTime<Utc>,
     pub last_used: Option<DateTime<Utc>>,
     pub status: AccessKeyStatus,
+    pub scope: Option<AccessKeyScope>,
 }
 
 /// Access key status
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum AccessKeyStatus {
     Active,
     Inactive,
 }
 
+/// Restriction of an access key to a single bucket and key prefix
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AccessKeyScope {
+    /// Bucket the key may access
+    pub bucket: String,
+    /// Key prefix the key may access (empty for the whole bucket)
+    #[serde(default)]
+    pub prefix: String,
+    /// Deny requests that modify objects
+    #[serde(default)]
+    pub read_only: bool,
+}
+
+impl AccessKeyScope {
+    /// Whether a request falls within this scope
+    ///
+    /// `key` is the object key, or the listing prefix for bucket-level reads;
+    /// `None` means the whole bucket. Bucket-level writes (create, delete,
+    /// configuration) are never in scope.
+    pub fn allows(&self, bucket: &str, key: Option<&str>, write: bool) -> bool {
+        if bucket != self.bucket || (write && self.read_only) {
+            return false;
+        }
+        match key {
+            Some(key) => key.starts_with(&self.prefix),
+            None => !write && self.prefix.is_empty(),
+        }
+    }
+}
+
 /// Access control grant
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Grant {
     pub grantee: Grantee,
     pub permission: Permission,
 }
 
+impl Grant {
+    pub fn new(grantee: Grantee, permission: Permission) -> Self {
+        Self { grantee, permission }
+    }
+}
+
+/// Whether `acl` gives `permission` to the user `user_id`, or to anonymous
+/// clients when `user_id` is `None`
+///
+/// Owners and admins have full control whatever the ACL says; callers check
+/// them first.
+pub fn acl_allows(acl: &[Grant], user_id: Option<&str>, permission: Permission) -> bool {
+    let user = user_id.and_then(|id| Uuid::parse_str(id).ok());
+    acl.iter().any(|grant| {
+        let grantee = match &grant.grantee {
+            Grantee::AllUsers => true,
+            Grantee::AuthenticatedUsers => user_id.is_some(),
+            Grantee::User(id) => user == Some(*id),
+            Grantee::Group(_) => false,
+        };
+        grantee && (grant.permission == permission || grant.permission == Permission::FullControl)
+    })
+}
+
 /// Grant recipient
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Grantee {
     User(Uuid),
     Group(String),
@@ -131,8 +269,24 @@ pub enum Grantee {
     AuthenticatedUsers,
 }
 
+impl Grantee {
+    /// S3 group URI for everyone, including anonymous clients
+    pub const ALL_USERS_URI: &'static str = "http://acs.amazonaws.com/groups/global/AllUsers";
+    /// S3 group URI for any authenticated client
+    pub const AUTHENTICATED_USERS_URI: &'static str = "http://acs.amazonaws.com/groups/global/AuthenticatedUsers";
+
+    /// Grantee for an S3 group URI, recognising the two predefined groups
+    pub fn group(uri: &str) -> Self {
+        match uri {
+            Self::ALL_USERS_URI => Self::AllUsers,
+            Self::AUTHENTICATED_USERS_URI => Self::AuthenticatedUsers,
+            _ => Self::Group(uri.to_string()),
+        }
+    }
+}
+
 /// Access permission
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Permission {
     Read,
     Write,
@@ -141,6 +295,88 @@ pub enum Permission {
     FullControl,
 }
 
+impl Permission {
+    /// Parse the name S3 uses in ACL documents, e.g. `READ_ACP`
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "READ" => Some(Self::Read),
+            "WRITE" => Some(Self::Write),
+            "READ_ACP" => Some(Self::ReadAcp),
+            "WRITE_ACP" => Some(Self::WriteAcp),
+            "FULL_CONTROL" => Some(Self::FullControl),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Read => "READ",
+            Self::Write => "WRITE",
+            Self::ReadAcp => "READ_ACP",
+            Self::WriteAcp => "WRITE_ACP",
+            Self::FullControl => "FULL_CONTROL",
+        }
+    }
+}
+
+/// S3 canned ACL, as sent in `x-amz-acl`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum CannedAcl {
+    #[default]
+    Private,
+    PublicRead,
+    PublicReadWrite,
+    AuthenticatedRead,
+    BucketOwnerRead,
+    BucketOwnerFullControl,
+}
+
+impl CannedAcl {
+    /// Request header carrying a canned ACL, and the metadata entry it is
+    /// passed to the metadata store under
+    pub const HEADER: &'static str = "x-amz-acl";
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "private" => Some(Self::Private),
+            "public-read" => Some(Self::PublicRead),
+            "public-read-write" => Some(Self::PublicReadWrite),
+            "authenticated-read" => Some(Self::AuthenticatedRead),
+            "bucket-owner-read" => Some(Self::BucketOwnerRead),
+            "bucket-owner-full-control" => Some(Self::BucketOwnerFullControl),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Private => "private",
+            Self::PublicRead => "public-read",
+            Self::PublicReadWrite => "public-read-write",
+            Self::AuthenticatedRead => "authenticated-read",
+            Self::BucketOwnerRead => "bucket-owner-read",
+            Self::BucketOwnerFullControl => "bucket-owner-full-control",
+        }
+    }
+
+    /// Grants the ACL adds to the owner's full control
+    ///
+    /// The `bucket-owner-*` ACLs add nothing, as objects here are always
+    /// owned by their bucket's owner.
+    pub fn grants(self) -> Vec<Grant> {
+        match self {
+            Self::Private | Self::BucketOwnerRead | Self::BucketOwnerFullControl => Vec::new(),
+            Self::PublicRead => vec![Grant::new(Grantee::AllUsers, Permission::Read)],
+            Self::PublicReadWrite => vec![
+                Grant::new(Grantee::AllUsers, Permission::Read),
+                Grant::new(Grantee::AllUsers, Permission::Write),
+            ],
+            Self::AuthenticatedRead => vec![Grant::new(Grantee::AuthenticatedUsers, Permission::Read)],
+        }
+    }
+}
+
 /// Bucket policy document
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BucketPolicy {
@@ -192,6 +428,89 @@ pub struct UploadPart {
     pub last_modified: DateTime<Utc>,
 }
 
+/// Part reference supplied when completing a multipart upload
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CompletedPart {
+    pub part_number: u32,
+    pub etag: String,
+}
+
+/// Preconditions a PUT places on the object it replaces
+///
+/// Backends check them atomically with the write: no other write to the key
+/// can land between the check and the new object taking its place. The
+/// default places none.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ConditionalPut {
+    /// Only replace an existing object whose ETag matches (`If-Match`)
+    pub if_match: Option<String>,
+    /// Only create the object if nothing is stored under the key
+    /// (`If-None-Match: *`)
+    pub if_none_match_star: bool,
+}
+
+impl ConditionalPut {
+    /// Whether the write goes ahead regardless of what is stored
+    pub fn is_unconditional(&self) -> bool {
+        self.if_match.is_none() && !self.if_none_match_star
+    }
+
+    /// Check the preconditions against the ETag of the object stored under
+    /// `bucket/key`, `None` if there is none
+    ///
+    /// `If-Match` on a missing object fails with `ObjectNotFound`, as in S3;
+    /// any other unmet precondition fails with `PreconditionFailed`.
+    pub fn check(&self, bucket: &str, key: &str, current_etag: Option<&str>) -> crate::Result<()> {
+        let failed = || crate::ObjectIOError::PreconditionFailed {
+            bucket: bucket.to_string(),
+            key: key.to_string(),
+        };
+        match (&self.if_match, current_etag) {
+            (Some(_), None) => {
+                return Err(crate::ObjectIOError::ObjectNotFound {
+                    bucket: bucket.to_string(),
+                    key: key.to_string(),
+                })
+            }
+            (Some(if_match), Some(etag)) if !crate::etag_matches(if_match, etag) => return Err(failed()),
+            _ => {}
+        }
+        if self.if_none_match_star && current_etag.is_some() {
+            return Err(failed());
+        }
+        Ok(())
+    }
+}
+
+/// List multipart uploads request parameters
+#[derive(Debug, Clone, Default)]
+pub struct ListUploadsRequest {
+    pub bucket: String,
+    pub prefix: Option<String>,
+    /// List uploads for keys after this one, or, with `upload_id_marker`,
+    /// uploads for this key after that upload ID too
+    pub key_marker: Option<String>,
+    pub upload_id_marker: Option<String>,
+    pub max_uploads: u32,
+}
+
+/// One page of in-progress multipart uploads, ordered by key then upload ID
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ListUploadsPage {
+    pub uploads: Vec<MultipartUpload>,
+    /// More uploads follow the last one returned
+    pub is_truncated: bool,
+}
+
+/// One page of an owner's buckets, in name order
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ListBucketsPage {
+    pub buckets: Vec<Bucket>,
+    /// Name of the last bucket returned when more follow; list again
+    /// starting after it for the next page
+    pub next_marker: Option<String>,
+}
+
 /// List objects request parameters
 #[derive(Debug, Clone, Default)]
 pub struct ListObjectsRequest {
@@ -215,3 +534,33 @@ pub struct ListObjectsResponse {
     pub objects: Vec<ObjectSummary>,
     pub common_prefixes: Vec<String>,
 }
+
+/// Number and total size of the objects under a key prefix
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PrefixUsage {
+    pub object_count: u64,
+    pub total_size: u64,
+}
+
+/// What happened to an object in a change feed event
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChangeOperation {
+    /// The object was created, overwritten or had its metadata replaced
+    Put,
+    Delete,
+}
+
+/// One entry of the change feed, the append-only log of object changes
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChangeEvent {
+    /// Position in the feed; every event is numbered one after the last
+    pub sequence: u64,
+    pub bucket: String,
+    pub key: String,
+    pub version_id: Option<String>,
+    /// ETag of the object put; `None` for deletes
+    pub etag: Option<String>,
+    pub operation: ChangeOperation,
+    pub timestamp: DateTime<Utc>,
+}
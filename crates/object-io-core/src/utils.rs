@@ -1,6 +1,7 @@
 //! Utility functions for ObjectIO
 
 use crate::error::{ObjectIOError, Result};
+use chrono::{DateTime, NaiveDateTime, SubsecRound, Utc};
 use sha2::{Digest, Sha256};
 use std::collections::HashMap;
 
@@ -29,6 +30,10 @@ pub fn validate_bucket_name(name: &str) -> Result<()> {
 }
 
 /// Validate S3 object key
+///
+/// Besides S3's own limits, a key may not start with `/` or have an empty,
+/// `.` or `..` segment between slashes: keys become paths under a bucket's
+/// directory, and such segments could name a path outside it.
 pub fn validate_object_key(key: &str) -> Result<()> {
     if key.is_empty() || key.len() > 1024 {
         return Err(ObjectIOError::InvalidObjectKey {
@@ -43,15 +48,143 @@ pub fn validate_object_key(key: &str) -> Result<()> {
         });
     }
 
+    if key.split('/').any(|segment| matches!(segment, "" | "." | "..")) {
+        return Err(ObjectIOError::InvalidObjectKey {
+            key: key.to_string(),
+        });
+    }
+
+    Ok(())
+}
+
+/// Largest total size of an object's user metadata, counting the bytes of
+/// each name (without its `x-amz-meta-` prefix) and value, as S3 allows
+pub const MAX_USER_METADATA_SIZE: usize = 2 * 1024;
+
+/// Validate user metadata given as `(name, value)` pairs, names without the
+/// `x-amz-meta-` prefix
+///
+/// Names must be non-empty printable ASCII, and names and values together
+/// must fit in `MAX_USER_METADATA_SIZE`.
+pub fn validate_user_metadata<'a>(entries: impl IntoIterator<Item = (&'a str, &'a str)>) -> Result<()> {
+    let mut size = 0;
+    for (name, value) in entries {
+        if name.is_empty() || !name.bytes().all(|b| b.is_ascii_graphic()) {
+            return Err(ObjectIOError::InvalidArgument {
+                message: format!("Invalid metadata name: {:?}", name),
+            });
+        }
+        size += name.len() + value.len();
+    }
+
+    if size > MAX_USER_METADATA_SIZE {
+        return Err(ObjectIOError::MetadataTooLarge {
+            size,
+            limit: MAX_USER_METADATA_SIZE,
+        });
+    }
     Ok(())
 }
 
+/// Incrementally computes an object's ETag (the hex SHA-256 of its data),
+/// so streamed data never has to be held in memory
+#[derive(Debug, Clone, Default)]
+pub struct EtagHasher {
+    hasher: Sha256,
+}
+
+impl EtagHasher {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add the next chunk of object data
+    pub fn update(&mut self, data: &[u8]) {
+        self.hasher.update(data);
+    }
+
+    /// Finish and format the ETag
+    pub fn finalize(self) -> String {
+        format!("{:x}", self.hasher.finalize())
+    }
+}
+
 /// Generate ETag for content
 pub fn generate_etag(content: &[u8]) -> String {
-    let mut hasher = Sha256::new();
+    let mut hasher = EtagHasher::new();
     hasher.update(content);
-    let result = hasher.finalize();
-    format!("{:x}", result)
+    hasher.finalize()
+}
+
+/// Strip an optional weak `W/` prefix and surrounding quotes from an ETag,
+/// leaving the bare form the server stores
+pub fn normalize_etag(etag: &str) -> &str {
+    let etag = etag.trim();
+    let etag = etag.strip_prefix("W/").unwrap_or(etag);
+    etag.trim_matches('"')
+}
+
+/// Compare two ETags in any mix of bare, quoted and weak forms
+pub fn etags_equal(a: &str, b: &str) -> bool {
+    normalize_etag(a) == normalize_etag(b)
+}
+
+/// Check an `If-Match`/`If-None-Match` value against an object's ETag
+///
+/// The condition is `*` (matches any ETag) or a comma-separated list of
+/// ETags, each of which may be quoted or weak.
+pub fn etag_matches(condition: &str, etag: &str) -> bool {
+    let condition = condition.trim();
+    condition == "*" || condition.split(',').any(|candidate| etags_equal(candidate, etag))
+}
+
+/// Guess a MIME type from an object key's extension, falling back to
+/// `application/octet-stream`
+pub fn guess_content_type(key: &str) -> &'static str {
+    let file_name = key.rsplit('/').next().unwrap_or(key);
+    let extension = match file_name.rsplit_once('.') {
+        Some((stem, ext)) if !stem.is_empty() => ext.to_ascii_lowercase(),
+        _ => return "application/octet-stream",
+    };
+
+    match extension.as_str() {
+        "txt" => "text/plain",
+        "csv" => "text/csv",
+        "html" | "htm" => "text/html",
+        "css" => "text/css",
+        "js" | "mjs" => "text/javascript",
+        "json" => "application/json",
+        "xml" => "application/xml",
+        "pdf" => "application/pdf",
+        "zip" => "application/zip",
+        "gz" => "application/gzip",
+        "tar" => "application/x-tar",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "svg" => "image/svg+xml",
+        "webp" => "image/webp",
+        "ico" => "image/x-icon",
+        "mp3" => "audio/mpeg",
+        "wav" => "audio/wav",
+        "mp4" => "video/mp4",
+        "webm" => "video/webm",
+        "wasm" => "application/wasm",
+        _ => "application/octet-stream",
+    }
+}
+
+/// Generate a new access key ID (20 uppercase alphanumeric characters)
+pub fn generate_access_key_id() -> String {
+    let id = uuid::Uuid::new_v4().simple().to_string().to_uppercase();
+    format!("AKIA{}", &id[..16])
+}
+
+/// Generate a new secret access key (40 characters)
+pub fn generate_secret_access_key() -> String {
+    let mut secret = uuid::Uuid::new_v4().simple().to_string();
+    secret.push_str(&uuid::Uuid::new_v4().simple().to_string()[..8]);
+    secret
 }
 
 /// Parse query parameters from URL
@@ -71,11 +204,69 @@ pub fn parse_query_params(query: &str) -> HashMap<String, String> {
         .collect()
 }
 
+/// Parse an `x-amz-copy-source` header (`[/]bucket/key[?versionId=...]`,
+/// URL-encoded) into its bucket and key
+pub fn parse_copy_source(source: &str) -> Option<(String, String)> {
+    let source = source.split('?').next().unwrap_or(source);
+    let source = urlencoding::decode(source).ok()?;
+    let source = source.strip_prefix('/').unwrap_or(&source);
+
+    let (bucket, key) = source.split_once('/')?;
+    if bucket.is_empty() || key.is_empty() {
+        return None;
+    }
+    Some((bucket.to_string(), key.to_string()))
+}
+
+/// Current time at the precision timestamps are stored with
+pub fn timestamp_now() -> DateTime<Utc> {
+    truncate_timestamp(Utc::now())
+}
+
+/// Truncate a timestamp to the stored precision of whole milliseconds, the
+/// finest S3 timestamps show, so a stored time matches what clients see
+pub fn truncate_timestamp(timestamp: DateTime<Utc>) -> DateTime<Utc> {
+    timestamp.trunc_subsecs(3)
+}
+
 /// Format timestamp for S3 responses
-pub fn format_s3_timestamp(timestamp: &chrono::DateTime<chrono::Utc>) -> String {
+pub fn format_s3_timestamp(timestamp: &DateTime<Utc>) -> String {
     timestamp.format("%Y-%m-%dT%H:%M:%S%.3fZ").to_string()
 }
 
+/// Parse a timestamp in the S3 response format, or any RFC 3339 timestamp
+pub fn parse_s3_timestamp(timestamp: &str) -> Result<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(timestamp)
+        .map(|t| t.with_timezone(&Utc))
+        .map_err(|e| ObjectIOError::InvalidArgument {
+            message: format!("Invalid timestamp '{}': {}", timestamp, e),
+        })
+}
+
+/// Format a timestamp as an HTTP date (RFC 9110 §5.6.7), which has whole
+/// seconds; the fraction is truncated, as S3 does for `Last-Modified`
+pub fn format_http_date(timestamp: &DateTime<Utc>) -> String {
+    timestamp.format("%a, %d %b %Y %H:%M:%S GMT").to_string()
+}
+
+/// Parse an HTTP date in any of the formats recipients must accept
+/// (RFC 9110 §5.6.7): IMF-fixdate, the obsolete RFC 850 format and asctime
+pub fn parse_http_date(date: &str) -> Result<DateTime<Utc>> {
+    const FORMATS: [&str; 3] = [
+        "%a, %d %b %Y %H:%M:%S GMT",
+        "%A, %d-%b-%y %H:%M:%S GMT",
+        "%a %b %e %H:%M:%S %Y",
+    ];
+    let date = date.trim();
+    FORMATS
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(date, format).ok())
+        .map(|date| date.and_utc())
+        .ok_or_else(|| ObjectIOError::InvalidArgument {
+            message: format!("Invalid HTTP date '{}'", date),
+        })
+}
+
 /// Parse content range header
 pub fn parse_content_range(range: &str) -> Option<(u64, Option<u64>)> {
     if !range.starts_with("bytes=") {
@@ -103,6 +294,75 @@ pub fn parse_content_range(range: &str) -> Option<(u64, Option<u64>)> {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_validate_user_metadata() {
+        let name = "n".repeat(48);
+        let at_limit = "v".repeat(MAX_USER_METADATA_SIZE - name.len());
+        assert!(validate_user_metadata([(name.as_str(), at_limit.as_str())]).is_ok());
+
+        let over_limit = format!("{}v", at_limit);
+        assert!(matches!(
+            validate_user_metadata([(name.as_str(), over_limit.as_str())]),
+            Err(ObjectIOError::MetadataTooLarge { size, .. }) if size == MAX_USER_METADATA_SIZE + 1
+        ));
+        // The limit covers all entries together
+        let half = "v".repeat(MAX_USER_METADATA_SIZE / 2);
+        assert!(validate_user_metadata([("a", half.as_str()), ("b", half.as_str())]).is_err());
+
+        for name in ["", "caf\u{e9}", "tab\tname", "new\nline", "with space"] {
+            assert!(
+                matches!(validate_user_metadata([(name, "v")]), Err(ObjectIOError::InvalidArgument { .. })),
+                "{:?}",
+                name
+            );
+        }
+        assert!(validate_user_metadata([("owner-id_2", "caf\u{e9} \t")]).is_ok());
+    }
+
+    #[test]
+    fn test_timestamp_precision() {
+        let stored = timestamp_now();
+        assert_eq!(stored.timestamp_subsec_nanos() % 1_000_000, 0);
+        assert_eq!(truncate_timestamp(stored), stored);
+
+        // The S3 format keeps every stored digit
+        let parsed = parse_s3_timestamp(&format_s3_timestamp(&stored)).unwrap();
+        assert_eq!(parsed, stored);
+
+        let timestamp = DateTime::parse_from_rfc3339("2024-02-29T23:59:59.987654321+02:00")
+            .unwrap()
+            .with_timezone(&Utc);
+        assert_eq!(format_s3_timestamp(&truncate_timestamp(timestamp)), "2024-02-29T21:59:59.987Z");
+        assert_eq!(format_http_date(&timestamp), "Thu, 29 Feb 2024 21:59:59 GMT");
+
+        assert!(matches!(
+            parse_s3_timestamp("yesterday"),
+            Err(ObjectIOError::InvalidArgument { .. })
+        ));
+    }
+
+    #[test]
+    fn test_parse_http_date() {
+        let expected = DateTime::parse_from_rfc3339("1994-11-06T08:49:37Z").unwrap().with_timezone(&Utc);
+        for date in [
+            "Sun, 06 Nov 1994 08:49:37 GMT",
+            "Sunday, 06-Nov-94 08:49:37 GMT",
+            "Sun Nov  6 08:49:37 1994",
+        ] {
+            assert_eq!(parse_http_date(date).unwrap(), expected, "{}", date);
+        }
+        let timestamp = timestamp_now();
+        assert_eq!(parse_http_date(&format_http_date(&timestamp)).unwrap(), timestamp.trunc_subsecs(0));
+
+        for date in ["", "1994-11-06T08:49:37Z", "Sun, 06 Nov 1994 08:49:37 PST", "Sun, 31 Nov 1994 08:49:37 GMT"] {
+            assert!(
+                matches!(parse_http_date(date), Err(ObjectIOError::InvalidArgument { .. })),
+                "{}",
+                date
+            );
+        }
+    }
+
     #[test]
     fn test_validate_bucket_name() {
         assert!(validate_bucket_name("valid-bucket-name").is_ok());
@@ -121,6 +381,12 @@ mod tests {
         
         assert!(validate_object_key("").is_err());
         assert!(validate_object_key("invalid\0key").is_err());
+
+        // Segments that could leave the bucket's directory
+        for key in ["../escaped.txt", "a/../../b", "./a", "a/./b", "/etc/passwd", "a//b", "folder/", ".."] {
+            assert!(validate_object_key(key).is_err(), "{}", key);
+        }
+        assert!(validate_object_key("a..b/.hidden/c.").is_ok());
     }
 
     #[test]
@@ -131,6 +397,85 @@ mod tests {
         assert_eq!(etag.len(), 64); // SHA256 hex length
     }
 
+    #[test]
+    fn test_etag_hasher_matches_generate_etag() {
+        let content: Vec<u8> = (0..100_000u32).map(|i| (i % 251) as u8).collect();
+        for chunk_size in [1, 7, 4096, content.len()] {
+            let mut hasher = EtagHasher::new();
+            for chunk in content.chunks(chunk_size) {
+                hasher.update(chunk);
+            }
+            assert_eq!(hasher.finalize(), generate_etag(&content), "chunk size {}", chunk_size);
+        }
+        assert_eq!(EtagHasher::new().finalize(), generate_etag(b""));
+    }
+
+    #[test]
+    fn test_etag_comparison() {
+        assert_eq!(normalize_etag("abc"), "abc");
+        assert_eq!(normalize_etag("\"abc\""), "abc");
+        assert_eq!(normalize_etag("W/\"abc\""), "abc");
+        assert_eq!(normalize_etag(" \"abc-2\" "), "abc-2");
+
+        assert!(etags_equal("\"abc\"", "abc"));
+        assert!(etags_equal("W/\"abc\"", "\"abc\""));
+        assert!(!etags_equal("\"abc\"", "abd"));
+
+        // Quoted, weak and wildcard conditions against a bare stored ETag
+        assert!(etag_matches("\"abc\"", "abc"));
+        assert!(etag_matches("W/\"abc\"", "abc"));
+        assert!(etag_matches("*", "abc"));
+        assert!(etag_matches("\"xyz\", W/\"abc\"", "abc"));
+        assert!(!etag_matches("\"xyz\", \"def\"", "abc"));
+        assert!(!etag_matches("", "abc"));
+    }
+
+    #[test]
+    fn test_guess_content_type() {
+        let cases = [
+            ("notes.txt", "text/plain"),
+            ("data/config.json", "application/json"),
+            ("site/index.html", "text/html"),
+            ("styles/main.CSS", "text/css"),
+            ("app.js", "text/javascript"),
+            ("images/logo.png", "image/png"),
+            ("photos/IMG_001.JPG", "image/jpeg"),
+            ("docs/report.pdf", "application/pdf"),
+            ("videos/clip.mp4", "video/mp4"),
+            ("backup.zip", "application/zip"),
+            ("archive.tar.gz", "application/gzip"),
+            ("no-extension", "application/octet-stream"),
+            (".hidden", "application/octet-stream"),
+            ("dir.d/file", "application/octet-stream"),
+            ("blob.unknown", "application/octet-stream"),
+        ];
+
+        for (key, expected) in cases {
+            assert_eq!(guess_content_type(key), expected, "key: {}", key);
+        }
+    }
+
+    #[test]
+    fn test_generate_access_keys() {
+        let access_key = generate_access_key_id();
+        assert_eq!(access_key.len(), 20);
+        assert!(access_key.starts_with("AKIA"));
+        assert!(access_key.chars().all(|c| c.is_ascii_uppercase() || c.is_ascii_digit()));
+
+        assert_eq!(generate_secret_access_key().len(), 40);
+        assert_ne!(generate_secret_access_key(), generate_secret_access_key());
+    }
+
+    #[test]
+    fn test_parse_copy_source() {
+        let expected = Some(("bucket".to_string(), "dir/my file.txt".to_string()));
+        assert_eq!(parse_copy_source("/bucket/dir/my%20file.txt"), expected);
+        assert_eq!(parse_copy_source("bucket/dir/my%20file.txt"), expected);
+        assert_eq!(parse_copy_source("bucket/dir/my%20file.txt?versionId=v1"), expected);
+        assert_eq!(parse_copy_source("bucket"), None);
+        assert_eq!(parse_copy_source("/bucket/"), None);
+    }
+
     #[test]
     fn test_parse_content_range() {
         assert_eq!(parse_content_range("bytes=0-499"), Some((0, Some(499))));
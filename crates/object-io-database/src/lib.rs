@@ -9,10 +9,12 @@ use std::path::Path;
 use std::sync::Arc;
 use tracing::{debug, info, instrument};
 
+pub mod migrations;
 pub mod models;
 pub mod operations;
 
-pub use models::{BucketInfo, ObjectInfo, UserInfo};
+pub use migrations::SCHEMA_VERSION;
+pub use models::{AccessKeyInfo, BucketAcl, BucketInfo, ObjectInfo, UserInfo};
 pub use operations::*;
 
 /// ObjectIO embedded database
@@ -22,10 +24,21 @@ pub struct ObjectDB {
     db: Arc<sled::Db>,
     /// Buckets tree
     buckets: sled::Tree,
-    /// Objects tree  
+    /// Objects tree, keyed `bucket:key`. Bucket names cannot contain ':', so
+    /// this orders records by (bucket, key) and serves as the index for
+    /// bucket and prefix listings.
     objects: sled::Tree,
     /// Users tree
     users: sled::Tree,
+    /// Access key to user ID index
+    access_keys: sled::Tree,
+    /// Delete markers of versioned buckets, keyed `bucket:key` like objects
+    delete_markers: sled::Tree,
+    /// Change feed, keyed by big-endian sequence number so it iterates in
+    /// the order changes were made
+    changes: sled::Tree,
+    /// Database-wide records such as the schema version
+    meta: sled::Tree,
 }
 
 impl ObjectDB {
@@ -39,6 +52,10 @@ impl ObjectDB {
         let buckets = db.open_tree("buckets")?;
         let objects = db.open_tree("objects")?;
         let users = db.open_tree("users")?;
+        let access_keys = db.open_tree("access_keys")?;
+        let delete_markers = db.open_tree("delete_markers")?;
+        let changes = db.open_tree("changes")?;
+        let meta = db.open_tree("meta")?;
         
         debug!("Database trees initialized successfully");
         
@@ -47,6 +64,10 @@ impl ObjectDB {
             buckets,
             objects,
             users,
+            access_keys,
+            delete_markers,
+            changes,
+            meta,
         })
     }
     
@@ -58,12 +79,20 @@ impl ObjectDB {
         let buckets = db.open_tree("buckets")?;
         let objects = db.open_tree("objects")?;
         let users = db.open_tree("users")?;
+        let access_keys = db.open_tree("access_keys")?;
+        let delete_markers = db.open_tree("delete_markers")?;
+        let changes = db.open_tree("changes")?;
+        let meta = db.open_tree("meta")?;
         
         Ok(Self {
             db: Arc::new(db),
             buckets,
             objects,
             users,
+            access_keys,
+            delete_markers,
+            changes,
+            meta,
         })
     }
     
@@ -123,4 +152,409 @@ mod tests {
         let db = ObjectDB::memory().expect("Failed to create in-memory database");
         db.flush().await.expect("Failed to flush database");
     }
+    
+    #[tokio::test]
+    async fn test_access_key_rotation() {
+        let db = ObjectDB::memory().expect("Failed to create in-memory database");
+        let user = UserInfo::new(
+            "user-1".to_string(),
+            "OLDKEY".to_string(),
+            "old-secret".to_string(),
+            "Test User".to_string(),
+            "test@example.com".to_string(),
+        );
+        db.create_user(user).await.expect("Failed to create user");
+        
+        // Add a second key and look the user up by either one
+        let mut user = db.get_user("user-1").await.unwrap().unwrap();
+        user.access_keys.push(AccessKeyInfo::new("NEWKEY".to_string(), "new-secret".to_string()));
+        db.update_user(user).await.expect("Failed to add access key");
+        assert_eq!(db.get_user_by_access_key("OLDKEY").await.unwrap().unwrap().user_id, "user-1");
+        assert_eq!(db.get_user_by_access_key("NEWKEY").await.unwrap().unwrap().user_id, "user-1");
+        
+        // Removing the old key drops it from the index
+        let mut user = db.get_user("user-1").await.unwrap().unwrap();
+        user.access_keys.retain(|key| key.access_key != "OLDKEY");
+        db.update_user(user).await.expect("Failed to remove access key");
+        assert!(db.get_user_by_access_key("OLDKEY").await.unwrap().is_none());
+        assert!(db.get_user_by_access_key("NEWKEY").await.unwrap().is_some());
+        
+        // Recording a use stamps both the key and the user
+        let used_at = chrono::Utc::now();
+        assert!(db.record_access_key_use("NEWKEY", used_at).await.unwrap());
+        let user = db.get_user("user-1").await.unwrap().unwrap();
+        assert_eq!(user.access_key("NEWKEY").unwrap().last_used, Some(used_at));
+        assert_eq!(user.last_access, Some(used_at));
+        
+        assert!(db.delete_user("NEWKEY").await.unwrap());
+        assert!(db.get_user("user-1").await.unwrap().is_none());
+        assert!(db.get_user_by_access_key("NEWKEY").await.unwrap().is_none());
+    }
+    
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_concurrent_access_key_changes() {
+        let db = ObjectDB::memory().expect("Failed to create in-memory database");
+        let user = UserInfo::new(
+            "user-1".to_string(),
+            "FIRSTKEY".to_string(),
+            "secret".to_string(),
+            "Test User".to_string(),
+            "test@example.com".to_string(),
+        );
+        db.create_user(user).await.unwrap();
+        
+        // Keys added at once are all kept, each in the record and the index
+        let tasks: Vec<_> = (0..20)
+            .map(|i| {
+                let db = db.clone();
+                tokio::spawn(async move {
+                    db.modify_user("user-1", |user| {
+                        user.access_keys.push(AccessKeyInfo::new(format!("KEY{}", i), "secret".to_string()));
+                        Ok(())
+                    })
+                    .await
+                })
+            })
+            .collect();
+        for task in tasks {
+            task.await.unwrap().unwrap();
+        }
+        let user = db.get_user("user-1").await.unwrap().unwrap();
+        assert_eq!(user.access_keys.len(), 21);
+        for i in 0..20 {
+            let access_key = format!("KEY{}", i);
+            assert_eq!(db.get_user_by_access_key(&access_key).await.unwrap().unwrap().user_id, "user-1");
+        }
+        
+        // A failed change leaves the record and index alone
+        let result = db
+            .modify_user("user-1", |user| {
+                user.access_keys.clear();
+                Err::<(), _>(object_io_core::ObjectIOError::InvalidRequest {
+                    message: "refused".to_string(),
+                })
+            })
+            .await;
+        assert!(result.is_err());
+        assert!(db.get_user_by_access_key("FIRSTKEY").await.unwrap().is_some());
+        
+        // Keys of another user, or of no user, are refused
+        let other = UserInfo::new(
+            "user-2".to_string(),
+            "KEY3".to_string(),
+            "secret".to_string(),
+            "Other".to_string(),
+            "other@example.com".to_string(),
+        );
+        let err = db.create_user(other).await.unwrap_err();
+        assert!(matches!(
+            object_io_core::ObjectIOError::from(err),
+            object_io_core::ObjectIOError::AccessKeyAlreadyExists { .. }
+        ));
+        assert!(db.get_user("user-2").await.unwrap().is_none());
+        let err = db.modify_user("missing", |_| Ok(())).await.unwrap_err();
+        assert!(matches!(
+            object_io_core::ObjectIOError::from(err),
+            object_io_core::ObjectIOError::UserNotFound { .. }
+        ));
+    }
+    
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_recording_key_use_keeps_concurrent_changes() {
+        let db = ObjectDB::memory().expect("Failed to create in-memory database");
+        let mut user = UserInfo::new(
+            "user-1".to_string(),
+            "USEDKEY".to_string(),
+            "secret".to_string(),
+            "Test User".to_string(),
+            "test@example.com".to_string(),
+        );
+        user.access_keys.push(AccessKeyInfo::new("REVOKEDKEY".to_string(), "secret".to_string()));
+        db.create_user(user).await.unwrap();
+        
+        // Uses recorded while another key is switched on and off must not
+        // bring back the state from before a switch
+        let recorder = {
+            let db = db.clone();
+            tokio::spawn(async move {
+                for _ in 0..500 {
+                    db.record_access_key_use("USEDKEY", chrono::Utc::now()).await.unwrap();
+                }
+            })
+        };
+        for active in (0..100).map(|i| i % 2 == 0).chain([false]) {
+            let mut user = db.get_user("user-1").await.unwrap().unwrap();
+            user.access_key_mut("REVOKEDKEY").unwrap().active = active;
+            db.update_user(user).await.unwrap();
+            tokio::task::yield_now().await;
+        }
+        recorder.await.unwrap();
+        
+        let user = db.get_user("user-1").await.unwrap().unwrap();
+        assert!(!user.access_key("REVOKEDKEY").unwrap().active);
+        assert!(user.access_key("USEDKEY").unwrap().last_used.is_some());
+        assert!(!db.record_access_key_use("MISSINGKEY", chrono::Utc::now()).await.unwrap());
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_concurrent_bucket_stats() {
+        let db = ObjectDB::memory().expect("Failed to create in-memory database");
+        db.create_bucket(BucketInfo::new("stats".to_string(), "owner".to_string(), "us-east-1".to_string()))
+            .await
+            .unwrap();
+        let stats = |bucket: BucketInfo| (bucket.object_count, bucket.total_size);
+
+        // Writes and deletes racing on one bucket each count exactly once
+        let tasks: Vec<_> = (0..20u64)
+            .map(|i| {
+                let db = db.clone();
+                tokio::spawn(async move {
+                    let key = format!("key-{}", i);
+                    let object = ObjectInfo::new(key.clone(), "stats".to_string(), i + 1, "text/plain".to_string(), "etag".to_string());
+                    db.put_object(object).await.unwrap();
+                    if i % 2 == 0 {
+                        db.delete_object("stats", &key).await.unwrap();
+                    }
+                })
+            })
+            .collect();
+        for task in tasks {
+            task.await.unwrap();
+        }
+        let kept_size = (0..20u64).filter(|i| i % 2 == 1).map(|i| i + 1).sum::<u64>();
+        assert_eq!(stats(db.get_bucket("stats").await.unwrap().unwrap()), (10, kept_size));
+
+        // Emptying the bucket takes every object out of its statistics
+        db.delete_all_objects_in_bucket("stats").await.unwrap();
+        assert_eq!(stats(db.get_bucket("stats").await.unwrap().unwrap()), (0, 0));
+    }
+
+    #[tokio::test]
+    async fn test_bucket_versioning_and_legacy_migration() {
+        use models::{BucketAcl, LegacyBucketInfo};
+        use object_io_core::VersioningStatus;
+        
+        let db = ObjectDB::memory().expect("Failed to create in-memory database");
+        
+        // A record written before versioning status was stored
+        let now = chrono::Utc::now();
+        let legacy = LegacyBucketInfo {
+            name: "old-bucket".to_string(),
+            created_at: now,
+            updated_at: now,
+            owner: "owner".to_string(),
+            acl: BucketAcl::default(),
+            region: "us-east-1".to_string(),
+            versioning_enabled: false,
+            object_count: 3,
+            total_size: 42,
+        };
+        db.buckets.insert("old-bucket", bincode::serialize(&legacy).unwrap()).unwrap();
+        
+        // Legacy records read as unversioned, then migrate in place
+        let bucket = db.get_bucket("old-bucket").await.unwrap().unwrap();
+        assert_eq!(bucket.versioning, VersioningStatus::Unversioned);
+        assert_eq!(bucket.total_size, 42);
+        assert_eq!(db.migrate_buckets().await.unwrap(), 1);
+        assert_eq!(db.migrate_buckets().await.unwrap(), 0);
+        
+        assert!(db.set_bucket_versioning("old-bucket", VersioningStatus::Enabled).await.unwrap());
+        let bucket = db.get_bucket("old-bucket").await.unwrap().unwrap();
+        assert_eq!(bucket.versioning, VersioningStatus::Enabled);
+        assert_eq!(bucket.object_count, 3);
+        
+        assert!(!db.set_bucket_versioning("missing", VersioningStatus::Enabled).await.unwrap());
+
+        // Object lock can be enabled on existing buckets
+        assert!(!bucket.object_lock_enabled);
+        assert!(bucket.default_retention.is_none());
+        let default_retention = object_io_core::DefaultRetention::new(
+            object_io_core::ObjectLockMode::Governance,
+            Some(7),
+            None,
+        )
+        .unwrap();
+        assert!(db.enable_bucket_object_lock("old-bucket", Some(default_retention)).await.unwrap());
+        let bucket = db.get_bucket("old-bucket").await.unwrap().unwrap();
+        assert!(bucket.object_lock_enabled);
+        assert_eq!(bucket.default_retention, Some(default_retention));
+        assert_eq!(bucket.versioning, VersioningStatus::Enabled);
+        assert!(!db.enable_bucket_object_lock("missing", None).await.unwrap());
+
+        // So can key patterns, which keep the rest of the record
+        assert!(bucket.key_patterns.is_none());
+        let key_patterns = object_io_core::KeyPatterns {
+            allow: vec!["uploads/*".to_string()],
+            deny: vec!["*.exe".to_string()],
+        };
+        assert!(db.set_bucket_key_patterns("old-bucket", Some(key_patterns.clone())).await.unwrap());
+        let bucket = db.get_bucket("old-bucket").await.unwrap().unwrap();
+        assert_eq!(bucket.key_patterns, Some(key_patterns));
+        assert_eq!(bucket.default_retention, Some(default_retention));
+        assert!(!db.set_bucket_key_patterns("missing", None).await.unwrap());
+    }
+    
+    #[tokio::test]
+    async fn test_access_key_scope_and_legacy_users() {
+        use models::{PreScopeAccessKeyInfo, PreScopeUserInfo, UserPermissions};
+        use object_io_core::AccessKeyScope;
+        
+        let db = ObjectDB::memory().expect("Failed to create in-memory database");
+        
+        // A record written before access key scopes were stored
+        let now = chrono::Utc::now();
+        let legacy = PreScopeUserInfo {
+            user_id: "user-1".to_string(),
+            access_keys: vec![PreScopeAccessKeyInfo {
+                access_key: "OLDKEY".to_string(),
+                secret_key_hash: "secret".to_string(),
+                active: true,
+                created_at: now,
+                last_used: None,
+            }],
+            display_name: "Legacy User".to_string(),
+            email: "legacy@example.com".to_string(),
+            active: true,
+            created_at: now,
+            last_access: None,
+            permissions: UserPermissions::default(),
+        };
+        db.users.insert("user-1", bincode::serialize(&legacy).unwrap()).unwrap();
+        db.access_keys.insert("OLDKEY", "user-1").unwrap();
+        
+        // Legacy keys are unscoped and can be scoped through a normal update
+        let mut user = db.get_user_by_access_key("OLDKEY").await.unwrap().unwrap();
+        assert_eq!(user.display_name, "Legacy User");
+        assert!(user.access_key("OLDKEY").unwrap().scope.is_none());
+        
+        let scope = AccessKeyScope {
+            bucket: "reports-bucket".to_string(),
+            prefix: "reports/".to_string(),
+            read_only: true,
+        };
+        user.access_key_mut("OLDKEY").unwrap().scope = Some(scope.clone());
+        db.update_user(user).await.expect("Failed to scope access key");
+        
+        let user = db.get_user("user-1").await.unwrap().unwrap();
+        assert_eq!(user.access_key("OLDKEY").unwrap().scope, Some(scope));
+        assert_eq!(db.list_users().await.unwrap().len(), 1);
+    }
+    
+    #[tokio::test]
+    async fn test_legacy_object_records() {
+        use models::{LegacyObjectInfo, StorageClass};
+        use std::collections::HashMap;
+        
+        let db = ObjectDB::memory().expect("Failed to create in-memory database");
+        
+        // A record written before the Expires header was stored
+        let now = chrono::Utc::now();
+        let legacy = LegacyObjectInfo {
+            key: "app.css".to_string(),
+            bucket: "assets".to_string(),
+            size: 10,
+            content_type: "text/css".to_string(),
+            etag: "etag".to_string(),
+            last_modified: now,
+            created_at: now,
+            metadata: HashMap::new(),
+            storage_class: StorageClass::Standard,
+            version_id: None,
+            is_delete_marker: false,
+            content_encoding: None,
+            content_language: None,
+            cache_control: Some("max-age=60".to_string()),
+            content_disposition: None,
+        };
+        db.objects.insert("assets:app.css", bincode::serialize(&legacy).unwrap()).unwrap();
+        
+        let object = db.get_object("assets", "app.css").await.unwrap().unwrap();
+        assert_eq!(object.cache_control.as_deref(), Some("max-age=60"));
+        assert!(object.expires.is_none());
+        assert!(object.acl.is_empty());
+        assert!(object.retention.is_none());
+        assert!(!object.legal_hold);
+        assert_eq!(db.list_objects("assets", None).await.unwrap().len(), 1);
+        
+        // Updating rewrites the record in the current layout
+        let updated = db
+            .update_object_metadata("assets", "app.css", |object| {
+                object.expires = Some("Thu, 01 Dec 2044 16:00:00 GMT".to_string());
+                object.retention = Some(object_io_core::ObjectRetention {
+                    mode: object_io_core::ObjectLockMode::Compliance,
+                    retain_until: now,
+                });
+                object.legal_hold = true;
+            })
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(updated.size, 10);
+        let object = db.get_object("assets", "app.css").await.unwrap().unwrap();
+        assert_eq!(object.expires.as_deref(), Some("Thu, 01 Dec 2044 16:00:00 GMT"));
+        assert_eq!(object.retention.unwrap().retain_until, now);
+        assert!(object.legal_hold);
+    }
+    
+    #[tokio::test]
+    async fn test_list_objects_by_prefix() {
+        let db = ObjectDB::memory().expect("Failed to create in-memory database");
+        let objects = [("logs", "2024/a"), ("logs", "2024/b"), ("logs", "2025/a"), ("logs2", "2024/c"), ("logs", "x:y")];
+        for (bucket, key) in objects {
+            let info = ObjectInfo::new(key.to_string(), bucket.to_string(), 1, "text/plain".to_string(), "etag".to_string());
+            db.put_object(info).await.unwrap();
+        }
+        
+        let keys = |objects: Vec<ObjectInfo>| objects.into_iter().map(|o| o.key).collect::<Vec<_>>();
+        assert_eq!(keys(db.list_objects("logs", Some("2024/")).await.unwrap()), ["2024/a", "2024/b"]);
+        assert_eq!(keys(db.list_objects("logs", None).await.unwrap()), ["2024/a", "2024/b", "2025/a", "x:y"]);
+        assert_eq!(keys(db.list_objects("logs", Some("x:")).await.unwrap()), ["x:y"]);
+        assert!(db.list_objects("log", None).await.unwrap().is_empty());
+
+        let usage = |object_count, total_size| object_io_core::PrefixUsage { object_count, total_size };
+        assert_eq!(db.prefix_usage("logs", "2024/").await.unwrap(), usage(2, 2));
+        assert_eq!(db.prefix_usage("logs", "").await.unwrap(), usage(4, 4));
+        assert_eq!(db.prefix_usage("log", "").await.unwrap(), usage(0, 0));
+    }
+    
+    #[tokio::test]
+    async fn test_change_feed() {
+        use object_io_core::ChangeOperation;
+        
+        let db = ObjectDB::memory().expect("Failed to create in-memory database");
+        let object = |key: &str, etag: &str| {
+            ObjectInfo::new(key.to_string(), "logs".to_string(), 1, "text/plain".to_string(), etag.to_string())
+        };
+        db.put_object(object("a", "etag-1")).await.unwrap();
+        db.put_objects("logs", &[object("a", "etag-2"), object("b", "etag-3")]).await.unwrap();
+        db.update_object_metadata("logs", "b", |info| info.cache_control = Some("no-cache".to_string()))
+            .await
+            .unwrap();
+        db.delete_object("logs", "a").await.unwrap();
+        // Deleting a missing key changes nothing
+        db.delete_object("logs", "a").await.unwrap();
+        db.delete_all_objects_in_bucket("logs").await.unwrap();
+        
+        let events = db.list_changes(0, 100).await.unwrap();
+        let summary = events
+            .iter()
+            .map(|event| (event.sequence, event.key.as_str(), event.operation, event.etag.as_deref()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            summary,
+            [
+                (1, "a", ChangeOperation::Put, Some("etag-1")),
+                (2, "a", ChangeOperation::Put, Some("etag-2")),
+                (3, "b", ChangeOperation::Put, Some("etag-3")),
+                (4, "b", ChangeOperation::Put, Some("etag-3")),
+                (5, "a", ChangeOperation::Delete, None),
+                (6, "b", ChangeOperation::Delete, None),
+            ]
+        );
+        
+        // Resuming from a sequence number continues right after it
+        let page = db.list_changes(2, 2).await.unwrap();
+        assert_eq!(page.iter().map(|event| event.sequence).collect::<Vec<_>>(), [3, 4]);
+        assert!(db.list_changes(6, 100).await.unwrap().is_empty());
+    }
 }
@@ -0,0 +1,263 @@
+//! Schema versioning and migrations
+//!
+//! The schema version is stored in the `meta` tree. Databases written before
+//! it was recorded are version 1; on startup [`ObjectDB::migrate`] applies
+//! every newer migration in order, recording the version after each so an
+//! interrupted run resumes where it stopped.
+
+use crate::{models::*, ObjectDB};
+use anyhow::{anyhow, bail, Result};
+use serde::{de::DeserializeOwned, Serialize};
+use sled::transaction::{TransactionError, Transactional};
+use tracing::{info, instrument};
+
+/// Schema version written by this release
+pub const SCHEMA_VERSION: u32 = 8;
+
+/// Key of the schema version record in the `meta` tree
+const SCHEMA_VERSION_KEY: &str = "schema_version";
+
+/// Migrations in the order they apply: the version each one brings the
+/// database to, and what it does
+pub const MIGRATIONS: &[(u32, &str)] = &[
+    (2, "Rewrite bucket records in the current layout"),
+    (3, "Rewrite object records in the current layout"),
+    (4, "Re-key user records by user ID and rewrite them in the current layout"),
+    (5, "Rewrite bucket and object records with object lock state"),
+    (6, "Rewrite object records with legal hold state"),
+    (7, "Rewrite bucket records with default retention"),
+    (8, "Rewrite bucket records with key patterns"),
+];
+
+/// Rewrite every record in `tree` that only `decode` can read, returning how many were rewritten
+fn rewrite_records<T: Serialize + DeserializeOwned>(
+    tree: &sled::Tree,
+    decode: fn(&[u8]) -> bincode::Result<T>,
+) -> Result<usize> {
+    let mut rewritten = 0;
+    for result in tree.iter() {
+        let (key, value) = result?;
+        if bincode::deserialize::<T>(&value).is_err() {
+            tree.insert(key, bincode::serialize(&decode(&value)?)?)?;
+            rewritten += 1;
+        }
+    }
+    Ok(rewritten)
+}
+
+impl ObjectDB {
+    /// Schema version of the database; 1 when none is recorded and it holds
+    /// data, the current version when it is empty
+    pub fn schema_version(&self) -> Result<u32> {
+        match self.meta.get(SCHEMA_VERSION_KEY)? {
+            Some(value) => Ok(bincode::deserialize(&value)?),
+            None if self.buckets.is_empty() && self.objects.is_empty() && self.users.is_empty() => {
+                Ok(SCHEMA_VERSION)
+            }
+            None => Ok(1),
+        }
+    }
+
+    fn set_schema_version(&self, version: u32) -> Result<()> {
+        self.meta.insert(SCHEMA_VERSION_KEY, bincode::serialize(&version)?)?;
+        self.meta.flush()?;
+        Ok(())
+    }
+
+    /// Re-key user records still in the first release's layout, which are
+    /// keyed by their only access key, by user ID, indexing that key in the
+    /// `access_keys` tree; returns how many were moved
+    ///
+    /// Each record moves in one transaction with its index entry.
+    fn migrate_legacy_users(&self) -> Result<usize> {
+        let mut legacy_users = Vec::new();
+        for result in self.users.iter() {
+            let (key, value) = result?;
+            if let Some(legacy) = LegacyUserInfo::decode_keyed(&key, &value) {
+                legacy_users.push((key, UserInfo::from(legacy)));
+            }
+        }
+
+        for (key, user_info) in &legacy_users {
+            let value = bincode::serialize(user_info)?;
+            (&self.users, &self.access_keys)
+                .transaction(|(users, access_keys)| {
+                    users.remove(key)?;
+                    users.insert(user_info.user_id.as_bytes(), value.as_slice())?;
+                    for access_key in &user_info.access_keys {
+                        access_keys.insert(access_key.access_key.as_bytes(), user_info.user_id.as_bytes())?;
+                    }
+                    Ok(())
+                })
+                .map_err(|e: TransactionError<bincode::Error>| anyhow!("Failed to re-key user {}: {}", user_info.user_id, e))?;
+        }
+        Ok(legacy_users.len())
+    }
+
+    /// Bring the database up to [`SCHEMA_VERSION`], returning the version it
+    /// was at before
+    ///
+    /// Fails without changing anything if the database was written by a
+    /// newer release.
+    #[instrument(skip(self))]
+    pub async fn migrate(&self) -> Result<u32> {
+        let version = self.schema_version()?;
+        if version > SCHEMA_VERSION {
+            bail!(
+                "Database schema version {} is newer than the supported version {}",
+                version,
+                SCHEMA_VERSION
+            );
+        }
+
+        for &(target, description) in MIGRATIONS.iter().filter(|(target, _)| *target > version) {
+            info!("Migrating database to schema version {}: {}", target, description);
+            let rewritten = match target {
+                2 => self.migrate_buckets().await?,
+                3 => rewrite_records(&self.objects, ObjectInfo::decode)?,
+                4 => self.migrate_legacy_users()? + rewrite_records(&self.users, UserInfo::decode)?,
+                5 => self.migrate_buckets().await? + rewrite_records(&self.objects, ObjectInfo::decode)?,
+                6 => rewrite_records(&self.objects, ObjectInfo::decode)?,
+                7 | 8 => self.migrate_buckets().await?,
+                _ => unreachable!("no migration to schema version {}", target),
+            };
+            info!("Rewrote {} records", rewritten);
+            self.set_schema_version(target)?;
+        }
+
+        // Record the version of a new or already current database too
+        if self.meta.get(SCHEMA_VERSION_KEY)?.is_none() {
+            self.set_schema_version(SCHEMA_VERSION)?;
+        }
+        Ok(version)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+    use std::collections::HashMap;
+
+    /// Write a version 1 database: legacy records and no schema version
+    async fn write_v1_database(path: &std::path::Path) {
+        let db = ObjectDB::new(path).await.unwrap();
+        let now = Utc::now();
+        let bucket = LegacyBucketInfo {
+            name: "photos".to_string(),
+            created_at: now,
+            updated_at: now,
+            owner: "owner".to_string(),
+            acl: BucketAcl::default(),
+            region: "us-east-1".to_string(),
+            versioning_enabled: false,
+            object_count: 1,
+            total_size: 5,
+        };
+        db.buckets.insert("photos", bincode::serialize(&bucket).unwrap()).unwrap();
+
+        let object = LegacyObjectInfo {
+            key: "cat.jpg".to_string(),
+            bucket: "photos".to_string(),
+            size: 5,
+            content_type: "image/jpeg".to_string(),
+            etag: "etag".to_string(),
+            last_modified: now,
+            created_at: now,
+            metadata: HashMap::new(),
+            storage_class: StorageClass::Standard,
+            version_id: None,
+            is_delete_marker: false,
+            content_encoding: None,
+            content_language: None,
+            cache_control: None,
+            content_disposition: None,
+        };
+        db.objects.insert("photos:cat.jpg", bincode::serialize(&object).unwrap()).unwrap();
+
+        // A first release user, keyed by its access key and not indexed
+        let user = LegacyUserInfo {
+            user_id: "user-1".to_string(),
+            access_key: "AKID".to_string(),
+            secret_key_hash: "hash".to_string(),
+            display_name: "User".to_string(),
+            email: "user@example.com".to_string(),
+            active: true,
+            created_at: now,
+            last_access: Some(now),
+            permissions: UserPermissions::default(),
+        };
+        db.users.insert("AKID", bincode::serialize(&user).unwrap()).unwrap();
+
+        // And one written by a release with several keys per user, before
+        // the schema version was recorded
+        let user = PreScopeUserInfo {
+            user_id: "user-2".to_string(),
+            access_keys: vec![PreScopeAccessKeyInfo {
+                access_key: "AKID2".to_string(),
+                secret_key_hash: "hash2".to_string(),
+                active: true,
+                created_at: now,
+                last_used: None,
+            }],
+            display_name: "User 2".to_string(),
+            email: "user2@example.com".to_string(),
+            active: true,
+            created_at: now,
+            last_access: None,
+            permissions: UserPermissions::default(),
+        };
+        db.users.insert("user-2", bincode::serialize(&user).unwrap()).unwrap();
+        db.access_keys.insert("AKID2", "user-2").unwrap();
+        db.flush().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_migrate_v1_database() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("db");
+        write_v1_database(&path).await;
+
+        let db = ObjectDB::new(&path).await.unwrap();
+        assert_eq!(db.schema_version().unwrap(), 1);
+        assert_eq!(db.migrate().await.unwrap(), 1);
+        assert_eq!(db.schema_version().unwrap(), SCHEMA_VERSION);
+
+        // Every record now reads in the current layout without fallbacks
+        let bucket: BucketInfo = bincode::deserialize(&db.buckets.get("photos").unwrap().unwrap()).unwrap();
+        assert_eq!(bucket.total_size, 5);
+        let object: ObjectInfo = bincode::deserialize(&db.objects.get("photos:cat.jpg").unwrap().unwrap()).unwrap();
+        assert_eq!(object.content_type, "image/jpeg");
+        for (user_id, access_key) in [("user-1", "AKID"), ("user-2", "AKID2")] {
+            let user: UserInfo = bincode::deserialize(&db.users.get(user_id).unwrap().unwrap()).unwrap();
+            assert!(user.access_key(access_key).unwrap().active);
+            assert!(user.access_key(access_key).unwrap().scope.is_none());
+            assert_eq!(db.get_user_by_access_key(access_key).await.unwrap().unwrap().user_id, user_id);
+        }
+        // The first release's record moved from under its access key
+        assert!(db.users.get("AKID").unwrap().is_none());
+        assert_eq!(db.users.len(), 2);
+        let user = db.get_user("user-1").await.unwrap().unwrap();
+        assert_eq!(user.access_keys[0].secret_key_hash, "hash");
+        assert_eq!(user.display_name, "User");
+
+        // Nothing left to do, including after reopening
+        assert_eq!(db.migrate().await.unwrap(), SCHEMA_VERSION);
+        drop(db);
+        let db = ObjectDB::new(&path).await.unwrap();
+        assert_eq!(db.schema_version().unwrap(), SCHEMA_VERSION);
+    }
+
+    #[tokio::test]
+    async fn test_migrate_new_and_newer_databases() {
+        let db = ObjectDB::memory().unwrap();
+        assert_eq!(db.migrate().await.unwrap(), SCHEMA_VERSION);
+        assert!(db.meta.get(SCHEMA_VERSION_KEY).unwrap().is_some());
+
+        // A database from a newer release is left alone
+        db.set_schema_version(SCHEMA_VERSION + 1).unwrap();
+        let err = db.migrate().await.unwrap_err();
+        assert!(err.to_string().contains("newer than the supported version"), "{}", err);
+        assert_eq!(db.schema_version().unwrap(), SCHEMA_VERSION + 1);
+    }
+}
@@ -1,6 +1,7 @@
 //! Data models for ObjectIO database
 
 use chrono::{DateTime, Utc};
+use object_io_core::{AccessKeyScope, DefaultRetention, Grant, KeyPatterns, ObjectRetention, VersioningStatus};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
@@ -19,18 +20,158 @@ pub struct BucketInfo {
     pub acl: BucketAcl,
     /// Bucket region (for S3 compatibility)
     pub region: String,
-    /// Versioning enabled
-    pub versioning_enabled: bool,
+    /// Versioning status
+    pub versioning: VersioningStatus,
     /// Total object count in bucket
     pub object_count: u64,
     /// Total size of all objects in bytes
     pub total_size: u64,
+    /// Whether object lock is enabled
+    pub object_lock_enabled: bool,
+    /// Retention given to new objects that don't ask for their own
+    pub default_retention: Option<DefaultRetention>,
+    /// Patterns restricting the keys that may be written, if any
+    pub key_patterns: Option<KeyPatterns>,
+}
+
+/// Bucket record layout from before key patterns were stored
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct PreKeyPatternsBucketInfo {
+    pub name: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub owner: String,
+    pub acl: BucketAcl,
+    pub region: String,
+    pub versioning: VersioningStatus,
+    pub object_count: u64,
+    pub total_size: u64,
+    pub object_lock_enabled: bool,
+    pub default_retention: Option<DefaultRetention>,
+}
+
+impl From<PreKeyPatternsBucketInfo> for BucketInfo {
+    fn from(old: PreKeyPatternsBucketInfo) -> Self {
+        Self {
+            name: old.name,
+            created_at: old.created_at,
+            updated_at: old.updated_at,
+            owner: old.owner,
+            acl: old.acl,
+            region: old.region,
+            versioning: old.versioning,
+            object_count: old.object_count,
+            total_size: old.total_size,
+            object_lock_enabled: old.object_lock_enabled,
+            default_retention: old.default_retention,
+            key_patterns: None,
+        }
+    }
+}
+
+/// Bucket record layout from before default retention was stored
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct PreRetentionBucketInfo {
+    pub name: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub owner: String,
+    pub acl: BucketAcl,
+    pub region: String,
+    pub versioning: VersioningStatus,
+    pub object_count: u64,
+    pub total_size: u64,
+    pub object_lock_enabled: bool,
+}
+
+impl From<PreRetentionBucketInfo> for PreKeyPatternsBucketInfo {
+    fn from(old: PreRetentionBucketInfo) -> Self {
+        Self {
+            name: old.name,
+            created_at: old.created_at,
+            updated_at: old.updated_at,
+            owner: old.owner,
+            acl: old.acl,
+            region: old.region,
+            versioning: old.versioning,
+            object_count: old.object_count,
+            total_size: old.total_size,
+            object_lock_enabled: old.object_lock_enabled,
+            default_retention: None,
+        }
+    }
+}
+
+/// Bucket record layout from before object lock was stored
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct PreLockBucketInfo {
+    pub name: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub owner: String,
+    pub acl: BucketAcl,
+    pub region: String,
+    pub versioning: VersioningStatus,
+    pub object_count: u64,
+    pub total_size: u64,
+}
+
+impl From<PreLockBucketInfo> for PreRetentionBucketInfo {
+    fn from(old: PreLockBucketInfo) -> Self {
+        Self {
+            name: old.name,
+            created_at: old.created_at,
+            updated_at: old.updated_at,
+            owner: old.owner,
+            acl: old.acl,
+            region: old.region,
+            versioning: old.versioning,
+            object_count: old.object_count,
+            total_size: old.total_size,
+            object_lock_enabled: false,
+        }
+    }
+}
+
+/// Bucket record layout from before versioning status was stored
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct LegacyBucketInfo {
+    pub name: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub owner: String,
+    pub acl: BucketAcl,
+    pub region: String,
+    pub versioning_enabled: bool,
+    pub object_count: u64,
+    pub total_size: u64,
+}
+
+impl From<LegacyBucketInfo> for PreLockBucketInfo {
+    fn from(legacy: LegacyBucketInfo) -> Self {
+        Self {
+            name: legacy.name,
+            created_at: legacy.created_at,
+            updated_at: legacy.updated_at,
+            owner: legacy.owner,
+            acl: legacy.acl,
+            region: legacy.region,
+            // The flag was never settable, so legacy buckets are unversioned
+            versioning: if legacy.versioning_enabled {
+                VersioningStatus::Enabled
+            } else {
+                VersioningStatus::Unversioned
+            },
+            object_count: legacy.object_count,
+            total_size: legacy.total_size,
+        }
+    }
 }
 
 impl BucketInfo {
     /// Create a new bucket
     pub fn new(name: String, owner: String, region: String) -> Self {
-        let now = Utc::now();
+        let now = object_io_core::timestamp_now();
         Self {
             name,
             created_at: now,
@@ -38,9 +179,34 @@ impl BucketInfo {
             owner,
             acl: BucketAcl::default(),
             region,
-            versioning_enabled: false,
+            versioning: VersioningStatus::Unversioned,
             object_count: 0,
             total_size: 0,
+            object_lock_enabled: false,
+            default_retention: None,
+            key_patterns: None,
+        }
+    }
+
+    /// Decode a stored bucket record, accepting the layouts from before key
+    /// patterns, default retention, object lock and versioning status were
+    /// stored
+    ///
+    /// Returns the record and whether it was in an older layout.
+    pub fn decode(bytes: &[u8]) -> bincode::Result<(Self, bool)> {
+        let pre_lock = || {
+            bincode::deserialize::<PreLockBucketInfo>(bytes)
+                .or_else(|_| bincode::deserialize::<LegacyBucketInfo>(bytes).map(PreLockBucketInfo::from))
+        };
+        let pre_retention = || {
+            bincode::deserialize::<PreRetentionBucketInfo>(bytes).or_else(|_| pre_lock().map(PreRetentionBucketInfo::from))
+        };
+        match bincode::deserialize::<BucketInfo>(bytes) {
+            Ok(bucket_info) => Ok((bucket_info, false)),
+            Err(e) => bincode::deserialize::<PreKeyPatternsBucketInfo>(bytes)
+                .or_else(|_| pre_retention().map(PreKeyPatternsBucketInfo::from))
+                .map(|old| (old.into(), true))
+                .map_err(|_| e),
         }
     }
 }
@@ -108,6 +274,198 @@ pub struct ObjectInfo {
     pub cache_control: Option<String>,
     /// Content disposition
     pub content_disposition: Option<String>,
+    /// Expires header, as sent by the client
+    pub expires: Option<String>,
+    /// Grants beyond the owner's full control
+    pub acl: Vec<Grant>,
+    /// Retention period set with object lock
+    pub retention: Option<ObjectRetention>,
+    /// Whether a legal hold is placed on the object
+    pub legal_hold: bool,
+}
+
+/// Object record layout from before legal holds were stored
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct PreHoldObjectInfo {
+    pub key: String,
+    pub bucket: String,
+    pub size: u64,
+    pub content_type: String,
+    pub etag: String,
+    pub last_modified: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+    pub metadata: HashMap<String, String>,
+    pub storage_class: StorageClass,
+    pub version_id: Option<String>,
+    pub is_delete_marker: bool,
+    pub content_encoding: Option<String>,
+    pub content_language: Option<String>,
+    pub cache_control: Option<String>,
+    pub content_disposition: Option<String>,
+    pub expires: Option<String>,
+    pub acl: Vec<Grant>,
+    pub retention: Option<ObjectRetention>,
+}
+
+impl From<PreHoldObjectInfo> for ObjectInfo {
+    fn from(old: PreHoldObjectInfo) -> Self {
+        Self {
+            key: old.key,
+            bucket: old.bucket,
+            size: old.size,
+            content_type: old.content_type,
+            etag: old.etag,
+            last_modified: old.last_modified,
+            created_at: old.created_at,
+            metadata: old.metadata,
+            storage_class: old.storage_class,
+            version_id: old.version_id,
+            is_delete_marker: old.is_delete_marker,
+            content_encoding: old.content_encoding,
+            content_language: old.content_language,
+            cache_control: old.cache_control,
+            content_disposition: old.content_disposition,
+            expires: old.expires,
+            acl: old.acl,
+            retention: old.retention,
+            legal_hold: false,
+        }
+    }
+}
+
+/// Object record layout from before object lock was stored
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct PreLockObjectInfo {
+    pub key: String,
+    pub bucket: String,
+    pub size: u64,
+    pub content_type: String,
+    pub etag: String,
+    pub last_modified: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+    pub metadata: HashMap<String, String>,
+    pub storage_class: StorageClass,
+    pub version_id: Option<String>,
+    pub is_delete_marker: bool,
+    pub content_encoding: Option<String>,
+    pub content_language: Option<String>,
+    pub cache_control: Option<String>,
+    pub content_disposition: Option<String>,
+    pub expires: Option<String>,
+    pub acl: Vec<Grant>,
+}
+
+impl From<PreLockObjectInfo> for PreHoldObjectInfo {
+    fn from(old: PreLockObjectInfo) -> Self {
+        Self {
+            key: old.key,
+            bucket: old.bucket,
+            size: old.size,
+            content_type: old.content_type,
+            etag: old.etag,
+            last_modified: old.last_modified,
+            created_at: old.created_at,
+            metadata: old.metadata,
+            storage_class: old.storage_class,
+            version_id: old.version_id,
+            is_delete_marker: old.is_delete_marker,
+            content_encoding: old.content_encoding,
+            content_language: old.content_language,
+            cache_control: old.cache_control,
+            content_disposition: old.content_disposition,
+            expires: old.expires,
+            acl: old.acl,
+            retention: None,
+        }
+    }
+}
+
+/// Object record layout from before object ACLs were stored
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct PreAclObjectInfo {
+    pub key: String,
+    pub bucket: String,
+    pub size: u64,
+    pub content_type: String,
+    pub etag: String,
+    pub last_modified: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+    pub metadata: HashMap<String, String>,
+    pub storage_class: StorageClass,
+    pub version_id: Option<String>,
+    pub is_delete_marker: bool,
+    pub content_encoding: Option<String>,
+    pub content_language: Option<String>,
+    pub cache_control: Option<String>,
+    pub content_disposition: Option<String>,
+    pub expires: Option<String>,
+}
+
+impl From<PreAclObjectInfo> for PreLockObjectInfo {
+    fn from(old: PreAclObjectInfo) -> Self {
+        Self {
+            key: old.key,
+            bucket: old.bucket,
+            size: old.size,
+            content_type: old.content_type,
+            etag: old.etag,
+            last_modified: old.last_modified,
+            created_at: old.created_at,
+            metadata: old.metadata,
+            storage_class: old.storage_class,
+            version_id: old.version_id,
+            is_delete_marker: old.is_delete_marker,
+            content_encoding: old.content_encoding,
+            content_language: old.content_language,
+            cache_control: old.cache_control,
+            content_disposition: old.content_disposition,
+            expires: old.expires,
+            acl: Vec::new(),
+        }
+    }
+}
+
+/// Object record layout from before the Expires header was stored
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct LegacyObjectInfo {
+    pub key: String,
+    pub bucket: String,
+    pub size: u64,
+    pub content_type: String,
+    pub etag: String,
+    pub last_modified: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+    pub metadata: HashMap<String, String>,
+    pub storage_class: StorageClass,
+    pub version_id: Option<String>,
+    pub is_delete_marker: bool,
+    pub content_encoding: Option<String>,
+    pub content_language: Option<String>,
+    pub cache_control: Option<String>,
+    pub content_disposition: Option<String>,
+}
+
+impl From<LegacyObjectInfo> for PreAclObjectInfo {
+    fn from(legacy: LegacyObjectInfo) -> Self {
+        Self {
+            key: legacy.key,
+            bucket: legacy.bucket,
+            size: legacy.size,
+            content_type: legacy.content_type,
+            etag: legacy.etag,
+            last_modified: legacy.last_modified,
+            created_at: legacy.created_at,
+            metadata: legacy.metadata,
+            storage_class: legacy.storage_class,
+            version_id: legacy.version_id,
+            is_delete_marker: legacy.is_delete_marker,
+            content_encoding: legacy.content_encoding,
+            content_language: legacy.content_language,
+            cache_control: legacy.cache_control,
+            content_disposition: legacy.content_disposition,
+            expires: None,
+        }
+    }
 }
 
 impl ObjectInfo {
@@ -119,7 +477,7 @@ impl ObjectInfo {
         content_type: String,
         etag: String,
     ) -> Self {
-        let now = Utc::now();
+        let now = object_io_core::timestamp_now();
         Self {
             key,
             bucket,
@@ -136,6 +494,28 @@ impl ObjectInfo {
             content_language: None,
             cache_control: None,
             content_disposition: None,
+            expires: None,
+            acl: Vec::new(),
+            retention: None,
+            legal_hold: false,
+        }
+    }
+
+    /// Decode a stored object record, accepting the layouts from before
+    /// legal holds, object lock, object ACLs and the Expires header were
+    /// stored
+    pub fn decode(bytes: &[u8]) -> bincode::Result<Self> {
+        match bincode::deserialize::<ObjectInfo>(bytes) {
+            Ok(object_info) => Ok(object_info),
+            Err(e) => bincode::deserialize::<PreHoldObjectInfo>(bytes)
+                .or_else(|_| bincode::deserialize::<PreLockObjectInfo>(bytes).map(PreHoldObjectInfo::from))
+                .or_else(|_| {
+                    bincode::deserialize::<PreAclObjectInfo>(bytes)
+                        .or_else(|_| bincode::deserialize::<LegacyObjectInfo>(bytes).map(PreAclObjectInfo::from))
+                        .map(|old| PreHoldObjectInfo::from(PreLockObjectInfo::from(old)))
+                })
+                .map(ObjectInfo::from)
+                .map_err(|_| e),
         }
     }
 }
@@ -160,10 +540,8 @@ impl Default for StorageClass {
 pub struct UserInfo {
     /// User ID (unique identifier)
     pub user_id: String,
-    /// Access key for S3 API
-    pub access_key: String,
-    /// Secret key for S3 API (hashed)
-    pub secret_key_hash: String,
+    /// Access keys for S3 API (more than one while rotating)
+    pub access_keys: Vec<AccessKeyInfo>,
     /// User display name
     pub display_name: String,
     /// User email
@@ -189,16 +567,170 @@ impl UserInfo {
     ) -> Self {
         Self {
             user_id,
-            access_key,
-            secret_key_hash,
+            access_keys: vec![AccessKeyInfo::new(access_key, secret_key_hash)],
             display_name,
             email,
             active: true,
-            created_at: Utc::now(),
+            created_at: object_io_core::timestamp_now(),
             last_access: None,
             permissions: UserPermissions::default(),
         }
     }
+
+    /// Find one of this user's access keys by ID
+    pub fn access_key(&self, access_key: &str) -> Option<&AccessKeyInfo> {
+        self.access_keys.iter().find(|key| key.access_key == access_key)
+    }
+
+    /// Find one of this user's access keys by ID for modification
+    pub fn access_key_mut(&mut self, access_key: &str) -> Option<&mut AccessKeyInfo> {
+        self.access_keys.iter_mut().find(|key| key.access_key == access_key)
+    }
+
+    /// Decode a stored user record, accepting the layout from before access
+    /// key scopes were stored
+    ///
+    /// Records in the first release's layout are keyed by access key, not
+    /// user ID, so only the migration that re-keys them reads those.
+    pub fn decode(bytes: &[u8]) -> bincode::Result<Self> {
+        match bincode::deserialize::<UserInfo>(bytes) {
+            Ok(user_info) => Ok(user_info),
+            Err(e) => bincode::deserialize::<PreScopeUserInfo>(bytes)
+                .map(UserInfo::from)
+                .map_err(|_| e),
+        }
+    }
+}
+
+/// User record layout from before access key scopes were stored
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct PreScopeUserInfo {
+    pub user_id: String,
+    pub access_keys: Vec<PreScopeAccessKeyInfo>,
+    pub display_name: String,
+    pub email: String,
+    pub active: bool,
+    pub created_at: DateTime<Utc>,
+    pub last_access: Option<DateTime<Utc>>,
+    pub permissions: UserPermissions,
+}
+
+/// Access key layout from before access key scopes were stored
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct PreScopeAccessKeyInfo {
+    pub access_key: String,
+    pub secret_key_hash: String,
+    pub active: bool,
+    pub created_at: DateTime<Utc>,
+    pub last_used: Option<DateTime<Utc>>,
+}
+
+/// User record layout of the first release: a single access key, with the
+/// record keyed by that key rather than by user ID
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct LegacyUserInfo {
+    pub user_id: String,
+    pub access_key: String,
+    pub secret_key_hash: String,
+    pub display_name: String,
+    pub email: String,
+    pub active: bool,
+    pub created_at: DateTime<Utc>,
+    pub last_access: Option<DateTime<Utc>>,
+    pub permissions: UserPermissions,
+}
+
+impl LegacyUserInfo {
+    /// Decode the record stored under `key` if it is in this layout: every
+    /// byte read as one, and keyed by its own access key
+    ///
+    /// Later layouts are keyed by user ID, and the check on trailing bytes
+    /// keeps them from being misread.
+    pub fn decode_keyed(key: &[u8], bytes: &[u8]) -> Option<Self> {
+        use bincode::Options;
+        let options = bincode::DefaultOptions::new().with_fixint_encoding().reject_trailing_bytes();
+        let legacy: Self = options.deserialize(bytes).ok()?;
+        (legacy.access_key.as_bytes() == key).then_some(legacy)
+    }
+}
+
+impl From<LegacyUserInfo> for UserInfo {
+    fn from(legacy: LegacyUserInfo) -> Self {
+        Self {
+            user_id: legacy.user_id,
+            access_keys: vec![AccessKeyInfo {
+                access_key: legacy.access_key,
+                secret_key_hash: legacy.secret_key_hash,
+                active: true,
+                created_at: legacy.created_at,
+                last_used: legacy.last_access,
+                scope: None,
+            }],
+            display_name: legacy.display_name,
+            email: legacy.email,
+            active: legacy.active,
+            created_at: legacy.created_at,
+            last_access: legacy.last_access,
+            permissions: legacy.permissions,
+        }
+    }
+}
+
+impl From<PreScopeUserInfo> for UserInfo {
+    fn from(old: PreScopeUserInfo) -> Self {
+        Self {
+            user_id: old.user_id,
+            access_keys: old
+                .access_keys
+                .into_iter()
+                .map(|key| AccessKeyInfo {
+                    access_key: key.access_key,
+                    secret_key_hash: key.secret_key_hash,
+                    active: key.active,
+                    created_at: key.created_at,
+                    last_used: key.last_used,
+                    scope: None,
+                })
+                .collect(),
+            display_name: old.display_name,
+            email: old.email,
+            active: old.active,
+            created_at: old.created_at,
+            last_access: old.last_access,
+            permissions: old.permissions,
+        }
+    }
+}
+
+/// Access key credential belonging to a user
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AccessKeyInfo {
+    /// Access key ID
+    pub access_key: String,
+    /// Secret key for S3 API (hashed)
+    pub secret_key_hash: String,
+    /// Is this key accepted for authentication
+    pub active: bool,
+    /// Creation timestamp
+    pub created_at: DateTime<Utc>,
+    /// Last successful authentication with this key
+    pub last_used: Option<DateTime<Utc>>,
+    /// Bucket and prefix the key is restricted to, if any
+    pub scope: Option<AccessKeyScope>,
+}
+
+impl AccessKeyInfo {
+    /// Create a new active access key
+    pub fn new(access_key: String, secret_key_hash: String) -> Self {
+        Self {
+            access_key,
+            secret_key_hash,
+            active: true,
+            created_at: object_io_core::timestamp_now(),
+            last_used: None,
+            scope: None,
+        }
+    }
 }
 
 /// User permissions
@@ -224,3 +756,19 @@ impl Default for UserPermissions {
         }
     }
 }
+
+impl UserPermissions {
+    /// Names of the granted permissions other than `admin`, which is
+    /// reported on its own
+    pub fn names(&self) -> Vec<String> {
+        [
+            ("create_bucket", self.create_bucket),
+            ("delete_bucket", self.delete_bucket),
+            ("list_all_buckets", self.list_all_buckets),
+        ]
+        .into_iter()
+        .filter(|(_, granted)| *granted)
+        .map(|(name, _)| name.to_string())
+        .collect()
+    }
+}
@@ -1,9 +1,88 @@
 //! Database operations for ObjectIO
 
 use crate::{models::*, ObjectDB};
-use anyhow::{anyhow, Result};
+use anyhow::Result;
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
-use tracing::{debug, instrument};
+use object_io_core::{ChangeEvent, ChangeOperation, DefaultRetention, KeyPatterns, ObjectIOError, PrefixUsage, VersioningStatus};
+use sled::transaction::{
+    ConflictableTransactionError, ConflictableTransactionResult, TransactionError, Transactional, TransactionalTree,
+};
+use std::ops::Bound;
+use tracing::{debug, info, instrument};
+
+/// Object records removed per batch when emptying a bucket
+const DELETE_BATCH_SIZE: usize = 1000;
+
+/// Key of the last change feed sequence number in the `meta` tree
+const CHANGE_SEQUENCE_KEY: &str = "change_sequence";
+
+/// Change feed event for `object_info` having been stored
+fn put_event(object_info: &ObjectInfo) -> ChangeEvent {
+    ChangeEvent {
+        sequence: 0,
+        bucket: object_info.bucket.clone(),
+        key: object_info.key.clone(),
+        version_id: object_info.version_id.clone(),
+        etag: Some(object_info.etag.clone()),
+        operation: ChangeOperation::Put,
+        timestamp: Utc::now(),
+    }
+}
+
+/// Change feed event for the removal of `object_info`'s record
+fn delete_event(object_info: &ObjectInfo) -> ChangeEvent {
+    ChangeEvent {
+        etag: None,
+        operation: ChangeOperation::Delete,
+        ..put_event(object_info)
+    }
+}
+
+/// Append `event` to the change feed within a transaction over the `meta`
+/// and `changes` trees
+///
+/// The sequence counter is read and bumped in the same transaction as the
+/// change it numbers, so events are numbered without gaps in the order their
+/// transactions commit, and an event exists exactly when its change does.
+fn record_change(
+    meta: &TransactionalTree,
+    changes: &TransactionalTree,
+    mut event: ChangeEvent,
+) -> ConflictableTransactionResult<(), bincode::Error> {
+    let last = match meta.get(CHANGE_SEQUENCE_KEY)? {
+        Some(value) => bincode::deserialize::<u64>(&value).map_err(ConflictableTransactionError::Abort)?,
+        None => 0,
+    };
+    event.sequence = last + 1;
+    let sequence = bincode::serialize(&event.sequence).map_err(ConflictableTransactionError::Abort)?;
+    let value = bincode::serialize(&event).map_err(ConflictableTransactionError::Abort)?;
+    meta.insert(CHANGE_SEQUENCE_KEY, sequence)?;
+    changes.insert(&event.sequence.to_be_bytes(), value)?;
+    Ok(())
+}
+
+/// Apply `modify` to the record of `bucket` within a transaction over the
+/// `buckets` tree, returning false if the bucket doesn't exist
+///
+/// Object changes adjust the bucket statistics this way in the same
+/// transaction as the object record, so concurrent writes to different keys
+/// of one bucket don't lose each other's updates.
+fn modify_bucket_record(
+    buckets: &TransactionalTree,
+    bucket: &str,
+    modify: impl FnOnce(&mut BucketInfo),
+) -> ConflictableTransactionResult<bool, bincode::Error> {
+    let Some(value) = buckets.get(bucket.as_bytes())? else {
+        return Ok(false);
+    };
+    let (mut bucket_info, _) = BucketInfo::decode(&value).map_err(ConflictableTransactionError::Abort)?;
+    modify(&mut bucket_info);
+    bucket_info.updated_at = object_io_core::timestamp_now();
+    let value = bincode::serialize(&bucket_info).map_err(ConflictableTransactionError::Abort)?;
+    buckets.insert(bucket.as_bytes(), value)?;
+    Ok(true)
+}
 
 /// Bucket operations
 impl ObjectDB {
@@ -15,7 +94,10 @@ impl ObjectDB {
         
         // Check if bucket already exists
         if self.buckets.contains_key(key)? {
-            return Err(anyhow!("Bucket '{}' already exists", bucket_info.name));
+            return Err(ObjectIOError::BucketAlreadyExists {
+                bucket: bucket_info.name,
+            }
+            .into());
         }
         
         self.buckets.insert(key, value)?;
@@ -29,7 +111,7 @@ impl ObjectDB {
         let key = name.as_bytes();
         match self.buckets.get(key)? {
             Some(value) => {
-                let bucket_info: BucketInfo = bincode::deserialize(&value)?;
+                let (bucket_info, _) = BucketInfo::decode(&value)?;
                 debug!("Retrieved bucket: {}", name);
                 Ok(Some(bucket_info))
             }
@@ -48,7 +130,10 @@ impl ObjectDB {
         
         // Check if bucket exists
         if !self.buckets.contains_key(key)? {
-            return Err(anyhow!("Bucket '{}' does not exist", bucket_info.name));
+            return Err(ObjectIOError::BucketNotFound {
+                bucket: bucket_info.name,
+            }
+            .into());
         }
         
         self.buckets.insert(key, value)?;
@@ -56,12 +141,31 @@ impl ObjectDB {
         Ok(())
     }
     
+    /// Apply `modify` to a bucket's record, returning false if the bucket
+    /// doesn't exist
+    ///
+    /// The record is read and written in one transaction, so settings
+    /// changes don't undo statistics updated meanwhile.
+    #[instrument(skip(self, modify))]
+    pub fn modify_bucket(&self, name: &str, modify: impl Fn(&mut BucketInfo)) -> Result<bool> {
+        self.buckets
+            .transaction(|buckets| modify_bucket_record(buckets, name, &modify))
+            .map_err(|e: TransactionError<bincode::Error>| anyhow::anyhow!("Failed to update bucket: {}", e))
+    }
+
     /// Delete a bucket
     #[instrument(skip(self))]
     pub async fn delete_bucket(&self, name: &str) -> Result<bool> {
         let key = name.as_bytes();
+        if self.objects.scan_prefix(format!("{}:", name).as_bytes()).next().is_some() {
+            return Err(ObjectIOError::BucketNotEmpty {
+                bucket: name.to_string(),
+            }
+            .into());
+        }
         match self.buckets.remove(key)? {
             Some(_) => {
+                self.clear_delete_markers(name)?;
                 debug!("Deleted bucket: {}", name);
                 Ok(true)
             }
@@ -72,13 +176,71 @@ impl ObjectDB {
         }
     }
     
+    /// Set a bucket's versioning status, returning false if the bucket doesn't exist
+    #[instrument(skip(self))]
+    pub async fn set_bucket_versioning(&self, name: &str, versioning: VersioningStatus) -> Result<bool> {
+        if !self.modify_bucket(name, |bucket_info| bucket_info.versioning = versioning)? {
+            return Ok(false);
+        }
+        debug!("Set versioning for bucket {} to {:?}", name, versioning);
+        Ok(true)
+    }
+
+    /// Enable object lock on a bucket and replace its default retention,
+    /// returning false if the bucket doesn't exist
+    #[instrument(skip(self))]
+    pub async fn enable_bucket_object_lock(
+        &self,
+        name: &str,
+        default_retention: Option<DefaultRetention>,
+    ) -> Result<bool> {
+        let enabled = self.modify_bucket(name, |bucket_info| {
+            bucket_info.object_lock_enabled = true;
+            bucket_info.default_retention = default_retention;
+        })?;
+        if !enabled {
+            return Ok(false);
+        }
+        debug!("Enabled object lock for bucket {}", name);
+        Ok(true)
+    }
+
+    /// Replace the patterns restricting the keys written to a bucket,
+    /// returning false if the bucket doesn't exist
+    #[instrument(skip(self))]
+    pub async fn set_bucket_key_patterns(&self, name: &str, key_patterns: Option<KeyPatterns>) -> Result<bool> {
+        if !self.modify_bucket(name, |bucket_info| bucket_info.key_patterns = key_patterns.clone())? {
+            return Ok(false);
+        }
+        debug!("Set key patterns for bucket {}", name);
+        Ok(true)
+    }
+
+    /// Rewrite bucket records stored in an older layout, returning how many were migrated
+    #[instrument(skip(self))]
+    pub async fn migrate_buckets(&self) -> Result<usize> {
+        let mut migrated = 0;
+        for result in self.buckets.iter() {
+            let (key, value) = result?;
+            let (bucket_info, legacy) = BucketInfo::decode(&value)?;
+            if legacy {
+                self.buckets.insert(key, bincode::serialize(&bucket_info)?)?;
+                migrated += 1;
+            }
+        }
+        if migrated > 0 {
+            info!("Migrated {} bucket records to the current layout", migrated);
+        }
+        Ok(migrated)
+    }
+
     /// List all buckets
     #[instrument(skip(self))]
     pub async fn list_buckets(&self) -> Result<Vec<BucketInfo>> {
         let mut buckets = Vec::new();
         for result in self.buckets.iter() {
             let (_key, value) = result?;
-            let bucket_info: BucketInfo = bincode::deserialize(&value)?;
+            let (bucket_info, _) = BucketInfo::decode(&value)?;
             buckets.push(bucket_info);
         }
         debug!("Listed {} buckets", buckets.len());
@@ -91,7 +253,7 @@ impl ObjectDB {
         let mut buckets = Vec::new();
         for result in self.buckets.iter() {
             let (_key, value) = result?;
-            let bucket_info: BucketInfo = bincode::deserialize(&value)?;
+            let (bucket_info, _) = BucketInfo::decode(&value)?;
             if bucket_info.owner == owner {
                 buckets.push(bucket_info);
             }
@@ -99,6 +261,35 @@ impl ObjectDB {
         debug!("Listed {} buckets for owner: {}", buckets.len(), owner);
         Ok(buckets)
     }
+
+    /// List up to `limit` buckets owned by a user in name order, starting
+    /// after `start_after`
+    #[instrument(skip(self))]
+    pub async fn list_buckets_by_owner_after(
+        &self,
+        owner: &str,
+        start_after: Option<&str>,
+        limit: usize,
+    ) -> Result<Vec<BucketInfo>> {
+        // Buckets are keyed by name, so the tree iterates in name order
+        let entries = match start_after {
+            Some(start) => self.buckets.range::<&[u8], _>((Bound::Excluded(start.as_bytes()), Bound::Unbounded)),
+            None => self.buckets.iter(),
+        };
+
+        let mut buckets = Vec::new();
+        for result in entries {
+            if buckets.len() >= limit {
+                break;
+            }
+            let (_key, value) = result?;
+            let (bucket_info, _) = BucketInfo::decode(&value)?;
+            if bucket_info.owner == owner {
+                buckets.push(bucket_info);
+            }
+        }
+        Ok(buckets)
+    }
 }
 
 /// Object operations
@@ -108,28 +299,82 @@ impl ObjectDB {
     pub async fn put_object(&self, object_info: ObjectInfo) -> Result<()> {
         let key = format!("{}:{}", object_info.bucket, object_info.key);
         let value = bincode::serialize(&object_info)?;
+        let event = put_event(&object_info);
         
-        self.objects.insert(key.as_bytes(), value)?;
-        
-        // Update bucket statistics
-        if let Ok(Some(mut bucket)) = self.get_bucket(&object_info.bucket).await {
-            bucket.object_count += 1;
-            bucket.total_size += object_info.size;
-            bucket.updated_at = chrono::Utc::now();
-            let _ = self.update_bucket(bucket).await;
-        }
+        (&self.objects, &self.delete_markers, &self.buckets, &self.meta, &self.changes)
+            .transaction(|(objects, delete_markers, buckets, meta, changes)| {
+                let previous = match objects.insert(key.as_bytes(), value.as_slice())? {
+                    Some(previous) => {
+                        Some(ObjectInfo::decode(&previous).map_err(ConflictableTransactionError::Abort)?)
+                    }
+                    None => None,
+                };
+                delete_markers.remove(key.as_bytes())?;
+                // Replace the previous version's size on overwrite
+                modify_bucket_record(buckets, &object_info.bucket, |bucket| {
+                    match &previous {
+                        Some(previous) => bucket.total_size = bucket.total_size.saturating_sub(previous.size),
+                        None => bucket.object_count += 1,
+                    }
+                    bucket.total_size += object_info.size;
+                })?;
+                record_change(meta, changes, event.clone())
+            })
+            .map_err(|e: TransactionError<bincode::Error>| anyhow::anyhow!("Failed to store object: {}", e))?;
         
         debug!("Stored object: {}/{}", object_info.bucket, object_info.key);
         Ok(())
     }
     
+    /// Store many objects of one bucket and update the bucket statistics in
+    /// a single transaction
+    ///
+    /// Later entries for the same key replace earlier ones, as with
+    /// successive `put_object` calls.
+    #[instrument(skip(self, objects), fields(count = objects.len()))]
+    pub async fn put_objects(&self, bucket: &str, objects: &[ObjectInfo]) -> Result<()> {
+        let records = objects
+            .iter()
+            .map(|object_info| {
+                let key = format!("{}:{}", bucket, object_info.key);
+                Ok((key, bincode::serialize(object_info)?, put_event(object_info)))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let stored_size = objects.iter().map(|object_info| object_info.size).sum::<u64>();
+        (&self.objects, &self.delete_markers, &self.buckets, &self.meta, &self.changes)
+            .transaction(|(tx, delete_markers, buckets, meta, changes)| {
+                let (mut added, mut replaced_size) = (0u64, 0u64);
+                for (key, value, event) in &records {
+                    delete_markers.remove(key.as_bytes())?;
+                    record_change(meta, changes, event.clone())?;
+                    match tx.insert(key.as_bytes(), value.as_slice())? {
+                        Some(previous) => {
+                            let previous = ObjectInfo::decode(&previous).map_err(ConflictableTransactionError::Abort)?;
+                            replaced_size += previous.size;
+                        }
+                        None => added += 1,
+                    }
+                }
+                modify_bucket_record(buckets, bucket, |bucket_info| {
+                    bucket_info.object_count += added;
+                    bucket_info.total_size = bucket_info.total_size.saturating_sub(replaced_size) + stored_size;
+                })?;
+                Ok(())
+            })
+            .map_err(|e: TransactionError<bincode::Error>| anyhow::anyhow!("Failed to store objects: {}", e))?;
+
+        debug!("Stored {} objects in bucket: {}", objects.len(), bucket);
+        Ok(())
+    }
+    
     /// Get object information
     #[instrument(skip(self))]
     pub async fn get_object(&self, bucket: &str, key: &str) -> Result<Option<ObjectInfo>> {
         let object_key = format!("{}:{}", bucket, key);
         match self.objects.get(object_key.as_bytes())? {
             Some(value) => {
-                let object_info: ObjectInfo = bincode::deserialize(&value)?;
+                let object_info = ObjectInfo::decode(&value)?;
                 debug!("Retrieved object: {}/{}", bucket, key);
                 Ok(Some(object_info))
             }
@@ -140,62 +385,97 @@ impl ObjectDB {
         }
     }
     
+    /// Update an object's content type, headers and custom metadata in place
+    ///
+    /// `update` must leave the size and ETag untouched, as bucket statistics
+    /// are not adjusted. Returns the updated record, or `None` if the object
+    /// doesn't exist.
+    #[instrument(skip(self, update))]
+    pub async fn update_object_metadata<F>(&self, bucket: &str, key: &str, update: F) -> Result<Option<ObjectInfo>>
+    where
+        F: FnOnce(&mut ObjectInfo),
+    {
+        let object_key = format!("{}:{}", bucket, key);
+        let mut object_info = match self.objects.get(object_key.as_bytes())? {
+            Some(value) => ObjectInfo::decode(&value)?,
+            None => return Ok(None),
+        };
+        
+        update(&mut object_info);
+        let value = bincode::serialize(&object_info)?;
+        let event = put_event(&object_info);
+        (&self.objects, &self.meta, &self.changes)
+            .transaction(|(objects, meta, changes)| {
+                objects.insert(object_key.as_bytes(), value.as_slice())?;
+                record_change(meta, changes, event.clone())
+            })
+            .map_err(|e: TransactionError<bincode::Error>| anyhow::anyhow!("Failed to update object: {}", e))?;
+        
+        debug!("Updated metadata for object: {}/{}", bucket, key);
+        Ok(Some(object_info))
+    }
+    
     /// Delete object
     #[instrument(skip(self))]
     pub async fn delete_object(&self, bucket: &str, key: &str) -> Result<bool> {
         let object_key = format!("{}:{}", bucket, key);
         
-        // Get object info for statistics update
-        let object_size = if let Some(obj) = self.get_object(bucket, key).await? {
-            obj.size
-        } else {
-            return Ok(false);
-        };
-        
-        match self.objects.remove(object_key.as_bytes())? {
-            Some(_) => {
-                // Update bucket statistics
-                if let Ok(Some(mut bucket_info)) = self.get_bucket(bucket).await {
+        let removed = (&self.objects, &self.buckets, &self.meta, &self.changes)
+            .transaction(|(objects, buckets, meta, changes)| {
+                let Some(value) = objects.remove(object_key.as_bytes())? else {
+                    return Ok(false);
+                };
+                let removed = ObjectInfo::decode(&value).map_err(ConflictableTransactionError::Abort)?;
+                modify_bucket_record(buckets, bucket, |bucket_info| {
                     bucket_info.object_count = bucket_info.object_count.saturating_sub(1);
-                    bucket_info.total_size = bucket_info.total_size.saturating_sub(object_size);
-                    bucket_info.updated_at = chrono::Utc::now();
-                    let _ = self.update_bucket(bucket_info).await;
-                }
-                
-                debug!("Deleted object: {}/{}", bucket, key);
+                    bucket_info.total_size = bucket_info.total_size.saturating_sub(removed.size);
+                })?;
+                record_change(meta, changes, delete_event(&removed))?;
                 Ok(true)
-            }
-            None => {
-                debug!("Object not found for deletion: {}/{}", bucket, key);
-                Ok(false)
-            }
+            })
+            .map_err(|e: TransactionError<bincode::Error>| anyhow::anyhow!("Failed to delete object: {}", e))?;
+        
+        if removed {
+            debug!("Deleted object: {}/{}", bucket, key);
+        } else {
+            debug!("Object not found for deletion: {}/{}", bucket, key);
         }
+        Ok(removed)
     }
     
     /// List objects in a bucket with optional prefix filter
     #[instrument(skip(self))]
     pub async fn list_objects(&self, bucket: &str, prefix: Option<&str>) -> Result<Vec<ObjectInfo>> {
-        let bucket_prefix = format!("{}:", bucket);
+        // Records are keyed `bucket:key`, so the objects under a prefix are
+        // one contiguous range of the tree and nothing else is read
+        let scan_prefix = format!("{}:{}", bucket, prefix.unwrap_or(""));
         let mut objects = Vec::new();
         
-        for result in self.objects.scan_prefix(bucket_prefix.as_bytes()) {
+        for result in self.objects.scan_prefix(scan_prefix.as_bytes()) {
             let (_key, value) = result?;
-            let object_info: ObjectInfo = bincode::deserialize(&value)?;
-            
-            // Apply prefix filter if specified
-            if let Some(prefix) = prefix {
-                if !object_info.key.starts_with(prefix) {
-                    continue;
-                }
-            }
-            
-            objects.push(object_info);
+            objects.push(ObjectInfo::decode(&value)?);
         }
         
         debug!("Listed {} objects in bucket: {}", objects.len(), bucket);
         Ok(objects)
     }
     
+    /// Count and sum the sizes of the objects under `prefix` in one scan
+    /// of their records, without collecting them
+    #[instrument(skip(self))]
+    pub async fn prefix_usage(&self, bucket: &str, prefix: &str) -> Result<PrefixUsage> {
+        let scan_prefix = format!("{}:{}", bucket, prefix);
+        let mut usage = PrefixUsage::default();
+        for result in self.objects.scan_prefix(scan_prefix.as_bytes()) {
+            let (_key, value) = result?;
+            usage.object_count += 1;
+            usage.total_size += ObjectInfo::decode(&value)?.size;
+        }
+
+        debug!("Found {} objects under {}/{}", usage.object_count, bucket, prefix);
+        Ok(usage)
+    }
+
     /// Get object count for a bucket
     #[instrument(skip(self))]
     pub async fn get_object_count(&self, bucket: &str) -> Result<u64> {
@@ -210,36 +490,144 @@ impl ObjectDB {
         debug!("Counted {} objects in bucket: {}", count, bucket);
         Ok(count)
     }
+
+    /// Record that `key` was deleted from a versioned bucket, replacing any
+    /// earlier marker; the marker is dropped when the key is stored again
+    #[instrument(skip(self))]
+    pub async fn put_delete_marker(&self, bucket: &str, key: &str, version_id: &str) -> Result<ObjectInfo> {
+        let mut marker = ObjectInfo::new(key.to_string(), bucket.to_string(), 0, String::new(), String::new());
+        marker.version_id = Some(version_id.to_string());
+        marker.is_delete_marker = true;
+        let marker_key = format!("{}:{}", bucket, key);
+        self.delete_markers.insert(marker_key.as_bytes(), bincode::serialize(&marker)?)?;
+        debug!("Stored delete marker {} for object: {}/{}", version_id, bucket, key);
+        Ok(marker)
+    }
+
+    /// Get the delete marker left by the last delete of `key`, if the key
+    /// hasn't been stored since
+    #[instrument(skip(self))]
+    pub async fn get_delete_marker(&self, bucket: &str, key: &str) -> Result<Option<ObjectInfo>> {
+        let marker_key = format!("{}:{}", bucket, key);
+        match self.delete_markers.get(marker_key.as_bytes())? {
+            Some(value) => Ok(Some(ObjectInfo::decode(&value)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Remove every delete marker in a bucket, returning how many there were
+    pub fn clear_delete_markers(&self, bucket: &str) -> Result<u64> {
+        let mut batch = sled::Batch::default();
+        let mut count = 0u64;
+        for result in self.delete_markers.scan_prefix(format!("{}:", bucket).as_bytes()) {
+            let (key, _value) = result?;
+            batch.remove(key);
+            count += 1;
+        }
+        self.delete_markers.apply_batch(batch)?;
+        Ok(count)
+    }
+}
+
+/// Abort a user transaction because a record could not be encoded or decoded
+fn user_record_error(e: bincode::Error) -> ConflictableTransactionError<ObjectIOError> {
+    ConflictableTransactionError::Abort(ObjectIOError::DatabaseError {
+        message: format!("Invalid user record: {}", e),
+    })
+}
+
+/// Turn a failed user transaction into an error, keeping the typed error it
+/// was aborted with
+fn user_transaction_error(context: &str, e: TransactionError<ObjectIOError>) -> anyhow::Error {
+    match e {
+        TransactionError::Abort(e) => e.into(),
+        TransactionError::Storage(e) => anyhow::anyhow!("{}: {}", context, e),
+    }
+}
+
+/// Write `user_info` within a transaction over the `users` and `access_keys`
+/// trees, indexing its access keys and dropping those `previous` had that it
+/// no longer does
+///
+/// Fails with `AccessKeyAlreadyExists` if one of its keys belongs to
+/// another user.
+fn store_user(
+    users: &TransactionalTree,
+    access_keys: &TransactionalTree,
+    user_info: &UserInfo,
+    previous: Option<&UserInfo>,
+) -> ConflictableTransactionResult<(), ObjectIOError> {
+    let key = user_info.user_id.as_bytes();
+    for access_key in &user_info.access_keys {
+        if let Some(owner) = access_keys.get(access_key.access_key.as_bytes())? {
+            if owner.as_ref() != key {
+                return Err(ConflictableTransactionError::Abort(ObjectIOError::AccessKeyAlreadyExists {
+                    access_key: access_key.access_key.clone(),
+                }));
+            }
+        }
+    }
+
+    users.insert(key, bincode::serialize(user_info).map_err(user_record_error)?)?;
+    for old_key in previous.iter().flat_map(|previous| &previous.access_keys) {
+        if user_info.access_key(&old_key.access_key).is_none() {
+            access_keys.remove(old_key.access_key.as_bytes())?;
+        }
+    }
+    for access_key in &user_info.access_keys {
+        access_keys.insert(access_key.access_key.as_bytes(), key)?;
+    }
+    Ok(())
 }
 
 /// User operations
+///
+/// A user record and its entries in the access key index are always read
+/// and written together in one transaction, so neither a crash nor a
+/// concurrent change can leave them disagreeing.
 impl ObjectDB {
     /// Create a new user
     #[instrument(skip(self, user_info), fields(user_id = %user_info.user_id))]
     pub async fn create_user(&self, user_info: UserInfo) -> Result<()> {
-        let key = user_info.access_key.as_bytes();
-        let value = bincode::serialize(&user_info)?;
-        
-        // Check if user already exists
-        if self.users.contains_key(key)? {
-            return Err(anyhow!("User with access key '{}' already exists", user_info.access_key));
-        }
-        
-        self.users.insert(key, value)?;
+        (&self.users, &self.access_keys)
+            .transaction(|(users, access_keys)| {
+                if users.get(user_info.user_id.as_bytes())?.is_some() {
+                    return Err(ConflictableTransactionError::Abort(ObjectIOError::UserAlreadyExists {
+                        user_id: user_info.user_id.clone(),
+                    }));
+                }
+                store_user(users, access_keys, &user_info, None)
+            })
+            .map_err(|e| user_transaction_error("Failed to create user", e))?;
         debug!("Created user: {}", user_info.user_id);
         Ok(())
     }
     
-    /// Get user by access key
+    /// Get user by user ID
     #[instrument(skip(self))]
-    pub async fn get_user_by_access_key(&self, access_key: &str) -> Result<Option<UserInfo>> {
-        let key = access_key.as_bytes();
-        match self.users.get(key)? {
+    pub async fn get_user(&self, user_id: &str) -> Result<Option<UserInfo>> {
+        match self.users.get(user_id.as_bytes())? {
             Some(value) => {
-                let user_info: UserInfo = bincode::deserialize(&value)?;
-                debug!("Retrieved user by access key: {}", access_key);
+                let user_info = UserInfo::decode(&value)?;
+                debug!("Retrieved user: {}", user_id);
                 Ok(Some(user_info))
             }
+            None => {
+                debug!("User not found: {}", user_id);
+                Ok(None)
+            }
+        }
+    }
+    
+    /// Get user by any of its access keys
+    #[instrument(skip(self))]
+    pub async fn get_user_by_access_key(&self, access_key: &str) -> Result<Option<UserInfo>> {
+        match self.access_keys.get(access_key.as_bytes())? {
+            Some(user_id) => {
+                let user_id = String::from_utf8(user_id.to_vec())?;
+                debug!("Resolved access key {} to user {}", access_key, user_id);
+                self.get_user(&user_id).await
+            }
             None => {
                 debug!("User not found by access key: {}", access_key);
                 Ok(None)
@@ -247,36 +635,105 @@ impl ObjectDB {
         }
     }
     
-    /// Update user information
+    /// Change a user's record, returning what `update` returns
+    ///
+    /// The record is read, passed to `update` and written back in one
+    /// transaction, re-indexing added or removed access keys. `update` runs
+    /// again if the transaction conflicts with another, and an error from it
+    /// leaves the record unchanged. Fails with `UserNotFound` if there is no
+    /// such user.
+    #[instrument(skip(self, update))]
+    pub async fn modify_user<T, F>(&self, user_id: &str, update: F) -> Result<T>
+    where
+        F: Fn(&mut UserInfo) -> std::result::Result<T, ObjectIOError>,
+    {
+        let result = (&self.users, &self.access_keys)
+            .transaction(|(users, access_keys)| {
+                let Some(value) = users.get(user_id.as_bytes())? else {
+                    return Err(ConflictableTransactionError::Abort(ObjectIOError::UserNotFound {
+                        user_id: user_id.to_string(),
+                    }));
+                };
+                let previous = UserInfo::decode(&value).map_err(user_record_error)?;
+                let mut user_info = previous.clone();
+                let result = update(&mut user_info).map_err(ConflictableTransactionError::Abort)?;
+                store_user(users, access_keys, &user_info, Some(&previous))?;
+                Ok(result)
+            })
+            .map_err(|e| user_transaction_error("Failed to update user", e))?;
+        
+        debug!("Updated user: {}", user_id);
+        Ok(result)
+    }
+    
+    /// Replace a user's record, re-indexing added or removed access keys
     #[instrument(skip(self, user_info), fields(user_id = %user_info.user_id))]
     pub async fn update_user(&self, user_info: UserInfo) -> Result<()> {
-        let key = user_info.access_key.as_bytes();
-        let value = bincode::serialize(&user_info)?;
+        self.modify_user(&user_info.user_id, |stored| {
+            *stored = user_info.clone();
+            Ok(())
+        })
+        .await
+    }
+    
+    /// Record a successful authentication with an access key
+    ///
+    /// The index and user record are read and written in one transaction,
+    /// retried on conflict, so a change to the user's keys made meanwhile
+    /// is never overwritten with the copy read before it.
+    #[instrument(skip(self))]
+    pub async fn record_access_key_use(&self, access_key: &str, used_at: DateTime<Utc>) -> Result<bool> {
+        let recorded = (&self.access_keys, &self.users)
+            .transaction(|(access_keys, users)| {
+                let Some(user_id) = access_keys.get(access_key.as_bytes())? else {
+                    return Ok(false);
+                };
+                let Some(value) = users.get(&user_id)? else {
+                    return Ok(false);
+                };
+                let mut user_info = UserInfo::decode(&value).map_err(ConflictableTransactionError::Abort)?;
+                match user_info.access_key_mut(access_key) {
+                    Some(key) => key.last_used = Some(used_at),
+                    None => return Ok(false),
+                }
+                user_info.last_access = Some(used_at);
+                let value = bincode::serialize(&user_info).map_err(ConflictableTransactionError::Abort)?;
+                users.insert(&user_id, value)?;
+                Ok(true)
+            })
+            .map_err(|e: TransactionError<bincode::Error>| anyhow::anyhow!("Failed to record access key use: {}", e))?;
         
-        // Check if user exists
-        if !self.users.contains_key(key)? {
-            return Err(anyhow!("User with access key '{}' does not exist", user_info.access_key));
+        if recorded {
+            debug!("Recorded use of access key: {}", access_key);
         }
-        
-        self.users.insert(key, value)?;
-        debug!("Updated user: {}", user_info.user_id);
-        Ok(())
+        Ok(recorded)
     }
     
-    /// Delete user
+    /// Delete the user owning an access key, along with all of its keys
     #[instrument(skip(self))]
     pub async fn delete_user(&self, access_key: &str) -> Result<bool> {
-        let key = access_key.as_bytes();
-        match self.users.remove(key)? {
-            Some(_) => {
-                debug!("Deleted user with access key: {}", access_key);
+        let deleted = (&self.users, &self.access_keys)
+            .transaction(|(users, access_keys)| {
+                let Some(user_id) = access_keys.get(access_key.as_bytes())? else {
+                    return Ok(false);
+                };
+                let Some(value) = users.remove(&user_id)? else {
+                    return Ok(false);
+                };
+                let user_info = UserInfo::decode(&value).map_err(user_record_error)?;
+                for key in &user_info.access_keys {
+                    access_keys.remove(key.access_key.as_bytes())?;
+                }
                 Ok(true)
-            }
-            None => {
-                debug!("User not found for deletion: {}", access_key);
-                Ok(false)
-            }
+            })
+            .map_err(|e| user_transaction_error("Failed to delete user", e))?;
+        
+        if deleted {
+            debug!("Deleted user with access key: {}", access_key);
+        } else {
+            debug!("User not found for deletion: {}", access_key);
         }
+        Ok(deleted)
     }
     
     /// List all users
@@ -285,7 +742,7 @@ impl ObjectDB {
         let mut users = Vec::new();
         for result in self.users.iter() {
             let (_key, value) = result?;
-            let user_info: UserInfo = bincode::deserialize(&value)?;
+            let user_info = UserInfo::decode(&value)?;
             users.push(user_info);
         }
         debug!("Listed {} users", users.len());
@@ -293,32 +750,74 @@ impl ObjectDB {
     }
 }
 
+/// Change feed operations
+impl ObjectDB {
+    /// Up to `limit` change feed events numbered after `after`, oldest first
+    ///
+    /// Passing the last sequence number seen resumes the feed exactly where
+    /// it was left off; 0 starts from the first event.
+    #[instrument(skip(self))]
+    pub async fn list_changes(&self, after: u64, limit: usize) -> Result<Vec<ChangeEvent>> {
+        let Some(start) = after.checked_add(1) else {
+            return Ok(Vec::new());
+        };
+        let mut events = Vec::new();
+        for result in self.changes.range(start.to_be_bytes()..).take(limit) {
+            let (_key, value) = result?;
+            events.push(bincode::deserialize(&value)?);
+        }
+        debug!("Listed {} changes after {}", events.len(), after);
+        Ok(events)
+    }
+}
+
 /// Bulk operations
 impl ObjectDB {
-    /// Delete all objects in a bucket (for bucket deletion)
+    /// Delete all objects in a bucket, taking them out of its statistics
+    ///
+    /// Keys are collected and removed in batches of [`DELETE_BATCH_SIZE`] so
+    /// a large bucket is never held in memory at once.
     #[instrument(skip(self))]
     pub async fn delete_all_objects_in_bucket(&self, bucket: &str) -> Result<u64> {
         let bucket_prefix = format!("{}:", bucket);
         let mut deleted_count = 0u64;
-        
-        // Collect keys to delete
-        let mut keys_to_delete = Vec::new();
-        for result in self.objects.scan_prefix(bucket_prefix.as_bytes()) {
-            let (key, _value) = result?;
-            keys_to_delete.push(key.to_vec());
-        }
-        
-        // Delete collected keys
-        for key in keys_to_delete {
-            if self.objects.remove(&key)?.is_some() {
-                deleted_count += 1;
+
+        loop {
+            let keys = self
+                .objects
+                .scan_prefix(bucket_prefix.as_bytes())
+                .keys()
+                .take(DELETE_BATCH_SIZE)
+                .collect::<std::result::Result<Vec<_>, _>>()?;
+            if keys.is_empty() {
+                break;
             }
+            let removed = (&self.objects, &self.buckets, &self.meta, &self.changes)
+                .transaction(|(objects, buckets, meta, changes)| {
+                    let (mut removed, mut removed_size) = (0u64, 0u64);
+                    for key in &keys {
+                        if let Some(value) = objects.remove(key)? {
+                            let object_info = ObjectInfo::decode(&value).map_err(ConflictableTransactionError::Abort)?;
+                            record_change(meta, changes, delete_event(&object_info))?;
+                            removed += 1;
+                            removed_size += object_info.size;
+                        }
+                    }
+                    modify_bucket_record(buckets, bucket, |bucket_info| {
+                        bucket_info.object_count = bucket_info.object_count.saturating_sub(removed);
+                        bucket_info.total_size = bucket_info.total_size.saturating_sub(removed_size);
+                    })?;
+                    Ok(removed)
+                })
+                .map_err(|e: TransactionError<bincode::Error>| anyhow::anyhow!("Failed to delete objects: {}", e))?;
+            deleted_count += removed;
         }
-        
+        self.clear_delete_markers(bucket)?;
+
         debug!("Deleted {} objects from bucket: {}", deleted_count, bucket);
         Ok(deleted_count)
     }
-    
+
     /// Get database health check information
     #[instrument(skip(self))]
     pub async fn health_check(&self) -> Result<HealthCheck> {
@@ -24,10 +24,13 @@ impl Database {
         &self.db
     }
 
-    /// Initialize database schema
+    /// Initialize database schema, applying any migrations needed to bring
+    /// a database from an older release up to the current schema version
     pub async fn init_schema(&self) -> Result<()> {
-        // With our embedded database, schema initialization is handled automatically
-        // when we create buckets, objects, and users. No explicit schema setup needed.
+        self.db.migrate().await
+            .map_err(|e| object_io_core::ObjectIOError::DatabaseError {
+                message: format!("Failed to migrate database schema: {}", e),
+            })?;
         Ok(())
     }
 
@@ -38,4 +41,12 @@ impl Database {
                 message: e.to_string(),
             })
     }
+
+    /// Compact database storage
+    pub async fn compact(&self) -> Result<()> {
+        self.db.compact().await
+            .map_err(|e| object_io_core::ObjectIOError::DatabaseError {
+                message: e.to_string(),
+            })
+    }
 }
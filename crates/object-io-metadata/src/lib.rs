@@ -1,10 +1,13 @@
 //! ObjectIO Metadata Management
 //!
-//! This crate handles metadata storage and retrieval using SurrealDB.
+//! This crate handles metadata storage and retrieval behind the
+//! `MetadataStore` trait, implemented over the embedded database.
 
 pub mod database;
 pub mod models;
 pub mod operations;
+pub mod store;
 
 pub use database::Database;
 pub use operations::MetadataOperations;
+pub use store::MetadataStore;
@@ -1,6 +1,7 @@
 //! Metadata models for database operations
 
 use chrono::{DateTime, Utc};
+use object_io_core::AccessKeyScope;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
@@ -38,9 +39,36 @@ pub struct UserRecord {
     pub id: Option<serde_json::Value>,
     pub access_key: String,
     pub secret_key: String,
-    pub created_at: String,
+    pub created_at: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_access: Option<DateTime<Utc>>,
     pub is_admin: bool,
     pub permissions: Vec<String>,
+    /// Scope of the matched access key, if restricted
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scope: Option<AccessKeyScope>,
+}
+
+/// Totals across the metadata store
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MetadataStats {
+    pub bucket_count: u64,
+    pub object_count: u64,
+    pub user_count: u64,
+    /// Space the store occupies on disk, in bytes
+    pub size_on_disk: u64,
+    pub checked_at: DateTime<Utc>,
+}
+
+/// One object's metadata in a batch write (see `MetadataStore::put_objects_metadata`)
+#[derive(Debug, Clone)]
+pub struct ObjectMetadataEntry {
+    pub key: String,
+    pub size: u64,
+    pub content_type: String,
+    pub etag: String,
+    /// Standard headers and custom metadata, split as for `put_object_metadata`
+    pub metadata: HashMap<String, String>,
 }
 
 /// Public User type for API operations
@@ -50,8 +78,10 @@ pub struct User {
     pub access_key: String,
     pub secret_key: String,
     pub created_at: DateTime<Utc>,
+    pub last_access: Option<DateTime<Utc>>,
     pub is_admin: bool,
     pub permissions: Vec<String>,
+    pub scope: Option<AccessKeyScope>,
 }
 
 impl From<UserRecord> for User {
@@ -60,11 +90,11 @@ impl From<UserRecord> for User {
             id: record.id.map(|v| v.to_string()),
             access_key: record.access_key,
             secret_key: record.secret_key,
-            created_at: DateTime::parse_from_rfc3339(&record.created_at)
-                .unwrap_or_else(|_| chrono::Utc::now().into())
-                .with_timezone(&Utc),
+            created_at: record.created_at,
+            last_access: record.last_access,
             is_admin: record.is_admin,
             permissions: record.permissions,
+            scope: record.scope,
         }
     }
 }
@@ -75,9 +105,11 @@ impl From<User> for UserRecord {
             id: user.id.map(|id| serde_json::Value::String(id)),
             access_key: user.access_key,
             secret_key: user.secret_key,
-            created_at: user.created_at.to_rfc3339(),
+            created_at: user.created_at,
+            last_access: user.last_access,
             is_admin: user.is_admin,
             permissions: user.permissions,
+            scope: user.scope,
         }
     }
 }
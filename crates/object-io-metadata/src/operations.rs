@@ -1,11 +1,27 @@
 //! Metadata operations for buckets, objects, and users
 
-use crate::{database::Database, models::*};
-use object_io_core::{Bucket, Object, ObjectInfo, Result, StorageClass, VersioningStatus, AccessControl, User};
-use object_io_database::{BucketInfo, ObjectInfo as DbObjectInfo, UserInfo};
+use crate::{database::Database, models::*, store::MetadataStore};
+use object_io_core::{
+    AccessControl, AccessKey, AccessKeyScope, AccessKeyStatus, Bucket, CannedAcl, ChangeEvent, DefaultRetention, Grant,
+    Grantee, KeyPatterns, ListBucketsPage, ListObjectsRequest, ListObjectsResponse, Object, ObjectIOError, ObjectInfo,
+    ObjectRetention, ObjectSummary, Permission, PrefixUsage, Result, StorageClass, User, VersioningStatus,
+};
+use object_io_database::{AccessKeyInfo, BucketAcl, BucketInfo, ObjectInfo as DbObjectInfo, UserInfo};
+use std::collections::HashMap;
 use uuid::Uuid;
 
-/// Metadata operations interface
+/// Map a database error into `ObjectIOError`, keeping typed errors (such as
+/// `BucketAlreadyExists`) and wrapping anything else as `DatabaseError`
+fn db_error(context: &'static str) -> impl Fn(anyhow::Error) -> ObjectIOError {
+    move |e| match ObjectIOError::from(e) {
+        ObjectIOError::Other(e) => ObjectIOError::DatabaseError {
+            message: format!("{}: {}", context, e),
+        },
+        e => e,
+    }
+}
+
+/// Metadata store backed by the embedded database
 pub struct MetadataOperations {
     db: Database,
 }
@@ -16,10 +32,68 @@ impl MetadataOperations {
         Self { db }
     }
 
+    /// Load a user record by ID, failing if it does not exist
+    async fn get_user_info(&self, user_id: &str) -> Result<UserInfo> {
+        self.db.connection()
+            .get_user(user_id)
+            .await
+            .map_err(db_error("Failed to get user"))?
+            .ok_or_else(|| ObjectIOError::UserNotFound {
+                user_id: user_id.to_string(),
+            })
+    }
+
+    /// Change a user's record in one transaction with its access key index,
+    /// failing if the user does not exist
+    async fn modify_user_info<T, F>(&self, user_id: &str, update: F) -> Result<T>
+    where
+        F: Fn(&mut UserInfo) -> Result<T>,
+    {
+        self.db.connection()
+            .modify_user(user_id, update)
+            .await
+            .map_err(db_error("Failed to update user"))
+    }
+}
+
+/// The access key `access_key` of `user_info` for modification, or
+/// `AccessKeyNotFound`
+fn access_key_mut<'a>(user_info: &'a mut UserInfo, access_key: &str) -> Result<&'a mut AccessKeyInfo> {
+    user_info
+        .access_key_mut(access_key)
+        .ok_or_else(|| ObjectIOError::AccessKeyNotFound {
+            access_key: access_key.to_string(),
+        })
+}
+
+#[async_trait::async_trait]
+impl MetadataStore for MetadataOperations {
+    async fn flush(&self) -> Result<()> {
+        self.db.flush().await
+    }
+
+    async fn compact(&self) -> Result<()> {
+        self.db.compact().await
+    }
+
+    async fn stats(&self) -> Result<MetadataStats> {
+        let health = self.db.connection()
+            .health_check()
+            .await
+            .map_err(db_error("Failed to read database stats"))?;
+
+        Ok(MetadataStats {
+            bucket_count: health.buckets_count as u64,
+            object_count: health.objects_count as u64,
+            user_count: health.users_count as u64,
+            size_on_disk: health.size_on_disk,
+            checked_at: health.last_checked,
+        })
+    }
+
     // Bucket operations
-    
-    /// Create a new bucket
-    pub async fn create_bucket(&self, name: &str, owner: &str) -> Result<Bucket> {
+
+    async fn create_bucket(&self, name: &str, owner: &str) -> Result<Bucket> {
         let bucket_info = BucketInfo::new(
             name.to_string(),
             owner.to_string(),
@@ -29,116 +103,157 @@ impl MetadataOperations {
         self.db.connection()
             .create_bucket(bucket_info.clone())
             .await
-            .map_err(|e| object_io_core::ObjectIOError::DatabaseError {
-                message: format!("Failed to create bucket: {}", e),
-            })?;
+            .map_err(db_error("Failed to create bucket"))?;
 
-        Ok(Bucket {
-            name: bucket_info.name,
-            created_at: bucket_info.created_at,
-            region: bucket_info.region,
-            versioning: VersioningStatus::Unversioned,
-            access_control: AccessControl {
-                owner: User {
-                    id: Uuid::new_v4(),
-                    name: bucket_info.owner,
-                    email: "owner@objectio.local".to_string(),
-                    access_keys: vec![],
-                    created_at: bucket_info.created_at,
-                },
-                acl: vec![],
-                policy: None,
-            },
-        })
+        Ok(bucket_from_info(bucket_info))
     }
 
-    /// Get bucket by name
-    pub async fn get_bucket(&self, name: &str) -> Result<Option<Bucket>> {
+    async fn get_bucket(&self, name: &str) -> Result<Option<Bucket>> {
         match self.db.connection()
             .get_bucket(name)
             .await
-            .map_err(|e| object_io_core::ObjectIOError::DatabaseError {
-                message: format!("Failed to get bucket: {}", e),
-            })? {
-            Some(bucket_info) => Ok(Some(Bucket {
-                name: bucket_info.name,
-                created_at: bucket_info.created_at,
-                region: bucket_info.region,
-                versioning: VersioningStatus::Unversioned,
-                access_control: AccessControl {
-                    owner: User {
-                        id: Uuid::new_v4(),
-                        name: bucket_info.owner,
-                        email: "owner@objectio.local".to_string(),
-                        access_keys: vec![],
-                        created_at: bucket_info.created_at,
-                    },
-                    acl: vec![],
-                    policy: None,
-                },
-            })),
+            .map_err(db_error("Failed to get bucket"))? {
+            Some(bucket_info) => Ok(Some(bucket_from_info(bucket_info))),
             None => Ok(None),
         }
     }
 
-    /// Check if bucket exists
-    pub async fn bucket_exists(&self, name: &str) -> Result<bool> {
-        Ok(self.get_bucket(name).await?.is_some())
+    async fn list_buckets(&self, owner: &str) -> Result<Vec<Bucket>> {
+        let bucket_infos = self.db.connection()
+            .list_buckets_by_owner(owner)
+            .await
+            .map_err(db_error("Failed to list buckets"))?;
+
+        Ok(bucket_infos.into_iter().map(bucket_from_info).collect())
+    }
+
+    async fn list_buckets_page(
+        &self,
+        owner: &str,
+        start_after: Option<&str>,
+        max_buckets: usize,
+    ) -> Result<ListBucketsPage> {
+        // Fetch one extra to tell whether another page follows
+        let mut bucket_infos = self.db.connection()
+            .list_buckets_by_owner_after(owner, start_after, max_buckets + 1)
+            .await
+            .map_err(db_error("Failed to list buckets"))?;
+
+        let truncated = bucket_infos.len() > max_buckets;
+        bucket_infos.truncate(max_buckets);
+        let next_marker = if truncated {
+            bucket_infos.last().map(|info| info.name.clone())
+        } else {
+            None
+        };
+
+        Ok(ListBucketsPage {
+            buckets: bucket_infos.into_iter().map(bucket_from_info).collect(),
+            next_marker,
+        })
+    }
+
+    async fn set_versioning(&self, bucket: &str, versioning: VersioningStatus) -> Result<()> {
+        let current = self.get_versioning(bucket).await?;
+        if versioning == VersioningStatus::Unversioned && current != VersioningStatus::Unversioned {
+            return Err(object_io_core::ObjectIOError::InvalidRequest {
+                message: "Versioning can only be suspended once enabled".to_string(),
+            });
+        }
+
+        let updated = self.db.connection()
+            .set_bucket_versioning(bucket, versioning)
+            .await
+            .map_err(db_error("Failed to set bucket versioning"))?;
+
+        if !updated {
+            return Err(object_io_core::ObjectIOError::BucketNotFound {
+                bucket: bucket.to_string(),
+            });
+        }
+        Ok(())
     }
 
-    /// List buckets for owner
-    pub async fn list_buckets(&self, owner: &str) -> Result<Vec<Bucket>> {
+    async fn enable_object_lock(&self, bucket: &str, default_retention: Option<DefaultRetention>) -> Result<()> {
+        let updated = self.db.connection()
+            .enable_bucket_object_lock(bucket, default_retention)
+            .await
+            .map_err(db_error("Failed to enable object lock"))?;
+
+        if !updated {
+            return Err(object_io_core::ObjectIOError::BucketNotFound {
+                bucket: bucket.to_string(),
+            });
+        }
+        Ok(())
+    }
+
+    async fn set_bucket_key_patterns(&self, bucket: &str, key_patterns: Option<KeyPatterns>) -> Result<()> {
+        let updated = self.db.connection()
+            .set_bucket_key_patterns(bucket, key_patterns)
+            .await
+            .map_err(db_error("Failed to set bucket key patterns"))?;
+
+        if !updated {
+            return Err(ObjectIOError::BucketNotFound {
+                bucket: bucket.to_string(),
+            });
+        }
+        Ok(())
+    }
+
+    async fn set_bucket_acl(&self, bucket: &str, acl: &[Grant]) -> Result<()> {
+        let public = |permission: Permission| {
+            acl.contains(&Grant::new(Grantee::AllUsers, permission))
+                || acl.contains(&Grant::new(Grantee::AllUsers, Permission::FullControl))
+        };
+        let (public_read, public_write) = (public(Permission::Read), public(Permission::Write));
+        let updated = self.db.connection()
+            .modify_bucket(bucket, |bucket_info| {
+                bucket_info.acl.public_read = public_read;
+                bucket_info.acl.public_write = public_write;
+            })
+            .map_err(db_error("Failed to update bucket ACL"))?;
+
+        if !updated {
+            return Err(ObjectIOError::BucketNotFound {
+                bucket: bucket.to_string(),
+            });
+        }
+        Ok(())
+    }
+
+    async fn list_all_buckets(&self) -> Result<Vec<Bucket>> {
         let bucket_infos = self.db.connection()
-            .list_buckets_by_owner(owner)
+            .list_buckets()
             .await
-            .map_err(|e| object_io_core::ObjectIOError::DatabaseError {
-                message: format!("Failed to list buckets: {}", e),
-            })?;
+            .map_err(db_error("Failed to list buckets"))?;
 
-        Ok(bucket_infos.into_iter().map(|info| Bucket {
-            name: info.name,
-            created_at: info.created_at,
-            region: info.region,
-            versioning: VersioningStatus::Unversioned,
-            access_control: AccessControl {
-                owner: User {
-                    id: Uuid::new_v4(),
-                    name: info.owner,
-                    email: "owner@objectio.local".to_string(),
-                    access_keys: vec![],
-                    created_at: info.created_at,
-                },
-                acl: vec![],
-                policy: None,
-            },
-        }).collect())
+        Ok(bucket_infos.into_iter().map(bucket_from_info).collect())
     }
 
-    /// Delete bucket
-    pub async fn delete_bucket(&self, name: &str) -> Result<bool> {
-        // First delete all objects in the bucket
-        let _deleted_objects = self.db.connection()
-            .delete_all_objects_in_bucket(name)
+    async fn list_bucket_names(&self) -> Result<Vec<String>> {
+        let bucket_infos = self.db.connection()
+            .list_buckets()
             .await
-            .map_err(|e| object_io_core::ObjectIOError::DatabaseError {
-                message: format!("Failed to delete objects in bucket: {}", e),
-            })?;
+            .map_err(db_error("Failed to list buckets"))?;
+
+        Ok(bucket_infos.into_iter().map(|info| info.name).collect())
+    }
 
-        // Then delete the bucket itself
+    async fn delete_bucket(&self, name: &str) -> Result<bool> {
+        // Fails with `BucketNotEmpty` while object records remain
         let deleted = self.db.connection()
             .delete_bucket(name)
             .await
-            .map_err(|e| object_io_core::ObjectIOError::DatabaseError {
-                message: format!("Failed to delete bucket: {}", e),
-            })?;
+            .map_err(db_error("Failed to delete bucket"))?;
 
         Ok(deleted)
     }
 
     // Object operations
 
-    /// Store object metadata
-    pub async fn put_object(&self, bucket: &str, key: &str, object_info: &ObjectInfo) -> Result<()> {
+    async fn put_object(&self, bucket: &str, key: &str, object_info: &ObjectInfo) -> Result<()> {
         let db_object_info = DbObjectInfo::new(
             key.to_string(),
             bucket.to_string(),
@@ -150,82 +265,228 @@ impl MetadataOperations {
         self.db.connection()
             .put_object(db_object_info)
             .await
-            .map_err(|e| object_io_core::ObjectIOError::DatabaseError {
-                message: format!("Failed to store object metadata: {}", e),
-            })?;
+            .map_err(db_error("Failed to store object metadata"))?;
 
         Ok(())
     }
 
-    /// Get object metadata
-    pub async fn get_object(&self, bucket: &str, key: &str) -> Result<Option<Object>> {
-        match self.db.connection()
-            .get_object(bucket, key)
+    async fn put_object_metadata(
+        &self,
+        bucket: &str,
+        key: &str,
+        size: u64,
+        content_type: &str,
+        etag: &str,
+        metadata: HashMap<String, String>,
+    ) -> Result<Object> {
+        let mut object_info = DbObjectInfo::new(
+            key.to_string(),
+            bucket.to_string(),
+            size,
+            content_type.to_string(),
+            etag.to_string(),
+        );
+        set_object_headers(&mut object_info, metadata);
+
+        self.db.connection()
+            .put_object(object_info.clone())
             .await
-            .map_err(|e| object_io_core::ObjectIOError::DatabaseError {
-                message: format!("Failed to get object: {}", e),
-            })? {
-            Some(object_info) => Ok(Some(Object {
-                key: object_info.key,
-                bucket: object_info.bucket,
-                size: object_info.size,
-                etag: object_info.etag,
-                last_modified: object_info.last_modified,
-                content_type: object_info.content_type,
-                content_encoding: object_info.content_encoding,
-                metadata: object_info.metadata,
-                storage_class: StorageClass::Standard,
-            })),
-            None => Ok(None),
+            .map_err(db_error("Failed to store object metadata"))?;
+
+        Ok(object_from_info(object_info))
+    }
+
+    async fn put_objects_metadata(&self, bucket: &str, objects: Vec<ObjectMetadataEntry>) -> Result<Vec<Object>> {
+        let object_infos: Vec<DbObjectInfo> = objects
+            .into_iter()
+            .map(|object| {
+                let mut object_info = DbObjectInfo::new(
+                    object.key,
+                    bucket.to_string(),
+                    object.size,
+                    object.content_type,
+                    object.etag,
+                );
+                set_object_headers(&mut object_info, object.metadata);
+                object_info
+            })
+            .collect();
+
+        self.db.connection()
+            .put_objects(bucket, &object_infos)
+            .await
+            .map_err(db_error("Failed to store object metadata"))?;
+
+        Ok(object_infos.into_iter().map(object_from_info).collect())
+    }
+
+    async fn update_object_metadata(
+        &self,
+        bucket: &str,
+        key: &str,
+        content_type: &str,
+        metadata: HashMap<String, String>,
+    ) -> Result<Object> {
+        let last_modified = object_io_core::timestamp_now();
+        self.db.connection()
+            .update_object_metadata(bucket, key, |object_info| {
+                object_info.content_type = content_type.to_string();
+                object_info.last_modified = last_modified;
+                set_object_headers(object_info, metadata);
+            })
+            .await
+            .map_err(db_error("Failed to update object metadata"))?
+            .map(object_from_info)
+            .ok_or_else(|| ObjectIOError::ObjectNotFound {
+                bucket: bucket.to_string(),
+                key: key.to_string(),
+            })
+    }
+
+    async fn move_object(&self, bucket: &str, src_key: &str, dst_key: &str) -> Result<Object> {
+        let db = self.db.connection();
+        let mut object_info = db
+            .get_object(bucket, src_key)
+            .await
+            .map_err(db_error("Failed to get object"))?
+            .ok_or_else(|| ObjectIOError::ObjectNotFound {
+                bucket: bucket.to_string(),
+                key: src_key.to_string(),
+            })?;
+        if src_key == dst_key {
+            return Ok(object_from_info(object_info));
         }
+
+        object_info.key = dst_key.to_string();
+        db.put_object(object_info.clone())
+            .await
+            .map_err(db_error("Failed to store moved object"))?;
+        db.delete_object(bucket, src_key)
+            .await
+            .map_err(db_error("Failed to remove moved object"))?;
+        Ok(object_from_info(object_info))
     }
 
-    /// List objects in bucket
-    pub async fn list_objects(&self, bucket: &str, prefix: Option<&str>, _max_keys: Option<u32>) -> Result<Vec<Object>> {
+    async fn touch_object(&self, bucket: &str, key: &str) -> Result<Object> {
+        let last_modified = object_io_core::timestamp_now();
+        self.db.connection()
+            .update_object_metadata(bucket, key, |object_info| object_info.last_modified = last_modified)
+            .await
+            .map_err(db_error("Failed to touch object"))?
+            .map(object_from_info)
+            .ok_or_else(|| ObjectIOError::ObjectNotFound {
+                bucket: bucket.to_string(),
+                key: key.to_string(),
+            })
+    }
+
+    async fn set_object_acl(&self, bucket: &str, key: &str, acl: &[Grant]) -> Result<()> {
+        self.db.connection()
+            .update_object_metadata(bucket, key, |object_info| object_info.acl = acl.to_vec())
+            .await
+            .map_err(db_error("Failed to update object ACL"))?
+            .map(|_| ())
+            .ok_or_else(|| ObjectIOError::ObjectNotFound {
+                bucket: bucket.to_string(),
+                key: key.to_string(),
+            })
+    }
+
+    async fn set_object_legal_hold(&self, bucket: &str, key: &str, on: bool) -> Result<()> {
+        self.db.connection()
+            .update_object_metadata(bucket, key, |object_info| object_info.legal_hold = on)
+            .await
+            .map_err(db_error("Failed to update object legal hold"))?
+            .map(|_| ())
+            .ok_or_else(|| ObjectIOError::ObjectNotFound {
+                bucket: bucket.to_string(),
+                key: key.to_string(),
+            })
+    }
+
+    async fn get_object(&self, bucket: &str, key: &str) -> Result<Option<Object>> {
+        Ok(self.db.connection()
+            .get_object(bucket, key)
+            .await
+            .map_err(db_error("Failed to get object"))?
+            .map(object_from_info))
+    }
+
+    async fn list_objects(&self, request: &ListObjectsRequest) -> Result<ListObjectsResponse> {
         let object_infos = self.db.connection()
-            .list_objects(bucket, prefix)
+            .list_objects(&request.bucket, request.prefix.as_deref())
             .await
-            .map_err(|e| object_io_core::ObjectIOError::DatabaseError {
-                message: format!("Failed to list objects: {}", e),
-            })?;
+            .map_err(db_error("Failed to list objects"))?;
 
-        Ok(object_infos.into_iter().map(|info| Object {
+        let summaries = object_infos.into_iter().map(|info| ObjectSummary {
             key: info.key,
-            bucket: info.bucket,
             size: info.size,
             etag: info.etag,
             last_modified: info.last_modified,
-            content_type: info.content_type,
-            content_encoding: info.content_encoding,
-            metadata: info.metadata,
             storage_class: StorageClass::Standard,
-        }).collect())
+        });
+        Ok(ListObjectsResponse::paginate(request, summaries))
     }
 
-    /// Delete object
-    pub async fn delete_object(&self, bucket: &str, key: &str) -> Result<bool> {
+    async fn delete_object(&self, bucket: &str, key: &str) -> Result<bool> {
         self.db.connection()
             .delete_object(bucket, key)
             .await
-            .map_err(|e| object_io_core::ObjectIOError::DatabaseError {
-                message: format!("Failed to delete object: {}", e),
-            })
+            .map_err(db_error("Failed to delete object"))
+    }
+
+    async fn put_delete_marker(&self, bucket: &str, key: &str) -> Result<String> {
+        let version_id = match self.get_versioning(bucket).await? {
+            VersioningStatus::Suspended => "null".to_string(),
+            _ => Uuid::new_v4().simple().to_string(),
+        };
+        self.db.connection()
+            .put_delete_marker(bucket, key, &version_id)
+            .await
+            .map_err(db_error("Failed to store delete marker"))?;
+        Ok(version_id)
+    }
+
+    async fn get_delete_marker(&self, bucket: &str, key: &str) -> Result<Option<String>> {
+        Ok(self.db.connection()
+            .get_delete_marker(bucket, key)
+            .await
+            .map_err(db_error("Failed to get delete marker"))?
+            .and_then(|marker| marker.version_id))
+    }
+
+    async fn clear_delete_markers(&self, bucket: &str) -> Result<u64> {
+        self.db.connection()
+            .clear_delete_markers(bucket)
+            .map_err(db_error("Failed to clear delete markers"))
     }
 
-    /// Get object count for bucket
-    pub async fn get_object_count(&self, bucket: &str) -> Result<u64> {
+    async fn get_object_count(&self, bucket: &str) -> Result<u64> {
         self.db.connection()
             .get_object_count(bucket)
             .await
-            .map_err(|e| object_io_core::ObjectIOError::DatabaseError {
-                message: format!("Failed to get object count: {}", e),
-            })
+            .map_err(db_error("Failed to get object count"))
+    }
+
+    async fn prefix_usage(&self, bucket: &str, prefix: &str) -> Result<PrefixUsage> {
+        self.db.connection()
+            .prefix_usage(bucket, prefix)
+            .await
+            .map_err(db_error("Failed to sum object sizes"))
+    }
+
+    // Change feed
+
+    async fn list_changes(&self, after: u64, limit: usize) -> Result<Vec<ChangeEvent>> {
+        self.db.connection()
+            .list_changes(after, limit)
+            .await
+            .map_err(db_error("Failed to list changes"))
     }
 
     // User operations
 
-    /// Create user
-    pub async fn create_user(&self, access_key: &str, secret_key_hash: &str, display_name: &str) -> Result<()> {
+    async fn create_user(&self, access_key: &str, secret_key_hash: &str, display_name: &str) -> Result<()> {
         let user_info = UserInfo::new(
             uuid::Uuid::new_v4().to_string(),
             access_key.to_string(),
@@ -237,77 +498,245 @@ impl MetadataOperations {
         self.db.connection()
             .create_user(user_info)
             .await
-            .map_err(|e| object_io_core::ObjectIOError::DatabaseError {
-                message: format!("Failed to create user: {}", e),
-            })?;
+            .map_err(db_error("Failed to create user"))?;
 
         Ok(())
     }
 
-    /// Get user by access key
-    pub async fn get_user_by_access_key(&self, access_key: &str) -> Result<Option<UserRecord>> {
+    async fn get_user_by_access_key(&self, access_key: &str) -> Result<Option<UserRecord>> {
         match self.db.connection()
             .get_user_by_access_key(access_key)
             .await
-            .map_err(|e| object_io_core::ObjectIOError::DatabaseError {
-                message: format!("Failed to get user: {}", e),
-            })? {
-            Some(user_info) => Ok(Some(UserRecord {
-                id: Some(serde_json::Value::String(user_info.user_id)),
-                access_key: user_info.access_key,
-                secret_key: user_info.secret_key_hash,
-                created_at: user_info.created_at.to_rfc3339(),
-                is_admin: user_info.permissions.admin,
-                permissions: vec![], // Convert from our permissions structure if needed
-            })),
-            None => Ok(None),
+            .map_err(db_error("Failed to get user"))? {
+            Some(user_info) if user_info.active => {
+                let key = match user_info.access_key(access_key) {
+                    Some(key) if key.active => key.clone(),
+                    _ => return Ok(None),
+                };
+                Ok(Some(UserRecord {
+                    id: Some(serde_json::Value::String(user_info.user_id)),
+                    access_key: key.access_key,
+                    secret_key: key.secret_key_hash,
+                    created_at: user_info.created_at,
+                    last_access: user_info.last_access,
+                    is_admin: user_info.permissions.admin,
+                    permissions: user_info.permissions.names(),
+                    scope: key.scope,
+                }))
+            }
+            _ => Ok(None),
         }
     }
 
-    /// Check if any admin users exist
-    pub async fn admin_user_exists(&self) -> Result<bool> {
-        let users = self.list_users().await?;
-        Ok(users.iter().any(|user| user.is_admin))
-    }
-
-    /// Check if any users exist (for initial setup)
-    pub async fn user_count(&self) -> Result<u64> {
+    async fn user_count(&self) -> Result<u64> {
         let users = self.db.connection()
             .list_users()
             .await
-            .map_err(|e| object_io_core::ObjectIOError::DatabaseError {
-                message: format!("Failed to count users: {}", e),
-            })?;
+            .map_err(db_error("Failed to count users"))?;
 
         Ok(users.len() as u64)
     }
 
-    /// List all users
-    pub async fn list_users(&self) -> Result<Vec<UserRecord>> {
+    async fn list_users(&self) -> Result<Vec<UserRecord>> {
         let user_infos = self.db.connection()
             .list_users()
             .await
-            .map_err(|e| object_io_core::ObjectIOError::DatabaseError {
-                message: format!("Failed to list users: {}", e),
-            })?;
-
-        Ok(user_infos.into_iter().map(|info| UserRecord {
-            id: Some(serde_json::Value::String(info.user_id)),
-            access_key: info.access_key,
-            secret_key: info.secret_key_hash,
-            created_at: info.created_at.to_rfc3339(),
-            is_admin: info.permissions.admin,
-            permissions: vec![], // Convert from our permissions structure if needed
+            .map_err(db_error("Failed to list users"))?;
+
+        Ok(user_infos.into_iter().map(|info| {
+            let primary_key = info.access_keys.into_iter().next();
+            UserRecord {
+                id: Some(serde_json::Value::String(info.user_id)),
+                access_key: primary_key.as_ref().map(|k| k.access_key.clone()).unwrap_or_default(),
+                secret_key: primary_key.as_ref().map(|k| k.secret_key_hash.clone()).unwrap_or_default(),
+                created_at: info.created_at,
+                last_access: info.last_access,
+                is_admin: info.permissions.admin,
+                permissions: info.permissions.names(),
+                scope: primary_key.and_then(|k| k.scope),
+            }
         }).collect())
     }
 
-    /// Delete user
-    pub async fn delete_user(&self, access_key: &str) -> Result<bool> {
+    async fn delete_user(&self, access_key: &str) -> Result<bool> {
         self.db.connection()
             .delete_user(access_key)
             .await
-            .map_err(|e| object_io_core::ObjectIOError::DatabaseError {
-                message: format!("Failed to delete user: {}", e),
+            .map_err(db_error("Failed to delete user"))
+    }
+
+    // Access key operations
+
+    async fn list_access_keys(&self, user_id: &str) -> Result<Vec<AccessKey>> {
+        let user_info = self.get_user_info(user_id).await?;
+        Ok(user_info.access_keys.into_iter().map(access_key_from_info).collect())
+    }
+
+    async fn create_access_key(&self, user_id: &str) -> Result<AccessKey> {
+        let key = AccessKeyInfo::new(
+            object_io_core::generate_access_key_id(),
+            object_io_core::generate_secret_access_key(),
+        );
+        self.modify_user_info(user_id, |user_info| {
+            user_info.access_keys.push(key.clone());
+            Ok(())
+        })
+        .await?;
+
+        Ok(access_key_from_info(key))
+    }
+
+    async fn set_access_key_status(
+        &self,
+        user_id: &str,
+        access_key: &str,
+        status: AccessKeyStatus,
+    ) -> Result<AccessKey> {
+        let key = self
+            .modify_user_info(user_id, |user_info| {
+                let key = access_key_mut(user_info, access_key)?;
+                key.active = status == AccessKeyStatus::Active;
+                Ok(key.clone())
+            })
+            .await?;
+
+        Ok(access_key_from_info(key))
+    }
+
+    async fn set_access_key_scope(
+        &self,
+        user_id: &str,
+        access_key: &str,
+        scope: Option<AccessKeyScope>,
+    ) -> Result<AccessKey> {
+        if let Some(scope) = &scope {
+            object_io_core::validate_bucket_name(&scope.bucket)?;
+        }
+
+        let key = self
+            .modify_user_info(user_id, |user_info| {
+                let key = access_key_mut(user_info, access_key)?;
+                key.scope = scope.clone();
+                Ok(key.clone())
             })
+            .await?;
+
+        Ok(access_key_from_info(key))
+    }
+
+    async fn delete_access_key(&self, user_id: &str, access_key: &str) -> Result<()> {
+        self.modify_user_info(user_id, |user_info| {
+            access_key_mut(user_info, access_key)?;
+            if user_info.access_keys.len() == 1 {
+                return Err(ObjectIOError::InvalidRequest {
+                    message: "Cannot delete the last access key of a user".to_string(),
+                });
+            }
+            user_info.access_keys.retain(|key| key.access_key != access_key);
+            Ok(())
+        })
+        .await
+    }
+
+    async fn record_access_key_use(&self, access_key: &str) -> Result<()> {
+        self.db.connection()
+            .record_access_key_use(access_key, object_io_core::timestamp_now())
+            .await
+            .map_err(db_error("Failed to record access key use"))?;
+
+        Ok(())
+    }
+}
+
+/// Convert a stored bucket record to the core bucket type
+fn bucket_from_info(info: BucketInfo) -> Bucket {
+    Bucket {
+        name: info.name,
+        created_at: info.created_at,
+        region: info.region,
+        versioning: info.versioning,
+        access_control: AccessControl {
+            owner: User {
+                id: Uuid::new_v4(),
+                name: info.owner,
+                email: "owner@objectio.local".to_string(),
+                access_keys: vec![],
+                created_at: info.created_at,
+            },
+            acl: bucket_acl_grants(&info.acl),
+            policy: None,
+        },
+        object_count: info.object_count,
+        total_size: info.total_size,
+        object_lock_enabled: info.object_lock_enabled,
+        default_retention: info.default_retention,
+        key_patterns: info.key_patterns,
+    }
+}
+
+/// Express a stored bucket ACL as grants
+fn bucket_acl_grants(acl: &BucketAcl) -> Vec<Grant> {
+    let mut grants = Vec::new();
+    if acl.public_read {
+        grants.push(Grant::new(Grantee::AllUsers, Permission::Read));
+    }
+    if acl.public_write {
+        grants.push(Grant::new(Grantee::AllUsers, Permission::Write));
+    }
+    grants
+}
+
+/// Convert a stored object record to the core object type
+fn object_from_info(info: DbObjectInfo) -> Object {
+    Object {
+        key: info.key,
+        bucket: info.bucket,
+        size: info.size,
+        etag: info.etag,
+        last_modified: info.last_modified,
+        content_type: info.content_type,
+        content_encoding: info.content_encoding,
+        cache_control: info.cache_control,
+        content_disposition: info.content_disposition,
+        content_language: info.content_language,
+        expires: info.expires,
+        metadata: info.metadata,
+        storage_class: StorageClass::Standard,
+        acl: info.acl,
+        retention: info.retention,
+        legal_hold: info.legal_hold,
+    }
+}
+
+/// Split the standard headers out of `metadata` into their own fields and
+/// store the rest as custom metadata
+fn set_object_headers(info: &mut DbObjectInfo, mut metadata: HashMap<String, String>) {
+    info.cache_control = metadata.remove("cache-control");
+    info.content_disposition = metadata.remove("content-disposition");
+    info.content_encoding = metadata.remove("content-encoding");
+    info.content_language = metadata.remove("content-language");
+    info.expires = metadata.remove("expires");
+    if let Some(acl) = metadata.remove(CannedAcl::HEADER) {
+        info.acl = CannedAcl::parse(&acl).map(CannedAcl::grants).unwrap_or_default();
+    }
+    if let Some(retention) = ObjectRetention::take_from(&mut metadata) {
+        info.retention = Some(retention);
+    }
+    info.metadata = metadata;
+}
+
+/// Convert a stored access key into the core representation
+fn access_key_from_info(info: AccessKeyInfo) -> AccessKey {
+    AccessKey {
+        access_key_id: info.access_key,
+        secret_access_key: info.secret_key_hash,
+        created_at: info.created_at,
+        last_used: info.last_used,
+        status: if info.active {
+            AccessKeyStatus::Active
+        } else {
+            AccessKeyStatus::Inactive
+        },
+        scope: info.scope,
     }
 }
@@ -0,0 +1,253 @@
+//! Pluggable metadata store interface
+
+use crate::models::{MetadataStats, ObjectMetadataEntry, UserRecord};
+use object_io_core::{
+    AccessKey, AccessKeyScope, AccessKeyStatus, Bucket, ChangeEvent, DefaultRetention, ListBucketsPage,
+    ListObjectsRequest, ListObjectsResponse, Grant, KeyPatterns, Object, ObjectInfo, PrefixUsage, Result,
+    VersioningStatus,
+};
+use std::collections::HashMap;
+
+/// Bucket, object and user metadata operations the API is built on
+///
+/// `MetadataOperations` implements it over the embedded database; other
+/// backends (or test doubles) can be plugged into the server in its place.
+#[async_trait::async_trait]
+pub trait MetadataStore: Send + Sync {
+    /// Flush pending metadata writes to disk
+    async fn flush(&self) -> Result<()>;
+
+    /// Compact the metadata database
+    async fn compact(&self) -> Result<()>;
+
+    /// Count the buckets, objects and users held by the store
+    async fn stats(&self) -> Result<MetadataStats>;
+
+    // Bucket operations
+
+    /// Create a new bucket
+    async fn create_bucket(&self, name: &str, owner: &str) -> Result<Bucket>;
+
+    /// Get bucket by name
+    async fn get_bucket(&self, name: &str) -> Result<Option<Bucket>>;
+
+    /// Check if bucket exists
+    async fn bucket_exists(&self, name: &str) -> Result<bool> {
+        Ok(self.get_bucket(name).await?.is_some())
+    }
+
+    /// List buckets for owner
+    async fn list_buckets(&self, owner: &str) -> Result<Vec<Bucket>>;
+
+    /// List up to `max_buckets` of an owner's buckets in name order,
+    /// starting after `start_after`
+    async fn list_buckets_page(
+        &self,
+        owner: &str,
+        start_after: Option<&str>,
+        max_buckets: usize,
+    ) -> Result<ListBucketsPage>;
+
+    /// Get a bucket's versioning status
+    async fn get_versioning(&self, bucket: &str) -> Result<VersioningStatus> {
+        self.get_bucket(bucket)
+            .await?
+            .map(|bucket| bucket.versioning)
+            .ok_or_else(|| object_io_core::ObjectIOError::BucketNotFound {
+                bucket: bucket.to_string(),
+            })
+    }
+
+    /// Set a bucket's versioning status
+    ///
+    /// Like S3, versioning can only be suspended once enabled, not turned off.
+    async fn set_versioning(&self, bucket: &str, versioning: VersioningStatus) -> Result<()>;
+
+    /// Enable object lock on a bucket, after which its objects may be given
+    /// retention periods, and replace the retention given to new objects
+    /// that don't ask for their own
+    ///
+    /// As in S3, object lock cannot be disabled once enabled.
+    async fn enable_object_lock(&self, bucket: &str, default_retention: Option<DefaultRetention>) -> Result<()>;
+
+    /// Replace the patterns restricting the keys that may be written to a
+    /// bucket; `None` removes them
+    async fn set_bucket_key_patterns(&self, bucket: &str, key_patterns: Option<KeyPatterns>) -> Result<()>;
+
+    /// Replace the grants a bucket gives beyond its owner's full control
+    ///
+    /// Only public read and write grants are stored for buckets; any other
+    /// grant is ignored.
+    async fn set_bucket_acl(&self, bucket: &str, acl: &[Grant]) -> Result<()>;
+
+    /// List all buckets, regardless of owner
+    async fn list_all_buckets(&self) -> Result<Vec<Bucket>>;
+
+    /// List the names of all buckets, regardless of owner
+    async fn list_bucket_names(&self) -> Result<Vec<String>>;
+
+    /// Delete bucket
+    async fn delete_bucket(&self, name: &str) -> Result<bool>;
+
+    // Object operations
+
+    /// Store object metadata
+    async fn put_object(&self, bucket: &str, key: &str, object_info: &ObjectInfo) -> Result<()>;
+
+    /// Record an object's metadata once its data has been stored
+    ///
+    /// Entries in `metadata` named after `OBJECT_HEADERS` are stored as the
+    /// object's standard headers, and those `ObjectRetention::insert_into`
+    /// adds as its retention; the rest are custom metadata.
+    async fn put_object_metadata(
+        &self,
+        bucket: &str,
+        key: &str,
+        size: u64,
+        content_type: &str,
+        etag: &str,
+        metadata: HashMap<String, String>,
+    ) -> Result<Object>;
+
+    /// Record the metadata of many objects in one bucket at once, as for
+    /// `put_object_metadata`, returning them in the order given
+    ///
+    /// The default stores them one by one; backends should override it to
+    /// write them in a single batch, updating the bucket statistics once.
+    async fn put_objects_metadata(&self, bucket: &str, objects: Vec<ObjectMetadataEntry>) -> Result<Vec<Object>> {
+        let mut stored = Vec::with_capacity(objects.len());
+        for object in objects {
+            let ObjectMetadataEntry { key, size, content_type, etag, metadata } = object;
+            stored.push(self.put_object_metadata(bucket, &key, size, &content_type, &etag, metadata).await?);
+        }
+        Ok(stored)
+    }
+
+    /// Replace an object's content type, standard headers and custom metadata
+    /// without touching its data, bumping its last-modified time
+    ///
+    /// `metadata` is split as for `put_object_metadata`.
+    async fn update_object_metadata(
+        &self,
+        bucket: &str,
+        key: &str,
+        content_type: &str,
+        metadata: HashMap<String, String>,
+    ) -> Result<Object>;
+
+    /// Move an object's record to another key in the same bucket, keeping its
+    /// ETag, size, headers, metadata and ACL, and replacing any record
+    /// already at `dst_key`
+    async fn move_object(&self, bucket: &str, src_key: &str, dst_key: &str) -> Result<Object>;
+
+    /// Bump an object's last-modified time to now, keeping its data, ETag and
+    /// metadata
+    async fn touch_object(&self, bucket: &str, key: &str) -> Result<Object>;
+
+    /// Replace the grants an object gives beyond its owner's full control
+    async fn set_object_acl(&self, bucket: &str, key: &str, acl: &[Grant]) -> Result<()>;
+
+    /// Place or remove a legal hold on an object
+    async fn set_object_legal_hold(&self, bucket: &str, key: &str, on: bool) -> Result<()>;
+
+    /// Get object metadata
+    async fn get_object(&self, bucket: &str, key: &str) -> Result<Option<Object>>;
+
+    /// List one page of objects in a bucket, ordered by key
+    ///
+    /// Paginates exactly like the storage backends' listings
+    /// (see `ListObjectsResponse::paginate`).
+    async fn list_objects(&self, request: &ListObjectsRequest) -> Result<ListObjectsResponse>;
+
+    /// Delete object
+    async fn delete_object(&self, bucket: &str, key: &str) -> Result<bool>;
+
+    /// Record that `key` was deleted from a versioned bucket, returning the
+    /// marker's version ID
+    ///
+    /// As in S3, the ID is `null` while versioning is suspended. Storing the
+    /// key again removes the marker.
+    async fn put_delete_marker(&self, bucket: &str, key: &str) -> Result<String>;
+
+    /// Version ID of the delete marker left by the last delete of `key`, if
+    /// the key hasn't been stored since
+    async fn get_delete_marker(&self, bucket: &str, key: &str) -> Result<Option<String>>;
+
+    /// Remove every delete marker in a bucket, returning how many there were
+    async fn clear_delete_markers(&self, bucket: &str) -> Result<u64>;
+
+    /// Get object count for bucket
+    async fn get_object_count(&self, bucket: &str) -> Result<u64>;
+
+    /// Number and total size of the objects under `prefix` in a bucket,
+    /// computed by the store rather than from listings
+    async fn prefix_usage(&self, bucket: &str, prefix: &str) -> Result<PrefixUsage>;
+
+    // Change feed
+
+    /// Up to `limit` object change events numbered after `after`, oldest first
+    ///
+    /// Every put and delete of an object record is appended to the feed in
+    /// the same transaction as the change itself, so a reader resuming after
+    /// the last sequence number it saw neither misses nor repeats an event.
+    async fn list_changes(&self, after: u64, limit: usize) -> Result<Vec<ChangeEvent>>;
+
+    // User operations
+
+    /// Create user
+    async fn create_user(&self, access_key: &str, secret_key_hash: &str, display_name: &str) -> Result<()>;
+
+    /// Get user by access key
+    ///
+    /// Any active key of an active user resolves to that user; the returned
+    /// record carries the matched key and its secret. Inactive keys resolve to `None`.
+    async fn get_user_by_access_key(&self, access_key: &str) -> Result<Option<UserRecord>>;
+
+    /// Check if any admin users exist
+    async fn admin_user_exists(&self) -> Result<bool> {
+        let users = self.list_users().await?;
+        Ok(users.iter().any(|user| user.is_admin))
+    }
+
+    /// Check if any users exist (for initial setup)
+    async fn user_count(&self) -> Result<u64>;
+
+    /// List all users
+    async fn list_users(&self) -> Result<Vec<UserRecord>>;
+
+    /// Delete user
+    async fn delete_user(&self, access_key: &str) -> Result<bool>;
+
+    // Access key operations
+
+    /// List access keys for a user
+    async fn list_access_keys(&self, user_id: &str) -> Result<Vec<AccessKey>>;
+
+    /// Create an additional active access key for a user
+    async fn create_access_key(&self, user_id: &str) -> Result<AccessKey>;
+
+    /// Mark one of a user's access keys active or inactive
+    async fn set_access_key_status(
+        &self,
+        user_id: &str,
+        access_key: &str,
+        status: AccessKeyStatus,
+    ) -> Result<AccessKey>;
+
+    /// Restrict one of a user's access keys to a bucket and key prefix, or
+    /// lift the restriction with `None`
+    async fn set_access_key_scope(
+        &self,
+        user_id: &str,
+        access_key: &str,
+        scope: Option<AccessKeyScope>,
+    ) -> Result<AccessKey>;
+
+    /// Delete one of a user's access keys
+    ///
+    /// The last remaining key cannot be deleted; delete the user instead.
+    async fn delete_access_key(&self, user_id: &str, access_key: &str) -> Result<()>;
+
+    /// Record a successful authentication with an access key
+    async fn record_access_key_use(&self, access_key: &str) -> Result<()>;
+}
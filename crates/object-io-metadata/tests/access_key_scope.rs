@@ -0,0 +1,58 @@
+//! Tests for bucket and prefix scoped access keys
+
+use object_io_core::{AccessKeyScope, ObjectIOError};
+use object_io_metadata::{Database, MetadataOperations, MetadataStore};
+use tempfile::TempDir;
+
+#[tokio::test]
+async fn test_access_key_scope_round_trip() {
+    let temp_dir = TempDir::new().unwrap();
+    let db_path = temp_dir.path().join("test_db");
+
+    let database = Database::new(db_path.to_str().unwrap()).await.unwrap();
+    database.init_schema().await.unwrap();
+    let ops = MetadataOperations::new(database);
+
+    ops.create_user("AKIASCOPETEST0000001", "secret", "Scoped User").await.unwrap();
+    let user = ops.get_user_by_access_key("AKIASCOPETEST0000001").await.unwrap().unwrap();
+    let user_id = user.id.as_ref().and_then(|id| id.as_str()).unwrap().to_string();
+    assert!(user.scope.is_none());
+
+    // Scope a second key to read-only access under reports/
+    let key = ops.create_access_key(&user_id).await.unwrap();
+    let scope = AccessKeyScope {
+        bucket: "analytics".to_string(),
+        prefix: "reports/".to_string(),
+        read_only: true,
+    };
+    let scoped = ops
+        .set_access_key_scope(&user_id, &key.access_key_id, Some(scope.clone()))
+        .await
+        .unwrap();
+    assert_eq!(scoped.scope, Some(scope.clone()));
+
+    // Authentication sees the matched key's scope; the other key stays unrestricted
+    let record = ops.get_user_by_access_key(&key.access_key_id).await.unwrap().unwrap();
+    assert_eq!(record.scope, Some(scope));
+    let record = ops.get_user_by_access_key("AKIASCOPETEST0000001").await.unwrap().unwrap();
+    assert!(record.scope.is_none());
+
+    // Lifting the scope
+    let unscoped = ops.set_access_key_scope(&user_id, &key.access_key_id, None).await.unwrap();
+    assert!(unscoped.scope.is_none());
+
+    // Invalid bucket names and unknown keys are rejected
+    let invalid = AccessKeyScope {
+        bucket: String::new(),
+        prefix: String::new(),
+        read_only: false,
+    };
+    assert!(matches!(
+        ops.set_access_key_scope(&user_id, &key.access_key_id, Some(invalid)).await,
+        Err(ObjectIOError::InvalidBucketName { .. })
+    ));
+    assert!(matches!(
+        ops.set_access_key_scope(&user_id, "AKIAMISSING", None).await,
+        Err(ObjectIOError::AccessKeyNotFound { .. })
+    ));
+}
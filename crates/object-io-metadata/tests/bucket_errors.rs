@@ -0,0 +1,32 @@
+//! Tests for the typed errors surfaced from the database layer
+
+use object_io_core::ObjectIOError;
+use object_io_metadata::{Database, MetadataOperations, MetadataStore};
+use std::collections::HashMap;
+use tempfile::TempDir;
+
+#[tokio::test]
+async fn test_bucket_errors_keep_their_kind() {
+    let temp_dir = TempDir::new().unwrap();
+    let db_path = temp_dir.path().join("test_db");
+
+    let database = Database::new(db_path.to_str().unwrap()).await.unwrap();
+    database.init_schema().await.unwrap();
+    let ops = MetadataOperations::new(database);
+
+    ops.create_bucket("errors", "owner").await.unwrap();
+    let err = ops.create_bucket("errors", "owner").await.unwrap_err();
+    assert!(matches!(err, ObjectIOError::BucketAlreadyExists { ref bucket } if bucket == "errors"));
+    assert_eq!(err.status_code(), 409);
+
+    ops.put_object_metadata("errors", "a.txt", 1, "text/plain", "etag-a", HashMap::new())
+        .await
+        .unwrap();
+    let err = ops.delete_bucket("errors").await.unwrap_err();
+    assert!(matches!(err, ObjectIOError::BucketNotEmpty { ref bucket } if bucket == "errors"));
+    assert!(ops.get_object("errors", "a.txt").await.unwrap().is_some());
+
+    assert!(ops.delete_object("errors", "a.txt").await.unwrap());
+    assert!(ops.delete_bucket("errors").await.unwrap());
+    assert!(!ops.delete_bucket("errors").await.unwrap());
+}
@@ -0,0 +1,101 @@
+//! Tests for the per-bucket object count and size reported in listings
+
+use object_io_metadata::{models::ObjectMetadataEntry, Database, MetadataOperations, MetadataStore};
+use std::time::Instant;
+use std::collections::HashMap;
+use tempfile::TempDir;
+
+#[tokio::test]
+async fn test_list_buckets_reports_maintained_stats() {
+    let temp_dir = TempDir::new().unwrap();
+    let db_path = temp_dir.path().join("test_db");
+
+    let database = Database::new(db_path.to_str().unwrap()).await.unwrap();
+    database.init_schema().await.unwrap();
+    let ops = MetadataOperations::new(database);
+
+    let created = ops.create_bucket("stats-bucket", "owner").await.unwrap();
+    assert_eq!((created.object_count, created.total_size), (0, 0));
+    ops.create_bucket("empty-bucket", "owner").await.unwrap();
+
+    ops.put_object_metadata("stats-bucket", "a.txt", 100, "text/plain", "etag-a", HashMap::new())
+        .await
+        .unwrap();
+    ops.put_object_metadata("stats-bucket", "b.txt", 50, "text/plain", "etag-b", HashMap::new())
+        .await
+        .unwrap();
+    // Overwriting replaces the previous size instead of adding to it
+    ops.put_object_metadata("stats-bucket", "a.txt", 30, "text/plain", "etag-a2", HashMap::new())
+        .await
+        .unwrap();
+
+    let stats = |buckets: &[object_io_core::Bucket], name: &str| {
+        let bucket = buckets.iter().find(|b| b.name == name).unwrap();
+        (bucket.object_count, bucket.total_size)
+    };
+
+    let buckets = ops.list_buckets("owner").await.unwrap();
+    assert_eq!(stats(&buckets, "stats-bucket"), (2, 80));
+    assert_eq!(stats(&buckets, "empty-bucket"), (0, 0));
+
+    assert!(ops.delete_object("stats-bucket", "b.txt").await.unwrap());
+    let bucket = ops.get_bucket("stats-bucket").await.unwrap().unwrap();
+    assert_eq!((bucket.object_count, bucket.total_size), (1, 30));
+}
+
+#[tokio::test]
+async fn test_put_objects_metadata_batch() {
+    let temp_dir = TempDir::new().unwrap();
+    let db_path = temp_dir.path().join("test_db");
+
+    let database = Database::new(db_path.to_str().unwrap()).await.unwrap();
+    database.init_schema().await.unwrap();
+    let ops = MetadataOperations::new(database);
+    ops.create_bucket("looped", "owner").await.unwrap();
+    ops.create_bucket("batched", "owner").await.unwrap();
+
+    let entries: Vec<ObjectMetadataEntry> = (0..1000)
+        .map(|i| ObjectMetadataEntry {
+            key: format!("import/{:04}.txt", i),
+            size: 10,
+            content_type: "text/plain".to_string(),
+            etag: format!("etag-{}", i),
+            metadata: HashMap::from([("cache-control".to_string(), "no-cache".to_string())]),
+        })
+        .collect();
+
+    let started = Instant::now();
+    for entry in entries.clone() {
+        ops.put_object_metadata("looped", &entry.key, entry.size, &entry.content_type, &entry.etag, entry.metadata)
+            .await
+            .unwrap();
+    }
+    let looped = started.elapsed();
+
+    let started = Instant::now();
+    let stored = ops.put_objects_metadata("batched", entries.clone()).await.unwrap();
+    let batched = started.elapsed();
+    eprintln!("1000 objects: {:?} one at a time, {:?} in one batch", looped, batched);
+
+    assert_eq!(stored.len(), 1000);
+    assert_eq!(stored[0].key, "import/0000.txt");
+    let object = ops.get_object("batched", "import/0999.txt").await.unwrap().unwrap();
+    assert_eq!(object.etag, "etag-999");
+    assert_eq!(object.cache_control.as_deref(), Some("no-cache"));
+
+    let stats = |buckets: &[object_io_core::Bucket], name: &str| {
+        let bucket = buckets.iter().find(|b| b.name == name).unwrap();
+        (bucket.object_count, bucket.total_size)
+    };
+    let buckets = ops.list_buckets("owner").await.unwrap();
+    assert_eq!(stats(&buckets, "batched"), (1000, 10_000));
+    assert_eq!(stats(&buckets, "batched"), stats(&buckets, "looped"));
+
+    // Overwrites within and across batches replace the previous size
+    let mut overwrites = entries[..2].to_vec();
+    overwrites[0].size = 4;
+    overwrites.push(ObjectMetadataEntry { size: 1, ..entries[1].clone() });
+    ops.put_objects_metadata("batched", overwrites).await.unwrap();
+    let buckets = ops.list_buckets("owner").await.unwrap();
+    assert_eq!(stats(&buckets, "batched"), (1000, 10_000 - 10 - 10 + 4 + 1));
+}
@@ -1,7 +1,7 @@
 //! Integration tests for metadata operations
 
 use chrono::Utc;
-use object_io_metadata::{Database, MetadataOperations};
+use object_io_metadata::{Database, MetadataOperations, MetadataStore};
 use std::collections::HashMap;
 use tempfile::TempDir;
 use tokio;
@@ -0,0 +1,57 @@
+//! Tests for paginated bucket listings
+
+use object_io_core::ListBucketsPage;
+use object_io_metadata::{Database, MetadataOperations, MetadataStore};
+use tempfile::TempDir;
+
+fn names(page: &ListBucketsPage) -> Vec<&str> {
+    page.buckets.iter().map(|b| b.name.as_str()).collect()
+}
+
+#[tokio::test]
+async fn test_list_buckets_pages_in_name_order() {
+    let temp_dir = TempDir::new().unwrap();
+    let db_path = temp_dir.path().join("test_db");
+
+    let database = Database::new(db_path.to_str().unwrap()).await.unwrap();
+    database.init_schema().await.unwrap();
+    let ops = MetadataOperations::new(database);
+
+    for name in ["delta", "alpha", "echo", "charlie", "bravo"] {
+        ops.create_bucket(name, "owner").await.unwrap();
+    }
+    // Other owners' buckets are interleaved but never listed
+    ops.create_bucket("beta", "someone-else").await.unwrap();
+    ops.create_bucket("zulu", "someone-else").await.unwrap();
+
+    let page = ops.list_buckets_page("owner", None, 10).await.unwrap();
+    assert_eq!(names(&page), ["alpha", "bravo", "charlie", "delta", "echo"]);
+    assert_eq!(page.next_marker, None);
+
+    // Walking with a small page size visits every bucket once, in order
+    let mut listed = Vec::new();
+    let mut start_after = None;
+    let mut pages = 0;
+    loop {
+        let page = ops.list_buckets_page("owner", start_after.as_deref(), 2).await.unwrap();
+        listed.extend(names(&page).into_iter().map(String::from));
+        pages += 1;
+        match page.next_marker {
+            Some(marker) => start_after = Some(marker),
+            None => break,
+        }
+    }
+    assert_eq!(pages, 3);
+    assert_eq!(listed, ["alpha", "bravo", "charlie", "delta", "echo"]);
+
+    // An exactly full last page is not reported as truncated
+    let page = ops.list_buckets_page("owner", Some("charlie"), 2).await.unwrap();
+    assert_eq!(names(&page), ["delta", "echo"]);
+    assert_eq!(page.next_marker, None);
+
+    // A bucket created behind the marker doesn't disturb later pages
+    let page = ops.list_buckets_page("owner", None, 2).await.unwrap();
+    ops.create_bucket("aardvark", "owner").await.unwrap();
+    let page = ops.list_buckets_page("owner", page.next_marker.as_deref(), 2).await.unwrap();
+    assert_eq!(names(&page), ["charlie", "delta"]);
+}
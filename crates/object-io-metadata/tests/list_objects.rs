@@ -0,0 +1,62 @@
+//! Tests for paginated object listings
+
+use object_io_core::{ListObjectsRequest, ListObjectsResponse};
+use object_io_metadata::{Database, MetadataOperations, MetadataStore};
+use std::collections::HashMap;
+use tempfile::TempDir;
+
+fn keys(page: &ListObjectsResponse) -> Vec<&str> {
+    page.objects.iter().map(|o| o.key.as_str()).collect()
+}
+
+#[tokio::test]
+async fn test_list_objects_pages_with_marker() {
+    let temp_dir = TempDir::new().unwrap();
+    let db_path = temp_dir.path().join("test_db");
+
+    let database = Database::new(db_path.to_str().unwrap()).await.unwrap();
+    database.init_schema().await.unwrap();
+    let ops = MetadataOperations::new(database);
+
+    ops.create_bucket("media", "owner").await.unwrap();
+    ops.create_bucket("media-archive", "owner").await.unwrap();
+    for key in ["logs/2024/a.log", "logs/2024/b.log", "logs/2025/c.log", "logs/index.txt", "readme.txt"] {
+        ops.put_object_metadata("media", key, 1, "text/plain", "etag", HashMap::new())
+            .await
+            .unwrap();
+    }
+    // Objects in a bucket sharing the name as a prefix stay out of the listing
+    ops.put_object_metadata("media-archive", "old.txt", 1, "text/plain", "etag", HashMap::new())
+        .await
+        .unwrap();
+
+    let page = ops.list_objects(&ListObjectsRequest::new("media")).await.unwrap();
+    assert_eq!(page.max_keys, 1000);
+    assert_eq!(keys(&page).len(), 5);
+    assert!(!page.is_truncated);
+
+    // Walking with a small max-keys visits every key once
+    let mut request = ListObjectsRequest::new("media").capped(2);
+    let mut listed = Vec::new();
+    loop {
+        let page = ops.list_objects(&request).await.unwrap();
+        assert!(page.objects.len() <= 2);
+        listed.extend(keys(&page).into_iter().map(str::to_string));
+        if !page.is_truncated {
+            break;
+        }
+        request.marker = page.next_marker;
+    }
+    assert_eq!(listed, ["logs/2024/a.log", "logs/2024/b.log", "logs/2025/c.log", "logs/index.txt", "readme.txt"]);
+
+    let request = ListObjectsRequest {
+        prefix: Some("logs/".to_string()),
+        delimiter: Some("/".to_string()),
+        max_keys: Some(2),
+        ..ListObjectsRequest::new("media")
+    };
+    let page = ops.list_objects(&request).await.unwrap();
+    assert_eq!(page.common_prefixes, ["logs/2024/", "logs/2025/"]);
+    assert!(page.is_truncated);
+    assert_eq!(page.next_marker.as_deref(), Some("logs/2025/"));
+}
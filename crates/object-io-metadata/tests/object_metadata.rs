@@ -0,0 +1,124 @@
+//! Tests for in-place object metadata updates
+
+use object_io_metadata::{Database, MetadataOperations, MetadataStore};
+use std::collections::HashMap;
+use tempfile::TempDir;
+
+#[tokio::test]
+async fn test_update_object_metadata_in_place() {
+    let temp_dir = TempDir::new().unwrap();
+    let db_path = temp_dir.path().join("test_db");
+
+    let database = Database::new(db_path.to_str().unwrap()).await.unwrap();
+    database.init_schema().await.unwrap();
+    let ops = MetadataOperations::new(database);
+
+    ops.create_bucket("test-bucket", "testuser").await.unwrap();
+    let original = ops
+        .put_object_metadata("test-bucket", "notes.txt", 1024, "text/plain", "abcdef", HashMap::new())
+        .await
+        .unwrap();
+
+    // Timestamps are stored in whole milliseconds
+    tokio::time::sleep(std::time::Duration::from_millis(2)).await;
+
+    // Change the content type and add a custom header
+    let mut metadata = HashMap::new();
+    metadata.insert("reviewed-by".to_string(), "ops".to_string());
+    let updated = ops
+        .update_object_metadata("test-bucket", "notes.txt", "text/markdown", metadata.clone())
+        .await
+        .unwrap();
+
+    assert_eq!(updated.content_type, "text/markdown");
+    assert_eq!(updated.metadata, metadata);
+    assert!(updated.last_modified > original.last_modified);
+
+    // Bytes are untouched, so size and ETag are unchanged
+    let stored = ops.get_object("test-bucket", "notes.txt").await.unwrap().unwrap();
+    assert_eq!(stored.content_type, "text/markdown");
+    assert_eq!(stored.metadata.get("reviewed-by").map(String::as_str), Some("ops"));
+    assert_eq!(stored.size, 1024);
+    assert_eq!(stored.etag, "abcdef");
+
+    // The update doesn't count as a new object
+    assert_eq!(ops.get_object_count("test-bucket").await.unwrap(), 1);
+
+    assert!(matches!(
+        ops.update_object_metadata("test-bucket", "missing.txt", "text/plain", HashMap::new()).await,
+        Err(object_io_core::ObjectIOError::ObjectNotFound { .. })
+    ));
+}
+
+#[tokio::test]
+async fn test_standard_headers_stored_separately() {
+    let temp_dir = TempDir::new().unwrap();
+    let db_path = temp_dir.path().join("test_db");
+
+    let database = Database::new(db_path.to_str().unwrap()).await.unwrap();
+    database.init_schema().await.unwrap();
+    let ops = MetadataOperations::new(database);
+
+    ops.create_bucket("assets", "owner").await.unwrap();
+    let metadata = HashMap::from([
+        ("cache-control".to_string(), "no-cache".to_string()),
+        ("expires".to_string(), "Thu, 01 Dec 2044 16:00:00 GMT".to_string()),
+        ("build".to_string(), "42".to_string()),
+    ]);
+    let object = ops
+        .put_object_metadata("assets", "app.js", 1, "text/javascript", "etag", metadata)
+        .await
+        .unwrap();
+    assert_eq!(object.cache_control.as_deref(), Some("no-cache"));
+    assert_eq!(object.header("expires"), Some("Thu, 01 Dec 2044 16:00:00 GMT"));
+    assert_eq!(object.metadata, HashMap::from([("build".to_string(), "42".to_string())]));
+
+    // Replacing metadata replaces the headers too
+    let metadata = HashMap::from([("content-language".to_string(), "fr".to_string())]);
+    ops.update_object_metadata("assets", "app.js", "text/javascript", metadata).await.unwrap();
+    let stored = ops.get_object("assets", "app.js").await.unwrap().unwrap();
+    let headers: Vec<_> = stored.headers().collect();
+    assert_eq!(headers, [("content-language", "fr")]);
+    assert!(stored.metadata.is_empty());
+}
+
+#[tokio::test]
+async fn test_timestamps_round_trip_exactly() {
+    let temp_dir = TempDir::new().unwrap();
+    let db_path = temp_dir.path().join("test_db");
+
+    let stored = {
+        let database = Database::new(db_path.to_str().unwrap()).await.unwrap();
+        database.init_schema().await.unwrap();
+        let ops = MetadataOperations::new(database);
+
+        ops.create_bucket("test-bucket", "testuser").await.unwrap();
+        let stored = ops
+            .put_object_metadata("test-bucket", "notes.txt", 1024, "text/plain", "abcdef", HashMap::new())
+            .await
+            .unwrap();
+        ops.flush().await.unwrap();
+        stored
+    };
+
+    // Stored at millisecond precision, the finest S3 timestamps show
+    assert_eq!(stored.last_modified.timestamp_subsec_nanos() % 1_000_000, 0);
+
+    let database = Database::new(db_path.to_str().unwrap()).await.unwrap();
+    let ops = MetadataOperations::new(database);
+    let reloaded = ops.get_object("test-bucket", "notes.txt").await.unwrap().unwrap();
+    assert_eq!(reloaded.last_modified, stored.last_modified);
+
+    let listed = ops
+        .list_objects(&object_io_core::ListObjectsRequest::new("test-bucket"))
+        .await
+        .unwrap();
+    assert_eq!(listed.objects[0].last_modified, stored.last_modified);
+
+    // What clients are shown parses back to the stored value
+    let shown = object_io_core::format_s3_timestamp(&reloaded.last_modified);
+    assert_eq!(object_io_core::parse_s3_timestamp(&shown).unwrap(), stored.last_modified);
+
+    let bucket = ops.get_bucket("test-bucket").await.unwrap().unwrap();
+    assert_eq!(bucket.created_at.timestamp_subsec_nanos() % 1_000_000, 0);
+}
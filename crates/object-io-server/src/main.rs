@@ -3,9 +3,12 @@
 //! Main server binary for the ObjectIO S3-compatible storage system.
 
 use anyhow::Result;
-use object_io_api::create_app;
-use std::net::SocketAddr;
+use object_io_api::{create_router, self_check::self_check, serve, tasks, AppState, ServerConfig};
+use object_io_storage::StorageBackend;
+use std::path::PathBuf;
+use std::time::Duration;
 use tokio::net::TcpListener;
+use tokio::sync::oneshot;
 use tracing::{info, warn};
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
@@ -22,33 +25,86 @@ async fn main() -> Result<()> {
 
     info!("Starting ObjectIO Server");
 
-    // Load configuration from environment
+    // Load configuration from the config file, environment and defaults
     dotenvy::dotenv().ok();
-    
-    let _host = std::env::var("HOST").unwrap_or_else(|_| "0.0.0.0".to_string());
-    let port = std::env::var("PORT")
-        .unwrap_or_else(|_| "5500".to_string())
-        .parse::<u16>()
-        .unwrap_or(5500);
 
-    let addr = SocketAddr::from(([0, 0, 0, 0], port));
-    info!("Server listening on {}", addr);
+    let config_path = config_path();
+    if let Some(path) = &config_path {
+        info!("Loading configuration from {}", path.display());
+    }
+    let config = ServerConfig::load(config_path.as_deref())?;
 
-    // Create the application
-    let app = create_app().await?;
+    // Report every misconfigured subsystem before binding the port
+    self_check(&config).await.into_result()?;
 
     // Create TCP listener
-    let listener = TcpListener::bind(addr).await?;
+    let listener = TcpListener::bind((config.host.as_str(), config.port)).await?;
+    info!("Server listening on {}", listener.local_addr()?);
+
+    // Create the storage backend and application
+    info!("Using {:?} storage backend", config.storage_backend);
+    let storage = StorageBackend::new(config.storage_config()?).await?;
+    let state = AppState::new(config, storage).await?;
+    let app = create_router(state.clone());
+
+    // Periodically abort abandoned multipart uploads
+    let sweeper = tasks::spawn_multipart_sweeper(state.clone());
+
+    // Copy object changes to the replication target, if one is configured
+    let replicator = tasks::spawn_replicator(state.clone()).await?;
 
-    // Start the server
-    axum::serve(listener, app)
-        .with_graceful_shutdown(shutdown_signal())
-        .await?;
+    // Start the server; once a shutdown signal arrives it stops accepting
+    // connections and drains in-flight requests
+    let (signal_tx, signal_rx) = oneshot::channel();
+    let config = state.config.clone();
+    let server = serve(listener, app, &config, async move {
+        shutdown_signal().await;
+        let _ = signal_tx.send(());
+    });
+    tokio::pin!(server);
+
+    tokio::select! {
+        result = &mut server => result?,
+        Ok(()) = signal_rx => {
+            let drain_timeout = Duration::from_secs(state.config.shutdown_timeout);
+            info!("Draining in-flight requests (timeout {:?})", drain_timeout);
+            match tokio::time::timeout(drain_timeout, &mut server).await {
+                Ok(result) => result?,
+                Err(_) => warn!(
+                    "Shutdown timeout reached with {} requests still in flight",
+                    state.in_flight.count()
+                ),
+            }
+        }
+    }
+
+    // Persist buffered writes before exiting
+    if let Some(sweeper) = sweeper {
+        sweeper.abort();
+    }
+    if let Some(replicator) = replicator {
+        replicator.abort();
+    }
+    state.shutdown().await?;
 
     info!("Server shut down gracefully");
     Ok(())
 }
 
+/// Config file path from `--config <path>` or the `OBJECTIO_CONFIG` environment variable
+fn config_path() -> Option<PathBuf> {
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "--config" {
+            return args.next().map(PathBuf::from);
+        }
+        if let Some(path) = arg.strip_prefix("--config=") {
+            return Some(PathBuf::from(path));
+        }
+    }
+    std::env::var_os("OBJECTIO_CONFIG").map(PathBuf::from)
+}
+
 async fn shutdown_signal() {
     let ctrl_c = async {
         tokio::signal::ctrl_c()
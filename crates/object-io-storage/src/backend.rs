@@ -1,32 +1,125 @@
 //! Storage backend factory and configuration
 
 use crate::traits::Storage;
-use crate::filesystem::FilesystemStorage;
-use object_io_core::Result;
+use crate::filesystem::{FilesystemStorage, KeyCase, KeyLayout};
+use crate::memory::MemoryStorage;
+use object_io_core::{ObjectIOError, Result};
+use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 
+/// Storage backend selection
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StorageBackendKind {
+    #[default]
+    Filesystem,
+    Memory,
+    // Future backends can be added here
+    // S3,
+    // GCS,
+}
+
 /// Storage backend configuration
 #[derive(Debug, Clone)]
 pub enum StorageConfig {
     Filesystem {
         root_path: String,
+        /// Directory for in-progress writes; `.objectio-tmp` under the root
+        /// when unset
+        temp_path: Option<String>,
+        /// How object keys map to paths
+        key_case: KeyCase,
+        /// Whether object keys are paths or hashed into them
+        key_layout: KeyLayout,
+        /// Store identical object content once
+        dedup: bool,
     },
+    Memory,
     // Future backends can be added here
     // S3 { endpoint: String, region: String },
     // GCS { project_id: String },
 }
 
+impl StorageConfig {
+    /// Build the configuration for a backend kind from the available settings
+    pub fn new(kind: StorageBackendKind, root_path: Option<&str>) -> Result<Self> {
+        let config = match kind {
+            StorageBackendKind::Filesystem => StorageConfig::Filesystem {
+                root_path: root_path.unwrap_or_default().to_string(),
+                temp_path: None,
+                key_case: KeyCase::default(),
+                key_layout: KeyLayout::default(),
+                dedup: false,
+            },
+            StorageBackendKind::Memory => StorageConfig::Memory,
+        };
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Write in-progress objects to `temp_path` (filesystem backend only)
+    pub fn with_temp_path(mut self, temp_path: Option<String>) -> Self {
+        if let StorageConfig::Filesystem { temp_path: path, .. } = &mut self {
+            *path = temp_path;
+        }
+        self
+    }
+
+    /// Map object keys to paths as `key_case` says (filesystem backend only)
+    pub fn with_key_case(mut self, key_case: KeyCase) -> Self {
+        if let StorageConfig::Filesystem { key_case: case, .. } = &mut self {
+            *case = key_case;
+        }
+        self
+    }
+
+    /// Lay out object files as `key_layout` says (filesystem backend only)
+    pub fn with_key_layout(mut self, key_layout: KeyLayout) -> Self {
+        if let StorageConfig::Filesystem { key_layout: layout, .. } = &mut self {
+            *layout = key_layout;
+        }
+        self
+    }
+
+    /// Store identical object content once (filesystem backend only)
+    pub fn with_dedup(mut self, dedup: bool) -> Self {
+        if let StorageConfig::Filesystem { dedup: enabled, .. } = &mut self {
+            *enabled = dedup;
+        }
+        self
+    }
+
+    /// Check that the settings required by the chosen backend are present
+    pub fn validate(&self) -> Result<()> {
+        match self {
+            StorageConfig::Filesystem { root_path, .. } if root_path.trim().is_empty() => {
+                Err(ObjectIOError::ConfigurationError {
+                    message: "Filesystem storage backend requires a storage path".to_string(),
+                })
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
 /// Storage backend factory
 pub struct StorageBackend;
 
 impl StorageBackend {
     /// Create a new storage backend from configuration
     pub async fn new(config: StorageConfig) -> Result<Arc<dyn Storage>> {
+        config.validate()?;
+
         match config {
-            StorageConfig::Filesystem { root_path } => {
-                let storage = FilesystemStorage::new(root_path).await?;
+            StorageConfig::Filesystem { root_path, temp_path, key_case, key_layout, dedup } => {
+                let storage = FilesystemStorage::with_temp_dir(root_path, temp_path)
+                    .await?
+                    .with_key_case(key_case)
+                    .with_key_layout(key_layout)
+                    .with_dedup(dedup);
                 Ok(Arc::new(storage))
             }
+            StorageConfig::Memory => Ok(Arc::new(MemoryStorage::new())),
         }
     }
 
@@ -35,4 +128,26 @@ impl StorageBackend {
         let storage = FilesystemStorage::new(root_path).await?;
         Ok(Arc::new(storage))
     }
+
+    /// Create a memory storage backend
+    pub fn memory() -> Arc<dyn Storage> {
+        Arc::new(MemoryStorage::new())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_storage_config_validation() {
+        assert!(StorageConfig::new(StorageBackendKind::Filesystem, Some("/tmp/objectio")).is_ok());
+        assert!(StorageConfig::new(StorageBackendKind::Memory, None).is_ok());
+
+        assert!(matches!(
+            StorageConfig::new(StorageBackendKind::Filesystem, None),
+            Err(ObjectIOError::ConfigurationError { .. })
+        ));
+        assert!(StorageConfig::new(StorageBackendKind::Filesystem, Some("  ")).is_err());
+    }
 }
@@ -0,0 +1,300 @@
+//! Behaviour every storage backend must share
+//!
+//! [`run`] drives a backend through the whole suite and panics on the first
+//! difference, so each backend's tests only need to construct an empty
+//! instance and hand it over. Backends outside this crate can use it by
+//! enabling the `conformance` feature in their dev-dependencies.
+
+use crate::traits::Storage;
+use chrono::Utc;
+use object_io_core::{generate_etag, ConditionalPut, ListObjectsRequest, ObjectIOError};
+use std::collections::HashMap;
+use std::io::Cursor;
+use tokio::io::AsyncReadExt;
+
+/// Run every check against `storage`, which must start out empty
+pub async fn run(storage: &dyn Storage) {
+    round_trip(storage).await;
+    head_object(storage).await;
+    list_objects(storage).await;
+    ranges(storage).await;
+    deletes(storage).await;
+    moves(storage).await;
+    conditional_puts(storage).await;
+}
+
+async fn put(storage: &dyn Storage, bucket: &str, key: &str, data: &[u8], metadata: HashMap<String, String>) {
+    let reader = Box::new(Cursor::new(data.to_vec()));
+    storage.put_object(bucket, key, reader, metadata).await.unwrap();
+}
+
+async fn read_from(storage: &dyn Storage, bucket: &str, key: &str) -> String {
+    let mut data = String::new();
+    storage.get_object(bucket, key).await.unwrap().read_to_string(&mut data).await.unwrap();
+    data
+}
+
+async fn read_range(storage: &dyn Storage, offset: u64, length: u64) -> Vec<u8> {
+    let mut buffer = Vec::new();
+    let mut reader = storage.get_object_range("ranges", "bytes.bin", offset, length).await.unwrap();
+    reader.read_to_end(&mut buffer).await.unwrap();
+    buffer
+}
+
+fn content_type(value: &str) -> HashMap<String, String> {
+    HashMap::from([("content-type".to_string(), value.to_string())])
+}
+
+/// Objects read back as written, with their metadata, until deleted
+pub async fn round_trip(storage: &dyn Storage) {
+    let etag = storage
+        .put_object("trip", "key", Box::new(Cursor::new(b"hello".to_vec())), content_type("text/plain"))
+        .await
+        .unwrap();
+    assert_eq!(etag, generate_etag(b"hello"));
+    assert!(storage.object_exists("trip", "key").await.unwrap());
+    assert!(!storage.object_exists("trip", "other").await.unwrap());
+    assert!(!storage.object_exists("nowhere", "key").await.unwrap());
+    assert_eq!(read_from(storage, "trip", "key").await, "hello");
+    assert_eq!(storage.get_object_metadata("trip", "key").await.unwrap(), content_type("text/plain"));
+
+    // Overwrites replace both data and metadata
+    put(storage, "trip", "key", b"", HashMap::new()).await;
+    assert_eq!(read_from(storage, "trip", "key").await, "");
+    assert!(storage.get_object_metadata("trip", "key").await.unwrap().is_empty());
+
+    storage.delete_object("trip", "key").await.unwrap();
+    assert!(!storage.object_exists("trip", "key").await.unwrap());
+    assert!(matches!(
+        storage.get_object("trip", "key").await,
+        Err(ObjectIOError::ObjectNotFound { .. })
+    ));
+}
+
+/// Heads report what was stored, and only for objects
+pub async fn head_object(storage: &dyn Storage) {
+    put(storage, "heads", "notes.txt", b"hello", content_type("text/plain")).await;
+
+    let head = storage.head_object("heads", "notes.txt").await.unwrap();
+    assert_eq!(head.size, 5);
+    assert_eq!(head.metadata, content_type("text/plain"));
+    // Backends need not know the ETag, but must not report a wrong one
+    if let Some(etag) = head.etag {
+        assert_eq!(etag, generate_etag(b"hello"));
+    }
+    assert!(head.last_modified > Utc::now() - chrono::Duration::minutes(1));
+
+    for key in ["missing.txt", "notes.txt/nested"] {
+        assert!(matches!(
+            storage.head_object("heads", key).await,
+            Err(ObjectIOError::ObjectNotFound { .. })
+        ));
+    }
+    // Keys sharing a prefix do not make the prefix an object
+    put(storage, "heads", "dir/file", b"", HashMap::new()).await;
+    assert!(matches!(storage.head_object("heads", "dir").await, Err(ObjectIOError::ObjectNotFound { .. })));
+    assert!(matches!(
+        storage.head_object("nowhere", "notes.txt").await,
+        Err(ObjectIOError::ObjectNotFound { .. })
+    ));
+}
+
+/// Listings are ordered by key, filtered by prefix, grouped by delimiter
+/// and paged by marker
+pub async fn list_objects(storage: &dyn Storage) {
+    let keys = ["logs/2024/a.log", "logs/2024/b.log", "logs/2025/c.log", "logs/index.txt", "readme.txt"];
+    // Written out of order, listed in order
+    for key in keys.iter().rev() {
+        put(storage, "listing", key, b"x", HashMap::new()).await;
+    }
+
+    let listing = storage.list_objects(&ListObjectsRequest::new("listing")).await.unwrap();
+    let listed: Vec<_> = listing.objects.iter().map(|o| o.key.as_str()).collect();
+    assert_eq!(listed, keys);
+    assert!(listing.objects.iter().all(|o| o.size == 1));
+    assert!(!listing.is_truncated);
+    assert_eq!(storage.bucket_usage("listing").await.unwrap(), (5, 5));
+
+    let request = ListObjectsRequest {
+        prefix: Some("logs/2024/".to_string()),
+        ..ListObjectsRequest::new("listing")
+    };
+    let listing = storage.list_objects(&request).await.unwrap();
+    let listed: Vec<_> = listing.objects.iter().map(|o| o.key.as_str()).collect();
+    assert_eq!(listed, ["logs/2024/a.log", "logs/2024/b.log"]);
+
+    let request = ListObjectsRequest {
+        prefix: Some("logs/".to_string()),
+        delimiter: Some("/".to_string()),
+        max_keys: Some(2),
+        ..ListObjectsRequest::new("listing")
+    };
+    let page = storage.list_objects(&request).await.unwrap();
+    assert!(page.objects.is_empty());
+    assert_eq!(page.common_prefixes, ["logs/2024/", "logs/2025/"]);
+    assert!(page.is_truncated);
+    assert_eq!(page.next_marker.as_deref(), Some("logs/2025/"));
+
+    let request = ListObjectsRequest {
+        marker: page.next_marker,
+        ..request
+    };
+    let page = storage.list_objects(&request).await.unwrap();
+    assert_eq!(page.objects.len(), 1);
+    assert_eq!(page.objects[0].key, "logs/index.txt");
+    assert!(page.common_prefixes.is_empty());
+    assert!(!page.is_truncated);
+
+    // A bucket that was never written to lists as empty
+    let listing = storage.list_objects(&ListObjectsRequest::new("nowhere")).await.unwrap();
+    assert!(listing.objects.is_empty());
+    assert_eq!(storage.bucket_usage("nowhere").await.unwrap(), (0, 0));
+}
+
+/// Ranges are clamped to the object, never an error for a present object
+pub async fn ranges(storage: &dyn Storage) {
+    let data: Vec<u8> = (0..=255u8).collect();
+    put(storage, "ranges", "bytes.bin", &data, HashMap::new()).await;
+
+    for (offset, length, expected) in [
+        (0, 1, &data[..1]),
+        (0, 256, &data[..]),
+        (100, 10, &data[100..110]),
+        (255, 1, &data[255..]),
+        (250, 100, &data[250..]),
+        (256, 10, &data[256..]),
+        (10, 0, &data[10..10]),
+        (u64::MAX, u64::MAX, &data[256..]),
+    ] {
+        assert_eq!(read_range(storage, offset, length).await, expected, "range {}+{}", offset, length);
+    }
+
+    assert!(matches!(
+        storage.get_object_range("ranges", "missing.bin", 0, 1).await,
+        Err(ObjectIOError::ObjectNotFound { .. })
+    ));
+}
+
+/// Deletes tell a missing bucket from a missing object
+pub async fn deletes(storage: &dyn Storage) {
+    assert!(matches!(
+        storage.delete_object("deletes", "a.txt").await,
+        Err(ObjectIOError::BucketNotFound { .. })
+    ));
+
+    put(storage, "deletes", "dir/a.txt", b"a", HashMap::new()).await;
+    assert!(matches!(
+        storage.delete_object("deletes", "b.txt").await,
+        Err(ObjectIOError::ObjectNotFound { .. })
+    ));
+    // A key naming a prefix of other keys is not an object
+    assert!(matches!(
+        storage.delete_object("deletes", "dir").await,
+        Err(ObjectIOError::ObjectNotFound { .. })
+    ));
+
+    storage.delete_object("deletes", "dir/a.txt").await.unwrap();
+    assert!(!storage.object_exists("deletes", "dir/a.txt").await.unwrap());
+    assert!(matches!(
+        storage.delete_object("deletes", "dir/a.txt").await,
+        Err(ObjectIOError::ObjectNotFound { .. })
+    ));
+}
+
+/// Moves carry data and metadata to the new key, replacing what was there
+pub async fn moves(storage: &dyn Storage) {
+    put(storage, "moves", "a.txt", b"alpha", content_type("text/plain")).await;
+    put(storage, "moves", "b.txt", b"beta!!", content_type("text/csv")).await;
+    assert_eq!(storage.bucket_usage("moves").await.unwrap(), (2, 11));
+
+    storage.move_object("moves", "a.txt", "nested/c.txt").await.unwrap();
+    assert!(!storage.object_exists("moves", "a.txt").await.unwrap());
+    assert_eq!(read_from(storage, "moves", "nested/c.txt").await, "alpha");
+    assert_eq!(storage.get_object_metadata("moves", "nested/c.txt").await.unwrap(), content_type("text/plain"));
+
+    storage.move_object("moves", "nested/c.txt", "b.txt").await.unwrap();
+    assert_eq!(storage.bucket_usage("moves").await.unwrap(), (1, 5));
+    assert_eq!(read_from(storage, "moves", "b.txt").await, "alpha");
+    assert_eq!(storage.get_object_metadata("moves", "b.txt").await.unwrap(), content_type("text/plain"));
+
+    assert!(matches!(
+        storage.move_object("moves", "a.txt", "d.txt").await,
+        Err(ObjectIOError::ObjectNotFound { .. })
+    ));
+    assert!(!storage.object_exists("moves", "d.txt").await.unwrap());
+
+    // Moving a whole prefix is optional, but moves all of it when done
+    put(storage, "moves", "dir/x.txt", b"x", content_type("text/plain")).await;
+    put(storage, "moves", "dir/sub/y.txt", b"y", HashMap::new()).await;
+    if storage.move_prefix("moves", "dir/", "moved/dir/").await.unwrap() {
+        assert!(!storage.object_exists("moves", "dir/x.txt").await.unwrap());
+        assert!(!storage.object_exists("moves", "dir/sub/y.txt").await.unwrap());
+        assert_eq!(read_from(storage, "moves", "moved/dir/x.txt").await, "x");
+        assert_eq!(storage.get_object_metadata("moves", "moved/dir/x.txt").await.unwrap(), content_type("text/plain"));
+        assert_eq!(read_from(storage, "moves", "moved/dir/sub/y.txt").await, "y");
+    } else {
+        assert!(storage.object_exists("moves", "dir/x.txt").await.unwrap());
+    }
+}
+
+async fn put_if(
+    storage: &dyn Storage,
+    key: &str,
+    data: &str,
+    condition: ConditionalPut,
+) -> object_io_core::Result<String> {
+    let reader = Box::new(Cursor::new(data.as_bytes().to_vec()));
+    storage.put_object_if("conditional", key, reader, HashMap::new(), &condition).await
+}
+
+async fn read(storage: &dyn Storage, key: &str) -> String {
+    read_from(storage, "conditional", key).await
+}
+
+fn if_match(etag: &str) -> ConditionalPut {
+    ConditionalPut {
+        if_match: Some(etag.to_string()),
+        if_none_match_star: false,
+    }
+}
+
+fn if_none_match_star() -> ConditionalPut {
+    ConditionalPut {
+        if_match: None,
+        if_none_match_star: true,
+    }
+}
+
+/// Conditional puts succeed and fail alike in every backend, and a failed
+/// one leaves the stored object untouched
+pub async fn conditional_puts(storage: &dyn Storage) {
+    // If-None-Match: * creates a key only once
+    let etag = put_if(storage, "a.txt", "first", if_none_match_star()).await.unwrap();
+    assert_eq!(etag, generate_etag(b"first"));
+    let result = put_if(storage, "a.txt", "second", if_none_match_star()).await;
+    assert!(matches!(result, Err(ObjectIOError::PreconditionFailed { .. })), "{:?}", result);
+    assert_eq!(read(storage, "a.txt").await, "first");
+
+    // If-Match replaces only the version it names, quoted or not
+    let result = put_if(storage, "a.txt", "second", if_match("0123456789abcdef")).await;
+    assert!(matches!(result, Err(ObjectIOError::PreconditionFailed { .. })), "{:?}", result);
+    assert_eq!(read(storage, "a.txt").await, "first");
+    let etag = put_if(storage, "a.txt", "second", if_match(&format!("\"{}\"", etag))).await.unwrap();
+    assert_eq!(read(storage, "a.txt").await, "second");
+    put_if(storage, "a.txt", "third", if_match(&etag)).await.unwrap();
+    put_if(storage, "a.txt", "fourth", if_match("*")).await.unwrap();
+    assert_eq!(read(storage, "a.txt").await, "fourth");
+
+    // If-Match on a missing key is NoSuchKey, and creates nothing
+    let result = put_if(storage, "missing.txt", "data", if_match("*")).await;
+    assert!(matches!(result, Err(ObjectIOError::ObjectNotFound { .. })), "{:?}", result);
+    assert!(!storage.object_exists("conditional", "missing.txt").await.unwrap());
+
+    // A deleted key can be created again
+    storage.delete_object("conditional", "a.txt").await.unwrap();
+    put_if(storage, "a.txt", "again", if_none_match_star()).await.unwrap();
+
+    // Unconditional puts replace whatever is there
+    put_if(storage, "a.txt", "anything", ConditionalPut::default()).await.unwrap();
+    assert_eq!(read(storage, "a.txt").await, "anything");
+}
@@ -1,20 +1,336 @@
 //! Filesystem storage backend implementation
 
+use crate::locks::KeyLocks;
+use crate::multipart::{self, CompositeEtag, UploadIndexKey};
 use crate::traits::Storage;
-use object_io_core::{Object, ObjectIOError, Result};
-use std::collections::HashMap;
+use chrono::{DateTime, Utc};
+use object_io_core::{
+    CompletedPart, ConditionalPut, ListObjectsRequest, ListObjectsResponse, ListUploadsPage, ListUploadsRequest,
+    MultipartUpload, ObjectHead, ObjectIOError, ObjectSummary, Result, UploadPart,
+};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
+use std::io::SeekFrom;
 use std::path::{Path, PathBuf};
 use tokio::fs;
-use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncSeekExt, AsyncWriteExt, BufWriter};
+use tokio::sync::{Mutex, MutexGuard};
+
+/// Directory under the storage root holding in-progress multipart uploads.
+/// Bucket names cannot start with '.', so this never collides with a bucket.
+const MULTIPART_DIR: &str = ".multipart";
+
+/// Directory under the storage root holding each object's metadata sidecar,
+/// at the same path under `<bucket>/` as the object under its bucket's
+/// directory, so no key can collide with a sidecar
+const METADATA_DIR: &str = ".objectio-meta";
+
+/// Default directory under the storage root where objects are written
+/// before being renamed into place, so an interrupted upload never leaves a
+/// partial object. It also stages copies from a temporary directory on
+/// another filesystem, which cannot be renamed into place directly.
+const TMP_DIR: &str = ".objectio-tmp";
+
+/// Directory under the storage root holding bucket snapshots, laid out as
+/// `<bucket>/<snapshot>/` with the objects and sidecars of the snapshot under
+/// `objects/` and `meta/`, and the manifest that completes it
+const SNAPSHOTS_DIR: &str = ".objectio-snapshots";
+const SNAPSHOT_OBJECTS: &str = "objects";
+const SNAPSHOT_METADATA: &str = "meta";
+const SNAPSHOT_MANIFEST: &str = "manifest.json";
+
+/// Directory under the storage root holding deduplicated object content, one
+/// file per distinct SHA-256 at `<first two hex digits>/<digest>`. Every
+/// object with that content is a hard link to the file, so the file's link
+/// count is its reference count, one more than the objects sharing it.
+const CONTENT_DIR: &str = ".objectio-content";
+
+/// Sidecar entry naming the content file an object links to. No header can
+/// have this name, so it never clashes with object metadata.
+const CONTENT_REF_KEY: &str = ":content";
+
+/// Sidecar entry holding the key of an object stored under the hashed
+/// layout, whose path doesn't give the key back
+const KEY_ENTRY: &str = ":key";
+
+/// Sidecar entry recording an object's ETag, for conditional writes to
+/// check without hashing the object again
+const ETAG_ENTRY: &str = ":etag";
+
+/// Prefix of every temporary file, so startup cleanup removes only the
+/// server's own files from a temporary directory it may share
+const TEMP_PREFIX: &str = "objectio-";
+
+/// `EXDEV`: a rename between filesystems
+const CROSS_DEVICE: i32 = 18;
+
+/// Remove the temporary files left in `dir` by writes interrupted when the
+/// server last stopped, creating the directory if needed
+async fn clear_temp_dir(dir: &Path) -> Result<()> {
+    let error = |e: std::io::Error| ObjectIOError::StorageError {
+        message: format!("Failed to clear temporary directory {}: {}", dir.display(), e),
+    };
+    fs::create_dir_all(dir).await.map_err(error)?;
+    let mut entries = fs::read_dir(dir).await.map_err(error)?;
+    while let Some(entry) = entries.next_entry().await.map_err(error)? {
+        if entry.file_name().to_string_lossy().starts_with(TEMP_PREFIX) {
+            match fs::remove_file(entry.path()).await {
+                Ok(()) => {}
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => return Err(error(e)),
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Move sidecars in the old layout, `.meta` files beside their objects, into
+/// the metadata directory, creating it to mark the root as migrated
+///
+/// The old layout replaced the object's extension, so objects differing only
+/// in extension shared a sidecar; each gets its own copy. The copies are made
+/// in a staging directory renamed into place once complete. `.meta` files
+/// without an object are left alone, and are listed as objects from now on.
+async fn migrate_sidecars(root_path: &Path) -> Result<()> {
+    let metadata_dir = root_path.join(METADATA_DIR);
+    if metadata_dir.is_dir() {
+        return Ok(());
+    }
+    let error = |e: std::io::Error| ObjectIOError::StorageError {
+        message: format!("Failed to migrate metadata sidecars: {}", e),
+    };
+    let staging_dir = root_path.join(format!("{}.migrating", METADATA_DIR));
+    match fs::remove_dir_all(&staging_dir).await {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(error(e)),
+        _ => {}
+    }
+    fs::create_dir_all(&staging_dir).await.map_err(error)?;
+
+    // Bucket directories, skipping the server's own directories
+    let mut pending = Vec::new();
+    let mut entries = fs::read_dir(root_path).await.map_err(error)?;
+    while let Some(entry) = entries.next_entry().await.map_err(error)? {
+        if !entry.file_name().to_string_lossy().starts_with('.') && entry.file_type().await.map_err(error)?.is_dir() {
+            pending.push(entry.path());
+        }
+    }
+
+    let mut migrated = Vec::new();
+    while let Some(dir) = pending.pop() {
+        let mut files = Vec::new();
+        let mut entries = fs::read_dir(&dir).await.map_err(error)?;
+        while let Some(entry) = entries.next_entry().await.map_err(error)? {
+            if entry.file_type().await.map_err(error)?.is_dir() {
+                pending.push(entry.path());
+            } else {
+                files.push(entry.path());
+            }
+        }
+
+        let (sidecars, objects): (Vec<PathBuf>, Vec<PathBuf>) =
+            files.into_iter().partition(|path| path.extension().and_then(|s| s.to_str()) == Some("meta"));
+        for sidecar in sidecars {
+            let owners: Vec<&PathBuf> =
+                objects.iter().filter(|object| object.with_extension("meta") == sidecar).collect();
+            for object in &owners {
+                let relative = object.strip_prefix(root_path).expect("walked from the root");
+                let destination = staging_dir.join(relative);
+                if let Some(parent) = destination.parent() {
+                    fs::create_dir_all(parent).await.map_err(error)?;
+                }
+                fs::copy(&sidecar, &destination).await.map_err(error)?;
+            }
+            if !owners.is_empty() {
+                migrated.push(sidecar);
+            }
+        }
+    }
+
+    fs::rename(&staging_dir, &metadata_dir).await.map_err(error)?;
+    for sidecar in &migrated {
+        fs::remove_file(sidecar).await.map_err(error)?;
+    }
+    if !migrated.is_empty() {
+        tracing::info!("Moved {} metadata sidecars into {}", migrated.len(), metadata_dir.display());
+    }
+    Ok(())
+}
+
+/// File written and removed under the storage root at startup to check
+/// that the root is writable
+const PROBE_FILE: &str = ".write-probe";
+
+/// Check that the storage root is a writable directory, so a bad mount fails
+/// at startup rather than on the first upload
+async fn check_root(root_path: &Path) -> Result<()> {
+    let error = |message: String| ObjectIOError::StorageError { message };
+    let metadata = fs::metadata(root_path)
+        .await
+        .map_err(|e| error(format!("Failed to read storage directory {}: {}", root_path.display(), e)))?;
+    if !metadata.is_dir() {
+        return Err(error(format!("Storage path {} is not a directory", root_path.display())));
+    }
+
+    let probe = root_path.join(PROBE_FILE);
+    fs::write(&probe, b"probe")
+        .await
+        .map_err(|e| error(format!("Storage directory {} is not writable: {}", root_path.display(), e)))?;
+    fs::remove_file(&probe)
+        .await
+        .map_err(|e| error(format!("Failed to remove {}: {}", probe.display(), e)))
+}
+
+/// Persisted state of an in-progress multipart upload
+#[derive(Debug, Serialize, Deserialize)]
+struct UploadManifest {
+    bucket: String,
+    key: String,
+    initiated: DateTime<Utc>,
+    metadata: HashMap<String, String>,
+}
+
+/// How object keys map to file paths under their bucket's directory
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum KeyCase {
+    /// Use keys as paths unchanged; on a case-insensitive filesystem, keys
+    /// differing only in case share one file
+    #[default]
+    Native,
+    /// Escape letters with case so keys differing only in case stay separate
+    /// on any filesystem (see [`encode_key`])
+    Preserve,
+}
+
+/// Where under its bucket's directory an object's file goes
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum KeyLayout {
+    /// The key is the path, with each `/` a directory, as shaped by
+    /// [`KeyCase`]; keys too long for the filesystem's path limits fail
+    #[default]
+    Nested,
+    /// The path is `<xx>/<yy>/<sha256 of key>`, with the key kept in the
+    /// metadata sidecar, so any key fits in a short path. Listings read every
+    /// sidecar in the bucket, so they are slower.
+    Hashed,
+}
+
+/// Path of `key` under the hashed layout: the SHA-256 of the key, under two
+/// levels of directories named by its first four hex digits
+fn hashed_key_path(key: &str) -> String {
+    let hash = format!("{:x}", Sha256::digest(key.as_bytes()));
+    format!("{}/{}/{}", &hash[..2], &hash[2..4], hash)
+}
+
+/// Escape character of case-preserving key paths
+const CASE_ESCAPE: char = '^';
+
+/// Encode a key as a path no case-insensitive filesystem confuses with
+/// another key's: `^` is doubled, an ASCII capital becomes `^` and its
+/// lower-case letter, and a non-ASCII letter with case becomes `^{hex}` of
+/// its code point. Everything else, including `/`, is kept.
+pub fn encode_key(key: &str) -> String {
+    let mut encoded = String::with_capacity(key.len());
+    for c in key.chars() {
+        if c == CASE_ESCAPE {
+            encoded.push_str("^^");
+        } else if c.is_ascii_uppercase() {
+            encoded.push(CASE_ESCAPE);
+            encoded.push(c.to_ascii_lowercase());
+        } else if !c.is_ascii() && (c.is_lowercase() || c.is_uppercase()) {
+            encoded.push_str(&format!("^{{{:x}}}", c as u32));
+        } else {
+            encoded.push(c);
+        }
+    }
+    encoded
+}
+
+/// Decode a path written by [`encode_key`], or `None` if it isn't one
+pub fn decode_key(encoded: &str) -> Option<String> {
+    let mut key = String::with_capacity(encoded.len());
+    let mut chars = encoded.chars();
+    while let Some(c) = chars.next() {
+        if c != CASE_ESCAPE {
+            key.push(c);
+            continue;
+        }
+        match chars.next()? {
+            CASE_ESCAPE => key.push(CASE_ESCAPE),
+            '{' => {
+                let hex: String = chars.by_ref().take_while(|&c| c != '}').collect();
+                key.push(char::from_u32(u32::from_str_radix(&hex, 16).ok()?)?);
+            }
+            c if c.is_ascii_lowercase() => key.push(c.to_ascii_uppercase()),
+            _ => return None,
+        }
+    }
+    Some(key)
+}
+
+/// Running object count and byte total of one bucket
+#[derive(Debug, Clone, Copy, Default)]
+struct BucketUsage {
+    objects: u64,
+    bytes: u64,
+}
+
+impl BucketUsage {
+    /// Account for an object of size `old` being replaced by one of size `new`
+    fn replace(&mut self, old: Option<u64>, new: Option<u64>) {
+        if let Some(old) = old {
+            self.objects -= 1;
+            self.bytes -= old;
+        }
+        if let Some(new) = new {
+            self.objects += 1;
+            self.bytes += new;
+        }
+    }
+}
 
 /// Filesystem-based storage backend
 pub struct FilesystemStorage {
     root_path: PathBuf,
+    /// Where in-progress writes go before being moved into place
+    temp_dir: PathBuf,
+    /// Usage counters of buckets scanned since startup. Held while objects
+    /// are moved into place or removed so the counters never miss a change.
+    usage: Mutex<HashMap<String, BucketUsage>>,
+    /// Initiation times of in-progress uploads, ordered for listings. Loaded
+    /// from the upload manifests on first use, then kept up to date.
+    uploads: Mutex<Option<BTreeMap<UploadIndexKey, DateTime<Utc>>>>,
+    /// How keys map to paths under a bucket
+    key_case: KeyCase,
+    /// Whether keys are paths or hashed into them
+    key_layout: KeyLayout,
+    /// Whether new objects are stored deduplicated
+    dedup: bool,
+    /// Whether the root may hold deduplicated content, which replaced and
+    /// deleted objects must release: while dedup is on, and after it was
+    shared_content: bool,
+    /// Held while content files are created, linked or removed, so a file
+    /// is never removed as a new object links to it
+    content_lock: Mutex<()>,
+    /// Held while an object is checked, installed and described, so a
+    /// conditional write sees the object it would replace
+    locks: KeyLocks,
 }
 
 impl FilesystemStorage {
     /// Create a new filesystem storage backend
     pub async fn new<P: AsRef<Path>>(root_path: P) -> Result<Self> {
+        Self::with_temp_dir(root_path, None::<PathBuf>).await
+    }
+
+    /// Create a new filesystem storage backend writing in-progress objects
+    /// to `temp_dir`, or to `.objectio-tmp` under the root when `None`
+    ///
+    /// A temporary directory on faster local disk helps when the root is a
+    /// network mount; objects written there are copied across on completion.
+    pub async fn with_temp_dir<P: AsRef<Path>, T: AsRef<Path>>(root_path: P, temp_dir: Option<T>) -> Result<Self> {
         let root_path = root_path.as_ref().to_path_buf();
         
         // Create root directory if it doesn't exist
@@ -25,8 +341,107 @@ impl FilesystemStorage {
                 }
             })?;
         }
+        check_root(&root_path).await?;
+        migrate_sidecars(&root_path).await?;
+
+        // Anything left in these was being written when the server last stopped
+        let staging_dir = root_path.join(TMP_DIR);
+        let temp_dir = temp_dir.map_or_else(|| staging_dir.clone(), |dir| dir.as_ref().to_path_buf());
+        clear_temp_dir(&staging_dir).await?;
+        if temp_dir != staging_dir {
+            clear_temp_dir(&temp_dir).await?;
+        }
+
+        let shared_content = root_path.join(CONTENT_DIR).is_dir();
+        Ok(Self {
+            root_path,
+            temp_dir,
+            usage: Mutex::new(HashMap::new()),
+            uploads: Mutex::new(None),
+            key_case: KeyCase::Native,
+            key_layout: KeyLayout::Nested,
+            dedup: false,
+            shared_content,
+            content_lock: Mutex::new(()),
+            locks: KeyLocks::new(),
+        })
+    }
+
+    /// Map keys to paths as `key_case` says
+    ///
+    /// Must match the layout the storage root was written with; objects
+    /// stored under the other layout are not found.
+    pub fn with_key_case(mut self, key_case: KeyCase) -> Self {
+        self.key_case = key_case;
+        self
+    }
+
+    /// Lay out object files as `key_layout` says; with
+    /// [`KeyLayout::Hashed`], `key_case` no longer applies
+    ///
+    /// Like `key_case`, must match the layout the storage root was written
+    /// with.
+    pub fn with_key_layout(mut self, key_layout: KeyLayout) -> Self {
+        self.key_layout = key_layout;
+        self
+    }
+
+    /// Store the content of new objects once per distinct SHA-256, with
+    /// every object holding it a hard link to one content file
+    ///
+    /// Objects stored before it was enabled keep their own copies. Needs a
+    /// filesystem with hard links; elsewhere each object gets a copy.
+    pub fn with_dedup(mut self, dedup: bool) -> Self {
+        self.dedup = dedup;
+        self.shared_content |= dedup;
+        self
+    }
+
+    /// Path of `key` relative to its bucket's directory, rejecting keys with
+    /// segments that could lead out of it (see [`validate_object_key`])
+    ///
+    /// [`validate_object_key`]: object_io_core::validate_object_key
+    fn key_path<'a>(&self, key: &'a str) -> Result<std::borrow::Cow<'a, str>> {
+        object_io_core::validate_object_key(key)?;
+        Ok(match (self.key_layout, self.key_case) {
+            (KeyLayout::Hashed, _) => hashed_key_path(key).into(),
+            (KeyLayout::Nested, KeyCase::Native) => key.into(),
+            (KeyLayout::Nested, KeyCase::Preserve) => encode_key(key).into(),
+        })
+    }
+
+    /// Get a fresh path to write an object to before renaming it into place
+    fn temp_path(&self) -> PathBuf {
+        self.temp_dir.join(format!("{}{}", TEMP_PREFIX, uuid::Uuid::new_v4().simple()))
+    }
+
+    /// Get a fresh path in the staging directory under the root, on the same
+    /// filesystem as every object
+    fn staging_path(&self) -> PathBuf {
+        self.root_path
+            .join(TMP_DIR)
+            .join(format!("{}{}", TEMP_PREFIX, uuid::Uuid::new_v4().simple()))
+    }
 
-        Ok(Self { root_path })
+    /// Rename `source` over `destination`, in one step even when `source` is
+    /// on another filesystem: it is then copied to the staging directory under
+    /// the root first, and renamed from there
+    async fn move_into_place(&self, source: &Path, destination: &Path) -> std::io::Result<()> {
+        match fs::rename(source, destination).await {
+            Err(e) if e.raw_os_error() == Some(CROSS_DEVICE) => {
+                let staged = self.staging_path();
+                let moved = match fs::copy(source, &staged).await {
+                    Ok(_) => fs::rename(&staged, destination).await,
+                    Err(e) => Err(e),
+                };
+                if moved.is_err() {
+                    let _ = fs::remove_file(&staged).await;
+                }
+                moved?;
+                fs::remove_file(source).await
+            }
+            result => result,
+        }
     }
 
     /// Get the full path for a bucket
@@ -35,226 +450,1927 @@ impl FilesystemStorage {
     }
 
     /// Get the full path for an object
-    fn object_path(&self, bucket: &str, key: &str) -> PathBuf {
-        self.bucket_path(bucket).join(key)
+    fn object_path(&self, bucket: &str, key: &str) -> Result<PathBuf> {
+        Ok(self.bucket_path(bucket).join(&*self.key_path(key)?))
     }
 
-    /// Get the metadata file path for an object
-    fn metadata_path(&self, bucket: &str, key: &str) -> PathBuf {
-        let object_path = self.object_path(bucket, key);
-        object_path.with_extension("meta")
+    /// Get the metadata sidecar path for an object
+    fn metadata_path(&self, bucket: &str, key: &str) -> Result<PathBuf> {
+        Ok(self.root_path.join(METADATA_DIR).join(bucket).join(&*self.key_path(key)?))
     }
-}
 
-#[async_trait::async_trait]
-impl Storage for FilesystemStorage {
-    async fn put_object(
-        &self,
-        bucket: &str,
-        key: &str,
-        mut data: Box<dyn AsyncRead + Send + Unpin>,
-        metadata: HashMap<String, String>,
-    ) -> Result<String> {
-        let object_path = self.object_path(bucket, key);
-        let metadata_path = self.metadata_path(bucket, key);
+    /// Get the directory of a bucket snapshot, rejecting IDs that are not a
+    /// single plain path segment
+    fn snapshot_path(&self, bucket: &str, snapshot: &str) -> Result<PathBuf> {
+        let valid = !snapshot.is_empty()
+            && !snapshot.starts_with('.')
+            && snapshot.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_'));
+        if !valid {
+            return Err(ObjectIOError::InvalidArgument {
+                message: format!("Invalid snapshot ID: {}", snapshot),
+            });
+        }
+        Ok(self.root_path.join(SNAPSHOTS_DIR).join(bucket).join(snapshot))
+    }
 
-        // Create bucket directory if it doesn't exist
-        if let Some(parent) = object_path.parent() {
-            fs::create_dir_all(parent).await.map_err(|e| {
-                ObjectIOError::StorageError {
-                    message: format!("Failed to create bucket directory: {}", e),
-                }
-            })?;
+    /// Get the path of the content file for a SHA-256 hex digest
+    fn content_path(&self, digest: &str) -> PathBuf {
+        self.root_path.join(CONTENT_DIR).join(&digest[..2]).join(digest)
+    }
+
+    /// Get the directory holding a multipart upload's manifest and parts
+    fn upload_path(&self, upload_id: &str) -> PathBuf {
+        self.root_path.join(MULTIPART_DIR).join(upload_id)
+    }
+
+    /// Get the data file path for a multipart upload part
+    fn part_path(&self, upload_id: &str, part_number: u32) -> PathBuf {
+        self.upload_path(upload_id).join(format!("{:05}.part", part_number))
+    }
+
+    /// Get the ETag file path for a multipart upload part
+    fn part_etag_path(&self, upload_id: &str, part_number: u32) -> PathBuf {
+        self.upload_path(upload_id).join(format!("{:05}.etag", part_number))
+    }
+
+    /// Load an upload's manifest, checking it belongs to the given object
+    async fn load_manifest(&self, bucket: &str, key: &str, upload_id: &str) -> Result<UploadManifest> {
+        let not_found = || ObjectIOError::UploadNotFound {
+            upload_id: upload_id.to_string(),
+        };
+
+        if !multipart::is_valid_upload_id(upload_id) {
+            return Err(not_found());
         }
 
-        // Write object data
-        let mut file = fs::File::create(&object_path).await.map_err(|e| {
+        let manifest_path = self.upload_path(upload_id).join("upload.json");
+        let content = match fs::read_to_string(&manifest_path).await {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Err(not_found()),
+            Err(e) => {
+                return Err(ObjectIOError::StorageError {
+                    message: format!("Failed to read upload manifest: {}", e),
+                })
+            }
+        };
+
+        let manifest: UploadManifest = serde_json::from_str(&content).map_err(|e| {
             ObjectIOError::StorageError {
-                message: format!("Failed to create object file: {}", e),
+                message: format!("Failed to parse upload manifest: {}", e),
             }
         })?;
 
-        let mut buffer = Vec::new();
-        data.read_to_end(&mut buffer).await.map_err(|e| {
+        if manifest.bucket != bucket || manifest.key != key {
+            return Err(not_found());
+        }
+
+        Ok(manifest)
+    }
+
+    /// Lock the upload index, scanning the upload manifests on first use
+    async fn upload_index(&self) -> Result<MutexGuard<'_, Option<BTreeMap<UploadIndexKey, DateTime<Utc>>>>> {
+        let mut index = self.uploads.lock().await;
+        if index.is_none() {
+            *index = Some(self.scan_uploads().await?);
+        }
+        Ok(index)
+    }
+
+    /// Read every upload manifest into an ordered index
+    async fn scan_uploads(&self) -> Result<BTreeMap<UploadIndexKey, DateTime<Utc>>> {
+        let multipart_path = self.root_path.join(MULTIPART_DIR);
+        let mut uploads = BTreeMap::new();
+        let mut entries = match fs::read_dir(&multipart_path).await {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(uploads),
+            Err(e) => {
+                return Err(ObjectIOError::StorageError {
+                    message: format!("Failed to read uploads directory: {}", e),
+                })
+            }
+        };
+
+        while let Some(entry) = entries.next_entry().await.map_err(|e| {
             ObjectIOError::StorageError {
-                message: format!("Failed to read data: {}", e),
+                message: format!("Failed to read uploads directory entry: {}", e),
             }
-        })?;
+        })? {
+            let upload_id = entry.file_name().to_string_lossy().into_owned();
+            let manifest_path = entry.path().join("upload.json");
+
+            // Skip uploads whose manifest is missing or unreadable (e.g. mid-abort)
+            let manifest: UploadManifest = match fs::read_to_string(&manifest_path).await {
+                Ok(content) => match serde_json::from_str(&content) {
+                    Ok(manifest) => manifest,
+                    Err(_) => continue,
+                },
+                Err(_) => continue,
+            };
+
+            uploads.insert((manifest.bucket, manifest.key, upload_id), manifest.initiated);
+        }
+
+        Ok(uploads)
+    }
+
+    /// Drop an upload from the index once its directory is gone
+    async fn unindex_upload(&self, bucket: &str, key: &str, upload_id: &str) {
+        if let Some(index) = self.uploads.lock().await.as_mut() {
+            index.remove(&(bucket.to_string(), key.to_string(), upload_id.to_string()));
+        }
+    }
+
+    /// Get the size of the object file at `path`, if there is one
+    async fn object_size(path: &Path) -> Result<Option<u64>> {
+        match fs::metadata(path).await {
+            Ok(metadata) if metadata.is_file() => Ok(Some(metadata.len())),
+            Ok(_) => Ok(None),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(ObjectIOError::StorageError {
+                message: format!("Failed to stat object: {}", e),
+            }),
+        }
+    }
 
-        file.write_all(&buffer).await.map_err(|e| {
+    /// Move a fully written file into place as `key`, updating the bucket's
+    /// usage counters if they have been loaded and releasing the content of
+    /// the object it replaces
+    async fn install_object(&self, bucket: &str, key: &str, source: &Path) -> Result<()> {
+        let object_path = self.object_path(bucket, key)?;
+        let replaced_content = self.content_ref(bucket, key).await?;
+        let mut usage = self.usage.lock().await;
+        let old = Self::object_size(&object_path).await?;
+        self.move_into_place(source, &object_path).await.map_err(|e| {
             ObjectIOError::StorageError {
-                message: format!("Failed to write object: {}", e),
+                message: format!("Failed to move object into place: {}", e),
             }
         })?;
+        // Renaming a link over another link to the same file does nothing
+        if let Err(e) = fs::remove_file(source).await {
+            if e.kind() != std::io::ErrorKind::NotFound {
+                tracing::warn!("Failed to remove {}: {}", source.display(), e);
+            }
+        }
+        if let Some(counters) = usage.get_mut(bucket) {
+            counters.replace(old, Self::object_size(&object_path).await?);
+        }
+        drop(usage);
+        self.release_content(replaced_content).await
+    }
 
-        // Generate ETag
-        let etag = object_io_core::utils::generate_etag(&buffer);
+    /// Move a fully written file with SHA-256 `digest` into the content
+    /// store, unless identical content is there already, and return a new
+    /// link to the content to install as an object
+    async fn store_content(&self, source: &Path, digest: &str) -> Result<PathBuf> {
+        let error = |e: std::io::Error| ObjectIOError::StorageError {
+            message: format!("Failed to store object content: {}", e),
+        };
+        let _lock = self.content_lock.lock().await;
+        let content_path = self.content_path(digest);
+        if content_path.is_file() {
+            let _ = fs::remove_file(source).await;
+        } else {
+            if let Some(parent) = content_path.parent() {
+                fs::create_dir_all(parent).await.map_err(error)?;
+            }
+            self.move_into_place(source, &content_path).await.map_err(error)?;
+        }
+        let staged = self.staging_path();
+        link_or_copy(&content_path, &staged).await.map_err(error)?;
+        Ok(staged)
+    }
+
+    /// The digest of the content file the object at `key` links to, if it
+    /// was stored deduplicated
+    async fn content_ref(&self, bucket: &str, key: &str) -> Result<Option<String>> {
+        if !self.shared_content {
+            return Ok(None);
+        }
+        let mut sidecar = self.read_sidecar(&self.metadata_path(bucket, key)?).await?;
+        Ok(sidecar
+            .remove(CONTENT_REF_KEY)
+            .filter(|digest| digest.len() == 64 && digest.bytes().all(|b| b.is_ascii_hexdigit())))
+    }
+
+    /// Remove the content file `digest` once no object links to it
+    async fn release_content(&self, digest: Option<String>) -> Result<()> {
+        let Some(digest) = digest else {
+            return Ok(());
+        };
+        let _lock = self.content_lock.lock().await;
+        let content_path = self.content_path(&digest);
+        let released = match fs::metadata(&content_path).await {
+            Ok(stat) if link_count(&stat) <= 1 => match fs::remove_file(&content_path).await {
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+                result => result,
+            },
+            Ok(_) => Ok(()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(e),
+        };
+        released.map_err(|e| ObjectIOError::StorageError {
+            message: format!("Failed to release object content: {}", e),
+        })
+    }
 
-        // Write metadata
-        let metadata_json = serde_json::to_string(&metadata).map_err(|e| {
+    /// Write the metadata sidecar of `key`, replacing any earlier one in a
+    /// single rename so readers never see it half written
+    ///
+    /// Under the hashed layout the key itself is stored with the metadata.
+    async fn write_metadata(&self, bucket: &str, key: &str, metadata: &HashMap<String, String>) -> Result<()> {
+        let metadata_path = self.metadata_path(bucket, key)?;
+        let mut sidecar = std::borrow::Cow::Borrowed(metadata);
+        if self.key_layout == KeyLayout::Hashed {
+            sidecar.to_mut().insert(KEY_ENTRY.to_string(), key.to_string());
+        }
+        let metadata_json = serde_json::to_string(&sidecar).map_err(|e| {
             ObjectIOError::StorageError {
                 message: format!("Failed to serialize metadata: {}", e),
             }
         })?;
 
-        fs::write(&metadata_path, metadata_json).await.map_err(|e| {
+        let temp_path = self.temp_path();
+        let written = match fs::write(&temp_path, metadata_json).await {
+            Ok(()) => match metadata_path.parent() {
+                Some(parent) => fs::create_dir_all(parent).await,
+                None => Ok(()),
+            },
+            Err(e) => Err(e),
+        };
+        let written = match written {
+            Ok(()) => self.move_into_place(&temp_path, &metadata_path).await,
+            Err(e) => Err(e),
+        };
+        written.map_err(|e| {
+            let _ = std::fs::remove_file(&temp_path);
             ObjectIOError::StorageError {
                 message: format!("Failed to write metadata: {}", e),
             }
-        })?;
-
-        Ok(etag)
+        })
     }
 
-    async fn get_object(&self, bucket: &str, key: &str) -> Result<Box<dyn AsyncRead + Send + Unpin>> {
-        let object_path = self.object_path(bucket, key);
-
-        if !object_path.exists() {
-            return Err(ObjectIOError::ObjectNotFound {
-                bucket: bucket.to_string(),
-                key: key.to_string(),
-            });
+    /// ETag of the object stored as `key`, `None` if there is none
+    ///
+    /// Objects stored before ETags were recorded in their sidecar are hashed.
+    async fn current_etag(&self, bucket: &str, key: &str) -> Result<Option<String>> {
+        let object_path = self.object_path(bucket, key)?;
+        match fs::metadata(&object_path).await {
+            Ok(stat) if stat.is_file() => {}
+            Ok(_) => return Ok(None),
+            Err(e) if matches!(e.kind(), std::io::ErrorKind::NotFound | std::io::ErrorKind::NotADirectory) => {
+                return Ok(None)
+            }
+            Err(e) => {
+                return Err(ObjectIOError::StorageError {
+                    message: format!("Failed to stat object: {}", e),
+                })
+            }
+        }
+        if let Some(etag) = self.read_sidecar(&self.metadata_path(bucket, key)?).await?.remove(ETAG_ENTRY) {
+            return Ok(Some(etag));
         }
 
-        let file = fs::File::open(object_path).await.map_err(|e| {
-            ObjectIOError::StorageError {
-                message: format!("Failed to open object: {}", e),
+        let error = |e: std::io::Error| ObjectIOError::StorageError {
+            message: format!("Failed to read object: {}", e),
+        };
+        let mut file = fs::File::open(&object_path).await.map_err(error)?;
+        let mut hasher = object_io_core::EtagHasher::new();
+        let mut chunk = vec![0u8; 64 * 1024];
+        loop {
+            let read = file.read(&mut chunk).await.map_err(error)?;
+            if read == 0 {
+                return Ok(Some(hasher.finalize()));
             }
-        })?;
+            hasher.update(&chunk[..read]);
+        }
+    }
 
-        Ok(Box::new(file))
+    /// Read an object's metadata, empty if it has none
+    async fn read_metadata(&self, metadata_path: &Path) -> Result<HashMap<String, String>> {
+        let mut metadata = self.read_sidecar(metadata_path).await?;
+        metadata.remove(CONTENT_REF_KEY);
+        metadata.remove(KEY_ENTRY);
+        metadata.remove(ETAG_ENTRY);
+        Ok(metadata)
     }
 
-    async fn delete_object(&self, bucket: &str, key: &str) -> Result<()> {
-        let object_path = self.object_path(bucket, key);
-        let metadata_path = self.metadata_path(bucket, key);
+    /// Read an object's metadata sidecar, with the storage's own entries
+    async fn read_sidecar(&self, metadata_path: &Path) -> Result<HashMap<String, String>> {
+        let metadata_content = match fs::read_to_string(metadata_path).await {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(HashMap::new()),
+            Err(e) => {
+                return Err(ObjectIOError::StorageError {
+                    message: format!("Failed to read metadata: {}", e),
+                })
+            }
+        };
 
-        if !object_path.exists() {
-            return Err(ObjectIOError::ObjectNotFound {
-                bucket: bucket.to_string(),
-                key: key.to_string(),
-            });
-        }
+        serde_json::from_str(&metadata_content).map_err(|e| {
+            ObjectIOError::StorageError {
+                message: format!("Failed to parse metadata: {}", e),
+            }
+        })
+    }
 
-        // Delete object file
-        fs::remove_file(&object_path).await.map_err(|e| {
+    /// Concatenate the parts of an upload into `path`, one at a time so
+    /// memory use doesn't grow with part count or size
+    async fn assemble_parts(
+        &self,
+        upload_id: &str,
+        parts: &[CompletedPart],
+        etags: &[String],
+        path: &Path,
+    ) -> Result<CompositeEtag> {
+        let file = fs::File::create(path).await.map_err(|e| {
             ObjectIOError::StorageError {
-                message: format!("Failed to delete object: {}", e),
+                message: format!("Failed to create assembled object: {}", e),
             }
         })?;
 
-        // Delete metadata file if it exists
-        if metadata_path.exists() {
-            fs::remove_file(&metadata_path).await.map_err(|e| {
+        let mut writer = BufWriter::new(file);
+        let mut composite = CompositeEtag::new();
+        for (part, etag) in parts.iter().zip(etags) {
+            let mut part_file = fs::File::open(self.part_path(upload_id, part.part_number))
+                .await
+                .map_err(|e| ObjectIOError::StorageError {
+                    message: format!("Failed to open part {}: {}", part.part_number, e),
+                })?;
+            tokio::io::copy(&mut part_file, &mut writer).await.map_err(|e| {
                 ObjectIOError::StorageError {
-                    message: format!("Failed to delete metadata: {}", e),
+                    message: format!("Failed to append part {}: {}", part.part_number, e),
                 }
             })?;
+
+            composite.add_part(etag);
         }
+        writer.flush().await.map_err(|e| {
+            ObjectIOError::StorageError {
+                message: format!("Failed to write assembled object: {}", e),
+            }
+        })?;
+        Ok(composite)
+    }
 
-        Ok(())
+    /// Read the stored ETag of a part, if the part exists
+    async fn read_part_etag(&self, upload_id: &str, part_number: u32) -> Result<Option<String>> {
+        match fs::read_to_string(self.part_etag_path(upload_id, part_number)).await {
+            Ok(etag) => Ok(Some(etag)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(ObjectIOError::StorageError {
+                message: format!("Failed to read part ETag: {}", e),
+            }),
+        }
     }
 
-    async fn object_exists(&self, bucket: &str, key: &str) -> Result<bool> {
-        let object_path = self.object_path(bucket, key);
-        Ok(object_path.exists())
+    /// Walk a bucket directory for objects whose keys start with `prefix`
+    ///
+    /// Only the directory holding the prefix's last complete path segment is
+    /// walked, except under the hashed layout, where the whole bucket is and
+    /// keys are read from the sidecars. ETags are not computed.
+    async fn collect_objects(&self, bucket: &str, prefix: &str) -> Result<Vec<ObjectSummary>> {
+        let bucket_path = self.bucket_path(bucket);
+        let start = match self.key_layout {
+            KeyLayout::Nested => {
+                let base = prefix.rfind('/').map(|i| &prefix[..i]).unwrap_or_default();
+                if base.is_empty() {
+                    bucket_path.clone()
+                } else {
+                    match self.key_path(base) {
+                        Ok(base) => bucket_path.join(&*base),
+                        // No key has a segment that could lead out of the bucket
+                        Err(_) => return Ok(Vec::new()),
+                    }
+                }
+            }
+            KeyLayout::Hashed => bucket_path.clone(),
+        };
+        let mut pending = vec![start];
+        let mut summaries = Vec::new();
+
+        while let Some(dir) = pending.pop() {
+            let mut entries = match fs::read_dir(&dir).await {
+                Ok(entries) => entries,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+                Err(e) => {
+                    return Err(ObjectIOError::StorageError {
+                        message: format!("Failed to read bucket directory: {}", e),
+                    })
+                }
+            };
+
+            while let Some(entry) = entries.next_entry().await.map_err(|e| {
+                ObjectIOError::StorageError {
+                    message: format!("Failed to read directory entry: {}", e),
+                }
+            })? {
+                let path = entry.path();
+                let metadata = entry.metadata().await.map_err(|e| {
+                    ObjectIOError::StorageError {
+                        message: format!("Failed to read file metadata: {}", e),
+                    }
+                })?;
+
+                if metadata.is_dir() {
+                    pending.push(path);
+                    continue;
+                }
+                let key = match path.strip_prefix(&bucket_path).ok().and_then(|p| p.to_str()) {
+                    Some(key) => key.replace(std::path::MAIN_SEPARATOR, "/"),
+                    None => continue,
+                };
+                let key = match (self.key_layout, self.key_case) {
+                    // A file without a sidecar naming its key is not an object
+                    (KeyLayout::Hashed, _) => {
+                        let metadata_path = self.root_path.join(METADATA_DIR).join(bucket).join(&key);
+                        match self.read_sidecar(&metadata_path).await?.remove(KEY_ENTRY) {
+                            Some(key) => key,
+                            None => continue,
+                        }
+                    }
+                    (KeyLayout::Nested, KeyCase::Native) => key,
+                    (KeyLayout::Nested, KeyCase::Preserve) => match decode_key(&key) {
+                        Some(key) => key,
+                        None => continue,
+                    },
+                };
+                if !key.starts_with(prefix) {
+                    continue;
+                }
+
+                summaries.push(ObjectSummary {
+                    key,
+                    size: metadata.len(),
+                    etag: String::new(),
+                    last_modified: DateTime::<Utc>::from(metadata.modified().unwrap_or(std::time::SystemTime::UNIX_EPOCH)),
+                    storage_class: object_io_core::StorageClass::Standard,
+                });
+            }
+        }
+
+        Ok(summaries)
     }
+}
 
-    async fn get_object_metadata(&self, bucket: &str, key: &str) -> Result<HashMap<String, String>> {
-        let metadata_path = self.metadata_path(bucket, key);
+/// Hard-link `source` at `destination` so both share one copy of the data,
+/// or copy it where the filesystem does not support hard links
+///
+/// Sharing is safe because stored files are never modified in place: every
+/// write replaces the file with a rename, leaving other links untouched.
+async fn link_or_copy(source: &Path, destination: &Path) -> std::io::Result<()> {
+    match fs::hard_link(source, destination).await {
+        Err(e) if !matches!(e.kind(), std::io::ErrorKind::NotFound | std::io::ErrorKind::AlreadyExists) => {
+            fs::copy(source, destination).await.map(|_| ())
+        }
+        result => result,
+    }
+}
+
+/// Number of hard links to a file; where the platform doesn't report it,
+/// files are never taken to be unshared
+#[cfg(unix)]
+fn link_count(metadata: &std::fs::Metadata) -> u64 {
+    use std::os::unix::fs::MetadataExt;
+    metadata.nlink()
+}
+
+#[cfg(not(unix))]
+fn link_count(_metadata: &std::fs::Metadata) -> u64 {
+    u64::MAX
+}
 
-        if !metadata_path.exists() {
-            return Ok(HashMap::new());
+/// Stream `data` into a new file at `path`, returning the SHA-256 ETag of what was written
+async fn write_hashed(path: &Path, data: &mut (dyn AsyncRead + Send + Unpin)) -> Result<String> {
+    let file = fs::File::create(path).await.map_err(|e| {
+        ObjectIOError::StorageError {
+            message: format!("Failed to create file: {}", e),
         }
+    })?;
 
-        let metadata_content = fs::read_to_string(&metadata_path).await.map_err(|e| {
+    let mut writer = BufWriter::new(file);
+    let mut hasher = object_io_core::EtagHasher::new();
+    let mut chunk = vec![0u8; 64 * 1024];
+    loop {
+        let read = data.read(&mut chunk).await.map_err(|e| {
             ObjectIOError::StorageError {
-                message: format!("Failed to read metadata: {}", e),
+                message: format!("Failed to read data: {}", e),
             }
         })?;
-
-        let metadata: HashMap<String, String> = serde_json::from_str(&metadata_content).map_err(|e| {
+        if read == 0 {
+            break;
+        }
+        hasher.update(&chunk[..read]);
+        writer.write_all(&chunk[..read]).await.map_err(|e| {
             ObjectIOError::StorageError {
-                message: format!("Failed to parse metadata: {}", e),
+                message: format!("Failed to write data: {}", e),
             }
         })?;
-
-        Ok(metadata)
     }
+    writer.flush().await.map_err(|e| {
+        ObjectIOError::StorageError {
+            message: format!("Failed to write data: {}", e),
+        }
+    })?;
+
+    Ok(hasher.finalize())
+}
 
-    async fn list_objects(
+#[async_trait::async_trait]
+impl Storage for FilesystemStorage {
+    async fn put_object_if(
         &self,
         bucket: &str,
-        prefix: Option<&str>,
-        _delimiter: Option<&str>,
-        max_keys: Option<u32>,
-    ) -> Result<Vec<Object>> {
-        let bucket_path = self.bucket_path(bucket);
+        key: &str,
+        mut data: Box<dyn AsyncRead + Send + Unpin>,
+        metadata: HashMap<String, String>,
+        condition: &ConditionalPut,
+    ) -> Result<String> {
+        let object_path = self.object_path(bucket, key)?;
 
-        if !bucket_path.exists() {
-            return Ok(Vec::new());
+        // Create bucket directory if it doesn't exist
+        if let Some(parent) = object_path.parent() {
+            fs::create_dir_all(parent).await.map_err(|e| {
+                ObjectIOError::StorageError {
+                    message: format!("Failed to create bucket directory: {}", e),
+                }
+            })?;
         }
 
-        let mut objects = Vec::new();
-        let mut entries = fs::read_dir(&bucket_path).await.map_err(|e| {
-            ObjectIOError::StorageError {
-                message: format!("Failed to read bucket directory: {}", e),
+        // Stream the data to a temporary file, hashing as we go, then move it
+        // into place so a failed upload leaves any existing object untouched
+        let temp_path = self.temp_path();
+        let etag = match write_hashed(&temp_path, &mut data).await {
+            Ok(etag) => etag,
+            Err(e) => {
+                let _ = fs::remove_file(&temp_path).await;
+                return Err(e);
             }
-        })?;
+        };
 
+        // The object in place is only checked once the new data has arrived
+        let _lock = self.locks.lock(bucket, key).await;
+        let checked = match self.current_etag(bucket, key).await {
+            Ok(current) if !condition.is_unconditional() => condition.check(bucket, key, current.as_deref()),
+            Ok(_) => Ok(()),
+            Err(e) => Err(e),
+        };
+        if let Err(e) = checked {
+            let _ = fs::remove_file(&temp_path).await;
+            return Err(e);
+        }
+
+        // Deduplicated objects link to the content file for their ETag, the
+        // SHA-256 of their data
+        let mut metadata = metadata;
+        metadata.insert(ETAG_ENTRY.to_string(), etag.clone());
+        let source = if self.dedup {
+            let staged = match self.store_content(&temp_path, &etag).await {
+                Ok(staged) => staged,
+                Err(e) => {
+                    let _ = fs::remove_file(&temp_path).await;
+                    return Err(e);
+                }
+            };
+            metadata.insert(CONTENT_REF_KEY.to_string(), etag.clone());
+            staged
+        } else {
+            temp_path
+        };
+        self.install_object(bucket, key, &source).await?;
+
+        self.write_metadata(bucket, key, &metadata).await?;
+
+        Ok(etag)
+    }
+
+    async fn get_object(&self, bucket: &str, key: &str) -> Result<Box<dyn AsyncRead + Send + Unpin>> {
+        let object_path = self.object_path(bucket, key)?;
+
+        if !object_path.exists() {
+            return Err(ObjectIOError::ObjectNotFound {
+                bucket: bucket.to_string(),
+                key: key.to_string(),
+            });
+        }
+
+        let file = fs::File::open(object_path).await.map_err(|e| {
+            ObjectIOError::StorageError {
+                message: format!("Failed to open object: {}", e),
+            }
+        })?;
+
+        Ok(Box::new(file))
+    }
+
+    async fn get_object_range(
+        &self,
+        bucket: &str,
+        key: &str,
+        offset: u64,
+        length: u64,
+    ) -> Result<Box<dyn AsyncRead + Send + Unpin>> {
+        let object_path = self.object_path(bucket, key)?;
+
+        if !object_path.exists() {
+            return Err(ObjectIOError::ObjectNotFound {
+                bucket: bucket.to_string(),
+                key: key.to_string(),
+            });
+        }
+
+        let mut file = fs::File::open(object_path).await.map_err(|e| {
+            ObjectIOError::StorageError {
+                message: format!("Failed to open object: {}", e),
+            }
+        })?;
+        // Offsets past the end read nothing, however large; seeking that far
+        // can overflow the platform's signed file offset
+        let size = file.metadata().await?.len();
+        file.seek(SeekFrom::Start(offset.min(size))).await.map_err(|e| {
+            ObjectIOError::StorageError {
+                message: format!("Failed to seek object: {}", e),
+            }
+        })?;
+
+        Ok(Box::new(file.take(length)))
+    }
+
+    async fn delete_object(&self, bucket: &str, key: &str) -> Result<()> {
+        let object_path = self.object_path(bucket, key)?;
+        let metadata_path = self.metadata_path(bucket, key)?;
+
+        // Bucket directories are created with their first object
+        if !self.bucket_path(bucket).is_dir() {
+            return Err(ObjectIOError::BucketNotFound {
+                bucket: bucket.to_string(),
+            });
+        }
+
+        let _lock = self.locks.lock(bucket, key).await;
+        let mut usage = self.usage.lock().await;
+        let size = Self::object_size(&object_path).await?.ok_or_else(|| ObjectIOError::ObjectNotFound {
+            bucket: bucket.to_string(),
+            key: key.to_string(),
+        })?;
+        let content = self.content_ref(bucket, key).await?;
+
+        // Delete object file
+        fs::remove_file(&object_path).await.map_err(|e| {
+            ObjectIOError::StorageError {
+                message: format!("Failed to delete object: {}", e),
+            }
+        })?;
+        if let Some(counters) = usage.get_mut(bucket) {
+            counters.replace(Some(size), None);
+        }
+        drop(usage);
+
+        // Delete metadata file if it exists
+        if metadata_path.exists() {
+            fs::remove_file(&metadata_path).await.map_err(|e| {
+                ObjectIOError::StorageError {
+                    message: format!("Failed to delete metadata: {}", e),
+                }
+            })?;
+        }
+
+        self.release_content(content).await
+    }
+
+    async fn move_object(&self, bucket: &str, src_key: &str, dst_key: &str) -> Result<()> {
+        let src_path = self.object_path(bucket, src_key)?;
+        let dst_path = self.object_path(bucket, dst_key)?;
+        if src_path == dst_path {
+            return Ok(());
+        }
+        if !self.bucket_path(bucket).is_dir() {
+            return Err(ObjectIOError::BucketNotFound {
+                bucket: bucket.to_string(),
+            });
+        }
+        if Self::object_size(&src_path).await?.is_none() {
+            return Err(ObjectIOError::ObjectNotFound {
+                bucket: bucket.to_string(),
+                key: src_key.to_string(),
+            });
+        }
+        if let Some(parent) = dst_path.parent() {
+            fs::create_dir_all(parent).await.map_err(|e| {
+                ObjectIOError::StorageError {
+                    message: format!("Failed to create object directory: {}", e),
+                }
+            })?;
+        }
+
+        // The data is renamed in one step, so readers see the object at
+        // either key, never neither
+        let replaced_content = self.content_ref(bucket, dst_key).await?;
+        let mut usage = self.usage.lock().await;
+        let replaced = Self::object_size(&dst_path).await?;
+        fs::rename(&src_path, &dst_path).await.map_err(|e| {
+            ObjectIOError::StorageError {
+                message: format!("Failed to move object: {}", e),
+            }
+        })?;
+        // Renaming a link over another link to the same file does nothing
+        match fs::remove_file(&src_path).await {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                return Err(ObjectIOError::StorageError {
+                    message: format!("Failed to move object: {}", e),
+                })
+            }
+            _ => {}
+        }
+        if let Some(counters) = usage.get_mut(bucket) {
+            counters.replace(replaced, None);
+        }
+        drop(usage);
+
+        let (src_metadata, dst_metadata) = (self.metadata_path(bucket, src_key)?, self.metadata_path(bucket, dst_key)?);
+        if let Some(parent) = dst_metadata.parent() {
+            fs::create_dir_all(parent).await.map_err(|e| {
+                ObjectIOError::StorageError {
+                    message: format!("Failed to create metadata directory: {}", e),
+                }
+            })?;
+        }
+        let moved = match fs::rename(&src_metadata, &dst_metadata).await {
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => match fs::remove_file(&dst_metadata).await {
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+                result => result,
+            },
+            result => result,
+        };
+        moved.map_err(|e| ObjectIOError::StorageError {
+            message: format!("Failed to move metadata: {}", e),
+        })?;
+        // The moved sidecar still names the source key
+        if self.key_layout == KeyLayout::Hashed {
+            let sidecar = self.read_sidecar(&dst_metadata).await?;
+            self.write_metadata(bucket, dst_key, &sidecar).await?;
+        }
+        self.release_content(replaced_content).await
+    }
+
+    async fn move_prefix(&self, bucket: &str, src_prefix: &str, dst_prefix: &str) -> Result<bool> {
+        // Only nested, unescaped keys keep a prefix's objects in one directory
+        if self.key_layout != KeyLayout::Nested || self.key_case != KeyCase::Native {
+            return Ok(false);
+        }
+        let (src_prefix, dst_prefix) = (src_prefix.trim_end_matches('/'), dst_prefix.trim_end_matches('/'));
+        let (src_path, dst_path) = (self.object_path(bucket, src_prefix)?, self.object_path(bucket, dst_prefix)?);
+        let (src_metadata, dst_metadata) =
+            (self.metadata_path(bucket, src_prefix)?, self.metadata_path(bucket, dst_prefix)?);
+        if !src_path.is_dir() || dst_path.exists() || dst_metadata.exists() {
+            return Ok(false);
+        }
+
+        let storage_error = |e: std::io::Error| ObjectIOError::StorageError {
+            message: format!("Failed to move prefix: {}", e),
+        };
+        for path in [&dst_path, &dst_metadata] {
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent).await.map_err(storage_error)?;
+            }
+        }
+        fs::rename(&src_path, &dst_path).await.map_err(storage_error)?;
+        if src_metadata.is_dir() {
+            // Put the data back rather than leave it apart from its metadata
+            if let Err(e) = fs::rename(&src_metadata, &dst_metadata).await {
+                let _ = fs::rename(&dst_path, &src_path).await;
+                return Err(storage_error(e));
+            }
+        }
+        Ok(true)
+    }
+
+    async fn object_exists(&self, bucket: &str, key: &str) -> Result<bool> {
+        let object_path = self.object_path(bucket, key)?;
+        Ok(object_path.exists())
+    }
+
+    async fn get_object_metadata(&self, bucket: &str, key: &str) -> Result<HashMap<String, String>> {
+        self.read_metadata(&self.metadata_path(bucket, key)?).await
+    }
+
+    async fn head_object(&self, bucket: &str, key: &str) -> Result<ObjectHead> {
+        let not_found = || ObjectIOError::ObjectNotFound {
+            bucket: bucket.to_string(),
+            key: key.to_string(),
+        };
+        let stat = match fs::metadata(self.object_path(bucket, key)?).await {
+            Ok(stat) if stat.is_file() => stat,
+            Ok(_) => return Err(not_found()),
+            // A key nested under another object's key leads through a file
+            Err(e) if matches!(e.kind(), std::io::ErrorKind::NotFound | std::io::ErrorKind::NotADirectory) => {
+                return Err(not_found())
+            }
+            Err(e) => {
+                return Err(ObjectIOError::StorageError {
+                    message: format!("Failed to stat object: {}", e),
+                })
+            }
+        };
+
+        // Objects stored before ETags were recorded in their sidecar have none
+        let mut metadata = self.read_sidecar(&self.metadata_path(bucket, key)?).await?;
+        let etag = metadata.remove(ETAG_ENTRY);
+        metadata.remove(CONTENT_REF_KEY);
+        metadata.remove(KEY_ENTRY);
+        Ok(ObjectHead {
+            size: stat.len(),
+            etag,
+            last_modified: DateTime::<Utc>::from(stat.modified().unwrap_or(std::time::SystemTime::UNIX_EPOCH)),
+            metadata,
+        })
+    }
+
+    async fn replace_object_metadata(&self, bucket: &str, key: &str, metadata: HashMap<String, String>) -> Result<()> {
+        let _lock = self.locks.lock(bucket, key).await;
+        if Self::object_size(&self.object_path(bucket, key)?).await?.is_none() {
+            return Err(ObjectIOError::ObjectNotFound {
+                bucket: bucket.to_string(),
+                key: key.to_string(),
+            });
+        }
+        // The storage's own entries stay with the data they describe
+        let mut sidecar = self.read_sidecar(&self.metadata_path(bucket, key)?).await?;
+        sidecar.retain(|name, _| [ETAG_ENTRY, CONTENT_REF_KEY].contains(&name.as_str()));
+        sidecar.extend(metadata);
+        self.write_metadata(bucket, key, &sidecar).await
+    }
+
+    async fn list_objects(&self, request: &ListObjectsRequest) -> Result<ListObjectsResponse> {
+        let prefix = request.prefix.as_deref().unwrap_or_default();
+        let mut summaries = self.collect_objects(&request.bucket, prefix).await?;
+        summaries.sort_by(|a, b| a.key.cmp(&b.key));
+        Ok(ListObjectsResponse::paginate(request, summaries))
+    }
+
+    async fn bucket_usage(&self, bucket: &str) -> Result<(u64, u64)> {
+        let mut usage = self.usage.lock().await;
+        let counters = match usage.get(bucket) {
+            Some(counters) => *counters,
+            None => {
+                // First request for this bucket since startup: scan it once,
+                // then keep the counters up to date as objects change
+                let mut counters = BucketUsage::default();
+                for summary in self.collect_objects(bucket, "").await? {
+                    counters.replace(None, Some(summary.size));
+                }
+                usage.insert(bucket.to_string(), counters);
+                counters
+            }
+        };
+        Ok((counters.objects, counters.bytes))
+    }
+
+    async fn create_multipart_upload(
+        &self,
+        bucket: &str,
+        key: &str,
+        metadata: HashMap<String, String>,
+    ) -> Result<String> {
+        let upload_id = multipart::new_upload_id();
+        let upload_path = self.upload_path(&upload_id);
+
+        fs::create_dir_all(&upload_path).await.map_err(|e| {
+            ObjectIOError::StorageError {
+                message: format!("Failed to create upload directory: {}", e),
+            }
+        })?;
+
+        let initiated = object_io_core::timestamp_now();
+        let manifest = UploadManifest {
+            bucket: bucket.to_string(),
+            key: key.to_string(),
+            initiated,
+            metadata,
+        };
+        let manifest_json = serde_json::to_string(&manifest).map_err(|e| {
+            ObjectIOError::StorageError {
+                message: format!("Failed to serialize upload manifest: {}", e),
+            }
+        })?;
+
+        fs::write(upload_path.join("upload.json"), manifest_json).await.map_err(|e| {
+            ObjectIOError::StorageError {
+                message: format!("Failed to write upload manifest: {}", e),
+            }
+        })?;
+
+        // An index loaded since the directory was created may already hold it
+        if let Some(index) = self.upload_index().await?.as_mut() {
+            index.insert((bucket.to_string(), key.to_string(), upload_id.clone()), initiated);
+        }
+
+        Ok(upload_id)
+    }
+
+    async fn upload_part(
+        &self,
+        bucket: &str,
+        key: &str,
+        upload_id: &str,
+        part_number: u32,
+        mut data: Box<dyn AsyncRead + Send + Unpin>,
+    ) -> Result<String> {
+        multipart::validate_part_number(part_number)?;
+        self.load_manifest(bucket, key, upload_id).await?;
+
+        // Without its ETag file a part counts as missing, so a replacement
+        // that fails part way through doesn't leave the old ETag behind
+        let part_path = self.part_path(upload_id, part_number);
+        let etag_path = self.part_etag_path(upload_id, part_number);
+        match fs::remove_file(&etag_path).await {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => {
+                return Err(ObjectIOError::StorageError {
+                    message: format!("Failed to replace part: {}", e),
+                })
+            }
+        }
+
+        let etag = match write_hashed(&part_path, &mut data).await {
+            Ok(etag) => etag,
+            Err(e) => {
+                let _ = fs::remove_file(&part_path).await;
+                return Err(e);
+            }
+        };
+        fs::write(&etag_path, &etag).await.map_err(|e| {
+            ObjectIOError::StorageError {
+                message: format!("Failed to write part ETag: {}", e),
+            }
+        })?;
+
+        Ok(etag)
+    }
+
+    async fn complete_multipart_upload(
+        &self,
+        bucket: &str,
+        key: &str,
+        upload_id: &str,
+        parts: &[CompletedPart],
+    ) -> Result<String> {
+        multipart::validate_part_order(parts)?;
+        let manifest = self.load_manifest(bucket, key, upload_id).await?;
+        let upload_path = self.upload_path(upload_id);
+
+        // Validate every listed part before writing anything
+        let mut etags = Vec::with_capacity(parts.len());
+        for (i, part) in parts.iter().enumerate() {
+            let etag = self.read_part_etag(upload_id, part.part_number).await?;
+            let stored = match &etag {
+                Some(etag) => {
+                    let size = fs::metadata(self.part_path(upload_id, part.part_number))
+                        .await
+                        .map_err(|e| ObjectIOError::StorageError {
+                            message: format!("Failed to stat part {}: {}", part.part_number, e),
+                        })?
+                        .len();
+                    Some((etag.as_str(), size))
+                }
+                None => None,
+            };
+            multipart::validate_part(part, stored, i == parts.len() - 1)?;
+            etags.extend(etag);
+        }
+
+        let assembled_path = self.temp_path();
+        let composite = match self.assemble_parts(upload_id, parts, &etags, &assembled_path).await {
+            Ok(composite) => composite,
+            Err(e) => {
+                let _ = fs::remove_file(&assembled_path).await;
+                return Err(e);
+            }
+        };
+
+        // Move the assembled object into place
+        let object_path = self.object_path(bucket, key)?;
+        if let Some(parent) = object_path.parent() {
+            fs::create_dir_all(parent).await.map_err(|e| {
+                ObjectIOError::StorageError {
+                    message: format!("Failed to create bucket directory: {}", e),
+                }
+            })?;
+        }
+        let _lock = self.locks.lock(bucket, key).await;
+        self.install_object(bucket, key, &assembled_path).await?;
+
+        let etag = composite.finish();
+        let mut metadata = manifest.metadata;
+        metadata.insert(ETAG_ENTRY.to_string(), etag.clone());
+        self.write_metadata(bucket, key, &metadata).await?;
+
+        // Parts are no longer needed once the object is in place
+        fs::remove_dir_all(&upload_path).await.map_err(|e| {
+            ObjectIOError::StorageError {
+                message: format!("Failed to remove upload parts: {}", e),
+            }
+        })?;
+        self.unindex_upload(bucket, key, upload_id).await;
+
+        Ok(etag)
+    }
+
+    async fn abort_multipart_upload(&self, bucket: &str, key: &str, upload_id: &str) -> Result<()> {
+        self.load_manifest(bucket, key, upload_id).await?;
+
+        fs::remove_dir_all(self.upload_path(upload_id)).await.map_err(|e| {
+            ObjectIOError::StorageError {
+                message: format!("Failed to remove upload: {}", e),
+            }
+        })?;
+        self.unindex_upload(bucket, key, upload_id).await;
+        Ok(())
+    }
+
+    async fn list_multipart_uploads(&self, bucket: &str) -> Result<Vec<MultipartUpload>> {
+        let request = ListUploadsRequest {
+            bucket: bucket.to_string(),
+            max_uploads: u32::MAX,
+            ..ListUploadsRequest::default()
+        };
+        Ok(self.list_multipart_uploads_page(&request).await?.uploads)
+    }
+
+    async fn list_multipart_uploads_page(&self, request: &ListUploadsRequest) -> Result<ListUploadsPage> {
+        let index = self.upload_index().await?;
+        let index = index.as_ref().expect("upload index loaded");
+        Ok(multipart::page_uploads(index, request, |initiated| *initiated))
+    }
+
+    async fn list_parts(&self, bucket: &str, key: &str, upload_id: &str) -> Result<Vec<UploadPart>> {
+        self.load_manifest(bucket, key, upload_id).await?;
+
+        let mut entries = fs::read_dir(self.upload_path(upload_id)).await.map_err(|e| {
+            ObjectIOError::StorageError {
+                message: format!("Failed to read upload directory: {}", e),
+            }
+        })?;
+
+        let mut parts = Vec::new();
         while let Some(entry) = entries.next_entry().await.map_err(|e| {
             ObjectIOError::StorageError {
-                message: format!("Failed to read directory entry: {}", e),
+                message: format!("Failed to read upload directory entry: {}", e),
             }
         })? {
-            let path = entry.path();
-            
-            // Skip metadata files
-            if path.extension().and_then(|s| s.to_str()) == Some("meta") {
-                continue;
-            }
-
-            if path.is_file() {
-                if let Some(file_name) = path.file_name().and_then(|s| s.to_str()) {
-                    // Apply prefix filter
-                    if let Some(prefix_str) = prefix {
-                        if !file_name.starts_with(prefix_str) {
-                            continue;
-                        }
-                    }
+            // Each completed part has an ETag file; part data without one is still being written
+            let file_name = entry.file_name().to_string_lossy().into_owned();
+            let part_number = match file_name.strip_suffix(".etag").and_then(|n| n.parse::<u32>().ok()) {
+                Some(part_number) => part_number,
+                None => continue,
+            };
 
-                    // Get file metadata
-                    let metadata = entry.metadata().await.map_err(|e| {
-                        ObjectIOError::StorageError {
-                            message: format!("Failed to read file metadata: {}", e),
-                        }
-                    })?;
-
-                    // Create object summary
-                    let object = Object {
-                        key: file_name.to_string(),
-                        bucket: bucket.to_string(),
-                        size: metadata.len(),
-                        etag: "".to_string(), // Would need to read file to generate
-                        last_modified: chrono::DateTime::<chrono::Utc>::from(metadata.modified().unwrap_or(std::time::SystemTime::UNIX_EPOCH)),
-                        content_type: "application/octet-stream".to_string(),
-                        content_encoding: None,
-                        metadata: HashMap::new(),
-                        storage_class: object_io_core::StorageClass::Standard,
-                    };
-
-                    objects.push(object);
-
-                    // Apply max_keys limit
-                    if let Some(max) = max_keys {
-                        if objects.len() >= max as usize {
-                            break;
-                        }
+            let etag = match self.read_part_etag(upload_id, part_number).await? {
+                Some(etag) => etag,
+                None => continue,
+            };
+            let metadata = fs::metadata(self.part_path(upload_id, part_number)).await.map_err(|e| {
+                ObjectIOError::StorageError {
+                    message: format!("Failed to stat part {}: {}", part_number, e),
+                }
+            })?;
+
+            parts.push(UploadPart {
+                part_number,
+                etag,
+                size: metadata.len(),
+                last_modified: DateTime::<Utc>::from(metadata.modified().unwrap_or(std::time::SystemTime::UNIX_EPOCH)),
+            });
+        }
+
+        parts.sort_by_key(|part| part.part_number);
+        Ok(parts)
+    }
+
+    async fn snapshot_object(&self, bucket: &str, snapshot: &str, key: &str) -> Result<()> {
+        let snapshot_path = self.snapshot_path(bucket, snapshot)?;
+        let key_path = self.key_path(key)?;
+        let error = |e: std::io::Error| ObjectIOError::StorageError {
+            message: format!("Failed to snapshot object: {}", e),
+        };
+
+        let data_path = snapshot_path.join(SNAPSHOT_OBJECTS).join(&*key_path);
+        if let Some(parent) = data_path.parent() {
+            fs::create_dir_all(parent).await.map_err(error)?;
+        }
+        match link_or_copy(&self.object_path(bucket, key)?, &data_path).await {
+            Err(e) if matches!(e.kind(), std::io::ErrorKind::NotFound | std::io::ErrorKind::NotADirectory) => {
+                return Err(ObjectIOError::ObjectNotFound {
+                    bucket: bucket.to_string(),
+                    key: key.to_string(),
+                })
+            }
+            result => result.map_err(error)?,
+        }
+
+        let metadata_path = snapshot_path.join(SNAPSHOT_METADATA).join(&*key_path);
+        if let Some(parent) = metadata_path.parent() {
+            fs::create_dir_all(parent).await.map_err(error)?;
+        }
+        match link_or_copy(&self.metadata_path(bucket, key)?, &metadata_path).await {
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            result => result.map_err(error),
+        }
+    }
+
+    async fn save_snapshot_manifest(&self, bucket: &str, snapshot: &str, manifest: &[u8]) -> Result<()> {
+        let snapshot_path = self.snapshot_path(bucket, snapshot)?;
+        let temp_path = self.temp_path();
+        let written = match fs::create_dir_all(&snapshot_path).await {
+            Ok(()) => fs::write(&temp_path, manifest).await,
+            Err(e) => Err(e),
+        };
+        let written = match written {
+            Ok(()) => self.move_into_place(&temp_path, &snapshot_path.join(SNAPSHOT_MANIFEST)).await,
+            Err(e) => Err(e),
+        };
+        written.map_err(|e| {
+            let _ = std::fs::remove_file(&temp_path);
+            ObjectIOError::StorageError {
+                message: format!("Failed to write snapshot manifest: {}", e),
+            }
+        })
+    }
+
+    async fn snapshot_manifest(&self, bucket: &str, snapshot: &str) -> Result<Vec<u8>> {
+        let snapshot_path = self.snapshot_path(bucket, snapshot)?;
+        match fs::read(snapshot_path.join(SNAPSHOT_MANIFEST)).await {
+            Ok(manifest) => Ok(manifest),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Err(ObjectIOError::SnapshotNotFound {
+                bucket: bucket.to_string(),
+                snapshot: snapshot.to_string(),
+            }),
+            Err(e) => Err(ObjectIOError::StorageError {
+                message: format!("Failed to read snapshot manifest: {}", e),
+            }),
+        }
+    }
+
+    async fn list_snapshots(&self, bucket: &str) -> Result<Vec<String>> {
+        let error = |e: std::io::Error| ObjectIOError::StorageError {
+            message: format!("Failed to list snapshots: {}", e),
+        };
+        let mut entries = match fs::read_dir(self.root_path.join(SNAPSHOTS_DIR).join(bucket)).await {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(error(e)),
+        };
+
+        // Snapshots interrupted before their manifest was saved are incomplete
+        let mut snapshots = Vec::new();
+        while let Some(entry) = entries.next_entry().await.map_err(error)? {
+            if entry.path().join(SNAPSHOT_MANIFEST).is_file() {
+                snapshots.extend(entry.file_name().to_str().map(str::to_string));
+            }
+        }
+        snapshots.sort();
+        Ok(snapshots)
+    }
+
+    async fn restore_object(&self, bucket: &str, snapshot: &str, key: &str) -> Result<()> {
+        let snapshot_path = self.snapshot_path(bucket, snapshot)?;
+        if !snapshot_path.join(SNAPSHOT_MANIFEST).is_file() {
+            return Err(ObjectIOError::SnapshotNotFound {
+                bucket: bucket.to_string(),
+                snapshot: snapshot.to_string(),
+            });
+        }
+        let key_path = self.key_path(key)?;
+        let error = |e: std::io::Error| ObjectIOError::StorageError {
+            message: format!("Failed to restore object: {}", e),
+        };
+
+        let object_path = self.object_path(bucket, key)?;
+        if let Some(parent) = object_path.parent() {
+            fs::create_dir_all(parent).await.map_err(error)?;
+        }
+        // Staged under the root, where the snapshot can be linked from, then
+        // moved into place in one step like any other write
+        let staged = self.staging_path();
+        match link_or_copy(&snapshot_path.join(SNAPSHOT_OBJECTS).join(&*key_path), &staged).await {
+            Err(e) if matches!(e.kind(), std::io::ErrorKind::NotFound | std::io::ErrorKind::NotADirectory) => {
+                return Err(ObjectIOError::ObjectNotFound {
+                    bucket: bucket.to_string(),
+                    key: key.to_string(),
+                })
+            }
+            result => result.map_err(error)?,
+        }
+        if let Err(e) = self.install_object(bucket, key, &staged).await {
+            let _ = fs::remove_file(&staged).await;
+            return Err(e);
+        }
+
+        // An object snapshotted without a sidecar had no metadata
+        let metadata_path = self.metadata_path(bucket, key)?;
+        let staged = self.staging_path();
+        let restored = async {
+            match link_or_copy(&snapshot_path.join(SNAPSHOT_METADATA).join(&*key_path), &staged).await {
+                Ok(()) => {
+                    if let Some(parent) = metadata_path.parent() {
+                        fs::create_dir_all(parent).await?;
                     }
+                    self.move_into_place(&staged, &metadata_path).await
                 }
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => match fs::remove_file(&metadata_path).await {
+                    Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+                    result => result,
+                },
+                Err(e) => Err(e),
             }
         }
+        .await;
+        restored.map_err(|e| {
+            let _ = std::fs::remove_file(&staged);
+            error(e)
+        })
+    }
+
+    async fn sync(&self) -> Result<()> {
+        let root_path = self.root_path.clone();
+        tokio::task::spawn_blocking(move || std::fs::File::open(root_path)?.sync_all())
+            .await
+            .map_err(|e| ObjectIOError::StorageError {
+                message: format!("Failed to sync storage: {}", e),
+            })?
+            .map_err(|e| ObjectIOError::StorageError {
+                message: format!("Failed to sync storage: {}", e),
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[tokio::test]
+    async fn test_conformance() {
+        for dedup in [false, true] {
+            let dir = tempfile::tempdir().unwrap();
+            let storage = FilesystemStorage::new(dir.path()).await.unwrap().with_dedup(dedup);
+            crate::conformance::run(&storage).await;
+        }
+        let dir = tempfile::tempdir().unwrap();
+        let storage = FilesystemStorage::new(dir.path()).await.unwrap().with_key_layout(KeyLayout::Hashed);
+        crate::conformance::run(&storage).await;
+    }
+
+    #[tokio::test]
+    async fn test_conditional_put_hashes_objects_without_recorded_etag() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = FilesystemStorage::new(dir.path()).await.unwrap();
+        storage
+            .put_object("bucket", "old.txt", Box::new(Cursor::new(b"legacy".to_vec())), HashMap::new())
+            .await
+            .unwrap();
+        // As written before ETags were recorded
+        let metadata_path = storage.metadata_path("bucket", "old.txt").unwrap();
+        let mut sidecar = storage.read_sidecar(&metadata_path).await.unwrap();
+        sidecar.remove(ETAG_ENTRY);
+        std::fs::write(&metadata_path, serde_json::to_string(&sidecar).unwrap()).unwrap();
+
+        let condition = ConditionalPut {
+            if_match: Some(object_io_core::generate_etag(b"legacy")),
+            if_none_match_star: false,
+        };
+        let data = Box::new(Cursor::new(b"new".to_vec()));
+        storage.put_object_if("bucket", "old.txt", data, HashMap::new(), &condition).await.unwrap();
+        assert!(storage.get_object_metadata("bucket", "old.txt").await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_new_rejects_unusable_root() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("file");
+        std::fs::write(&file, b"not a directory").unwrap();
+        let err = FilesystemStorage::new(&file).await.err().unwrap();
+        assert!(err.to_string().contains("is not a directory"), "{}", err);
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let read_only = dir.path().join("read-only");
+            std::fs::create_dir(&read_only).unwrap();
+            std::fs::set_permissions(&read_only, std::fs::Permissions::from_mode(0o555)).unwrap();
+            // Permissions do not bind a privileged user, so only check when
+            // the directory really is read-only
+            if std::fs::write(read_only.join("check"), b"").is_err() {
+                let err = FilesystemStorage::new(&read_only).await.err().unwrap();
+                assert!(err.to_string().contains("is not writable"), "{}", err);
+            }
+            std::fs::set_permissions(&read_only, std::fs::Permissions::from_mode(0o755)).unwrap();
+        }
+
+        let storage = FilesystemStorage::new(dir.path().join("fresh")).await.unwrap();
+        assert!(!storage.root_path.join(PROBE_FILE).exists());
+    }
+
+    #[tokio::test]
+    async fn test_multipart_upload_assembles_large_parts() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = FilesystemStorage::new(dir.path()).await.unwrap();
+
+        let mut metadata = HashMap::new();
+        metadata.insert("content-type".to_string(), "video/mp4".to_string());
+        let upload_id = storage
+            .create_multipart_upload("media", "videos/big.mp4", metadata)
+            .await
+            .unwrap();
+
+        const PART_SIZE: usize = 6 * 1024 * 1024;
+        let mut parts = Vec::new();
+        for part_number in 1..=5u32 {
+            let data = vec![part_number as u8; PART_SIZE];
+            let etag = storage
+                .upload_part("media", "videos/big.mp4", &upload_id, part_number, Box::new(Cursor::new(data)))
+                .await
+                .unwrap();
+            parts.push(CompletedPart { part_number, etag });
+        }
+
+        let etag = storage
+            .complete_multipart_upload("media", "videos/big.mp4", &upload_id, &parts)
+            .await
+            .unwrap();
+        assert!(etag.ends_with("-5"));
+
+        let mut assembled = Vec::new();
+        storage
+            .get_object("media", "videos/big.mp4")
+            .await
+            .unwrap()
+            .read_to_end(&mut assembled)
+            .await
+            .unwrap();
+        assert_eq!(assembled.len(), 5 * PART_SIZE);
+        for (i, chunk) in assembled.chunks(PART_SIZE).enumerate() {
+            assert!(chunk.iter().all(|&b| b == i as u8 + 1));
+        }
+
+        let metadata = storage.get_object_metadata("media", "videos/big.mp4").await.unwrap();
+        assert_eq!(metadata.get("content-type").map(String::as_str), Some("video/mp4"));
+
+        // Parts are cleaned up and the upload can no longer be used
+        assert!(!storage.upload_path(&upload_id).exists());
+        assert!(matches!(
+            storage.abort_multipart_upload("media", "videos/big.mp4", &upload_id).await,
+            Err(ObjectIOError::UploadNotFound { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_multipart_upload_rejects_invalid_complete() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = FilesystemStorage::new(dir.path()).await.unwrap();
+        let upload_id = storage
+            .create_multipart_upload("media", "small.bin", HashMap::new())
+            .await
+            .unwrap();
+
+        assert!(matches!(
+            storage
+                .upload_part("media", "small.bin", &upload_id, 0, Box::new(Cursor::new(vec![0u8; 4])))
+                .await,
+            Err(ObjectIOError::InvalidArgument { .. })
+        ));
+
+        let mut parts = Vec::new();
+        for part_number in 1..=2u32 {
+            let etag = storage
+                .upload_part("media", "small.bin", &upload_id, part_number, Box::new(Cursor::new(vec![0u8; 4])))
+                .await
+                .unwrap();
+            parts.push(CompletedPart { part_number, etag });
+        }
+
+        // Uploaded parts can be listed to resume the upload
+        let listed = storage.list_parts("media", "small.bin", &upload_id).await.unwrap();
+        assert_eq!(listed.iter().map(|p| p.part_number).collect::<Vec<_>>(), [1, 2]);
+        assert_eq!(listed[0].etag, parts[0].etag);
+        assert_eq!(listed[0].size, 4);
+        let uploads = storage.list_multipart_uploads("media").await.unwrap();
+        assert_eq!(uploads.len(), 1);
+        assert_eq!(uploads[0].upload_id, upload_id);
+        assert!(storage.list_multipart_uploads("other").await.unwrap().is_empty());
+
+        // Non-final part under the minimum size
+        assert!(matches!(
+            storage.complete_multipart_upload("media", "small.bin", &upload_id, &parts).await,
+            Err(ObjectIOError::EntityTooSmall { .. })
+        ));
+
+        // Nothing was written and the upload is still usable
+        assert!(!storage.object_exists("media", "small.bin").await.unwrap());
+        storage
+            .complete_multipart_upload("media", "small.bin", &upload_id, &parts[1..])
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_list_multipart_uploads_pages() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = FilesystemStorage::new(dir.path()).await.unwrap();
+        for key in ["b", "a", "c", "a"] {
+            storage.create_multipart_upload("media", key, HashMap::new()).await.unwrap();
+        }
+        storage.create_multipart_upload("other", "a", HashMap::new()).await.unwrap();
+
+        async fn all_pages(storage: &FilesystemStorage) -> Vec<Vec<String>> {
+            let mut request = ListUploadsRequest {
+                bucket: "media".to_string(),
+                max_uploads: 2,
+                ..ListUploadsRequest::default()
+            };
+            let mut pages = Vec::new();
+            loop {
+                let page = storage.list_multipart_uploads_page(&request).await.unwrap();
+                pages.push(page.uploads.iter().map(|upload| upload.key.clone()).collect());
+                let Some(last) = page.uploads.last().filter(|_| page.is_truncated) else {
+                    return pages;
+                };
+                request.key_marker = Some(last.key.clone());
+                request.upload_id_marker = Some(last.upload_id.clone());
+            }
+        }
+        assert_eq!(all_pages(&storage).await, [vec!["a", "a"], vec!["b", "c"]]);
+
+        // Finished uploads leave the index straight away
+        let first = storage.list_multipart_uploads("media").await.unwrap().remove(0);
+        storage.abort_multipart_upload("media", "a", &first.upload_id).await.unwrap();
+        assert_eq!(all_pages(&storage).await, [vec!["a", "b"], vec!["c"]]);
+
+        // A fresh instance rebuilds the index from the manifests
+        let reopened = FilesystemStorage::new(dir.path()).await.unwrap();
+        assert_eq!(all_pages(&reopened).await, [vec!["a", "b"], vec!["c"]]);
+    }
+
+    #[test]
+    fn test_key_encoding_round_trip() {
+        assert_eq!(encode_key("photos/cat.jpg"), "photos/cat.jpg");
+        assert_eq!(encode_key("Photos/Cat.JPG"), "^photos/^cat.^j^p^g");
+        assert_eq!(encode_key("a^b"), "a^^b");
+        assert_eq!(encode_key("Été"), "^{c9}t^{e9}");
+        for key in ["photos/cat.jpg", "Photos/Cat.JPG", "a^b", "^A^", "Été/ß/Ω", "emoji-😀", "a.meta"] {
+            assert_eq!(decode_key(&encode_key(key)).as_deref(), Some(key));
+        }
+        assert_eq!(decode_key("^"), None);
+        assert_eq!(decode_key("^1"), None);
+        assert_eq!(decode_key("^{zz}"), None);
+    }
+
+    #[tokio::test]
+    async fn test_keys_differing_in_case() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = FilesystemStorage::new(dir.path()).await.unwrap().with_key_case(KeyCase::Preserve);
+        for (key, body) in [("Docs/File.txt", "upper"), ("docs/file.txt", "lower")] {
+            let mut metadata = HashMap::new();
+            metadata.insert("content-type".to_string(), format!("text/{}", body));
+            storage
+                .put_object("bucket", key, Box::new(Cursor::new(body.as_bytes().to_vec())), metadata)
+                .await
+                .unwrap();
+        }
+
+        // Stored apart even where the filesystem ignores case
+        assert!(dir.path().join("bucket/^docs/^file.txt").is_file());
+        assert!(dir.path().join("bucket/docs/file.txt").is_file());
+        for (key, body) in [("Docs/File.txt", "upper"), ("docs/file.txt", "lower")] {
+            let mut data = Vec::new();
+            storage.get_object("bucket", key).await.unwrap().read_to_end(&mut data).await.unwrap();
+            assert_eq!(data, body.as_bytes());
+            let metadata = storage.get_object_metadata("bucket", key).await.unwrap();
+            assert_eq!(metadata["content-type"], format!("text/{}", body));
+        }
+
+        let listed = |prefix: &str| {
+            let mut request = ListObjectsRequest::new("bucket");
+            request.prefix = Some(prefix.to_string());
+            let storage = &storage;
+            async move {
+                let listing = storage.list_objects(&request).await.unwrap();
+                listing.objects.into_iter().map(|object| object.key).collect::<Vec<_>>()
+            }
+        };
+        assert_eq!(listed("").await, ["Docs/File.txt", "docs/file.txt"]);
+        assert_eq!(listed("Docs/F").await, ["Docs/File.txt"]);
+        assert_eq!(listed("docs/").await, ["docs/file.txt"]);
+
+        storage.delete_object("bucket", "Docs/File.txt").await.unwrap();
+        assert!(storage.object_exists("bucket", "docs/file.txt").await.unwrap());
+        assert!(!storage.object_exists("bucket", "Docs/File.txt").await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_hashed_layout_stores_maximal_keys() {
+        // A 1024-byte key, as long as S3 allows, nested 300 deep and ending
+        // in a segment longer than a path component may be
+        let long_key = format!("{}{}", "d/".repeat(300), "x".repeat(424));
+        assert_eq!(long_key.len(), 1024);
+        let dir = tempfile::tempdir().unwrap();
+        let nested = FilesystemStorage::new(dir.path().join("nested")).await.unwrap();
+        let data = || Box::new(Cursor::new(b"long".to_vec()));
+        assert!(nested.put_object("bucket", &long_key, data(), HashMap::new()).await.is_err());
+
+        let storage = FilesystemStorage::new(dir.path().join("hashed"))
+            .await
+            .unwrap()
+            .with_key_layout(KeyLayout::Hashed);
+        let mut metadata = HashMap::new();
+        metadata.insert("content-type".to_string(), "text/plain".to_string());
+        storage.put_object("bucket", &long_key, data(), metadata).await.unwrap();
+        storage.put_object("bucket", "d/short", data(), HashMap::new()).await.unwrap();
+        let mut read = Vec::new();
+        storage.get_object("bucket", &long_key).await.unwrap().read_to_end(&mut read).await.unwrap();
+        assert_eq!(read, b"long");
+        let hashed = hashed_key_path(&long_key);
+        assert!(dir.path().join("hashed/bucket").join(&hashed).is_file());
+        // The key kept in the sidecar is not part of the object's metadata
+        let metadata = storage.get_object_metadata("bucket", &long_key).await.unwrap();
+        assert_eq!(metadata, HashMap::from([("content-type".to_string(), "text/plain".to_string())]));
+
+        let listed = |prefix: &str| {
+            let mut request = ListObjectsRequest::new("bucket");
+            request.prefix = Some(prefix.to_string());
+            let storage = &storage;
+            async move {
+                let listing = storage.list_objects(&request).await.unwrap();
+                listing.objects.into_iter().map(|object| object.key).collect::<Vec<_>>()
+            }
+        };
+        assert_eq!(listed("d/d/").await, [long_key.as_str()]);
+        assert_eq!(listed("d/").await, [long_key.as_str(), "d/short"]);
+
+        // A moved object is listed under its new key
+        let moved_key = format!("{}y", &long_key[..1023]);
+        storage.move_object("bucket", &long_key, &moved_key).await.unwrap();
+        assert_eq!(listed("").await, [moved_key.as_str(), "d/short"]);
+        assert!(!storage.object_exists("bucket", &long_key).await.unwrap());
+
+        storage.delete_object("bucket", &moved_key).await.unwrap();
+        assert_eq!(listed("").await, ["d/short"]);
+        assert_eq!(storage.bucket_usage("bucket").await.unwrap(), (1, 4));
+    }
+
+    #[tokio::test]
+    async fn test_keys_ending_in_meta() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = FilesystemStorage::new(dir.path()).await.unwrap();
+        for key in ["report", "report.meta", "report.txt"] {
+            let mut metadata = HashMap::new();
+            metadata.insert("x-amz-meta-name".to_string(), key.to_string());
+            storage
+                .put_object("bucket", key, Box::new(Cursor::new(key.as_bytes().to_vec())), metadata)
+                .await
+                .unwrap();
+        }
+
+        for key in ["report", "report.meta", "report.txt"] {
+            let mut data = Vec::new();
+            storage.get_object("bucket", key).await.unwrap().read_to_end(&mut data).await.unwrap();
+            assert_eq!(data, key.as_bytes());
+            assert_eq!(storage.get_object_metadata("bucket", key).await.unwrap()["x-amz-meta-name"], key);
+        }
+        let listing = storage.list_objects(&ListObjectsRequest::new("bucket")).await.unwrap();
+        let keys: Vec<&str> = listing.objects.iter().map(|object| object.key.as_str()).collect();
+        assert_eq!(keys, ["report", "report.meta", "report.txt"]);
+
+        storage.delete_object("bucket", "report").await.unwrap();
+        let metadata = storage.get_object_metadata("bucket", "report.meta").await.unwrap();
+        assert_eq!(metadata["x-amz-meta-name"], "report.meta");
+        storage.move_object("bucket", "report.meta", "nested/copy.meta").await.unwrap();
+        assert_eq!(
+            storage.get_object_metadata("bucket", "nested/copy.meta").await.unwrap()["x-amz-meta-name"],
+            "report.meta"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_legacy_sidecars_are_migrated() {
+        let dir = tempfile::tempdir().unwrap();
+        let bucket = dir.path().join("bucket");
+        std::fs::create_dir_all(bucket.join("docs")).unwrap();
+        std::fs::write(bucket.join("docs/a.txt"), b"a").unwrap();
+        std::fs::write(bucket.join("docs/a.pdf"), b"a").unwrap();
+        std::fs::write(bucket.join("docs/a.meta"), r#"{"content-type":"text/plain"}"#).unwrap();
+        std::fs::write(bucket.join("orphan.meta"), b"{}").unwrap();
+
+        let storage = FilesystemStorage::new(dir.path()).await.unwrap();
+        for key in ["docs/a.txt", "docs/a.pdf"] {
+            assert_eq!(storage.get_object_metadata("bucket", key).await.unwrap()["content-type"], "text/plain");
+        }
+        assert!(!bucket.join("docs/a.meta").exists());
+        let listing = storage.list_objects(&ListObjectsRequest::new("bucket")).await.unwrap();
+        let keys: Vec<&str> = listing.objects.iter().map(|object| object.key.as_str()).collect();
+        assert_eq!(keys, ["docs/a.pdf", "docs/a.txt", "orphan.meta"]);
+
+        // Only migrated once: `.meta` files written later are objects
+        std::fs::write(bucket.join("docs/a.meta"), b"{}").unwrap();
+        let storage = FilesystemStorage::new(dir.path()).await.unwrap();
+        assert!(storage.object_exists("bucket", "docs/a.meta").await.unwrap());
+        assert_eq!(storage.get_object_metadata("bucket", "docs/a.txt").await.unwrap()["content-type"], "text/plain");
+    }
+
+    /// Reader yielding some data and then failing, like a client that stalls mid-upload
+    fn failing_reader() -> Box<dyn AsyncRead + Send + Unpin> {
+        let error = std::io::Error::new(std::io::ErrorKind::TimedOut, "body timed out");
+        Box::new(Cursor::new(b"partial".to_vec()).chain(tokio_test::io::Builder::new().read_error(error).build()))
+    }
+
+    #[tokio::test]
+    async fn test_failed_writes_leave_no_partial_data() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = FilesystemStorage::new(dir.path()).await.unwrap();
+
+        storage
+            .put_object("bucket", "key", Box::new(Cursor::new(b"original".to_vec())), HashMap::new())
+            .await
+            .unwrap();
+        assert!(storage.put_object("bucket", "key", failing_reader(), HashMap::new()).await.is_err());
+
+        let mut data = Vec::new();
+        storage.get_object("bucket", "key").await.unwrap().read_to_end(&mut data).await.unwrap();
+        assert_eq!(data, b"original");
+        assert_eq!(std::fs::read_dir(dir.path().join(TMP_DIR)).unwrap().count(), 0);
+
+        let upload_id = storage.create_multipart_upload("bucket", "big", HashMap::new()).await.unwrap();
+        storage
+            .upload_part("bucket", "big", &upload_id, 1, Box::new(Cursor::new(b"first".to_vec())))
+            .await
+            .unwrap();
+        assert!(storage.upload_part("bucket", "big", &upload_id, 1, failing_reader()).await.is_err());
+        assert!(storage.list_parts("bucket", "big", &upload_id).await.unwrap().is_empty());
+
+        // Leftovers from an interrupted write are cleared on startup
+        std::fs::write(dir.path().join(TMP_DIR).join(format!("{}stale", TEMP_PREFIX)), b"partial").unwrap();
+        FilesystemStorage::new(dir.path()).await.unwrap();
+        assert_eq!(std::fs::read_dir(dir.path().join(TMP_DIR)).unwrap().count(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_separate_temp_dir() {
+        let root = tempfile::tempdir().unwrap();
+        let temp = tempfile::tempdir().unwrap();
+        let temp_files = || std::fs::read_dir(temp.path()).unwrap().count();
+
+        // Only the server's own leftovers are cleared from a shared directory
+        std::fs::write(temp.path().join(format!("{}stale", TEMP_PREFIX)), b"partial").unwrap();
+        std::fs::write(temp.path().join("unrelated"), b"keep").unwrap();
+        let storage = FilesystemStorage::with_temp_dir(root.path(), Some(temp.path())).await.unwrap();
+        assert_eq!(temp_files(), 1);
+
+        storage
+            .put_object("bucket", "key", Box::new(Cursor::new(b"data".to_vec())), HashMap::new())
+            .await
+            .unwrap();
+        let upload_id = storage.create_multipart_upload("bucket", "big", HashMap::new()).await.unwrap();
+        let etag = storage
+            .upload_part("bucket", "big", &upload_id, 1, Box::new(Cursor::new(b"part".to_vec())))
+            .await
+            .unwrap();
+        let part = CompletedPart { part_number: 1, etag };
+        storage.complete_multipart_upload("bucket", "big", &upload_id, &[part]).await.unwrap();
+
+        for (key, expected) in [("key", b"data"), ("big", b"part")] {
+            let mut data = Vec::new();
+            storage.get_object("bucket", key).await.unwrap().read_to_end(&mut data).await.unwrap();
+            assert_eq!(data, expected);
+        }
+        assert_eq!(temp_files(), 1);
+        assert_eq!(std::fs::read_dir(root.path().join(TMP_DIR)).unwrap().count(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_snapshots_share_data_until_changed() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = FilesystemStorage::new(dir.path()).await.unwrap();
+        let put = |key: &'static str, data: &'static [u8], owner: &str| {
+            let metadata = HashMap::from([("x-amz-meta-owner".to_string(), owner.to_string())]);
+            storage.put_object("bucket", key, Box::new(Cursor::new(data.to_vec())), metadata)
+        };
+        put("notes.txt", b"first", "alice").await.unwrap();
+        put("dir/plan.txt", b"plan", "alice").await.unwrap();
+
+        for key in ["notes.txt", "dir/plan.txt"] {
+            storage.snapshot_object("bucket", "s1", key).await.unwrap();
+        }
+        assert!(storage.list_snapshots("bucket").await.unwrap().is_empty());
+        storage.save_snapshot_manifest("bucket", "s1", b"[]").await.unwrap();
+        assert_eq!(storage.list_snapshots("bucket").await.unwrap(), ["s1"]);
+        assert_eq!(storage.snapshot_manifest("bucket", "s1").await.unwrap(), b"[]");
+
+        let snapshot_data = dir.path().join(SNAPSHOTS_DIR).join("bucket/s1/objects/notes.txt");
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::MetadataExt;
+            let live = std::fs::metadata(dir.path().join("bucket/notes.txt")).unwrap();
+            assert_eq!(std::fs::metadata(&snapshot_data).unwrap().ino(), live.ino());
+        }
+
+        // Changing the live bucket leaves the snapshot as it was
+        put("notes.txt", b"second", "bob").await.unwrap();
+        storage.delete_object("bucket", "dir/plan.txt").await.unwrap();
+        assert_eq!(std::fs::read(&snapshot_data).unwrap(), b"first");
+
+        for key in ["notes.txt", "dir/plan.txt"] {
+            storage.restore_object("bucket", "s1", key).await.unwrap();
+        }
+        let mut data = Vec::new();
+        storage.get_object("bucket", "notes.txt").await.unwrap().read_to_end(&mut data).await.unwrap();
+        assert_eq!(data, b"first");
+        let metadata = storage.get_object_metadata("bucket", "notes.txt").await.unwrap();
+        assert_eq!(metadata["x-amz-meta-owner"], "alice");
+        assert!(storage.object_exists("bucket", "dir/plan.txt").await.unwrap());
+        assert_eq!(storage.bucket_usage("bucket").await.unwrap(), (2, 9));
+
+        // Restored objects are written over like any other
+        put("notes.txt", b"third", "carol").await.unwrap();
+        assert_eq!(std::fs::read(&snapshot_data).unwrap(), b"first");
+
+        assert!(matches!(
+            storage.restore_object("bucket", "s1", "missing.txt").await,
+            Err(ObjectIOError::ObjectNotFound { .. })
+        ));
+        // Snapshots without a manifest are incomplete
+        storage.snapshot_object("bucket", "s2", "notes.txt").await.unwrap();
+        assert!(matches!(
+            storage.restore_object("bucket", "s2", "notes.txt").await,
+            Err(ObjectIOError::SnapshotNotFound { .. })
+        ));
+        for snapshot in ["", "..", "../bucket", "a/b"] {
+            assert!(matches!(
+                storage.snapshot_manifest("bucket", snapshot).await,
+                Err(ObjectIOError::InvalidArgument { .. })
+            ));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_dedup_shares_identical_content() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = FilesystemStorage::new(dir.path()).await.unwrap().with_dedup(true);
+        let put = |key: &'static str, data: &'static [u8]| {
+            let metadata = HashMap::from([("content-type".to_string(), "text/plain".to_string())]);
+            storage.put_object("bucket", key, Box::new(Cursor::new(data.to_vec())), metadata)
+        };
+        let content_files = || {
+            let mut files = Vec::new();
+            for shard in std::fs::read_dir(dir.path().join(CONTENT_DIR)).unwrap() {
+                files.extend(std::fs::read_dir(shard.unwrap().path()).unwrap().map(|file| file.unwrap().path()));
+            }
+            files
+        };
+
+        let etag = put("a.txt", b"same bytes").await.unwrap();
+        assert_eq!(put("b/c.txt", b"same bytes").await.unwrap(), etag);
+        assert_eq!(content_files(), [storage.content_path(&etag)]);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::MetadataExt;
+            let inode = |path: &str| std::fs::metadata(dir.path().join("bucket").join(path)).unwrap().ino();
+            assert_eq!(inode("a.txt"), inode("b/c.txt"));
+        }
+
+        // The reference is the storage's own, not object metadata
+        let metadata = storage.get_object_metadata("bucket", "a.txt").await.unwrap();
+        assert_eq!(metadata, HashMap::from([("content-type".to_string(), "text/plain".to_string())]));
+        assert_eq!(storage.bucket_usage("bucket").await.unwrap(), (2, 20));
+
+        // Content goes once the last object holding it is replaced or deleted
+        put("a.txt", b"other bytes").await.unwrap();
+        assert_eq!(content_files().len(), 2);
+        storage.move_object("bucket", "a.txt", "moved.txt").await.unwrap();
+        storage.delete_object("bucket", "b/c.txt").await.unwrap();
+        assert_eq!(content_files().len(), 1);
+        let mut data = Vec::new();
+        storage.get_object("bucket", "moved.txt").await.unwrap().read_to_end(&mut data).await.unwrap();
+        assert_eq!(data, b"other bytes");
+
+        // Re-storing identical content under the same key keeps one link
+        put("moved.txt", b"other bytes").await.unwrap();
+        storage.delete_object("bucket", "moved.txt").await.unwrap();
+        assert!(content_files().is_empty());
+        assert_eq!(storage.bucket_usage("bucket").await.unwrap(), (0, 0));
+        assert_eq!(std::fs::read_dir(dir.path().join(TMP_DIR)).unwrap().count(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_move_object_renames_data_and_metadata() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = FilesystemStorage::new(dir.path()).await.unwrap();
+        let metadata = HashMap::from([("content-type".to_string(), "text/plain".to_string())]);
+        storage
+            .put_object("media", "a.txt", Box::new(Cursor::new(b"alpha".to_vec())), metadata)
+            .await
+            .unwrap();
+
+        // The sidecar moves with the data rather than being left behind
+        storage.move_object("media", "a.txt", "nested/c.txt").await.unwrap();
+        assert!(!storage.metadata_path("media", "a.txt").unwrap().exists());
+        assert!(storage.get_object_metadata("media", "a.txt").await.unwrap().is_empty());
+
+        assert!(matches!(
+            storage.move_object("other", "a.txt", "d.txt").await,
+            Err(ObjectIOError::BucketNotFound { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_keys_cannot_leave_the_bucket() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("root");
+        let storage = FilesystemStorage::new(&root).await.unwrap();
+        let data = || Box::new(Cursor::new(b"alpha".to_vec()));
+        storage.put_object("b", "a.txt", data(), HashMap::new()).await.unwrap();
+
+        for key in ["../../escaped.txt", "../b2/a.txt", "x/../../../escaped.txt", "/etc/escaped.txt", "./a.txt"] {
+            let result = storage.move_object("b", "a.txt", key).await;
+            assert!(matches!(result, Err(ObjectIOError::InvalidObjectKey { .. })), "{}", key);
+            let result = storage.put_object("b", key, data(), HashMap::new()).await;
+            assert!(matches!(result, Err(ObjectIOError::InvalidObjectKey { .. })), "{}", key);
+        }
+        assert!(storage.get_object("b", "../b/a.txt").await.is_err());
+        assert!(!storage.move_prefix("b", "x/", "../../").await.unwrap_or(false));
+        assert!(!dir.path().join("escaped.txt").exists());
+        assert!(storage.object_exists("b", "a.txt").await.unwrap());
+        let mut request = ListObjectsRequest::new("b");
+        request.prefix = Some("../".to_string());
+        assert!(storage.list_objects(&request).await.unwrap().objects.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_move_prefix_renames_directories() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = FilesystemStorage::new(dir.path()).await.unwrap();
+        let metadata = HashMap::from([("content-type".to_string(), "image/jpeg".to_string())]);
+        for key in ["2023/a.jpg", "2023/trip/b.jpg", "2024/c.jpg"] {
+            let reader = Box::new(Cursor::new(key.as_bytes().to_vec()));
+            storage.put_object("photos", key, reader, metadata.clone()).await.unwrap();
+        }
+
+        assert!(storage.move_prefix("photos", "2023/", "archive/2023/").await.unwrap());
+        assert!(!storage.bucket_path("photos").join("2023").exists());
+        assert!(!storage.metadata_path("photos", "2023").unwrap().exists());
+        for key in ["archive/2023/a.jpg", "archive/2023/trip/b.jpg"] {
+            assert!(storage.object_exists("photos", key).await.unwrap());
+            assert_eq!(storage.get_object_metadata("photos", key).await.unwrap(), metadata);
+        }
+        assert_eq!(storage.bucket_usage("photos").await.unwrap().0, 3);
+
+        // Existing destinations and missing sources are left to per-object moves
+        assert!(!storage.move_prefix("photos", "2024/", "archive/").await.unwrap());
+        assert!(!storage.move_prefix("photos", "2025/", "2026/").await.unwrap());
+        let hashed = FilesystemStorage::new(dir.path()).await.unwrap().with_key_layout(KeyLayout::Hashed);
+        assert!(!hashed.move_prefix("photos", "2024/", "old/").await.unwrap());
+        assert!(storage.object_exists("photos", "2024/c.jpg").await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_bucket_usage_tracks_changes() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = FilesystemStorage::new(dir.path()).await.unwrap();
+        let put = |key: &'static str, size: usize| {
+            storage.put_object("bucket", key, Box::new(Cursor::new(vec![b'x'; size])), HashMap::new())
+        };
+
+        // Sum of a full listing, which the counters must always agree with
+        async fn listed(storage: &FilesystemStorage) -> (u64, u64) {
+            let listing = storage.list_objects(&ListObjectsRequest::new("bucket")).await.unwrap();
+            (listing.objects.len() as u64, listing.objects.iter().map(|o| o.size).sum())
+        }
+
+        assert_eq!(storage.bucket_usage("bucket").await.unwrap(), (0, 0));
+        put("a", 10).await.unwrap();
+        put("b", 20).await.unwrap();
+        assert_eq!(storage.bucket_usage("bucket").await.unwrap(), (2, 30));
+
+        // Overwrites replace the old size, failed writes change nothing
+        put("a", 5).await.unwrap();
+        storage.put_object("bucket", "a", failing_reader(), HashMap::new()).await.unwrap_err();
+        assert_eq!(storage.bucket_usage("bucket").await.unwrap(), (2, 25));
+
+        let upload_id = storage.create_multipart_upload("bucket", "c", HashMap::new()).await.unwrap();
+        let etag = storage
+            .upload_part("bucket", "c", &upload_id, 1, Box::new(Cursor::new(vec![b'y'; 7])))
+            .await
+            .unwrap();
+        let parts = [CompletedPart { part_number: 1, etag }];
+        storage.complete_multipart_upload("bucket", "c", &upload_id, &parts).await.unwrap();
+        assert_eq!(storage.bucket_usage("bucket").await.unwrap(), (3, 32));
+
+        storage.delete_object("bucket", "b").await.unwrap();
+        storage.delete_object("bucket", "b").await.unwrap_err();
+        assert_eq!(storage.bucket_usage("bucket").await.unwrap(), (2, 12));
+        assert_eq!(listed(&storage).await, (2, 12));
 
-        Ok(objects)
+        // A fresh instance rebuilds the same counters from disk
+        let reopened = FilesystemStorage::new(dir.path()).await.unwrap();
+        assert_eq!(reopened.bucket_usage("bucket").await.unwrap(), (2, 12));
     }
 }
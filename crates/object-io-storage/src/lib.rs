@@ -3,8 +3,16 @@
 //! This crate provides a pluggable storage backend abstraction for ObjectIO.
 
 pub mod backend;
+#[cfg(any(test, feature = "conformance"))]
+pub mod conformance;
 pub mod filesystem;
+pub mod locks;
+pub mod memory;
+pub mod multipart;
+pub mod scrub;
 pub mod traits;
 
-pub use backend::StorageBackend;
+pub use backend::{StorageBackend, StorageBackendKind, StorageConfig};
+pub use filesystem::{KeyCase, KeyLayout};
+pub use locks::KeyLocks;
 pub use traits::Storage;
@@ -0,0 +1,90 @@
+//! Per-object locks serializing writes and conditional operations on a key
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tokio::sync::{Mutex as AsyncMutex, OwnedMutexGuard};
+
+type LockMap = Mutex<HashMap<(String, String), Arc<AsyncMutex<()>>>>;
+
+/// Locks keyed by bucket and object key
+///
+/// A lock exists only while it is held or awaited, so the map stays as small
+/// as the number of objects being written concurrently.
+#[derive(Debug, Default)]
+pub struct KeyLocks {
+    locks: Arc<LockMap>,
+}
+
+impl KeyLocks {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Wait for exclusive access to `bucket/key`
+    pub async fn lock(&self, bucket: &str, key: &str) -> KeyLockGuard {
+        let id = (bucket.to_string(), key.to_string());
+        let lock = self.locks.lock().unwrap().entry(id.clone()).or_default().clone();
+        KeyLockGuard {
+            guard: Some(lock.lock_owned().await),
+            locks: self.locks.clone(),
+            id,
+        }
+    }
+
+    /// Number of keys currently locked or awaited
+    pub fn len(&self) -> usize {
+        self.locks.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Exclusive access to one object key, released on drop
+#[derive(Debug)]
+pub struct KeyLockGuard {
+    guard: Option<OwnedMutexGuard<()>>,
+    locks: Arc<LockMap>,
+    id: (String, String),
+}
+
+impl Drop for KeyLockGuard {
+    fn drop(&mut self) {
+        let mut locks = self.locks.lock().unwrap();
+        self.guard.take();
+        // Only the map still refers to the lock once nobody holds or awaits it
+        if locks.get(&self.id).is_some_and(|lock| Arc::strong_count(lock) == 1) {
+            locks.remove(&self.id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_key_locks_serialize_and_clean_up() {
+        let locks = Arc::new(KeyLocks::new());
+
+        let guard = locks.lock("bucket", "a").await;
+        // Other keys are independent
+        drop(locks.lock("bucket", "b").await);
+        assert_eq!(locks.len(), 1);
+
+        let waiter = {
+            let locks = locks.clone();
+            tokio::spawn(async move {
+                let _guard = locks.lock("bucket", "a").await;
+            })
+        };
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert!(!waiter.is_finished());
+
+        drop(guard);
+        waiter.await.unwrap();
+        assert!(locks.is_empty());
+    }
+}
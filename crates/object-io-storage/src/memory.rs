@@ -0,0 +1,370 @@
+//! In-memory storage backend implementation
+
+use crate::multipart::{self, CompositeEtag, UploadIndexKey};
+use crate::traits::Storage;
+use chrono::{DateTime, Utc};
+use object_io_core::{
+    CompletedPart, ConditionalPut, ListObjectsRequest, ListObjectsResponse, ListUploadsPage, ListUploadsRequest,
+    MultipartUpload, ObjectHead, ObjectIOError, ObjectSummary, Result, UploadPart,
+};
+use std::collections::{BTreeMap, HashMap};
+use std::io::Cursor;
+use tokio::io::{AsyncRead, AsyncReadExt};
+use tokio::sync::RwLock;
+
+/// A stored object held entirely in memory
+#[derive(Debug, Clone)]
+struct MemoryObject {
+    data: Vec<u8>,
+    etag: String,
+    metadata: HashMap<String, String>,
+    last_modified: DateTime<Utc>,
+}
+
+/// An uploaded multipart upload part held in memory
+#[derive(Debug)]
+struct MemoryPart {
+    data: Vec<u8>,
+    etag: String,
+    last_modified: DateTime<Utc>,
+}
+
+/// An in-progress multipart upload held in memory
+#[derive(Debug)]
+struct MemoryUpload {
+    initiated: DateTime<Utc>,
+    metadata: HashMap<String, String>,
+    /// Uploaded parts keyed by part number
+    parts: BTreeMap<u32, MemoryPart>,
+}
+
+/// Memory-based storage backend, useful for tests and ephemeral deployments
+#[derive(Debug, Default)]
+pub struct MemoryStorage {
+    /// Objects keyed by bucket, then by key (ordered for listings)
+    buckets: RwLock<HashMap<String, BTreeMap<String, MemoryObject>>>,
+    /// In-progress multipart uploads, ordered for listings
+    uploads: RwLock<BTreeMap<UploadIndexKey, MemoryUpload>>,
+}
+
+impl MemoryStorage {
+    /// Create a new, empty memory storage backend
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn not_found(bucket: &str, key: &str) -> ObjectIOError {
+        ObjectIOError::ObjectNotFound {
+            bucket: bucket.to_string(),
+            key: key.to_string(),
+        }
+    }
+
+    fn upload_key(bucket: &str, key: &str, upload_id: &str) -> UploadIndexKey {
+        (bucket.to_string(), key.to_string(), upload_id.to_string())
+    }
+
+    fn upload_not_found(upload_id: &str) -> ObjectIOError {
+        ObjectIOError::UploadNotFound {
+            upload_id: upload_id.to_string(),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl Storage for MemoryStorage {
+    async fn put_object_if(
+        &self,
+        bucket: &str,
+        key: &str,
+        mut data: Box<dyn AsyncRead + Send + Unpin>,
+        metadata: HashMap<String, String>,
+        condition: &ConditionalPut,
+    ) -> Result<String> {
+        let mut buffer = Vec::new();
+        data.read_to_end(&mut buffer).await.map_err(|e| {
+            ObjectIOError::StorageError {
+                message: format!("Failed to read data: {}", e),
+            }
+        })?;
+
+        let etag = object_io_core::utils::generate_etag(&buffer);
+        let object = MemoryObject {
+            data: buffer,
+            etag: etag.clone(),
+            metadata,
+            last_modified: object_io_core::timestamp_now(),
+        };
+
+        // Checked under the same lock the object is inserted with
+        let mut buckets = self.buckets.write().await;
+        let objects = buckets.entry(bucket.to_string()).or_default();
+        let current = objects.get(key).map(|object| object.etag.as_str());
+        condition.check(bucket, key, current)?;
+        objects.insert(key.to_string(), object);
+
+        Ok(etag)
+    }
+
+    async fn get_object(&self, bucket: &str, key: &str) -> Result<Box<dyn AsyncRead + Send + Unpin>> {
+        let buckets = self.buckets.read().await;
+        let object = buckets
+            .get(bucket)
+            .and_then(|objects| objects.get(key))
+            .ok_or_else(|| Self::not_found(bucket, key))?;
+
+        Ok(Box::new(Cursor::new(object.data.clone())))
+    }
+
+    async fn get_object_range(
+        &self,
+        bucket: &str,
+        key: &str,
+        offset: u64,
+        length: u64,
+    ) -> Result<Box<dyn AsyncRead + Send + Unpin>> {
+        let buckets = self.buckets.read().await;
+        let object = buckets
+            .get(bucket)
+            .and_then(|objects| objects.get(key))
+            .ok_or_else(|| Self::not_found(bucket, key))?;
+
+        let len = object.data.len();
+        let start = usize::try_from(offset).unwrap_or(usize::MAX).min(len);
+        let end = start + (len - start).min(usize::try_from(length).unwrap_or(usize::MAX));
+        Ok(Box::new(Cursor::new(object.data[start..end].to_vec())))
+    }
+
+    async fn delete_object(&self, bucket: &str, key: &str) -> Result<()> {
+        let mut buckets = self.buckets.write().await;
+        let objects = buckets.get_mut(bucket).ok_or_else(|| ObjectIOError::BucketNotFound {
+            bucket: bucket.to_string(),
+        })?;
+        objects
+            .remove(key)
+            .map(|_| ())
+            .ok_or_else(|| Self::not_found(bucket, key))
+    }
+
+    async fn object_exists(&self, bucket: &str, key: &str) -> Result<bool> {
+        let buckets = self.buckets.read().await;
+        Ok(buckets.get(bucket).is_some_and(|objects| objects.contains_key(key)))
+    }
+
+    async fn get_object_metadata(&self, bucket: &str, key: &str) -> Result<HashMap<String, String>> {
+        let buckets = self.buckets.read().await;
+        Ok(buckets
+            .get(bucket)
+            .and_then(|objects| objects.get(key))
+            .map(|object| object.metadata.clone())
+            .unwrap_or_default())
+    }
+
+    async fn replace_object_metadata(&self, bucket: &str, key: &str, metadata: HashMap<String, String>) -> Result<()> {
+        let mut buckets = self.buckets.write().await;
+        let object = buckets
+            .get_mut(bucket)
+            .and_then(|objects| objects.get_mut(key))
+            .ok_or_else(|| Self::not_found(bucket, key))?;
+        object.metadata = metadata;
+        Ok(())
+    }
+
+    async fn head_object(&self, bucket: &str, key: &str) -> Result<ObjectHead> {
+        let buckets = self.buckets.read().await;
+        let object = buckets
+            .get(bucket)
+            .and_then(|objects| objects.get(key))
+            .ok_or_else(|| ObjectIOError::ObjectNotFound {
+                bucket: bucket.to_string(),
+                key: key.to_string(),
+            })?;
+        Ok(ObjectHead {
+            size: object.data.len() as u64,
+            etag: Some(object.etag.clone()),
+            last_modified: object.last_modified,
+            metadata: object.metadata.clone(),
+        })
+    }
+
+    async fn list_objects(&self, request: &ListObjectsRequest) -> Result<ListObjectsResponse> {
+        let buckets = self.buckets.read().await;
+        let objects = match buckets.get(&request.bucket) {
+            Some(objects) => objects,
+            None => return Ok(ListObjectsResponse::paginate(request, Vec::new())),
+        };
+
+        let prefix = request.prefix.clone().unwrap_or_default();
+        let summaries = objects
+            .range(prefix.clone()..)
+            .take_while(|(key, _)| key.starts_with(&prefix))
+            .map(|(key, object)| ObjectSummary {
+                key: key.clone(),
+                size: object.data.len() as u64,
+                etag: object.etag.clone(),
+                last_modified: object.last_modified,
+                storage_class: object_io_core::StorageClass::Standard,
+            });
+        Ok(ListObjectsResponse::paginate(request, summaries))
+    }
+
+    async fn bucket_usage(&self, bucket: &str) -> Result<(u64, u64)> {
+        let buckets = self.buckets.read().await;
+        Ok(buckets.get(bucket).map_or((0, 0), |objects| {
+            let bytes = objects.values().map(|object| object.data.len() as u64).sum();
+            (objects.len() as u64, bytes)
+        }))
+    }
+
+    async fn create_multipart_upload(
+        &self,
+        bucket: &str,
+        key: &str,
+        metadata: HashMap<String, String>,
+    ) -> Result<String> {
+        let upload_id = multipart::new_upload_id();
+        self.uploads.write().await.insert(
+            Self::upload_key(bucket, key, &upload_id),
+            MemoryUpload {
+                initiated: object_io_core::timestamp_now(),
+                metadata,
+                parts: BTreeMap::new(),
+            },
+        );
+        Ok(upload_id)
+    }
+
+    async fn upload_part(
+        &self,
+        bucket: &str,
+        key: &str,
+        upload_id: &str,
+        part_number: u32,
+        mut data: Box<dyn AsyncRead + Send + Unpin>,
+    ) -> Result<String> {
+        multipart::validate_part_number(part_number)?;
+
+        let mut buffer = Vec::new();
+        data.read_to_end(&mut buffer).await.map_err(|e| {
+            ObjectIOError::StorageError {
+                message: format!("Failed to read data: {}", e),
+            }
+        })?;
+        let etag = object_io_core::utils::generate_etag(&buffer);
+
+        let mut uploads = self.uploads.write().await;
+        let upload = uploads
+            .get_mut(&Self::upload_key(bucket, key, upload_id))
+            .ok_or_else(|| Self::upload_not_found(upload_id))?;
+        upload.parts.insert(
+            part_number,
+            MemoryPart {
+                data: buffer,
+                etag: etag.clone(),
+                last_modified: object_io_core::timestamp_now(),
+            },
+        );
+
+        Ok(etag)
+    }
+
+    async fn complete_multipart_upload(
+        &self,
+        bucket: &str,
+        key: &str,
+        upload_id: &str,
+        parts: &[CompletedPart],
+    ) -> Result<String> {
+        multipart::validate_part_order(parts)?;
+
+        let upload_key = Self::upload_key(bucket, key, upload_id);
+        let mut uploads = self.uploads.write().await;
+        let upload = uploads
+            .get(&upload_key)
+            .ok_or_else(|| Self::upload_not_found(upload_id))?;
+
+        for (i, part) in parts.iter().enumerate() {
+            let stored = upload
+                .parts
+                .get(&part.part_number)
+                .map(|part| (part.etag.as_str(), part.data.len() as u64));
+            multipart::validate_part(part, stored, i == parts.len() - 1)?;
+        }
+
+        let mut data = Vec::new();
+        let mut composite = CompositeEtag::new();
+        for part in parts {
+            let stored = &upload.parts[&part.part_number];
+            data.extend_from_slice(&stored.data);
+            composite.add_part(&stored.etag);
+        }
+
+        let etag = composite.finish();
+        let upload = uploads.remove(&upload_key).expect("upload checked above");
+        self.buckets
+            .write()
+            .await
+            .entry(bucket.to_string())
+            .or_default()
+            .insert(
+                key.to_string(),
+                MemoryObject {
+                    data,
+                    etag: etag.clone(),
+                    metadata: upload.metadata,
+                    last_modified: object_io_core::timestamp_now(),
+                },
+            );
+
+        Ok(etag)
+    }
+
+    async fn abort_multipart_upload(&self, bucket: &str, key: &str, upload_id: &str) -> Result<()> {
+        match self.uploads.write().await.remove(&Self::upload_key(bucket, key, upload_id)) {
+            Some(_) => Ok(()),
+            None => Err(Self::upload_not_found(upload_id)),
+        }
+    }
+
+    async fn list_multipart_uploads(&self, bucket: &str) -> Result<Vec<MultipartUpload>> {
+        let request = ListUploadsRequest {
+            bucket: bucket.to_string(),
+            max_uploads: u32::MAX,
+            ..ListUploadsRequest::default()
+        };
+        Ok(self.list_multipart_uploads_page(&request).await?.uploads)
+    }
+
+    async fn list_multipart_uploads_page(&self, request: &ListUploadsRequest) -> Result<ListUploadsPage> {
+        let uploads = self.uploads.read().await;
+        Ok(multipart::page_uploads(&uploads, request, |upload| upload.initiated))
+    }
+
+    async fn list_parts(&self, bucket: &str, key: &str, upload_id: &str) -> Result<Vec<UploadPart>> {
+        let uploads = self.uploads.read().await;
+        let upload = uploads
+            .get(&Self::upload_key(bucket, key, upload_id))
+            .ok_or_else(|| Self::upload_not_found(upload_id))?;
+
+        Ok(upload
+            .parts
+            .iter()
+            .map(|(part_number, part)| UploadPart {
+                part_number: *part_number,
+                etag: part.etag.clone(),
+                size: part.data.len() as u64,
+                last_modified: part.last_modified,
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_conformance() {
+        crate::conformance::run(&MemoryStorage::new()).await;
+    }
+}
@@ -0,0 +1,318 @@
+//! Helpers shared by multipart upload implementations
+
+use crate::traits::Storage;
+use chrono::{DateTime, Duration, Utc};
+use object_io_core::{CompletedPart, ListUploadsPage, ListUploadsRequest, MultipartUpload, ObjectIOError, Result};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::ops::Bound;
+
+/// Lowest valid part number
+pub const MIN_PART_NUMBER: u32 = 1;
+
+/// Highest valid part number
+pub const MAX_PART_NUMBER: u32 = 10_000;
+
+/// Minimum size of every part except the last (5 MiB)
+pub const MIN_PART_SIZE: u64 = 5 * 1024 * 1024;
+
+/// Incrementally computes the ETag of an assembled multipart object
+///
+/// The ETag is the hash of the part ETags in order, suffixed with the part
+/// count (`<hash>-<parts>`), so no part data needs to be held in memory.
+pub struct CompositeEtag {
+    hasher: Sha256,
+    parts: usize,
+}
+
+impl CompositeEtag {
+    /// Start a new composite ETag
+    pub fn new() -> Self {
+        Self {
+            hasher: Sha256::new(),
+            parts: 0,
+        }
+    }
+
+    /// Add the next part's ETag
+    pub fn add_part(&mut self, etag: &str) {
+        self.hasher.update(object_io_core::normalize_etag(etag).as_bytes());
+        self.parts += 1;
+    }
+
+    /// Finish and format the composite ETag
+    pub fn finish(self) -> String {
+        format!("{:x}-{}", self.hasher.finalize(), self.parts)
+    }
+}
+
+impl Default for CompositeEtag {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Generate a new multipart upload ID
+pub fn new_upload_id() -> String {
+    uuid::Uuid::new_v4().simple().to_string()
+}
+
+/// Check that an upload ID is one we could have generated (safe to use in paths)
+pub fn is_valid_upload_id(upload_id: &str) -> bool {
+    !upload_id.is_empty() && upload_id.chars().all(|c| c.is_ascii_alphanumeric())
+}
+
+/// Check that a part number is within the range S3 allows
+pub fn validate_part_number(part_number: u32) -> Result<()> {
+    if (MIN_PART_NUMBER..=MAX_PART_NUMBER).contains(&part_number) {
+        Ok(())
+    } else {
+        Err(ObjectIOError::InvalidArgument {
+            message: format!(
+                "Part number must be between {} and {}, got {}",
+                MIN_PART_NUMBER, MAX_PART_NUMBER, part_number
+            ),
+        })
+    }
+}
+
+/// Check that a complete request lists at least one part, in strictly ascending order
+pub fn validate_part_order(parts: &[CompletedPart]) -> Result<()> {
+    if parts.is_empty() {
+        return Err(ObjectIOError::InvalidRequest {
+            message: "You must specify at least one part".to_string(),
+        });
+    }
+
+    for pair in parts.windows(2) {
+        if pair[1].part_number <= pair[0].part_number {
+            return Err(ObjectIOError::InvalidPartOrder {
+                message: format!(
+                    "Part {} is listed after part {}",
+                    pair[1].part_number, pair[0].part_number
+                ),
+            });
+        }
+    }
+
+    Ok(())
+}
+
+/// Check a listed part against the stored part's ETag and size
+///
+/// `stored` is `None` when the part was never uploaded.
+pub fn validate_part(part: &CompletedPart, stored: Option<(&str, u64)>, is_last: bool) -> Result<()> {
+    let (etag, size) = stored.ok_or_else(|| ObjectIOError::InvalidPart {
+        message: format!("Part {} has not been uploaded", part.part_number),
+    })?;
+
+    if !object_io_core::etags_equal(&part.etag, etag) {
+        return Err(ObjectIOError::InvalidPart {
+            message: format!("ETag for part {} does not match the uploaded part", part.part_number),
+        });
+    }
+
+    if !is_last && size < MIN_PART_SIZE {
+        return Err(ObjectIOError::EntityTooSmall {
+            message: format!(
+                "Part {} is {} bytes; all parts but the last must be at least {} bytes",
+                part.part_number, size, MIN_PART_SIZE
+            ),
+        });
+    }
+
+    Ok(())
+}
+
+/// Key of an upload in an ordered upload index: `(bucket, key, upload ID)`,
+/// so each bucket's uploads are contiguous and in listing order
+pub type UploadIndexKey = (String, String, String);
+
+/// Select one page of uploads from an ordered index, seeking straight to the
+/// first upload after the markers (or the first under the prefix) and
+/// stopping once past the prefix
+///
+/// `initiated` gives the initiation time of an indexed upload.
+pub fn page_uploads<V>(
+    index: &BTreeMap<UploadIndexKey, V>,
+    request: &ListUploadsRequest,
+    initiated: impl Fn(&V) -> DateTime<Utc>,
+) -> ListUploadsPage {
+    let bucket = request.bucket.as_str();
+    let prefix = request.prefix.as_deref().unwrap_or("");
+    let start = match (&request.key_marker, &request.upload_id_marker) {
+        // The upload ID marker only applies within the key marker
+        (Some(key), Some(id)) if key.as_str() >= prefix => {
+            Bound::Excluded((bucket.to_string(), key.clone(), id.clone()))
+        }
+        // The smallest key after the marker, so every upload for it is skipped
+        (Some(key), None) if key.as_str() >= prefix => {
+            Bound::Included((bucket.to_string(), format!("{}\0", key), String::new()))
+        }
+        _ => Bound::Included((bucket.to_string(), prefix.to_string(), String::new())),
+    };
+
+    let max_uploads = request.max_uploads as usize;
+    let mut uploads: Vec<MultipartUpload> = index
+        .range((start, Bound::Unbounded))
+        .take_while(|((upload_bucket, key, _), _)| upload_bucket == bucket && key.starts_with(prefix))
+        .take(max_uploads + 1)
+        .map(|((bucket, key, upload_id), value)| MultipartUpload {
+            upload_id: upload_id.clone(),
+            bucket: bucket.clone(),
+            key: key.clone(),
+            initiated: initiated(value),
+            parts: Vec::new(),
+        })
+        .collect();
+
+    let is_truncated = uploads.len() > max_uploads;
+    uploads.truncate(max_uploads);
+    ListUploadsPage { uploads, is_truncated }
+}
+
+/// Abort uploads in the given buckets that were initiated more than `max_age`
+/// before `now`, like S3 lifecycle's `AbortIncompleteMultipartUpload`
+///
+/// Returns the uploads that were aborted.
+pub async fn abort_incomplete_uploads(
+    storage: &dyn Storage,
+    buckets: &[String],
+    max_age: Duration,
+    now: DateTime<Utc>,
+) -> Result<Vec<MultipartUpload>> {
+    let cutoff = now - max_age;
+    let mut aborted = Vec::new();
+
+    for bucket in buckets {
+        for upload in storage.list_multipart_uploads(bucket).await? {
+            if upload.initiated >= cutoff {
+                continue;
+            }
+
+            match storage.abort_multipart_upload(bucket, &upload.key, &upload.upload_id).await {
+                Ok(()) => aborted.push(upload),
+                // Completed or aborted since it was listed
+                Err(ObjectIOError::UploadNotFound { .. }) => {}
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    Ok(aborted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory::MemoryStorage;
+    use std::collections::HashMap;
+
+    fn part(part_number: u32, etag: &str) -> CompletedPart {
+        CompletedPart {
+            part_number,
+            etag: etag.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_validate_part_number() {
+        assert!(validate_part_number(1).is_ok());
+        assert!(validate_part_number(10_000).is_ok());
+        assert!(matches!(validate_part_number(0), Err(ObjectIOError::InvalidArgument { .. })));
+        assert!(matches!(validate_part_number(10_001), Err(ObjectIOError::InvalidArgument { .. })));
+    }
+
+    #[test]
+    fn test_validate_part_order() {
+        assert!(validate_part_order(&[part(1, "a"), part(2, "b"), part(5, "c")]).is_ok());
+        assert!(matches!(validate_part_order(&[]), Err(ObjectIOError::InvalidRequest { .. })));
+        assert!(matches!(
+            validate_part_order(&[part(2, "a"), part(1, "b")]),
+            Err(ObjectIOError::InvalidPartOrder { .. })
+        ));
+        assert!(matches!(
+            validate_part_order(&[part(1, "a"), part(1, "a")]),
+            Err(ObjectIOError::InvalidPartOrder { .. })
+        ));
+    }
+
+    #[test]
+    fn test_validate_part() {
+        // Quoted ETags from clients match the bare stored form
+        assert!(validate_part(&part(1, "\"abc\""), Some(("abc", MIN_PART_SIZE)), false).is_ok());
+        // The last part may be any size
+        assert!(validate_part(&part(2, "abc"), Some(("abc", 1)), true).is_ok());
+
+        assert!(matches!(
+            validate_part(&part(1, "abc"), None, false),
+            Err(ObjectIOError::InvalidPart { .. })
+        ));
+        assert!(matches!(
+            validate_part(&part(1, "abc"), Some(("def", MIN_PART_SIZE)), false),
+            Err(ObjectIOError::InvalidPart { .. })
+        ));
+        assert!(matches!(
+            validate_part(&part(1, "abc"), Some(("abc", MIN_PART_SIZE - 1)), false),
+            Err(ObjectIOError::EntityTooSmall { .. })
+        ));
+    }
+
+    #[test]
+    fn test_page_uploads() {
+        let index: BTreeMap<UploadIndexKey, ()> = [
+            ("bucket", "a", "1"),
+            ("bucket", "a", "2"),
+            ("bucket", "b", "3"),
+            ("bucket", "logs/c", "4"),
+            ("other", "a", "5"),
+        ]
+        .into_iter()
+        .map(|(bucket, key, id)| ((bucket.to_string(), key.to_string(), id.to_string()), ()))
+        .collect();
+        let page = |prefix: Option<&str>, key_marker: Option<&str>, upload_id_marker: Option<&str>, max_uploads| {
+            let request = ListUploadsRequest {
+                bucket: "bucket".to_string(),
+                prefix: prefix.map(str::to_string),
+                key_marker: key_marker.map(str::to_string),
+                upload_id_marker: upload_id_marker.map(str::to_string),
+                max_uploads,
+            };
+            let page = page_uploads(&index, &request, |_| Utc::now());
+            let ids: Vec<String> = page.uploads.into_iter().map(|upload| upload.upload_id).collect();
+            (ids, page.is_truncated)
+        };
+
+        assert_eq!(page(None, None, None, 2), (vec!["1".into(), "2".into()], true));
+        // Resume from the last upload of the previous page
+        assert_eq!(page(None, Some("a"), Some("2"), 2), (vec!["3".into(), "4".into()], false));
+        // A key marker alone skips every upload for that key
+        assert_eq!(page(None, Some("a"), None, 10).0, ["3", "4"]);
+        assert_eq!(page(Some("logs/"), None, None, 10).0, ["4"]);
+        // A marker before the prefix starts at the prefix; one after it ends the listing
+        assert_eq!(page(Some("logs/"), Some("a"), Some("2"), 10).0, ["4"]);
+        assert!(page(Some("a"), Some("b"), None, 10).0.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_abort_incomplete_uploads() {
+        let storage = MemoryStorage::new();
+        let upload_id = storage
+            .create_multipart_upload("bucket", "stale.bin", HashMap::new())
+            .await
+            .unwrap();
+        let buckets = vec!["bucket".to_string()];
+        let max_age = Duration::days(7);
+
+        // Not yet old enough
+        let aborted = abort_incomplete_uploads(&storage, &buckets, max_age, Utc::now()).await.unwrap();
+        assert!(aborted.is_empty());
+        assert_eq!(storage.list_multipart_uploads("bucket").await.unwrap().len(), 1);
+
+        let later = Utc::now() + Duration::days(8);
+        let aborted = abort_incomplete_uploads(&storage, &buckets, max_age, later).await.unwrap();
+        assert_eq!(aborted.len(), 1);
+        assert_eq!(aborted[0].upload_id, upload_id);
+        assert!(storage.list_multipart_uploads("bucket").await.unwrap().is_empty());
+    }
+}
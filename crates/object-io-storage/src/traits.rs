@@ -1,8 +1,12 @@
 //! Storage trait definitions
 
-use object_io_core::{Object, Result};
-use std::collections::HashMap;
-use tokio::io::AsyncRead;
+use crate::multipart;
+use object_io_core::{
+    CompletedPart, ConditionalPut, ListObjectsRequest, ListObjectsResponse, ListUploadsPage, ListUploadsRequest,
+    MultipartUpload, ObjectHead, ObjectIOError, Result, UploadPart,
+};
+use std::collections::{BTreeMap, HashMap};
+use tokio::io::{AsyncRead, AsyncReadExt};
 
 /// Core storage trait for object operations
 #[async_trait::async_trait]
@@ -14,26 +18,226 @@ pub trait Storage: Send + Sync {
         key: &str,
         data: Box<dyn AsyncRead + Send + Unpin>,
         metadata: HashMap<String, String>,
+    ) -> Result<String> {
+        self.put_object_if(bucket, key, data, metadata, &ConditionalPut::default()).await
+    }
+
+    /// Store an object if the object it replaces meets `condition`,
+    /// returning its ETag
+    ///
+    /// The condition is checked against the object in place when the new one
+    /// is about to be installed, after the data has been received, and no
+    /// other write to the key can land in between. Unmet conditions fail as
+    /// `ConditionalPut::check` says, leaving the stored object untouched.
+    async fn put_object_if(
+        &self,
+        bucket: &str,
+        key: &str,
+        data: Box<dyn AsyncRead + Send + Unpin>,
+        metadata: HashMap<String, String>,
+        condition: &ConditionalPut,
     ) -> Result<String>;
 
     /// Retrieve an object by key
     async fn get_object(&self, bucket: &str, key: &str) -> Result<Box<dyn AsyncRead + Send + Unpin>>;
 
+    /// Retrieve `length` bytes of an object starting at `offset`. A range
+    /// running past the end of the object is truncated at the end.
+    ///
+    /// The default reads and discards the leading bytes; backends that can
+    /// seek should override it.
+    async fn get_object_range(
+        &self,
+        bucket: &str,
+        key: &str,
+        offset: u64,
+        length: u64,
+    ) -> Result<Box<dyn AsyncRead + Send + Unpin>> {
+        let mut reader = self.get_object(bucket, key).await?;
+        tokio::io::copy(&mut (&mut reader).take(offset), &mut tokio::io::sink())
+            .await
+            .map_err(|e| ObjectIOError::StorageError {
+                message: format!("Failed to skip to range start: {}", e),
+            })?;
+        Ok(Box::new(reader.take(length)))
+    }
+
     /// Delete an object by key
+    ///
+    /// Fails with `BucketNotFound` if the backend holds nothing for the
+    /// bucket, and `ObjectNotFound` if only the key is missing.
     async fn delete_object(&self, bucket: &str, key: &str) -> Result<()>;
 
+    /// Move an object to another key in the same bucket, with its metadata,
+    /// replacing any object already at `dst_key`
+    ///
+    /// The default copies the data and then deletes the source, so the
+    /// returned ETag is recomputed and the move is not atomic; backends that
+    /// can rename in place should override it.
+    async fn move_object(&self, bucket: &str, src_key: &str, dst_key: &str) -> Result<()> {
+        let metadata = self.get_object_metadata(bucket, src_key).await?;
+        let reader = self.get_object(bucket, src_key).await?;
+        self.put_object(bucket, dst_key, reader, metadata).await?;
+        self.delete_object(bucket, src_key).await
+    }
+
+    /// Move every object under `src_prefix` to the same key under
+    /// `dst_prefix` in one step, with their metadata, returning false if the
+    /// backend can't
+    ///
+    /// Callers pass prefixes ending in `/`, neither inside the other, with
+    /// nothing stored under `dst_prefix`. The default returns false, leaving
+    /// callers to move the objects one at a time; backends that keep a
+    /// prefix's objects together can override it.
+    async fn move_prefix(&self, bucket: &str, src_prefix: &str, dst_prefix: &str) -> Result<bool> {
+        let _ = (bucket, src_prefix, dst_prefix);
+        Ok(false)
+    }
+
     /// Check if an object exists
     async fn object_exists(&self, bucket: &str, key: &str) -> Result<bool>;
 
     /// Get object metadata
     async fn get_object_metadata(&self, bucket: &str, key: &str) -> Result<HashMap<String, String>>;
 
-    /// List objects in a bucket with optional prefix
-    async fn list_objects(
+    /// Get an object's size, modification time and metadata in one lookup,
+    /// failing with `ObjectNotFound` if it doesn't exist
+    async fn head_object(&self, bucket: &str, key: &str) -> Result<ObjectHead>;
+
+    /// Replace an object's metadata, keeping its data and ETag, failing with
+    /// `ObjectNotFound` if it doesn't exist
+    ///
+    /// The default stores the object again with the new metadata; backends
+    /// that keep metadata apart from the data should override it.
+    async fn replace_object_metadata(&self, bucket: &str, key: &str, metadata: HashMap<String, String>) -> Result<()> {
+        let data = self.get_object(bucket, key).await?;
+        self.put_object(bucket, key, data, metadata).await.map(|_| ())
+    }
+
+    /// List one page of objects in a bucket, ordered by key
+    ///
+    /// The page size defaults to `ListObjectsRequest::DEFAULT_MAX_KEYS`;
+    /// callers enforce any server-side cap (see `ListObjectsRequest::capped`).
+    async fn list_objects(&self, request: &ListObjectsRequest) -> Result<ListObjectsResponse>;
+
+    /// Count the objects in a bucket and their total size in bytes, as
+    /// `(object_count, total_bytes)`
+    ///
+    /// The default pages through the whole listing; backends that maintain
+    /// counters should override it.
+    async fn bucket_usage(&self, bucket: &str) -> Result<(u64, u64)> {
+        let mut request = ListObjectsRequest::new(bucket);
+        let (mut count, mut bytes) = (0u64, 0u64);
+        loop {
+            let page = self.list_objects(&request).await?;
+            count += page.objects.len() as u64;
+            bytes += page.objects.iter().map(|o| o.size).sum::<u64>();
+            match page.next_marker {
+                Some(marker) if page.is_truncated => request.marker = Some(marker),
+                _ => return Ok((count, bytes)),
+            }
+        }
+    }
+
+    /// Start a multipart upload, returning its upload ID
+    async fn create_multipart_upload(
+        &self,
+        bucket: &str,
+        key: &str,
+        metadata: HashMap<String, String>,
+    ) -> Result<String>;
+
+    /// Store one part of a multipart upload, returning the part's ETag
+    async fn upload_part(
         &self,
         bucket: &str,
-        prefix: Option<&str>,
-        delimiter: Option<&str>,
-        max_keys: Option<u32>,
-    ) -> Result<Vec<Object>>;
+        key: &str,
+        upload_id: &str,
+        part_number: u32,
+        data: Box<dyn AsyncRead + Send + Unpin>,
+    ) -> Result<String>;
+
+    /// Assemble the listed parts into the final object, returning its composite ETag
+    async fn complete_multipart_upload(
+        &self,
+        bucket: &str,
+        key: &str,
+        upload_id: &str,
+        parts: &[CompletedPart],
+    ) -> Result<String>;
+
+    /// Discard a multipart upload and all of its parts
+    async fn abort_multipart_upload(&self, bucket: &str, key: &str, upload_id: &str) -> Result<()>;
+
+    /// List in-progress multipart uploads in a bucket, ordered by key then
+    /// upload ID (parts are not loaded)
+    async fn list_multipart_uploads(&self, bucket: &str) -> Result<Vec<MultipartUpload>>;
+
+    /// List one page of in-progress multipart uploads in a bucket, ordered
+    /// by key then upload ID
+    ///
+    /// The default indexes the full listing; backends that keep their uploads
+    /// ordered should override it to seek to the page directly.
+    async fn list_multipart_uploads_page(&self, request: &ListUploadsRequest) -> Result<ListUploadsPage> {
+        let index: BTreeMap<_, _> = self
+            .list_multipart_uploads(&request.bucket)
+            .await?
+            .into_iter()
+            .map(|upload| ((upload.bucket, upload.key, upload.upload_id), upload.initiated))
+            .collect();
+        Ok(multipart::page_uploads(&index, request, |initiated| *initiated))
+    }
+
+    /// List the parts uploaded so far for a multipart upload, ordered by part number
+    async fn list_parts(&self, bucket: &str, key: &str, upload_id: &str) -> Result<Vec<UploadPart>>;
+
+    /// Add an object's current data and metadata to `snapshot` of its
+    /// bucket, creating the snapshot if needed
+    ///
+    /// Snapshots are point-in-time copies that later writes to the bucket
+    /// never change. The default fails with `NotImplemented`; backends that
+    /// can share unchanged data between a snapshot and the live bucket
+    /// implement the snapshot methods.
+    async fn snapshot_object(&self, bucket: &str, snapshot: &str, key: &str) -> Result<()> {
+        let _ = (bucket, snapshot, key);
+        Err(snapshots_unsupported())
+    }
+
+    /// Complete `snapshot` by recording `manifest`, the caller's description
+    /// of the objects in it; only completed snapshots are listed
+    async fn save_snapshot_manifest(&self, bucket: &str, snapshot: &str, manifest: &[u8]) -> Result<()> {
+        let _ = (bucket, snapshot, manifest);
+        Err(snapshots_unsupported())
+    }
+
+    /// Read the manifest `snapshot` was completed with, failing with
+    /// `SnapshotNotFound` if there is no such completed snapshot
+    async fn snapshot_manifest(&self, bucket: &str, snapshot: &str) -> Result<Vec<u8>> {
+        let _ = (bucket, snapshot);
+        Err(snapshots_unsupported())
+    }
+
+    /// List the completed snapshots of a bucket, oldest first
+    async fn list_snapshots(&self, bucket: &str) -> Result<Vec<String>> {
+        let _ = bucket;
+        Err(snapshots_unsupported())
+    }
+
+    /// Put an object's data and metadata back as they were in `snapshot`,
+    /// replacing the live object
+    async fn restore_object(&self, bucket: &str, snapshot: &str, key: &str) -> Result<()> {
+        let _ = (bucket, snapshot, key);
+        Err(snapshots_unsupported())
+    }
+
+    /// Flush any buffered writes to durable storage
+    async fn sync(&self) -> Result<()> {
+        Ok(())
+    }
+}
+
+fn snapshots_unsupported() -> ObjectIOError {
+    ObjectIOError::NotImplemented {
+        message: "Snapshots are not supported by this storage backend".to_string(),
+    }
 }